@@ -1,21 +1,25 @@
 mod base_ops;
 mod breakpoints;
+mod monitor;
 mod registers;
 mod resume;
 mod step;
 
+use std::num::NonZeroUsize;
+
 use gdbstub::{
-    arch::Arch,
-    common::Signal,
+    arch::{Arch, RegId},
+    common::{Signal, Tid},
     conn::Connection,
     stub::{
         state_machine::GdbStubStateMachine, DisconnectReason, GdbStub, GdbStubError,
-        SingleThreadStopReason,
+        MultiThreadStopReason,
     },
     target::{
         ext::{
             base::{reverse_exec::ReplayLogPosition, BaseOps},
-            breakpoints::BreakpointsOps,
+            breakpoints::{BreakpointsOps, WatchKind},
+            monitor_cmd::MonitorCmdOps,
         },
         Target, TargetError,
     },
@@ -24,6 +28,7 @@ use gdbstub_arch::riscv::{
     reg::{id::RiscvRegId, RiscvCoreRegs},
     Riscv32,
 };
+use red_planet_core::core::watchpoint;
 use tokio::{
     io::AsyncReadExt,
     select,
@@ -41,13 +46,29 @@ impl Arch for OurRiscv32 {
     type Usize = u32;
     type Registers = RiscvCoreRegs<u32>;
     type BreakpointKind = <Riscv32 as Arch>::BreakpointKind;
-    type RegId = RiscvRegId<u32>;
+    type RegId = OurRiscvRegId;
 
     fn target_description_xml() -> Option<&'static str> {
         Some(include_str!("./gdb/rv32-csrs.xml"))
     }
 }
 
+/// Same as [`RiscvRegId<u32>`], except that the floating-point registers are 64 bits wide, since
+/// the D extension is supported (FLEN=64).
+#[derive(Debug, Clone, Copy)]
+pub struct OurRiscvRegId(pub RiscvRegId<u32>);
+
+impl RegId for OurRiscvRegId {
+    fn from_raw_id(id: usize) -> Option<(Self, Option<NonZeroUsize>)> {
+        let (reg_id, size) = RiscvRegId::<u32>::from_raw_id(id)?;
+        let size = match reg_id {
+            RiscvRegId::Fpr(_) => NonZeroUsize::new(8),
+            _ => size,
+        };
+        Some((Self(reg_id), size))
+    }
+}
+
 #[derive(Debug)]
 pub enum GdbTargetError {
     TargetGone,
@@ -60,19 +81,32 @@ impl std::fmt::Display for GdbTargetError {
     }
 }
 
+/// Resume action requested by GDB for a single hart. Harts without an action are continued.
+#[derive(Debug, Clone, Copy)]
+enum ResumeAction {
+    Step(usize),
+    RangeStep(usize, u32, u32),
+}
+
+/// Every hart of the board is exposed to GDB as a thread, with thread ID `hart + 1`.
 pub struct GdbTarget {
     command_sender: UnboundedSender<Command>,
     event_receiver: UnboundedReceiver<Event>,
+    hart_count: usize,
+    resume_action: Option<ResumeAction>,
 }
 
 impl GdbTarget {
     pub fn new(
         command_sender: UnboundedSender<Command>,
         event_receiver: UnboundedReceiver<Event>,
+        hart_count: usize,
     ) -> Self {
         Self {
             command_sender,
             event_receiver,
+            hart_count,
+            resume_action: None,
         }
     }
 
@@ -90,14 +124,29 @@ impl Target for GdbTarget {
     type Arch = OurRiscv32;
     type Error = GdbTargetError;
 
-    fn base_ops(&mut self) -> BaseOps<Self::Arch, Self::Error> {
-        // Indicate our target is single-threaded
-        BaseOps::SingleThread(self)
+    fn base_ops(&mut self) -> BaseOps<'_, Self::Arch, Self::Error> {
+        // Every hart is a thread
+        BaseOps::MultiThread(self)
     }
 
     fn support_breakpoints(&mut self) -> Option<BreakpointsOps<'_, Self>> {
         Some(self)
     }
+
+    fn support_monitor_cmd(&mut self) -> Option<MonitorCmdOps<'_, Self>> {
+        Some(self)
+    }
+}
+
+/// Converts a GDB thread ID to the ID of the hart it represents.
+fn tid_to_hart(tid: Tid) -> usize {
+    tid.get() - 1
+}
+
+/// Converts a hart ID to the GDB thread ID representing it.
+fn hart_to_tid(hart: usize) -> Tid {
+    // Unwrap safety: hart IDs are well below `usize::MAX`.
+    Tid::new(hart + 1).unwrap()
 }
 
 #[derive(Debug)]
@@ -135,7 +184,7 @@ pub async fn run_server(
 
             GdbStubStateMachine::CtrlCInterrupt(gdb) => {
                 let _ = target.command_sender.send(Command::Pause);
-                gdb.interrupt_handled(target, None::<SingleThreadStopReason<u32>>)
+                gdb.interrupt_handled(target, None::<MultiThreadStopReason<u32>>)
                     .map_err(GdbError::Inner)?
             }
 
@@ -152,14 +201,26 @@ pub async fn run_server(
                     event = target.event_receiver.recv() => {
                         let event = event.ok_or(GdbError::TargetThreadStoped)?;
                         let stop_reason = match event {
-                            Event::DoneStep => SingleThreadStopReason::DoneStep,
-                            Event::ReachedStart => SingleThreadStopReason::ReplayLog {
+                            Event::DoneStep => MultiThreadStopReason::DoneStep,
+                            Event::ReachedStart => MultiThreadStopReason::ReplayLog {
                                 tid: None,
                                 pos: ReplayLogPosition::Begin,
                             },
-                            Event::PoweredDown => SingleThreadStopReason::DoneStep,
-                            Event::Break => SingleThreadStopReason::SwBreak(()),
-                            Event::Pause => SingleThreadStopReason::Signal(Signal::SIGINT),
+                            Event::PoweredDown(status) => {
+                                MultiThreadStopReason::Exited(status.exit_code())
+                            }
+                            Event::Break(hart) => MultiThreadStopReason::SwBreak(hart_to_tid(hart)),
+                            Event::Watch(hart, hit) => MultiThreadStopReason::Watch {
+                                tid: hart_to_tid(hart),
+                                kind: match hit.watchpoint.kind {
+                                    watchpoint::WatchKind::Read => WatchKind::Read,
+                                    watchpoint::WatchKind::Write => WatchKind::Write,
+                                    watchpoint::WatchKind::Access => WatchKind::ReadWrite,
+                                },
+                                addr: hit.address,
+                            },
+                            Event::Pause => MultiThreadStopReason::Signal(Signal::SIGINT),
+                            Event::StepLimit => MultiThreadStopReason::Signal(Signal::SIGXCPU),
                         };
                         gdb.report_stop(target, stop_reason).map_err(GdbError::Inner)?
                     }
@@ -1,7 +1,8 @@
+use gdbstub::common::Tid;
 use gdbstub::target::{
     ext::base::{
+        multithread::{MultiThreadBase, MultiThreadResumeOps},
         single_register_access::SingleRegisterAccessOps,
-        singlethread::{SingleThreadBase, SingleThreadResumeOps},
     },
     TargetError, TargetResult,
 };
@@ -9,18 +10,22 @@ use gdbstub_arch::riscv::reg::RiscvCoreRegs;
 use red_planet_core::registers::{Registers, Specifier};
 
 use crate::{
-    gdb::{GdbTarget, GdbTargetError},
+    gdb::{hart_to_tid, tid_to_hart, GdbTarget, GdbTargetError},
     target::command::Command,
 };
 
-impl SingleThreadBase for GdbTarget {
-    fn support_resume(&mut self) -> Option<SingleThreadResumeOps<'_, Self>> {
+impl MultiThreadBase for GdbTarget {
+    fn support_resume(&mut self) -> Option<MultiThreadResumeOps<'_, Self>> {
         Some(self)
     }
 
-    fn read_registers(&mut self, regs: &mut RiscvCoreRegs<u32>) -> TargetResult<(), Self> {
+    fn read_registers(
+        &mut self,
+        regs: &mut RiscvCoreRegs<u32>,
+        tid: Tid,
+    ) -> TargetResult<(), Self> {
         let (sender, reciver) = oneshot::channel();
-        self.send_command(Command::ReadRegisters(sender))?;
+        self.send_command(Command::ReadRegisters(tid_to_hart(tid), sender))?;
         let registers = reciver
             .recv()
             .map_err(|_| TargetError::Fatal(GdbTargetError::NoAnswer))?;
@@ -32,23 +37,33 @@ impl SingleThreadBase for GdbTarget {
         Ok(())
     }
 
-    fn write_registers(&mut self, regs: &RiscvCoreRegs<u32>) -> TargetResult<(), Self> {
+    fn write_registers(&mut self, regs: &RiscvCoreRegs<u32>, tid: Tid) -> TargetResult<(), Self> {
         let mut registers = Registers::default();
         for r in Specifier::iter_all() {
             registers.set_x(r, regs.x[usize::from(r)])
         }
         *registers.pc_mut() = regs.pc;
 
-        self.send_command(Command::WriteRegisters(registers))
+        self.send_command(Command::WriteRegisters(tid_to_hart(tid), registers))
     }
 
-    fn support_single_register_access(&mut self) -> Option<SingleRegisterAccessOps<'_, (), Self>> {
+    fn support_single_register_access(&mut self) -> Option<SingleRegisterAccessOps<'_, Tid, Self>> {
         Some(self)
     }
 
-    fn read_addrs(&mut self, start_addr: u32, data: &mut [u8]) -> TargetResult<usize, Self> {
+    fn read_addrs(
+        &mut self,
+        start_addr: u32,
+        data: &mut [u8],
+        tid: Tid,
+    ) -> TargetResult<usize, Self> {
         let (sender, reciver) = oneshot::channel();
-        self.send_command(Command::ReadAddrs(start_addr, data.len(), sender))?;
+        self.send_command(Command::ReadAddrs(
+            tid_to_hart(tid),
+            start_addr,
+            data.len(),
+            sender,
+        ))?;
         let r = reciver
             .recv()
             .map_err(|_| TargetError::Fatal(GdbTargetError::NoAnswer))??;
@@ -57,13 +72,29 @@ impl SingleThreadBase for GdbTarget {
         Ok(r.len())
     }
 
-    fn write_addrs(&mut self, start_addr: u32, data: &[u8]) -> TargetResult<(), Self> {
+    fn write_addrs(&mut self, start_addr: u32, data: &[u8], tid: Tid) -> TargetResult<(), Self> {
         let (sender, reciver) = oneshot::channel();
-        self.send_command(Command::WriteAddrs(start_addr, data.to_owned(), sender))?;
+        self.send_command(Command::WriteAddrs(
+            tid_to_hart(tid),
+            start_addr,
+            data.to_owned(),
+            sender,
+        ))?;
 
         reciver
             .recv()
             .map_err(|_| TargetError::Fatal(GdbTargetError::NoAnswer))?
             .map_err(|_| TargetError::NonFatal)
     }
+
+    #[inline(always)]
+    fn list_active_threads(
+        &mut self,
+        thread_is_active: &mut dyn FnMut(Tid),
+    ) -> Result<(), Self::Error> {
+        for hart in 0..self.hart_count {
+            thread_is_active(hart_to_tid(hart));
+        }
+        Ok(())
+    }
 }
@@ -1,10 +1,13 @@
 use gdbstub::{
     arch::Arch,
     target::{
-        ext::breakpoints::{Breakpoints, HwBreakpoint, SwBreakpoint, SwBreakpointOps},
+        ext::breakpoints::{
+            Breakpoints, HwBreakpoint, HwWatchpoint, SwBreakpoint, SwBreakpointOps, WatchKind,
+        },
         TargetResult,
     },
 };
+use red_planet_core::core::watchpoint::{self, Watchpoint};
 
 use crate::{gdb::GdbTarget, target::command::Command};
 
@@ -22,7 +25,7 @@ impl Breakpoints for GdbTarget {
     fn support_hw_watchpoint(
         &mut self,
     ) -> Option<gdbstub::target::ext::breakpoints::HwWatchpointOps<'_, Self>> {
-        None
+        Some(self)
     }
 }
 
@@ -65,3 +68,37 @@ impl HwBreakpoint for GdbTarget {
         Ok(true)
     }
 }
+
+impl HwWatchpoint for GdbTarget {
+    fn add_hw_watchpoint(
+        &mut self,
+        addr: u32,
+        len: u32,
+        kind: WatchKind,
+    ) -> TargetResult<bool, Self> {
+        self.send_command(Command::AddWatchpoint(watchpoint(addr, len, kind)))?;
+        Ok(true)
+    }
+
+    fn remove_hw_watchpoint(
+        &mut self,
+        addr: u32,
+        len: u32,
+        kind: WatchKind,
+    ) -> TargetResult<bool, Self> {
+        self.send_command(Command::RemoveWatchpoint(watchpoint(addr, len, kind)))?;
+        Ok(true)
+    }
+}
+
+fn watchpoint(address: u32, len: u32, kind: WatchKind) -> Watchpoint {
+    Watchpoint {
+        address,
+        len,
+        kind: match kind {
+            WatchKind::Read => watchpoint::WatchKind::Read,
+            WatchKind::Write => watchpoint::WatchKind::Write,
+            WatchKind::ReadWrite => watchpoint::WatchKind::Access,
+        },
+    }
+}
@@ -0,0 +1,57 @@
+use gdbstub::target::ext::monitor_cmd::{outputln, ConsoleOutput, MonitorCmd};
+
+use crate::{
+    gdb::{GdbTarget, GdbTargetError},
+    target::{command::Command, format_branch},
+};
+
+impl MonitorCmd for GdbTarget {
+    fn handle_monitor_cmd(
+        &mut self,
+        cmd: &[u8],
+        mut out: ConsoleOutput<'_>,
+    ) -> Result<(), Self::Error> {
+        let cmd = String::from_utf8_lossy(cmd);
+        match cmd.split_whitespace().collect::<Vec<_>>().as_slice() {
+            ["branches"] => {
+                let (sender, reciver) = oneshot::channel();
+                self.send_branch_command(Command::ListBranches(sender))?;
+                let branches = reciver.recv().map_err(|_| GdbTargetError::NoAnswer)?;
+                for branch in &branches {
+                    outputln!(out, "{}", format_branch(branch));
+                }
+            }
+            ["branch", branch] => {
+                let (sender, reciver) = oneshot::channel();
+                self.send_branch_command(Command::SwitchBranch(branch.to_string(), sender))?;
+                match reciver.recv().map_err(|_| GdbTargetError::NoAnswer)? {
+                    // Registers cached by GDB belong to the previous branch.
+                    Ok(()) => outputln!(
+                        out,
+                        "Switched to branch {branch}, use `flushregs` to refresh registers"
+                    ),
+                    Err(e) => outputln!(out, "{e}"),
+                }
+            }
+            ["name-branch", name] => {
+                self.send_branch_command(Command::NameBranch(name.to_string()))?;
+            }
+            _ => {
+                outputln!(out, "Unknown command: {cmd}");
+                outputln!(
+                    out,
+                    "Available commands: branches, branch <BRANCH>, name-branch <NAME>"
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+impl GdbTarget {
+    fn send_branch_command(&mut self, command: Command) -> Result<(), GdbTargetError> {
+        self.command_sender
+            .send(command)
+            .map_err(|_| GdbTargetError::TargetGone)
+    }
+}
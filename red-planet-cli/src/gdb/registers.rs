@@ -1,36 +1,43 @@
+use gdbstub::common::Tid;
 use gdbstub::target::{
     ext::base::single_register_access::SingleRegisterAccess, TargetError, TargetResult,
 };
-use gdbstub_arch::riscv::reg::id::RiscvRegId;
 use std::io::Write;
 
-use crate::{gdb::GdbTarget, target::command::Command};
+use crate::{
+    gdb::{tid_to_hart, GdbTarget, OurRiscvRegId},
+    target::command::Command,
+};
 
 use super::GdbTargetError;
 
-impl SingleRegisterAccess<()> for GdbTarget {
+impl SingleRegisterAccess<Tid> for GdbTarget {
     fn read_register(
         &mut self,
-        _tid: (),
-        reg_id: RiscvRegId<u32>,
+        tid: Tid,
+        reg_id: OurRiscvRegId,
         mut buf: &mut [u8],
     ) -> TargetResult<usize, Self> {
         let (sender, reciver) = oneshot::channel();
-        self.send_command(Command::ReadRegister(reg_id, sender))?;
-        let value = reciver.recv().map_err(|_| TargetError::NonFatal)?;
-        let bytes = value.to_le_bytes();
+        self.send_command(Command::ReadRegister(tid_to_hart(tid), reg_id.0, sender))?;
+        let bytes = reciver.recv().map_err(|_| TargetError::NonFatal)?;
         buf.write_all(&bytes)?;
         Ok(bytes.len())
     }
 
     fn write_register(
         &mut self,
-        _tid: (),
-        reg_id: RiscvRegId<u32>,
+        tid: Tid,
+        reg_id: OurRiscvRegId,
         val: &[u8],
     ) -> TargetResult<(), Self> {
         let (sender, reciver) = oneshot::channel();
-        self.send_command(Command::WriteRegister(reg_id, val.to_owned(), sender))?;
+        self.send_command(Command::WriteRegister(
+            tid_to_hart(tid),
+            reg_id.0,
+            val.to_owned(),
+            sender,
+        ))?;
 
         reciver
             .recv()
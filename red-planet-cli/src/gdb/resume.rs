@@ -1,41 +1,59 @@
 use gdbstub::{
-    common::Signal,
+    common::{Signal, Tid},
     target::ext::base::{
+        multithread::{MultiThreadRangeSteppingOps, MultiThreadResume, MultiThreadSingleStepOps},
         reverse_exec::{ReverseCont, ReverseContOps, ReverseStepOps},
-        singlethread::{
-            SingleThreadRangeSteppingOps, SingleThreadResume, SingleThreadSingleStepOps,
-        },
     },
 };
 
 use crate::target::command::Command;
 
-use super::{GdbTarget, GdbTargetError};
-
-impl SingleThreadResume for GdbTarget {
-    fn resume(&mut self, _signal: Option<Signal>) -> Result<(), Self::Error> {
-        self.send_command(Command::Continue)
+use super::{GdbTarget, GdbTargetError, ResumeAction};
+
+impl MultiThreadResume for GdbTarget {
+    fn resume(&mut self) -> Result<(), Self::Error> {
+        // Harts are interleaved deterministically, so they can only be stepped or continued all
+        // together. Stepping a single hart runs the others until it executed an instruction.
+        let command = match self.resume_action.take() {
+            Some(ResumeAction::Step(hart)) => Command::Step(hart),
+            Some(ResumeAction::RangeStep(hart, start, end)) => Command::RangeStep(hart, start, end),
+            None => Command::Continue,
+        };
+        self.send_command(command)
             .map_err(|_| GdbTargetError::TargetGone)
     }
 
-    fn support_single_step(&mut self) -> Option<SingleThreadSingleStepOps<'_, Self>> {
+    fn clear_resume_actions(&mut self) -> Result<(), Self::Error> {
+        self.resume_action = None;
+        Ok(())
+    }
+
+    fn set_resume_action_continue(
+        &mut self,
+        _tid: Tid,
+        _signal: Option<Signal>,
+    ) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn support_single_step(&mut self) -> Option<MultiThreadSingleStepOps<'_, Self>> {
         Some(self)
     }
 
-    fn support_range_step(&mut self) -> Option<SingleThreadRangeSteppingOps<'_, Self>> {
+    fn support_range_step(&mut self) -> Option<MultiThreadRangeSteppingOps<'_, Self>> {
         Some(self)
     }
 
-    fn support_reverse_step(&mut self) -> Option<ReverseStepOps<'_, (), Self>> {
+    fn support_reverse_step(&mut self) -> Option<ReverseStepOps<'_, Tid, Self>> {
         Some(self)
     }
 
-    fn support_reverse_cont(&mut self) -> Option<ReverseContOps<'_, (), Self>> {
+    fn support_reverse_cont(&mut self) -> Option<ReverseContOps<'_, Tid, Self>> {
         Some(self)
     }
 }
 
-impl ReverseCont<()> for GdbTarget {
+impl ReverseCont<Tid> for GdbTarget {
     fn reverse_cont(&mut self) -> Result<(), Self::Error> {
         self.send_command(Command::ReverseContinue)
             .map_err(|_| GdbTargetError::TargetGone)
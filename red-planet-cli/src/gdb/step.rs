@@ -1,32 +1,41 @@
 use gdbstub::{
-    common::Signal,
+    common::{Signal, Tid},
     target::ext::base::{
+        multithread::{MultiThreadRangeStepping, MultiThreadSingleStep},
         reverse_exec::ReverseStep,
-        singlethread::{SingleThreadRangeStepping, SingleThreadSingleStep},
     },
 };
 
 use crate::target::command::Command;
 
-use super::{GdbTarget, GdbTargetError};
+use super::{tid_to_hart, GdbTarget, GdbTargetError, ResumeAction};
 
-impl SingleThreadSingleStep for GdbTarget {
-    fn step(&mut self, _signal: Option<Signal>) -> Result<(), Self::Error> {
-        self.send_command(Command::Step)
-            .map_err(|_| GdbTargetError::TargetGone)
+impl MultiThreadSingleStep for GdbTarget {
+    fn set_resume_action_step(
+        &mut self,
+        tid: Tid,
+        _signal: Option<Signal>,
+    ) -> Result<(), Self::Error> {
+        self.resume_action = Some(ResumeAction::Step(tid_to_hart(tid)));
+        Ok(())
     }
 }
 
-impl ReverseStep<()> for GdbTarget {
-    fn reverse_step(&mut self, _tid: ()) -> Result<(), Self::Error> {
-        self.send_command(Command::StepBack)
+impl ReverseStep<Tid> for GdbTarget {
+    fn reverse_step(&mut self, tid: Tid) -> Result<(), Self::Error> {
+        self.send_command(Command::StepBack(tid_to_hart(tid)))
             .map_err(|_| GdbTargetError::TargetGone)
     }
 }
 
-impl SingleThreadRangeStepping for GdbTarget {
-    fn resume_range_step(&mut self, start: u32, end: u32) -> Result<(), Self::Error> {
-        self.send_command(Command::RangeStep(start, end))
-            .map_err(|_| GdbTargetError::TargetGone)
+impl MultiThreadRangeStepping for GdbTarget {
+    fn set_resume_action_range_step(
+        &mut self,
+        tid: Tid,
+        start: u32,
+        end: u32,
+    ) -> Result<(), Self::Error> {
+        self.resume_action = Some(ResumeAction::RangeStep(tid_to_hart(tid), start, end));
+        Ok(())
     }
 }
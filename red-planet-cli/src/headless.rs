@@ -0,0 +1,120 @@
+//! Running without the TUI, for use in scripts and CI.
+
+use std::{process::ExitCode, time::Duration};
+
+use log::{error, info};
+use tokio::{
+    io::{stdin, stdout, AsyncReadExt, AsyncWriteExt},
+    select, spawn,
+    sync::{
+        mpsc::{UnboundedReceiver, UnboundedSender},
+        watch,
+    },
+    time::sleep,
+};
+
+use crate::target::{command::Command, Event, SharedTargetState};
+
+/// Exit code used when the guest did not power down within the limits, like `timeout(1)` does.
+const LIMIT_EXIT_CODE: u8 = 124;
+
+/// Bridges stdin and stdout to the UART, and returns the exit code of the process once the guest
+/// powers down or one of the limits is reached.
+///
+/// The target is told to exit before returning, and all output of the UART is written to stdout.
+pub async fn run_headless(
+    command_sender: UnboundedSender<Command>,
+    mut event_receiver: UnboundedReceiver<Event>,
+    mut shared_state: watch::Receiver<SharedTargetState>,
+    uart_sender: UnboundedSender<u8>,
+    timeout: Option<Duration>,
+) -> ExitCode {
+    spawn(forward_stdin(uart_sender));
+
+    let mut stdout = stdout();
+    let mut written = 0;
+    let timeout = async {
+        match timeout {
+            Some(timeout) => sleep(timeout).await,
+            None => std::future::pending().await,
+        }
+    };
+    tokio::pin!(timeout);
+
+    let exit_code = loop {
+        select! {
+            changed = shared_state.changed() => {
+                if changed.is_err() {
+                    error!("Target stopped unexpectedly");
+                    break ExitCode::FAILURE;
+                }
+                written = write_output(&mut stdout, &shared_state, written).await;
+            }
+            event = event_receiver.recv() => match event {
+                Some(Event::PoweredDown(status)) => {
+                    info!("Guest powered down with {status:?}");
+                    break ExitCode::from(status.exit_code());
+                }
+                Some(Event::StepLimit) => {
+                    error!("Guest did not power down within the maximum number of steps");
+                    break ExitCode::from(LIMIT_EXIT_CODE);
+                }
+                Some(event) => {
+                    error!("Target stopped due to {event:?}");
+                    break ExitCode::FAILURE;
+                }
+                None => {
+                    error!("Target stopped unexpectedly");
+                    break ExitCode::FAILURE;
+                }
+            },
+            () = &mut timeout => {
+                error!("Guest did not power down before the timeout");
+                break ExitCode::from(LIMIT_EXIT_CODE);
+            }
+        }
+    };
+
+    // The last output is only shared once the target has stopped, which drops the sender.
+    let _ = command_sender.send(Command::Exit);
+    while shared_state.changed().await.is_ok() {}
+    write_output(&mut stdout, &shared_state, written).await;
+
+    exit_code
+}
+
+/// Writes the UART output from index `written` onwards to stdout, returning the new number of
+/// bytes written.
+async fn write_output(
+    stdout: &mut tokio::io::Stdout,
+    shared_state: &watch::Receiver<SharedTargetState>,
+    written: usize,
+) -> usize {
+    let output = shared_state.borrow().output_buffer[written..].to_vec();
+    if let Err(e) = stdout.write_all(&output).await {
+        error!("Failed to write UART output: {e}");
+    }
+    let _ = stdout.flush().await;
+    written + output.len()
+}
+
+async fn forward_stdin(uart_sender: UnboundedSender<u8>) {
+    let mut stdin = stdin();
+    let mut buf = [0; 256];
+    loop {
+        match stdin.read(&mut buf).await {
+            Ok(0) => break,
+            Ok(len) => {
+                for &byte in &buf[..len] {
+                    if uart_sender.send(byte).is_err() {
+                        return;
+                    }
+                }
+            }
+            Err(e) => {
+                error!("Failed to read stdin: {e}");
+                break;
+            }
+        }
+    }
+}
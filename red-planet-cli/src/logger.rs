@@ -0,0 +1,61 @@
+//! Plain logger writing to a file or stderr, used instead of the TUI log pane in headless mode.
+
+use std::{
+    fs::File,
+    io::{stderr, Write},
+    sync::Mutex,
+};
+
+use log::{LevelFilter, Log, Metadata, Record};
+
+/// Parses a level filter for clap, since [`log::ParseLevelError`] is not an error type without
+/// the `std` feature of `log`.
+pub fn parse_level(level: &str) -> Result<LevelFilter, String> {
+    level
+        .parse()
+        .map_err(|_| format!("unknown log level `{level}`"))
+}
+
+struct Logger {
+    output: Mutex<Box<dyn Write + Send>>,
+}
+
+/// Installs a logger that writes every record up to `level` to the file at `path`, or to stderr
+/// if no path is given. Stdout is left alone, since it carries the output of the UART.
+pub fn init(level: LevelFilter, path: Option<&str>) -> std::io::Result<()> {
+    let output: Box<dyn Write + Send> = match path {
+        Some(path) => Box::new(File::create(path)?),
+        None => Box::new(stderr()),
+    };
+    let logger = Box::leak(Box::new(Logger {
+        output: Mutex::new(output),
+    }));
+    log::set_logger(logger).map_err(|e| std::io::Error::other(e.to_string()))?;
+    log::set_max_level(level);
+    Ok(())
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let mut output = self.output.lock().unwrap();
+        // There is nowhere left to report a failing log output to.
+        let _ = writeln!(
+            output,
+            "[{:<5} {}] {}",
+            record.level(),
+            record.target(),
+            record.args()
+        );
+    }
+
+    fn flush(&self) {
+        let _ = self.output.lock().unwrap().flush();
+    }
+}
@@ -1,4 +1,8 @@
 mod gdb;
+mod headless;
+mod logger;
+mod session;
+mod symbols;
 mod target;
 mod tcp;
 mod tui;
@@ -7,12 +11,19 @@ use gdb::{run_server, GdbTarget};
 use gdbstub::stub::DisconnectReason;
 use goblin::elf::program_header::PT_LOAD;
 use log::{debug, info, warn};
+use session::{AddOutputBuffer, Description};
 use target::{SharedTargetState, SimTarget};
 
 use clap::Parser;
-use red_planet_core::board::{Board, Config};
+use headless::run_headless;
+use log::LevelFilter;
+use red_planet_core::board::{Board, HtifConfig};
+use red_planet_core::simulator::snapshot_policy::LongHistoryPolicy;
 use red_planet_core::simulator::SimulationAllocator;
 use std::fs::File;
+use std::num::{NonZeroU32, NonZeroUsize};
+use std::process::ExitCode;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tcp::TcpStream;
 use tokio::net::TcpListener;
 use tokio::sync::mpsc::unbounded_channel;
@@ -30,51 +41,203 @@ struct Args {
     gdb: Option<u16>,
     #[arg(short, long, default_value_t = true)]
     elf: bool,
+    /// Board description file with the memory map and devices of the simulated board. Defaults to
+    /// the built-in board, see `examples/boards/default.toml`.
+    #[arg(long)]
+    board: Option<String>,
+    /// Number of harts of the simulated board, overriding the board description.
+    #[arg(long)]
+    harts: Option<NonZeroUsize>,
+    /// Number of consecutive instructions a hart executes before the next hart is scheduled,
+    /// overriding the board description.
+    #[arg(long)]
+    quantum: Option<NonZeroU32>,
+    /// Disk image to attach as a virtio block device, which can be given multiple times. The image
+    /// is never written to: writes of the guest are only kept in the simulation.
+    #[arg(long = "drive", value_name = "IMAGE")]
+    drives: Vec<String>,
+    /// Handle semihosting calls of the guest, instead of treating the semihosting sequence as a
+    /// breakpoint. The console output of the guest is shown like the output of the UART.
+    #[arg(long)]
+    semihosting: bool,
+    /// Directory to which the file accesses of semihosting calls are sandboxed. Implies
+    /// `--semihosting`. Files the guest changed are written back when the simulator exits, if the
+    /// guest exited.
+    #[arg(long, value_name = "DIR")]
+    semihosting_root: Option<String>,
+    /// Device tree blob whose address is passed to the harts in a1, instead of the one generated
+    /// from the board description. It is placed in MROM, right after the reset vector.
+    #[arg(long, value_name = "FILE")]
+    dtb: Option<String>,
+    /// Firmware to boot Linux with, such as OpenSBI's `fw_jump.bin`. It is loaded as a raw binary
+    /// at the start of DRAM, instead of the binary argument.
+    #[arg(long, value_name = "FILE", conflicts_with = "binary")]
+    bios: Option<String>,
+    /// Linux kernel image, loaded 4 MiB into DRAM, which is where `fw_jump` jumps to on RV32.
+    #[arg(
+        long,
+        value_name = "FILE",
+        requires = "bios",
+        conflicts_with = "binary"
+    )]
+    kernel: Option<String>,
+    /// Initial RAM disk, loaded 128 MiB after the kernel (or half the size of DRAM, if that is
+    /// smaller), and passed to the kernel in the generated device tree.
+    #[arg(long, value_name = "FILE", requires = "kernel", conflicts_with_all = ["binary", "dtb"])]
+    initrd: Option<String>,
+    /// Kernel command line, passed to the kernel in the generated device tree.
+    #[arg(long, value_name = "ARGS", requires = "kernel", conflicts_with_all = ["binary", "dtb"])]
+    append: Option<String>,
+    /// Let WFI stall harts, and skip time to the next timer event while all harts are stalled.
+    #[arg(long)]
+    idle_skip: bool,
+    /// Thin out older snapshots of the history, so memory stays bounded during long runs.
+    #[arg(long)]
+    long_history: bool,
+    /// Resume the timeline stored in a session file (saved with the `save` command), instead of
+    /// starting a new one. The board options and binary are taken from the session.
+    #[arg(long, conflicts_with_all = ["binary", "bios"])]
+    load_session: Option<String>,
+    /// Run without the TUI: stdin and stdout are connected to the UART, and the process exits
+    /// once the guest powers down.
+    #[arg(long, conflicts_with = "gdb")]
+    headless: bool,
+    /// Stop running forward once the history has this many steps. In headless mode, this exits
+    /// with code 124.
+    #[arg(long)]
+    max_steps: Option<usize>,
+    /// Exit with code 124 if the guest did not power down after this many seconds.
+    #[arg(long, requires = "headless")]
+    timeout: Option<u64>,
+    /// Most verbose level of the log messages shown, one of off, error, warn, info, debug and
+    /// trace. Defaults to warn in headless mode, and debug otherwise.
+    #[arg(long, value_parser = logger::parse_level)]
+    log_level: Option<LevelFilter>,
+    /// File to write the log to in headless mode, instead of stderr.
+    #[arg(long, requires = "headless")]
+    log_file: Option<String>,
     /// Binary file to execute.
-    binary: String,
+    #[arg(required_unless_present_any = ["load_session", "bios"])]
+    binary: Option<String>,
 }
 
-fn main() -> std::io::Result<()> {
+fn main() -> std::io::Result<ExitCode> {
     let rt = runtime::Runtime::new()?;
-    rt.block_on(start())?;
+    let exit_code = rt.block_on(start())?;
     rt.shutdown_background();
-    Ok(())
+    Ok(exit_code)
 }
 
-async fn start() -> std::io::Result<()> {
+async fn start() -> std::io::Result<ExitCode> {
     let args = Args::parse();
 
-    tui_logger::init_logger(log::LevelFilter::Trace).unwrap();
-    tui_logger::set_default_level(log::LevelFilter::Debug);
+    if args.headless {
+        logger::init(
+            args.log_level.unwrap_or(LevelFilter::Warn),
+            args.log_file.as_deref(),
+        )?;
+    } else {
+        tui_logger::init_logger(LevelFilter::Trace).unwrap();
+        tui_logger::set_default_level(args.log_level.unwrap_or(LevelFilter::Debug));
+    }
     // tui_logger::set_level_for_target(module_path!(), log::LevelFilter::Trace);
     // tui_logger::set_level_for_target("red_planet_core", log::LevelFilter::Trace);
 
-    let mut buf = Vec::new();
+    let (description, mut simulator, output_buffer) = match args.load_session {
+        Some(path) => session::load(path).map_err(std::io::Error::other)?,
+        None => {
+            let mut binary = Vec::new();
 
-    use std::io::Read;
-    let mut file = File::open(args.binary)?;
-    file.read_to_end(&mut buf)?;
+            use std::io::Read;
+            // Unwrap safety: clap requires `binary` if `load_session` and `bios` are absent.
+            let (elf, path) = match args.bios {
+                Some(bios) => (false, bios),
+                None => (args.elf, args.binary.unwrap()),
+            };
+            let mut file = File::open(path)?;
+            file.read_to_end(&mut binary)?;
 
-    let mut simulator = Simulator::new(|allocator| {
-        let board = Board::new(allocator, Config::default());
-        if args.elf {
-            load_elf(&board, allocator, &buf).unwrap()
-        } else {
-            board.load_physical(allocator, 0x8000_0000, &buf);
-        }
-        board
-    });
+            let board = match args.board {
+                Some(path) => std::fs::read_to_string(path)?,
+                None => String::new(),
+            };
 
-    let terminal_drop_gard = TermSetupDropGard::new().unwrap();
+            let dtb = args.dtb.map(std::fs::read).transpose()?;
+            let kernel = args.kernel.map(std::fs::read).transpose()?;
+            let initrd = args.initrd.map(std::fs::read).transpose()?;
+
+            let mut description = Description {
+                board,
+                harts: args.harts,
+                quantum: args.quantum,
+                idle_skip: args.idle_skip,
+                elf,
+                binary,
+                drives: args.drives,
+                semihosting: args.semihosting,
+                semihosting_root: args.semihosting_root,
+                start_time: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |time| time.as_secs() as u32),
+                dtb,
+                kernel,
+                initrd,
+                append: args.append,
+                host_digests: Vec::new(),
+            };
+            let config = description.config().map_err(std::io::Error::other)?;
+            description.host_digests = session::host_digests(&config)?
+                .into_iter()
+                .map(|(_, digest)| digest)
+                .collect();
+            if !description.kernel_fits(&config) {
+                return Err(std::io::Error::other("kernel does not fit in DRAM"));
+            }
+            if !description.initrd_after_kernel(&config) {
+                return Err(std::io::Error::other("kernel overlaps the initrd"));
+            }
+
+            let mut simulator = Simulator::new(|allocator| description.build(allocator));
+            let output_buffer = simulator.step_recorded(AddOutputBuffer);
+            (description, simulator, output_buffer)
+        }
+    };
+    if args.long_history {
+        simulator.set_snapshot_policy(LongHistoryPolicy::default());
+    }
+    let hart_count = simulator.inspect().1.hart_count();
 
     let (shared_state_sender, shared_state_receiver) = watch::channel(SharedTargetState::default());
     let (uart_sender, uart_receiver) = unbounded_channel();
 
-    let (target, command_sender, event_receiver) =
-        SimTarget::new(&mut simulator, shared_state_sender, uart_receiver);
+    let (mut target, command_sender, event_receiver) = SimTarget::new(
+        description,
+        output_buffer,
+        shared_state_sender,
+        uart_receiver,
+    );
+    target.set_max_steps(args.max_steps);
+
+    if args.headless {
+        command_sender
+            .send(target::command::Command::Continue)
+            .unwrap();
+        let timeout = args.timeout.map(Duration::from_secs);
+        let headless = spawn(run_headless(
+            command_sender,
+            event_receiver,
+            shared_state_receiver,
+            uart_sender,
+            timeout,
+        ));
+        target.run(simulator).await;
+        return headless.await.map_err(std::io::Error::other);
+    }
+
+    let terminal_drop_gard = TermSetupDropGard::new().unwrap();
 
     if let Some(port) = args.gdb {
-        let gdb_target = GdbTarget::new(command_sender.clone(), event_receiver);
+        let gdb_target = GdbTarget::new(command_sender.clone(), event_receiver, hart_count);
         spawn(run_gdb(gdb_target, port));
     } else {
         command_sender
@@ -88,14 +251,11 @@ async fn start() -> std::io::Result<()> {
 
     drop(terminal_drop_gard);
 
-    Ok(())
+    Ok(ExitCode::SUCCESS)
 }
 
-fn load_elf(
-    board: &Board<SimulationAllocator>,
-    allocator: &mut SimulationAllocator,
-    program_elf: &[u8],
-) -> Result<(), goblin::error::Error> {
+/// Returns the physical address and contents of the loadable segments of an ELF file.
+pub(crate) fn elf_images(program_elf: &[u8]) -> Result<Vec<(u32, Vec<u8>)>, goblin::error::Error> {
     // load ELF
     let elf_header = goblin::elf::Elf::parse(program_elf)?;
 
@@ -105,6 +265,7 @@ fn load_elf(
         .iter()
         .filter(|h| h.p_type == PT_LOAD);
 
+    let mut images = Vec::new();
     for h in segments {
         debug!(
             "loading segment: file range [{:#010x?}..{:#010x?}] to pmem range [{:#010x?}..{:#010x?}] (virt {:#010x?})",
@@ -116,10 +277,25 @@ fn load_elf(
         );
 
         let buf = &program_elf[h.file_range()];
-        board.load_physical(allocator, h.p_paddr as u32, buf);
+        images.push((h.p_paddr as u32, buf.to_vec()));
     }
 
-    Ok(())
+    Ok(images)
+}
+
+/// Returns the HTIF of a program built for Spike, which is used if it has a `tohost` symbol.
+pub(crate) fn htif_config(program_elf: &[u8]) -> Option<HtifConfig> {
+    let elf = goblin::elf::Elf::parse(program_elf).ok()?;
+    let address_of = |name| {
+        elf.syms
+            .iter()
+            .find(|sym| elf.strtab.get_at(sym.st_name) == Some(name))
+            .map(|sym| sym.st_value as u32)
+    };
+    Some(HtifConfig {
+        tohost: address_of("tohost")?,
+        fromhost: address_of("fromhost"),
+    })
 }
 
 async fn run_gdb(mut target: GdbTarget, port: u16) {
@@ -0,0 +1,635 @@
+//! Session files, which store a full recorded timeline so it can be resumed later (possibly by
+//! someone else) with `--load-session`.
+//!
+//! All custom ticks performed by the CLI are [`RecordedTick`]s, so they can be replayed.
+
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::num::{NonZeroU32, NonZeroUsize};
+use std::path::Path;
+
+use gdbstub::target::TargetError;
+use gdbstub_arch::riscv::reg::id::RiscvRegId;
+use red_planet_core::board::{
+    Board, BootMode, ChosenConfig, Config, ConfigError, Region, SemihostingConfig,
+    VirtioBlockConfig,
+};
+use red_planet_core::core::csr::CsrSpecifier;
+use red_planet_core::core::mmu::MemoryError;
+use red_planet_core::core::CsrReadResult;
+use red_planet_core::registers::{Registers, Specifier};
+use red_planet_core::simulator::session::{Decoder, Encoder, RecordedTick, Session, SessionError};
+use red_planet_core::simulator::SimulationAllocator;
+use red_planet_core::{Allocator, ArrayAccessorMut};
+use sha2::{Digest, Sha256};
+
+use crate::{elf_images, htif_config, Simulator};
+
+type BoardSA = Board<SimulationAllocator>;
+
+/// Base address of the virtio block device of the first `--drive`, each next drive is mapped
+/// [`DRIVE_STRIDE`] bytes further.
+const DRIVE_BASE: u32 = 0x1000_1000;
+const DRIVE_STRIDE: u32 = 0x1000;
+/// PLIC interrupt source of the first `--drive`, each next drive uses the next one.
+const DRIVE_IRQ: u32 = 8;
+/// Offset in DRAM of the `--kernel`, which is where OpenSBI's `fw_jump` jumps to on RV32. The
+/// kernel must be 4 MiB aligned there.
+const KERNEL_OFFSET: u32 = 0x40_0000;
+/// Largest distance between the start of the kernel and the `--initrd`, which leaves room for the
+/// kernel to unpack itself. This is the distance QEMU uses.
+const INITRD_MAX_DISTANCE: u32 = 0x800_0000;
+
+/// SHA-256 digest of a host file or directory.
+pub type HostDigest = [u8; 32];
+
+/// Ids of the buffer collecting all UART output.
+pub type OutputBuffer = (
+    <SimulationAllocator as Allocator>::ArrayId<u8>,
+    <SimulationAllocator as Allocator>::Id<usize>,
+);
+
+/// Everything needed to construct the simulated board, stored as [`Session::description`].
+#[derive(Debug, Clone)]
+pub struct Description {
+    /// Board description file, see [`Config`]. Empty for the built-in board.
+    pub board: String,
+    /// Overrides the number of harts of the board description.
+    pub harts: Option<NonZeroUsize>,
+    /// Overrides the quantum of the board description.
+    pub quantum: Option<NonZeroU32>,
+    /// Enables idle skipping, even if the board description does not.
+    pub idle_skip: bool,
+    pub elf: bool,
+    pub binary: Vec<u8>,
+    /// Disk images attached as virtio block devices, in addition to those of the board
+    /// description.
+    pub drives: Vec<String>,
+    /// Enables semihosting, even if the board description does not.
+    pub semihosting: bool,
+    /// Overrides the semihosting root directory of the board description, and enables
+    /// semihosting.
+    pub semihosting_root: Option<String>,
+    /// Seconds since the Unix epoch at which the session started, see
+    /// [`SemihostingConfig::start_time`].
+    pub start_time: u32,
+    /// Device tree blob replacing the one generated from the board description.
+    pub dtb: Option<Vec<u8>>,
+    /// Linux kernel image, loaded [`KERNEL_OFFSET`] bytes into DRAM.
+    pub kernel: Option<Vec<u8>>,
+    /// Initial RAM disk of the kernel, see [`Description::initrd_address`].
+    pub initrd: Option<Vec<u8>>,
+    /// Kernel command line.
+    pub append: Option<String>,
+    /// Digests of the host files the board reads while running, see [`host_digests`]. A session
+    /// is only loaded if they did not change, since replaying it would give a different timeline.
+    pub host_digests: Vec<HostDigest>,
+}
+
+impl Description {
+    /// The board description with the overrides applied.
+    ///
+    /// A raw binary is loaded at the start of DRAM, unless the board boots from flash, in which
+    /// case it becomes the contents of flash. An ELF with a `tohost` symbol gets a HTIF. Drive `i`
+    /// is mapped at `DRIVE_BASE + i * DRIVE_STRIDE` with IRQ `DRIVE_IRQ + i`. The location of the
+    /// initrd and the kernel command line are added to the generated device tree.
+    pub fn config(&self) -> Result<Config, ConfigError> {
+        let mut config = Config::from_toml(&self.board)?;
+        if let Some(harts) = self.harts {
+            config.harts.count = harts;
+        }
+        if let Some(quantum) = self.quantum {
+            config.harts.quantum = quantum;
+        }
+        config.harts.idle_skip |= self.idle_skip;
+        for (index, drive) in (0..).zip(&self.drives) {
+            config.devices.virtio_blocks.push(VirtioBlockConfig {
+                base: DRIVE_BASE + index * DRIVE_STRIDE,
+                irq: DRIVE_IRQ + index,
+                image: drive.into(),
+            });
+        }
+        if self.semihosting || self.semihosting_root.is_some() {
+            let semihosting = (config.semihosting).get_or_insert_with(SemihostingConfig::default);
+            if let Some(root) = &self.semihosting_root {
+                semihosting.root = Some(root.into());
+            }
+        }
+        if let Some(semihosting) = &mut config.semihosting {
+            semihosting.start_time = self.start_time;
+        }
+        if let Some(dtb) = &self.dtb {
+            config.dtb = Some(dtb.clone());
+        }
+        config.chosen = ChosenConfig {
+            bootargs: self.append.clone(),
+            initrd: (self.initrd.as_ref()).map(|initrd| Region {
+                base: Self::initrd_address(&config),
+                size: u32::try_from(initrd.len()).unwrap_or(u32::MAX),
+            }),
+        };
+        if !self.elf && config.boot == BootMode::Flash {
+            config.flash = self.binary.clone();
+        }
+        if self.elf {
+            config.htif = htif_config(&self.binary);
+        }
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Construct the board and load the binary into it.
+    ///
+    /// Panics if [`Self::config`] fails, so that must be checked first.
+    pub fn build(&self, allocator: &mut SimulationAllocator) -> BoardSA {
+        let config = self.config().unwrap();
+        let boot = config.boot;
+        let dram_base = config.memory.dram.base;
+        let initrd_address = Self::initrd_address(&config);
+        let board = Board::new(allocator, config);
+        let mut images = Vec::new();
+        if self.elf {
+            images = elf_images(&self.binary).unwrap();
+        } else if boot == BootMode::Dram {
+            images.push((dram_base, self.binary.clone()));
+        }
+        if let Some(kernel) = &self.kernel {
+            images.push((dram_base + KERNEL_OFFSET, kernel.clone()));
+        }
+        if let Some(initrd) = &self.initrd {
+            images.push((initrd_address, initrd.clone()));
+        }
+        board.set_boot_images(allocator, images);
+        board
+    }
+
+    /// Returns `true` if the kernel fits in the DRAM of a board with the given configuration.
+    pub fn kernel_fits(&self, config: &Config) -> bool {
+        let kernel_size = self.kernel.as_ref().map_or(0, Vec::len) as u64;
+        KERNEL_OFFSET as u64 + kernel_size <= config.memory.dram.size as u64
+    }
+
+    /// Returns `true` if the initrd starts after the end of the kernel on a board with the given
+    /// configuration. That the initrd fits in DRAM is checked by [`Config::validate`].
+    pub fn initrd_after_kernel(&self, config: &Config) -> bool {
+        let kernel_end = KERNEL_OFFSET as u64 + self.kernel.as_ref().map_or(0, Vec::len) as u64;
+        self.initrd.is_none()
+            || kernel_end <= (Self::initrd_address(config) - config.memory.dram.base) as u64
+    }
+
+    /// Returns the address of the initrd, which is [`INITRD_MAX_DISTANCE`] bytes after the start
+    /// of the kernel, or half the size of DRAM if that is smaller.
+    fn initrd_address(config: &Config) -> u32 {
+        let dram = config.memory.dram;
+        (dram.base)
+            .saturating_add(KERNEL_OFFSET)
+            .saturating_add((dram.size / 2).min(INITRD_MAX_DISTANCE))
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut encoder = Encoder::default();
+        encoder.put_str(&self.board);
+        // Zero means the board description is not overridden.
+        encoder.put_usize(self.harts.map_or(0, NonZeroUsize::get));
+        encoder.put_u32(self.quantum.map_or(0, NonZeroU32::get));
+        encoder.put_bool(self.idle_skip);
+        encoder.put_bool(self.elf);
+        encoder.put_bytes(&self.binary);
+        encoder.put_usize(self.drives.len());
+        for drive in &self.drives {
+            encoder.put_str(drive);
+        }
+        encoder.put_bool(self.semihosting);
+        // An empty root means the board description is not overridden.
+        encoder.put_str(self.semihosting_root.as_deref().unwrap_or_default());
+        encoder.put_u32(self.start_time);
+        // An empty blob means the device tree is generated.
+        encoder.put_bytes(self.dtb.as_deref().unwrap_or_default());
+        // Empty means absent.
+        encoder.put_bytes(self.kernel.as_deref().unwrap_or_default());
+        encoder.put_bytes(self.initrd.as_deref().unwrap_or_default());
+        encoder.put_str(self.append.as_deref().unwrap_or_default());
+        encoder.put_usize(self.host_digests.len());
+        for digest in &self.host_digests {
+            encoder.put_bytes(digest);
+        }
+        encoder.into_bytes()
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, SessionError> {
+        let mut decoder = Decoder::new(bytes);
+        let description = Self {
+            board: decoder.get_str()?.to_owned(),
+            harts: NonZeroUsize::new(decoder.get_usize()?),
+            quantum: NonZeroU32::new(decoder.get_u32()?),
+            idle_skip: decoder.get_bool()?,
+            elf: decoder.get_bool()?,
+            binary: decoder.get_bytes()?.to_vec(),
+            drives: (0..decoder.get_usize()?)
+                .map(|_| decoder.get_str().map(str::to_owned))
+                .collect::<Result<_, _>>()?,
+            semihosting: decoder.get_bool()?,
+            semihosting_root: Some(decoder.get_str()?)
+                .filter(|root| !root.is_empty())
+                .map(str::to_owned),
+            start_time: decoder.get_u32()?,
+            dtb: Some(decoder.get_bytes()?)
+                .filter(|dtb| !dtb.is_empty())
+                .map(<[u8]>::to_vec),
+            kernel: Some(decoder.get_bytes()?)
+                .filter(|kernel| !kernel.is_empty())
+                .map(<[u8]>::to_vec),
+            initrd: Some(decoder.get_bytes()?)
+                .filter(|initrd| !initrd.is_empty())
+                .map(<[u8]>::to_vec),
+            append: Some(decoder.get_str()?)
+                .filter(|append| !append.is_empty())
+                .map(str::to_owned),
+            host_digests: (0..decoder.get_usize()?)
+                .map(|_| {
+                    decoder
+                        .get_bytes()?
+                        .try_into()
+                        .map_err(|_| SessionError::Malformed)
+                })
+                .collect::<Result<_, _>>()?,
+        };
+        if !decoder.is_empty() {
+            return Err(SessionError::Malformed);
+        }
+        let config = description.config().map_err(|_| SessionError::Malformed)?;
+        let host_digests = host_digests(&config)?;
+        if host_digests.len() != description.host_digests.len() {
+            return Err(SessionError::Malformed);
+        }
+        for ((name, digest), recorded) in host_digests.iter().zip(&description.host_digests) {
+            if digest != recorded {
+                return Err(SessionError::HostFileChanged(name.clone()));
+            }
+        }
+        Ok(description)
+    }
+}
+
+/// Returns a name and the digest of every host file the board reads while running: the disk
+/// images of the virtio block devices, and the semihosting root directory.
+pub fn host_digests(config: &Config) -> io::Result<Vec<(String, HostDigest)>> {
+    let mut digests = Vec::new();
+    for drive in &config.devices.virtio_blocks {
+        let mut hasher = Sha256::new();
+        io::copy(&mut File::open(&drive.image)?, &mut hasher)?;
+        let name = format!("disk image {}", drive.image.display());
+        digests.push((name, hasher.finalize().into()));
+    }
+    let root = (config.semihosting.as_ref()).and_then(|semihosting| semihosting.root.as_ref());
+    if let Some(root) = root {
+        let mut hasher = Sha256::new();
+        hash_directory(&mut hasher, root)?;
+        let name = format!("semihosting root {}", root.display());
+        digests.push((name, hasher.finalize().into()));
+    }
+    Ok(digests)
+}
+
+/// Feeds the names and contents of all entries of the directory at `path` into `hasher`,
+/// recursively and in a fixed order. Symbolic links are hashed as their target path.
+fn hash_directory(hasher: &mut Sha256, path: &Path) -> io::Result<()> {
+    let mut entries = fs::read_dir(path)?.collect::<io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let name = entry.file_name();
+        let name = name.as_encoded_bytes();
+        hasher.update((name.len() as u64).to_le_bytes());
+        hasher.update(name);
+        let file_type = entry.file_type()?;
+        if file_type.is_symlink() {
+            let target = fs::read_link(entry.path())?;
+            let target = target.as_os_str().as_encoded_bytes();
+            hasher.update([0]);
+            hasher.update((target.len() as u64).to_le_bytes());
+            hasher.update(target);
+        } else if file_type.is_dir() {
+            hasher.update([1]);
+            hash_directory(hasher, &entry.path())?;
+            // Marks the end of the directory, so its entries are not mistaken for entries of the
+            // parent directory.
+            hasher.update([2]);
+        } else {
+            hasher.update([3]);
+            hasher.update(entry.metadata()?.len().to_le_bytes());
+            io::copy(&mut File::open(entry.path())?, hasher)?;
+        }
+    }
+    Ok(())
+}
+
+/// Write the full history of `simulator` to the file at `path`.
+pub fn save(
+    simulator: &Simulator,
+    description: &Description,
+    path: impl AsRef<Path>,
+) -> Result<(), SessionError> {
+    let session = simulator.session(description.encode())?;
+    let mut writer = BufWriter::new(File::create(path)?);
+    session.write(&mut writer)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Restore a simulator from the session file at `path`.
+pub fn load(
+    path: impl AsRef<Path>,
+) -> Result<(Description, Simulator, OutputBuffer), SessionError> {
+    let session = Session::read(&mut BufReader::new(File::open(path)?))?;
+    let description = Description::decode(&session.description)?;
+
+    let mut output_buffer = None;
+    let simulator = Simulator::from_session(
+        &session,
+        |allocator| description.build(allocator),
+        |simulator, name, record| {
+            let mut decoder = Decoder::new(record);
+            match name {
+                AddOutputBuffer::NAME => {
+                    output_buffer = Some(simulator.step_recorded(AddOutputBuffer));
+                }
+                ComWithUart::NAME => {
+                    let output_buffer = output_buffer.ok_or(SessionError::Malformed)?;
+                    let input = decoder.get_bytes()?.to_vec();
+                    simulator.step_recorded(ComWithUart {
+                        input,
+                        output_buffer,
+                    });
+                }
+                ReadCsr::NAME => {
+                    let hart = decoder.get_usize()?;
+                    let specifier = decoder.get_u16()?;
+                    let _ = simulator.step_recorded(ReadCsr { hart, specifier });
+                }
+                WriteRegister::NAME => {
+                    let hart = decoder.get_usize()?;
+                    let reg_id = match decoder.get_u8()? {
+                        0 => RiscvRegId::Gpr(decoder.get_u8()?),
+                        1 => RiscvRegId::Fpr(decoder.get_u8()?),
+                        2 => RiscvRegId::Pc,
+                        3 => RiscvRegId::Csr(decoder.get_u16()?),
+                        4 => RiscvRegId::Priv,
+                        _ => return Err(SessionError::Malformed),
+                    };
+                    let value = decoder.get_bytes()?.to_vec();
+                    let _ = simulator.step_recorded(WriteRegister {
+                        hart,
+                        reg_id,
+                        value,
+                    });
+                }
+                WriteRegisters::NAME => {
+                    let hart = decoder.get_usize()?;
+                    let mut registers = Registers::new(decoder.get_u32()?);
+                    for specifier in Specifier::iter_all() {
+                        registers.set_x(specifier, decoder.get_u32()?);
+                    }
+                    simulator.step_recorded(WriteRegisters { hart, registers });
+                }
+                WriteAddrs::NAME => {
+                    let hart = decoder.get_usize()?;
+                    let addr = decoder.get_u32()?;
+                    let data = decoder.get_bytes()?.to_vec();
+                    let _ = simulator.step_recorded(WriteAddrs { hart, addr, data });
+                }
+                _ => return Err(SessionError::UnknownTick(name.to_owned())),
+            }
+            match decoder.is_empty() {
+                true => Ok(()),
+                false => Err(SessionError::Malformed),
+            }
+        },
+    )?;
+
+    let output_buffer = output_buffer.ok_or(SessionError::Malformed)?;
+    Ok((description, simulator, output_buffer))
+}
+
+/// Allocates the [`OutputBuffer`].
+pub struct AddOutputBuffer;
+
+impl RecordedTick<BoardSA> for AddOutputBuffer {
+    type Output = OutputBuffer;
+
+    const NAME: &'static str = "adding output buffer";
+
+    fn encode(&self, _encoder: &mut Encoder) {}
+
+    fn tick(&self, allocator: &mut SimulationAllocator, _board: &BoardSA) -> OutputBuffer {
+        let output_buffer = allocator.insert_array(0, 1024);
+        let output_buffer_len = allocator.insert(0);
+        (output_buffer, output_buffer_len)
+    }
+}
+
+/// Pushes `input` into the first UART, and appends its output and the console output of the HTIF
+/// and semihosting to the [`OutputBuffer`].
+pub struct ComWithUart {
+    pub input: Vec<u8>,
+    pub output_buffer: OutputBuffer,
+}
+
+impl RecordedTick<BoardSA> for ComWithUart {
+    type Output = ();
+
+    const NAME: &'static str = "com with uart";
+
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.put_bytes(&self.input);
+    }
+
+    fn tick(&self, allocator: &mut SimulationAllocator, board: &BoardSA) {
+        let (output_buffer, output_buffer_len) = self.output_buffer;
+        let mut output = match board.uart(0) {
+            Some(uart) => uart.push_and_read(allocator, &self.input).1,
+            None => Vec::new(),
+        };
+        if let Some(htif) = board.htif() {
+            output.extend(htif.take_output(allocator));
+        }
+        if let Some(semihosting) = board.semihosting() {
+            output.extend(semihosting.take_output(allocator));
+        }
+
+        let Ok(len) = allocator.get(output_buffer_len) else {
+            return;
+        };
+        let len = *len;
+
+        log::trace!(
+            "Updating output buffer to {}+{}={} bytes",
+            len,
+            output.len(),
+            len + output.len()
+        );
+        let _ = allocator
+            .get_array_mut(output_buffer)
+            .unwrap()
+            .write(len, &output);
+
+        *allocator.get_mut(output_buffer_len).unwrap() += output.len();
+    }
+}
+
+/// Reads a CSR, which may have side effects.
+pub struct ReadCsr {
+    pub hart: usize,
+    pub specifier: CsrSpecifier,
+}
+
+impl RecordedTick<BoardSA> for ReadCsr {
+    type Output = CsrReadResult;
+
+    const NAME: &'static str = "inspect csr";
+
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.put_usize(self.hart);
+        encoder.put_u16(self.specifier);
+    }
+
+    fn tick(&self, allocator: &mut SimulationAllocator, board: &BoardSA) -> CsrReadResult {
+        let core = board.core(self.hart);
+        core.read_csr(allocator, self.specifier, core.privilege_mode(allocator))
+    }
+}
+
+/// Writes a single register from its little-endian bytes.
+pub struct WriteRegister {
+    pub hart: usize,
+    /// One of [`RiscvRegId::Gpr`], [`RiscvRegId::Fpr`], [`RiscvRegId::Pc`], [`RiscvRegId::Csr`] or
+    /// [`RiscvRegId::Priv`].
+    pub reg_id: RiscvRegId<u32>,
+    pub value: Vec<u8>,
+}
+
+impl RecordedTick<BoardSA> for WriteRegister {
+    type Output = Result<(), TargetError<()>>;
+
+    const NAME: &'static str = "gdb write single register";
+
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.put_usize(self.hart);
+        match self.reg_id {
+            RiscvRegId::Gpr(i) => {
+                encoder.put_u8(0);
+                encoder.put_u8(i);
+            }
+            RiscvRegId::Fpr(i) => {
+                encoder.put_u8(1);
+                encoder.put_u8(i);
+            }
+            RiscvRegId::Pc => encoder.put_u8(2),
+            RiscvRegId::Csr(specifier) => {
+                encoder.put_u8(3);
+                encoder.put_u16(specifier);
+            }
+            RiscvRegId::Priv => encoder.put_u8(4),
+            _ => unreachable!("unsupported register"),
+        }
+        encoder.put_bytes(&self.value);
+    }
+
+    fn tick(
+        &self,
+        allocator: &mut SimulationAllocator,
+        board: &BoardSA,
+    ) -> Result<(), TargetError<()>> {
+        use std::io::Write;
+
+        let core = board.core(self.hart);
+        match self.reg_id {
+            RiscvRegId::Gpr(i) => {
+                let mut buf = [0u8; 4];
+                buf.as_mut_slice().write_all(&self.value)?;
+                let registers = core.registers_mut(allocator);
+                registers.set_x(Specifier::new(i).unwrap(), u32::from_le_bytes(buf));
+                Ok(())
+            }
+            RiscvRegId::Fpr(i) => {
+                let mut buf = [0u8; 8];
+                buf.as_mut_slice().write_all(&self.value)?;
+                let float_registers = core.float_registers_mut(allocator);
+                float_registers.set_f(Specifier::new(i).unwrap(), u64::from_le_bytes(buf));
+                Ok(())
+            }
+            RiscvRegId::Pc => {
+                let mut buf = [0u8; 4];
+                buf.as_mut_slice().write_all(&self.value)?;
+                let registers = core.registers_mut(allocator);
+                *registers.pc_mut() = u32::from_le_bytes(buf);
+                Ok(())
+            }
+            RiscvRegId::Csr(specifier) => {
+                let mut buf = [0u8; 4];
+                buf.as_mut_slice().write_all(&self.value)?;
+                core.write_csr(
+                    allocator,
+                    specifier,
+                    core.privilege_mode(allocator),
+                    u32::from_le_bytes(buf),
+                    0xFFFF_FFFF,
+                )
+                .map_err(|_| TargetError::NonFatal)
+            }
+            RiscvRegId::Priv => todo!(),
+            _ => Err(TargetError::NonFatal),
+        }
+    }
+}
+
+/// Overwrites the `x` registers and `pc` of a hart.
+pub struct WriteRegisters {
+    pub hart: usize,
+    pub registers: Registers,
+}
+
+impl RecordedTick<BoardSA> for WriteRegisters {
+    type Output = ();
+
+    const NAME: &'static str = "write all registers";
+
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.put_usize(self.hart);
+        encoder.put_u32(self.registers.pc());
+        for specifier in Specifier::iter_all() {
+            encoder.put_u32(self.registers.x(specifier));
+        }
+    }
+
+    fn tick(&self, allocator: &mut SimulationAllocator, board: &BoardSA) {
+        *board.core(self.hart).registers_mut(allocator) = self.registers.clone();
+    }
+}
+
+/// Writes memory as seen by a hart.
+pub struct WriteAddrs {
+    pub hart: usize,
+    pub addr: u32,
+    pub data: Vec<u8>,
+}
+
+impl RecordedTick<BoardSA> for WriteAddrs {
+    type Output = Result<(), MemoryError>;
+
+    const NAME: &'static str = "write data";
+
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.put_usize(self.hart);
+        encoder.put_u32(self.addr);
+        encoder.put_bytes(&self.data);
+    }
+
+    fn tick(
+        &self,
+        allocator: &mut SimulationAllocator,
+        board: &BoardSA,
+    ) -> Result<(), MemoryError> {
+        let memory = board.core(self.hart).mmu();
+        memory.write_range(allocator, self.addr, &self.data)
+    }
+}
@@ -0,0 +1,67 @@
+//! Symbols of the ELF file being run, used to annotate addresses.
+
+use goblin::elf::{
+    sym::{STT_FUNC, STT_NOTYPE, STT_OBJECT},
+    Elf,
+};
+
+/// Named addresses, looked up like objdump does: an address belongs to the closest symbol at or
+/// before it, regardless of the size of that symbol.
+#[derive(Debug, Default)]
+pub struct Symbols {
+    /// Sorted by address.
+    symbols: Vec<(u32, String)>,
+}
+
+impl Symbols {
+    /// Collects the defined function, object and untyped symbols of an ELF file. An ELF file
+    /// without a symbol table, or one that cannot be parsed, has no symbols.
+    pub fn from_elf(program_elf: &[u8]) -> Self {
+        let Ok(elf) = Elf::parse(program_elf) else {
+            return Self::default();
+        };
+        let mut symbols: Vec<_> = elf
+            .syms
+            .iter()
+            .filter(|sym| {
+                matches!(sym.st_type(), STT_FUNC | STT_OBJECT | STT_NOTYPE) && sym.st_shndx != 0
+            })
+            .filter_map(|sym| {
+                let name = elf.strtab.get_at(sym.st_name)?;
+                // Skip mapping symbols (`$x`, `$d`) and local labels.
+                if name.is_empty() || name.starts_with('$') || name.starts_with(".L") {
+                    return None;
+                }
+                Some((sym.st_value as u32, name.to_owned()))
+            })
+            .collect();
+        symbols.sort();
+        symbols.dedup_by_key(|(address, _)| *address);
+        Self { symbols }
+    }
+
+    /// Returns the name of the symbol `address` belongs to, together with the offset of `address`
+    /// from the start of that symbol.
+    pub fn lookup(&self, address: u32) -> Option<(&str, u32)> {
+        let index = self
+            .symbols
+            .partition_point(|(start, _)| *start <= address)
+            .checked_sub(1)?;
+        let (start, name) = &self.symbols[index];
+        Some((name, address - start))
+    }
+
+    /// Returns the name of the symbol that starts exactly at `address`, if any.
+    pub fn starting_at(&self, address: u32) -> Option<&str> {
+        self.lookup(address)
+            .and_then(|(name, offset)| (offset == 0).then_some(name))
+    }
+
+    /// Formats `address` relative to its symbol like objdump does, e.g. `<main+0x8>`.
+    pub fn annotate(&self, address: u32) -> Option<String> {
+        self.lookup(address).map(|(name, offset)| match offset {
+            0 => format!("<{name}>"),
+            offset => format!("<{name}+{offset:#x}>"),
+        })
+    }
+}
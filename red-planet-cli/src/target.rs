@@ -5,25 +5,48 @@ use std::collections::HashSet;
 use command::Command;
 use gdbstub::target::TargetError;
 use gdbstub_arch::riscv::reg::id::RiscvRegId;
-use log::{error, info, trace};
+use log::{debug, error, info, trace};
 use red_planet_core::{
-    board::Board,
+    board::{Board, ExitStatus},
+    core::watchpoint::{WatchKind, Watchpoint, WatchpointHit},
+    instruction::{instruction_length, Instruction},
     registers::Specifier,
-    simulator::{SimulationAllocator, UndoStepStopReason},
-    Allocator, ArrayAccessor, ArrayAccessorMut,
+    simulator::{
+        branch::{BranchId, BranchInfo},
+        diff::DiffContext,
+        SimulationAllocator, StepStop, UndoStepStopReason,
+    },
+    AddressRange, Allocator, ArrayAccessor,
 };
 use tokio::sync::{
     mpsc::{error::TryRecvError, unbounded_channel, UnboundedReceiver, UnboundedSender},
     watch,
 };
 
+use crate::session::{
+    self, ComWithUart, Description, OutputBuffer, ReadCsr, WriteAddrs, WriteRegister,
+    WriteRegisters,
+};
+use crate::symbols::Symbols;
 use crate::Simulator;
 
+/// Number of instructions before the `pc` that are disassembled, if they can be found.
+const DISASSEMBLY_BEFORE: u32 = 8;
+/// Total number of instructions that are disassembled.
+const DISASSEMBLY_LEN: usize = 48;
+
 #[derive(Debug, Clone)]
 pub enum Event {
     DoneStep,
-    PoweredDown,
-    Break,
+    /// The guest powered down the board with the given status.
+    PoweredDown(ExitStatus),
+    /// The given hart is about to execute an instruction at a breakpoint.
+    Break(usize),
+    /// The given hart accessed watched memory. When running forward, the access was made by the
+    /// last executed instruction; when running backwards, by the next one.
+    Watch(usize, WatchpointHit),
+    /// The maximum number of steps has been reached.
+    StepLimit,
     ReachedStart,
     Pause,
 }
@@ -36,9 +59,9 @@ pub enum AdvanceResult {
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ExecutionType {
-    Step,
-    StepBack,
-    RangeStep(u32, u32),
+    Step(usize),
+    StepBack(usize),
+    RangeStep(usize, u32, u32),
     Continue,
     ReverseContinue,
 }
@@ -49,6 +72,48 @@ pub struct SharedTargetState {
     pub total_steps: usize,
     pub current_step: usize,
     pub state: Option<ExecutionType>,
+    /// `true` if all harts are stalled by WFI, so time is being skipped.
+    pub sleeping: bool,
+    /// Name of the active branch, or its id if it has no name.
+    pub branch: String,
+    /// `pc` of the scheduled hart.
+    pub pc: u32,
+    /// Instructions around `pc`, only filled in while execution is stopped.
+    pub disassembly: Vec<DisassemblyLine>,
+}
+
+/// A single disassembled instruction.
+#[derive(Debug, Clone)]
+pub struct DisassemblyLine {
+    pub address: u32,
+    /// Name of the symbol starting at `address`, if any.
+    pub symbol: Option<String>,
+    /// The instruction in the syntax of objdump, including the symbol of its jump target.
+    pub text: String,
+}
+
+/// Describes a branch on a single line, for listing branches to the user.
+pub fn format_branch(info: &BranchInfo) -> String {
+    let mut line = format!("{} {}", if info.active { "*" } else { " " }, info.id);
+    if let Some(name) = &info.name {
+        line += &format!(" ({name})");
+    }
+    line += &format!(": {} steps", info.steps);
+    if let Some((parent, steps)) = info.fork {
+        line += &format!(", forked from {parent} at step {steps}");
+    }
+    line
+}
+
+/// Finds the branch with the given name, or the given id if no branch has that name.
+fn find_branch(simulator: &Simulator, branch: &str) -> Result<BranchId, String> {
+    let branches: Vec<_> = simulator.branches().collect();
+    branches
+        .iter()
+        .find(|info| info.name.as_deref() == Some(branch))
+        .or_else(|| branches.iter().find(|info| info.id.to_string() == branch))
+        .map(|info| info.id)
+        .ok_or_else(|| format!("No branch named {branch}"))
 }
 
 pub struct SimTarget {
@@ -61,21 +126,82 @@ pub struct SimTarget {
     state: TargetState,
 
     shared_state: watch::Sender<SharedTargetState>,
+
+    /// How the simulated board was constructed, stored in saved sessions.
+    description: Description,
+
+    /// Running forward stops once history has this many steps.
+    max_steps: Option<usize>,
 }
 
 #[derive(Debug, Default)]
 struct BreakReasons {
     breakpoints: HashSet<u32>,
+    /// Watchpoints added to every hart of the board.
+    watchpoints: Vec<Watchpoint>,
 }
 
 impl BreakReasons {
+    /// Returns the hart that is about to execute an instruction at a breakpoint, if any.
+    ///
+    /// Only the scheduled hart is checked, since other harts do not execute the next instruction.
     fn should_break(
         &self,
         allocator: &SimulationAllocator,
         board: &Board<SimulationAllocator>,
-    ) -> bool {
+    ) -> Option<usize> {
+        let hart = board.scheduled_hart(allocator);
         self.breakpoints
-            .contains(&board.core().registers(allocator).pc())
+            .contains(&board.core(hart).registers(allocator).pc())
+            .then_some(hart)
+    }
+
+    /// Returns the first watchpoint hit since the last call, together with the hart that hit it.
+    /// The hits of all other harts are discarded.
+    fn take_watchpoint_hit(
+        &self,
+        board: &Board<SimulationAllocator>,
+    ) -> Option<(usize, WatchpointHit)> {
+        if self.watchpoints.is_empty() {
+            return None;
+        }
+        (0..board.hart_count())
+            .filter_map(|hart| Some((hart, board.core(hart).take_watchpoint_hit()?)))
+            .reduce(|first, _| first)
+    }
+
+    /// Returns `true` if stretches of history in which watched memory was not written can be
+    /// skipped when running backwards, since nothing can stop execution there.
+    ///
+    /// Watchpoints are on virtual addresses, so this requires that no hart ever enabled paging in
+    /// the history leading up to the current state. Privilege changes do not matter then, since
+    /// addresses are never translated.
+    fn can_skip_unwritten(
+        &self,
+        allocator: &SimulationAllocator,
+        board: &Board<SimulationAllocator>,
+    ) -> bool {
+        self.breakpoints.is_empty()
+            && self
+                .watchpoints
+                .iter()
+                .all(|watchpoint| watchpoint.kind == WatchKind::Write)
+            && (0..board.hart_count()).all(|hart| !board.core(hart).paging_ever_enabled(allocator))
+    }
+
+    /// Returns `true` if any watched memory may have been written in between the states compared
+    /// by `context`.
+    fn may_have_written(&self, board: &Board<SimulationAllocator>, context: &DiffContext) -> bool {
+        self.watchpoints.iter().any(|watchpoint| {
+            let end = watchpoint
+                .address
+                .wrapping_add(watchpoint.len.saturating_sub(1));
+            match AddressRange::new(watchpoint.address, end) {
+                Ok(range) => board.may_have_written(context, range),
+                // Wraps around the address space.
+                Err(_) => true,
+            }
+        })
     }
 }
 
@@ -84,6 +210,8 @@ struct TargetState {
     output_buffer_len: <SimulationAllocator as Allocator>::Id<usize>,
 
     execution_type: Option<ExecutionType>,
+
+    symbols: Symbols,
 }
 
 impl TargetState {
@@ -115,32 +243,124 @@ impl TargetState {
             shared_state.current_step = simulator.current_steps();
 
             shared_state.state = self.execution_type;
+
+            let (allocator, board) = simulator.inspect();
+            shared_state.sleeping = board.is_sleeping(allocator);
+
+            let hart = board.scheduled_hart(allocator);
+            shared_state.pc = board.core(hart).registers(allocator).pc();
+            shared_state.disassembly = match self.execution_type {
+                None => self.disassemble(allocator, board, hart, shared_state.pc),
+                // Would only slow down execution, since it is outdated right away.
+                Some(_) => Vec::new(),
+            };
+
+            let branch = simulator.active_branch();
+            shared_state.branch = simulator
+                .branches()
+                .find(|info| info.id == branch)
+                .and_then(|info| info.name)
+                .unwrap_or_else(|| branch.to_string());
         })
     }
+
+    /// Disassemble the instructions around `pc`, as seen by `hart`.
+    fn disassemble(
+        &self,
+        allocator: &SimulationAllocator,
+        board: &Board<SimulationAllocator>,
+        hart: usize,
+        pc: u32,
+    ) -> Vec<DisassemblyLine> {
+        let mmu = board.core(hart).mmu();
+        // Instructions are always little endian.
+        let read_parcel = |address: u32| {
+            let mut parcel = [0; 2];
+            mmu.read_range_debug(&mut parcel, allocator, address)
+                .ok()
+                .map(|()| u16::from_le_bytes(parcel) as u32)
+        };
+        let read_instruction = |address: u32| {
+            let low = read_parcel(address)?;
+            match instruction_length(low) {
+                2 => Some(low),
+                _ => Some(low | read_parcel(address.checked_add(2)?)? << 16),
+            }
+        };
+
+        // Instructions can be 2 or 4 bytes long, so look for the furthest address before `pc` from
+        // which decoding ends up at `pc` exactly. Stay within the function of `pc`, if it is known.
+        let mut lowest = pc.saturating_sub(4 * DISASSEMBLY_BEFORE) & !1;
+        if let Some((_, offset)) = self.symbols.lookup(pc) {
+            lowest = lowest.max(pc - offset);
+        }
+        let start = (lowest..pc)
+            .step_by(2)
+            .find(|&start| {
+                let mut address = start;
+                while address < pc {
+                    match read_instruction(address) {
+                        Some(raw) => address += instruction_length(raw),
+                        None => return false,
+                    }
+                }
+                address == pc
+            })
+            .unwrap_or(pc);
+
+        let mut lines = Vec::with_capacity(DISASSEMBLY_LEN);
+        let mut address = Some(start);
+        while let Some(current) = address.filter(|_| lines.len() < DISASSEMBLY_LEN) {
+            let Some(raw) = read_instruction(current) else {
+                break;
+            };
+            let text = match Instruction::decode(raw) {
+                Ok(instruction) => {
+                    let mut text = instruction.disassemble(current).to_string();
+                    let target = instruction.jump_target(current);
+                    if let Some(symbol) = target.and_then(|target| self.symbols.annotate(target)) {
+                        text += &format!(" {symbol}");
+                    }
+                    text
+                }
+                Err(_) if instruction_length(raw) == 2 => format!(".2byte\t{raw:#06x}"),
+                Err(_) => format!(".4byte\t{raw:#010x}"),
+            };
+            lines.push(DisassemblyLine {
+                address: current,
+                symbol: self.symbols.starting_at(current).map(str::to_owned),
+                text,
+            });
+            address = current.checked_add(instruction_length(raw));
+        }
+        lines
+    }
 }
 
 impl SimTarget {
     pub fn new(
-        simulator: &mut Simulator,
+        description: Description,
+        (output_buffer, output_buffer_len): OutputBuffer,
         shared_state: watch::Sender<SharedTargetState>,
         uart_channel: UnboundedReceiver<u8>,
     ) -> (Self, UnboundedSender<Command>, UnboundedReceiver<Event>) {
-        let (output_buffer, output_buffer_len) =
-            simulator.step_with("adding output buffer", |allocator, _| {
-                let output_buffer = allocator.insert_array(0, 1024);
-                let output_buffer_len = allocator.insert(0);
-                (output_buffer, output_buffer_len)
-            });
-
         let (c_sender, c_receiver) = unbounded_channel();
         let (e_sender, e_receiver) = unbounded_channel();
 
+        let symbols = match description.elf {
+            true => Symbols::from_elf(&description.binary),
+            false => Symbols::default(),
+        };
+
         let target = Self {
             command_channel: c_receiver,
             event_channel: e_sender,
             uart_channel,
 
             shared_state,
+            description,
+
+            max_steps: None,
 
             break_reasons: BreakReasons::default(),
 
@@ -149,16 +369,36 @@ impl SimTarget {
                 output_buffer_len,
 
                 execution_type: None,
+
+                symbols,
             },
         };
         (target, c_sender, e_receiver)
     }
 
+    /// Limits the number of steps when running forward, see [`Event::StepLimit`].
+    pub fn set_max_steps(&mut self, max_steps: Option<usize>) {
+        self.max_steps = max_steps;
+    }
+
     fn com_with_uart(&mut self, simulator: &mut Simulator) {
         let (allocator, board) = simulator.inspect();
-
-        let pending_output_amount = board.uart0().pending_output_amount(allocator);
-        let input_space = board.uart0().input_space(allocator);
+        // The terminal is connected to the first UART, if the board has one, and to the HTIF and
+        // semihosting console.
+        let (uart_output_amount, input_space) = match board.uart(0) {
+            Some(uart) => (
+                uart.pending_output_amount(allocator),
+                uart.input_space(allocator),
+            ),
+            None => (0, 0),
+        };
+        let pending_output_amount = uart_output_amount
+            + board
+                .htif()
+                .map_or(0, |htif| htif.pending_output(allocator).len())
+            + board
+                .semihosting()
+                .map_or(0, |semihosting| semihosting.pending_output(allocator).len());
 
         let input_buf = if input_space != 0 {
             let mut input_buf = Vec::new();
@@ -174,46 +414,50 @@ impl SimTarget {
         };
 
         if !input_buf.is_empty() || pending_output_amount != 0 {
-            let output_buffer = self.state.output_buffer;
-            let output_buffer_len = self.state.output_buffer_len;
-
-            simulator.step_with("com with uart", move |allocator, board| {
-                let output = board.uart0().push_and_read(allocator, &input_buf).1;
-
-                let Ok(len) = allocator.get(output_buffer_len) else {
-                    return;
-                };
-                let len = *len;
-
-                trace!(
-                    "Updating output buffer to {}+{}={} bytes",
-                    len,
-                    output.len(),
-                    len + output.len()
-                );
-                let _ = allocator
-                    .get_array_mut(output_buffer)
-                    .unwrap()
-                    .write(len, &output);
-
-                *allocator.get_mut(output_buffer_len).unwrap() += output.len();
+            simulator.step_recorded(ComWithUart {
+                input: input_buf,
+                output_buffer: (self.state.output_buffer, self.state.output_buffer_len),
             });
         }
     }
 
     fn step(&mut self, simulator: &mut Simulator) -> Option<Event> {
-        if !simulator.redo_step() {
+        if self
+            .max_steps
+            .is_some_and(|max_steps| simulator.current_steps() >= max_steps)
+        {
+            return Some(Event::StepLimit);
+        }
+
+        // Discard hits of steps that were replayed for other reasons.
+        self.break_reasons
+            .take_watchpoint_hit(simulator.simulatable());
+
+        // Restoring a snapshot would not reveal which memory the step accessed.
+        let redone = match self.break_reasons.watchpoints.is_empty() {
+            true => simulator.redo_step(),
+            false => simulator.redo_step_by_replay(),
+        };
+        if !redone {
             self.com_with_uart(simulator);
             simulator.step();
         }
 
         let (allocator, board) = simulator.inspect();
 
-        if self.break_reasons.should_break(allocator, board) {
-            return Some(Event::Break);
+        if let Some((hart, hit)) = self.break_reasons.take_watchpoint_hit(board) {
+            return Some(Event::Watch(hart, hit));
         }
-        if board.is_powered_down(allocator) {
-            return Some(Event::PoweredDown);
+        if let Some(hart) = self.break_reasons.should_break(allocator, board) {
+            return Some(Event::Break(hart));
+        }
+        if let Some(status) = board.exit_status(allocator) {
+            // Collect what was written to the UART right before powering down. When redoing, this
+            // is the next step in history instead.
+            if !redone {
+                self.com_with_uart(simulator);
+            }
+            return Some(Event::PoweredDown(status));
         }
         None
     }
@@ -224,11 +468,11 @@ impl SimTarget {
         }
 
         let (allocator, board) = simulator.inspect();
-        if self.break_reasons.should_break(allocator, board) {
-            return Some(Event::Break);
+        if let Some(hart) = self.break_reasons.should_break(allocator, board) {
+            return Some(Event::Break(hart));
         }
-        if board.is_powered_down(allocator) {
-            return Some(Event::PoweredDown);
+        if let Some(status) = board.exit_status(allocator) {
+            return Some(Event::PoweredDown(status));
         }
         None
     }
@@ -239,11 +483,34 @@ impl SimTarget {
         simulator: &mut Simulator,
     ) -> AdvanceResult {
         match execution_type {
-            ExecutionType::Step => {
-                AdvanceResult::Event(self.step(simulator).unwrap_or(Event::DoneStep))
+            ExecutionType::Step(hart) => {
+                for _ in 0..1024 {
+                    let (allocator, board) = simulator.inspect();
+                    let stepped_hart = board.scheduled_hart(allocator);
+
+                    if let Some(event) = self.step(simulator) {
+                        return AdvanceResult::Event(event);
+                    };
+
+                    if stepped_hart == hart {
+                        return AdvanceResult::Event(Event::DoneStep);
+                    }
+                }
+                AdvanceResult::Continue
             }
-            ExecutionType::StepBack => {
-                AdvanceResult::Event(self.step_back(simulator).unwrap_or(Event::DoneStep))
+            ExecutionType::StepBack(hart) => {
+                for _ in 0..1024 {
+                    if let Some(event) = self.step_back(simulator) {
+                        return AdvanceResult::Event(event);
+                    };
+
+                    // After undoing a step, the scheduled hart is the one that executed it.
+                    let (allocator, board) = simulator.inspect();
+                    if board.scheduled_hart(allocator) == hart {
+                        return AdvanceResult::Event(Event::DoneStep);
+                    }
+                }
+                AdvanceResult::Continue
             }
             ExecutionType::Continue => {
                 for _ in 0..1024 {
@@ -253,7 +520,7 @@ impl SimTarget {
                 }
                 AdvanceResult::Continue
             }
-            ExecutionType::RangeStep(start, end) => {
+            ExecutionType::RangeStep(hart, start, end) => {
                 for _ in 0..1024 {
                     if let Some(event) = self.step(simulator) {
                         return AdvanceResult::Event(event);
@@ -261,12 +528,56 @@ impl SimTarget {
 
                     let (allocator, board) = simulator.inspect();
 
-                    if !(start..end).contains(&board.core().registers(allocator).pc()) {
+                    if !(start..end).contains(&board.core(hart).registers(allocator).pc()) {
                         return AdvanceResult::Event(Event::DoneStep);
                     }
                 }
                 AdvanceResult::Continue
             }
+            ExecutionType::ReverseContinue if !self.break_reasons.watchpoints.is_empty() => {
+                let mut has_notified_about_command_channel = false;
+
+                let (allocator, board) = simulator.inspect();
+                self.break_reasons.take_watchpoint_hit(board);
+                let skip_unwritten = self.break_reasons.can_skip_unwritten(allocator, board);
+
+                // Watchpoints are hit by steps, so the steps are inspected rather than the states.
+                let result = simulator.undo_steps_until_step(
+                    |board, context| {
+                        skip_unwritten && !self.break_reasons.may_have_written(board, context)
+                    },
+                    |allocator, board| {
+                        let hit = self.break_reasons.take_watchpoint_hit(board);
+                        if let Some(hart) = self.break_reasons.should_break(allocator, board) {
+                            let event = AdvanceResult::Event(Event::Break(hart));
+                            return Some((StepStop::After, event));
+                        }
+                        if let Some((hart, hit)) = hit {
+                            let event = AdvanceResult::Event(Event::Watch(hart, hit));
+                            return Some((StepStop::Before, event));
+                        }
+
+                        if !self.command_channel.is_empty() && !has_notified_about_command_channel {
+                            has_notified_about_command_channel = true;
+                            return Some((StepStop::After, AdvanceResult::Continue));
+                        }
+
+                        None
+                    },
+                    |simulator| {
+                        self.state
+                            .write_to_shared_state(simulator, &self.shared_state);
+                    },
+                );
+                // Going to the resulting state may have replayed steps again.
+                self.break_reasons
+                    .take_watchpoint_hit(simulator.simulatable());
+
+                match result {
+                    UndoStepStopReason::ReachedStart => AdvanceResult::Event(Event::ReachedStart),
+                    UndoStepStopReason::Pred(result) => result,
+                }
+            }
             ExecutionType::ReverseContinue => {
                 // Using this var to only send a single true about the command channel, this way
                 // we can go as far back as possible if a command where to accrue.
@@ -274,8 +585,8 @@ impl SimTarget {
 
                 let result = simulator.undo_steps_until(
                     |allocator, board| {
-                        if self.break_reasons.should_break(allocator, board) {
-                            return Some(AdvanceResult::Event(Event::Break));
+                        if let Some(hart) = self.break_reasons.should_break(allocator, board) {
+                            return Some(AdvanceResult::Event(Event::Break(hart)));
                         }
 
                         if !self.command_channel.is_empty() && !has_notified_about_command_channel {
@@ -299,78 +610,70 @@ impl SimTarget {
         }
     }
 
-    fn read_register(&self, reg_id: RiscvRegId<u32>, simulator: &mut Simulator) -> Option<u32> {
+    fn read_register(
+        &self,
+        hart: usize,
+        reg_id: RiscvRegId<u32>,
+        simulator: &mut Simulator,
+    ) -> Option<Vec<u8>> {
         let (allocator, board) = simulator.inspect();
 
         match reg_id {
             RiscvRegId::Gpr(i) => {
-                let registers = board.core().registers(allocator);
-                Some(registers.x(Specifier::new(i).unwrap()))
+                let registers = board.core(hart).registers(allocator);
+                Some(registers.x(Specifier::new(i)?).to_le_bytes().to_vec())
+            }
+            RiscvRegId::Fpr(i) => {
+                let float_registers = board.core(hart).float_registers(allocator);
+                Some(float_registers.f(Specifier::new(i)?).to_le_bytes().to_vec())
             }
-            RiscvRegId::Fpr(_) => None,
-            RiscvRegId::Pc => Some(board.core().registers(allocator).pc()),
+            RiscvRegId::Pc => Some(
+                board
+                    .core(hart)
+                    .registers(allocator)
+                    .pc()
+                    .to_le_bytes()
+                    .to_vec(),
+            ),
             RiscvRegId::Csr(specifier) => simulator
-                .step_with("inspect csr", move |allocator, board| {
-                    board.core().read_csr(
-                        allocator,
-                        specifier,
-                        board.core().privilege_mode(allocator),
-                    )
-                })
-                .ok(),
-            RiscvRegId::Priv => Some(board.core().privilege_mode(allocator) as u8 as u32),
+                .step_recorded(ReadCsr { hart, specifier })
+                .ok()
+                .map(|value| value.to_le_bytes().to_vec()),
+            RiscvRegId::Priv => Some(vec![board.core(hart).privilege_mode(allocator) as u8]),
             _ => None,
         }
     }
 
     fn write_register(
         &self,
+        hart: usize,
         reg_id: RiscvRegId<u32>,
         val: Vec<u8>,
         simulator: &mut Simulator,
     ) -> Result<(), TargetError<()>> {
-        use std::io::Write;
-
-        simulator.step_with(
-            "gdb write single register",
-            move |allocator, board| match reg_id {
-                RiscvRegId::Gpr(i) => {
-                    let mut buf = [0u8; 4];
-                    buf.as_mut_slice().write_all(&val)?;
-                    let registers = board.core().registers_mut(allocator);
-                    registers.set_x(Specifier::new(i).unwrap(), u32::from_le_bytes(buf));
-                    Ok(())
-                }
-                RiscvRegId::Fpr(_) => todo!(),
-                RiscvRegId::Pc => {
-                    let mut buf = [0u8; 4];
-                    buf.as_mut_slice().write_all(&val)?;
-                    let registers = board.core().registers_mut(allocator);
-                    *registers.pc_mut() = u32::from_le_bytes(buf);
-                    Ok(())
-                }
-                RiscvRegId::Csr(specifier) => {
-                    let mut buf = [0u8; 4];
-                    buf.as_mut_slice().write_all(&val)?;
-                    board
-                        .core()
-                        .write_csr(
-                            allocator,
-                            specifier,
-                            board.core().privilege_mode(allocator),
-                            u32::from_le_bytes(buf),
-                            0xFFFF_FFFF,
-                        )
-                        .map_err(|_| TargetError::NonFatal)
-                }
-                RiscvRegId::Priv => todo!(),
-                _ => Err(TargetError::NonFatal),
-            },
-        )
+        match reg_id {
+            RiscvRegId::Gpr(_)
+            | RiscvRegId::Fpr(_)
+            | RiscvRegId::Pc
+            | RiscvRegId::Csr(_)
+            | RiscvRegId::Priv => simulator.step_recorded(WriteRegister {
+                hart,
+                reg_id,
+                value: val,
+            }),
+            _ => Err(TargetError::NonFatal),
+        }
     }
 
     pub fn execute_command(&mut self, command: Command, simulator: &mut Simulator) -> bool {
         trace!("Got command: {}", &command);
+        if let Some(hart) = command.hart() {
+            if simulator.simulatable().get_core(hart).is_none() {
+                // Dropping the command closes its return channel, if any.
+                error!("Hart {hart} does not exist");
+                return false;
+            }
+        }
         match command {
             Command::Exit => return true,
             Command::Pause => {
@@ -381,55 +684,78 @@ impl SimTarget {
             Command::ReverseContinue => {
                 self.state.execution_type = Some(ExecutionType::ReverseContinue)
             }
-            Command::Step => self.state.execution_type = Some(ExecutionType::Step),
-            Command::StepBack => self.state.execution_type = Some(ExecutionType::StepBack),
-            Command::RangeStep(s, e) => {
-                self.state.execution_type = Some(ExecutionType::RangeStep(s, e))
+            Command::Step(hart) => self.state.execution_type = Some(ExecutionType::Step(hart)),
+            Command::StepBack(hart) => {
+                self.state.execution_type = Some(ExecutionType::StepBack(hart))
+            }
+            Command::RangeStep(hart, s, e) => {
+                self.state.execution_type = Some(ExecutionType::RangeStep(hart, s, e))
             }
             Command::AddBreakpoint(addr) => {
                 self.break_reasons.breakpoints.insert(addr);
             }
+            Command::AddWatchpoint(watchpoint) => {
+                let board = simulator.simulatable();
+                for hart in 0..board.hart_count() {
+                    board.core(hart).add_watchpoint(watchpoint);
+                }
+                self.break_reasons.watchpoints.push(watchpoint);
+            }
+            Command::RemoveWatchpoint(watchpoint) => {
+                let board = simulator.simulatable();
+                for hart in 0..board.hart_count() {
+                    board.core(hart).remove_watchpoint(watchpoint);
+                }
+                let watchpoints = &mut self.break_reasons.watchpoints;
+                if let Some(index) = watchpoints.iter().position(|w| *w == watchpoint) {
+                    watchpoints.remove(index);
+                }
+            }
             Command::RemoveBreakpoint(addr) => {
                 self.break_reasons.breakpoints.remove(&addr);
             }
-            Command::ReadRegisters(return_channel) => {
+            Command::ReadRegisters(hart, return_channel) => {
                 let (allocator, board) = simulator.inspect();
 
-                let registers = board.core().registers(allocator);
+                let registers = board.core(hart).registers(allocator);
 
                 let _ = return_channel.send(registers.clone());
             }
-            Command::WriteRegisters(registers) => {
-                simulator.step_with("write all registers", move |allocator, board| {
-                    *board.core().registers_mut(allocator) = registers.clone();
-                })
+            Command::WriteRegisters(hart, registers) => {
+                simulator.step_recorded(WriteRegisters { hart, registers })
             }
-            Command::ReadRegister(reg_id, return_channel) => {
-                if let Some(value) = self.read_register(reg_id, simulator) {
+            Command::ReadRegister(hart, reg_id, return_channel) => {
+                if let Some(value) = self.read_register(hart, reg_id, simulator) {
                     let _ = return_channel.send(value);
                 }
             }
-            Command::WriteRegister(reg_id, val, return_channel) => {
-                let _ = return_channel
-                    .send(self.write_register(reg_id, val, simulator).map_err(|_| ()));
+            Command::WriteRegister(hart, reg_id, val, return_channel) => {
+                let _ = return_channel.send(
+                    self.write_register(hart, reg_id, val, simulator)
+                        .map_err(|_| ()),
+                );
             }
-            Command::ReadAddrs(addr, len, return_channel) => {
+            Command::ReadAddrs(hart, addr, len, return_channel) => {
                 let (allocator, board) = simulator.inspect();
 
-                let memory = board.core().mmu();
+                let memory = board.core(hart).mmu();
 
                 let mut data = vec![0; len];
                 let result = match memory.read_range_debug(&mut data, allocator, addr) {
                     Ok(()) => Ok(data),
-                    Err(_) => Err(TargetError::NonFatal),
+                    Err(e) => {
+                        debug!("Failed to read {len} bytes at {addr:#010x}: {e}");
+                        Err(TargetError::NonFatal)
+                    }
                 };
                 let _ = return_channel.send(result);
             }
-            Command::WriteAddrs(addr, data, return_channel) => {
-                let result = simulator.step_with("write data", move |allocator, board| {
-                    let memory = board.core().mmu();
-                    memory.write_range(allocator, addr, &data)
-                });
+            Command::WriteAddrs(hart, addr, data, return_channel) => {
+                let len = data.len();
+                let result = simulator.step_recorded(WriteAddrs { hart, addr, data });
+                if let Err(e) = &result {
+                    debug!("Failed to write {len} bytes at {addr:#010x}: {e}");
+                }
                 let _ = return_channel.send(result);
             }
             Command::DeleteFuture => {
@@ -440,6 +766,38 @@ impl SimTarget {
                     simulator.go_to(steps);
                 }
             }
+            Command::SaveSession(path) => {
+                match session::save(simulator, &self.description, &path) {
+                    Ok(()) => info!("Saved session to {path}"),
+                    Err(e) => error!("Failed to save session to {path}: {e}"),
+                }
+            }
+            Command::ListBranches(return_channel) => {
+                let _ = return_channel.send(simulator.branches().collect());
+            }
+            Command::SwitchBranch(branch, return_channel) => {
+                let result = find_branch(simulator, &branch).and_then(|id| {
+                    simulator
+                        .switch_branch(id)
+                        .map_err(|e| format!("Failed to switch branch: {e}"))
+                });
+                let _ = return_channel.send(result);
+            }
+            Command::NameBranch(name) => {
+                let branch = simulator.active_branch();
+                simulator.rename_branch(branch, Some(name)).unwrap();
+            }
+            Command::DeleteBranch(branch, return_channel) => {
+                let result = find_branch(simulator, &branch).and_then(|id| {
+                    simulator
+                        .delete_branch(id)
+                        .map_err(|e| format!("Failed to delete branch: {e}"))
+                });
+                let _ = return_channel.send(result);
+            }
+            Command::Diff(from, to, return_channel) => {
+                let _ = return_channel.send(simulator.diff(from, to));
+            }
         }
         false
     }
@@ -488,5 +846,26 @@ impl SimTarget {
                 }
             }
         }
+        Self::write_back(&simulator);
+    }
+
+    /// Writes the files the guest changed through semihosting back to the host, if the guest
+    /// exited in the current state.
+    ///
+    /// This is only done when exiting, since the contents of the files in all other states would no
+    /// longer match those on the host.
+    fn write_back(simulator: &Simulator) {
+        let (allocator, board) = simulator.inspect();
+        let Some(semihosting) = board.semihosting() else {
+            return;
+        };
+        if !board.is_powered_down(allocator) {
+            info!("Not writing back the files of semihosting, since the guest did not exit");
+            return;
+        }
+        match semihosting.write_back(allocator) {
+            Ok(()) => info!("Wrote back the files of semihosting"),
+            Err(e) => error!("Failed to write back the files of semihosting: {e}"),
+        }
     }
 }
@@ -0,0 +1,113 @@
+use gdbstub::target::TargetError;
+use gdbstub_arch::riscv::reg::id::RiscvRegId;
+use red_planet_core::{
+    board::diff::BoardDiff,
+    core::{mmu::MemoryError, watchpoint::Watchpoint},
+    registers::Registers,
+    simulator::branch::BranchInfo,
+};
+
+use crate::gdb::GdbTargetError;
+
+type FailableReturnChannel<T> = oneshot::Sender<Result<T, TargetError<GdbTargetError>>>;
+
+pub enum Command {
+    // Close the program
+    Exit,
+    // Pause execution
+    Pause,
+    Continue,
+    ReverseContinue,
+    /// Run until the given hart executed a single instruction.
+    Step(usize),
+    /// Undo steps until an instruction of the given hart was undone.
+    StepBack(usize),
+    /// Run until the pc of the given hart leaves the given range.
+    RangeStep(usize, u32, u32),
+    RemoveBreakpoint(u32),
+    AddBreakpoint(u32),
+    /// Watch the data accesses of all harts.
+    AddWatchpoint(Watchpoint),
+    RemoveWatchpoint(Watchpoint),
+    // All commands below act on the hart given as their first field.
+    ReadRegisters(usize, oneshot::Sender<Registers>),
+    WriteRegisters(usize, Registers),
+    /// Read a single register, answering with its little-endian bytes.
+    ReadRegister(usize, RiscvRegId<u32>, oneshot::Sender<Vec<u8>>),
+    WriteRegister(
+        usize,
+        RiscvRegId<u32>,
+        Vec<u8>,
+        oneshot::Sender<Result<(), ()>>,
+    ),
+    ReadAddrs(usize, u32, usize, FailableReturnChannel<Vec<u8>>),
+    WriteAddrs(
+        usize,
+        u32,
+        Vec<u8>,
+        oneshot::Sender<Result<(), MemoryError>>,
+    ),
+    DeleteFuture,
+    GoTo(usize),
+    /// Save the full history to the session file at the given path.
+    SaveSession(String),
+    ListBranches(oneshot::Sender<Vec<BranchInfo>>),
+    /// Switch to the branch with the given name or id, answering with an error message on failure.
+    SwitchBranch(String, oneshot::Sender<Result<(), String>>),
+    /// Name the active branch.
+    NameBranch(String),
+    /// Delete the branch with the given name or id, answering with an error message on failure.
+    DeleteBranch(String, oneshot::Sender<Result<(), String>>),
+    /// Compare the states after both step counts, answering with `None` if either is out of range.
+    Diff(usize, usize, oneshot::Sender<Option<BoardDiff>>),
+}
+
+impl Command {
+    /// Returns the hart this command acts on, if any.
+    pub fn hart(&self) -> Option<usize> {
+        match self {
+            Command::Step(hart)
+            | Command::StepBack(hart)
+            | Command::RangeStep(hart, _, _)
+            | Command::ReadRegisters(hart, _)
+            | Command::WriteRegisters(hart, _)
+            | Command::ReadRegister(hart, _, _)
+            | Command::WriteRegister(hart, _, _, _)
+            | Command::ReadAddrs(hart, _, _, _)
+            | Command::WriteAddrs(hart, _, _, _) => Some(*hart),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Command {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Command::Exit => write!(f, "Exit"),
+            Command::Pause => write!(f, "Stop"),
+            Command::Continue => write!(f, "Continue"),
+            Command::ReverseContinue => write!(f, "ReverseContinue"),
+            Command::Step(_) => write!(f, "Step"),
+            Command::StepBack(_) => write!(f, "ReverseStep"),
+            Command::RangeStep(_, _, _) => write!(f, "RangeStep"),
+            Command::RemoveBreakpoint(_) => write!(f, "RemoveBreakpoint"),
+            Command::AddBreakpoint(_) => write!(f, "AddBreakpoint"),
+            Command::AddWatchpoint(_) => write!(f, "AddWatchpoint"),
+            Command::RemoveWatchpoint(_) => write!(f, "RemoveWatchpoint"),
+            Command::ReadRegisters(_, _) => write!(f, "ReadRegisters"),
+            Command::WriteRegister(_, _, _, _) => write!(f, "WriteRegister"),
+            Command::ReadRegister(_, _, _) => write!(f, "ReadRegister"),
+            Command::WriteRegisters(_, _) => write!(f, "WriteRegisters"),
+            Command::ReadAddrs(_, _, _, _) => write!(f, "ReadAddrs"),
+            Command::WriteAddrs(_, _, _, _) => write!(f, "WriteAddrs"),
+            Command::DeleteFuture => write!(f, "DeleteFuture"),
+            Command::GoTo(_) => write!(f, "GoTo"),
+            Command::SaveSession(_) => write!(f, "SaveSession"),
+            Command::ListBranches(_) => write!(f, "ListBranches"),
+            Command::SwitchBranch(_, _) => write!(f, "SwitchBranch"),
+            Command::NameBranch(_) => write!(f, "NameBranch"),
+            Command::DeleteBranch(_, _) => write!(f, "DeleteBranch"),
+            Command::Diff(_, _, _) => write!(f, "Diff"),
+        }
+    }
+}
@@ -11,11 +11,17 @@ use ratatui::{
     backend::{Backend, CrosstermBackend},
     layout::{Constraint, Layout, Rect},
     style::{Color, Style},
-    text::Span,
-    widgets::{Block, Borders, Gauge},
+    text::{Line, Span},
+    widgets::{Block, Borders, Gauge, Paragraph},
     Frame, Terminal,
 };
-use red_planet_core::registers::{Registers, Specifier};
+use red_planet_core::{
+    board::diff::BoardDiff,
+    core::mmu::MemoryError,
+    instruction::Instruction,
+    registers::{Registers, Specifier},
+    simulator::branch::BranchInfo,
+};
 use tokio::{
     select, spawn,
     sync::{mpsc::UnboundedSender, watch},
@@ -24,7 +30,7 @@ use tokio::{
 use tui_logger::{TuiLoggerWidget, TuiWidgetState};
 use tui_textarea::TextArea;
 
-use crate::target::{command::Command, ExecutionType, SharedTargetState};
+use crate::target::{command::Command, format_branch, ExecutionType, SharedTargetState};
 
 /// Sets up the terminal on creation, and resets it back when dropped.
 pub struct TermSetupDropGard {}
@@ -98,6 +104,10 @@ impl TuiState {
 
         enum CommandResponse {
             Registers(oneshot::Receiver<Registers>),
+            Branches(oneshot::Receiver<Vec<BranchInfo>>),
+            Result(oneshot::Receiver<Result<(), String>>),
+            Diff(oneshot::Receiver<Option<BoardDiff>>),
+            Patch(u32, oneshot::Receiver<Result<(), MemoryError>>),
         }
 
         let (command, command_response) = match command_str
@@ -108,21 +118,81 @@ impl TuiState {
             ["q" | "quit"] => (Command::Exit, None),
             ["p" | "pause"] => (Command::Pause, None),
             ["c" | "continue"] => (Command::Continue, None),
-            ["s" | "step"] => (Command::Step, None),
+            ["s" | "step"] => (Command::Step(0), None),
+            ["s" | "step", hart] if hart.parse::<usize>().is_ok() => {
+                (Command::Step(hart.parse().unwrap()), None)
+            }
             ["rc" | "reverse-continue"] => (Command::ReverseContinue, None),
-            ["rs" | "reverse-step"] => (Command::StepBack, None),
+            ["rs" | "reverse-step"] => (Command::StepBack(0), None),
+            ["rs" | "reverse-step", hart] if hart.parse::<usize>().is_ok() => {
+                (Command::StepBack(hart.parse().unwrap()), None)
+            }
             ["df" | "delete-future"] => (Command::DeleteFuture, None),
             ["g" | "goto", amount] if amount.parse::<usize>().is_ok() => {
                 let amount = amount.parse::<usize>().unwrap();
                 (Command::GoTo(amount), None)
             }
-            ["regs"] => {
+            ["save", path] => (Command::SaveSession(path.to_string()), None),
+            ["branches"] => {
+                let (sender, receiver) = oneshot::channel();
+                (
+                    Command::ListBranches(sender),
+                    Some(CommandResponse::Branches(receiver)),
+                )
+            }
+            ["branch", branch] => {
+                let (sender, receiver) = oneshot::channel();
+                (
+                    Command::SwitchBranch(branch.to_string(), sender),
+                    Some(CommandResponse::Result(receiver)),
+                )
+            }
+            ["name-branch", name] => (Command::NameBranch(name.to_string()), None),
+            ["delete-branch", branch] => {
+                let (sender, receiver) = oneshot::channel();
+                (
+                    Command::DeleteBranch(branch.to_string(), sender),
+                    Some(CommandResponse::Result(receiver)),
+                )
+            }
+            ["diff", from, to] if from.parse::<usize>().is_ok() && to.parse::<usize>().is_ok() => {
+                let (sender, receiver) = oneshot::channel();
+                (
+                    Command::Diff(from.parse().unwrap(), to.parse().unwrap(), sender),
+                    Some(CommandResponse::Diff(receiver)),
+                )
+            }
+            ["regs", hart @ ..]
+                if hart.len() <= 1 && hart.iter().all(|hart| hart.parse::<usize>().is_ok()) =>
+            {
+                let hart = hart.first().map_or(0, |hart| hart.parse().unwrap());
                 let (sender, receiver) = oneshot::channel();
                 (
-                    Command::ReadRegisters(sender),
+                    Command::ReadRegisters(hart, sender),
                     Some(CommandResponse::Registers(receiver)),
                 )
             }
+            ["patch", addr, asm @ ..] if !asm.is_empty() => {
+                let Ok(addr) = u32::from_str_radix(addr.trim_start_matches("0x"), 16) else {
+                    error!("Invalid address `{addr}`");
+                    return true;
+                };
+                let encoded = match Instruction::assemble(&asm.join(" "), addr)
+                    .map_err(|e| e.to_string())
+                    .and_then(|instruction| instruction.encode().map_err(|e| e.to_string()))
+                {
+                    Ok(encoded) => encoded,
+                    Err(e) => {
+                        error!("{e}");
+                        return true;
+                    }
+                };
+                let (sender, receiver) = oneshot::channel();
+                (
+                    Command::WriteAddrs(0, addr, encoded.to_le_bytes().to_vec(), sender),
+                    Some(CommandResponse::Patch(addr, receiver)),
+                )
+            }
             _ => return false,
         };
         self.last_command = Some(command_str.to_owned());
@@ -141,6 +211,28 @@ impl TuiState {
                             info!("$pc: {}", registers.pc());
                         }
                     }
+                    CommandResponse::Branches(branches) => {
+                        if let Ok(branches) = branches.await {
+                            for branch in &branches {
+                                info!("{}", format_branch(branch));
+                            }
+                        }
+                    }
+                    CommandResponse::Result(result) => {
+                        if let Ok(Err(e)) = result.await {
+                            error!("{e}");
+                        }
+                    }
+                    CommandResponse::Diff(diff) => match diff.await {
+                        Ok(Some(diff)) => log_diff(&diff),
+                        Ok(None) => error!("Step out of range"),
+                        Err(_) => {}
+                    },
+                    CommandResponse::Patch(addr, result) => match result.await {
+                        Ok(Ok(())) => info!("Patched {addr:#010x}"),
+                        Ok(Err(e)) => error!("Failed to patch {addr:#010x}: {e}"),
+                        Err(_) => {}
+                    },
                 }
             });
         }
@@ -156,15 +248,16 @@ impl TuiState {
         .clamp(0.0, 1.0);
 
         let running_state_name = match state.state {
-            Some(ExecutionType::Step) => "Step",
-            Some(ExecutionType::StepBack) => "Step Back",
-            Some(ExecutionType::RangeStep(_, _)) => "Running",
+            Some(ExecutionType::Step(_)) => "Step",
+            Some(ExecutionType::StepBack(_)) => "Step Back",
+            Some(ExecutionType::RangeStep(_, _, _)) => "Running",
+            Some(ExecutionType::Continue) if state.sleeping => "Sleeping",
             Some(ExecutionType::Continue) => "Running",
             Some(ExecutionType::ReverseContinue) => "Running Back",
             None => "Stopped",
         };
 
-        let state_block = Block::bordered().title("State");
+        let state_block = Block::bordered().title(format!("State (branch {})", state.branch));
 
         let [running_state_area, current_step_area, bar_area, total_steps_area] =
             Layout::horizontal([
@@ -197,6 +290,40 @@ impl TuiState {
         frame.render_widget(total_steps, total_steps_area);
     }
 
+    fn draw_disassembly(state: &SharedTargetState, frame: &mut Frame, rect: Rect) {
+        let block = Block::bordered().title("Disassembly");
+
+        let mut lines = Vec::new();
+        let mut pc_line = 0;
+        for line in &state.disassembly {
+            if let Some(symbol) = &line.symbol {
+                lines.push(Line::raw(format!("<{symbol}>:")));
+            }
+            let (marker, style) = match line.address == state.pc {
+                true => {
+                    pc_line = lines.len();
+                    ("> ", Style::default().fg(Color::Yellow))
+                }
+                false => ("  ", Style::default()),
+            };
+            let text = match line.text.split_once('\t') {
+                Some((mnemonic, operands)) => format!("{mnemonic:<8}{operands}"),
+                None => line.text.clone(),
+            };
+            lines.push(Line::styled(
+                format!("{marker}{:08x}:  {text}", line.address),
+                style,
+            ));
+        }
+
+        // Keep the line of the `pc` in the top third.
+        let scroll = pc_line.saturating_sub(block.inner(rect).height as usize / 3);
+        let disassembly = Paragraph::new(lines)
+            .block(block)
+            .scroll((scroll as u16, 0));
+        frame.render_widget(disassembly, rect);
+    }
+
     fn draw(&mut self, frame: &mut Frame) {
         let shared_state = self.shared_state.borrow_and_update();
         let uart_output = String::from_utf8_lossy(&shared_state.output_buffer);
@@ -213,13 +340,17 @@ impl TuiState {
 
         Self::draw_status(&shared_state, frame, status_area);
 
+        let [uart_area, disassembly_area] =
+            Layout::horizontal([Constraint::Fill(1), Constraint::Length(48)]).areas(uart_area);
+        Self::draw_disassembly(&shared_state, frame, disassembly_area);
+
         let selected_style = Style::default().fg(Color::Green);
         let deselected_style = Style::default();
         let (uart_style, prompt_style) = match &self.selected {
             Selected::Uart => (selected_style, deselected_style),
             Selected::Prompt => (deselected_style, selected_style),
         };
-        let uart = ratatui::widgets::Paragraph::new(uart_output).block(
+        let uart = Paragraph::new(uart_output).block(
             Block::new()
                 .borders(Borders::ALL)
                 .title("UART")
@@ -335,6 +466,37 @@ impl TuiState {
     }
 }
 
+/// Log every change in `diff`, one per line.
+fn log_diff(diff: &BoardDiff) {
+    if diff.is_empty() {
+        info!("No differences");
+    }
+    for hart in &diff.harts {
+        info!("Hart {}:", hart.hart);
+        for (r, changed) in &hart.registers {
+            info!("  ${}: {:#x} -> {:#x}", r, changed.old, changed.new);
+        }
+        if let Some(changed) = &hart.pc {
+            info!("  $pc: {:#x} -> {:#x}", changed.old, changed.new);
+        }
+        for (csr, changed) in &hart.csrs {
+            info!(
+                "  csr {:#05x}: {:#x} -> {:#x}",
+                csr, changed.old, changed.new
+            );
+        }
+        if let Some(changed) = &hart.privilege_mode {
+            info!("  privilege mode: {} -> {}", changed.old, changed.new);
+        }
+    }
+    for (name, changed) in &diff.devices {
+        info!("{}: {:#x} -> {:#x}", name, changed.old, changed.new);
+    }
+    for range in &diff.memory {
+        info!("memory: {}", range);
+    }
+}
+
 pub async fn run_tui(
     command_sender: UnboundedSender<Command>,
     shared_state_receiver: watch::Receiver<SharedTargetState>,
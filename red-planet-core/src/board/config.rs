@@ -0,0 +1,693 @@
+//! Configuration of a [`Board`](super::Board), which can be loaded from a board description file.
+
+use std::fs::{self, File};
+use std::io;
+use std::num::{NonZeroU32, NonZeroUsize};
+use std::path::PathBuf;
+
+use serde::Deserialize;
+use thiserror::Error;
+
+use super::fdt;
+use super::system_bus::Resource;
+use crate::core::clint;
+use crate::resources::{plic, virtio_block};
+use crate::{AddressRange, Endianness};
+
+/// Size of the address range of a CLINT.
+pub const CLINT_SIZE: u32 = 0x1_0000;
+/// Size of the address range of a PLIC.
+pub const PLIC_SIZE: u32 = 0x400_0000;
+/// Size of the address range of a UART.
+pub const UART_SIZE: u32 = 0x100;
+/// Size of the address range of a virtio block device.
+pub const VIRTIO_BLOCK_SIZE: u32 = virtio_block::MMIO_SIZE;
+/// Size of the address range of the power-down device.
+pub const POWER_DOWN_SIZE: u32 = 0x4;
+/// Size of the reset vector placed at the start of MROM, which is followed by the device tree.
+pub const RESET_VECTOR_SIZE: u32 = 24;
+
+/// Configuration of a [`Board`](super::Board): its harts, memory map and devices.
+///
+/// The default is the built-in layout. A board description file is this configuration in TOML, in
+/// which every section that is left out keeps its default. A `[memory]` or `[devices]` section
+/// that is given must be complete, and optional memories and devices that it leaves out are not
+/// present. For example, the built-in layout is:
+///
+/// ```toml
+/// boot = "dram"      # or "flash"
+/// endianness = "le"  # M-mode endianness, "le" or "be"
+///
+/// [harts]
+/// count = 1
+/// quantum = 1
+/// idle_skip = false
+/// pmp_entries = 0
+/// trigger_count = 4
+/// support_misaligned_memory_access = true
+/// strict_instruction_alignment = false
+/// # sc_failure_interval = 100
+///
+/// [memory]
+/// mrom = { base = 0x1000, size = 0xF000 }
+/// flash = { base = 0x2000_0000, size = 0x400_0000 }
+/// dram = { base = 0x8000_0000, size = 0x8000_0000 }
+///
+/// [devices]
+/// clint = { base = 0x200_0000 }
+/// plic = { base = 0xC00_0000 }
+/// power_down = { base = 0x10_0000 }
+/// uarts = [{ base = 0x1000_0000, irq = 3 }]
+/// # virtio_blocks = [{ base = 0x1000_1000, irq = 8, image = "disk.img" }]
+///
+/// # [semihosting]
+/// # root = "files"
+/// # cmdline = "test --verbose"
+/// # supervisor = false
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Where the reset vector in MROM jumps to.
+    pub boot: BootMode,
+    /// M-mode endianness
+    pub endianness: Endianness,
+    pub harts: HartConfig,
+    pub memory: MemoryConfig,
+    pub devices: DeviceConfig,
+    /// If set, the semihosting sequence performs a semihosting call, rather than raising a
+    /// breakpoint exception.
+    pub semihosting: Option<SemihostingConfig>,
+    /// Contents of flash, at most the size of [`MemoryConfig::flash`]. This is not part of a board
+    /// description file.
+    #[serde(skip)]
+    pub flash: Vec<u8>,
+    /// HTIF of the loaded program, which the ELF loader sets if the program has a `tohost` symbol.
+    /// This is not part of a board description file.
+    #[serde(skip)]
+    pub htif: Option<HtifConfig>,
+    /// Device tree blob to use instead of the one generated from this configuration, see
+    /// [`Config::device_tree`]. This is not part of a board description file.
+    #[serde(skip)]
+    pub dtb: Option<Vec<u8>>,
+    /// Boot information of an operating system loaded into DRAM, which is passed to it in the
+    /// generated device tree. This is not part of a board description file.
+    #[serde(skip)]
+    pub chosen: ChosenConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            boot: BootMode::Dram,
+            endianness: Endianness::LE,
+            harts: HartConfig::default(),
+            memory: MemoryConfig::default(),
+            devices: DeviceConfig::default(),
+            semihosting: None,
+            flash: Vec::default(),
+            htif: None,
+            dtb: None,
+            chosen: ChosenConfig::default(),
+        }
+    }
+}
+
+/// Start address of the program the reset vector jumps to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BootMode {
+    /// Start of DRAM.
+    Dram,
+    /// Start of flash.
+    Flash,
+}
+
+/// Configuration shared by all harts of a board.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HartConfig {
+    /// Number of harts sharing the system bus. Hart IDs are assigned from 0 upwards.
+    pub count: NonZeroUsize,
+    /// Number of consecutive instructions a hart executes before the next hart is scheduled.
+    pub quantum: NonZeroU32,
+    /// If `true`, WFI stalls a hart until an interrupt is pending, and while all harts are
+    /// stalled, mtime is fast-forwarded to the next timer event in a single step.
+    ///
+    /// This avoids recording a step for every tick of mtime while the guest is sleeping. Since
+    /// the skip only depends on the simulated state, time-travel stays deterministic.
+    pub idle_skip: bool,
+    /// Force every n-th otherwise successful SC to fail spuriously.
+    /// See [`crate::core::Config::sc_failure_interval`].
+    pub sc_failure_interval: Option<NonZeroU32>,
+    /// See [`crate::core::Config::pmp_entries`].
+    ///
+    /// Defaults to zero, so S-mode and U-mode can access all memory. With PMP entries, all their
+    /// accesses fail until M-mode programs an entry that allows them.
+    pub pmp_entries: u8,
+    /// See [`crate::core::Config::trigger_count`].
+    pub trigger_count: u8,
+    /// See [`crate::core::Config::support_misaligned_memory_access`].
+    pub support_misaligned_memory_access: bool,
+    /// See [`crate::core::Config::strict_instruction_alignment`].
+    pub strict_instruction_alignment: bool,
+}
+
+impl Default for HartConfig {
+    fn default() -> Self {
+        Self {
+            count: NonZeroUsize::MIN,
+            quantum: NonZeroU32::MIN,
+            idle_skip: false,
+            sc_failure_interval: None,
+            pmp_entries: 0,
+            trigger_count: 4,
+            support_misaligned_memory_access: true,
+            strict_instruction_alignment: false,
+        }
+    }
+}
+
+/// Memories of a board.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MemoryConfig {
+    /// Mask ROM holding the reset vector, which is where all harts start.
+    pub mrom: Region,
+    pub flash: Option<Region>,
+    pub dram: Region,
+}
+
+impl Default for MemoryConfig {
+    fn default() -> Self {
+        Self {
+            mrom: Region {
+                base: 0x0000_1000,
+                size: 0xF000,
+            },
+            flash: Some(Region {
+                base: 0x2000_0000,
+                size: 0x400_0000,
+            }),
+            dram: Region {
+                base: 0x8000_0000,
+                size: 0x8000_0000,
+            },
+        }
+    }
+}
+
+/// Memory-mapped devices of a board.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DeviceConfig {
+    pub clint: DeviceRegion,
+    /// Handles the external interrupts of all devices. Every hart has an M-mode and an S-mode
+    /// context: context `2 * hart` targets M-mode, and context `2 * hart + 1` targets S-mode.
+    pub plic: DeviceRegion,
+    /// SiFive test finisher, which powers down the board when `0x5555` (pass) or
+    /// `0x3333 | code << 16` (fail) is written to it, and resets it on `0x7777`. See
+    /// [`Board::exit_status`](super::Board::exit_status).
+    pub power_down: Option<DeviceRegion>,
+    /// NS16550A compatible UARTs, numbered in this order.
+    #[serde(default)]
+    pub uarts: Vec<UartConfig>,
+    /// Virtio block devices, numbered in this order.
+    #[serde(default)]
+    pub virtio_blocks: Vec<VirtioBlockConfig>,
+}
+
+impl Default for DeviceConfig {
+    fn default() -> Self {
+        Self {
+            clint: DeviceRegion { base: 0x0200_0000 },
+            plic: DeviceRegion { base: 0x0C00_0000 },
+            power_down: Some(DeviceRegion { base: 0x0010_0000 }),
+            uarts: vec![UartConfig {
+                base: 0x1000_0000,
+                irq: 3,
+            }],
+            virtio_blocks: Vec::new(),
+        }
+    }
+}
+
+/// Location and interrupt sources of a user-defined device, see
+/// [`DeviceRegistration`](super::DeviceRegistration).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct DeviceMapping {
+    /// Name used in errors about this device.
+    pub name: String,
+    pub base: u32,
+    pub size: u32,
+    /// PLIC interrupt sources of the device.
+    pub irqs: Vec<u32>,
+}
+
+/// Range of physical addresses of a memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Region {
+    pub base: u32,
+    /// Size in bytes, which must be at least one.
+    pub size: u32,
+}
+
+/// Location of a device, whose size is fixed by the kind of device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DeviceRegion {
+    pub base: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UartConfig {
+    pub base: u32,
+    /// PLIC interrupt source of the UART.
+    pub irq: u32,
+}
+
+/// Addresses of the `tohost` and `fromhost` words of a
+/// [`Htif`](crate::resources::htif::Htif), which must be in DRAM.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HtifConfig {
+    pub tohost: u32,
+    pub fromhost: Option<u32>,
+}
+
+/// Contents of the `/chosen` node of the generated device tree, through which Linux gets its
+/// command line and initial RAM disk. It is not added to a custom [`Config::dtb`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChosenConfig {
+    /// Kernel command line, the `bootargs` property.
+    pub bootargs: Option<String>,
+    /// Location of the initial RAM disk, which must be in DRAM.
+    pub initrd: Option<Region>,
+}
+
+/// Host side of semihosting, see [`Semihosting`](crate::resources::semihosting::Semihosting).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SemihostingConfig {
+    /// Directory to which the files of the guest are sandboxed. Without one, the guest can only
+    /// use the console.
+    pub root: Option<PathBuf>,
+    /// Command line that the guest gets through `SYS_GET_CMDLINE`.
+    pub cmdline: String,
+    /// If `true`, semihosting calls made in S-mode are performed as well. Calls are always
+    /// performed in M-mode, and never in U-mode, where the sequence raises a breakpoint exception.
+    pub supervisor: bool,
+    /// Seconds since the Unix epoch at which the simulation started, from which `SYS_TIME` counts.
+    /// This is not part of a board description file, so the time is the same every run.
+    #[serde(skip)]
+    pub start_time: u32,
+}
+
+/// Virtio block device with the MMIO transport, see
+/// [`VirtioBlock`](crate::resources::virtio_block::VirtioBlock).
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct VirtioBlockConfig {
+    pub base: u32,
+    /// PLIC interrupt source of the device.
+    pub irq: u32,
+    /// Path of the disk image, which is never written to. Writes of the guest are only kept in
+    /// the simulation.
+    pub image: PathBuf,
+}
+
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("invalid board description: {0}")]
+    Parse(#[from] toml::de::Error),
+    #[error("{0} is empty or does not fit in the address space")]
+    InvalidRegion(String),
+    #[error("{0} overlaps with {1}")]
+    Overlap(String, String),
+    #[error("MROM must hold at least {0} bytes for the reset vector and device tree")]
+    MromTooSmall(u32),
+    #[error("device tree blob does not start with a valid header")]
+    InvalidDtb,
+    #[error("cannot boot from flash, since there is none")]
+    NoFlash,
+    #[error("flash contents do not fit in flash")]
+    FlashTooLarge,
+    #[error("{0} has invalid IRQ {1}, which must be in 1..={max}", max = plic::MAX_SOURCE)]
+    InvalidIrq(String, u32),
+    #[error("{0} and {1} share IRQ {2}")]
+    SharedIrq(String, String, u32),
+    #[error("at most {max} harts are supported", max = clint::MAX_HARTS)]
+    TooManyHarts,
+    #[error("HTIF {0} is not in DRAM")]
+    HtifOutsideDram(&'static str),
+    #[error("initrd is not in DRAM")]
+    InitrdOutsideDram,
+    #[error("cannot open disk image of {0}: {1}")]
+    Image(String, #[source] io::Error),
+    #[error("cannot use semihosting root: {0}")]
+    SemihostingRoot(#[source] io::Error),
+}
+
+impl Config {
+    /// Parses a board description file, see [`Config`] for its format.
+    pub fn from_toml(description: &str) -> Result<Self, ConfigError> {
+        let config: Self = toml::from_str(description)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Checks that a board can be built from this configuration.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        self.validate_with(&[])
+    }
+
+    /// Checks that a board can be built from this configuration and the mappings of the given
+    /// user-defined devices.
+    pub(super) fn validate_with(&self, mappings: &[&DeviceMapping]) -> Result<(), ConfigError> {
+        if self.harts.count.get() > clint::MAX_HARTS {
+            return Err(ConfigError::TooManyHarts);
+        }
+        match self.memory.flash {
+            None if self.boot == BootMode::Flash => return Err(ConfigError::NoFlash),
+            None if !self.flash.is_empty() => return Err(ConfigError::FlashTooLarge),
+            Some(flash) if self.flash.len() > flash.size as usize => {
+                return Err(ConfigError::FlashTooLarge)
+            }
+            _ => {}
+        }
+
+        let mut regions = self.regions(mappings)?;
+        regions.sort_by_key(|(_, range, _)| range.start());
+        for pair in regions.windows(2) {
+            let [(name, range, _), (next_name, next_range, _)] = pair else {
+                unreachable!()
+            };
+            if next_range.start() <= range.end() {
+                return Err(ConfigError::Overlap(name.clone(), next_name.clone()));
+            }
+        }
+
+        let uart_irqs = (self.devices.uarts.iter().enumerate())
+            .map(|(index, uart)| (format!("uart{index}"), uart.irq));
+        let virtio_block_irqs = (self.devices.virtio_blocks.iter().enumerate())
+            .map(|(index, block)| (format!("virtio_block{index}"), block.irq));
+        let device_irqs = (mappings.iter())
+            .flat_map(|mapping| mapping.irqs.iter().map(|&irq| (mapping.name.clone(), irq)));
+        let mut irqs: Vec<(String, u32)> = Vec::new();
+        for (name, irq) in uart_irqs.chain(virtio_block_irqs).chain(device_irqs) {
+            if !(1..=plic::MAX_SOURCE).contains(&irq) {
+                return Err(ConfigError::InvalidIrq(name, irq));
+            }
+            if let Some((other, _)) = irqs.iter().find(|(_, other_irq)| *other_irq == irq) {
+                return Err(ConfigError::SharedIrq(other.clone(), name, irq));
+            }
+            irqs.push((name, irq));
+        }
+
+        let dram = self.memory.dram;
+        let in_dram = |address: u32, size: u32| {
+            address >= dram.base
+                && address as u64 + size as u64 <= dram.base as u64 + dram.size as u64
+        };
+        if let Some(htif) = self.htif {
+            if !in_dram(htif.tohost, 8) {
+                return Err(ConfigError::HtifOutsideDram("tohost"));
+            }
+            if !htif.fromhost.is_none_or(|fromhost| in_dram(fromhost, 8)) {
+                return Err(ConfigError::HtifOutsideDram("fromhost"));
+            }
+        }
+        if let Some(initrd) = self.chosen.initrd {
+            if !in_dram(initrd.base, initrd.size) {
+                return Err(ConfigError::InitrdOutsideDram);
+            }
+        }
+
+        if let Some(dtb) = &self.dtb {
+            let header = |index: usize| {
+                let word = dtb.get(4 * index..4 * index + 4)?;
+                Some(u32::from_be_bytes(word.try_into().unwrap()))
+            };
+            if header(0) != Some(fdt::FDT_MAGIC) || header(1) != Some(dtb.len() as u32) {
+                return Err(ConfigError::InvalidDtb);
+            }
+        }
+        let mrom_size = self.device_tree_offset() as u64 + self.device_tree().len() as u64;
+        if mrom_size > self.memory.mrom.size as u64 {
+            return Err(ConfigError::MromTooSmall(mrom_size as u32));
+        }
+
+        for index in 0..self.devices.virtio_blocks.len() {
+            self.open_image(index)?;
+        }
+        if let Some(root) =
+            (self.semihosting.as_ref()).and_then(|semihosting| semihosting.root.as_ref())
+        {
+            fs::read_dir(root).map_err(ConfigError::SemihostingRoot)?;
+        }
+        Ok(())
+    }
+
+    /// Opens the disk image of the virtio block device with the given index.
+    pub(super) fn open_image(&self, index: usize) -> Result<File, ConfigError> {
+        File::open(&self.devices.virtio_blocks[index].image)
+            .map_err(|e| ConfigError::Image(format!("virtio_block{index}"), e))
+    }
+
+    /// Returns the name, address range and resource of every memory and device, including the
+    /// user-defined devices with the given mappings.
+    pub(super) fn regions(
+        &self,
+        mappings: &[&DeviceMapping],
+    ) -> Result<Vec<(String, AddressRange, Resource)>, ConfigError> {
+        let memory = &self.memory;
+        let devices = &self.devices;
+        let mut regions = vec![
+            (
+                "mrom".to_owned(),
+                memory.mrom.base,
+                memory.mrom.size,
+                Resource::Mrom,
+            ),
+            (
+                "dram".to_owned(),
+                memory.dram.base,
+                memory.dram.size,
+                Resource::Dram,
+            ),
+            (
+                "clint".to_owned(),
+                devices.clint.base,
+                CLINT_SIZE,
+                Resource::Clint,
+            ),
+            (
+                "plic".to_owned(),
+                devices.plic.base,
+                PLIC_SIZE,
+                Resource::Plic,
+            ),
+        ];
+        if let Some(flash) = memory.flash {
+            regions.push(("flash".to_owned(), flash.base, flash.size, Resource::Flash));
+        }
+        if let Some(power_down) = devices.power_down {
+            regions.push((
+                "power_down".to_owned(),
+                power_down.base,
+                POWER_DOWN_SIZE,
+                Resource::PowerDown,
+            ));
+        }
+        for (index, uart) in devices.uarts.iter().enumerate() {
+            regions.push((
+                format!("uart{index}"),
+                uart.base,
+                UART_SIZE,
+                Resource::Uart(index),
+            ));
+        }
+        for (index, block) in devices.virtio_blocks.iter().enumerate() {
+            regions.push((
+                format!("virtio_block{index}"),
+                block.base,
+                VIRTIO_BLOCK_SIZE,
+                Resource::VirtioBlock(index),
+            ));
+        }
+        for (index, mapping) in mappings.iter().enumerate() {
+            regions.push((
+                mapping.name.clone(),
+                mapping.base,
+                mapping.size,
+                Resource::Device(index),
+            ));
+        }
+
+        regions
+            .into_iter()
+            .map(|(name, base, size, resource)| {
+                size.checked_sub(1)
+                    .and_then(|delta| base.checked_add(delta))
+                    .and_then(|end| AddressRange::new(base, end).ok())
+                    .map(|range| (name.clone(), range, resource))
+                    .ok_or(ConfigError::InvalidRegion(name))
+            })
+            .collect()
+    }
+
+    /// Returns the device tree blob whose address the reset vector passes to the harts in a1: the
+    /// custom [`Config::dtb`], or otherwise one describing the harts, DRAM and built-in devices.
+    pub fn device_tree(&self) -> Vec<u8> {
+        match &self.dtb {
+            Some(dtb) => dtb.clone(),
+            None => fdt::generate(self),
+        }
+    }
+
+    /// Returns the offset of the device tree in MROM, which is the first 8-byte aligned address
+    /// after the reset vector.
+    pub(super) fn device_tree_offset(&self) -> u32 {
+        let base = self.memory.mrom.base as u64;
+        ((base + RESET_VECTOR_SIZE as u64).next_multiple_of(8) - base) as u32
+    }
+
+    /// Returns the address the reset vector jumps to.
+    pub fn start_address(&self) -> u32 {
+        match (self.boot, self.memory.flash) {
+            (BootMode::Flash, Some(flash)) => flash.base,
+            _ => self.memory.dram.base,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn built_in_layout() {
+        let config = Config::default();
+        assert_eq!(config.validate().map_err(|e| e.to_string()), Ok(()));
+        assert_eq!(Config::from_toml("").unwrap(), config);
+
+        let description = include_str!("../../../examples/boards/default.toml");
+        assert_eq!(Config::from_toml(description).unwrap(), config);
+    }
+
+    #[test]
+    fn linux_layout() {
+        let description = include_str!("../../../examples/boards/linux.toml");
+        let config = Config::from_toml(description).unwrap();
+        assert_eq!(config.validate().map_err(|e| e.to_string()), Ok(()));
+        assert!(config.harts.idle_skip);
+        assert_eq!(config.memory.dram.size, 0x1000_0000);
+        assert_eq!(config.memory.flash, None);
+        assert_eq!(config.devices, DeviceConfig::default());
+    }
+
+    #[test]
+    fn description() {
+        let config = Config::from_toml(
+            r#"
+            boot = "flash"
+
+            [harts]
+            count = 2
+
+            [memory]
+            mrom = { base = 0x1000, size = 0x1000 }
+            flash = { base = 0x2000_0000, size = 0x10_0000 }
+            dram = { base = 0x4000_0000, size = 0x100_0000 }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.harts.count.get(), 2);
+        assert_eq!(config.harts.quantum.get(), 1);
+        assert_eq!(config.start_address(), 0x2000_0000);
+        assert_eq!(config.memory.dram.size, 0x100_0000);
+        assert_eq!(config.devices, DeviceConfig::default());
+    }
+
+    #[test]
+    fn invalid_descriptions() {
+        let error = |description| Config::from_toml(description).unwrap_err().to_string();
+        assert_eq!(
+            error("[devices]\nclint = { base = 0x200_0000 }\nplic = { base = 0x1000 }"),
+            "mrom overlaps with plic"
+        );
+        assert_eq!(
+            error(
+                "[memory]\nmrom = { base = 0x1000, size = 0x100 }\n\
+                 dram = { base = 0xFFFF_0000, size = 0x2_0000 }"
+            ),
+            "dram is empty or does not fit in the address space"
+        );
+        assert_eq!(
+            error(
+                "boot = \"flash\"\n[memory]\nmrom = { base = 0x1000, size = 0x100 }\n\
+                 dram = { base = 0x8000_0000, size = 0x1000 }"
+            ),
+            "cannot boot from flash, since there is none"
+        );
+        assert_eq!(
+            error(
+                "[devices]\nclint = { base = 0x200_0000 }\nplic = { base = 0xC00_0000 }\n\
+                 uarts = [{ base = 0x1000_0000, irq = 3 }, { base = 0x1000_1000, irq = 3 }]"
+            ),
+            "uart0 and uart1 share IRQ 3"
+        );
+        assert_eq!(
+            error("[devices]\nclint = { base = 0x200_0000 }\nplic = { base = 0xC00_0000 }\nuarts = [{ base = 0x1000_0000, irq = 0 }]"),
+            "uart0 has invalid IRQ 0, which must be in 1..=52"
+        );
+        assert!(error(
+            "[devices]\nclint = { base = 0x200_0000 }\nplic = { base = 0xC00_0000 }\n\
+             virtio_blocks = [{ base = 0x1000_1000, irq = 8, image = \"/nonexistent.img\" }]"
+        )
+        .starts_with("cannot open disk image of virtio_block0"));
+        assert!(error("[semihosting]\nroot = \"/nonexistent\"")
+            .starts_with("cannot use semihosting root"));
+        assert!(error("[harts]\ncount = 0").starts_with("invalid board description"));
+        assert!(error("[harts]\ncores = 1").starts_with("invalid board description"));
+    }
+
+    #[test]
+    fn initrd() {
+        let mut config = Config::default();
+        config.chosen.initrd = Some(Region {
+            base: 0xFFFF_F000,
+            size: 0x1000,
+        });
+        assert_eq!(config.validate().map_err(|e| e.to_string()), Ok(()));
+        config.chosen.initrd = Some(Region {
+            base: 0xFFFF_F000,
+            size: 0x1001,
+        });
+        assert!(matches!(
+            config.validate(),
+            Err(ConfigError::InitrdOutsideDram)
+        ));
+    }
+
+    #[test]
+    fn htif() {
+        let htif = |tohost, fromhost| Config {
+            htif: Some(HtifConfig { tohost, fromhost }),
+            ..Config::default()
+        };
+        assert_eq!(
+            htif(0x8000_1000, Some(0x8000_1008)).validate().ok(),
+            Some(())
+        );
+        assert!(matches!(
+            htif(0x1000, None).validate(),
+            Err(ConfigError::HtifOutsideDram("tohost"))
+        ));
+        assert!(matches!(
+            htif(0x8000_1000, Some(0x1000)).validate(),
+            Err(ConfigError::HtifOutsideDram("fromhost"))
+        ));
+    }
+}
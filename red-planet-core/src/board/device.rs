@@ -0,0 +1,128 @@
+//! User-defined memory-mapped devices, which crates depending on this one can attach to a
+//! [`Board`](super::Board) with [`Board::with_devices`](super::Board::with_devices).
+
+use std::any::Any;
+use std::fmt::Debug;
+
+use super::config::DeviceMapping;
+use crate::bus::Bus;
+use crate::interrupt::DynIrqCallback;
+use crate::simulator::Simulatable;
+use crate::system_bus::AccessType;
+use crate::Allocator;
+
+/// A memory-mapped device that is not built into the board.
+///
+/// Like the built-in devices, a device must keep all of its simulated state in the allocator, so
+/// it is tracked in the history and restored when going back in time. Addresses passed to the
+/// [`Bus`] methods are relative to the base address of the device. [`Simulatable::tick`] is called
+/// once per board step, after the scheduled hart executed an instruction.
+pub trait Device<A: Allocator>: Bus<A> + Simulatable<A> + Any {
+    /// Returns whether the device supports an access of `size` bytes at `address`. Unsupported
+    /// accesses are not forwarded to the device, and cause an access fault instead.
+    ///
+    /// By default, all accesses are supported.
+    fn accepts(&self, address: u32, size: usize, access_type: AccessType) -> bool {
+        let _ = (address, size, access_type);
+        true
+    }
+
+    /// Puts the device in its reset state, when the board is reset.
+    ///
+    /// By default, nothing happens.
+    fn reset(&self, allocator: &mut A) {
+        let _ = allocator;
+    }
+}
+
+/// Constructs a device, given the interrupt callbacks of its IRQs.
+type Build<A> = Box<dyn FnOnce(&mut A, Vec<DynIrqCallback<A>>) -> Box<dyn AnyDevice<A>>>;
+
+/// A user-defined device to attach to a [`Board`](super::Board), together with the address range
+/// and PLIC interrupt sources it is attached to.
+pub struct DeviceRegistration<A: Allocator> {
+    pub(super) mapping: DeviceMapping,
+    build: Build<A>,
+}
+
+impl<A: Allocator> DeviceRegistration<A> {
+    /// Registers a device named `name`, mapped to the `size` bytes starting at `base`, and
+    /// connected to the PLIC interrupt sources `irqs`.
+    ///
+    /// When the board is created, `build` is called to construct the device, with an interrupt
+    /// callback for every IRQ in `irqs`, in the same order. The name is only used in errors.
+    pub fn new<D: Device<A>>(
+        name: impl Into<String>,
+        base: u32,
+        size: u32,
+        irqs: Vec<u32>,
+        build: impl FnOnce(&mut A, Vec<DynIrqCallback<A>>) -> D + 'static,
+    ) -> Self {
+        Self {
+            mapping: DeviceMapping {
+                name: name.into(),
+                base,
+                size,
+                irqs,
+            },
+            build: Box::new(|allocator, callbacks| Box::new(build(allocator, callbacks))),
+        }
+    }
+
+    pub(super) fn build(
+        self,
+        allocator: &mut A,
+        callbacks: Vec<DynIrqCallback<A>>,
+    ) -> Box<dyn AnyDevice<A>> {
+        (self.build)(allocator, callbacks)
+    }
+}
+
+impl<A: Allocator> Debug for DeviceRegistration<A> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DeviceRegistration")
+            .field("mapping", &self.mapping)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Object-safe version of [`Device`], since [`Simulatable::drop`] takes the device by value.
+pub(super) trait AnyDevice<A: Allocator>: Debug {
+    fn as_bus(&self) -> &dyn Bus<A>;
+
+    fn as_any(&self) -> &dyn Any;
+
+    fn accepts(&self, address: u32, size: usize, access_type: AccessType) -> bool;
+
+    fn tick(&self, allocator: &mut A);
+
+    fn reset(&self, allocator: &mut A);
+
+    fn drop(self: Box<Self>, allocator: &mut A);
+}
+
+impl<A: Allocator, D: Device<A>> AnyDevice<A> for D {
+    fn as_bus(&self) -> &dyn Bus<A> {
+        self
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn accepts(&self, address: u32, size: usize, access_type: AccessType) -> bool {
+        Device::accepts(self, address, size, access_type)
+    }
+
+    fn tick(&self, allocator: &mut A) {
+        Simulatable::tick(self, allocator)
+    }
+
+    fn reset(&self, allocator: &mut A) {
+        Device::reset(self, allocator)
+    }
+
+    fn drop(self: Box<Self>, allocator: &mut A) {
+        Simulatable::drop(*self, allocator)
+    }
+}
@@ -0,0 +1,382 @@
+//! Differences between two states of a [`Board`], see [`crate::simulator::Simulator::diff`].
+
+use super::system_bus::Resource;
+use super::Board;
+use crate::bus::Bus;
+use crate::core::clint::{msip_addr, mtimecmp_addr};
+use crate::core::CsrSpecifier;
+use crate::registers::{Registers, Specifier};
+use crate::resources::plic;
+use crate::simulator::diff::{DiffContext, Diffable};
+use crate::simulator::SimulationAllocator;
+use crate::{AddressRange, PrivilegeLevel};
+
+/// Number of 32-bit words holding the pending or enable bits of all PLIC sources.
+const PLIC_WORDS: u32 = plic::MAX_SOURCE / 32 + 1;
+
+/// A value that differs between two states.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Changed<T> {
+    pub old: T,
+    pub new: T,
+}
+
+impl<T: PartialEq> Changed<T> {
+    /// Returns `None` if `old` and `new` are equal.
+    fn new(old: T, new: T) -> Option<Self> {
+        (old != new).then_some(Self { old, new })
+    }
+}
+
+/// Differences in the state of a single hart.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HartDiff {
+    /// ID of the hart.
+    pub hart: usize,
+    /// Changed x registers, in increasing order.
+    pub registers: Vec<(Specifier, Changed<u32>)>,
+    pub pc: Option<Changed<u32>>,
+    /// Changed CSRs, in increasing order. Only CSRs that are readable in M-mode are compared.
+    pub csrs: Vec<(CsrSpecifier, Changed<u32>)>,
+    pub privilege_mode: Option<Changed<PrivilegeLevel>>,
+}
+
+impl HartDiff {
+    /// Returns `true` if nothing changed in this hart.
+    pub fn is_empty(&self) -> bool {
+        self.registers.is_empty()
+            && self.pc.is_none()
+            && self.csrs.is_empty()
+            && self.privilege_mode.is_none()
+    }
+}
+
+/// Differences between two states of a [`Board`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BoardDiff {
+    /// Harts with at least one change, in order of hart ID.
+    pub harts: Vec<HartDiff>,
+    /// Changed device registers, named like `uart0.lcr` or `clint.mtimecmp[1]`.
+    pub devices: Vec<(String, Changed<u64>)>,
+    /// Modified ranges of physical memory, in increasing order. Only RAM is compared, since all
+    /// other memory is either read-only or part of a device.
+    pub memory: Vec<AddressRange>,
+}
+
+impl BoardDiff {
+    /// Returns `true` if both states are equal.
+    pub fn is_empty(&self) -> bool {
+        self.harts.is_empty() && self.devices.is_empty() && self.memory.is_empty()
+    }
+}
+
+/// State of a [`Board`] that is compared by value, see [`Diffable::capture`].
+#[derive(Debug)]
+pub struct BoardCapture {
+    harts: Vec<HartCapture>,
+    devices: Vec<(String, u64)>,
+}
+
+#[derive(Debug)]
+struct HartCapture {
+    registers: Registers,
+    privilege_mode: PrivilegeLevel,
+    csrs: Vec<(CsrSpecifier, u32)>,
+}
+
+impl Diffable for Board<SimulationAllocator> {
+    type Capture = BoardCapture;
+    type Diff = BoardDiff;
+
+    fn capture(&self, allocator: &mut SimulationAllocator) -> BoardCapture {
+        let harts = self
+            .cores
+            .iter()
+            .map(|core| HartCapture {
+                registers: core.registers(allocator).clone(),
+                privilege_mode: core.privilege_mode(allocator),
+                // Reading a CSR can have side effects, which are discarded by the simulator.
+                csrs: (0..1 << 12)
+                    .filter_map(|specifier| {
+                        core.read_csr(allocator, specifier, PrivilegeLevel::Machine)
+                            .ok()
+                            .map(|value| (specifier, value))
+                    })
+                    .collect(),
+            })
+            .collect();
+        BoardCapture {
+            harts,
+            devices: self.capture_devices(allocator),
+        }
+    }
+
+    fn diff(&self, old: BoardCapture, new: BoardCapture, context: &DiffContext) -> BoardDiff {
+        let harts = old
+            .harts
+            .into_iter()
+            .zip(new.harts)
+            .enumerate()
+            .map(|(hart, (old, new))| HartDiff {
+                hart,
+                registers: Specifier::iter_all()
+                    .filter_map(|specifier| {
+                        let changed =
+                            Changed::new(old.registers.x(specifier), new.registers.x(specifier));
+                        changed.map(|changed| (specifier, changed))
+                    })
+                    .collect(),
+                pc: Changed::new(old.registers.pc(), new.registers.pc()),
+                csrs: diff_sorted(old.csrs, new.csrs),
+                privilege_mode: Changed::new(old.privilege_mode, new.privilege_mode),
+            })
+            .filter(|hart| !hart.is_empty())
+            .collect();
+
+        let dram_base = self
+            .system_bus
+            .memory_map
+            .range_for(&Resource::Dram)
+            .unwrap()
+            .start();
+        let memory = context
+            .changed_ranges(self.system_bus.dram.data_id())
+            .into_iter()
+            .map(|range| {
+                AddressRange::new(
+                    dram_base + range.start as u32,
+                    dram_base + (range.end - 1) as u32,
+                )
+                .unwrap()
+            })
+            .collect();
+
+        BoardDiff {
+            harts,
+            devices: diff_sorted(old.devices, new.devices),
+            memory,
+        }
+    }
+}
+
+impl Board<SimulationAllocator> {
+    /// Returns `false` if physical memory in `range` was certainly not written in between both
+    /// states compared by `context`.
+    ///
+    /// Only RAM is tracked, so this returns `true` if `range` is not entirely within RAM.
+    pub fn may_have_written(&self, context: &DiffContext, range: AddressRange) -> bool {
+        let dram_range = self
+            .system_bus
+            .memory_map
+            .range_for(&Resource::Dram)
+            .unwrap();
+        if range.start() < dram_range.start() || range.end() > dram_range.end() {
+            return true;
+        }
+        let start = (range.start() - dram_range.start()) as usize;
+        let end = (range.end() - dram_range.start()) as usize + 1;
+        context
+            .written_ranges(self.system_bus.dram.data_id())
+            .iter()
+            .any(|written| written.start < end && start < written.end)
+    }
+
+    /// Read all device registers without side effects.
+    fn capture_devices(&self, allocator: &SimulationAllocator) -> Vec<(String, u64)> {
+        let bus = &self.system_bus;
+        let mut devices = Vec::new();
+        let mut add = |name: String, value: u64| devices.push((name, value));
+
+        for (index, uart) in bus.uarts.iter().enumerate() {
+            for (name, value) in [
+                ("ier", uart.read_ier_pure(allocator)),
+                ("iir", uart.read_iir_pure(allocator)),
+                ("lcr", uart.read_lcr_pure(allocator)),
+                ("lsr", uart.read_lsr_pure(allocator)),
+                ("msr", uart.read_msr_pure(allocator)),
+                ("dll", uart.read_dll_pure(allocator)),
+                ("dlh", uart.read_dlh_pure(allocator)),
+            ] {
+                add(format!("uart{index}.{name}"), value.into());
+            }
+        }
+
+        let clint = &bus.clint;
+        add("clint.mtime".to_owned(), clint.mtime(allocator));
+        for hart in 0..self.cores.len() as u32 {
+            let msip = clint.read_u32(allocator, msip_addr(hart));
+            add(format!("clint.msip[{hart}]"), msip.into());
+            let lo = clint.read_u32(allocator, mtimecmp_addr(hart));
+            let hi = clint.read_u32(allocator, mtimecmp_addr(hart) + 4);
+            add(
+                format!("clint.mtimecmp[{hart}]"),
+                (u64::from(hi) << 32) | u64::from(lo),
+            );
+        }
+
+        let read_plic = |address: u32| {
+            let mut buf = [0; 4];
+            // Unwrap safety: only registers of existing sources and contexts are read.
+            bus.plic.read_debug(&mut buf, allocator, address).unwrap();
+            u32::from_le_bytes(buf).into()
+        };
+        for source in 1..=plic::MAX_SOURCE {
+            add(format!("plic.priority[{source}]"), read_plic(4 * source));
+        }
+        for word in 0..PLIC_WORDS {
+            let address = plic::PENDING_BASE_ADDR + 4 * word;
+            add(format!("plic.pending[{word}]"), read_plic(address));
+        }
+        // Every hart has an M-mode and an S-mode context.
+        for context in 0..2 * self.cores.len() as u32 {
+            for word in 0..PLIC_WORDS {
+                let address = plic::ENABLES_BASE_ADDR + context * plic::ENABLES_STRIDE + 4 * word;
+                add(
+                    format!("plic.enable[{context}][{word}]"),
+                    read_plic(address),
+                );
+            }
+            let address = plic::THRESHOLD_ADDR + context * plic::CONTEXT_STRIDE;
+            add(format!("plic.threshold[{context}]"), read_plic(address));
+        }
+
+        add(
+            "power_down".to_owned(),
+            self.is_powered_down(allocator).into(),
+        );
+        devices
+    }
+}
+
+/// Compare two lists of values with the same keys in the same order.
+fn diff_sorted<K: PartialEq, T: PartialEq>(
+    old: Vec<(K, T)>,
+    new: Vec<(K, T)>,
+) -> Vec<(K, Changed<T>)> {
+    old.into_iter()
+        .zip(new)
+        .filter_map(|((key, old), (new_key, new))| {
+            debug_assert!(key == new_key);
+            Changed::new(old, new).map(|changed| (key, changed))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Config;
+    use crate::core::watchpoint::{WatchKind, Watchpoint, WatchpointHit};
+    use crate::simulator::snapshot_policy::FixedIntervalPolicy;
+    use crate::simulator::{Simulator, StepStop, UndoStepStopReason};
+
+    #[test]
+    fn diff_board() {
+        let program: [u32; 3] = [
+            0x800012B7, // lui    t0, 0x80001
+            0x0052A023, // sw     t0, 0(t0)
+            0x0000006F, // j      .
+        ];
+        let mut simulator = Simulator::new(|allocator| {
+            let board = Board::new(allocator, Config::default());
+            let bytes: Vec<u8> = program.iter().flat_map(|i| i.to_le_bytes()).collect();
+            board.load_physical(allocator, 0x8000_0000, &bytes);
+            board
+        });
+        // Run the reset vector in MROM, and the program.
+        for _ in 0..8 {
+            simulator.step();
+        }
+
+        let diff = simulator.diff(0, 8).unwrap();
+        let [hart] = diff.harts.as_slice() else {
+            panic!("expected exactly one changed hart");
+        };
+        assert_eq!(hart.hart, 0);
+        assert!(hart.registers.contains(&(
+            Specifier::new(5).unwrap(),
+            Changed {
+                old: 0,
+                new: 0x8000_1000
+            }
+        )));
+        assert_eq!(
+            hart.pc,
+            Some(Changed {
+                old: 0x1000,
+                new: 0x8000_0008
+            })
+        );
+        assert!(hart.privilege_mode.is_none());
+        assert!(diff
+            .devices
+            .contains(&("clint.mtime".to_owned(), Changed { old: 0, new: 8 })));
+        // Only the non-zero bytes of the stored word changed.
+        assert_eq!(
+            diff.memory,
+            vec![
+                AddressRange::new(0x8000_1001, 0x8000_1001).unwrap(),
+                AddressRange::new(0x8000_1003, 0x8000_1003).unwrap(),
+            ]
+        );
+
+        let diff = simulator.diff(8, 8).unwrap();
+        assert!(diff.is_empty());
+        assert_eq!(simulator.current_steps(), 8);
+    }
+
+    #[test]
+    fn reverse_to_write() {
+        let program: [u32; 4] = [
+            0x800012B7, // lui    t0, 0x80001
+            0x0052A023, // sw     t0, 0(t0)
+            0x0002A303, // lw     t1, 0(t0)
+            0x0000006F, // j      .
+        ];
+        let mut simulator = Simulator::new(|allocator| {
+            let board = Board::new(allocator, Config::default());
+            let bytes: Vec<u8> = program.iter().flat_map(|i| i.to_le_bytes()).collect();
+            board.load_physical(allocator, 0x8000_0000, &bytes);
+            board
+        });
+        simulator.set_snapshot_policy(FixedIntervalPolicy::new(2));
+        for _ in 0..100 {
+            simulator.step();
+        }
+
+        let watchpoint = Watchpoint {
+            address: 0x8000_1002,
+            len: 2,
+            kind: WatchKind::Write,
+        };
+        simulator.simulatable().core(0).add_watchpoint(watchpoint);
+        let range = AddressRange::new(0x8000_1002, 0x8000_1003).unwrap();
+        let mut replayed = 0;
+        let result = simulator.undo_steps_until_step(
+            |board, context| !board.may_have_written(context, range),
+            |_, board| {
+                replayed += 1;
+                let hit = board.core(0).take_watchpoint_hit()?;
+                Some((StepStop::Before, hit))
+            },
+            |_| {},
+        );
+
+        let UndoStepStopReason::Pred(hit) = result else {
+            panic!("expected to stop at the store");
+        };
+        assert_eq!(
+            hit,
+            WatchpointHit {
+                watchpoint,
+                address: 0x8000_1002,
+                write: true,
+            }
+        );
+        // Stopped right before the store, after the reset vector and the lui.
+        let (allocator, board) = simulator.inspect();
+        assert_eq!(board.core(0).registers(allocator).pc(), 0x8000_0004);
+        // Only the steps in between the two snapshots around the store were replayed.
+        assert_eq!(replayed, 2);
+    }
+}
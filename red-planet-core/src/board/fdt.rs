@@ -0,0 +1,416 @@
+//! Generation of the flattened device tree (FDT) that describes a board to the software it runs,
+//! such as OpenSBI, U-Boot and Linux.
+//!
+//! Resources:
+//! - <https://github.com/devicetree-org/devicetree-specification/releases/tag/v0.4>
+//! - <https://github.com/qemu/qemu/blob/master/hw/riscv/virt.c>
+
+use std::collections::BTreeMap;
+
+use super::config::{Config, POWER_DOWN_SIZE, UART_SIZE, VIRTIO_BLOCK_SIZE};
+use super::config::{CLINT_SIZE, PLIC_SIZE};
+use crate::core::clint;
+use crate::resources::plic;
+
+/// Magic number at the start of every device tree blob.
+pub const FDT_MAGIC: u32 = 0xD00D_FEED;
+const FDT_VERSION: u32 = 17;
+const FDT_LAST_COMP_VERSION: u32 = 16;
+const HEADER_SIZE: usize = 40;
+
+const FDT_BEGIN_NODE: u32 = 0x1;
+const FDT_END_NODE: u32 = 0x2;
+const FDT_PROP: u32 = 0x3;
+const FDT_END: u32 = 0x9;
+
+/// Frequency in Hz of the clock of the UARTs, which only matters for the baud rate the guest
+/// computes. This is the value QEMU uses.
+const UART_CLOCK_FREQUENCY: u32 = 3_686_400;
+
+/// Interrupt numbers of the local interrupt controller of a hart, as used in `interrupts-extended`.
+const IRQ_M_SOFT: u32 = 3;
+const IRQ_M_TIMER: u32 = 7;
+const IRQ_S_EXT: u32 = 9;
+const IRQ_M_EXT: u32 = 11;
+
+/// Generates the device tree blob describing the harts, memories and built-in devices of a board
+/// with the given configuration.
+///
+/// The flash and MROM are not described, since they are not RAM, and neither are user-defined
+/// devices, since their compatible strings are unknown.
+pub(super) fn generate(config: &Config) -> Vec<u8> {
+    let hart_count = config.harts.count.get() as u32;
+    // Phandles 1..=hart_count are the interrupt controllers of the harts.
+    let intc = |hart: u32| hart + 1;
+    let plic_phandle = hart_count + 1;
+    let test_phandle = hart_count + 2;
+
+    let mut fdt = FdtWriter::new();
+    fdt.begin_node("");
+    fdt.property_cells("#address-cells", &[2]);
+    fdt.property_cells("#size-cells", &[2]);
+    fdt.property_string("compatible", "redplanet");
+    fdt.property_string("model", "RedPlanet");
+
+    fdt.begin_node("chosen");
+    if let Some(uart) = config.devices.uarts.first() {
+        fdt.property_string("stdout-path", &format!("/soc/serial@{:x}", uart.base));
+    }
+    if let Some(bootargs) = &config.chosen.bootargs {
+        fdt.property_string("bootargs", bootargs);
+    }
+    if let Some(initrd) = config.chosen.initrd {
+        let end = initrd.base as u64 + initrd.size as u64;
+        fdt.property_cells("linux,initrd-start", &[0, initrd.base]);
+        fdt.property_cells("linux,initrd-end", &[(end >> 32) as u32, end as u32]);
+    }
+    fdt.end_node();
+
+    fdt.begin_node("cpus");
+    fdt.property_cells("#address-cells", &[1]);
+    fdt.property_cells("#size-cells", &[0]);
+    fdt.property_cells("timebase-frequency", &[clint::MTIME_FREQUENCY]);
+    let mut extensions = vec!["i", "m", "a", "f", "d"];
+    if !config.harts.strict_instruction_alignment {
+        extensions.push("c");
+    }
+    extensions.extend(["zicsr", "zifencei", "zicntr", "zba", "zbb", "zbc", "zbs"]);
+    let (single, multi): (Vec<&str>, Vec<&str>) = extensions.iter().partition(|ext| ext.len() == 1);
+    let isa = format!("rv32{}_{}", single.concat(), multi.join("_"));
+    for hart in 0..hart_count {
+        fdt.begin_node(&format!("cpu@{hart}"));
+        fdt.property_string("device_type", "cpu");
+        fdt.property_cells("reg", &[hart]);
+        fdt.property_string("status", "okay");
+        fdt.property_string("compatible", "riscv");
+        fdt.property_string("riscv,isa", &isa);
+        fdt.property_string("riscv,isa-base", "rv32i");
+        fdt.property_strings("riscv,isa-extensions", &extensions);
+        fdt.property_string("mmu-type", "riscv,sv32");
+        fdt.begin_node("interrupt-controller");
+        fdt.property_cells("#interrupt-cells", &[1]);
+        fdt.property("interrupt-controller", &[]);
+        fdt.property_string("compatible", "riscv,cpu-intc");
+        fdt.property_cells("phandle", &[intc(hart)]);
+        fdt.end_node();
+        fdt.end_node();
+    }
+    fdt.end_node();
+
+    let dram = config.memory.dram;
+    fdt.begin_node(&format!("memory@{:x}", dram.base));
+    fdt.property_string("device_type", "memory");
+    fdt.property_cells("reg", &reg(dram.base, dram.size));
+    fdt.end_node();
+
+    let devices = &config.devices;
+    fdt.begin_node("soc");
+    fdt.property_cells("#address-cells", &[2]);
+    fdt.property_cells("#size-cells", &[2]);
+    fdt.property_string("compatible", "simple-bus");
+    fdt.property("ranges", &[]);
+
+    fdt.begin_node(&format!("clint@{:x}", devices.clint.base));
+    fdt.property_strings("compatible", &["sifive,clint0", "riscv,clint0"]);
+    fdt.property_cells("reg", &reg(devices.clint.base, CLINT_SIZE));
+    let interrupts: Vec<_> = (0..hart_count)
+        .flat_map(|hart| [intc(hart), IRQ_M_SOFT, intc(hart), IRQ_M_TIMER])
+        .collect();
+    fdt.property_cells("interrupts-extended", &interrupts);
+    fdt.end_node();
+
+    fdt.begin_node(&format!("plic@{:x}", devices.plic.base));
+    fdt.property_strings("compatible", &["sifive,plic-1.0.0", "riscv,plic0"]);
+    fdt.property_cells("reg", &reg(devices.plic.base, PLIC_SIZE));
+    // Context `2 * hart` targets M-mode, and context `2 * hart + 1` targets S-mode.
+    let interrupts: Vec<_> = (0..hart_count)
+        .flat_map(|hart| [intc(hart), IRQ_M_EXT, intc(hart), IRQ_S_EXT])
+        .collect();
+    fdt.property_cells("interrupts-extended", &interrupts);
+    fdt.property_cells("riscv,ndev", &[plic::MAX_SOURCE]);
+    fdt.property_cells("#address-cells", &[0]);
+    fdt.property_cells("#interrupt-cells", &[1]);
+    fdt.property("interrupt-controller", &[]);
+    fdt.property_cells("phandle", &[plic_phandle]);
+    fdt.end_node();
+
+    if let Some(power_down) = devices.power_down {
+        fdt.begin_node(&format!("test@{:x}", power_down.base));
+        fdt.property_strings("compatible", &["sifive,test1", "sifive,test0", "syscon"]);
+        fdt.property_cells("reg", &reg(power_down.base, POWER_DOWN_SIZE));
+        fdt.property_cells("phandle", &[test_phandle]);
+        fdt.end_node();
+    }
+
+    for uart in &devices.uarts {
+        fdt.begin_node(&format!("serial@{:x}", uart.base));
+        fdt.property_string("compatible", "ns16550a");
+        fdt.property_cells("reg", &reg(uart.base, UART_SIZE));
+        fdt.property_cells("clock-frequency", &[UART_CLOCK_FREQUENCY]);
+        fdt.property_cells("interrupt-parent", &[plic_phandle]);
+        fdt.property_cells("interrupts", &[uart.irq]);
+        fdt.end_node();
+    }
+
+    for block in &devices.virtio_blocks {
+        fdt.begin_node(&format!("virtio_mmio@{:x}", block.base));
+        fdt.property_string("compatible", "virtio,mmio");
+        fdt.property_cells("reg", &reg(block.base, VIRTIO_BLOCK_SIZE));
+        fdt.property_cells("interrupt-parent", &[plic_phandle]);
+        fdt.property_cells("interrupts", &[block.irq]);
+        fdt.end_node();
+    }
+    fdt.end_node();
+
+    if devices.power_down.is_some() {
+        for (name, value) in [("poweroff", 0x5555), ("reboot", 0x7777)] {
+            fdt.begin_node(name);
+            fdt.property_string("compatible", &format!("syscon-{name}"));
+            fdt.property_cells("regmap", &[test_phandle]);
+            fdt.property_cells("offset", &[0]);
+            fdt.property_cells("value", &[value]);
+            fdt.end_node();
+        }
+    }
+
+    fdt.end_node();
+    fdt.finish()
+}
+
+/// Returns the cells of a `reg` property with two address and two size cells.
+fn reg(base: u32, size: u32) -> [u32; 4] {
+    [0, base, 0, size]
+}
+
+/// Writer of a device tree blob, in which nodes are written depth-first. Property names are
+/// stored only once in the strings block.
+#[derive(Debug, Default)]
+struct FdtWriter {
+    structure: Vec<u8>,
+    strings: Vec<u8>,
+    /// Offset in `strings` of every property name written so far.
+    string_offsets: BTreeMap<String, u32>,
+}
+
+impl FdtWriter {
+    fn new() -> Self {
+        Self::default()
+    }
+
+    fn token(&mut self, token: u32) {
+        self.structure.extend(token.to_be_bytes());
+    }
+
+    /// Pads the structure block with zeroes to a multiple of 4 bytes.
+    fn align(&mut self) {
+        let len = self.structure.len().next_multiple_of(4);
+        self.structure.resize(len, 0);
+    }
+
+    /// Starts a child of the current node, or the root node if `name` is empty.
+    fn begin_node(&mut self, name: &str) {
+        self.token(FDT_BEGIN_NODE);
+        self.structure.extend(name.as_bytes());
+        self.structure.push(0);
+        self.align();
+    }
+
+    fn end_node(&mut self) {
+        self.token(FDT_END_NODE);
+    }
+
+    fn property(&mut self, name: &str, value: &[u8]) {
+        let offset = match self.string_offsets.get(name) {
+            Some(&offset) => offset,
+            None => {
+                let offset = self.strings.len() as u32;
+                self.strings.extend(name.as_bytes());
+                self.strings.push(0);
+                self.string_offsets.insert(name.to_owned(), offset);
+                offset
+            }
+        };
+        self.token(FDT_PROP);
+        self.structure.extend((value.len() as u32).to_be_bytes());
+        self.structure.extend(offset.to_be_bytes());
+        self.structure.extend(value);
+        self.align();
+    }
+
+    fn property_cells(&mut self, name: &str, cells: &[u32]) {
+        let value: Vec<u8> = cells.iter().flat_map(|cell| cell.to_be_bytes()).collect();
+        self.property(name, &value);
+    }
+
+    fn property_string(&mut self, name: &str, value: &str) {
+        self.property_strings(name, &[value]);
+    }
+
+    fn property_strings(&mut self, name: &str, values: &[&str]) {
+        let value: Vec<u8> = values
+            .iter()
+            .flat_map(|value| value.bytes().chain([0]))
+            .collect();
+        self.property(name, &value);
+    }
+
+    /// Returns the blob: the header, an empty memory reservation block, the structure block and
+    /// the strings block.
+    fn finish(mut self) -> Vec<u8> {
+        self.token(FDT_END);
+        let reservations = [0; 16];
+        let off_mem_rsvmap = HEADER_SIZE;
+        let off_dt_struct = off_mem_rsvmap + reservations.len();
+        let off_dt_strings = off_dt_struct + self.structure.len();
+        let total_size = off_dt_strings + self.strings.len();
+
+        let header = [
+            FDT_MAGIC,
+            total_size as u32,
+            off_dt_struct as u32,
+            off_dt_strings as u32,
+            off_mem_rsvmap as u32,
+            FDT_VERSION,
+            FDT_LAST_COMP_VERSION,
+            // boot_cpuid_phys
+            0,
+            self.strings.len() as u32,
+            self.structure.len() as u32,
+        ];
+        let mut blob: Vec<u8> = header.iter().flat_map(|word| word.to_be_bytes()).collect();
+        blob.extend(reservations);
+        blob.extend(self.structure);
+        blob.extend(self.strings);
+        blob
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::config::{ChosenConfig, Region, UartConfig, VirtioBlockConfig};
+
+    /// Returns every property of the blob by its path, such as `/cpus/cpu@0/reg`.
+    fn properties(blob: &[u8]) -> BTreeMap<String, Vec<u8>> {
+        let word = |offset: usize| u32::from_be_bytes(blob[offset..offset + 4].try_into().unwrap());
+        assert_eq!(word(0), FDT_MAGIC);
+        assert_eq!(word(4) as usize, blob.len());
+        let strings = word(12) as usize;
+        let string = |offset: usize| {
+            let len = blob[offset..].iter().position(|&b| b == 0).unwrap();
+            String::from_utf8(blob[offset..offset + len].to_vec()).unwrap()
+        };
+
+        let mut properties = BTreeMap::new();
+        let mut path: Vec<String> = Vec::new();
+        let mut offset = word(8) as usize;
+        loop {
+            let token = word(offset);
+            offset += 4;
+            match token {
+                FDT_BEGIN_NODE => {
+                    let name = string(offset);
+                    offset = (offset + name.len() + 1).next_multiple_of(4);
+                    path.push(name);
+                }
+                FDT_END_NODE => {
+                    path.pop().unwrap();
+                }
+                FDT_PROP => {
+                    let len = word(offset) as usize;
+                    let name = string(strings + word(offset + 4) as usize);
+                    let value = blob[offset + 8..offset + 8 + len].to_vec();
+                    offset = (offset + 8 + len).next_multiple_of(4);
+                    properties.insert(format!("{}/{name}", path.join("/")), value);
+                }
+                FDT_END => break,
+                _ => panic!("invalid token {token:#x}"),
+            }
+        }
+        assert!(path.is_empty());
+        properties
+    }
+
+    fn cells(cells: &[u32]) -> Vec<u8> {
+        cells.iter().flat_map(|cell| cell.to_be_bytes()).collect()
+    }
+
+    #[test]
+    fn built_in_layout() {
+        let properties = properties(&generate(&Config::default()));
+        let get = |path: &str| properties.get(path).unwrap().as_slice();
+        assert_eq!(get("/chosen/stdout-path"), b"/soc/serial@10000000\0");
+        assert_eq!(get("/cpus/timebase-frequency"), cells(&[10_000_000]));
+        assert_eq!(
+            get("/cpus/cpu@0/riscv,isa"),
+            b"rv32imafdc_zicsr_zifencei_zicntr_zba_zbb_zbc_zbs\0"
+        );
+        assert_eq!(get("/cpus/cpu@0/interrupt-controller/phandle"), cells(&[1]));
+        assert_eq!(
+            get("/memory@80000000/reg"),
+            cells(&[0, 0x8000_0000, 0, 0x8000_0000])
+        );
+        assert_eq!(
+            get("/soc/clint@2000000/interrupts-extended"),
+            cells(&[1, 3, 1, 7])
+        );
+        assert_eq!(
+            get("/soc/plic@c000000/interrupts-extended"),
+            cells(&[1, 11, 1, 9])
+        );
+        assert_eq!(get("/soc/plic@c000000/phandle"), cells(&[2]));
+        assert_eq!(get("/soc/serial@10000000/interrupts"), cells(&[3]));
+        assert_eq!(get("/soc/serial@10000000/interrupt-parent"), cells(&[2]));
+        assert_eq!(get("/poweroff/regmap"), cells(&[3]));
+        assert_eq!(get("/reboot/value"), cells(&[0x7777]));
+        assert!(!properties.keys().any(|path| path.contains("virtio_mmio")));
+        assert!(!properties.contains_key("/chosen/bootargs"));
+    }
+
+    #[test]
+    fn configured_devices() {
+        let mut config = Config::default();
+        config.harts.count = 2.try_into().unwrap();
+        config.harts.strict_instruction_alignment = true;
+        config.devices.power_down = None;
+        config.chosen = ChosenConfig {
+            bootargs: Some("console=ttyS0".to_owned()),
+            initrd: Some(Region {
+                base: 0x8840_0000,
+                size: 0x1000,
+            }),
+        };
+        config.devices.uarts.push(UartConfig {
+            base: 0x1000_0100,
+            irq: 4,
+        });
+        config.devices.virtio_blocks.push(VirtioBlockConfig {
+            base: 0x1000_1000,
+            irq: 8,
+            image: "disk.img".into(),
+        });
+        let properties = properties(&generate(&config));
+        let get = |path: &str| properties.get(path).unwrap().as_slice();
+        assert_eq!(
+            get("/cpus/cpu@1/riscv,isa"),
+            b"rv32imafd_zicsr_zifencei_zicntr_zba_zbb_zbc_zbs\0"
+        );
+        assert_eq!(get("/cpus/cpu@1/reg"), cells(&[1]));
+        assert_eq!(get("/chosen/bootargs"), b"console=ttyS0\0");
+        assert_eq!(get("/chosen/linux,initrd-start"), cells(&[0, 0x8840_0000]));
+        assert_eq!(get("/chosen/linux,initrd-end"), cells(&[0, 0x8840_1000]));
+        assert_eq!(
+            get("/soc/plic@c000000/interrupts-extended"),
+            cells(&[1, 11, 1, 9, 2, 11, 2, 9])
+        );
+        assert_eq!(get("/soc/plic@c000000/phandle"), cells(&[3]));
+        assert_eq!(get("/soc/serial@10000100/interrupts"), cells(&[4]));
+        assert_eq!(get("/soc/virtio_mmio@10001000/interrupts"), cells(&[8]));
+        assert_eq!(
+            get("/soc/virtio_mmio@10001000/compatible"),
+            b"virtio,mmio\0"
+        );
+        assert!(!properties.keys().any(|path| path.contains("test@")));
+        assert!(!properties.contains_key("/poweroff/regmap"));
+    }
+}
@@ -1,197 +1,454 @@
 //! Provides a generic board built around the SiFive FE310-G002 SoC.
 
+mod config;
+mod device;
+pub mod diff;
+mod fdt;
 mod system_bus;
+#[cfg(test)]
+pub(crate) mod testing;
 
-use crate::bus::Bus;
-use crate::core::clint::{Clint, MTIMECMP_ADDR_LO, MTIME_ADDR_LO};
+use crate::address_map::TwoWayAddressMap;
+use crate::bus::{Bus, BusError};
+use crate::core::clint::{mtimecmp_addr, Clint, HartInterrupts, MTIME_ADDR_LO};
 use crate::core::{Core, Interrupt};
+use crate::resources::htif::Htif;
 use crate::resources::plic::Plic;
 use crate::resources::ram::Ram;
 use crate::resources::rom::Rom;
+use crate::resources::semihosting::Semihosting;
 use crate::resources::uart::Uart;
+use crate::resources::virtio_block::{Disk, VirtioBlock};
 use crate::simulator::Simulatable;
 use crate::system_bus::AccessType;
-use crate::{two_way_addr_map, Allocated, Allocator, Endianness};
+use crate::{Allocated, Allocator, Endianness, PrivilegeLevel};
 use log::{debug, trace};
+use std::cell::OnceCell;
+use std::num::NonZeroU32;
 use std::ops::Deref;
-use std::rc::Rc;
+use std::rc::{Rc, Weak};
 use system_bus::{Resource, SystemBus};
 
-#[derive(Debug, Clone)]
-pub struct Config {
-    /// If `true`, the reset vector in MROM will jump to flash, otherwise to the start of RAM.
-    pub boot_to_flash: bool,
-    /// M-mode endianness
-    pub endianness: Endianness,
-    /// Contents of flash (max 64 MiB)
-    pub flash: Vec<u8>,
-}
+pub use device::{Device, DeviceRegistration};
 
-impl Default for Config {
-    fn default() -> Self {
-        Self {
-            boot_to_flash: false,
-            endianness: Endianness::LE,
-            flash: Vec::default(),
-        }
-    }
-}
+pub use config::{
+    BootMode, ChosenConfig, Config, ConfigError, DeviceConfig, DeviceRegion, HartConfig,
+    HtifConfig, MemoryConfig, Region, SemihostingConfig, UartConfig, VirtioBlockConfig,
+};
 
 /// RISC-V hardware platform representing a board built around the SiFive FE310-G002 SoC.
 ///
-/// This currently is a single-core board, with a single-hart core.
-/// Multiprocessing and hardware multithreading are not supported.
+/// The board has [`HartConfig::count`] single-hart cores sharing the system bus. Harts are
+/// scheduled round-robin: each board step executes a single instruction on the scheduled hart, and
+/// after [`HartConfig::quantum`] instructions the next hart is scheduled. Since harts never execute
+/// concurrently, every instruction (including AMOs) is atomic with respect to all other harts.
+///
+/// Every hart has its own msip and mtimecmp registers in the CLINT, and two PLIC contexts:
+/// context `2 * hart` targets M-mode, and context `2 * hart + 1` targets S-mode.
+///
+/// The memory map and built-in devices are described by a [`Config`]. Crates depending on this one
+/// can attach their own devices with [`Board::with_devices`].
 ///
 /// > A RISC-V hardware platform can contain one or more RISC-V-compatible processing cores together
 /// > with other non-RISC-V-compatible cores, fixed-function accelerators, various physical memory
 /// > structures, I/O devices, and an interconnect structure to allow the components to communicate.
 #[derive(Debug)]
 pub struct Board<A: Allocator> {
-    /// The single core of this board. Multiprocessing is not supported.
-    core: Rc<Core<A, Interconnect<A>>>,
+    /// The harts of this board, indexed by hart ID.
+    cores: Vec<Rc<BoardCore<A>>>,
     system_bus: Rc<SystemBus<A>>,
+    schedule: Allocated<A, Schedule>,
+    config: Config,
+    /// Address and contents of the images set with [`Board::set_boot_images`], which are written
+    /// again by [`Board::reset`].
+    boot_images: OnceCell<Vec<(u32, Vec<u8>)>>,
+}
+
+/// Round-robin scheduling state of the harts of a [`Board`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Schedule {
+    /// Hart that executes the next instruction.
+    hart: usize,
+    /// Number of instructions `hart` executed since it was scheduled.
+    executed: u32,
+}
+
+impl Schedule {
+    fn next(self, quantum: NonZeroU32, hart_count: usize) -> Self {
+        if self.executed + 1 < quantum.get() {
+            Self {
+                executed: self.executed + 1,
+                ..self
+            }
+        } else {
+            Self {
+                hart: (self.hart + 1) % hart_count,
+                executed: 0,
+            }
+        }
+    }
 }
 
 impl<A: Allocator> Board<A> {
+    /// Creates a board as described by `config`.
+    ///
+    /// Panics if `config` is invalid, see [`Config::validate`].
     pub fn new(allocator: &mut A, config: Config) -> Self {
-        debug!("Creating board with config {config:?}");
-
-        let memory_map = two_way_addr_map! {
-            [0x0000_1000, 0x0000_FFFF] <=> Resource::Mrom,
-            [0x0010_0000, 0x0010_0003] <=> Resource::PowerDown,
-            [0x0200_0000, 0x0200_FFFF] <=> Resource::Clint,
-            [0x0C00_0000, 0x0C20_0FFF] <=> Resource::Plic,
-            [0x1000_0000, 0x1000_00FF] <=> Resource::Uart0,
-            [0x2000_0000, 0x23FF_FFFF] <=> Resource::Flash,
-            [0x8000_0000, 0xFFFF_FFFF] <=> Resource::Dram,
-        };
+        Self::with_devices(allocator, config, Vec::new())
+            .unwrap_or_else(|e| panic!("invalid board config: {e}"))
+    }
+
+    /// Creates a board as described by `config`, with the user-defined `devices` attached.
+    ///
+    /// Fails if `config` is invalid, or if a device does not fit in the address space, overlaps
+    /// with another memory or device, or shares an IRQ. Device `i` can be accessed with
+    /// [`Self::device`]`(i)`.
+    pub fn with_devices(
+        allocator: &mut A,
+        config: Config,
+        devices: Vec<DeviceRegistration<A>>,
+    ) -> Result<Self, ConfigError> {
+        debug!("Creating board with config {config:?} and devices {devices:?}");
+
+        let mappings: Vec<_> = devices.iter().map(|device| &device.mapping).collect();
+        config.validate_with(&mappings)?;
+        // Unwrap safety: validation succeeded, so all regions are valid and do not overlap.
+        let regions = config.regions(&mappings).unwrap();
+        let memory_map = TwoWayAddressMap::try_from(
+            regions
+                .into_iter()
+                .map(|(_, range, resource)| (range, resource))
+                .collect::<Vec<_>>(),
+        )
+        .unwrap();
 
         let mrom_range = memory_map.range_for(&Resource::Mrom).unwrap();
         let clint_range = memory_map.range_for(&Resource::Clint).unwrap();
-        let flash_range = memory_map.range_for(&Resource::Flash).unwrap();
-        let dram_range = memory_map.range_for(&Resource::Dram).unwrap();
-
-        let start_address: u32 = if config.boot_to_flash {
-            flash_range.start()
-        } else {
-            dram_range.start()
-        };
 
+        // The reset vector passes the hart ID in a0 and the address of the device tree in a1, which
+        // is the boot protocol expected by OpenSBI, U-Boot and Linux.
+        let device_tree_offset = config.device_tree_offset();
         let reset_vector = {
             let s: [u8; 4] = match config.endianness {
-                Endianness::LE => start_address.to_le_bytes(),
-                Endianness::BE => start_address.to_be_bytes(),
+                Endianness::LE => config.start_address().to_le_bytes(),
+                Endianness::BE => config.start_address().to_be_bytes(),
             };
+            let a1 = (0x0002_8593 | device_tree_offset << 20).to_le_bytes();
             [
                 0x97, 0x02, 0x00, 0x00, // auipc  t0, 0x0
+                a1[0], a1[1], a1[2], a1[3], // addi   a1, t0, device_tree_offset
                 0x73, 0x25, 0x40, 0xf1, // csrr   a0, mhartid
-                0x83, 0xa2, 0x02, 0x01, // lw     t0, 16(t0)
+                0x83, 0xa2, 0x42, 0x01, // lw     t0, 20(t0)
                 0x67, 0x80, 0x02, 0x00, // jr     t0
                 s[0], s[1], s[2], s[3], // .word start_address
             ]
         };
+        let mut mrom_contents = reset_vector.to_vec();
+        mrom_contents.resize(device_tree_offset as usize, 0);
+        mrom_contents.extend(config.device_tree());
+
+        let disks = (0..config.devices.virtio_blocks.len())
+            .map(|index| {
+                let image = config.open_image(index)?;
+                Disk::new(Box::new(image))
+                    .map_err(|e| ConfigError::Image(format!("virtio_block{index}"), e))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let harts = &config.harts;
+        let hart_count = harts.count.get();
 
-        let core = Rc::new_cyclic(|weak| {
-            let mrom = Rom::new(allocator, mrom_range.size().unwrap(), &reset_vector).unwrap();
+        let system_bus = Rc::new_cyclic(|weak_bus: &Weak<SystemBus<A>>| {
+            let mrom = Rom::new(allocator, mrom_range.size().unwrap(), &mrom_contents).unwrap();
 
-            let callback = Core::get_irq_callback(weak.clone(), Interrupt::MachineTimerInterrupt);
-            let clint = Clint::new(allocator, callback);
+            let harts = (0..hart_count)
+                .map(|hart| HartInterrupts {
+                    timer: SystemBus::get_hart_irq_callback(
+                        weak_bus.clone(),
+                        hart,
+                        Interrupt::MachineTimerInterrupt,
+                    ),
+                    software: SystemBus::get_hart_irq_callback(
+                        weak_bus.clone(),
+                        hart,
+                        Interrupt::MachineSoftwareInterrupt,
+                    ),
+                })
+                .collect();
+            let clint = Clint::new(allocator, harts);
 
-            let callback =
-                Core::get_irq_callback(weak.clone(), Interrupt::MachineExternalInterrupt);
-            let plic = Plic::new(allocator, callback);
+            let contexts = (0..hart_count)
+                .flat_map(|hart| {
+                    [
+                        Interrupt::MachineExternalInterrupt,
+                        Interrupt::SupervisorExternalInterrupt,
+                    ]
+                    .map(|code| SystemBus::get_hart_irq_callback(weak_bus.clone(), hart, code))
+                })
+                .collect();
+            let plic = Plic::new(allocator, contexts);
 
-            let flash = Rom::new(allocator, flash_range.size().unwrap(), &config.flash).unwrap();
+            let flash = memory_map
+                .range_for(&Resource::Flash)
+                .map(|range| Rom::new(allocator, range.size().unwrap(), &config.flash).unwrap());
 
+            let dram_range = memory_map.range_for(&Resource::Dram).unwrap();
             let dram = Ram::new(allocator, dram_range.size().unwrap()).unwrap();
 
             let power_down = PowerDown::new(allocator);
 
-            let system_bus = Rc::new_cyclic(|weak_bus| {
-                let callback = SystemBus::get_plic_irq_callback(weak_bus.clone(), 3);
-                let uart0 = Uart::new(allocator, callback);
-
-                SystemBus {
-                    memory_map,
-                    mrom,
-                    clint,
-                    plic,
-                    uart0,
-                    flash,
-                    dram,
-                    power_down,
-                }
-            });
+            let uarts = config
+                .devices
+                .uarts
+                .iter()
+                .map(|uart| {
+                    let callback = SystemBus::get_plic_irq_callback(weak_bus.clone(), uart.irq);
+                    Uart::new(allocator, callback)
+                })
+                .collect();
+
+            let virtio_blocks = (config.devices.virtio_blocks.iter())
+                .zip(disks)
+                .map(|(block, disk)| {
+                    let dma = SystemBus::get_dma_bus(weak_bus.clone());
+                    let callback = SystemBus::get_plic_irq_callback(weak_bus.clone(), block.irq);
+                    VirtioBlock::new(allocator, disk, dma, callback)
+                })
+                .collect();
+
+            let devices = devices
+                .into_iter()
+                .map(|device| {
+                    let callbacks = (device.mapping.irqs.iter())
+                        .map(|&irq| SystemBus::get_plic_irq_callback(weak_bus.clone(), irq))
+                        .collect();
+                    device.build(allocator, callbacks)
+                })
+                .collect();
 
-            Core::new(
-                allocator,
-                Rc::clone(&system_bus),
-                crate::core::Config {
-                    // At least one Hart must have ID 0 according to the spec.
-                    hart_id: 0,
-                    mtime_address: clint_range.start() + MTIME_ADDR_LO,
-                    mtimecmp_address: clint_range.start() + MTIMECMP_ADDR_LO,
-                    support_misaligned_memory_access: true,
-                    strict_instruction_alignment: false,
-                    reset_vector: mrom_range.start(),
-                    // TODO: Research what address QEMU virt uses for this.
-                    nmi_vector: mrom_range.start(),
-                },
-            )
+            SystemBus {
+                memory_map,
+                mrom,
+                clint,
+                plic,
+                uarts,
+                virtio_blocks,
+                flash,
+                dram,
+                power_down,
+                devices,
+                htif: (config.htif).map(|htif| Htif::new(allocator, htif.tohost, htif.fromhost)),
+                semihosting: (config.semihosting.clone()).map(|semihosting| {
+                    Semihosting::new(
+                        allocator,
+                        semihosting.root,
+                        semihosting.cmdline,
+                        semihosting.supervisor,
+                        semihosting.start_time,
+                    )
+                }),
+                harts: OnceCell::new(),
+            }
         });
 
-        let system_bus = core.system_bus().clone();
+        let cores: Vec<_> = (0..hart_count)
+            .map(|hart| {
+                Rc::new(Core::new(
+                    allocator,
+                    Rc::clone(&system_bus),
+                    crate::core::Config {
+                        // At least one Hart must have ID 0 according to the spec.
+                        hart_id: hart as u32,
+                        mtime_address: clint_range.start() + MTIME_ADDR_LO,
+                        mtimecmp_address: clint_range.start() + mtimecmp_addr(hart as u32),
+                        support_misaligned_memory_access: harts.support_misaligned_memory_access,
+                        strict_instruction_alignment: harts.strict_instruction_alignment,
+                        reset_vector: mrom_range.start(),
+                        // TODO: Research what address QEMU virt uses for this.
+                        nmi_vector: mrom_range.start(),
+                        sc_failure_interval: harts.sc_failure_interval,
+                        pmp_entries: harts.pmp_entries,
+                        trigger_count: harts.trigger_count,
+                        stall_on_wfi: harts.idle_skip,
+                    },
+                ))
+            })
+            .collect();
+
+        // Unwrap safety: the harts are only set here, right after creating the system bus.
+        system_bus
+            .harts
+            .set(cores.iter().map(Rc::downgrade).collect())
+            .unwrap();
 
-        Self { core, system_bus }
+        Ok(Self {
+            cores,
+            system_bus,
+            schedule: Allocated::new(allocator, Schedule::default()),
+            config,
+            boot_images: OnceCell::new(),
+        })
     }
 
     pub fn drop(self, allocator: &mut A) {
-        // Unwrap safety: There should only be weak ptrs to the `core`.
-        Rc::into_inner(self.core).unwrap().drop(allocator);
+        for core in self.cores {
+            // Unwrap safety: There should only be weak ptrs to the `core`.
+            Rc::into_inner(core).unwrap().drop(allocator);
+        }
 
-        // Unwrap safety: `core` is the only other owner and it has been dropped in the line above.
+        // Unwrap safety: The cores are the only other owners and they have been dropped above.
         Rc::into_inner(self.system_bus).unwrap().drop(allocator);
+
+        self.schedule.drop(allocator);
+    }
+
+    /// Returns the configuration this board was created with.
+    pub fn config(&self) -> &Config {
+        &self.config
     }
 
-    pub fn core(&self) -> &Core<A, impl crate::system_bus::SystemBus<A>> {
-        &self.core
+    /// Number of harts on this board.
+    pub fn hart_count(&self) -> usize {
+        self.cores.len()
+    }
+
+    /// Returns the core of the hart with ID `hart`.
+    ///
+    /// Panics if `hart` is not less than [`Board::hart_count`].
+    pub fn core(&self, hart: usize) -> &Core<A, impl crate::system_bus::SystemBus<A>> {
+        &self.cores[hart]
+    }
+
+    /// Returns the core of the hart with ID `hart`, or `None` if there is no such hart.
+    pub fn get_core(&self, hart: usize) -> Option<&Core<A, impl crate::system_bus::SystemBus<A>>> {
+        self.cores.get(hart).map(|core| &**core)
+    }
+
+    /// Returns `true` if all harts are stalled by WFI, waiting for an interrupt.
+    pub fn is_sleeping(&self, allocator: &A) -> bool {
+        self.cores
+            .iter()
+            .all(|core| core.is_waiting_for_interrupt(allocator))
+    }
+
+    /// Returns the ID of the hart that will execute the next instruction.
+    pub fn scheduled_hart(&self, allocator: &A) -> usize {
+        self.schedule.get(allocator).hart
     }
 
     pub fn mrom(&self) -> &Rom<A> {
         &self.system_bus.mrom
     }
 
-    pub fn flash(&self) -> &Rom<A> {
-        &self.system_bus.flash
+    /// Returns the flash of this board, if it has one.
+    pub fn flash(&self) -> Option<&Rom<A>> {
+        self.system_bus.flash.as_ref()
     }
 
     pub fn dram(&self) -> &Ram<A> {
         &self.system_bus.dram
     }
 
-    pub fn uart0(&self) -> &Uart<A> {
-        &self.system_bus.uart0
+    /// Returns the user-defined device with the given index in the devices passed to
+    /// [`Self::with_devices`], if there is one and it has type `D`.
+    pub fn device<D: Device<A>>(&self, index: usize) -> Option<&D> {
+        self.system_bus.devices.get(index)?.as_any().downcast_ref()
+    }
+
+    /// Returns the HTIF of the loaded program, if [`Config::htif`] is set.
+    pub fn htif(&self) -> Option<&Htif<A>> {
+        self.system_bus.htif.as_ref()
+    }
+
+    /// Returns the host side of semihosting, if [`Config::semihosting`] is set.
+    pub fn semihosting(&self) -> Option<&Semihosting<A>> {
+        self.system_bus.semihosting.as_ref()
+    }
+
+    /// Returns the UART with the given index in [`DeviceConfig::uarts`], if there is one.
+    pub fn uart(&self, index: usize) -> Option<&Uart<A>> {
+        self.system_bus.uarts.get(index)
+    }
+
+    /// Returns the virtio block device with the given index in [`DeviceConfig::virtio_blocks`], if
+    /// there is one.
+    pub fn virtio_block(&self, index: usize) -> Option<&VirtioBlock<A>> {
+        self.system_bus.virtio_blocks.get(index)
     }
 
     /// Force board back to its reset state. Matches a hardware reset, meaning this is **not**
     /// equivalent to replacing this with [`Board::new`]. For example, some registers may not be
     /// cleared.
+    ///
+    /// DRAM is cleared, after which the images set with [`Board::set_boot_images`] are loaded
+    /// again, like QEMU does with the images it loads. This way the guest starts from the same
+    /// program, kernel, and initrd. The CLINT, PLIC and the other devices are reset as well.
     pub fn reset(&self, allocator: &mut A) {
-        self.core.reset(allocator);
+        for core in &self.cores {
+            core.reset(allocator);
+        }
+        *self.schedule.get_mut(allocator) = Schedule::default();
         self.system_bus.dram.reset(allocator);
-        self.system_bus.uart0.reset(allocator);
+        for (base_address, buf) in self.boot_images.get().into_iter().flatten() {
+            self.load_physical(allocator, *base_address, buf);
+        }
+        self.system_bus.clint.reset(allocator);
+        self.system_bus.plic.reset(allocator);
+        if let Some(htif) = &self.system_bus.htif {
+            htif.reset(allocator);
+        }
+        if let Some(semihosting) = &self.system_bus.semihosting {
+            semihosting.reset(allocator);
+        }
+        for uart in &self.system_bus.uarts {
+            uart.reset(allocator);
+        }
+        for virtio_block in &self.system_bus.virtio_blocks {
+            virtio_block.reset(allocator);
+        }
+        for device in &self.system_bus.devices {
+            device.reset(allocator);
+        }
+        self.system_bus.power_down.reset(allocator);
     }
 
-    /// Power down the board. This makes ticks do nothing.
+    /// Power down the board, as if the guest passed. This makes ticks do nothing.
     pub fn power_down(&self, allocator: &mut A) {
-        self.system_bus.power_down.power_down(allocator);
+        self.system_bus
+            .power_down
+            .power_down(allocator, ExitStatus::Pass);
     }
 
     pub fn is_powered_down(&self, allocator: &A) -> bool {
-        self.system_bus.power_down.is_powered_down(allocator)
+        self.exit_status(allocator).is_some()
+    }
+
+    /// Returns how the board was powered down, or `None` if it is still running.
+    pub fn exit_status(&self, allocator: &A) -> Option<ExitStatus> {
+        match *self.system_bus.power_down.0.get(allocator) {
+            FinisherState::PoweredDown(status) => Some(status),
+            FinisherState::Running | FinisherState::ResetRequested => None,
+        }
     }
 
-    /// Write a byte buffer into the physical address space.
+    /// Loads the images the board boots from, given as base address and contents, and keeps them
+    /// so they are loaded again whenever the board is [reset](Self::reset).
+    ///
+    /// This must be called while building the board, before it is first stepped, since the images
+    /// are kept outside of the simulated state.
+    ///
+    /// Panics if the boot images were set before.
+    pub fn set_boot_images(&self, allocator: &mut A, images: Vec<(u32, Vec<u8>)>) {
+        for (base_address, buf) in &images {
+            self.load_physical(allocator, *base_address, buf);
+        }
+        if self.boot_images.set(images).is_err() {
+            panic!("the boot images of a board can only be set once");
+        }
+    }
+
+    /// Write a byte buffer into the physical address space. Unlike the images set with
+    /// [`Self::set_boot_images`], the buffer is not written again when the board is reset.
     ///
     /// Bytes written to vacant, read-only, or I/O regions are ignored.
     pub fn load_physical(&self, allocator: &mut A, base_address: u32, buf: &[u8]) {
@@ -223,13 +480,16 @@ impl<A: Allocator> Board<A> {
                          {address:#010x} through system bus"
                     );
                     let slice = &buf[slice_start..=slice_end];
-                    self.system_bus.write(allocator, address, slice);
+                    // Unwrap safety: DRAM accepts all writes within its range.
+                    self.system_bus.write(allocator, address, slice).unwrap();
                 }
                 // Skip read-only
                 Resource::Mrom => {}
                 Resource::Flash => {}
                 // Skip MMIO
-                Resource::Uart0 => {}
+                Resource::Uart(_) => {}
+                Resource::VirtioBlock(_) => {}
+                Resource::Device(_) => {}
                 Resource::Clint => {}
                 Resource::Plic => {}
                 Resource::PowerDown => {}
@@ -237,15 +497,50 @@ impl<A: Allocator> Board<A> {
         }
     }
 
-    /// Step the single core of this board once, if the board is not powered down.
+    /// Step the scheduled hart of this board once, if the board is not powered down.
+    ///
+    /// mtime is incremented once per board step, regardless of the number of harts. If
+    /// [`HartConfig::idle_skip`] is enabled and all harts are stalled by WFI, mtime is instead advanced
+    /// to the next timer event.
+    ///
+    /// If the guest requested a reset through the power-down device, the board is [reset] at the
+    /// end of the step. If the board has a HTIF, its command is handled at the end of the step, and
+    /// an exit through it powers down the board.
+    ///
+    /// [reset]: Self::reset
     pub fn step(&self, allocator: &mut A) {
         if self.is_powered_down(allocator) {
             trace!("Not stepping board as it is powered down");
             return;
         }
-        trace!("Stepping board");
-        self.core.step(allocator);
-        self.system_bus.clint.step(allocator);
+        let schedule = *self.schedule.get(allocator);
+        trace!("Stepping board on hart {}", schedule.hart);
+        self.cores[schedule.hart].step(allocator);
+        if self.config.harts.idle_skip && self.is_sleeping(allocator) {
+            self.system_bus.clint.skip_to_next_event(allocator);
+        } else {
+            self.system_bus.clint.step(allocator);
+        }
+        for device in &self.system_bus.devices {
+            device.tick(allocator);
+        }
+        if let Some(code) = (self.system_bus.htif.as_ref())
+            .and_then(|htif| htif.poll(allocator, self.system_bus.as_ref()))
+        {
+            debug!("Powering down board because of a HTIF exit with code {code}");
+            let status = ExitStatus::from_code(code);
+            self.system_bus.power_down.power_down(allocator, status);
+        }
+        if *self.system_bus.power_down.0.get(allocator) == FinisherState::ResetRequested {
+            debug!("Resetting board because 0x7777 was written");
+            self.reset(allocator);
+            return;
+        }
+        let next = schedule.next(self.config.harts.quantum, self.cores.len());
+        // Avoid touching the allocated state if there is nothing to schedule.
+        if next != schedule {
+            *self.schedule.get_mut(allocator) = next;
+        }
     }
 }
 
@@ -261,16 +556,19 @@ impl<A: Allocator> Simulatable<A> for Board<A> {
 
 type Interconnect<A> = Rc<SystemBus<A>>;
 
+/// Core of a single hart of a [`Board`].
+type BoardCore<A> = Core<A, Interconnect<A>>;
+
 impl<A: Allocator> Bus<A> for Interconnect<A> {
-    fn read(&self, buf: &mut [u8], allocator: &mut A, address: u32) {
+    fn read(&self, buf: &mut [u8], allocator: &mut A, address: u32) -> Result<(), BusError> {
         self.deref().read(buf, allocator, address)
     }
 
-    fn read_debug(&self, buf: &mut [u8], allocator: &A, address: u32) {
+    fn read_debug(&self, buf: &mut [u8], allocator: &A, address: u32) -> Result<(), BusError> {
         self.deref().read_debug(buf, allocator, address)
     }
 
-    fn write(&self, allocator: &mut A, address: u32, buf: &[u8]) {
+    fn write(&self, allocator: &mut A, address: u32, buf: &[u8]) -> Result<(), BusError> {
         self.deref().write(allocator, address, buf)
     }
 }
@@ -279,42 +577,728 @@ impl<A: Allocator> crate::system_bus::SystemBus<A> for Interconnect<A> {
     fn accepts(&self, address: u32, size: usize, access_type: AccessType) -> bool {
         self.deref().accepts(address, size, access_type)
     }
+
+    fn semihosting_call(
+        &self,
+        allocator: &mut A,
+        memory: &dyn Bus<A>,
+        privilege: PrivilegeLevel,
+        operation: u32,
+        parameter: u32,
+    ) -> Option<u32> {
+        (self.deref()).semihosting_call(allocator, memory, privilege, operation, parameter)
+    }
 }
 
+/// How the guest powered down the board, see [`Board::exit_status`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitStatus {
+    /// `0x5555` was written to the power-down device, or the board was powered down by
+    /// [`Board::power_down`].
+    Pass,
+    /// `0x3333 | code << 16` was written to the power-down device.
+    Fail(u16),
+}
+
+impl ExitStatus {
+    /// Returns the status of a guest that exited with `code` through HTIF or semihosting, where
+    /// zero means it passed.
+    ///
+    /// Codes that do not fit in 16 bits saturate to `u16::MAX`, rather than keeping only their
+    /// lower bits, which could make them look like a different failure, or zero.
+    fn from_code(code: u64) -> Self {
+        match code {
+            0 => Self::Pass,
+            _ => Self::Fail(u16::try_from(code).unwrap_or(u16::MAX)),
+        }
+    }
+
+    /// Returns the exit code of a process reporting this status: 0 for [`Self::Pass`], and the
+    /// lowest byte of the code for [`Self::Fail`], but at least 1 so a failure never looks like a
+    /// pass.
+    pub fn exit_code(self) -> u8 {
+        match self {
+            Self::Pass => 0,
+            Self::Fail(code) => (code as u8).max(1),
+        }
+    }
+}
+
+/// State of the [`PowerDown`] device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FinisherState {
+    Running,
+    /// `0x7777` was written, and the board resets at the end of the current step.
+    ResetRequested,
+    PoweredDown(ExitStatus),
+}
+
+/// SiFive test finisher, which the guest uses to power down or reset the board.
+///
+/// Only the lowest 16 bits of a write select the action, writes of other values are ignored:
+/// - `0x3333`: power down with [`ExitStatus::Fail`], with the upper 16 bits as code.
+/// - `0x5555`: power down with [`ExitStatus::Pass`].
+/// - `0x7777`: reset the board, see [`Board::reset`].
 #[derive(Debug)]
-struct PowerDown<A: Allocator>(Allocated<A, bool>);
+struct PowerDown<A: Allocator>(Allocated<A, FinisherState>);
 
 impl<A: Allocator> PowerDown<A> {
+    const FAIL: u16 = 0x3333;
+    const PASS: u16 = 0x5555;
+    const RESET: u16 = 0x7777;
+
     fn new(allocator: &mut A) -> Self {
-        Self(Allocated::new(allocator, false))
+        Self(Allocated::new(allocator, FinisherState::Running))
     }
 
-    fn power_down(&self, allocator: &mut A) {
-        *self.0.get_mut(allocator) = true;
+    fn power_down(&self, allocator: &mut A, status: ExitStatus) {
+        *self.0.get_mut(allocator) = FinisherState::PoweredDown(status);
     }
 
-    fn is_powered_down(&self, allocator: &A) -> bool {
-        *self.0.get(allocator)
+    fn reset(&self, allocator: &mut A) {
+        // Avoid touching the allocated state if it does not change.
+        if *self.0.get(allocator) != FinisherState::Running {
+            *self.0.get_mut(allocator) = FinisherState::Running;
+        }
     }
 }
 
 impl<A: Allocator> Bus<A> for PowerDown<A> {
-    fn read(&self, _buf: &mut [u8], _allocator: &mut A, _address: u32) {
-        // Reads are not supported, so do nothing.
+    fn read(&self, buf: &mut [u8], allocator: &mut A, address: u32) -> Result<(), BusError> {
+        self.read_debug(buf, allocator, address)
     }
 
-    fn read_debug(&self, _buf: &mut [u8], _allocator: &A, _address: u32) {
-        // Reads are not supported, so do nothing.
+    /// Reads as zero, like in QEMU. The syscon-poweroff and syscon-reboot drivers of Linux read the
+    /// register before writing it.
+    fn read_debug(&self, buf: &mut [u8], _allocator: &A, _address: u32) -> Result<(), BusError> {
+        buf.fill(0);
+        Ok(())
     }
 
-    fn write(&self, allocator: &mut A, address: u32, buf: &[u8]) {
+    fn write(&self, allocator: &mut A, address: u32, buf: &[u8]) -> Result<(), BusError> {
         // Ignore address, since it should be in the range 0x0..0x4, and the behavior is to round
         // down the address to the closest 4-byte aligned address.
         let _ = address;
-        // If the lower 2 bytes are both 0x55, then we will power down.
-        if let Some(&[0x55, 0x55]) = buf.get(..2) {
-            debug!("Powering down board because 0x5555 was written");
-            self.power_down(allocator);
+        let Some(&[low_0, low_1]) = buf.get(..2) else {
+            return Ok(());
+        };
+        let code = match buf.get(2..4) {
+            Some(&[high_0, high_1]) => u16::from_le_bytes([high_0, high_1]),
+            _ => 0,
+        };
+        match u16::from_le_bytes([low_0, low_1]) {
+            Self::FAIL => {
+                debug!("Powering down board because 0x3333 was written, with code {code}");
+                self.power_down(allocator, ExitStatus::Fail(code));
+            }
+            Self::PASS => {
+                debug!("Powering down board because 0x5555 was written");
+                self.power_down(allocator, ExitStatus::Pass);
+            }
+            Self::RESET => *self.0.get_mut(allocator) = FinisherState::ResetRequested,
+            _ => {}
         }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::{boot, boot_images, code, run_until};
+    use super::*;
+    use crate::core::csr;
+    use crate::core::mmu::MemoryError;
+    use crate::interrupt::DynIrqCallback;
+    use crate::registers::Specifier;
+    use crate::resources::plic;
+    use crate::simulator::{SimulationAllocator, Simulator};
+    use crate::PrivilegeLevel;
+    use std::num::NonZeroUsize;
+
+    /// Hart 0 does an LR/SC pair on 0x8000_1000, while hart 1 stores to it in between. Hart 0
+    /// writes the result of the SC to t2.
+    const LR_SC_PROGRAM: [u32; 9] = [
+        0x00051C63, // bnez   a0, hart1
+        0x800012B7, // lui    t0, 0x80001
+        0x1002A32F, // lr.w   t1, (t0)
+        0x1862A3AF, // sc.w   t2, t1, (t0)
+        0x0000006F, // j      .
+        0x00000013, // nop
+        0x800012B7, // hart1: lui t0, 0x80001
+        0x0002A023, // sw     zero, 0(t0)
+        0x0000006F, // j      .
+    ];
+
+    fn run_lr_sc_program(hart_count: usize) -> u32 {
+        let mut config = Config::default();
+        config.harts.count = NonZeroUsize::new(hart_count).unwrap();
+        let mut simulator = boot(config, &LR_SC_PROGRAM);
+        // Hart 0 loops once the SC is done.
+        run_until(&mut simulator, 0x8000_0010);
+        let (allocator, board) = simulator.inspect();
+        board
+            .core(0)
+            .registers(allocator)
+            .x(Specifier::new(7).unwrap())
+    }
+
+    #[test]
+    fn test_cross_hart_reservation() {
+        // A single hart keeps its reservation.
+        assert_eq!(0, run_lr_sc_program(1));
+        // The store of hart 1 invalidates the reservation of hart 0.
+        assert_eq!(1, run_lr_sc_program(2));
+    }
+
+    #[test]
+    fn test_own_store_reservation() {
+        // A store of the hart itself goes through the system bus, which invalidates the
+        // reservation.
+        let program = [
+            0x800012B7, // lui    t0, 0x80001
+            0x1002A32F, // lr.w   t1, (t0)
+            0x0002A023, // sw     zero, 0(t0)
+            0x1862A3AF, // sc.w   t2, t1, (t0)
+            0x0000006F, // j      .
+        ];
+        let mut simulator = boot(Config::default(), &program);
+        run_until(&mut simulator, 0x8000_0010);
+        let (allocator, board) = simulator.inspect();
+        let t2 = (board.core(0).registers(allocator)).x(Specifier::new(7).unwrap());
+        assert_eq!(1, t2);
+    }
+
+    #[test]
+    fn test_get_core() {
+        let simulator = Simulator::new(|allocator| {
+            let mut config = Config::default();
+            config.harts.count = NonZeroUsize::new(2).unwrap();
+            Board::new(allocator, config)
+        });
+        let (_, board): (&SimulationAllocator, _) = simulator.inspect();
+        assert!(board.get_core(1).is_some());
+        assert!(board.get_core(2).is_none());
+    }
+
+    #[test]
+    fn test_paging_ever_enabled() {
+        const PROGRAM: [u32; 4] = [
+            0x800002B7, // lui    t0, 0x80000
+            0x18029073, // csrw   satp, t0
+            0x18001073, // csrw   satp, zero
+            0x0000006F, // j      .
+        ];
+        let mut simulator = boot(Config::default(), &PROGRAM);
+        run_until(&mut simulator, 0x8000_0004);
+        let (allocator, board) = simulator.inspect();
+        assert!(!board.core(0).paging_ever_enabled(allocator));
+        simulator.step();
+        let (allocator, board) = simulator.inspect();
+        assert!(board.core(0).paging_enabled(allocator));
+        simulator.step();
+        let (allocator, board) = simulator.inspect();
+        assert!(!board.core(0).paging_enabled(allocator));
+        assert!(board.core(0).paging_ever_enabled(allocator));
+    }
+
+    /// Sets mtimecmp of hart 0 to 1000, enables MTIE, and waits for an interrupt.
+    const WFI_PROGRAM: [u32; 8] = [
+        0x020042B7, // lui    t0, 0x2004
+        0x3E800313, // li     t1, 1000
+        0x0062A023, // sw     t1, 0(t0)
+        0x0002A223, // sw     zero, 4(t0)
+        0x08000313, // li     t1, 0x80
+        0x30431073, // csrw   mie, t1
+        0x10500073, // wfi
+        0x0000006F, // j      .
+    ];
+
+    #[test]
+    fn test_idle_skip() {
+        let mut config = Config::default();
+        config.harts.idle_skip = true;
+        let mut simulator = boot(config, &WFI_PROGRAM);
+        run_until(&mut simulator, 0x8000_0018);
+        simulator.step();
+
+        // The step executing the WFI also skips all the way to mtimecmp, since the hart is then
+        // sleeping. That raises MTIP, which wakes the hart up again.
+        let (allocator, board) = simulator.inspect();
+        assert_eq!(0x8000_001C, board.core(0).registers(allocator).pc());
+        assert_eq!(1000, board.system_bus.clint.mtime(allocator));
+        assert!(!board.is_sleeping(allocator));
+
+        // The hart resumes after the WFI. No trap is taken, since mstatus.MIE is not set.
+        simulator.step();
+        let (allocator, board) = simulator.inspect();
+        assert_eq!(0x8000_001C, board.core(0).registers(allocator).pc());
+        assert_eq!(1001, board.system_bus.clint.mtime(allocator));
+    }
+
+    /// Counts board steps, reads return the count, and writes clear it. Raises its IRQ once the
+    /// count reaches 8.
+    #[derive(Debug)]
+    struct Counter {
+        count: <SimulationAllocator as Allocator>::Id<u32>,
+        irq: DynIrqCallback<SimulationAllocator>,
+    }
+
+    impl Counter {
+        fn count(&self, allocator: &SimulationAllocator) -> u32 {
+            *allocator.get(self.count).unwrap()
+        }
+    }
+
+    impl Bus<SimulationAllocator> for Counter {
+        fn read(
+            &self,
+            buf: &mut [u8],
+            allocator: &mut SimulationAllocator,
+            address: u32,
+        ) -> Result<(), BusError> {
+            self.read_debug(buf, allocator, address)
+        }
+
+        fn read_debug(
+            &self,
+            buf: &mut [u8],
+            allocator: &SimulationAllocator,
+            _address: u32,
+        ) -> Result<(), BusError> {
+            buf.copy_from_slice(&self.count(allocator).to_le_bytes());
+            Ok(())
+        }
+
+        fn write(
+            &self,
+            allocator: &mut SimulationAllocator,
+            _address: u32,
+            _buf: &[u8],
+        ) -> Result<(), BusError> {
+            *allocator.get_mut(self.count).unwrap() = 0;
+            Ok(())
+        }
+    }
+
+    impl Simulatable<SimulationAllocator> for Counter {
+        fn tick(&self, allocator: &mut SimulationAllocator) {
+            let count = allocator.get_mut(self.count).unwrap();
+            *count += 1;
+            if *count == 8 {
+                self.irq.raise(allocator);
+            }
+        }
+
+        fn drop(self, allocator: &mut SimulationAllocator) {
+            allocator.remove(self.count).unwrap();
+        }
+    }
+
+    impl Device<SimulationAllocator> for Counter {
+        fn accepts(&self, address: u32, size: usize, _access_type: AccessType) -> bool {
+            address == 0 && size == 4
+        }
+    }
+
+    fn counter_registration(base: u32, irq: u32) -> DeviceRegistration<SimulationAllocator> {
+        DeviceRegistration::new(
+            "counter",
+            base,
+            4,
+            vec![irq],
+            |allocator: &mut SimulationAllocator, mut irqs| Counter {
+                count: allocator.insert(0),
+                irq: irqs.remove(0),
+            },
+        )
+    }
+
+    /// Clears the counter, and then loads its value into t1.
+    const COUNTER_PROGRAM: [u32; 4] = [
+        0x400002B7, // lui    t0, 0x40000
+        0x0002A023, // sw     zero, 0(t0)
+        0x0002A303, // lw     t1, 0(t0)
+        0x0000006F, // j      .
+    ];
+
+    #[test]
+    fn test_user_defined_device() {
+        let mut simulator = Simulator::new(|allocator| {
+            let counter = counter_registration(0x4000_0000, 5);
+            let board = Board::with_devices(allocator, Config::default(), vec![counter]).unwrap();
+            board.set_boot_images(allocator, vec![(0x8000_0000, code(&COUNTER_PROGRAM))]);
+            board
+        });
+        let pending = |simulator: &Simulator<Board<SimulationAllocator>>| {
+            let (allocator, board) = simulator.inspect();
+            let mut buf = [0; 4];
+            board
+                .system_bus
+                .plic
+                .read_debug(&mut buf, allocator, plic::PENDING_BASE_ADDR)
+                .unwrap();
+            u32::from_le_bytes(buf) & (1 << 5) != 0
+        };
+
+        run_until(&mut simulator, 0x8000_000C);
+        let (allocator, board) = simulator.inspect();
+        // The counter is ticked after the store, before the load.
+        assert_eq!(
+            1,
+            board
+                .core(0)
+                .registers(allocator)
+                .x(Specifier::new(6).unwrap())
+        );
+        assert_eq!(2, board.device::<Counter>(0).unwrap().count(allocator));
+        assert!(board.device::<Counter>(1).is_none());
+
+        for _ in 0..6 {
+            simulator.step();
+        }
+        assert!(pending(&simulator));
+
+        simulator.undo_step();
+        let (allocator, board) = simulator.inspect();
+        assert_eq!(7, board.device::<Counter>(0).unwrap().count(allocator));
+        assert!(!pending(&simulator));
+
+        // Misaligned accesses are rejected, since the device does not accept them.
+        let (allocator, board) = simulator.inspect();
+        let mmu = board.core(0).mmu();
+        assert!(mmu.read_word_debug(allocator, 0x4000_0000).is_ok());
+        assert!(mmu.read_byte_debug(allocator, 0x4000_0001).is_err());
+    }
+
+    #[test]
+    fn test_invalid_user_defined_device() {
+        let error = |base, irq| {
+            let mut error = None;
+            Simulator::new(|allocator| {
+                let counter = counter_registration(base, irq);
+                match Board::with_devices(allocator, Config::default(), vec![counter]) {
+                    Ok(board) => board,
+                    Err(e) => {
+                        error = Some(e.to_string());
+                        Board::new(allocator, Config::default())
+                    }
+                }
+            });
+            error
+        };
+        assert_eq!(None, error(0x4000_0000, 5));
+        assert_eq!(
+            Some("uart0 overlaps with counter".to_owned()),
+            error(0x1000_0000, 5)
+        );
+        assert_eq!(
+            Some("uart0 and counter share IRQ 3".to_owned()),
+            error(0x4000_0000, 3)
+        );
+    }
+
+    #[test]
+    fn test_bus_fault() {
+        let mut simulator = Simulator::new(|allocator| Board::new(allocator, Config::default()));
+        let (allocator, board) = simulator.inspect();
+        let mmu = board.core(0).mmu();
+        assert!(mmu.read_byte_debug(allocator, 0x1000_0005).is_ok());
+        // No register is mapped at this offset of the UART.
+        assert_eq!(
+            Err(MemoryError::BusFault(BusError::Unsupported)),
+            mmu.read_byte_debug(allocator, 0x1000_0010)
+        );
+        // Nothing is mapped here, so the system bus does not accept the access at all.
+        assert_eq!(
+            Err(MemoryError::AccessFault),
+            mmu.read_byte_debug(allocator, 0x5000_0000)
+        );
+
+        // The line status register of the UART is read-only.
+        let result = simulator.step_with("write lsr", |allocator, board| {
+            board.core(0).mmu().write_byte(allocator, 0x1000_0005, 0)
+        });
+        assert_eq!(Err(MemoryError::BusFault(BusError::Denied)), result);
+    }
+
+    #[test]
+    fn test_amo_exceptions() {
+        // Runs `amoadd.w` on `lui t1, upper; addi t1, t1, lower`, and returns mcause and mtval.
+        let run = |upper: u32, lower: u32| {
+            let program = [
+                0x00000297,               // auipc  t0, 0
+                0x01C28293,               // addi   t0, t0, 0x1c
+                0x30529073,               // csrw   mtvec, t0
+                0x00000337 | upper,       // lui    t1, upper
+                0x00030313 | lower << 20, // addi   t1, t1, lower
+                0x000323AF,               // amoadd.w t2, zero, (t1)
+                0x0000006F,               // j      .
+                0x0000006F,               // handler: j .
+            ];
+            let mut simulator = boot(Config::default(), &program);
+            run_until(&mut simulator, 0x8000_0014);
+            simulator.step();
+            simulator.step_with("read mcause and mtval", |allocator, board| {
+                let core = board.core(0);
+                let mut read =
+                    |csr| (core.read_csr(allocator, csr, PrivilegeLevel::Machine)).unwrap();
+                (read(csr::MCAUSE), read(csr::MTVAL))
+            })
+        };
+
+        // AMOs raise store/AMO exceptions, also for the read half of the access.
+        assert_eq!((0, 0), run(0x8000_1000, 0));
+        assert_eq!((6, 0x8000_1002), run(0x8000_1000, 2));
+        assert_eq!((7, 0x5000_0000), run(0x5000_0000, 0));
+    }
+
+    /// Sets a load trigger on 0x8000_1000 in M-mode with mstatus.MIE set, and loads from it. The
+    /// trap handler loads from it again, and writes mtval to a3.
+    const TRIGGER_PROGRAM: [u32; 14] = [
+        0x00000297, // auipc  t0, 0
+        0x02C28293, // addi   t0, t0, 44
+        0x30529073, // csrw   mtvec, t0
+        0x80001537, // lui    a0, 0x80001
+        0x7A251073, // csrw   tdata2, a0
+        0x60000337, // lui    t1, 0x60000
+        0x04130313, // addi   t1, t1, 0x41
+        0x7A131073, // csrw   tdata1, t1
+        0x30046073, // csrsi  mstatus, 8
+        0x00052583, // lw     a1, 0(a0)
+        0x0000006F, // j      .
+        0x00052603, // handler: lw a2, 0(a0)
+        0x343026F3, // csrr   a3, mtval
+        0x0000006F, // j      .
+    ];
+
+    #[test]
+    fn test_trigger_in_handler() {
+        let mut simulator = boot(Config::default(), &TRIGGER_PROGRAM);
+        run_until(&mut simulator, 0x8000_0034);
+        // The trigger fires once: the trap cleared mstatus.MIE, so the load in the handler does
+        // not fire it again.
+        let mcause = simulator.step_with("read mcause", |allocator, board| {
+            let registers = board.core(0).registers(allocator);
+            assert_eq!(0x8000_1000, registers.x(Specifier::new(13).unwrap()));
+            let core = board.core(0);
+            core.read_csr(allocator, csr::MCAUSE, PrivilegeLevel::Machine)
+        });
+        assert_eq!(3, mcause.unwrap());
+    }
+
+    #[test]
+    fn test_boot_protocol() {
+        // Runs the reset vector, and returns a1 and the first bytes it points to.
+        let run = |config: Config| {
+            let mrom_base = config.memory.mrom.base;
+            let mut simulator = boot_images(config, Vec::new());
+            run_until(&mut simulator, 0x8000_0000);
+            let (allocator, board) = simulator.inspect();
+            let registers = board.core(0).registers(allocator);
+            assert_eq!(0, registers.x(Specifier::A0));
+            let a1 = registers.x(Specifier::A1);
+            let mut header = [0; 24];
+            board.mrom().read(&mut header, allocator, a1 - mrom_base);
+            (a1, header)
+        };
+
+        let (a1, header) = run(Config::default());
+        assert_eq!(0x1018, a1);
+        assert_eq!(fdt::FDT_MAGIC.to_be_bytes(), header[..4]);
+
+        // The device tree stays 8-byte aligned, and can be replaced.
+        let mut config = Config::default();
+        config.memory.mrom.base = 0x1004;
+        let mut dtb = fdt::generate(&config);
+        dtb[20..24].copy_from_slice(&[0xAB; 4]);
+        config.dtb = Some(dtb);
+        let (a1, header) = run(config);
+        assert_eq!(0x1020, a1);
+        assert_eq!([0xAB; 4], header[20..24]);
+
+        let mut config = Config::default();
+        config.memory.mrom.size = 0x100;
+        assert!(matches!(
+            config.validate(),
+            Err(ConfigError::MromTooSmall(size)) if size > 0x100
+        ));
+        config.dtb = Some(vec![0xD0, 0x0D, 0xFE, 0xED, 0, 0, 0, 8]);
+        assert_eq!(config.validate().map_err(|e| e.to_string()), Ok(()));
+        config.dtb = Some(vec![0; 8]);
+        assert!(matches!(config.validate(), Err(ConfigError::InvalidDtb)));
+    }
+
+    #[test]
+    fn test_linux_boot_handoff() {
+        // Only the handoff to the firmware and the kernel, using stand-ins for both.
+        // Like OpenSBI's fw_jump, jumps to the kernel 4 MiB into DRAM, leaving a0 and a1 alone.
+        let firmware = code(&[
+            0x804002B7, // lui    t0, 0x80400
+            0x00028067, // jr     t0
+        ]);
+        // Reads the device tree and the initrd, and powers down.
+        let kernel = code(&[
+            0x0005A303, // lw     t1, 0(a1)
+            0x884003B7, // lui    t2, 0x88400
+            0x0003A383, // lw     t2, 0(t2)
+            0x001002B7, // lui    t0, 0x100
+            0x00005E37, // lui    t3, 5
+            0x555E0E13, // addi   t3, t3, 0x555
+            0x01C2A023, // sw     t3, 0(t0)
+            0x0000006F, // j      .
+        ]);
+        let initrd = b"070701";
+        let config = Config {
+            chosen: ChosenConfig {
+                bootargs: Some("console=ttyS0 earlycon".to_owned()),
+                initrd: Some(Region {
+                    base: 0x8840_0000,
+                    size: initrd.len() as u32,
+                }),
+            },
+            ..Config::default()
+        };
+        let images = vec![
+            (0x8000_0000, firmware),
+            (0x8040_0000, kernel),
+            (0x8840_0000, initrd.to_vec()),
+        ];
+        let mut simulator = boot_images(config, images);
+        run_until(&mut simulator, 0x8040_001C);
+
+        let (allocator, board) = simulator.inspect();
+        assert_eq!(Some(ExitStatus::Pass), board.exit_status(allocator));
+        let registers = board.core(0).registers(allocator);
+        assert_eq!(0, registers.x(Specifier::A0));
+        assert_eq!(
+            fdt::FDT_MAGIC.to_be_bytes(),
+            registers.x(Specifier::new(6).unwrap()).to_le_bytes()
+        );
+        assert_eq!(
+            b"0707",
+            &registers.x(Specifier::new(7).unwrap()).to_le_bytes()
+        );
+    }
+
+    #[test]
+    fn test_power_down_device() {
+        let program = [
+            0x800012B7, // lui    t0, 0x80001
+            0x00100313, // li     t1, 1
+            0x0062A023, // sw     t1, 0(t0)
+            0x0000006F, // j      .
+        ];
+        let mut simulator = boot(Config::default(), &program);
+        let write = |simulator: &mut Simulator<Board<SimulationAllocator>>, value: u32| {
+            simulator.step_with("write power_down", move |allocator, board| {
+                board
+                    .system_bus
+                    .write(allocator, 0x10_0000, &value.to_le_bytes())
+                    .unwrap()
+            });
+        };
+
+        let marker = |simulator: &Simulator<Board<SimulationAllocator>>| {
+            let (allocator, board) = simulator.inspect();
+            let mut buf = [0; 4];
+            (board.system_bus)
+                .read_debug(&mut buf, allocator, 0x8000_1000)
+                .unwrap();
+            u32::from_le_bytes(buf)
+        };
+
+        run_until(&mut simulator, 0x8000_000C);
+        write(&mut simulator, 0x1234);
+        let (allocator, board) = simulator.inspect();
+        assert_eq!(None, board.exit_status(allocator));
+        assert_eq!(1, marker(&simulator));
+
+        // The reset happens at the end of the next step. It clears DRAM, but loads the program
+        // again, which then runs again.
+        write(&mut simulator, 0x7777);
+        simulator.step();
+        let (allocator, board) = simulator.inspect();
+        assert_eq!(None, board.exit_status(allocator));
+        assert_eq!(0x1000, board.core(0).registers(allocator).pc());
+        assert_eq!(0, marker(&simulator));
+        run_until(&mut simulator, 0x8000_000C);
+        assert_eq!(1, marker(&simulator));
+
+        write(&mut simulator, 0x0003_3333);
+        let (allocator, board) = simulator.inspect();
+        assert_eq!(Some(ExitStatus::Fail(3)), board.exit_status(allocator));
+        assert_eq!(3, ExitStatus::Fail(3).exit_code());
+        assert_eq!(1, ExitStatus::Fail(0x100).exit_code());
+        assert_eq!(0, ExitStatus::Pass.exit_code());
+    }
+
+    #[test]
+    fn test_reset() {
+        let mut simulator = boot(Config::default(), &[0x0000006F]);
+        let write = |simulator: &mut Simulator<Board<SimulationAllocator>>, address, value: u32| {
+            simulator.step_with("write", move |allocator, board| {
+                (board.system_bus)
+                    .write(allocator, address, &value.to_le_bytes())
+                    .unwrap()
+            });
+        };
+        let read = |simulator: &Simulator<Board<SimulationAllocator>>, address| {
+            let (allocator, board) = simulator.inspect();
+            let mut buf = [0; 4];
+            (board.system_bus)
+                .read_debug(&mut buf, allocator, address)
+                .unwrap();
+            u32::from_le_bytes(buf)
+        };
+
+        // CLINT msip and mtimecmp, PLIC priority and enable, and DRAM outside of the boot image.
+        let registers = [
+            (0x200_0000, 1),
+            (0x200_4000, 1),
+            (0xC00_0004, 1),
+            (0xC00_2000, 2),
+            (0x8000_1000, 1),
+        ];
+        for (address, value) in registers {
+            write(&mut simulator, address, value);
+            assert_eq!(value, read(&simulator, address));
+        }
+        simulator.step();
+        let (allocator, board) = simulator.inspect();
+        assert_ne!(0, board.system_bus.clint.mtime(allocator));
+
+        write(&mut simulator, 0x10_0000, 0x7777);
+        simulator.step();
+        let (allocator, board) = simulator.inspect();
+        assert_eq!(0, board.system_bus.clint.mtime(allocator));
+        for (address, _) in registers {
+            assert_eq!(0, read(&simulator, address), "{address:#x}");
+        }
+        assert_eq!(0x6F, read(&simulator, 0x8000_0000));
+    }
+
+    #[test]
+    fn test_exit_status_from_code() {
+        assert_eq!(ExitStatus::Pass, ExitStatus::from_code(0));
+        assert_eq!(ExitStatus::Fail(3), ExitStatus::from_code(3));
+        assert_eq!(ExitStatus::Fail(0xFFFF), ExitStatus::from_code(0xFFFF));
+        assert_eq!(ExitStatus::Fail(0xFFFF), ExitStatus::from_code(0x1_0000));
+        assert_eq!(ExitStatus::Fail(0xFFFF), ExitStatus::from_code(u64::MAX));
+        assert_eq!(255, ExitStatus::from_code(0x1_0000).exit_code());
+    }
+
+    #[test]
+    fn test_schedule() {
+        let quantum = NonZeroU32::new(2).unwrap();
+        let harts: Vec<usize> = std::iter::successors(Some(Schedule::default()), |schedule| {
+            Some(schedule.next(quantum, 3))
+        })
+        .take(8)
+        .map(|schedule| schedule.hart)
+        .collect();
+        assert_eq!(vec![0, 0, 1, 1, 2, 2, 0, 0], harts);
+
+        let schedule = Schedule::default();
+        assert_eq!(schedule, schedule.next(NonZeroU32::MIN, 1));
     }
 }
@@ -1,15 +1,24 @@
+use std::cell::OnceCell;
+use std::fmt::{self, Debug};
 use std::rc::Weak;
 
-use super::PowerDown;
+use super::device::AnyDevice;
+use super::{BoardCore, ExitStatus, PowerDown};
 use crate::address_map::TwoWayAddressMap;
-use crate::bus::Bus;
+use crate::bus::{Bus, BusError};
 use crate::core::clint::Clint;
+use crate::core::Interrupt;
 use crate::interrupt::{DynIrqCallback, IrqCallback};
-use crate::resources::plic::Plic;
+use crate::resources::htif::Htif;
+use crate::resources::plic::{self, Plic};
 use crate::resources::ram::Ram;
 use crate::resources::rom::Rom;
+use crate::resources::semihosting::{Outcome, Semihosting};
 use crate::resources::uart::Uart;
+use crate::resources::virtio_block::VirtioBlock;
 use crate::system_bus::AccessType;
+use crate::PrivilegeLevel;
+use log::debug;
 use space_time::allocator::Allocator;
 
 /// Enum that uniquely identifies every device attached to a [`SystemBus`] (as a slave).
@@ -18,10 +27,15 @@ pub(super) enum Resource {
     Mrom,
     Clint,
     Plic,
-    Uart0,
+    /// UART with the given index in [`super::DeviceConfig::uarts`].
+    Uart(usize),
+    /// Virtio block device with the given index in [`super::DeviceConfig::virtio_blocks`].
+    VirtioBlock(usize),
     Flash,
     Dram,
     PowerDown,
+    /// User-defined device with the given index in [`SystemBus::devices`].
+    Device(usize),
 }
 
 /// Abstraction of a system's main bus connecting all devices to the core.
@@ -31,12 +45,15 @@ pub(super) enum Resource {
 /// interface depending on a configurable address mapping.
 ///
 /// Note that vacant memory regions (i.e. unmapped address ranges) are allowed, but accessing them
-/// will do nothing.
+/// fails with [`BusError::Denied`].
 ///
 /// Accesses are always in the form of `(address, size)` pairs. The access request is forwarded to
 /// the *slave* interface that `address` maps to, if and only if the entire address range
 /// `address..(address+size)` is contained within the memory region that `address` is in. Otherwise,
-/// the access is not forwarded and will do nothing.
+/// the access is not forwarded and fails with [`BusError::Denied`].
+///
+/// Every write that is forwarded invalidates the LR reservations of all harts that overlap with the
+/// written bytes, which makes LR/SC sequences observe stores (including AMOs) of other harts.
 ///
 /// See also the [`crate::system_bus::SystemBus`] trait.
 #[derive(Debug)]
@@ -45,10 +62,18 @@ pub(super) struct SystemBus<A: Allocator> {
     pub mrom: Rom<A>,
     pub clint: Clint<A>,
     pub plic: Plic<A>,
-    pub uart0: Uart<A>,
-    pub flash: Rom<A>,
+    pub uarts: Vec<Uart<A>>,
+    pub virtio_blocks: Vec<VirtioBlock<A>>,
+    pub flash: Option<Rom<A>>,
     pub dram: Ram<A>,
     pub power_down: PowerDown<A>,
+    pub devices: Vec<Box<dyn AnyDevice<A>>>,
+    /// Observes all forwarded writes, since `tohost` is in DRAM.
+    pub htif: Option<Htif<A>>,
+    /// Handles the semihosting calls of all harts.
+    pub semihosting: Option<Semihosting<A>>,
+    /// The harts attached to this bus, indexed by hart ID. Set once after the harts are created.
+    pub harts: OnceCell<Vec<Weak<BoardCore<A>>>>,
 }
 
 struct PlicIrqCallback<A: Allocator> {
@@ -70,12 +95,72 @@ impl<A: Allocator> IrqCallback<A> for PlicIrqCallback<A> {
     }
 }
 
+/// Gives a device direct access to the system bus, like a DMA controller.
+struct DmaBus<A: Allocator> {
+    bus: Weak<SystemBus<A>>,
+}
+
+impl<A: Allocator> Debug for DmaBus<A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DmaBus").finish_non_exhaustive()
+    }
+}
+
+impl<A: Allocator> Bus<A> for DmaBus<A> {
+    fn read(&self, buf: &mut [u8], allocator: &mut A, address: u32) -> Result<(), BusError> {
+        let bus = self.bus.upgrade().ok_or(BusError::Denied)?;
+        bus.read(buf, allocator, address)
+    }
+
+    fn read_debug(&self, buf: &mut [u8], allocator: &A, address: u32) -> Result<(), BusError> {
+        let bus = self.bus.upgrade().ok_or(BusError::Denied)?;
+        bus.read_debug(buf, allocator, address)
+    }
+
+    fn write(&self, allocator: &mut A, address: u32, buf: &[u8]) -> Result<(), BusError> {
+        let bus = self.bus.upgrade().ok_or(BusError::Denied)?;
+        bus.write(allocator, address, buf)
+    }
+}
+
+struct HartIrqCallback<A: Allocator> {
+    bus: Weak<SystemBus<A>>,
+    hart: usize,
+    code: Interrupt,
+}
+
+impl<A: Allocator> HartIrqCallback<A> {
+    fn with_core(&self, op: impl FnOnce(&BoardCore<A>)) {
+        let Some(bus) = self.bus.upgrade() else {
+            return;
+        };
+        if let Some(core) = bus
+            .harts
+            .get()
+            .and_then(|harts| harts.get(self.hart))
+            .and_then(Weak::upgrade)
+        {
+            op(&core)
+        }
+    }
+}
+
+impl<A: Allocator> IrqCallback<A> for HartIrqCallback<A> {
+    fn raise(&self, allocator: &mut A) {
+        self.with_core(|core| core.raise_interrupt(allocator, self.code))
+    }
+
+    fn lower(&self, allocator: &mut A) {
+        self.with_core(|core| core.lower_interrupt(allocator, self.code))
+    }
+}
+
 impl<A: Allocator> SystemBus<A> {
-    /// Validates the `(address, size)` pair, returning `Some((resource, mapped_address))` if the
-    /// access is accepted, and `None` otherwise.
-    fn check_access(&self, address: u32, size: usize) -> Option<(Resource, u32)> {
+    /// Validates the `(address, size)` pair, returning `(resource, mapped_address)` if the access
+    /// is forwarded, and [`BusError::Denied`] otherwise.
+    fn check_access(&self, address: u32, size: usize) -> Result<(Resource, u32), BusError> {
         let (range, Some(&resource)) = self.memory_map.range_value(address) else {
-            return None;
+            return Err(BusError::Denied);
         };
 
         if size
@@ -84,10 +169,10 @@ impl<A: Allocator> SystemBus<A> {
             .map(|delta| range.end() - address < delta)
             .unwrap_or(true)
         {
-            return None;
+            return Err(BusError::Denied);
         }
 
-        Some((resource, address - range.start()))
+        Ok((resource, address - range.start()))
     }
 
     fn bus_of(&self, resource: Resource) -> &dyn Bus<A> {
@@ -95,35 +180,71 @@ impl<A: Allocator> SystemBus<A> {
             Resource::Mrom => &self.mrom,
             Resource::Clint => &self.clint,
             Resource::Plic => &self.plic,
-            Resource::Uart0 => &self.uart0,
-            Resource::Flash => &self.flash,
+            // Unwrap safety: resources are only mapped for the devices that are present.
+            Resource::Uart(index) => &self.uarts[index],
+            Resource::VirtioBlock(index) => &self.virtio_blocks[index],
+            Resource::Flash => self.flash.as_ref().unwrap(),
             Resource::Dram => &self.dram,
             Resource::PowerDown => &self.power_down,
+            Resource::Device(index) => self.devices[index].as_bus(),
         }
     }
 
-    /// Panics if `index` is not in 1..=52
-    pub fn get_plic_irq_callback(bus: Weak<Self>, index: u8) -> DynIrqCallback<A> {
-        if !(1..=52).contains(&index) {
+    /// Panics if `index` is not in `1..=`[`plic::MAX_SOURCE`].
+    pub fn get_plic_irq_callback(bus: Weak<Self>, index: u32) -> DynIrqCallback<A> {
+        if !(1..=plic::MAX_SOURCE).contains(&index) {
             panic!("Invalid interrupt idx: {index}");
         }
 
-        DynIrqCallback(Box::new(PlicIrqCallback { bus, index }))
+        DynIrqCallback(Box::new(PlicIrqCallback {
+            bus,
+            index: index as u8,
+        }))
+    }
+
+    /// Returns a bus through which a device can access all memories and devices on `bus`.
+    pub fn get_dma_bus(bus: Weak<Self>) -> Box<dyn Bus<A>> {
+        Box::new(DmaBus { bus })
+    }
+
+    /// Returns a callback that raises or lowers the interrupt `code` of the hart with ID `hart`.
+    pub fn get_hart_irq_callback(
+        bus: Weak<Self>,
+        hart: usize,
+        code: Interrupt,
+    ) -> DynIrqCallback<A> {
+        DynIrqCallback(Box::new(HartIrqCallback { bus, hart, code }))
     }
 
     pub(super) fn drop(self, allocator: &mut A) {
         self.mrom.drop(allocator);
         self.clint.drop(allocator);
         self.plic.drop(allocator);
-        self.uart0.drop(allocator);
-        self.flash.drop(allocator);
+        for uart in self.uarts {
+            uart.drop(allocator);
+        }
+        for virtio_block in self.virtio_blocks {
+            virtio_block.drop(allocator);
+        }
+        if let Some(flash) = self.flash {
+            flash.drop(allocator);
+        }
         self.dram.drop(allocator);
+        for device in self.devices {
+            device.drop(allocator);
+        }
+        if let Some(htif) = self.htif {
+            htif.drop(allocator);
+        }
+        if let Some(semihosting) = self.semihosting {
+            semihosting.drop(allocator);
+        }
     }
 }
 
 impl<A: Allocator> crate::system_bus::SystemBus<A> for SystemBus<A> {
     fn accepts(&self, address: u32, size: usize, access_type: AccessType) -> bool {
-        let Some((resource, _)) = self.check_access(address, size) else {
+        let Ok((resource, mapped_address)) = self.check_access(address, size) else {
             return false;
         };
 
@@ -131,34 +252,64 @@ impl<A: Allocator> crate::system_bus::SystemBus<A> for SystemBus<A> {
             Resource::Mrom => !matches!(access_type, AccessType::Write),
             Resource::Clint => size == 4 || size == 8,
             Resource::Plic => size == 4,
-            Resource::Uart0 => true,
+            Resource::Uart(_) => true,
+            Resource::VirtioBlock(_) => true,
             Resource::Flash => !matches!(access_type, AccessType::Write),
             Resource::Dram => true,
             Resource::PowerDown => matches!(access_type, AccessType::Write),
+            Resource::Device(index) => {
+                self.devices[index].accepts(mapped_address, size, access_type)
+            }
+        }
+    }
+
+    fn semihosting_call(
+        &self,
+        allocator: &mut A,
+        memory: &dyn Bus<A>,
+        privilege: PrivilegeLevel,
+        operation: u32,
+        parameter: u32,
+    ) -> Option<u32> {
+        let semihosting = (self.semihosting.as_ref()).filter(|s| s.accepts(privilege))?;
+        let ticks = self.clint.mtime(allocator);
+        match semihosting.call(allocator, memory, operation, parameter, ticks) {
+            Outcome::Return(result) => Some(result),
+            Outcome::Exit(code) => {
+                debug!("Powering down board because of a semihosting exit with code {code}");
+                let status = ExitStatus::from_code(code as u64);
+                self.power_down.power_down(allocator, status);
+                // Leave a0 unchanged.
+                Some(operation)
+            }
         }
     }
 }
 
 impl<A: Allocator> Bus<A> for SystemBus<A> {
-    fn read(&self, buf: &mut [u8], allocator: &mut A, address: u32) {
-        // If no region is being accessed, or the access is not valid, nothing happens.
-        if let Some((resource, mapped_address)) = self.check_access(address, buf.len()) {
-            self.bus_of(resource).read(buf, allocator, mapped_address);
-        }
+    fn read(&self, buf: &mut [u8], allocator: &mut A, address: u32) -> Result<(), BusError> {
+        let (resource, mapped_address) = self.check_access(address, buf.len())?;
+        self.bus_of(resource).read(buf, allocator, mapped_address)
     }
 
-    fn read_debug(&self, buf: &mut [u8], allocator: &A, address: u32) {
-        // If no region is being accessed, or the access is not valid, nothing happens.
-        if let Some((resource, mapped_address)) = self.check_access(address, buf.len()) {
-            self.bus_of(resource)
-                .read_debug(buf, allocator, mapped_address)
-        }
+    fn read_debug(&self, buf: &mut [u8], allocator: &A, address: u32) -> Result<(), BusError> {
+        let (resource, mapped_address) = self.check_access(address, buf.len())?;
+        self.bus_of(resource)
+            .read_debug(buf, allocator, mapped_address)
     }
 
-    fn write(&self, allocator: &mut A, address: u32, buf: &[u8]) {
-        // If no region is being accessed, or the access is not valid, nothing happens.
-        if let Some((resource, mapped_address)) = self.check_access(address, buf.len()) {
-            self.bus_of(resource).write(allocator, mapped_address, buf);
+    fn write(&self, allocator: &mut A, address: u32, buf: &[u8]) -> Result<(), BusError> {
+        let (resource, mapped_address) = self.check_access(address, buf.len())?;
+        self.bus_of(resource)
+            .write(allocator, mapped_address, buf)?;
+        for core in self.harts.get().into_iter().flatten() {
+            if let Some(core) = core.upgrade() {
+                core.invalidate_reservation_on_write(allocator, address, buf.len());
+            }
+        }
+        if let Some(htif) = &self.htif {
+            htif.observe_write(allocator, address, buf.len());
         }
+        Ok(())
     }
 }
@@ -0,0 +1,43 @@
+//! Helpers for tests that run programs on a [`Board`].
+
+use super::{Board, Config};
+use crate::simulator::{SimulationAllocator, Simulator};
+
+/// Most steps [`run_until`] takes before giving up.
+const MAX_STEPS: usize = 1000;
+
+/// Returns the little-endian bytes of `instructions`.
+pub(crate) fn code(instructions: &[u32]) -> Vec<u8> {
+    instructions.iter().flat_map(|i| i.to_le_bytes()).collect()
+}
+
+/// Builds a board with the given configuration, which boots into `program` at `0x8000_0000`.
+pub(crate) fn boot(config: Config, program: &[u32]) -> Simulator<Board<SimulationAllocator>> {
+    boot_images(config, vec![(0x8000_0000, code(program))])
+}
+
+/// Builds a board with the given configuration and boot images, see [`Board::set_boot_images`].
+pub(crate) fn boot_images(
+    config: Config,
+    images: Vec<(u32, Vec<u8>)>,
+) -> Simulator<Board<SimulationAllocator>> {
+    Simulator::new(|allocator| {
+        let board = Board::new(allocator, config);
+        board.set_boot_images(allocator, images);
+        board
+    })
+}
+
+/// Steps until the PC of hart 0 is `pc`, which includes running the reset vector.
+///
+/// Panics if that takes more than [`MAX_STEPS`] steps.
+pub(crate) fn run_until(simulator: &mut Simulator<Board<SimulationAllocator>>, pc: u32) {
+    for _ in 0..MAX_STEPS {
+        let (allocator, board) = simulator.inspect();
+        if board.core(0).registers(allocator).pc() == pc {
+            return;
+        }
+        simulator.step();
+    }
+    panic!("hart 0 did not reach {pc:#010x} within {MAX_STEPS} steps");
+}
@@ -2,6 +2,7 @@
 
 use crate::Allocator;
 use std::fmt::Debug;
+use thiserror::Error;
 
 /// A generalization of a TileLink-like bus interface, without the hardware details.
 ///
@@ -45,13 +46,9 @@ use std::fmt::Debug;
 /// ordering to all master devices. This means all values that are read must be serialized to bytes
 /// in little-endian order. All values that are written are also sent in little-endian byte order.
 ///
-/// The regular access methods can never fail.
-/// In the future an error type for possible bus access errors may be added. These errors would be,
-/// in theory, based on the errors in the TileLink protocol. TileLink specifies two possible errors:
-/// data corruption, and access denied. The former cannot happen in this simulated environment. The
-/// latter is only applicable if the implementation has the TL-C conformance level, i.e. it is only
-/// used for caching operations. Given that caching is not (yet) implemented in this simulation,
-/// this is not (yet) applicable.
+/// Accesses can fail with a [`BusError`], which the slave uses to signal that it denied the access
+/// or does not support it. A failed access must not have any side effects, and leaves the contents
+/// of `buf` unspecified. The MMU of a core turns a failed access into an access fault.
 pub trait Bus<A: Allocator>: Debug {
     /// Invoke a read access for `address` with size `buf.len()`, writing the result to `buf`.
     ///
@@ -59,7 +56,7 @@ pub trait Bus<A: Allocator>: Debug {
     /// `address`, but just cannot return any value.
     ///
     /// Values should generally be serialized in little-endian byte order.
-    fn read(&self, buf: &mut [u8], allocator: &mut A, address: u32);
+    fn read(&self, buf: &mut [u8], allocator: &mut A, address: u32) -> Result<(), BusError>;
 
     /// Perform a debug read for `address` with size `buf.len()`, writing the result to `buf`.
     ///
@@ -69,7 +66,9 @@ pub trait Bus<A: Allocator>: Debug {
     /// Note that the term "debug" here should not be confused with the "debug" behavior of
     /// [`std::fmt::Debug`]. It is not used for debugging the host Rust application, but rather for
     /// the "debugging" process of the end user from within that application.
-    fn read_debug(&self, buf: &mut [u8], allocator: &A, address: u32);
+    ///
+    /// This must fail if and only if [`Bus::read`] would fail.
+    fn read_debug(&self, buf: &mut [u8], allocator: &A, address: u32) -> Result<(), BusError>;
 
     /// Invoke a write access for `address` with size `buf.len()`, reading the data from `buf`.
     ///
@@ -77,5 +76,22 @@ pub trait Bus<A: Allocator>: Debug {
     /// (simulated).
     ///
     /// Values are generally deserialized in little-endian byte order.
-    fn write(&self, allocator: &mut A, address: u32, buf: &[u8]);
+    fn write(&self, allocator: &mut A, address: u32, buf: &[u8]) -> Result<(), BusError>;
+}
+
+/// Error of a failed [`Bus`] access.
+///
+/// These are loosely based on the errors of the TileLink protocol, which specifies two possible
+/// errors: data corruption, and access denied. Data corruption cannot happen in this simulated
+/// environment, but accesses the slave cannot handle are distinguished from denied ones instead.
+#[derive(Error, Debug, Clone, Copy, Eq, PartialEq)]
+pub enum BusError {
+    /// The slave does not support accesses of this size or alignment, or has no register at the
+    /// address.
+    #[error("unsupported bus access")]
+    Unsupported,
+    /// The slave refused the access, e.g. a write to a read-only register, or nothing is mapped at
+    /// the address.
+    #[error("bus access denied")]
+    Denied,
 }
@@ -3,72 +3,161 @@
 use log::trace;
 use space_time::allocator::Allocator;
 
-use crate::bus::Bus;
+use crate::bus::{Bus, BusError};
 use crate::interrupt::DynIrqCallback;
 
 // https://github.com/qemu/qemu/blob/master/include/hw/intc/riscv_aclint.h#L74
 const SWI_SIZE: u32 = 0x4000;
+pub const MSIP_ADDR: u32 = 0x0;
 #[allow(clippy::identity_op)] // for clarity
 pub const MTIMECMP_ADDR_LO: u32 = SWI_SIZE + 0x0;
 pub const MTIMECMP_ADDR_HI: u32 = MTIMECMP_ADDR_LO + 4;
 pub const MTIME_ADDR_LO: u32 = SWI_SIZE + 0x7ff8;
 pub const MTIME_ADDR_HI: u32 = MTIME_ADDR_LO + 4;
 
+/// Frequency in Hz at which mtime is reported to run, like on QEMU's virt board. mtime actually
+/// increments once per board step, so this only relates simulated time to wall-clock time.
+pub const MTIME_FREQUENCY: u32 = 10_000_000;
+
+/// Maximum number of harts a single CLINT can serve, limited by the size of the mtimecmp region.
+pub const MAX_HARTS: usize = ((MTIME_ADDR_LO - MTIMECMP_ADDR_LO) / 8) as usize;
+
+/// Returns the offset of the msip register of `hart` within the CLINT.
+pub const fn msip_addr(hart: u32) -> u32 {
+    MSIP_ADDR + 4 * hart
+}
+
+/// Returns the offset of the lower half of the mtimecmp register of `hart` within the CLINT.
+pub const fn mtimecmp_addr(hart: u32) -> u32 {
+    MTIMECMP_ADDR_LO + 8 * hart
+}
+
+/// Interrupt lines connecting the CLINT to a single hart.
+#[derive(Debug)]
+pub struct HartInterrupts<A: Allocator> {
+    /// Raised while mtime >= mtimecmp of the hart (MTIP).
+    pub timer: DynIrqCallback<A>,
+    /// Raised while bit 0 of the msip register of the hart is set (MSIP).
+    pub software: DynIrqCallback<A>,
+}
+
 #[derive(Debug)]
 pub struct Clint<A: Allocator> {
     state: A::Id<State>,
-    interrupt_callback: DynIrqCallback<A>,
+    harts: Vec<HartInterrupts<A>>,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 struct State {
     mtime: u64,
-    mtimecmp: u64,
+    mtimecmp: Vec<u64>,
+    msip: Vec<bool>,
+}
+
+/// Register of the CLINT an address maps to.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Register {
+    Msip(usize),
+    MtimecmpLo(usize),
+    MtimecmpHi(usize),
+    MtimeLo,
+    MtimeHi,
+}
+
+impl Register {
+    fn from_address(address: u32, hart_count: usize) -> Option<Self> {
+        let register = match address {
+            MTIME_ADDR_LO => Self::MtimeLo,
+            MTIME_ADDR_HI => Self::MtimeHi,
+            MTIMECMP_ADDR_LO.. => {
+                let hart = ((address - MTIMECMP_ADDR_LO) / 8) as usize;
+                if address & 0b100 == 0 {
+                    Self::MtimecmpLo(hart)
+                } else {
+                    Self::MtimecmpHi(hart)
+                }
+            }
+            MSIP_ADDR.. => Self::Msip(((address - MSIP_ADDR) / 4) as usize),
+        };
+        match register {
+            Self::Msip(hart) | Self::MtimecmpLo(hart) | Self::MtimecmpHi(hart)
+                if hart >= hart_count =>
+            {
+                None
+            }
+            register => Some(register),
+        }
+    }
 }
 
 impl State {
-    fn new() -> Self {
+    fn new(hart_count: usize) -> Self {
         Self {
             mtime: 0,
-            mtimecmp: 0,
+            mtimecmp: vec![0; hart_count],
+            msip: vec![false; hart_count],
         }
     }
 
-    fn set_mtime_higher(&mut self, value: u32) {
-        self.mtime = ((value as u64) << 32) & (self.mtime & 0xffffffff);
+    fn read_u32(&self, register: Register) -> u32 {
+        match register {
+            Register::Msip(hart) => self.msip[hart] as u32,
+            Register::MtimecmpLo(hart) => self.mtimecmp[hart] as u32,
+            Register::MtimecmpHi(hart) => (self.mtimecmp[hart] >> 32) as u32,
+            Register::MtimeLo => self.mtime as u32,
+            Register::MtimeHi => (self.mtime >> 32) as u32,
+        }
     }
 
-    fn set_mtime_lower(&mut self, value: u32) {
-        self.mtime = (self.mtime & 0xffffffff_00000000) & (value as u64 & 0xffffffff);
+    fn write_u32(&mut self, register: Register, value: u32) {
+        match register {
+            // > Each MSIP register is a 32-bit wide WARL register where the upper 31 bits are
+            // > wired to zero. The least significant bit is reflected in MSIP of the mip CSR.
+            Register::Msip(hart) => self.msip[hart] = value & 1 != 0,
+            Register::MtimecmpLo(hart) => set_lower(&mut self.mtimecmp[hart], value),
+            Register::MtimecmpHi(hart) => set_higher(&mut self.mtimecmp[hart], value),
+            Register::MtimeLo => set_lower(&mut self.mtime, value),
+            Register::MtimeHi => set_higher(&mut self.mtime, value),
+        }
     }
 
-    fn set_mtimecmp_higher(&mut self, value: u32) {
-        self.mtimecmp = ((value as u64) << 32) & (self.mtimecmp & 0xffffffff);
+    fn needs_timer_interrupt(&self, hart: usize) -> bool {
+        self.mtimecmp[hart] <= self.mtime
     }
+}
 
-    fn set_mtimecmp_lower(&mut self, value: u32) {
-        self.mtimecmp = (self.mtimecmp & 0xffffffff_00000000) & (value as u64 & 0xffffffff);
-    }
+fn set_lower(register: &mut u64, value: u32) {
+    *register = (*register & 0xffffffff_00000000) | value as u64;
+}
 
-    fn needs_interrupt(&self) -> bool {
-        self.mtimecmp <= self.mtime
-    }
+fn set_higher(register: &mut u64, value: u32) {
+    *register = ((value as u64) << 32) | (*register & 0xffffffff);
 }
 
 impl<A: Allocator> Clint<A> {
-    /// Create new Clint in reset state.
-    pub fn new(allocator: &mut A, interrupt_callback: DynIrqCallback<A>) -> Self {
+    /// Create new Clint in reset state, serving one hart for every entry of `harts`.
+    ///
+    /// Hart `i` is given the msip register at [`msip_addr(i)`](msip_addr) and the mtimecmp
+    /// register at [`mtimecmp_addr(i)`](mtimecmp_addr).
+    ///
+    /// Panics if more than [`MAX_HARTS`] harts are given.
+    pub fn new(allocator: &mut A, harts: Vec<HartInterrupts<A>>) -> Self {
+        assert!(
+            harts.len() <= MAX_HARTS,
+            "a CLINT supports at most {MAX_HARTS} harts",
+        );
         Self {
-            state: allocator.insert(State::new()),
-            interrupt_callback,
+            state: allocator.insert(State::new(harts.len())),
+            harts,
         }
     }
 
     /// Restart the CLINT, setting everything to its reset state.
     ///
-    /// mtime will be set to 0, mtimecmp will not be changed.
+    /// mtime, mtimecmp and msip are all cleared, and the interrupts of the harts updated to match.
     pub fn reset(&self, allocator: &mut A) {
-        self.update(allocator, |state| state.mtime = 0);
+        let hart_count = self.harts.len();
+        self.update(allocator, |state| *state = State::new(hart_count));
     }
 
     pub fn step(&self, allocator: &mut A) {
@@ -81,103 +170,222 @@ impl<A: Allocator> Clint<A> {
         allocator.remove(self.state).unwrap();
     }
 
-    /// Read a u32 from the mmio registers.
-    ///
-    /// Only 4 byte alligned values will work
-    pub fn read_u32(&self, allocator: &A, address: u32) -> u32 {
-        match address {
-            MTIMECMP_ADDR_HI => (allocator.get(self.state).unwrap().mtimecmp >> 32) as u32,
-            MTIMECMP_ADDR_LO => allocator.get(self.state).unwrap().mtimecmp as u32,
-            MTIME_ADDR_HI => (allocator.get(self.state).unwrap().mtime >> 32) as u32,
-            MTIME_ADDR_LO => allocator.get(self.state).unwrap().mtime as u32,
-            _ => 0,
-        }
+    pub fn mtime(&self, allocator: &A) -> u64 {
+        allocator.get(self.state).unwrap().mtime
     }
 
-    /// Write an u32 to the mmio registers.
+    /// Returns the earliest future value of mtime at which a timer interrupt becomes pending, if
+    /// any hart has its mtimecmp set in the future.
+    pub fn next_timer_event(&self, allocator: &A) -> Option<u64> {
+        let state = allocator.get(self.state).unwrap();
+        state
+            .mtimecmp
+            .iter()
+            .copied()
+            .filter(|&mtimecmp| mtimecmp > state.mtime)
+            .min()
+    }
+
+    /// Advance mtime to the next timer event in a single step, or by one if there is none.
     ///
-    /// Only 4 byte aligned values will work
-    fn write_u32(&self, allocator: &mut A, address: u32, value: u32) {
-        match address {
-            MTIMECMP_ADDR_HI => self.update(allocator, |state| state.set_mtimecmp_higher(value)),
-            MTIMECMP_ADDR_LO => self.update(allocator, |state| state.set_mtimecmp_lower(value)),
-            MTIME_ADDR_HI => self.update(allocator, |state| state.set_mtime_higher(value)),
-            MTIME_ADDR_LO => self.update(allocator, |state| state.set_mtime_lower(value)),
-            _ => {}
-        }
+    /// Used to skip time while all harts are idle, since nothing can happen in between.
+    pub fn skip_to_next_event(&self, allocator: &mut A) {
+        let Some(mtime) = self.next_timer_event(allocator) else {
+            self.step(allocator);
+            return;
+        };
+        trace!("Skipping time of CLINT to {mtime}");
+        self.update(allocator, |state| state.mtime = mtime);
     }
 
-    /// Write an u64 to the mmio registers.
+    /// Read a u32 from the mmio registers.
     ///
-    /// Only 8 byte aligned values will work
-    fn write_u64(&self, allocator: &mut A, address: u32, value: u64) {
-        match address {
-            MTIMECMP_ADDR_HI => self.update(allocator, |state| state.mtimecmp = value),
-            MTIME_ADDR_HI => self.update(allocator, |state| state.mtime = value),
-            _ => {}
+    /// Only 4 byte alligned values will work
+    pub fn read_u32(&self, allocator: &A, address: u32) -> u32 {
+        match Register::from_address(address, self.harts.len()) {
+            Some(register) => allocator.get(self.state).unwrap().read_u32(register),
+            None => 0,
         }
     }
 
-    pub fn read(&self, buf: &mut [u8], allocator: &A, address: u32) {
-        if address != address & !0b11 {
-            return;
-        }
-        match buf.len() {
-            4 => {
-                let v = self.read_u32(allocator, address);
-                buf.copy_from_slice(&v.to_le_bytes())
+    /// Reads the register(s) at `address`, which must be 4-byte aligned. Accesses of 4 bytes read
+    /// a single register, and accesses of 8 bytes read two consecutive ones.
+    pub fn read(&self, buf: &mut [u8], allocator: &A, address: u32) -> Result<(), BusError> {
+        let state = allocator.get(self.state).unwrap();
+        match self.registers(address, buf.len())? {
+            (lo, None) => buf.copy_from_slice(&state.read_u32(lo).to_le_bytes()),
+            (lo, Some(hi)) => {
+                let value = (state.read_u32(hi) as u64) << 32 | state.read_u32(lo) as u64;
+                buf.copy_from_slice(&value.to_le_bytes())
             }
-            8 => {
-                let hi = self.read_u32(allocator, address) as u64;
-                let lo = self.read_u32(allocator, address + 4) as u64;
-                buf.copy_from_slice(&(hi << 32 | lo).to_le_bytes())
-            }
-            _ => {}
         }
+        Ok(())
     }
 
-    pub fn write(&self, allocator: &mut A, address: u32, buf: &[u8]) {
-        if address != address & !0b11 {
-            return;
-        }
-        match buf {
-            [a, b, c, d] => {
-                self.write_u32(allocator, address, u32::from_le_bytes([*a, *b, *c, *d]));
-            }
-            [a, b, c, d, e, f, g, h] => {
-                self.write_u64(
-                    allocator,
-                    address,
-                    u64::from_le_bytes([*a, *b, *c, *d, *e, *f, *g, *h]),
-                );
+    /// Writes the register(s) at `address`, see [`Self::read`].
+    pub fn write(&self, allocator: &mut A, address: u32, buf: &[u8]) -> Result<(), BusError> {
+        let registers = self.registers(address, buf.len())?;
+        let (lo_value, hi_value) = buf.split_at(4);
+        // Unwrap safety: the registers are only found for accesses of 4 or 8 bytes.
+        let lo_value = u32::from_le_bytes(lo_value.try_into().unwrap());
+        match registers {
+            (lo, None) => self.update(allocator, |state| state.write_u32(lo, lo_value)),
+            (lo, Some(hi)) => {
+                let hi_value = u32::from_le_bytes(hi_value.try_into().unwrap());
+                // Both halves are written at once, so no spurious interrupt can be caused by the
+                // intermediate value.
+                self.update(allocator, |state| {
+                    state.write_u32(lo, lo_value);
+                    state.write_u32(hi, hi_value);
+                });
             }
-            _ => {}
         }
+        Ok(())
+    }
+
+    /// Returns the registers accessed by an access of `size` bytes at `address`.
+    fn registers(
+        &self,
+        address: u32,
+        size: usize,
+    ) -> Result<(Register, Option<Register>), BusError> {
+        let hart_count = self.harts.len();
+        let register = |address| Register::from_address(address, hart_count);
+        match size {
+            _ if !address.is_multiple_of(4) => None,
+            4 => register(address).map(|lo| (lo, None)),
+            8 => register(address)
+                .zip(register(address.wrapping_add(4)))
+                .map(|(lo, hi)| (lo, Some(hi))),
+            _ => None,
+        }
+        .ok_or(BusError::Unsupported)
     }
 
     fn update(&self, allocator: &mut A, op: impl FnOnce(&mut State)) {
         let state = allocator.get_mut(self.state).unwrap();
-        let irq_before = state.needs_interrupt();
+        let before = state.clone();
         op(state);
-        let irq_after = state.needs_interrupt();
-        match (irq_before, irq_after) {
-            (true, false) => self.interrupt_callback.lower(allocator),
-            (false, true) => self.interrupt_callback.raise(allocator),
-            _ => {}
+        let after = state.clone();
+        for (hart, interrupts) in self.harts.iter().enumerate() {
+            match (
+                before.needs_timer_interrupt(hart),
+                after.needs_timer_interrupt(hart),
+            ) {
+                (true, false) => interrupts.timer.lower(allocator),
+                (false, true) => interrupts.timer.raise(allocator),
+                _ => {}
+            }
+            match (before.msip[hart], after.msip[hart]) {
+                (true, false) => interrupts.software.lower(allocator),
+                (false, true) => interrupts.software.raise(allocator),
+                _ => {}
+            }
         }
     }
 }
 
 impl<A: Allocator> Bus<A> for Clint<A> {
-    fn read(&self, buf: &mut [u8], allocator: &mut A, address: u32) {
+    fn read(&self, buf: &mut [u8], allocator: &mut A, address: u32) -> Result<(), BusError> {
         self.read(buf, allocator, address)
     }
 
-    fn read_debug(&self, buf: &mut [u8], allocator: &A, address: u32) {
+    fn read_debug(&self, buf: &mut [u8], allocator: &A, address: u32) -> Result<(), BusError> {
         self.read(buf, allocator, address)
     }
 
-    fn write(&self, allocator: &mut A, address: u32, buf: &[u8]) {
+    fn write(&self, allocator: &mut A, address: u32, buf: &[u8]) -> Result<(), BusError> {
         self.write(allocator, address, buf)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    use space_time::SpaceTime;
+
+    use super::*;
+    use crate::interrupt::IrqCallback;
+
+    struct Line(Rc<Cell<bool>>);
+
+    impl IrqCallback<SpaceTime> for Line {
+        fn raise(&self, _allocator: &mut SpaceTime) {
+            self.0.set(true);
+        }
+
+        fn lower(&self, _allocator: &mut SpaceTime) {
+            self.0.set(false);
+        }
+    }
+
+    fn line() -> (Rc<Cell<bool>>, DynIrqCallback<SpaceTime>) {
+        let state = Rc::new(Cell::new(false));
+        (state.clone(), DynIrqCallback(Box::new(Line(state))))
+    }
+
+    #[test]
+    fn test_per_hart_registers() {
+        let mut allocator = SpaceTime::new();
+        let mut timers = Vec::new();
+        let mut softs = Vec::new();
+        let harts = (0..2)
+            .map(|_| {
+                let (timer_state, timer) = line();
+                let (soft_state, software) = line();
+                timers.push(timer_state);
+                softs.push(soft_state);
+                HartInterrupts { timer, software }
+            })
+            .collect();
+        let clint = Clint::new(&mut allocator, harts);
+
+        clint
+            .write(&mut allocator, msip_addr(1), &1u32.to_le_bytes())
+            .unwrap();
+        assert!(!softs[0].get());
+        assert!(softs[1].get());
+        clint
+            .write(&mut allocator, msip_addr(1), &2u32.to_le_bytes())
+            .unwrap();
+        assert!(!softs[1].get());
+
+        clint
+            .write(&mut allocator, mtimecmp_addr(0), &5u64.to_le_bytes())
+            .unwrap();
+        clint
+            .write(&mut allocator, mtimecmp_addr(1), &3u64.to_le_bytes())
+            .unwrap();
+        for _ in 0..3 {
+            clint.step(&mut allocator);
+        }
+        assert!(!timers[0].get());
+        assert!(timers[1].get());
+        for _ in 0..2 {
+            clint.step(&mut allocator);
+        }
+        assert!(timers[0].get());
+
+        // Writing the upper half keeps the lower half.
+        clint
+            .write(&mut allocator, mtimecmp_addr(0) + 4, &1u32.to_le_bytes())
+            .unwrap();
+        assert!(!timers[0].get());
+        let mut buf = [0; 8];
+        clint.read(&mut buf, &allocator, mtimecmp_addr(0)).unwrap();
+        assert_eq!(0x1_0000_0005, u64::from_le_bytes(buf));
+
+        // Registers of harts that do not exist, and misaligned accesses, are not supported.
+        let mut buf = [0; 4];
+        assert_eq!(
+            Err(BusError::Unsupported),
+            clint.read(&mut buf, &allocator, msip_addr(2))
+        );
+        assert_eq!(
+            Err(BusError::Unsupported),
+            clint.write(&mut allocator, msip_addr(0) + 2, &buf)
+        );
+    }
+}
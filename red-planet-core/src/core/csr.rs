@@ -617,7 +617,7 @@ pub const MHPMEVENT30: CsrSpecifier = 0x33E;
 pub const MHPMEVENT31: CsrSpecifier = 0x33F;
 
 //
-// Debug/trace registers (`0x7A0..=0x7A3`, `0x7A8`)
+// Debug/trace registers (`0x7A0..=0x7A4`, `0x7A8`)
 //
 /// Debug/trace trigger register select.
 pub const TSELECT: CsrSpecifier = 0x7A0;
@@ -627,9 +627,300 @@ pub const TDATA1: CsrSpecifier = 0x7A1;
 pub const TDATA2: CsrSpecifier = 0x7A2;
 /// Third debug/trace trigger data register.
 pub const TDATA3: CsrSpecifier = 0x7A3;
+/// Debug/trace trigger info register.
+pub const TINFO: CsrSpecifier = 0x7A4;
 /// Machine-mode context register.
 pub const MCONTEXT: CsrSpecifier = 0x7A8;
 
+/// Returns the name of this CSR as used in assembly, e.g. `"mstatus"` for [`MSTATUS`], or `None`
+/// if the CSR is not supported.
+pub fn name(specifier: CsrSpecifier) -> Option<&'static str> {
+    Some(match specifier {
+        FFLAGS => "fflags",
+        FRM => "frm",
+        FCSR => "fcsr",
+        CYCLE => "cycle",
+        TIME => "time",
+        INSTRET => "instret",
+        HPMCOUNTER3 => "hpmcounter3",
+        HPMCOUNTER4 => "hpmcounter4",
+        HPMCOUNTER5 => "hpmcounter5",
+        HPMCOUNTER6 => "hpmcounter6",
+        HPMCOUNTER7 => "hpmcounter7",
+        HPMCOUNTER8 => "hpmcounter8",
+        HPMCOUNTER9 => "hpmcounter9",
+        HPMCOUNTER10 => "hpmcounter10",
+        HPMCOUNTER11 => "hpmcounter11",
+        HPMCOUNTER12 => "hpmcounter12",
+        HPMCOUNTER13 => "hpmcounter13",
+        HPMCOUNTER14 => "hpmcounter14",
+        HPMCOUNTER15 => "hpmcounter15",
+        HPMCOUNTER16 => "hpmcounter16",
+        HPMCOUNTER17 => "hpmcounter17",
+        HPMCOUNTER18 => "hpmcounter18",
+        HPMCOUNTER19 => "hpmcounter19",
+        HPMCOUNTER20 => "hpmcounter20",
+        HPMCOUNTER21 => "hpmcounter21",
+        HPMCOUNTER22 => "hpmcounter22",
+        HPMCOUNTER23 => "hpmcounter23",
+        HPMCOUNTER24 => "hpmcounter24",
+        HPMCOUNTER25 => "hpmcounter25",
+        HPMCOUNTER26 => "hpmcounter26",
+        HPMCOUNTER27 => "hpmcounter27",
+        HPMCOUNTER28 => "hpmcounter28",
+        HPMCOUNTER29 => "hpmcounter29",
+        HPMCOUNTER30 => "hpmcounter30",
+        HPMCOUNTER31 => "hpmcounter31",
+        CYCLEH => "cycleh",
+        TIMEH => "timeh",
+        INSTRETH => "instreth",
+        HPMCOUNTER3H => "hpmcounter3h",
+        HPMCOUNTER4H => "hpmcounter4h",
+        HPMCOUNTER5H => "hpmcounter5h",
+        HPMCOUNTER6H => "hpmcounter6h",
+        HPMCOUNTER7H => "hpmcounter7h",
+        HPMCOUNTER8H => "hpmcounter8h",
+        HPMCOUNTER9H => "hpmcounter9h",
+        HPMCOUNTER10H => "hpmcounter10h",
+        HPMCOUNTER11H => "hpmcounter11h",
+        HPMCOUNTER12H => "hpmcounter12h",
+        HPMCOUNTER13H => "hpmcounter13h",
+        HPMCOUNTER14H => "hpmcounter14h",
+        HPMCOUNTER15H => "hpmcounter15h",
+        HPMCOUNTER16H => "hpmcounter16h",
+        HPMCOUNTER17H => "hpmcounter17h",
+        HPMCOUNTER18H => "hpmcounter18h",
+        HPMCOUNTER19H => "hpmcounter19h",
+        HPMCOUNTER20H => "hpmcounter20h",
+        HPMCOUNTER21H => "hpmcounter21h",
+        HPMCOUNTER22H => "hpmcounter22h",
+        HPMCOUNTER23H => "hpmcounter23h",
+        HPMCOUNTER24H => "hpmcounter24h",
+        HPMCOUNTER25H => "hpmcounter25h",
+        HPMCOUNTER26H => "hpmcounter26h",
+        HPMCOUNTER27H => "hpmcounter27h",
+        HPMCOUNTER28H => "hpmcounter28h",
+        HPMCOUNTER29H => "hpmcounter29h",
+        HPMCOUNTER30H => "hpmcounter30h",
+        HPMCOUNTER31H => "hpmcounter31h",
+        SSTATUS => "sstatus",
+        SIE => "sie",
+        STVEC => "stvec",
+        SCOUNTEREN => "scounteren",
+        SENVCFG => "senvcfg",
+        SSCRATCH => "sscratch",
+        SEPC => "sepc",
+        SCAUSE => "scause",
+        STVAL => "stval",
+        SIP => "sip",
+        SATP => "satp",
+        SCONTEXT => "scontext",
+        MVENDORID => "mvendorid",
+        MARCHID => "marchid",
+        MIMPID => "mimpid",
+        MHARTID => "mhartid",
+        MCONFIGPTR => "mconfigptr",
+        MSTATUS => "mstatus",
+        MISA => "misa",
+        MEDELEG => "medeleg",
+        MIDELEG => "mideleg",
+        MIE => "mie",
+        MTVEC => "mtvec",
+        MCOUNTEREN => "mcounteren",
+        MSTATUSH => "mstatush",
+        MSCRATCH => "mscratch",
+        MEPC => "mepc",
+        MCAUSE => "mcause",
+        MTVAL => "mtval",
+        MIP => "mip",
+        MTINST => "mtinst",
+        MTVAL2 => "mtval2",
+        MENVCFG => "menvcfg",
+        MENVCFGH => "menvcfgh",
+        MSECCFG => "mseccfg",
+        MSECCFGH => "mseccfgh",
+        PMPCFG0 => "pmpcfg0",
+        PMPCFG1 => "pmpcfg1",
+        PMPCFG2 => "pmpcfg2",
+        PMPCFG3 => "pmpcfg3",
+        PMPCFG4 => "pmpcfg4",
+        PMPCFG5 => "pmpcfg5",
+        PMPCFG6 => "pmpcfg6",
+        PMPCFG7 => "pmpcfg7",
+        PMPCFG8 => "pmpcfg8",
+        PMPCFG9 => "pmpcfg9",
+        PMPCFG10 => "pmpcfg10",
+        PMPCFG11 => "pmpcfg11",
+        PMPCFG12 => "pmpcfg12",
+        PMPCFG13 => "pmpcfg13",
+        PMPCFG14 => "pmpcfg14",
+        PMPCFG15 => "pmpcfg15",
+        PMPADDR0 => "pmpaddr0",
+        PMPADDR1 => "pmpaddr1",
+        PMPADDR2 => "pmpaddr2",
+        PMPADDR3 => "pmpaddr3",
+        PMPADDR4 => "pmpaddr4",
+        PMPADDR5 => "pmpaddr5",
+        PMPADDR6 => "pmpaddr6",
+        PMPADDR7 => "pmpaddr7",
+        PMPADDR8 => "pmpaddr8",
+        PMPADDR9 => "pmpaddr9",
+        PMPADDR10 => "pmpaddr10",
+        PMPADDR11 => "pmpaddr11",
+        PMPADDR12 => "pmpaddr12",
+        PMPADDR13 => "pmpaddr13",
+        PMPADDR14 => "pmpaddr14",
+        PMPADDR15 => "pmpaddr15",
+        PMPADDR16 => "pmpaddr16",
+        PMPADDR17 => "pmpaddr17",
+        PMPADDR18 => "pmpaddr18",
+        PMPADDR19 => "pmpaddr19",
+        PMPADDR20 => "pmpaddr20",
+        PMPADDR21 => "pmpaddr21",
+        PMPADDR22 => "pmpaddr22",
+        PMPADDR23 => "pmpaddr23",
+        PMPADDR24 => "pmpaddr24",
+        PMPADDR25 => "pmpaddr25",
+        PMPADDR26 => "pmpaddr26",
+        PMPADDR27 => "pmpaddr27",
+        PMPADDR28 => "pmpaddr28",
+        PMPADDR29 => "pmpaddr29",
+        PMPADDR30 => "pmpaddr30",
+        PMPADDR31 => "pmpaddr31",
+        PMPADDR32 => "pmpaddr32",
+        PMPADDR33 => "pmpaddr33",
+        PMPADDR34 => "pmpaddr34",
+        PMPADDR35 => "pmpaddr35",
+        PMPADDR36 => "pmpaddr36",
+        PMPADDR37 => "pmpaddr37",
+        PMPADDR38 => "pmpaddr38",
+        PMPADDR39 => "pmpaddr39",
+        PMPADDR40 => "pmpaddr40",
+        PMPADDR41 => "pmpaddr41",
+        PMPADDR42 => "pmpaddr42",
+        PMPADDR43 => "pmpaddr43",
+        PMPADDR44 => "pmpaddr44",
+        PMPADDR45 => "pmpaddr45",
+        PMPADDR46 => "pmpaddr46",
+        PMPADDR47 => "pmpaddr47",
+        PMPADDR48 => "pmpaddr48",
+        PMPADDR49 => "pmpaddr49",
+        PMPADDR50 => "pmpaddr50",
+        PMPADDR51 => "pmpaddr51",
+        PMPADDR52 => "pmpaddr52",
+        PMPADDR53 => "pmpaddr53",
+        PMPADDR54 => "pmpaddr54",
+        PMPADDR55 => "pmpaddr55",
+        PMPADDR56 => "pmpaddr56",
+        PMPADDR57 => "pmpaddr57",
+        PMPADDR58 => "pmpaddr58",
+        PMPADDR59 => "pmpaddr59",
+        PMPADDR60 => "pmpaddr60",
+        PMPADDR61 => "pmpaddr61",
+        PMPADDR62 => "pmpaddr62",
+        PMPADDR63 => "pmpaddr63",
+        MCYCLE => "mcycle",
+        MINSTRET => "minstret",
+        MHPMCOUNTER3 => "mhpmcounter3",
+        MHPMCOUNTER4 => "mhpmcounter4",
+        MHPMCOUNTER5 => "mhpmcounter5",
+        MHPMCOUNTER6 => "mhpmcounter6",
+        MHPMCOUNTER7 => "mhpmcounter7",
+        MHPMCOUNTER8 => "mhpmcounter8",
+        MHPMCOUNTER9 => "mhpmcounter9",
+        MHPMCOUNTER10 => "mhpmcounter10",
+        MHPMCOUNTER11 => "mhpmcounter11",
+        MHPMCOUNTER12 => "mhpmcounter12",
+        MHPMCOUNTER13 => "mhpmcounter13",
+        MHPMCOUNTER14 => "mhpmcounter14",
+        MHPMCOUNTER15 => "mhpmcounter15",
+        MHPMCOUNTER16 => "mhpmcounter16",
+        MHPMCOUNTER17 => "mhpmcounter17",
+        MHPMCOUNTER18 => "mhpmcounter18",
+        MHPMCOUNTER19 => "mhpmcounter19",
+        MHPMCOUNTER20 => "mhpmcounter20",
+        MHPMCOUNTER21 => "mhpmcounter21",
+        MHPMCOUNTER22 => "mhpmcounter22",
+        MHPMCOUNTER23 => "mhpmcounter23",
+        MHPMCOUNTER24 => "mhpmcounter24",
+        MHPMCOUNTER25 => "mhpmcounter25",
+        MHPMCOUNTER26 => "mhpmcounter26",
+        MHPMCOUNTER27 => "mhpmcounter27",
+        MHPMCOUNTER28 => "mhpmcounter28",
+        MHPMCOUNTER29 => "mhpmcounter29",
+        MHPMCOUNTER30 => "mhpmcounter30",
+        MHPMCOUNTER31 => "mhpmcounter31",
+        MCYCLEH => "mcycleh",
+        MINSTRETH => "minstreth",
+        MHPMCOUNTER3H => "mhpmcounter3h",
+        MHPMCOUNTER4H => "mhpmcounter4h",
+        MHPMCOUNTER5H => "mhpmcounter5h",
+        MHPMCOUNTER6H => "mhpmcounter6h",
+        MHPMCOUNTER7H => "mhpmcounter7h",
+        MHPMCOUNTER8H => "mhpmcounter8h",
+        MHPMCOUNTER9H => "mhpmcounter9h",
+        MHPMCOUNTER10H => "mhpmcounter10h",
+        MHPMCOUNTER11H => "mhpmcounter11h",
+        MHPMCOUNTER12H => "mhpmcounter12h",
+        MHPMCOUNTER13H => "mhpmcounter13h",
+        MHPMCOUNTER14H => "mhpmcounter14h",
+        MHPMCOUNTER15H => "mhpmcounter15h",
+        MHPMCOUNTER16H => "mhpmcounter16h",
+        MHPMCOUNTER17H => "mhpmcounter17h",
+        MHPMCOUNTER18H => "mhpmcounter18h",
+        MHPMCOUNTER19H => "mhpmcounter19h",
+        MHPMCOUNTER20H => "mhpmcounter20h",
+        MHPMCOUNTER21H => "mhpmcounter21h",
+        MHPMCOUNTER22H => "mhpmcounter22h",
+        MHPMCOUNTER23H => "mhpmcounter23h",
+        MHPMCOUNTER24H => "mhpmcounter24h",
+        MHPMCOUNTER25H => "mhpmcounter25h",
+        MHPMCOUNTER26H => "mhpmcounter26h",
+        MHPMCOUNTER27H => "mhpmcounter27h",
+        MHPMCOUNTER28H => "mhpmcounter28h",
+        MHPMCOUNTER29H => "mhpmcounter29h",
+        MHPMCOUNTER30H => "mhpmcounter30h",
+        MHPMCOUNTER31H => "mhpmcounter31h",
+        MCOUNTINHIBIT => "mcountinhibit",
+        MHPMEVENT3 => "mhpmevent3",
+        MHPMEVENT4 => "mhpmevent4",
+        MHPMEVENT5 => "mhpmevent5",
+        MHPMEVENT6 => "mhpmevent6",
+        MHPMEVENT7 => "mhpmevent7",
+        MHPMEVENT8 => "mhpmevent8",
+        MHPMEVENT9 => "mhpmevent9",
+        MHPMEVENT10 => "mhpmevent10",
+        MHPMEVENT11 => "mhpmevent11",
+        MHPMEVENT12 => "mhpmevent12",
+        MHPMEVENT13 => "mhpmevent13",
+        MHPMEVENT14 => "mhpmevent14",
+        MHPMEVENT15 => "mhpmevent15",
+        MHPMEVENT16 => "mhpmevent16",
+        MHPMEVENT17 => "mhpmevent17",
+        MHPMEVENT18 => "mhpmevent18",
+        MHPMEVENT19 => "mhpmevent19",
+        MHPMEVENT20 => "mhpmevent20",
+        MHPMEVENT21 => "mhpmevent21",
+        MHPMEVENT22 => "mhpmevent22",
+        MHPMEVENT23 => "mhpmevent23",
+        MHPMEVENT24 => "mhpmevent24",
+        MHPMEVENT25 => "mhpmevent25",
+        MHPMEVENT26 => "mhpmevent26",
+        MHPMEVENT27 => "mhpmevent27",
+        MHPMEVENT28 => "mhpmevent28",
+        MHPMEVENT29 => "mhpmevent29",
+        MHPMEVENT30 => "mhpmevent30",
+        MHPMEVENT31 => "mhpmevent31",
+        TSELECT => "tselect",
+        TDATA1 => "tdata1",
+        TDATA2 => "tdata2",
+        TDATA3 => "tdata3",
+        TINFO => "tinfo",
+        MCONTEXT => "mcontext",
+        _ => return None,
+    })
+}
+
 /// Returns `true` if `specifier` is valid, which is the case if it fits in 12 bits.
 pub fn is_valid(specifier: CsrSpecifier) -> bool {
     specifier < 1 << 12
@@ -1,8 +1,11 @@
 use log::trace;
 
 use super::mmu::MemoryError;
+use super::status::ExtensionContextStatus;
+use super::trigger::TriggerAccess;
 use crate::core::{Core, CsrSpecifier, Exception, ExecutionResult};
-use crate::instruction::{CsrOp, FenceOrderCombination};
+use crate::float::{Format, RoundingMode};
+use crate::instruction::{CsrOp, FenceOrderCombination, FloatPrecision};
 use crate::registers::{Registers, Specifier};
 use crate::system_bus::SystemBus;
 use crate::{Alignment, Allocator, PrivilegeLevel, RawPrivilegeLevel};
@@ -11,6 +14,9 @@ use crate::{Alignment, Allocator, PrivilegeLevel, RawPrivilegeLevel};
 pub(super) struct Executor<'a, 'c, A: Allocator, B: SystemBus<A>> {
     pub allocator: &'a mut A,
     pub core: &'c Core<A, B>,
+    /// Length in bytes of the instruction being executed: `2` for compressed instructions, `4`
+    /// otherwise. This is the amount by which `pc` is incremented.
+    pub instruction_length: u32,
 }
 
 impl<A: Allocator, B: SystemBus<A>> Executor<'_, '_, A, B> {
@@ -162,7 +168,7 @@ impl<A: Allocator, B: SystemBus<A>> Executor<'_, '_, A, B> {
         let result = immediate as u32 & !0xFFF;
         let registers = self.core.registers_mut(self.allocator);
         registers.set_x(dest, result);
-        increment_pc(registers);
+        increment_pc(registers, self.instruction_length);
         Ok(())
     }
 
@@ -181,7 +187,7 @@ impl<A: Allocator, B: SystemBus<A>> Executor<'_, '_, A, B> {
         let registers = self.core.registers_mut(self.allocator);
         let result = registers.pc().wrapping_add_signed(immediate & !0xFFF);
         registers.set_x(dest, result);
-        increment_pc(registers);
+        increment_pc(registers, self.instruction_length);
         Ok(())
     }
 
@@ -426,6 +432,404 @@ impl<A: Allocator, B: SystemBus<A>> Executor<'_, '_, A, B> {
         self.reg_reg_op(dest, src1, src2, |s1, s2| s1.checked_rem(s2).unwrap_or(s1))
     }
 
+    /// Executes a `sh1add` instruction.
+    ///
+    /// Corresponds to the assembly instruction `sh1add dest src1 src2`.
+    ///
+    /// > This instruction shifts rs1 to the left by 1 bit and adds it to rs2.
+    pub fn sh1add(&mut self, dest: Specifier, src1: Specifier, src2: Specifier) -> ExecutionResult {
+        trace!("Executing sh1add {dest} {src1} {src2}");
+        self.reg_reg_op(dest, src1, src2, |s1, s2| s2.wrapping_add(s1 << 1))
+    }
+
+    /// Executes a `sh2add` instruction.
+    ///
+    /// Corresponds to the assembly instruction `sh2add dest src1 src2`.
+    ///
+    /// > This instruction shifts rs1 to the left by 2 bits and adds it to rs2.
+    pub fn sh2add(&mut self, dest: Specifier, src1: Specifier, src2: Specifier) -> ExecutionResult {
+        trace!("Executing sh2add {dest} {src1} {src2}");
+        self.reg_reg_op(dest, src1, src2, |s1, s2| s2.wrapping_add(s1 << 2))
+    }
+
+    /// Executes a `sh3add` instruction.
+    ///
+    /// Corresponds to the assembly instruction `sh3add dest src1 src2`.
+    ///
+    /// > This instruction shifts rs1 to the left by 3 bits and adds it to rs2.
+    pub fn sh3add(&mut self, dest: Specifier, src1: Specifier, src2: Specifier) -> ExecutionResult {
+        trace!("Executing sh3add {dest} {src1} {src2}");
+        self.reg_reg_op(dest, src1, src2, |s1, s2| s2.wrapping_add(s1 << 3))
+    }
+
+    /// Executes an `andn` instruction.
+    ///
+    /// Corresponds to the assembly instruction `andn dest src1 src2`.
+    ///
+    /// > This instruction performs the bitwise logical AND operation between rs1 and the bitwise
+    /// > inversion of rs2.
+    pub fn andn(&mut self, dest: Specifier, src1: Specifier, src2: Specifier) -> ExecutionResult {
+        trace!("Executing andn {dest} {src1} {src2}");
+        self.reg_reg_op(dest, src1, src2, |s1, s2| s1 & !s2)
+    }
+
+    /// Executes an `orn` instruction.
+    ///
+    /// Corresponds to the assembly instruction `orn dest src1 src2`.
+    ///
+    /// > This instruction performs the bitwise logical OR operation between rs1 and the bitwise
+    /// > inversion of rs2.
+    pub fn orn(&mut self, dest: Specifier, src1: Specifier, src2: Specifier) -> ExecutionResult {
+        trace!("Executing orn {dest} {src1} {src2}");
+        self.reg_reg_op(dest, src1, src2, |s1, s2| s1 | !s2)
+    }
+
+    /// Executes a `xnor` instruction.
+    ///
+    /// Corresponds to the assembly instruction `xnor dest src1 src2`.
+    ///
+    /// > This instruction performs the bit-wise exclusive-NOR operation on rs1 and rs2.
+    pub fn xnor(&mut self, dest: Specifier, src1: Specifier, src2: Specifier) -> ExecutionResult {
+        trace!("Executing xnor {dest} {src1} {src2}");
+        self.reg_reg_op(dest, src1, src2, |s1, s2| !(s1 ^ s2))
+    }
+
+    /// Executes a `max` instruction.
+    ///
+    /// Corresponds to the assembly instruction `max dest src1 src2`.
+    ///
+    /// > This instruction returns the larger of two signed integers.
+    pub fn max(&mut self, dest: Specifier, src1: Specifier, src2: Specifier) -> ExecutionResult {
+        trace!("Executing max {dest} {src1} {src2}");
+        self.reg_reg_op(dest, src1, src2, |s1, s2| (s1 as i32).max(s2 as i32) as u32)
+    }
+
+    /// Executes a `maxu` instruction.
+    ///
+    /// Corresponds to the assembly instruction `maxu dest src1 src2`.
+    ///
+    /// > This instruction returns the larger of two unsigned integers.
+    pub fn maxu(&mut self, dest: Specifier, src1: Specifier, src2: Specifier) -> ExecutionResult {
+        trace!("Executing maxu {dest} {src1} {src2}");
+        self.reg_reg_op(dest, src1, src2, |s1, s2| s1.max(s2))
+    }
+
+    /// Executes a `min` instruction.
+    ///
+    /// Corresponds to the assembly instruction `min dest src1 src2`.
+    ///
+    /// > This instruction returns the smaller of two signed integers.
+    pub fn min(&mut self, dest: Specifier, src1: Specifier, src2: Specifier) -> ExecutionResult {
+        trace!("Executing min {dest} {src1} {src2}");
+        self.reg_reg_op(dest, src1, src2, |s1, s2| (s1 as i32).min(s2 as i32) as u32)
+    }
+
+    /// Executes a `minu` instruction.
+    ///
+    /// Corresponds to the assembly instruction `minu dest src1 src2`.
+    ///
+    /// > This instruction returns the smaller of two unsigned integers.
+    pub fn minu(&mut self, dest: Specifier, src1: Specifier, src2: Specifier) -> ExecutionResult {
+        trace!("Executing minu {dest} {src1} {src2}");
+        self.reg_reg_op(dest, src1, src2, |s1, s2| s1.min(s2))
+    }
+
+    /// Executes a `rol` instruction.
+    ///
+    /// Corresponds to the assembly instruction `rol dest src1 src2`.
+    ///
+    /// > This instruction performs a rotate left of rs1 by the amount in least-significant log2(XLEN)
+    /// > bits of rs2.
+    pub fn rol(&mut self, dest: Specifier, src1: Specifier, src2: Specifier) -> ExecutionResult {
+        trace!("Executing rol {dest} {src1} {src2}");
+        self.reg_reg_op(dest, src1, src2, |s1, s2| s1.rotate_left(s2 & 0x1F))
+    }
+
+    /// Executes a `ror` instruction.
+    ///
+    /// Corresponds to the assembly instruction `ror dest src1 src2`.
+    ///
+    /// > This instruction performs a rotate right of rs1 by the amount in least-significant
+    /// > log2(XLEN) bits of rs2.
+    pub fn ror(&mut self, dest: Specifier, src1: Specifier, src2: Specifier) -> ExecutionResult {
+        trace!("Executing ror {dest} {src1} {src2}");
+        self.reg_reg_op(dest, src1, src2, |s1, s2| s1.rotate_right(s2 & 0x1F))
+    }
+
+    /// Executes a `rori` instruction.
+    ///
+    /// Corresponds to the assembly instruction `rori dest src shift_amount_u5`.
+    ///
+    /// > This instruction performs a rotate right of rs1 by the amount in the least-significant
+    /// > log2(XLEN) bits of shamt.
+    ///
+    /// # Panics
+    ///
+    /// `shift_amount` must fit in a u5 (`0..=31`), otherwise this will panic.
+    pub fn rori(
+        &mut self,
+        dest: Specifier,
+        src: Specifier,
+        shift_amount_u5: u32,
+    ) -> ExecutionResult {
+        trace!("Executing rori {dest} {src} {shift_amount_u5}");
+        self.reg_shamt_op(dest, src, shift_amount_u5, |s, shamt| s.rotate_right(shamt))
+    }
+
+    /// Executes a `clz` instruction.
+    ///
+    /// Corresponds to the assembly instruction `clz dest src`.
+    ///
+    /// > This instruction counts the number of 0's before the first 1, starting at the
+    /// > most-significant bit (i.e., XLEN-1) and progressing to bit 0. Accordingly, if the input is
+    /// > 0, the output is XLEN, and if the most-significant bit of the input is a 1, the output is 0.
+    pub fn clz(&mut self, dest: Specifier, src: Specifier) -> ExecutionResult {
+        trace!("Executing clz {dest} {src}");
+        self.reg_unary_op(dest, src, |s| s.leading_zeros())
+    }
+
+    /// Executes a `ctz` instruction.
+    ///
+    /// Corresponds to the assembly instruction `ctz dest src`.
+    ///
+    /// > This instruction counts the number of 0's before the first 1, starting at the
+    /// > least-significant bit (i.e., 0) and progressing to the most-significant bit (i.e.,
+    /// > XLEN-1). Accordingly, if the input is 0, the output is XLEN, and if the least-significant
+    /// > bit of the input is a 1, the output is 0.
+    pub fn ctz(&mut self, dest: Specifier, src: Specifier) -> ExecutionResult {
+        trace!("Executing ctz {dest} {src}");
+        self.reg_unary_op(dest, src, |s| s.trailing_zeros())
+    }
+
+    /// Executes a `cpop` instruction.
+    ///
+    /// Corresponds to the assembly instruction `cpop dest src`.
+    ///
+    /// > This instructions counts the number of 1's (i.e., set bits) in the source register.
+    pub fn cpop(&mut self, dest: Specifier, src: Specifier) -> ExecutionResult {
+        trace!("Executing cpop {dest} {src}");
+        self.reg_unary_op(dest, src, |s| s.count_ones())
+    }
+
+    /// Executes a `sext.b` instruction.
+    ///
+    /// Corresponds to the assembly instruction `sext.b dest src`.
+    ///
+    /// > This instruction sign-extends the least-significant byte in the source to XLEN by copying
+    /// > the most-significant bit in the byte (i.e., bit 7) to all of the more-significant bits.
+    pub fn sext_b(&mut self, dest: Specifier, src: Specifier) -> ExecutionResult {
+        trace!("Executing sext.b {dest} {src}");
+        self.reg_unary_op(dest, src, |s| s as i8 as u32)
+    }
+
+    /// Executes a `sext.h` instruction.
+    ///
+    /// Corresponds to the assembly instruction `sext.h dest src`.
+    ///
+    /// > This instruction sign-extends the least-significant halfword in rs to XLEN by copying the
+    /// > most-significant bit in the halfword (i.e., bit 15) to all of the more-significant bits.
+    pub fn sext_h(&mut self, dest: Specifier, src: Specifier) -> ExecutionResult {
+        trace!("Executing sext.h {dest} {src}");
+        self.reg_unary_op(dest, src, |s| s as i16 as u32)
+    }
+
+    /// Executes a `zext.h` instruction.
+    ///
+    /// Corresponds to the assembly instruction `zext.h dest src`.
+    ///
+    /// > This instruction zero-extends the least-significant halfword of the source to XLEN by
+    /// > inserting 0's into all of the bits more significant than 15.
+    pub fn zext_h(&mut self, dest: Specifier, src: Specifier) -> ExecutionResult {
+        trace!("Executing zext.h {dest} {src}");
+        self.reg_unary_op(dest, src, |s| s as u16 as u32)
+    }
+
+    /// Executes an `orc.b` instruction.
+    ///
+    /// Corresponds to the assembly instruction `orc.b dest src`.
+    ///
+    /// > Combines the bits within each byte using bitwise logical OR. This sets the bits of each
+    /// > byte in the result rd to all zeros if no bit within the respective byte of rs is set, or to
+    /// > all ones if any bit within the respective byte of rs is set.
+    pub fn orc_b(&mut self, dest: Specifier, src: Specifier) -> ExecutionResult {
+        trace!("Executing orc.b {dest} {src}");
+        self.reg_unary_op(dest, src, |s| {
+            u32::from_le_bytes(s.to_le_bytes().map(|byte| if byte == 0 { 0 } else { 0xFF }))
+        })
+    }
+
+    /// Executes a `rev8` instruction.
+    ///
+    /// Corresponds to the assembly instruction `rev8 dest src`.
+    ///
+    /// > This instruction reverses the order of the bytes in rs.
+    pub fn rev8(&mut self, dest: Specifier, src: Specifier) -> ExecutionResult {
+        trace!("Executing rev8 {dest} {src}");
+        self.reg_unary_op(dest, src, |s| s.swap_bytes())
+    }
+
+    /// Executes a `clmul` instruction.
+    ///
+    /// Corresponds to the assembly instruction `clmul dest src1 src2`.
+    ///
+    /// > clmul produces the lower half of the 2·XLEN carry-less product.
+    pub fn clmul(&mut self, dest: Specifier, src1: Specifier, src2: Specifier) -> ExecutionResult {
+        trace!("Executing clmul {dest} {src1} {src2}");
+        self.reg_reg_op(dest, src1, src2, |s1, s2| carryless_multiply(s1, s2) as u32)
+    }
+
+    /// Executes a `clmulh` instruction.
+    ///
+    /// Corresponds to the assembly instruction `clmulh dest src1 src2`.
+    ///
+    /// > clmulh produces the upper half of the 2·XLEN carry-less product.
+    pub fn clmulh(&mut self, dest: Specifier, src1: Specifier, src2: Specifier) -> ExecutionResult {
+        trace!("Executing clmulh {dest} {src1} {src2}");
+        self.reg_reg_op(dest, src1, src2, |s1, s2| {
+            (carryless_multiply(s1, s2) >> 32) as u32
+        })
+    }
+
+    /// Executes a `clmulr` instruction.
+    ///
+    /// Corresponds to the assembly instruction `clmulr dest src1 src2`.
+    ///
+    /// > clmulr produces bits 2·XLEN−2:XLEN-1 of the 2·XLEN carry-less product.
+    pub fn clmulr(&mut self, dest: Specifier, src1: Specifier, src2: Specifier) -> ExecutionResult {
+        trace!("Executing clmulr {dest} {src1} {src2}");
+        self.reg_reg_op(dest, src1, src2, |s1, s2| {
+            (carryless_multiply(s1, s2) >> 31) as u32
+        })
+    }
+
+    /// Executes a `bclr` instruction.
+    ///
+    /// Corresponds to the assembly instruction `bclr dest src1 src2`.
+    ///
+    /// > This instruction returns rs1 with a single bit cleared at the index specified in rs2. The
+    /// > index is read from the lower log2(XLEN) bits of rs2.
+    pub fn bclr(&mut self, dest: Specifier, src1: Specifier, src2: Specifier) -> ExecutionResult {
+        trace!("Executing bclr {dest} {src1} {src2}");
+        self.reg_reg_op(dest, src1, src2, |s1, s2| s1 & !(1 << (s2 & 0x1F)))
+    }
+
+    /// Executes a `bclri` instruction.
+    ///
+    /// Corresponds to the assembly instruction `bclri dest src shift_amount_u5`.
+    ///
+    /// > This instruction returns rs1 with a single bit cleared at the index specified in shamt. The
+    /// > index is read from the lower log2(XLEN) bits of shamt. For RV32, the encodings corresponding
+    /// > to shamt[5]=1 are reserved.
+    ///
+    /// # Panics
+    ///
+    /// `shift_amount` must fit in a u5 (`0..=31`), otherwise this will panic.
+    pub fn bclri(
+        &mut self,
+        dest: Specifier,
+        src: Specifier,
+        shift_amount_u5: u32,
+    ) -> ExecutionResult {
+        trace!("Executing bclri {dest} {src} {shift_amount_u5}");
+        self.reg_shamt_op(dest, src, shift_amount_u5, |s, shamt| s & !(1 << shamt))
+    }
+
+    /// Executes a `bext` instruction.
+    ///
+    /// Corresponds to the assembly instruction `bext dest src1 src2`.
+    ///
+    /// > This instruction returns a single bit extracted from rs1 at the index specified in rs2. The
+    /// > index is read from the lower log2(XLEN) bits of rs2.
+    pub fn bext(&mut self, dest: Specifier, src1: Specifier, src2: Specifier) -> ExecutionResult {
+        trace!("Executing bext {dest} {src1} {src2}");
+        self.reg_reg_op(dest, src1, src2, |s1, s2| (s1 >> (s2 & 0x1F)) & 1)
+    }
+
+    /// Executes a `bexti` instruction.
+    ///
+    /// Corresponds to the assembly instruction `bexti dest src shift_amount_u5`.
+    ///
+    /// > This instruction returns a single bit extracted from rs1 at the index specified in shamt. The
+    /// > index is read from the lower log2(XLEN) bits of shamt. For RV32, the encodings corresponding
+    /// > to shamt[5]=1 are reserved.
+    ///
+    /// # Panics
+    ///
+    /// `shift_amount` must fit in a u5 (`0..=31`), otherwise this will panic.
+    pub fn bexti(
+        &mut self,
+        dest: Specifier,
+        src: Specifier,
+        shift_amount_u5: u32,
+    ) -> ExecutionResult {
+        trace!("Executing bexti {dest} {src} {shift_amount_u5}");
+        self.reg_shamt_op(dest, src, shift_amount_u5, |s, shamt| (s >> shamt) & 1)
+    }
+
+    /// Executes a `binv` instruction.
+    ///
+    /// Corresponds to the assembly instruction `binv dest src1 src2`.
+    ///
+    /// > This instruction returns rs1 with a single bit inverted at the index specified in rs2. The
+    /// > index is read from the lower log2(XLEN) bits of rs2.
+    pub fn binv(&mut self, dest: Specifier, src1: Specifier, src2: Specifier) -> ExecutionResult {
+        trace!("Executing binv {dest} {src1} {src2}");
+        self.reg_reg_op(dest, src1, src2, |s1, s2| s1 ^ (1 << (s2 & 0x1F)))
+    }
+
+    /// Executes a `binvi` instruction.
+    ///
+    /// Corresponds to the assembly instruction `binvi dest src shift_amount_u5`.
+    ///
+    /// > This instruction returns rs1 with a single bit inverted at the index specified in shamt. The
+    /// > index is read from the lower log2(XLEN) bits of shamt. For RV32, the encodings corresponding
+    /// > to shamt[5]=1 are reserved.
+    ///
+    /// # Panics
+    ///
+    /// `shift_amount` must fit in a u5 (`0..=31`), otherwise this will panic.
+    pub fn binvi(
+        &mut self,
+        dest: Specifier,
+        src: Specifier,
+        shift_amount_u5: u32,
+    ) -> ExecutionResult {
+        trace!("Executing binvi {dest} {src} {shift_amount_u5}");
+        self.reg_shamt_op(dest, src, shift_amount_u5, |s, shamt| s ^ (1 << shamt))
+    }
+
+    /// Executes a `bset` instruction.
+    ///
+    /// Corresponds to the assembly instruction `bset dest src1 src2`.
+    ///
+    /// > This instruction returns rs1 with a single bit set at the index specified in rs2. The index is
+    /// > read from the lower log2(XLEN) bits of rs2.
+    pub fn bset(&mut self, dest: Specifier, src1: Specifier, src2: Specifier) -> ExecutionResult {
+        trace!("Executing bset {dest} {src1} {src2}");
+        self.reg_reg_op(dest, src1, src2, |s1, s2| s1 | (1 << (s2 & 0x1F)))
+    }
+
+    /// Executes a `bseti` instruction.
+    ///
+    /// Corresponds to the assembly instruction `bseti dest src shift_amount_u5`.
+    ///
+    /// > This instruction returns rs1 with a single bit set at the index specified in shamt. The index
+    /// > is read from the lower log2(XLEN) bits of shamt. For RV32, the encodings corresponding to
+    /// > shamt[5]=1 are reserved.
+    ///
+    /// # Panics
+    ///
+    /// `shift_amount` must fit in a u5 (`0..=31`), otherwise this will panic.
+    pub fn bseti(
+        &mut self,
+        dest: Specifier,
+        src: Specifier,
+        shift_amount_u5: u32,
+    ) -> ExecutionResult {
+        trace!("Executing bseti {dest} {src} {shift_amount_u5}");
+        self.reg_shamt_op(dest, src, shift_amount_u5, |s, shamt| s | (1 << shamt))
+    }
+
     pub fn jal(&mut self, dest: Specifier, offset: i32) -> ExecutionResult {
         trace!("Executing jal {dest} {offset}");
         self.jump_op(dest, |registers| registers.pc().wrapping_add_signed(offset))
@@ -540,25 +944,47 @@ impl<A: Allocator, B: SystemBus<A>> Executor<'_, '_, A, B> {
         })
     }
 
+    /// Executes an `lr.w` instruction.
+    ///
+    /// Corresponds to the assembly instruction `lr.w dest (addr)`.
+    ///
+    /// > LR.W loads a word from the address in rs1, places the sign-extended value in rd, and
+    /// > registers a reservation set—a set of bytes that subsumes the bytes in the addressed word.
     pub fn lr(&mut self, dest: Specifier, _src: Specifier, addr: Specifier) -> ExecutionResult {
         trace!("Executing lr {dest}, ({addr})");
         self.load_op(dest, addr, 0, |this, address| {
             if !Alignment::WORD.is_aligned(address) {
                 return Err(MemoryError::MisalignedAccess);
             }
-            this.core.mmu().read_word(this.allocator, address)
+            this.core.mmu().load_reserved_word(this.allocator, address)
         })
     }
 
+    /// Executes an `sc.w` instruction.
+    ///
+    /// Corresponds to the assembly instruction `sc.w dest src (addr)`.
+    ///
+    /// > SC.W conditionally writes a word in rs2 to the address in rs1: the SC.W succeeds only if
+    /// > the reservation is still valid and the reservation set contains the bytes being written.
+    /// > If the SC.W succeeds, the instruction writes the word in rs2 to memory, and it writes
+    /// > zero to rd. If the SC.W fails, the instruction does not write to memory, and it writes a
+    /// > nonzero value to rd. Regardless of success or failure, executing an SC.W instruction
+    /// > invalidates any reservation held by this hart.
     pub fn sc(&mut self, dest: Specifier, src: Specifier, addr: Specifier) -> ExecutionResult {
         trace!("Executing sc {dest}, {src}, ({addr})");
         self.store_op(src, addr, 0, |this, address, value| {
             if !Alignment::WORD.is_aligned(address) {
                 return Err(MemoryError::MisalignedAccess);
             }
-            // Since only one core is supported, sc always succeeds.
-            this.core.registers_mut(this.allocator).set_x(dest, 0);
-            this.core.mmu().write_word(this.allocator, address, value)
+            let success = this
+                .core
+                .mmu()
+                .store_conditional_word(this.allocator, address, value)?;
+            // Failure code 1 encodes an unspecified failure.
+            this.core
+                .registers_mut(this.allocator)
+                .set_x(dest, !success as u32);
+            Ok(())
         })
     }
 
@@ -620,7 +1046,21 @@ impl<A: Allocator, B: SystemBus<A>> Executor<'_, '_, A, B> {
         // Since only one core is supported, this is equivalent to a nop instruction.
         let _ = predecessor;
         let _ = successor;
-        increment_pc(self.core.registers_mut(self.allocator));
+        increment_pc(
+            self.core.registers_mut(self.allocator),
+            self.instruction_length,
+        );
+        Ok(())
+    }
+
+    /// Executes a `fence.i` instruction, which is a nop since instructions are always fetched from
+    /// memory, rather than from a cache that could be stale.
+    pub fn fence_i(&mut self) -> ExecutionResult {
+        trace!("Executing fence.i");
+        increment_pc(
+            self.core.registers_mut(self.allocator),
+            self.instruction_length,
+        );
         Ok(())
     }
 
@@ -633,9 +1073,25 @@ impl<A: Allocator, B: SystemBus<A>> Executor<'_, '_, A, B> {
         }
     }
 
+    /// Executes an `ebreak` instruction.
+    ///
+    /// If it is part of the semihosting sequence and the system bus supports semihosting in the
+    /// current privilege mode, this performs the semihosting call instead of raising a breakpoint
+    /// exception. See [`Core::is_semihosting_sequence`].
     pub fn ebreak(&mut self) -> ExecutionResult {
         trace!("Executing ebreak");
-        Err(Exception::Breakpoint)
+        let pc = self.core.registers(self.allocator).pc();
+        if self.instruction_length == 4
+            && self.core.is_semihosting_sequence(self.allocator, pc)
+            && self.core.semihosting_call(self.allocator)
+        {
+            increment_pc(
+                self.core.registers_mut(self.allocator),
+                self.instruction_length,
+            );
+            return Ok(());
+        }
+        Err(Exception::Breakpoint(pc))
     }
 
     /// Executes a `csrrw` instruction.
@@ -857,8 +1313,13 @@ impl<A: Allocator, B: SystemBus<A>> Executor<'_, '_, A, B> {
         {
             return Err(Exception::IllegalInstruction(None));
         }
-        // Implemented as a nop, which is allowed.
-        increment_pc(self.core.registers_mut(self.allocator));
+        // Execution resumes after the WFI, so an interrupt taken while stalled returns to the next
+        // instruction.
+        increment_pc(
+            self.core.registers_mut(self.allocator),
+            self.instruction_length,
+        );
+        self.core.wait_for_interrupt(self.allocator);
         Ok(())
     }
 
@@ -869,7 +1330,531 @@ impl<A: Allocator, B: SystemBus<A>> Executor<'_, '_, A, B> {
         if self.core.status.get(self.allocator).tvm() {
             return Err(Exception::IllegalInstruction(None));
         }
-        increment_pc(self.core.registers_mut(self.allocator));
+        increment_pc(
+            self.core.registers_mut(self.allocator),
+            self.instruction_length,
+        );
+        Ok(())
+    }
+
+    /// Executes a `flw` or `fld` instruction.
+    ///
+    /// Corresponds to the assembly instruction `flw dest offset(base)` or `fld dest offset(base)`.
+    ///
+    /// > The FLW instruction loads a single-precision floating-point value from memory into
+    /// > floating-point register rd. [...] FLW and FSW are only guaranteed to execute atomically
+    /// > if the effective address is naturally aligned. FLW and FSW do not modify the bits being
+    /// > transferred; in particular, the payloads of non-canonical NaNs are preserved.
+    pub fn fload(
+        &mut self,
+        precision: FloatPrecision,
+        dest: Specifier,
+        base: Specifier,
+        offset: i32,
+    ) -> ExecutionResult {
+        trace!(
+            "Executing fl{} f{} {offset}({base})",
+            match precision {
+                FloatPrecision::Single => "w",
+                FloatPrecision::Double => "d",
+            },
+            u8::from(dest)
+        );
+        self.check_fp_enabled()?;
+        let address = self
+            .core
+            .registers(self.allocator)
+            .x(base)
+            .wrapping_add_signed(offset);
+        self.core
+            .check_triggers(self.allocator, TriggerAccess::Load, address, None)?;
+        let mmu = self.core.mmu();
+        let value = match precision {
+            FloatPrecision::Single => mmu
+                .read_word(self.allocator, address)
+                .map(|value| value as u64),
+            FloatPrecision::Double => mmu.read_doubleword(self.allocator, address),
+        }
+        .map_err(|err| match err {
+            MemoryError::MisalignedAccess => Exception::LoadAddressMisaligned(address),
+            MemoryError::AccessFault | MemoryError::BusFault(_) => {
+                Exception::LoadAccessFault(address)
+            }
+            MemoryError::PageFault => Exception::LoadPageFault(address),
+        })?;
+        // Data triggers compare XLEN bits, so only the lower word of a double is compared.
+        self.core.check_triggers(
+            self.allocator,
+            TriggerAccess::Load,
+            address,
+            Some(value as u32),
+        )?;
+        self.write_f(precision, dest, value, 0);
+        Ok(())
+    }
+
+    /// Executes a `fsw` or `fsd` instruction.
+    ///
+    /// Corresponds to the assembly instruction `fsw src offset(base)` or `fsd src offset(base)`.
+    ///
+    /// > FSW stores a single-precision value from floating-point register rs2 to memory.
+    ///
+    /// The stored bits are taken from the register as is, without checking the NaN-boxing.
+    pub fn fstore(
+        &mut self,
+        precision: FloatPrecision,
+        src: Specifier,
+        base: Specifier,
+        offset: i32,
+    ) -> ExecutionResult {
+        trace!(
+            "Executing fs{} f{} {offset}({base})",
+            match precision {
+                FloatPrecision::Single => "w",
+                FloatPrecision::Double => "d",
+            },
+            u8::from(src)
+        );
+        self.check_fp_enabled()?;
+        let address = self
+            .core
+            .registers(self.allocator)
+            .x(base)
+            .wrapping_add_signed(offset);
+        let value = self.core.float_registers(self.allocator).f(src);
+        self.core
+            .check_triggers(self.allocator, TriggerAccess::Store, address, None)?;
+        // Data triggers compare XLEN bits, so only the lower word of a double is compared.
+        self.core.check_triggers(
+            self.allocator,
+            TriggerAccess::Store,
+            address,
+            Some(value as u32),
+        )?;
+        let mmu = self.core.mmu();
+        match precision {
+            FloatPrecision::Single => mmu.write_word(self.allocator, address, value as u32),
+            FloatPrecision::Double => mmu.write_doubleword(self.allocator, address, value),
+        }
+        .map_err(|err| match err {
+            MemoryError::MisalignedAccess => Exception::StoreOrAmoAddressMisaligned(address),
+            MemoryError::AccessFault | MemoryError::BusFault(_) => {
+                Exception::StoreOrAmoAccessFault(address)
+            }
+            MemoryError::PageFault => Exception::StoreOrAmoPageFault(address),
+        })?;
+        increment_pc(
+            self.core.registers_mut(self.allocator),
+            self.instruction_length,
+        );
+        Ok(())
+    }
+
+    /// Executes a fused multiply-add instruction: `fmadd`, `fmsub`, `fnmsub`, or `fnmadd`.
+    ///
+    /// > FMADD.S multiplies the values in rs1 and rs2, adds the value in rs3, and writes the final
+    /// > result to rd. FMADD.S computes (rs1×rs2)+rs3.
+    /// >
+    /// > FMSUB.S multiplies the values in rs1 and rs2, subtracts the value in rs3, and writes the
+    /// > final result to rd. FMSUB.S computes (rs1×rs2)-rs3.
+    /// >
+    /// > FNMSUB.S multiplies the values in rs1 and rs2, negates the product, adds the value in rs3,
+    /// > and writes the final result to rd. FNMSUB.S computes -(rs1×rs2)+rs3.
+    /// >
+    /// > FNMADD.S multiplies the values in rs1 and rs2, negates the product, subtracts the value in
+    /// > rs3, and writes the final result to rd. FNMADD.S computes -(rs1×rs2)-rs3.
+    #[allow(clippy::too_many_arguments)]
+    pub fn fused_multiply_add(
+        &mut self,
+        negate_product: bool,
+        negate_addend: bool,
+        precision: FloatPrecision,
+        rounding_mode: Option<RoundingMode>,
+        dest: Specifier,
+        src1: Specifier,
+        src2: Specifier,
+        src3: Specifier,
+    ) -> ExecutionResult {
+        trace!(
+            "Executing f{}m{}.{} f{} f{} f{} f{}",
+            if negate_product { "n" } else { "" },
+            if negate_product != negate_addend {
+                "sub"
+            } else {
+                "add"
+            },
+            width_suffix(precision),
+            u8::from(dest),
+            u8::from(src1),
+            u8::from(src2),
+            u8::from(src3)
+        );
+        self.check_fp_enabled()?;
+        let rm = self.resolve_rounding_mode(rounding_mode)?;
+        let a = self.read_f(precision, src1);
+        let b = self.read_f(precision, src2);
+        let c = self.read_f(precision, src3);
+        let mut flags = 0;
+        let result =
+            format(precision).mul_add(a, b, c, negate_product, negate_addend, rm, &mut flags);
+        self.write_f(precision, dest, result, flags);
+        Ok(())
+    }
+
+    /// Executes a `fadd` instruction.
+    ///
+    /// > FADD.S and FMUL.S perform single-precision floating-point addition and multiplication
+    /// > respectively, between rs1 and rs2.
+    pub fn fadd(
+        &mut self,
+        precision: FloatPrecision,
+        rounding_mode: Option<RoundingMode>,
+        dest: Specifier,
+        src1: Specifier,
+        src2: Specifier,
+    ) -> ExecutionResult {
+        trace!(
+            "Executing fadd.{} f{} f{} f{}",
+            width_suffix(precision),
+            u8::from(dest),
+            u8::from(src1),
+            u8::from(src2)
+        );
+        self.fp_arith_op(precision, rounding_mode, dest, src1, src2, Format::add)
+    }
+
+    /// Executes a `fsub` instruction.
+    ///
+    /// > FSUB.S performs the single-precision floating-point subtraction of rs2 from rs1.
+    pub fn fsub(
+        &mut self,
+        precision: FloatPrecision,
+        rounding_mode: Option<RoundingMode>,
+        dest: Specifier,
+        src1: Specifier,
+        src2: Specifier,
+    ) -> ExecutionResult {
+        trace!(
+            "Executing fsub.{} f{} f{} f{}",
+            width_suffix(precision),
+            u8::from(dest),
+            u8::from(src1),
+            u8::from(src2)
+        );
+        self.fp_arith_op(precision, rounding_mode, dest, src1, src2, Format::sub)
+    }
+
+    /// Executes a `fmul` instruction.
+    ///
+    /// > FADD.S and FMUL.S perform single-precision floating-point addition and multiplication
+    /// > respectively, between rs1 and rs2.
+    pub fn fmul(
+        &mut self,
+        precision: FloatPrecision,
+        rounding_mode: Option<RoundingMode>,
+        dest: Specifier,
+        src1: Specifier,
+        src2: Specifier,
+    ) -> ExecutionResult {
+        trace!(
+            "Executing fmul.{} f{} f{} f{}",
+            width_suffix(precision),
+            u8::from(dest),
+            u8::from(src1),
+            u8::from(src2)
+        );
+        self.fp_arith_op(precision, rounding_mode, dest, src1, src2, Format::mul)
+    }
+
+    /// Executes a `fdiv` instruction.
+    ///
+    /// > FDIV.S performs the single-precision floating-point division of rs1 by rs2.
+    pub fn fdiv(
+        &mut self,
+        precision: FloatPrecision,
+        rounding_mode: Option<RoundingMode>,
+        dest: Specifier,
+        src1: Specifier,
+        src2: Specifier,
+    ) -> ExecutionResult {
+        trace!(
+            "Executing fdiv.{} f{} f{} f{}",
+            width_suffix(precision),
+            u8::from(dest),
+            u8::from(src1),
+            u8::from(src2)
+        );
+        self.fp_arith_op(precision, rounding_mode, dest, src1, src2, Format::div)
+    }
+
+    /// Executes a `fsqrt` instruction.
+    ///
+    /// > FSQRT.S computes the square root of rs1.
+    pub fn fsqrt(
+        &mut self,
+        precision: FloatPrecision,
+        rounding_mode: Option<RoundingMode>,
+        dest: Specifier,
+        src: Specifier,
+    ) -> ExecutionResult {
+        trace!(
+            "Executing fsqrt.{} f{} f{}",
+            width_suffix(precision),
+            u8::from(dest),
+            u8::from(src)
+        );
+        self.check_fp_enabled()?;
+        let rm = self.resolve_rounding_mode(rounding_mode)?;
+        let a = self.read_f(precision, src);
+        let mut flags = 0;
+        let result = format(precision).sqrt(a, rm, &mut flags);
+        self.write_f(precision, dest, result, flags);
+        Ok(())
+    }
+
+    /// Executes a `fsgnj`, `fsgnjn`, or `fsgnjx` instruction.
+    ///
+    /// `op` computes the sign bit of the result from the sign bits of `src1` and `src2`.
+    ///
+    /// > Floating-point to floating-point sign-injection instructions, FSGNJ.S, FSGNJN.S, and
+    /// > FSGNJX.S, produce a result that takes all bits except the sign bit from rs1. For FSGNJ,
+    /// > the result's sign bit is rs2's sign bit; for FSGNJN, the result's sign bit is the
+    /// > opposite of rs2's sign bit; and for FSGNJX, the sign bit is the XOR of the sign bits of
+    /// > rs1 and rs2. Sign-injection instructions do not set floating-point exception flags, nor
+    /// > do they canonicalize NaNs.
+    pub fn fsgnj<F>(
+        &mut self,
+        precision: FloatPrecision,
+        dest: Specifier,
+        src1: Specifier,
+        src2: Specifier,
+        op: F,
+    ) -> ExecutionResult
+    where
+        F: FnOnce(bool, bool) -> bool,
+    {
+        trace!(
+            "Executing fsgnj*.{} f{} f{} f{}",
+            width_suffix(precision),
+            u8::from(dest),
+            u8::from(src1),
+            u8::from(src2)
+        );
+        self.check_fp_enabled()?;
+        let sign_mask = match precision {
+            FloatPrecision::Single => 1 << 31,
+            FloatPrecision::Double => 1 << 63,
+        };
+        let a = self.read_f(precision, src1);
+        let b = self.read_f(precision, src2);
+        let sign = op(a & sign_mask != 0, b & sign_mask != 0);
+        let result = (a & !sign_mask) | if sign { sign_mask } else { 0 };
+        self.write_f(precision, dest, result, 0);
+        Ok(())
+    }
+
+    /// Executes a `fmin` or `fmax` instruction (selected by `max`).
+    ///
+    /// > Floating-point minimum-number and maximum-number instructions FMIN.S and FMAX.S write,
+    /// > respectively, the smaller or larger of rs1 and rs2 to rd.
+    pub fn fmin_max(
+        &mut self,
+        max: bool,
+        precision: FloatPrecision,
+        dest: Specifier,
+        src1: Specifier,
+        src2: Specifier,
+    ) -> ExecutionResult {
+        trace!(
+            "Executing f{}.{} f{} f{} f{}",
+            if max { "max" } else { "min" },
+            width_suffix(precision),
+            u8::from(dest),
+            u8::from(src1),
+            u8::from(src2)
+        );
+        self.check_fp_enabled()?;
+        let a = self.read_f(precision, src1);
+        let b = self.read_f(precision, src2);
+        let mut flags = 0;
+        let result = match max {
+            true => format(precision).max(a, b, &mut flags),
+            false => format(precision).min(a, b, &mut flags),
+        };
+        self.write_f(precision, dest, result, flags);
+        Ok(())
+    }
+
+    /// Executes a `feq`, `flt`, or `fle` instruction.
+    ///
+    /// > Floating-point compare instructions (FEQ.S, FLT.S, FLE.S) perform the specified
+    /// > comparison between floating-point registers (rs1 = rs2, rs1 < rs2, rs1 ≤ rs2) writing 1
+    /// > to the integer register rd if the condition holds, and 0 otherwise.
+    /// >
+    /// > FLT.S and FLE.S perform what the IEEE 754-2008 standard refers to as signaling
+    /// > comparisons: that is, they set the invalid operation exception flag if either input is
+    /// > NaN. FEQ.S performs a quiet comparison: it only sets the invalid operation exception flag
+    /// > if either input is a signaling NaN.
+    pub fn fcompare<F>(
+        &mut self,
+        precision: FloatPrecision,
+        dest: Specifier,
+        src1: Specifier,
+        src2: Specifier,
+        op: F,
+    ) -> ExecutionResult
+    where
+        F: FnOnce(Format, u64, u64, &mut u8) -> bool,
+    {
+        trace!(
+            "Executing fcmp.{} {dest} f{} f{}",
+            width_suffix(precision),
+            u8::from(src1),
+            u8::from(src2)
+        );
+        self.check_fp_enabled()?;
+        let a = self.read_f(precision, src1);
+        let b = self.read_f(precision, src2);
+        let mut flags = 0;
+        let result = op(format(precision), a, b, &mut flags);
+        self.write_x(dest, result as u32, flags);
+        Ok(())
+    }
+
+    /// Executes a `fclass` instruction.
+    ///
+    /// > The FCLASS.S instruction examines the value in floating-point register rs1 and writes to
+    /// > integer register rd a 10-bit mask that indicates the class of the floating-point number.
+    /// > [...] FCLASS.S does not set the floating-point exception flags.
+    pub fn fclass(
+        &mut self,
+        precision: FloatPrecision,
+        dest: Specifier,
+        src: Specifier,
+    ) -> ExecutionResult {
+        trace!(
+            "Executing fclass.{} {dest} f{}",
+            width_suffix(precision),
+            u8::from(src)
+        );
+        self.check_fp_enabled()?;
+        let a = self.read_f(precision, src);
+        self.write_x(dest, format(precision).classify(a), 0);
+        Ok(())
+    }
+
+    /// Executes a `fcvt.w` or `fcvt.wu` instruction, converting to a signed or unsigned integer.
+    ///
+    /// > FCVT.W.S or FCVT.L.S converts a floating-point number in floating-point register rs1 to
+    /// > a signed 32-bit or 64-bit integer, respectively, in integer register rd. FCVT.WU.S [...]
+    /// > converts to unsigned integer values.
+    pub fn fcvt_to_int(
+        &mut self,
+        signed: bool,
+        precision: FloatPrecision,
+        rounding_mode: Option<RoundingMode>,
+        dest: Specifier,
+        src: Specifier,
+    ) -> ExecutionResult {
+        trace!(
+            "Executing fcvt.w{}.{} {dest} f{}",
+            if signed { "" } else { "u" },
+            width_suffix(precision),
+            u8::from(src)
+        );
+        self.check_fp_enabled()?;
+        let rm = self.resolve_rounding_mode(rounding_mode)?;
+        let a = self.read_f(precision, src);
+        let mut flags = 0;
+        let result = format(precision).to_int(a, signed, rm, &mut flags);
+        self.write_x(dest, result, flags);
+        Ok(())
+    }
+
+    /// Executes a `fcvt.*.w` or `fcvt.*.wu` instruction, converting from a signed or unsigned
+    /// integer.
+    ///
+    /// > FCVT.S.W or FCVT.S.L converts a 32-bit or 64-bit signed integer, respectively, in integer
+    /// > register rs1 into a floating-point number in floating-point register rd. FCVT.S.WU [...]
+    /// > converts from unsigned integer values.
+    pub fn fcvt_from_int(
+        &mut self,
+        signed: bool,
+        precision: FloatPrecision,
+        rounding_mode: Option<RoundingMode>,
+        dest: Specifier,
+        src: Specifier,
+    ) -> ExecutionResult {
+        trace!(
+            "Executing fcvt.{}.w{} f{} {src}",
+            width_suffix(precision),
+            if signed { "" } else { "u" },
+            u8::from(dest)
+        );
+        self.check_fp_enabled()?;
+        let rm = self.resolve_rounding_mode(rounding_mode)?;
+        let value = self.core.registers(self.allocator).x(src);
+        let mut flags = 0;
+        let result = format(precision).from_int(value, signed, rm, &mut flags);
+        self.write_f(precision, dest, result, flags);
+        Ok(())
+    }
+
+    /// Executes a `fcvt.s.d` or `fcvt.d.s` instruction.
+    ///
+    /// > The double-precision to single-precision and single-precision to double-precision
+    /// > conversion instructions, FCVT.S.D and FCVT.D.S, are encoded in the OP-FP major opcode
+    /// > space and both the source and destination are floating-point registers. [...] FCVT.S.D
+    /// > rounds according to the RM field; FCVT.D.S will never round.
+    pub fn fcvt_float(
+        &mut self,
+        from: FloatPrecision,
+        to: FloatPrecision,
+        rounding_mode: Option<RoundingMode>,
+        dest: Specifier,
+        src: Specifier,
+    ) -> ExecutionResult {
+        trace!(
+            "Executing fcvt.{}.{} f{} f{}",
+            width_suffix(to),
+            width_suffix(from),
+            u8::from(dest),
+            u8::from(src)
+        );
+        self.check_fp_enabled()?;
+        let rm = self.resolve_rounding_mode(rounding_mode)?;
+        let a = self.read_f(from, src);
+        let mut flags = 0;
+        let result = format(from).convert(format(to), a, rm, &mut flags);
+        self.write_f(to, dest, result, flags);
+        Ok(())
+    }
+
+    /// Executes a `fmv.x.w` instruction.
+    ///
+    /// > FMV.X.W moves the single-precision value in floating-point register rs1 represented in
+    /// > IEEE 754-2008 encoding to the lower 32 bits of integer register rd. The bits are not
+    /// > modified in the transfer, and in particular, the payloads of non-canonical NaNs are
+    /// > preserved.
+    pub fn fmv_x_w(&mut self, dest: Specifier, src: Specifier) -> ExecutionResult {
+        trace!("Executing fmv.x.w {dest} f{}", u8::from(src));
+        self.check_fp_enabled()?;
+        let value = self.core.float_registers(self.allocator).f(src) as u32;
+        self.write_x(dest, value, 0);
+        Ok(())
+    }
+
+    /// Executes a `fmv.w.x` instruction.
+    ///
+    /// > FMV.W.X moves the single-precision value encoded in IEEE 754-2008 standard encoding from
+    /// > the lower 32 bits of integer register rs1 to the floating-point register rd. The bits are
+    /// > not modified in the transfer, and in particular, the payloads of non-canonical NaNs are
+    /// > preserved.
+    pub fn fmv_w_x(&mut self, dest: Specifier, src: Specifier) -> ExecutionResult {
+        trace!("Executing fmv.w.x f{} {src}", u8::from(dest));
+        self.check_fp_enabled()?;
+        let value = self.core.registers(self.allocator).x(src);
+        self.write_f(FloatPrecision::Single, dest, value as u64, 0);
         Ok(())
     }
 
@@ -887,7 +1872,7 @@ impl<A: Allocator, B: SystemBus<A>> Executor<'_, '_, A, B> {
     {
         let registers = self.core.registers_mut(self.allocator);
         registers.set_x(dest, op(registers.x(src), immediate));
-        increment_pc(registers);
+        increment_pc(registers, self.instruction_length);
         Ok(())
     }
 
@@ -906,7 +1891,17 @@ impl<A: Allocator, B: SystemBus<A>> Executor<'_, '_, A, B> {
         }
         let registers = self.core.registers_mut(self.allocator);
         registers.set_x(dest, op(registers.x(src), shift_amount_u5));
-        increment_pc(registers);
+        increment_pc(registers, self.instruction_length);
+        Ok(())
+    }
+
+    fn reg_unary_op<F>(&mut self, dest: Specifier, src: Specifier, op: F) -> ExecutionResult
+    where
+        F: FnOnce(u32) -> u32,
+    {
+        let registers = self.core.registers_mut(self.allocator);
+        registers.set_x(dest, op(registers.x(src)));
+        increment_pc(registers, self.instruction_length);
         Ok(())
     }
 
@@ -922,7 +1917,7 @@ impl<A: Allocator, B: SystemBus<A>> Executor<'_, '_, A, B> {
     {
         let registers = self.core.registers_mut(self.allocator);
         registers.set_x(dest, op(registers.x(src1), registers.x(src2)));
-        increment_pc(registers);
+        increment_pc(registers, self.instruction_length);
         Ok(())
     }
 
@@ -941,35 +1936,60 @@ impl<A: Allocator, B: SystemBus<A>> Executor<'_, '_, A, B> {
         let address = registers.x(addr);
 
         if !Alignment::WORD.is_aligned(address) {
-            return Err(Exception::LoadAddressMisaligned(address));
+            return Err(Exception::StoreOrAmoAddressMisaligned(address));
         }
 
         let src_value = registers.x(src);
 
+        // AMOs are both loads and stores, so either kind of trigger may fire. Address triggers are
+        // checked once for each kind, and data triggers against the loaded and the stored value.
+        self.core
+            .check_triggers(self.allocator, TriggerAccess::Load, address, None)?;
+        self.core
+            .check_triggers(self.allocator, TriggerAccess::Store, address, None)?;
+
         let mem_value =
             self.core
                 .mmu()
                 .read_word(self.allocator, address)
                 .map_err(|err| match err {
-                    MemoryError::MisalignedAccess => Exception::LoadAddressMisaligned(address),
-                    MemoryError::AccessFault => Exception::LoadAccessFault(address),
+                    MemoryError::MisalignedAccess => {
+                        Exception::StoreOrAmoAddressMisaligned(address)
+                    }
+                    MemoryError::AccessFault | MemoryError::BusFault(_) => {
+                        Exception::StoreOrAmoAccessFault(address)
+                    }
                     MemoryError::PageFault => Exception::StoreOrAmoPageFault(address),
                 })?;
 
+        self.core.check_triggers(
+            self.allocator,
+            TriggerAccess::Load,
+            address,
+            Some(mem_value),
+        )?;
         let new_value = op(mem_value, src_value);
+        self.core.check_triggers(
+            self.allocator,
+            TriggerAccess::Store,
+            address,
+            Some(new_value),
+        )?;
 
         self.core
             .mmu()
             .write_word(self.allocator, address, new_value)
             .map_err(|err| match err {
-                MemoryError::MisalignedAccess => Exception::LoadAddressMisaligned(address),
-                MemoryError::AccessFault => Exception::LoadAccessFault(address),
+                MemoryError::MisalignedAccess => Exception::StoreOrAmoAddressMisaligned(address),
+                MemoryError::AccessFault | MemoryError::BusFault(_) => {
+                    Exception::StoreOrAmoAccessFault(address)
+                }
                 MemoryError::PageFault => Exception::StoreOrAmoPageFault(address),
             })?;
 
         let registers = self.core.registers_mut(self.allocator);
         registers.set_x(dest, mem_value);
-        increment_pc(registers);
+        increment_pc(registers, self.instruction_length);
         Ok(())
     }
 
@@ -980,14 +2000,14 @@ impl<A: Allocator, B: SystemBus<A>> Executor<'_, '_, A, B> {
         let registers = self.core.registers_mut(self.allocator);
         // Compute target pc
         let new_pc = compute_target(registers);
-        // Check target pc is word-aligned
-        if !Alignment::WORD.is_aligned(new_pc) {
+        // Check target pc is aligned to IALIGN
+        if !self.core.instruction_alignment().is_aligned(new_pc) {
             return Err(Exception::InstructionAddressMisaligned(new_pc));
         }
         // Update pc to target
         let old_pc = std::mem::replace(registers.pc_mut(), new_pc);
         // Write incremented old pc to `dest` register
-        registers.set_x(dest, old_pc.wrapping_add(4));
+        registers.set_x(dest, old_pc.wrapping_add(self.instruction_length));
         Ok(())
     }
 
@@ -1005,13 +2025,13 @@ impl<A: Allocator, B: SystemBus<A>> Executor<'_, '_, A, B> {
         let registers = self.core.registers_mut(self.allocator);
         if predicate(registers.x(src1), registers.x(src2)) {
             let new_pc = registers.pc().wrapping_add_signed(offset);
-            // Check target pc is word-aligned
-            if !Alignment::WORD.is_aligned(new_pc) {
+            // Check target pc is aligned to IALIGN
+            if !self.core.instruction_alignment().is_aligned(new_pc) {
                 return Err(Exception::InstructionAddressMisaligned(new_pc));
             }
             *registers.pc_mut() = new_pc;
         } else {
-            increment_pc(registers);
+            increment_pc(registers, self.instruction_length);
         }
         Ok(())
     }
@@ -1028,14 +2048,21 @@ impl<A: Allocator, B: SystemBus<A>> Executor<'_, '_, A, B> {
     {
         let registers = self.core.registers(self.allocator);
         let address = registers.x(base).wrapping_add_signed(offset);
+        self.core
+            .check_triggers(self.allocator, TriggerAccess::Load, address, None)?;
         let value = op(self, address).map_err(|err| match err {
             MemoryError::MisalignedAccess => Exception::LoadAddressMisaligned(address),
-            MemoryError::AccessFault => Exception::LoadAccessFault(address),
+            MemoryError::AccessFault | MemoryError::BusFault(_) => {
+                Exception::LoadAccessFault(address)
+            }
             MemoryError::PageFault => Exception::LoadPageFault(address),
         })?;
+        // Load data triggers fire before the destination register is written.
+        self.core
+            .check_triggers(self.allocator, TriggerAccess::Load, address, Some(value))?;
         let registers = self.core.registers_mut(self.allocator);
         registers.set_x(dest, value);
-        increment_pc(registers);
+        increment_pc(registers, self.instruction_length);
         Ok(())
     }
 
@@ -1052,12 +2079,108 @@ impl<A: Allocator, B: SystemBus<A>> Executor<'_, '_, A, B> {
         let registers = self.core.registers(self.allocator);
         let value = registers.x(src);
         let address = registers.x(base).wrapping_add_signed(offset);
+        self.core
+            .check_triggers(self.allocator, TriggerAccess::Store, address, None)?;
+        self.core
+            .check_triggers(self.allocator, TriggerAccess::Store, address, Some(value))?;
         op(self, address, value).map_err(|err| match err {
             MemoryError::MisalignedAccess => Exception::StoreOrAmoAddressMisaligned(address),
-            MemoryError::AccessFault => Exception::StoreOrAmoAccessFault(address),
+            MemoryError::AccessFault | MemoryError::BusFault(_) => {
+                Exception::StoreOrAmoAccessFault(address)
+            }
             MemoryError::PageFault => Exception::StoreOrAmoPageFault(address),
         })?;
-        increment_pc(self.core.registers_mut(self.allocator));
+        increment_pc(
+            self.core.registers_mut(self.allocator),
+            self.instruction_length,
+        );
+        Ok(())
+    }
+
+    /// Returns an illegal instruction exception if the floating-point unit is turned off.
+    ///
+    /// > When an extension's status is set to Off, any instruction that attempts to read or write
+    /// > the corresponding state will cause an illegal instruction exception.
+    fn check_fp_enabled(&self) -> ExecutionResult {
+        match self.core.status.get(self.allocator).fs() {
+            ExtensionContextStatus::Off => Err(Exception::IllegalInstruction(None)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Resolves a rounding mode field, where `None` selects the dynamic rounding mode in `frm`.
+    ///
+    /// > If frm is set to an invalid value (101–111), any subsequent attempt to execute a
+    /// > floating-point operation with a dynamic rounding mode will raise an illegal instruction
+    /// > exception.
+    fn resolve_rounding_mode(
+        &self,
+        rounding_mode: Option<RoundingMode>,
+    ) -> Result<RoundingMode, Exception> {
+        match rounding_mode {
+            Some(rounding_mode) => Ok(rounding_mode),
+            None => RoundingMode::from_u3(self.core.float_registers(self.allocator).frm())
+                .ok_or(Exception::IllegalInstruction(None)),
+        }
+    }
+
+    /// Reads an operand of the given precision from an `f` register, unboxing single-precision
+    /// values.
+    fn read_f(&self, precision: FloatPrecision, src: Specifier) -> u64 {
+        let float_registers = self.core.float_registers(self.allocator);
+        match precision {
+            FloatPrecision::Single => float_registers.f32(src) as u64,
+            FloatPrecision::Double => float_registers.f(src),
+        }
+    }
+
+    /// Writes a result of the given precision to an `f` register, accrues `flags`, and increments
+    /// the pc.
+    fn write_f(&mut self, precision: FloatPrecision, dest: Specifier, value: u64, flags: u8) {
+        let float_registers = self.core.float_registers_mut(self.allocator);
+        match precision {
+            FloatPrecision::Single => float_registers.set_f32(dest, value as u32),
+            FloatPrecision::Double => float_registers.set_f(dest, value),
+        }
+        float_registers.accrue_fflags(flags);
+        increment_pc(
+            self.core.registers_mut(self.allocator),
+            self.instruction_length,
+        );
+    }
+
+    /// Writes the result of a floating-point instruction to an `x` register, accrues `flags`, and
+    /// increments the pc. The floating-point state is only marked as dirty if any flags are raised.
+    fn write_x(&mut self, dest: Specifier, value: u32, flags: u8) {
+        if flags != 0 {
+            self.core
+                .float_registers_mut(self.allocator)
+                .accrue_fflags(flags);
+        }
+        let registers = self.core.registers_mut(self.allocator);
+        registers.set_x(dest, value);
+        increment_pc(registers, self.instruction_length);
+    }
+
+    fn fp_arith_op<F>(
+        &mut self,
+        precision: FloatPrecision,
+        rounding_mode: Option<RoundingMode>,
+        dest: Specifier,
+        src1: Specifier,
+        src2: Specifier,
+        op: F,
+    ) -> ExecutionResult
+    where
+        F: FnOnce(Format, u64, u64, RoundingMode, &mut u8) -> u64,
+    {
+        self.check_fp_enabled()?;
+        let rm = self.resolve_rounding_mode(rounding_mode)?;
+        let a = self.read_f(precision, src1);
+        let b = self.read_f(precision, src2);
+        let mut flags = 0;
+        let result = op(format(precision), a, b, rm, &mut flags);
+        self.write_f(precision, dest, result, flags);
         Ok(())
     }
 
@@ -1121,12 +2244,37 @@ impl<A: Allocator, B: SystemBus<A>> Executor<'_, '_, A, B> {
                 .write_csr(self.allocator, csr, privilege_level, value, mask)
                 .map_err(|_| Exception::IllegalInstruction(None))?;
         }
-        increment_pc(self.core.registers_mut(self.allocator));
+        increment_pc(
+            self.core.registers_mut(self.allocator),
+            self.instruction_length,
+        );
         Ok(())
     }
 }
 
-fn increment_pc(registers: &mut Registers) {
+fn format(precision: FloatPrecision) -> Format {
+    match precision {
+        FloatPrecision::Single => Format::SINGLE,
+        FloatPrecision::Double => Format::DOUBLE,
+    }
+}
+
+/// Returns the suffix used in the mnemonics of instructions of the given precision.
+fn width_suffix(precision: FloatPrecision) -> &'static str {
+    match precision {
+        FloatPrecision::Single => "s",
+        FloatPrecision::Double => "d",
+    }
+}
+
+/// Returns the full 64-bit carry-less product of `a` and `b`.
+fn carryless_multiply(a: u32, b: u32) -> u64 {
+    (0..32)
+        .filter(|i| (b >> i) & 1 == 1)
+        .fold(0, |product, i| product ^ ((a as u64) << i))
+}
+
+fn increment_pc(registers: &mut Registers, instruction_length: u32) {
     let pc = registers.pc_mut();
-    *pc = pc.wrapping_add(4);
+    *pc = pc.wrapping_add(instruction_length);
 }
@@ -0,0 +1,85 @@
+use space_time::allocator::Allocator;
+
+use crate::system_bus::SystemBus;
+
+use super::csr::{self, CsrSpecifier};
+use super::status::ExtensionContextStatus;
+use super::{Core, CsrAccessError, CsrReadResult, CsrWriteResult};
+
+/// Provides the fflags, frm, and fcsr registers, which are stored in
+/// [`FloatRegisters`](crate::registers::FloatRegisters).
+///
+/// > The fcsr register is a 32-bit read/write register that selects the dynamic rounding mode for
+/// > floating-point arithmetic operations and holds the accrued exception flags.
+/// >
+/// > The fcsr register can be read and written with the FRCSR and FSCSR instructions, which are
+/// > assembler pseudoinstructions built on the underlying CSR access instructions. [...] The
+/// > fields within the fcsr can also be accessed individually through different CSR addresses,
+/// > and separate assembler pseudoinstructions are defined for these accesses.
+///
+/// All three are unavailable while mstatus.FS is Off:
+///
+/// > When an extension's status is set to Off, any instruction that attempts to read or write the
+/// > corresponding state will cause an illegal instruction exception.
+///
+/// Writing any of them sets FS to Dirty, through [`Core::float_registers_mut`].
+impl<A: Allocator, B: SystemBus<A>> Core<A, B> {
+    pub fn read_fflags(&self, allocator: &mut A) -> CsrReadResult {
+        self.check_fcsr_access(allocator, csr::FFLAGS)?;
+        Ok(self.float_registers(allocator).fflags() as u32)
+    }
+
+    pub fn write_fflags(&self, allocator: &mut A, value: u32, mask: u32) -> CsrWriteResult {
+        self.check_fcsr_access(allocator, csr::FFLAGS)?;
+        let float_registers = self.float_registers_mut(allocator);
+        let fflags = float_registers.fflags() as u32 & !mask | value & mask;
+        float_registers.set_fflags(fflags as u8);
+        Ok(())
+    }
+
+    pub fn read_frm(&self, allocator: &mut A) -> CsrReadResult {
+        self.check_fcsr_access(allocator, csr::FRM)?;
+        Ok(self.float_registers(allocator).frm() as u32)
+    }
+
+    /// The frm field is WARL in the sense that invalid rounding modes can be written, but using
+    /// them for dynamic rounding raises an illegal instruction exception.
+    pub fn write_frm(&self, allocator: &mut A, value: u32, mask: u32) -> CsrWriteResult {
+        self.check_fcsr_access(allocator, csr::FRM)?;
+        let float_registers = self.float_registers_mut(allocator);
+        let frm = float_registers.frm() as u32 & !mask | value & mask;
+        float_registers.set_frm(frm as u8);
+        Ok(())
+    }
+
+    pub fn read_fcsr(&self, allocator: &mut A) -> CsrReadResult {
+        self.check_fcsr_access(allocator, csr::FCSR)?;
+        let float_registers = self.float_registers(allocator);
+        Ok((float_registers.frm() as u32) << 5 | float_registers.fflags() as u32)
+    }
+
+    /// Bits 31–8 of fcsr are reserved and read as zero.
+    pub fn write_fcsr(&self, allocator: &mut A, value: u32, mask: u32) -> CsrWriteResult {
+        self.check_fcsr_access(allocator, csr::FCSR)?;
+        let float_registers = self.float_registers_mut(allocator);
+        let fcsr = ((float_registers.frm() as u32) << 5 | float_registers.fflags() as u32) & !mask
+            | value & mask;
+        float_registers.set_fflags(fcsr as u8);
+        float_registers.set_frm((fcsr >> 5) as u8);
+        Ok(())
+    }
+
+    fn check_fcsr_access(
+        &self,
+        allocator: &mut A,
+        specifier: CsrSpecifier,
+    ) -> Result<(), CsrAccessError> {
+        if self.status.get(allocator).fs() == ExtensionContextStatus::Off {
+            return Err(CsrAccessError::CsrUnavailable(
+                specifier,
+                "floating-point CSRs cannot be accessed when FS=Off".to_owned(),
+            ));
+        }
+        Ok(())
+    }
+}
@@ -108,14 +108,12 @@ impl Interrupts {
 
     // set_s_timer is missing, since STIP is only controllable by M-mode guest code.
 
-    /// Indicate that an M-level software interrupt is pending (MSIP).
-    ///
-    /// Note that it is not possible to clear this bit. That is only possible from guest code.
+    /// Indicate whether there is an M-level software interrupt pending (MSIP).
     ///
     /// Controlled by accesses to memory-mapped control registers.
-    pub fn set_m_soft(&mut self) {
-        trace!("Setting mip.MSIP to true");
-        self.mip.set_bit(MACHINE_SOFTWARE_INTERRUPT, true);
+    pub fn set_m_soft(&mut self, value: bool) {
+        trace!("Setting mip.MSIP to {value}");
+        self.mip.set_bit(MACHINE_SOFTWARE_INTERRUPT, value);
     }
 
     /// Indicate that an S-level software interrupt is pending (SSIP).
@@ -128,6 +126,12 @@ impl Interrupts {
         self.mip.set_bit(SUPERVISOR_SOFTWARE_INTERRUPT, true);
     }
 
+    /// Returns `true` if any interrupt is both pending in mip and enabled in mie, regardless of
+    /// the global interrupt enable bits and delegation.
+    pub fn any_locally_enabled(&self) -> bool {
+        self.mip & self.mie != 0
+    }
+
     /// Returns `Some(interrupt)` if there is an interrupt ready to be taken in M-mode. If there are
     /// multiple interrupts ready, `interrupt` will be the one with the highest priority.
     ///
@@ -288,11 +292,12 @@ impl<A: Allocator, B: SystemBus<A>> Core<A, B> {
         Ok(())
     }
 
-    fn raise(&self, code: Interrupt, allocator: &mut A) {
+    /// Raise the interrupt line for `code`, as done by an interrupt controller.
+    pub fn raise_interrupt(&self, allocator: &mut A, code: Interrupt) {
         let interrupts = self.interrupts.get_mut(allocator);
         match code {
             Interrupt::SupervisorSoftwareInterrupt => interrupts.set_s_soft(),
-            Interrupt::MachineSoftwareInterrupt => interrupts.set_m_soft(),
+            Interrupt::MachineSoftwareInterrupt => interrupts.set_m_soft(true),
             Interrupt::SupervisorTimerInterrupt => {}
             Interrupt::MachineTimerInterrupt => interrupts.set_m_timer(true),
             Interrupt::SupervisorExternalInterrupt => interrupts.set_s_external(true),
@@ -300,11 +305,14 @@ impl<A: Allocator, B: SystemBus<A>> Core<A, B> {
         }
     }
 
-    fn lower(&self, code: Interrupt, allocator: &mut A) {
+    /// Lower the interrupt line for `code`, as done by an interrupt controller.
+    ///
+    /// Lowering SSIP does nothing, since it can only be cleared by guest code.
+    pub fn lower_interrupt(&self, allocator: &mut A, code: Interrupt) {
         let interrupts = self.interrupts.get_mut(allocator);
         match code {
             Interrupt::SupervisorSoftwareInterrupt => {}
-            Interrupt::MachineSoftwareInterrupt => {}
+            Interrupt::MachineSoftwareInterrupt => interrupts.set_m_soft(false),
             Interrupt::SupervisorTimerInterrupt => {}
             Interrupt::MachineTimerInterrupt => interrupts.set_m_timer(false),
             Interrupt::SupervisorExternalInterrupt => interrupts.set_s_external(false),
@@ -327,13 +335,13 @@ impl<A: Allocator, B: SystemBus<A> + 'static> Core<A, B> {
 impl<A: Allocator, B: SystemBus<A>> IrqCallback<A> for CoreIrqCallback<A, B> {
     fn raise(&self, allocator: &mut A) {
         if let Some(core) = self.core.upgrade() {
-            core.raise(self.code, allocator)
+            core.raise_interrupt(allocator, self.code)
         }
     }
 
     fn lower(&self, allocator: &mut A) {
         if let Some(core) = self.core.upgrade() {
-            core.lower(self.code, allocator)
+            core.lower_interrupt(allocator, self.code)
         }
     }
 }
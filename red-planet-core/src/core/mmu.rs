@@ -2,6 +2,8 @@ use std::borrow::Borrow;
 
 use super::trap::SatpMode;
 use super::Core;
+use crate::bus::BusError;
+use crate::instruction::is_compressed;
 use crate::system_bus::{AccessType, SystemBus};
 use crate::{Alignment, Allocator, Endianness, PrivilegeLevel};
 use bitvec::field::BitField;
@@ -19,6 +21,7 @@ macro_rules! access_fns {
                 let privilege_level = self.core.effective_privilege_mode(allocator);
                 let mut buf = [0u8; std::mem::size_of::<$u>()];
                 self.read(&mut buf, allocator, address, privilege_level, false)?;
+                self.core.check_watchpoints(address, buf.len(), false);
                 Ok(match self.core.endianness(allocator, privilege_level) {
                     Endianness::LE => $u::from_le_bytes(buf),
                     Endianness::BE => $u::from_be_bytes(buf),
@@ -53,7 +56,9 @@ macro_rules! access_fns {
                     Endianness::LE => value.to_le_bytes(),
                     Endianness::BE => value.to_be_bytes(),
                 };
-                self.write(allocator, address, &buf, privilege_level)
+                self.write(allocator, address, &buf, privilege_level)?;
+                self.core.check_watchpoints(address, buf.len(), true);
+                Ok(())
             }
         )*
     };
@@ -64,6 +69,11 @@ const PAGE_TABLE_LEVELS: u32 = 2;
 const PAGE_SIZE_SHF: u32 = 12;
 // log2(Size of a single PTE (in bytes))
 const PTE_SIZE_SHF: u32 = 2;
+/// Privilege level at which the implicit page-table accesses are checked against the PMP.
+///
+/// > PMP checks are also applied to page-table accesses for virtual-address translation, for
+/// > which the effective privilege mode is S.
+const PTW_PRIVILEGE_LEVEL: PrivilegeLevel = PrivilegeLevel::Supervisor;
 
 /// Access wrapper around a raw bus to address it as memory from this core's point of view.
 ///
@@ -83,8 +93,9 @@ impl<A: Allocator, B: SystemBus<A>> Mmu<'_, A, B> {
         trace!("Reading byte from memory at vaddr {address:#010x}");
         let privilege_level = self.core.effective_privilege_mode(allocator);
         let mut buf = [0];
-        self.read(&mut buf, allocator, address, privilege_level, false)
-            .map(|()| buf[0])
+        self.read(&mut buf, allocator, address, privilege_level, false)?;
+        self.core.check_watchpoints(address, 1, false);
+        Ok(buf[0])
     }
 
     pub fn read_byte_debug(&self, allocator: &A, address: u32) -> Result<u8, MemoryError> {
@@ -103,7 +114,9 @@ impl<A: Allocator, B: SystemBus<A>> Mmu<'_, A, B> {
     ) -> Result<(), MemoryError> {
         trace!(value; "Writing byte to memory at vaddr {address:#010x}");
         let privilege_level = self.core.effective_privilege_mode(allocator);
-        self.write(allocator, address, &[value], privilege_level)
+        self.write(allocator, address, &[value], privilege_level)?;
+        self.core.check_watchpoints(address, 1, true);
+        Ok(())
     }
 
     access_fns! {
@@ -113,31 +126,111 @@ impl<A: Allocator, B: SystemBus<A>> Mmu<'_, A, B> {
         read_quadword, read_quadword_debug, write_quadword => u128,
     }
 
-    /// Reads a naturally-aligned 32-bit little-endian word from memory.
+    /// Invoke a load-reserved read of a word at the specified address, registering a reservation
+    /// set on the physical address it maps to.
+    pub fn load_reserved_word(&self, allocator: &mut A, address: u32) -> Result<u32, MemoryError> {
+        trace!("Load-reserved reading word from memory at vaddr {address:#010x}");
+        let privilege_level = self.core.effective_privilege_mode(allocator);
+        let mut buf = [0u8; 4];
+        let physical_address = self.access_virtual(
+            allocator,
+            address,
+            buf.len(),
+            AccessType::Read,
+            privilege_level,
+        )?;
+        self.read_physical(&mut buf, allocator, physical_address)?;
+        self.core
+            .reservation
+            .get_mut(allocator)
+            .reserve(physical_address);
+        self.core.check_watchpoints(address, buf.len(), false);
+        Ok(match self.core.endianness(allocator, privilege_level) {
+            Endianness::LE => u32::from_le_bytes(buf),
+            Endianness::BE => u32::from_be_bytes(buf),
+        })
+    }
+
+    /// Invoke a store-conditional write of a word to the specified address.
     ///
-    /// > The base RISC-V ISA has fixed-length 32-bit instructions that must be naturally aligned on
-    /// > 32-bit boundaries.
+    /// Returns `Ok(true)` if the reservation was valid and the word was written, `Ok(false)` if
+    /// nothing was written. The reservation is invalidated either way. Address translation and
+    /// access checks are performed regardless of the reservation, so faults are always reported.
+    pub fn store_conditional_word(
+        &self,
+        allocator: &mut A,
+        address: u32,
+        value: u32,
+    ) -> Result<bool, MemoryError> {
+        trace!(value; "Store-conditional writing word to memory at vaddr {address:#010x}");
+        let privilege_level = self.core.effective_privilege_mode(allocator);
+        let buf = match self.core.endianness(allocator, privilege_level) {
+            Endianness::LE => value.to_le_bytes(),
+            Endianness::BE => value.to_be_bytes(),
+        };
+        let physical_address = self.access_virtual(
+            allocator,
+            address,
+            buf.len(),
+            AccessType::Write,
+            privilege_level,
+        )?;
+        let success = self
+            .core
+            .reservation
+            .get_mut(allocator)
+            .store_conditional(physical_address, self.core.config.sc_failure_interval);
+        if success {
+            self.write_physical(allocator, physical_address, &buf)?;
+            self.core.check_watchpoints(address, buf.len(), true);
+        }
+        Ok(success)
+    }
+
+    /// Fetches the instruction at `address`, which must be aligned to IALIGN.
+    ///
+    /// Returns a 32-bit instruction, or a 16-bit compressed instruction zero-extended to 32 bits
+    /// (see [`is_compressed`]).
     ///
     /// > Instructions are stored in memory as a sequence of 16-bit little-endian parcels,
     /// > regardless of memory system endianness. Parcels forming one instruction are stored at
     /// > increasing halfword addresses, with the lowest-addressed parcel holding the
     /// > lowest-numbered bits in the instruction specification.
-    pub fn fetch_instruction(&self, allocator: &mut A, address: u32) -> Result<u32, MemoryError> {
+    ///
+    /// Each parcel is fetched separately, so a 32-bit instruction straddling a page or region
+    /// boundary is translated and checked correctly. If fetching a parcel fails, the returned
+    /// [`FetchError`] holds the address of that parcel, rather than the address of the instruction:
+    ///
+    /// > If mtval is written with a nonzero value when an instruction access-fault or page-fault
+    /// > exception occurs on a system with variable-length instructions, then mtval will contain
+    /// > the virtual address of the portion of the instruction that caused the fault, while mepc
+    /// > will point to the beginning of the instruction.
+    pub fn fetch_instruction(&self, allocator: &mut A, address: u32) -> Result<u32, FetchError> {
         trace!("Fetching instruction from memory at vaddr {address:#010x}");
-        let alignment = match self.core.config.strict_instruction_alignment {
-            true => Alignment::WORD,
-            false => Alignment::HALFWORD,
-        };
-        if !alignment.is_aligned(address) {
+        if !self.core.instruction_alignment().is_aligned(address) {
             debug!("Failed to fetch instruction: address misaligned: {address:#010x}");
-            return Err(MemoryError::MisalignedAccess);
+            return Err(FetchError {
+                address,
+                error: MemoryError::MisalignedAccess,
+            });
         }
+        let low = self.fetch_parcel(allocator, address)?;
+        if is_compressed(low as u32) {
+            return Ok(low as u32);
+        }
+        let high = self.fetch_parcel(allocator, address.wrapping_add(2))?;
+        Ok((high as u32) << 16 | low as u32)
+    }
+
+    /// Fetches a single 16-bit instruction parcel.
+    fn fetch_parcel(&self, allocator: &mut A, address: u32) -> Result<u16, FetchError> {
         // Use the core's current privilege level, not its *effective* privilege level, since that
         // shouldn't be used for instruction fetches.
         let privilege_level = self.core.privilege_mode(allocator);
-        let mut buf = [0u8; 4];
+        let mut buf = [0u8; 2];
         self.read(&mut buf, allocator, address, privilege_level, true)
-            .map(|()| u32::from_le_bytes(buf))
+            .map(|()| u16::from_le_bytes(buf))
+            .map_err(|error| FetchError { address, error })
     }
 
     pub fn read_range(
@@ -184,8 +277,7 @@ impl<A: Allocator, B: SystemBus<A>> Mmu<'_, A, B> {
         };
         let physical_address =
             self.access_virtual(allocator, address, buf.len(), access_type, privilege_level)?;
-        self.core.system_bus.read(buf, allocator, physical_address);
-        Ok(())
+        self.read_physical(buf, allocator, physical_address)
     }
 
     fn read_debug(
@@ -204,8 +296,8 @@ impl<A: Allocator, B: SystemBus<A>> Mmu<'_, A, B> {
             self.access_virtual_debug(allocator, address, buf.len(), access_type, privilege_level)?;
         self.core
             .system_bus
-            .read_debug(buf, allocator, physical_address);
-        Ok(())
+            .read_debug(buf, allocator, physical_address)
+            .map_err(|error| bus_fault(physical_address, error))
     }
 
     fn write(
@@ -222,8 +314,33 @@ impl<A: Allocator, B: SystemBus<A>> Mmu<'_, A, B> {
             AccessType::Write,
             privilege_level,
         )?;
-        self.core.system_bus.write(allocator, physical_address, buf);
-        Ok(())
+        self.write_physical(allocator, physical_address, buf)
+    }
+
+    /// Reads from the system bus at `physical_address`, which passed all access checks.
+    fn read_physical(
+        &self,
+        buf: &mut [u8],
+        allocator: &mut A,
+        physical_address: u32,
+    ) -> Result<(), MemoryError> {
+        self.core
+            .system_bus
+            .read(buf, allocator, physical_address)
+            .map_err(|error| bus_fault(physical_address, error))
+    }
+
+    /// Writes to the system bus at `physical_address`, which passed all access checks.
+    fn write_physical(
+        &self,
+        allocator: &mut A,
+        physical_address: u32,
+        buf: &[u8],
+    ) -> Result<(), MemoryError> {
+        self.core
+            .system_bus
+            .write(allocator, physical_address, buf)
+            .map_err(|error| bus_fault(physical_address, error))
     }
 
     /// Performs the necessary checks for access virtual `address` of `size` bytes.
@@ -239,7 +356,13 @@ impl<A: Allocator, B: SystemBus<A>> Mmu<'_, A, B> {
         self.access_virtual_pre_translate_checks(address, size, access_type)?;
         let physical_address =
             self.translate_address(allocator, address, access_type, privilege_level)?;
-        self.access_physical(physical_address, size, access_type)?;
+        self.access_physical(
+            allocator,
+            physical_address,
+            size,
+            access_type,
+            privilege_level,
+        )?;
         Ok(physical_address)
     }
 
@@ -256,7 +379,13 @@ impl<A: Allocator, B: SystemBus<A>> Mmu<'_, A, B> {
         self.access_virtual_pre_translate_checks(address, size, access_type)?;
         let physical_address =
             self.translate_address_debug(allocator, address, access_type, privilege_level)?;
-        self.access_physical(physical_address, size, access_type)?;
+        self.access_physical(
+            allocator,
+            physical_address,
+            size,
+            access_type,
+            privilege_level,
+        )?;
         Ok(physical_address)
     }
 
@@ -288,20 +417,30 @@ impl<A: Allocator, B: SystemBus<A>> Mmu<'_, A, B> {
     // Perform PMA & PMP checks for physical (`address`, `size`) accesses of type `access_type`.
     fn access_physical(
         &self,
+        allocator: &A,
         address: u32,
         size: usize,
         access_type: AccessType,
+        privilege_level: PrivilegeLevel,
     ) -> Result<(), MemoryError> {
-        // TODO: PMP checks
-        if self.core.system_bus.accepts(address, size, access_type) {
-            Ok(())
-        } else {
+        if !self.core.system_bus.accepts(address, size, access_type) {
             debug!(
                 address, size, access_type:%;
                 "Memory access not accepted by system bus"
             );
-            Err(MemoryError::AccessFault)
+            return Err(MemoryError::AccessFault);
         }
+        if !self
+            .core
+            .pmp_check(allocator, address, size, access_type, privilege_level)
+        {
+            debug!(
+                address, size, access_type:%, privilege_level:%;
+                "Memory access denied by PMP"
+            );
+            return Err(MemoryError::AccessFault);
+        }
+        Ok(())
     }
 
     /// Map a virtual byte address to the corresponding physical byte address.
@@ -441,28 +580,34 @@ impl<A: Allocator, B: SystemBus<A>> Mmu<'_, A, B> {
 
     fn read_pte(&self, allocator: &mut A, address: u32) -> Result<u32, MemoryError> {
         assert_eq!(1 << PTE_SIZE_SHF, 4);
-        self.access_physical(address, 4, AccessType::Read)?;
+        self.access_physical(allocator, address, 4, AccessType::Read, PTW_PRIVILEGE_LEVEL)?;
         let mut buf = [0u8; 4];
-        self.core.system_bus.read(&mut buf, allocator, address);
+        self.read_physical(&mut buf, allocator, address)?;
         Ok(u32::from_le_bytes(buf))
     }
 
     fn read_pte_debug(&self, allocator: &A, address: u32) -> Result<u32, MemoryError> {
         assert_eq!(1 << PTE_SIZE_SHF, 4);
-        self.access_physical(address, 4, AccessType::Read)?;
+        self.access_physical(allocator, address, 4, AccessType::Read, PTW_PRIVILEGE_LEVEL)?;
         let mut buf = [0u8; 4];
         self.core
             .system_bus
-            .read_debug(&mut buf, allocator, address);
+            .read_debug(&mut buf, allocator, address)
+            .map_err(|error| bus_fault(address, error))?;
         Ok(u32::from_le_bytes(buf))
     }
 
     fn write_pte(&self, allocator: &mut A, address: u32, value: u32) -> Result<(), MemoryError> {
         assert_eq!(1 << PTE_SIZE_SHF, 4);
-        self.access_physical(address, 4, AccessType::Write)?;
+        self.access_physical(
+            allocator,
+            address,
+            4,
+            AccessType::Write,
+            PTW_PRIVILEGE_LEVEL,
+        )?;
         let buf = value.to_le_bytes();
-        self.core.system_bus.write(allocator, address, &buf);
-        Ok(())
+        self.write_physical(allocator, address, &buf)
     }
 }
 
@@ -482,12 +627,31 @@ mod pte {
     }
 }
 
+/// Error returned by [`Mmu::fetch_instruction`].
+#[derive(Error, Debug, Clone, Eq, PartialEq)]
+#[error("{error} while fetching instruction parcel at {address:#010x}")]
+pub struct FetchError {
+    /// Virtual address of the instruction parcel that could not be fetched.
+    pub address: u32,
+    pub error: MemoryError,
+}
+
 #[derive(Error, Debug, Clone, Eq, PartialEq)]
 pub enum MemoryError {
     #[error("misaligned access")]
     MisalignedAccess,
     #[error("access fault")]
     AccessFault,
+    /// The access passed all checks, but the device it was forwarded to failed it. This causes an
+    /// access fault, just like [`MemoryError::AccessFault`].
+    #[error("access fault: {0}")]
+    BusFault(BusError),
     #[error("page fault")]
     PageFault,
 }
+
+/// Turns a failed bus access at `physical_address` into a [`MemoryError::BusFault`].
+fn bus_fault(physical_address: u32, error: BusError) -> MemoryError {
+    debug!(physical_address, error:%; "Memory access failed on the system bus");
+    MemoryError::BusFault(error)
+}
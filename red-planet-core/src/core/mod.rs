@@ -6,20 +6,28 @@ mod counters;
 pub mod csr;
 mod envcfg;
 mod execute;
+mod fcsr;
 mod interrupts;
 pub mod mmu;
+pub mod pmp;
+mod reservation;
+mod semihosting;
 mod status;
 mod trap;
+mod trigger;
+pub mod watchpoint;
 
-use crate::core::mmu::MemoryError;
+use crate::core::mmu::{FetchError, MemoryError};
+use crate::float::Format;
 use crate::instruction::{
-    AmoOp, BranchCondition, CsrOp, Instruction, LoadWidth, RegImmOp, RegRegOp, RegShiftImmOp,
-    StoreWidth,
+    instruction_length, is_compressed, AmoOp, BranchCondition, CsrOp, FloatCompareOp, FloatOp,
+    FusedMultiplyAddOp, Instruction, LoadWidth, MinMaxOp, RegImmOp, RegRegOp, RegShiftImmOp,
+    RegUnaryOp, SignInjectOp, StoreWidth,
 };
-use crate::registers::Registers;
+use crate::registers::{FloatRegisters, Registers};
 use crate::simulator::Simulatable;
 use crate::system_bus::SystemBus;
-use crate::{Allocated, Allocator, Endianness, PrivilegeLevel, RawPrivilegeLevel};
+use crate::{Alignment, Allocated, Allocator, Endianness, PrivilegeLevel, RawPrivilegeLevel};
 use counter_control::CounterControl;
 use counters::Counters;
 use envcfg::Envcfg;
@@ -27,10 +35,16 @@ use execute::Executor;
 use interrupts::Interrupts;
 use log::{debug, trace};
 use mmu::Mmu;
-use status::Status;
+use pmp::{Pmp, MAX_PMP_ENTRIES};
+use reservation::Reservation;
+use status::{ExtensionContextStatus, Status};
+use std::cell::RefCell;
 use std::fmt::Debug;
+use std::num::NonZeroU32;
 use thiserror::Error;
 use trap::Trap;
+use trigger::{TriggerAccess, Triggers};
+use watchpoint::Watchpoints;
 
 pub use csr::CsrSpecifier;
 
@@ -57,17 +71,49 @@ pub struct Config {
     /// If `true`, non-naturally-aligned memory accesses are supported.
     /// If `false`, they will generate an address-misaligned exception.
     pub support_misaligned_memory_access: bool,
-    /// If `true`, instruction must be word-aligned as specified in the spec.
-    /// If `false`, instruction must only be haldword-aligned, which would be the default if the C
-    /// extension were supported.
+    /// If `true`, instructions must be word-aligned (IALIGN=32), as specified for cores without
+    /// the C extension. Compressed instructions are then treated as illegal instructions, and the
+    /// C extension is not reported in misa.
+    /// If `false`, instructions must only be halfword-aligned (IALIGN=16), and compressed
+    /// instructions are supported.
     pub strict_instruction_alignment: bool,
     /// Address to which the core's PC register is reset.
     pub reset_vector: u32,
     /// Address of the handler for Non-Maskable Interrupts.
     pub nmi_vector: u32,
+    /// If set to `Some(n)`, every n-th SC that would otherwise succeed fails spuriously instead,
+    /// writing failure code 1 to rd. This allows testing the retry paths of guest code.
+    ///
+    /// > The failure code with value 1 encodes an unspecified failure. Other failure codes are
+    /// > reserved at this time.
+    ///
+    /// Failures are injected deterministically, so re-executing a program yields the same results.
+    pub sc_failure_interval: Option<NonZeroU32>,
+    /// Number of implemented PMP entries, at most [`MAX_PMP_ENTRIES`].
+    ///
+    /// > Up to 64 PMP entries are supported. Implementations may implement zero, 16, or 64 PMP
+    /// > entries; the lowest-numbered PMP entries must be implemented first.
+    ///
+    /// Other counts are accepted as well, to allow mimicking specific hardware.
+    pub pmp_entries: u8,
+    /// Number of Sdtrig triggers, selectable through tselect. If zero, the trigger CSRs are not
+    /// implemented.
+    ///
+    /// Each trigger can be configured as an mcontrol6 (address/data match) or icount (instruction
+    /// count) trigger.
+    pub trigger_count: u8,
+    /// If `true`, WFI stalls the hart until an interrupt is pending and enabled in mie. Otherwise,
+    /// WFI is implemented as a nop.
+    ///
+    /// > The operation of WFI must be unaffected by the global interrupt bits in mstatus (MIE and
+    /// > SIE) and the delegation register mideleg (i.e., the hart must resume if a locally enabled
+    /// > interrupt becomes pending, even if it has been delegated to a higher-privilege mode)
+    ///
+    /// See [`Core::is_waiting_for_interrupt`].
+    pub stall_on_wfi: bool,
 }
 
-/// RISC-V core implementing the RV32IMAZicsr ISA.
+/// RISC-V core implementing the RV32IMACZicsr ISA.
 ///
 /// As we don't support hardware multithreading, every core always only has a single hart.
 /// We therefore don't model RISC-V harts explicitly, but rather consider [`Core`] to be the whole
@@ -117,6 +163,11 @@ pub struct Core<A: Allocator, B: SystemBus<A>> {
     system_bus: B,
     /// General purpose registers: x and pc registers.
     registers: Allocated<A, Registers>,
+    /// Floating-point registers: f registers and the fcsr fields.
+    ///
+    /// Allocated separately from the general purpose registers, since most programs never touch
+    /// them.
+    float_registers: Allocated<A, FloatRegisters>,
     /// The core's current privilege mode.
     ///
     /// Allocated separately, because this is updated independently of other registers.
@@ -147,11 +198,31 @@ pub struct Core<A: Allocator, B: SystemBus<A>> {
     /// Allocated separately, because these are mutated independently of other registers, and likely
     /// not used often.
     envcfg: Allocated<A, Envcfg>,
+    /// Reservation set registered by LR, and consumed by SC.
+    ///
+    /// Allocated separately, because it is updated independently of all registers.
+    reservation: Allocated<A, Reservation>,
+    /// Physical memory protection (pmpcfg, pmpaddr) registers.
+    ///
+    /// Allocated separately, because these are rarely written.
+    pmp: Allocated<A, Pmp>,
+    /// Sdtrig trigger (tselect, tdata1, tdata2) registers.
+    ///
+    /// Allocated separately, because these are rarely written.
+    triggers: Allocated<A, Triggers>,
+    /// `true` while the hart is stalled by a WFI.
+    waiting_for_interrupt: Allocated<A, bool>,
+    /// `true` once satp has selected paging, see [`Core::paging_ever_enabled`].
+    paging_ever_enabled: Allocated<A, bool>,
+    /// Debugger watchpoints, which are deliberately not part of the simulated state.
+    watchpoints: RefCell<Watchpoints>,
 }
 
 impl<A: Allocator, B: SystemBus<A>> Core<A, B> {
-    /// The misa CSR is set to `0x4014_1101`, indicating that MXL=32 and that the following
-    /// extensions are supported: A, I, M, S, U.
+    /// The misa CSR is set to `0x4014_112D`, indicating that MXL=32 and that the following
+    /// extensions are supported: A, C, D, F, I, M, S, U.
+    ///
+    /// If [`Config::strict_instruction_alignment`] is set, the C bit will read as zero.
     ///
     /// > The misa CSR is a WARL read-write register reporting the ISA supported by the hart. This
     /// > register must be readable in any implementation, but a value of zero can be returned to
@@ -169,7 +240,7 @@ impl<A: Allocator, B: SystemBus<A>> Core<A, B> {
     /// > |   1 |   32 |
     /// > |   2 |   64 |
     /// > |   3 |  128 |
-    pub const MISA: u32 = 0x4014_1101;
+    pub const MISA: u32 = 0x4014_112D;
     /// The mvendorid CSR is set to 0 to indicate this is a non-commercial implementation.
     ///
     /// > The mvendorid CSR is a 32-bit read-only register providing the JEDEC manufacturer ID of
@@ -200,11 +271,17 @@ impl<A: Allocator, B: SystemBus<A>> Core<A, B> {
 
     pub fn new(allocator: &mut A, system_bus: B, config: Config) -> Self {
         debug!("Creating core with config {config:?}");
+        assert!(
+            config.pmp_entries as usize <= MAX_PMP_ENTRIES,
+            "at most {MAX_PMP_ENTRIES} PMP entries are supported",
+        );
         let registers = Allocated::new(allocator, Registers::new(config.reset_vector));
+        let triggers = Allocated::new(allocator, Triggers::new(config.trigger_count));
         Self {
             config,
             system_bus,
             registers,
+            float_registers: Allocated::new(allocator, FloatRegisters::new()),
             privilege_mode: Allocated::new(allocator, PrivilegeLevel::Machine),
             status: Allocated::new(allocator, Status::new()),
             counters: Allocated::new(allocator, Counters::new()),
@@ -212,11 +289,18 @@ impl<A: Allocator, B: SystemBus<A>> Core<A, B> {
             trap: Allocated::new(allocator, Trap::new()),
             interrupts: Allocated::new(allocator, Interrupts::new()),
             envcfg: Allocated::new(allocator, Envcfg::new()),
+            reservation: Allocated::new(allocator, Reservation::new()),
+            pmp: Allocated::new(allocator, Pmp::new()),
+            triggers,
+            waiting_for_interrupt: Allocated::new(allocator, false),
+            paging_ever_enabled: Allocated::new(allocator, false),
+            watchpoints: RefCell::default(),
         }
     }
 
     pub fn drop(self, allocator: &mut A) {
         self.registers.drop(allocator);
+        self.float_registers.drop(allocator);
         self.privilege_mode.drop(allocator);
         self.status.drop(allocator);
         self.counters.drop(allocator);
@@ -224,6 +308,11 @@ impl<A: Allocator, B: SystemBus<A>> Core<A, B> {
         self.trap.drop(allocator);
         self.interrupts.drop(allocator);
         self.envcfg.drop(allocator);
+        self.reservation.drop(allocator);
+        self.pmp.drop(allocator);
+        self.triggers.drop(allocator);
+        self.waiting_for_interrupt.drop(allocator);
+        self.paging_ever_enabled.drop(allocator);
     }
 
     pub fn system_bus(&self) -> &B {
@@ -267,6 +356,19 @@ impl<A: Allocator, B: SystemBus<A>> Core<A, B> {
         }
     }
 
+    /// Returns `true` if satp selects Sv32 paging, meaning that loads, stores and instruction
+    /// fetches below M-mode use virtual addresses.
+    pub fn paging_enabled(&self, allocator: &A) -> bool {
+        self.trap.get(allocator).satp_mode() != trap::SatpMode::Bare
+    }
+
+    /// Returns `true` if paging was enabled (see [`Core::paging_enabled`]) at any point up to now,
+    /// even before a reset. If this returns `false`, addresses were never translated in any past
+    /// state.
+    pub fn paging_ever_enabled(&self, allocator: &A) -> bool {
+        *self.paging_ever_enabled.get(allocator)
+    }
+
     /// Returns the endianness of the core for the given privilege mode.
     pub fn endianness(&self, allocator: &A, privilege_mode: PrivilegeLevel) -> Endianness {
         let status = self.status.get(allocator);
@@ -291,11 +393,27 @@ impl<A: Allocator, B: SystemBus<A>> Core<A, B> {
         self.registers.get_mut(allocator)
     }
 
+    /// Provides immutable access to the floating-point (f) registers, and the fcsr fields.
+    pub fn float_registers<'a>(&self, allocator: &'a A) -> &'a FloatRegisters {
+        self.float_registers.get(allocator)
+    }
+
+    /// Provides mutable access to the floating-point (f) registers, and the fcsr fields.
+    ///
+    /// Since the floating-point state is assumed to be modified, mstatus.FS is set to Dirty.
+    pub fn float_registers_mut<'a>(&self, allocator: &'a mut A) -> &'a mut FloatRegisters {
+        self.status
+            .get_mut(allocator)
+            .set_fs(ExtensionContextStatus::Dirty);
+        self.float_registers.get_mut(allocator)
+    }
+
     /// Generate a Reset.
     pub fn reset(&self, allocator: &mut A) {
         trace!("Resetting core");
         // Clear all x registers, reset pc to the configured reset vector.
         *self.registers.get_mut(allocator) = Registers::new(self.config.reset_vector);
+        *self.float_registers.get_mut(allocator) = FloatRegisters::new();
         // Set mcause to an all-zero value.
         let trap = self.trap.get_mut(allocator);
         trap.set_m_trap_cause(None::<Exception>);
@@ -306,12 +424,21 @@ impl<A: Allocator, B: SystemBus<A>> Core<A, B> {
         status.set_mie(false);
         status.set_mprv(false);
         status.set_mbe(false);
+        status.set_fs(ExtensionContextStatus::Initial);
         // Switch to M-mode.
         *self.privilege_mode.get_mut(allocator) = PrivilegeLevel::Machine;
         // Reset control registers.
         *self.counter_control.get_mut(allocator) = CounterControl::new();
         // Reset mconfig register.
         *self.envcfg.get_mut(allocator) = Envcfg::new();
+        // Drop any reservation.
+        *self.reservation.get_mut(allocator) = Reservation::new();
+        // Unlock and disable all PMP entries.
+        *self.pmp.get_mut(allocator) = Pmp::new();
+        // Disable all triggers.
+        *self.triggers.get_mut(allocator) = Triggers::new(self.config.trigger_count);
+        // Wake up from WFI.
+        *self.waiting_for_interrupt.get_mut(allocator) = false;
     }
 
     /// Generate a Non-Maskable Interrupt.
@@ -325,6 +452,8 @@ impl<A: Allocator, B: SystemBus<A>> Core<A, B> {
         let trap = self.trap.get_mut(allocator);
         trap.set_mepc(old_pc);
         trap.set_m_trap_cause(None::<Interrupt>);
+        // Wake up from WFI.
+        self.wake_up(allocator);
         // Switch to M-mode.
         *self.privilege_mode.get_mut(allocator) = PrivilegeLevel::Machine;
     }
@@ -348,7 +477,9 @@ impl<A: Allocator, B: SystemBus<A>> Core<A, B> {
             //
             // Unprivileged Floating-Point CSRs
             //
-            csr::FFLAGS | csr::FRM | csr::FCSR => Err(CsrAccessError::CsrUnsupported(specifier)),
+            csr::FFLAGS => self.read_fflags(allocator),
+            csr::FRM => self.read_frm(allocator),
+            csr::FCSR => self.read_fcsr(allocator),
             //
             // Unprivileged Counter/Timers
             //
@@ -401,7 +532,7 @@ impl<A: Allocator, B: SystemBus<A>> Core<A, B> {
             // Machine Trap Setup
             //
             csr::MSTATUS => self.read_mstatus(allocator),
-            csr::MISA => Ok(Self::MISA),
+            csr::MISA => Ok(self.misa()),
             csr::MEDELEG => self.read_medeleg(allocator),
             csr::MIDELEG => self.read_mideleg(allocator),
             csr::MIE => self.read_mie(allocator),
@@ -427,8 +558,13 @@ impl<A: Allocator, B: SystemBus<A>> Core<A, B> {
             //
             // Machine Memory Protection
             //
-            csr::PMPCFG0..=csr::PMPCFG15 | csr::PMPADDR0..=csr::PMPADDR63 => {
-                Err(CsrAccessError::CsrUnsupported(specifier))
+            csr::PMPCFG0..=csr::PMPCFG15 => {
+                let n = specifier - csr::PMPCFG0;
+                self.read_pmpcfg(allocator, n as usize)
+            }
+            csr::PMPADDR0..=csr::PMPADDR63 => {
+                let n = specifier - csr::PMPADDR0;
+                self.read_pmpaddr(allocator, n as usize)
             }
             //
             // Machine Counters/Timers
@@ -456,9 +592,17 @@ impl<A: Allocator, B: SystemBus<A>> Core<A, B> {
             //
             // Debug/Trace Registers
             //
-            csr::TSELECT | csr::TDATA1 | csr::TDATA2 | csr::TDATA3 | csr::MCONTEXT => {
+            csr::TSELECT | csr::TDATA1 | csr::TDATA2 | csr::TDATA3 | csr::TINFO
+                if self.config.trigger_count == 0 =>
+            {
                 Err(CsrAccessError::CsrUnsupported(specifier))
             }
+            csr::TSELECT => self.read_tselect(allocator),
+            csr::TDATA1 => self.read_tdata1(allocator),
+            csr::TDATA2 => self.read_tdata2(allocator),
+            csr::TDATA3 => self.read_tdata3(allocator),
+            csr::TINFO => self.read_tinfo(allocator),
+            csr::MCONTEXT => Err(CsrAccessError::CsrUnsupported(specifier)),
             _ => Err(CsrAccessError::CsrUnsupported(specifier)),
         }
     }
@@ -490,7 +634,9 @@ impl<A: Allocator, B: SystemBus<A>> Core<A, B> {
             //
             // Unprivileged Floating-Point CSRs
             //
-            csr::FFLAGS | csr::FRM | csr::FCSR => Err(CsrAccessError::CsrUnsupported(specifier))?,
+            csr::FFLAGS => self.write_fflags(allocator, value, mask),
+            csr::FRM => self.write_frm(allocator, value, mask),
+            csr::FCSR => self.write_fcsr(allocator, value, mask),
             //
             // Unprivileged Counter/Timers (read-only)
             //
@@ -561,8 +707,13 @@ impl<A: Allocator, B: SystemBus<A>> Core<A, B> {
             //
             // Machine Memory Protection
             //
-            csr::PMPCFG0..=csr::PMPCFG15 | csr::PMPADDR0..=csr::PMPADDR63 => {
-                Err(CsrAccessError::CsrUnsupported(specifier))?
+            csr::PMPCFG0..=csr::PMPCFG15 => {
+                let n = specifier - csr::PMPCFG0;
+                self.write_pmpcfg(allocator, n as usize, value, mask)
+            }
+            csr::PMPADDR0..=csr::PMPADDR63 => {
+                let n = specifier - csr::PMPADDR0;
+                self.write_pmpaddr(allocator, n as usize, value, mask)
             }
             //
             // Machine Counters/Timers
@@ -590,9 +741,17 @@ impl<A: Allocator, B: SystemBus<A>> Core<A, B> {
             //
             // Debug/Trace Registers
             //
-            csr::TSELECT | csr::TDATA1 | csr::TDATA2 | csr::TDATA3 | csr::MCONTEXT => {
+            csr::TSELECT | csr::TDATA1 | csr::TDATA2 | csr::TDATA3 | csr::TINFO
+                if self.config.trigger_count == 0 =>
+            {
                 Err(CsrAccessError::CsrUnsupported(specifier))?
             }
+            csr::TSELECT => self.write_tselect(allocator, value, mask),
+            csr::TDATA1 => self.write_tdata1(allocator, value, mask),
+            csr::TDATA2 => self.write_tdata2(allocator, value, mask),
+            csr::TDATA3 => self.write_tdata3(allocator, value, mask),
+            csr::TINFO => Ok(()),
+            csr::MCONTEXT => Err(CsrAccessError::CsrUnsupported(specifier))?,
             _ => Err(CsrAccessError::CsrUnsupported(specifier))?,
         }
     }
@@ -622,20 +781,20 @@ impl<A: Allocator, B: SystemBus<A>> Core<A, B> {
         Ok(())
     }
 
-    /// Performs a read of the memory-mapped mtime CSR.
+    /// Performs a read of the memory-mapped mtime CSR, which reads as zero if it is not mapped.
     pub fn read_mtime(&self, allocator: &mut A) -> u64 {
         let mut buf = [0u8; 8];
         self.system_bus
-            .read(&mut buf, allocator, self.config.mtime_address);
-        u64::from_le_bytes(buf)
+            .read(&mut buf, allocator, self.config.mtime_address)
+            .map_or(0, |()| u64::from_le_bytes(buf))
     }
 
-    /// Performs a read of the memory-mapped mtimecmp CSR.
+    /// Performs a read of the memory-mapped mtimecmp CSR, which reads as zero if it is not mapped.
     pub fn read_mtimecmp(&self, allocator: &mut A) -> u64 {
         let mut buf = [0u8; 8];
         self.system_bus
-            .read(&mut buf, allocator, self.config.mtimecmp_address);
-        u64::from_le_bytes(buf)
+            .read(&mut buf, allocator, self.config.mtimecmp_address)
+            .map_or(0, |()| u64::from_le_bytes(buf))
     }
 
     /// Provides an access wrapper around the system bus to address it as memory from this core's
@@ -644,29 +803,71 @@ impl<A: Allocator, B: SystemBus<A>> Core<A, B> {
     /// This takes into account the core's current privilege level, its memory mapping (i.e. which
     /// regions can be accessed), its configuration (e.g. whether misaligned memory accesses are
     /// supported), etc.
-    pub fn mmu(&self) -> Mmu<A, B> {
+    pub fn mmu(&self) -> Mmu<'_, A, B> {
         Mmu { core: self }
     }
 
+    /// Returns the value of the misa CSR, see [`Self::MISA`].
+    fn misa(&self) -> u32 {
+        match self.config.strict_instruction_alignment {
+            true => Self::MISA & !(1 << 2),
+            false => Self::MISA,
+        }
+    }
+
+    /// Returns the alignment required for instruction addresses (IALIGN).
+    ///
+    /// > IALIGN is either 16 or 32 bits. [...] The base ISA has IALIGN=32, but some standard
+    /// > extensions, including the compressed ISA extension, relax IALIGN to 16.
+    fn instruction_alignment(&self) -> Alignment {
+        match self.config.strict_instruction_alignment {
+            true => Alignment::WORD,
+            false => Alignment::HALFWORD,
+        }
+    }
+
     /// Fetch the next instruction at pc and execute.
     ///
     /// If an interrupt is ready to be taken, this will perform a trap for that interrupt, rather
     /// than executing the next instruction. Additionally, if the executed instruction causes an
     /// interrupt (indirectly), it will also be taken by this method.
+    ///
+    /// While the hart is stalled by a WFI (see [`Config::stall_on_wfi`]), this only counts a cycle,
+    /// until an interrupt becomes pending.
     pub fn step(&self, allocator: &mut A) {
         let pc = self.registers(allocator).pc();
         trace!("Stepping core, pc = {pc:#010x}");
+        if self.is_waiting_for_interrupt(allocator) {
+            trace!("Hart is stalled by WFI");
+            self.increment_cycle_counter(allocator);
+            return;
+        }
+        self.wake_up(allocator);
         if self.check_for_interrupts(allocator) {
             return;
         }
-        let raw_instruction =
-            self.mmu()
-                .fetch_instruction(allocator, pc)
-                .map_err(|err| match err {
-                    MemoryError::MisalignedAccess => Exception::InstructionAddressMisaligned(pc),
-                    MemoryError::AccessFault => Exception::InstructionAccessFault(pc),
-                    MemoryError::PageFault => Exception::InstructionPageFault(pc),
-                });
+        // Triggers on the instruction address fire before the instruction is fetched, triggers on
+        // the instruction itself right after.
+        let raw_instruction = self
+            .check_icount_triggers(allocator)
+            .and_then(|()| self.check_triggers(allocator, TriggerAccess::Execute, pc, None))
+            .and_then(|()| {
+                self.mmu().fetch_instruction(allocator, pc).map_err(
+                    |FetchError { address, error }| match error {
+                        MemoryError::MisalignedAccess => {
+                            Exception::InstructionAddressMisaligned(address)
+                        }
+                        MemoryError::AccessFault | MemoryError::BusFault(_) => {
+                            Exception::InstructionAccessFault(address)
+                        }
+                        MemoryError::PageFault => Exception::InstructionPageFault(address),
+                    },
+                )
+            })
+            .and_then(|raw| {
+                self.check_triggers(allocator, TriggerAccess::Execute, pc, Some(raw))?;
+                Ok(raw)
+            });
         self.step_with_raw(allocator, raw_instruction);
         self.check_for_interrupts(allocator);
     }
@@ -675,18 +876,20 @@ impl<A: Allocator, B: SystemBus<A>> Core<A, B> {
     ///
     /// Never checks for interrupts.
     fn step_with_raw(&self, allocator: &mut A, raw_instruction: ExecutionResult<u32>) {
-        let instruction = raw_instruction.and_then(|raw| {
-            Instruction::decode(raw).map_err(|_| Exception::IllegalInstruction(Some(raw)))
-        });
+        let instruction =
+            raw_instruction.and_then(|raw| Ok((self.decode(raw)?, instruction_length(raw))));
         self.step_with(allocator, instruction);
     }
 
-    /// Execute a single (decoded) instruction.
+    /// Execute a single (decoded) instruction of the given length.
     ///
     /// Never checks for interrupts.
-    fn step_with(&self, allocator: &mut A, instruction: ExecutionResult<Instruction>) {
+    fn step_with(&self, allocator: &mut A, instruction: ExecutionResult<(Instruction, u32)>) {
+        let privilege_mode = self.privilege_mode(allocator);
         let exception = instruction
-            .and_then(|instruction| self.execute_instruction(allocator, instruction))
+            .and_then(|(instruction, instruction_length)| {
+                self.execute_instruction(allocator, instruction, instruction_length)
+            })
             .err();
 
         if let Some(exception) = exception {
@@ -696,10 +899,17 @@ impl<A: Allocator, B: SystemBus<A>> Core<A, B> {
         trace!("Updating counters after instruction execution");
         self.increment_cycle_counter(allocator);
         match instruction {
-            // ECALL and EBREAK are not considered to retire.
-            // Similarly, if the instruction fetch failed, then instret should not be incremented.
-            Ok(Instruction::Ecall | Instruction::Ebreak) | Err(_) => {}
-            _ => self.increment_instret_counter(allocator),
+            // ECALL and EBREAK are not considered to retire, unless EBREAK performed a semihosting
+            // call. Similarly, if the instruction fetch failed, then instret should not be
+            // incremented.
+            Ok((Instruction::Ecall | Instruction::Ebreak, _)) if exception.is_some() => {}
+            Err(_) => {}
+            _ => {
+                self.increment_instret_counter(allocator);
+                if exception.is_none() {
+                    self.count_instruction_for_triggers(allocator, privilege_mode);
+                }
+            }
         };
 
         if let Some(exception) = exception {
@@ -717,9 +927,13 @@ impl<A: Allocator, B: SystemBus<A>> Core<A, B> {
     ///
     /// This can be useful for executing the operation defined by an instruction, without actually
     /// progressing general execution. If used for this scenario, consider first decrementing the
-    /// `pc` register by `4` so that the current instruction is in fact treated as the next, which
-    /// will ensure the `pc` register will be as expected after executing the instruction. Take into
-    /// account that this influences jump/branch targets.
+    /// `pc` register by the instruction's length (see [`instruction_length`]) so that the current
+    /// instruction is in fact treated as the next, which will ensure the `pc` register will be as
+    /// expected after executing the instruction. Take into account that this influences jump/branch
+    /// targets.
+    ///
+    /// If the lower 16 bits of `raw_instruction` hold a compressed instruction, the upper 16 bits
+    /// are ignored.
     ///
     /// # Unspecified behavior
     ///
@@ -732,9 +946,13 @@ impl<A: Allocator, B: SystemBus<A>> Core<A, B> {
         allocator: &mut A,
         raw_instruction: u32,
     ) -> ExecutionResult {
-        let instruction = Instruction::decode(raw_instruction)
-            .map_err(|_| Exception::IllegalInstruction(Some(raw_instruction)))?;
-        self.execute_instruction(allocator, instruction)
+        let instruction_length = instruction_length(raw_instruction);
+        let raw_instruction = match instruction_length {
+            2 => raw_instruction & 0xFFFF,
+            _ => raw_instruction,
+        };
+        let instruction = self.decode(raw_instruction)?;
+        self.execute_instruction(allocator, instruction, instruction_length)
             .map_err(|err| match err {
                 Exception::IllegalInstruction(None) => {
                     Exception::IllegalInstruction(Some(raw_instruction))
@@ -743,22 +961,42 @@ impl<A: Allocator, B: SystemBus<A>> Core<A, B> {
             })
     }
 
+    /// Decodes `raw_instruction`, raising an [`Exception::IllegalInstruction`] if it cannot be
+    /// decoded, or if it is a compressed instruction while
+    /// [`Config::strict_instruction_alignment`] is set.
+    fn decode(&self, raw_instruction: u32) -> ExecutionResult<Instruction> {
+        if self.config.strict_instruction_alignment && is_compressed(raw_instruction) {
+            return Err(Exception::IllegalInstruction(Some(raw_instruction)));
+        }
+        Instruction::decode(raw_instruction)
+            .map_err(|_| Exception::IllegalInstruction(Some(raw_instruction)))
+    }
+
     /// Execute a single (decoded) instruction.
     ///
     /// Performs the same operation as [`Self::execute_raw_instruction`], but takes an already
     /// decoded instruction.
     ///
+    /// `instruction_length` is the length in bytes of the encoded instruction, i.e. `2` if it was
+    /// decoded from a compressed instruction and `4` otherwise. It determines by how much the `pc`
+    /// register is incremented, and which return address is written by jumps.
+    ///
     /// Note that this is not the same as [`Self::step`]!
     /// See [`Self::execute_raw_instruction`] for why.
     pub fn execute_instruction(
         &self,
         allocator: &mut A,
         instruction: Instruction,
+        instruction_length: u32,
     ) -> ExecutionResult {
-        trace!("Executing instruction {instruction:?}");
+        trace!(
+            "Executing instruction {}",
+            instruction.disassemble(self.registers(allocator).pc())
+        );
         let mut executor = Executor {
             allocator,
             core: self,
+            instruction_length,
         };
         match instruction {
             Instruction::OpImm {
@@ -787,6 +1025,11 @@ impl<A: Allocator, B: SystemBus<A>> Core<A, B> {
                     RegShiftImmOp::Slli => Executor::slli,
                     RegShiftImmOp::Srli => Executor::srli,
                     RegShiftImmOp::Srai => Executor::srai,
+                    RegShiftImmOp::Rori => Executor::rori,
+                    RegShiftImmOp::Bclri => Executor::bclri,
+                    RegShiftImmOp::Bexti => Executor::bexti,
+                    RegShiftImmOp::Binvi => Executor::binvi,
+                    RegShiftImmOp::Bseti => Executor::bseti,
                 };
                 op(&mut executor, dest, src, shift_amount_u5)
             }
@@ -840,9 +1083,41 @@ impl<A: Allocator, B: SystemBus<A>> Core<A, B> {
                     RegRegOp::Divu => Executor::divu,
                     RegRegOp::Rem => Executor::rem,
                     RegRegOp::Remu => Executor::remu,
+                    RegRegOp::Sh1add => Executor::sh1add,
+                    RegRegOp::Sh2add => Executor::sh2add,
+                    RegRegOp::Sh3add => Executor::sh3add,
+                    RegRegOp::Andn => Executor::andn,
+                    RegRegOp::Orn => Executor::orn,
+                    RegRegOp::Xnor => Executor::xnor,
+                    RegRegOp::Max => Executor::max,
+                    RegRegOp::Maxu => Executor::maxu,
+                    RegRegOp::Min => Executor::min,
+                    RegRegOp::Minu => Executor::minu,
+                    RegRegOp::Rol => Executor::rol,
+                    RegRegOp::Ror => Executor::ror,
+                    RegRegOp::Clmul => Executor::clmul,
+                    RegRegOp::Clmulh => Executor::clmulh,
+                    RegRegOp::Clmulr => Executor::clmulr,
+                    RegRegOp::Bclr => Executor::bclr,
+                    RegRegOp::Bext => Executor::bext,
+                    RegRegOp::Binv => Executor::binv,
+                    RegRegOp::Bset => Executor::bset,
                 };
                 op(&mut executor, dest, src1, src2)
             }
+            Instruction::OpUnary { op, dest, src } => {
+                let op = match op {
+                    RegUnaryOp::Clz => Executor::clz,
+                    RegUnaryOp::Ctz => Executor::ctz,
+                    RegUnaryOp::Cpop => Executor::cpop,
+                    RegUnaryOp::SextB => Executor::sext_b,
+                    RegUnaryOp::SextH => Executor::sext_h,
+                    RegUnaryOp::ZextH => Executor::zext_h,
+                    RegUnaryOp::OrcB => Executor::orc_b,
+                    RegUnaryOp::Rev8 => Executor::rev8,
+                };
+                op(&mut executor, dest, src)
+            }
             Instruction::Jal { dest, offset } => executor.jal(dest, offset),
             Instruction::Jalr { dest, base, offset } => executor.jalr(dest, base, offset),
             Instruction::Branch {
@@ -893,6 +1168,7 @@ impl<A: Allocator, B: SystemBus<A>> Core<A, B> {
                 predecessor,
                 successor,
             } => executor.fence(predecessor, successor),
+            Instruction::FenceI => executor.fence_i(),
             Instruction::Ecall => executor.ecall(),
             Instruction::Ebreak => executor.ebreak(),
             Instruction::Sret => executor.sret(),
@@ -920,6 +1196,154 @@ impl<A: Allocator, B: SystemBus<A>> Core<A, B> {
                 };
                 op(&mut executor, dest, csr, immediate)
             }
+            Instruction::LoadFp {
+                precision,
+                dest,
+                base,
+                offset,
+            } => executor.fload(precision, dest, base, offset),
+            Instruction::StoreFp {
+                precision,
+                src,
+                base,
+                offset,
+            } => executor.fstore(precision, src, base, offset),
+            Instruction::FusedMultiplyAdd {
+                op,
+                precision,
+                rounding_mode,
+                dest,
+                src1,
+                src2,
+                src3,
+            } => {
+                let (negate_product, negate_addend) = match op {
+                    FusedMultiplyAddOp::Madd => (false, false),
+                    FusedMultiplyAddOp::Msub => (false, true),
+                    FusedMultiplyAddOp::Nmsub => (true, false),
+                    FusedMultiplyAddOp::Nmadd => (true, true),
+                };
+                executor.fused_multiply_add(
+                    negate_product,
+                    negate_addend,
+                    precision,
+                    rounding_mode,
+                    dest,
+                    src1,
+                    src2,
+                    src3,
+                )
+            }
+            Instruction::OpFp {
+                op,
+                precision,
+                rounding_mode,
+                dest,
+                src1,
+                src2,
+            } => {
+                let op = match op {
+                    FloatOp::Add => Executor::fadd,
+                    FloatOp::Sub => Executor::fsub,
+                    FloatOp::Mul => Executor::fmul,
+                    FloatOp::Div => Executor::fdiv,
+                };
+                op(&mut executor, precision, rounding_mode, dest, src1, src2)
+            }
+            Instruction::FloatSqrt {
+                precision,
+                rounding_mode,
+                dest,
+                src,
+            } => executor.fsqrt(precision, rounding_mode, dest, src),
+            Instruction::FloatSignInject {
+                op,
+                precision,
+                dest,
+                src1,
+                src2,
+            } => {
+                let op = match op {
+                    SignInjectOp::Sgnj => |_, s2| s2,
+                    SignInjectOp::Sgnjn => |_, s2: bool| !s2,
+                    SignInjectOp::Sgnjx => |s1: bool, s2: bool| s1 ^ s2,
+                };
+                executor.fsgnj(precision, dest, src1, src2, op)
+            }
+            Instruction::FloatMinMax {
+                op,
+                precision,
+                dest,
+                src1,
+                src2,
+            } => executor.fmin_max(op == MinMaxOp::Max, precision, dest, src1, src2),
+            Instruction::FloatCompare {
+                op,
+                precision,
+                dest,
+                src1,
+                src2,
+            } => {
+                let op = match op {
+                    FloatCompareOp::Eq => Format::eq,
+                    FloatCompareOp::Lt => Format::lt,
+                    FloatCompareOp::Le => Format::le,
+                };
+                executor.fcompare(precision, dest, src1, src2, op)
+            }
+            Instruction::FloatClassify {
+                precision,
+                dest,
+                src,
+            } => executor.fclass(precision, dest, src),
+            Instruction::FloatToInt {
+                signed,
+                precision,
+                rounding_mode,
+                dest,
+                src,
+            } => executor.fcvt_to_int(signed, precision, rounding_mode, dest, src),
+            Instruction::IntToFloat {
+                signed,
+                precision,
+                rounding_mode,
+                dest,
+                src,
+            } => executor.fcvt_from_int(signed, precision, rounding_mode, dest, src),
+            Instruction::FloatConvert {
+                from,
+                to,
+                rounding_mode,
+                dest,
+                src,
+            } => executor.fcvt_float(from, to, rounding_mode, dest, src),
+            Instruction::FloatMoveToInt { dest, src } => executor.fmv_x_w(dest, src),
+            Instruction::FloatMoveFromInt { dest, src } => executor.fmv_w_x(dest, src),
+        }
+    }
+
+    /// Returns `true` if the hart is stalled by a WFI, waiting for an interrupt to become pending.
+    ///
+    /// Once an interrupt is pending, this returns `false`, even though the hart only resumes on
+    /// the next step.
+    pub fn is_waiting_for_interrupt(&self, allocator: &A) -> bool {
+        *self.waiting_for_interrupt.get(allocator)
+            && !self.interrupts.get(allocator).any_locally_enabled()
+    }
+
+    /// Stalls the hart until an interrupt becomes pending, if enabled by
+    /// [`Config::stall_on_wfi`].
+    fn wait_for_interrupt(&self, allocator: &mut A) {
+        if self.config.stall_on_wfi {
+            debug!("Stalling hart until an interrupt is pending");
+            *self.waiting_for_interrupt.get_mut(allocator) = true;
+        }
+    }
+
+    fn wake_up(&self, allocator: &mut A) {
+        if *self.waiting_for_interrupt.get(allocator) {
+            debug!("Waking up hart from WFI");
+            *self.waiting_for_interrupt.get_mut(allocator) = false;
         }
     }
 
@@ -1059,7 +1483,7 @@ impl TryFrom<u32> for ExceptionCode {
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub enum Exception {
-    /// Instruction address is not on a four-byte aligned boundary in memory.
+    /// Instruction address is not aligned to IALIGN (see [`Config::strict_instruction_alignment`]).
     ///
     /// The inner value is the faulting virtual address.
     InstructionAddressMisaligned(u32),
@@ -1072,9 +1496,13 @@ pub enum Exception {
     /// - Attempt to access a CSR without the appropriate privilege level.
     /// - Attempt to write to a read-only CSR.
     ///
-    /// The inner value is the raw instruction if that data was available.
+    /// The inner value is the raw instruction if that data was available. For compressed
+    /// instructions, only the lower 16 bits are set.
     IllegalInstruction(Option<u32>),
-    Breakpoint,
+    /// The inner value is the virtual address that caused the breakpoint: the address of the
+    /// instruction for `ebreak` and execute triggers, and the accessed address for load and store
+    /// triggers.
+    Breakpoint(u32),
     /// The inner value is the virtual address of the portion of the access that caused the fault.
     LoadAddressMisaligned(u32),
     /// The inner value is the faulting virtual address.
@@ -1101,7 +1529,7 @@ impl Exception {
             Self::InstructionAddressMisaligned(_) => ExceptionCode::InstructionAddressMisaligned,
             Self::InstructionAccessFault(_) => ExceptionCode::InstructionAccessFault,
             Self::IllegalInstruction(_) => ExceptionCode::IllegalInstruction,
-            Self::Breakpoint => ExceptionCode::Breakpoint,
+            Self::Breakpoint(_) => ExceptionCode::Breakpoint,
             Self::LoadAddressMisaligned(_) => ExceptionCode::LoadAddressMisaligned,
             Self::LoadAccessFault(_) => ExceptionCode::LoadAccessFault,
             Self::StoreOrAmoAddressMisaligned(_) => ExceptionCode::StoreOrAmoAddressMisaligned,
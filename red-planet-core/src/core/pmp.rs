@@ -0,0 +1,408 @@
+//! Physical Memory Protection (PMP) unit.
+//!
+//! > To support secure processing and contain faults, it is desirable to limit the physical
+//! > addresses accessible by software running on a hart. An optional physical memory protection
+//! > (PMP) unit provides per-hart machine-mode control registers to allow physical memory access
+//! > privileges (read, write, execute) to be specified for each physical memory region.
+
+use bitvec::{field::BitField, order::Lsb0, view::BitView};
+use log::{debug, trace};
+use space_time::allocator::Allocator;
+
+use crate::system_bus::{AccessType, SystemBus};
+use crate::PrivilegeLevel;
+
+use super::{Core, CsrReadResult, CsrWriteResult};
+
+/// Maximum number of PMP entries.
+///
+/// > Up to 64 PMP entries are supported. Implementations may implement zero, 16, or 64 PMP
+/// > entries; the lowest-numbered PMP entries must be implemented first.
+pub const MAX_PMP_ENTRIES: usize = 64;
+
+/// Provides the pmpcfg0–pmpcfg15 and pmpaddr0–pmpaddr63 registers.
+///
+/// All entries are stored, but only the first [`Config::pmp_entries`](super::Config::pmp_entries)
+/// are implemented: the others are read-only zero, and never match any access.
+///
+/// The PMP grain is 4 bytes (G=0), so all address-matching modes are supported.
+#[derive(Debug, Clone)]
+pub struct Pmp {
+    /// The 8-bit configuration register of each entry.
+    cfg: [u8; MAX_PMP_ENTRIES],
+    /// The address register of each entry, encoding bits 33–2 of a 34-bit physical address.
+    addr: [u32; MAX_PMP_ENTRIES],
+}
+
+impl Default for Pmp {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Pmp {
+    /// > Upon reset, the A and L fields of all PMP entries must be zeroed.
+    ///
+    /// All other fields are zeroed as well.
+    pub fn new() -> Self {
+        Self {
+            cfg: [0; MAX_PMP_ENTRIES],
+            addr: [0; MAX_PMP_ENTRIES],
+        }
+    }
+
+    pub fn cfg(&self, index: usize) -> u8 {
+        self.cfg[index]
+    }
+
+    pub fn addr(&self, index: usize) -> u32 {
+        self.addr[index]
+    }
+
+    pub fn r(&self, index: usize) -> bool {
+        self.cfg[index].view_bits::<Lsb0>()[idx::R]
+    }
+
+    pub fn w(&self, index: usize) -> bool {
+        self.cfg[index].view_bits::<Lsb0>()[idx::W]
+    }
+
+    pub fn x(&self, index: usize) -> bool {
+        self.cfg[index].view_bits::<Lsb0>()[idx::X]
+    }
+
+    pub fn address_matching(&self, index: usize) -> AddressMatching {
+        match self.cfg[index].view_bits::<Lsb0>()[idx::A].load_le::<u8>() {
+            0 => AddressMatching::Off,
+            1 => AddressMatching::Tor,
+            2 => AddressMatching::Na4,
+            3 => AddressMatching::Napot,
+            _ => unreachable!(),
+        }
+    }
+
+    /// > The L bit indicates that the PMP entry is locked, i.e., writes to the configuration
+    /// > register and associated address registers are ignored. Locked PMP entries remain locked
+    /// > until the hart is reset.
+    pub fn l(&self, index: usize) -> bool {
+        self.cfg[index].view_bits::<Lsb0>()[idx::L]
+    }
+
+    /// Writes the configuration register of entry `index`, unless it is locked.
+    ///
+    /// The reserved bits 6–5 are hardwired to zero, and the reserved combination R=0 and W=1 is
+    /// avoided by clearing W.
+    pub fn write_cfg(&mut self, index: usize, value: u8) {
+        if self.l(index) {
+            debug!("Ignoring write to locked pmp{index}cfg");
+            return;
+        }
+        let mut value = value & !0b0110_0000;
+        let bits = value.view_bits_mut::<Lsb0>();
+        if !bits[idx::R] {
+            bits.set(idx::W, false);
+        }
+        trace!("Setting pmp{index}cfg to {value:#04x}");
+        self.cfg[index] = value;
+    }
+
+    /// Writes the address register of entry `index`, unless it is locked.
+    ///
+    /// > If PMP entry i is locked, writes to pmpicfg and pmpaddri are ignored. Additionally, if PMP
+    /// > entry i is locked and pmpicfg.A is set to TOR, writes to pmpaddri-1 are ignored.
+    pub fn write_addr(&mut self, index: usize, value: u32) {
+        let next_locks = index + 1 < MAX_PMP_ENTRIES
+            && self.l(index + 1)
+            && self.address_matching(index + 1) == AddressMatching::Tor;
+        if self.l(index) || next_locks {
+            debug!("Ignoring write to locked pmpaddr{index}");
+            return;
+        }
+        trace!("Setting pmpaddr{index} to {value:#010x}");
+        self.addr[index] = value;
+    }
+
+    /// Returns the range of physical byte addresses `start..end` matched by entry `index`, or
+    /// `None` if the entry is disabled or matches no address.
+    pub fn range(&self, index: usize) -> Option<(u64, u64)> {
+        let addr = self.addr[index] as u64;
+        match self.address_matching(index) {
+            AddressMatching::Off => None,
+            // > If TOR is selected, the associated address register forms the top of the address
+            // > range, and the preceding PMP address register forms the bottom of the address
+            // > range. If PMP entry i's A field is set to TOR, the entry matches any address y such
+            // > that pmpaddri-1≤y<pmpaddri (irrespective of the value of pmpcfgi-1). If PMP entry
+            // > 0's A field is set to TOR, zero is used for the lower bound, and so it matches any
+            // > address y<pmpaddr0.
+            AddressMatching::Tor => {
+                let start = match index {
+                    0 => 0,
+                    _ => (self.addr[index - 1] as u64) << 2,
+                };
+                let end = addr << 2;
+                (start < end).then_some((start, end))
+            }
+            AddressMatching::Na4 => Some((addr << 2, (addr << 2) + 4)),
+            // The number of trailing ones in pmpaddr encodes the size of the naturally aligned
+            // power-of-two region, which is at least 8 bytes.
+            AddressMatching::Napot => {
+                let ones = addr.trailing_ones();
+                let start = (addr & !((1 << ones) - 1)) << 2;
+                Some((start, start + (1 << (ones + 3))))
+            }
+        }
+    }
+
+    /// Checks whether an access of `size` bytes at the physical `address` is permitted, taking
+    /// only the first `entries` PMP entries into account.
+    ///
+    /// > PMP entries are statically prioritized. The lowest-numbered PMP entry that matches any
+    /// > byte of an access determines whether that access succeeds or fails. The matching PMP
+    /// > entry must match all bytes of an access, or the access fails, irrespective of the L, R,
+    /// > W, and X bits.
+    ///
+    /// > If a PMP entry matches all bytes of an access, then the L, R, W, and X bits determine
+    /// > whether the access succeeds or fails. If the L bit is clear and the privilege mode of
+    /// > the access is M, the access succeeds. Otherwise, if the L bit is set or the privilege
+    /// > mode of the access is S or U, then the access succeeds only if the R, W, or X bit
+    /// > corresponding to the access type is set.
+    ///
+    /// > If no PMP entry matches an M-mode access, the access succeeds. If no PMP entry matches an
+    /// > S-mode or U-mode access, but at least one PMP entry is implemented, the access fails.
+    pub fn check(
+        &self,
+        entries: usize,
+        address: u32,
+        size: usize,
+        access_type: AccessType,
+        privilege_level: PrivilegeLevel,
+    ) -> bool {
+        let start = address as u64;
+        let end = start + size as u64;
+        for index in 0..entries {
+            let Some((entry_start, entry_end)) = self.range(index) else {
+                continue;
+            };
+            if start >= entry_end || end <= entry_start {
+                continue;
+            }
+            if start < entry_start || end > entry_end {
+                debug!("Access only partially matches PMP entry {index}");
+                return false;
+            }
+            if privilege_level == PrivilegeLevel::Machine && !self.l(index) {
+                return true;
+            }
+            return match access_type {
+                AccessType::Read => self.r(index),
+                AccessType::Write => self.w(index),
+                AccessType::Execute => self.x(index),
+            };
+        }
+        privilege_level == PrivilegeLevel::Machine || entries == 0
+    }
+}
+
+/// Address-matching mode of a PMP entry, encoded in the A field of its configuration register.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum AddressMatching {
+    /// Null region (disabled).
+    Off,
+    /// Top of range.
+    Tor,
+    /// Naturally aligned four-byte region.
+    Na4,
+    /// Naturally aligned power-of-two region, ≥8 bytes.
+    Napot,
+}
+
+/// Bit indices for the fields of a PMP configuration register.
+mod idx {
+    use std::ops::Range;
+
+    pub const R: usize = 0;
+    pub const W: usize = 1;
+    pub const X: usize = 2;
+    pub const A: Range<usize> = 3..5;
+    pub const L: usize = 7;
+}
+
+impl<A: Allocator, B: SystemBus<A>> Core<A, B> {
+    /// Reads pmpcfg`n`, which holds the configuration of PMP entries `4n` to `4n + 3`.
+    ///
+    /// Unimplemented entries read as zero.
+    pub fn read_pmpcfg(&self, allocator: &mut A, n: usize) -> CsrReadResult {
+        let pmp = self.pmp.get(allocator);
+        Ok(u32::from_le_bytes([0, 1, 2, 3].map(|i| pmp.cfg(n * 4 + i))))
+    }
+
+    /// Writes pmpcfg`n`, which holds the configuration of PMP entries `4n` to `4n + 3`.
+    ///
+    /// Writes to unimplemented or locked entries are ignored.
+    pub fn write_pmpcfg(
+        &self,
+        allocator: &mut A,
+        n: usize,
+        value: u32,
+        mask: u32,
+    ) -> CsrWriteResult {
+        let entries = self.config.pmp_entries as usize;
+        let pmp = self.pmp.get_mut(allocator);
+        let old = u32::from_le_bytes([0, 1, 2, 3].map(|i| pmp.cfg(n * 4 + i)));
+        let new = (old & !mask | value & mask).to_le_bytes();
+        for (i, cfg) in new.into_iter().enumerate() {
+            let index = n * 4 + i;
+            if index < entries && cfg != pmp.cfg(index) {
+                pmp.write_cfg(index, cfg);
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads pmpaddr`n`. Unimplemented entries read as zero.
+    pub fn read_pmpaddr(&self, allocator: &mut A, n: usize) -> CsrReadResult {
+        Ok(self.pmp.get(allocator).addr(n))
+    }
+
+    /// Writes pmpaddr`n`. Writes to unimplemented or locked entries are ignored.
+    pub fn write_pmpaddr(
+        &self,
+        allocator: &mut A,
+        n: usize,
+        value: u32,
+        mask: u32,
+    ) -> CsrWriteResult {
+        if n < self.config.pmp_entries as usize {
+            let pmp = self.pmp.get_mut(allocator);
+            let addr = pmp.addr(n) & !mask | value & mask;
+            pmp.write_addr(n, addr);
+        }
+        Ok(())
+    }
+
+    /// Checks an access of `size` bytes at the physical `address` against this hart's PMP entries.
+    ///
+    /// See [`Pmp::check`].
+    pub fn pmp_check(
+        &self,
+        allocator: &A,
+        address: u32,
+        size: usize,
+        access_type: AccessType,
+        privilege_level: PrivilegeLevel,
+    ) -> bool {
+        self.pmp.get(allocator).check(
+            self.config.pmp_entries as usize,
+            address,
+            size,
+            access_type,
+            privilege_level,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const R: u8 = 0b0000_0001;
+    const W: u8 = 0b0000_0010;
+    const X: u8 = 0b0000_0100;
+    const TOR: u8 = 0b0000_1000;
+    const NA4: u8 = 0b0001_0000;
+    const NAPOT: u8 = 0b0001_1000;
+    const L: u8 = 0b1000_0000;
+
+    use AccessType::{Execute, Read, Write};
+    use PrivilegeLevel::{Machine, Supervisor, User};
+
+    #[test]
+    fn test_no_entries() {
+        let pmp = Pmp::new();
+        assert!(pmp.check(0, 0x8000_0000, 4, Read, User));
+        assert!(pmp.check(16, 0x8000_0000, 4, Read, Machine));
+        assert!(!pmp.check(16, 0x8000_0000, 4, Read, User));
+        assert!(!pmp.check(16, 0x8000_0000, 4, Execute, Supervisor));
+    }
+
+    #[test]
+    fn test_tor() {
+        let mut pmp = Pmp::new();
+        pmp.write_addr(0, 0x8000_0000 >> 2);
+        pmp.write_addr(1, 0x8000_1000 >> 2);
+        pmp.write_cfg(1, TOR | R | X);
+        assert_eq!(Some((0x8000_0000, 0x8000_1000)), pmp.range(1));
+        assert!(pmp.check(16, 0x8000_0000, 4, Read, User));
+        assert!(pmp.check(16, 0x8000_0FFC, 4, Execute, User));
+        assert!(!pmp.check(16, 0x8000_0FFC, 4, Write, User));
+        assert!(!pmp.check(16, 0x8000_1000, 4, Read, User));
+        assert!(!pmp.check(16, 0x7FFF_FFFC, 4, Read, User));
+        // Partially matching accesses always fail, even in M-mode.
+        assert!(!pmp.check(16, 0x8000_0FFE, 4, Read, Machine));
+        // Entry 0 as TOR matches everything below pmpaddr0.
+        pmp.write_cfg(0, TOR | R);
+        assert_eq!(Some((0, 0x8000_0000)), pmp.range(0));
+        assert!(pmp.check(16, 0x1000, 4, Read, User));
+    }
+
+    #[test]
+    fn test_na4_napot() {
+        let mut pmp = Pmp::new();
+        pmp.write_addr(0, 0x1000 >> 2);
+        pmp.write_cfg(0, NA4 | R);
+        assert_eq!(Some((0x1000, 0x1004)), pmp.range(0));
+        // 0x2000..0x3000 is encoded as 0x2000 >> 2 | (0x1000 >> 3) - 1.
+        pmp.write_addr(1, 0x2000 >> 2 | 0x01FF);
+        pmp.write_cfg(1, NAPOT | R | W);
+        assert_eq!(Some((0x2000, 0x3000)), pmp.range(1));
+        pmp.write_addr(2, 0xFFFF_FFFF);
+        pmp.write_cfg(2, NAPOT | X);
+        assert_eq!(Some((0, 1 << 35)), pmp.range(2));
+        assert!(pmp.check(16, 0x1000, 4, Read, User));
+        assert!(!pmp.check(16, 0x1000, 8, Read, User));
+        assert!(pmp.check(16, 0x2FFC, 4, Write, Supervisor));
+        assert!(pmp.check(16, 0x3000, 4, Execute, User));
+        assert!(!pmp.check(16, 0x3000, 4, Read, User));
+    }
+
+    #[test]
+    fn test_priority() {
+        let mut pmp = Pmp::new();
+        pmp.write_addr(0, 0x1000 >> 2);
+        pmp.write_cfg(0, NA4);
+        pmp.write_addr(1, 0xFFFF_FFFF);
+        pmp.write_cfg(1, NAPOT | R | W | X);
+        assert!(!pmp.check(16, 0x1000, 4, Read, User));
+        assert!(pmp.check(16, 0x1004, 4, Read, User));
+        // Entries beyond the implemented count are ignored.
+        assert!(!pmp.check(1, 0x1004, 4, Read, User));
+    }
+
+    #[test]
+    fn test_lock() {
+        let mut pmp = Pmp::new();
+        pmp.write_addr(0, 0x1000 >> 2);
+        pmp.write_addr(1, 0x2000 >> 2);
+        pmp.write_cfg(1, TOR | R | L);
+        // Without the L bit, M-mode accesses always succeed.
+        assert!(pmp.check(16, 0x1000, 4, Read, Machine));
+        assert!(!pmp.check(16, 0x1000, 4, Write, Machine));
+        // Locked entries, and the address of an entry below a locked TOR entry, can't be written.
+        pmp.write_cfg(1, TOR | R | W);
+        pmp.write_addr(1, 0);
+        pmp.write_addr(0, 0);
+        assert_eq!(TOR | R | L, pmp.cfg(1));
+        assert_eq!(Some((0x1000, 0x2000)), pmp.range(1));
+        // Entry 0 is not locked itself.
+        pmp.write_cfg(0, NA4 | R);
+        assert_eq!(NA4 | R, pmp.cfg(0));
+    }
+
+    #[test]
+    fn test_write_cfg_warl() {
+        let mut pmp = Pmp::new();
+        // Reserved bits read as zero, and W is cleared if R is clear.
+        pmp.write_cfg(0, 0b0110_0000 | W | X);
+        assert_eq!(X, pmp.cfg(0));
+    }
+}
@@ -0,0 +1,162 @@
+use std::num::NonZeroU32;
+
+use log::trace;
+use space_time::allocator::Allocator;
+
+use crate::system_bus::SystemBus;
+
+use super::Core;
+
+/// Size in bytes of a reservation set. Reservation sets are naturally aligned to this size.
+///
+/// > LR.W loads a word from the address in rs1, places the sign-extended value in rd, and
+/// > registers a reservation set—a set of bytes that subsumes the bytes in the addressed word.
+pub const RESERVATION_GRANULE: u32 = 4;
+
+/// Reservation set registered by the last LR, used to determine whether a subsequent SC succeeds.
+///
+/// > SC.W conditionally writes a word in rs2 to the address in rs1: the SC.W succeeds only if the
+/// > reservation is still valid and the reservation set contains the bytes being written. If the
+/// > SC.W succeeds, the instruction writes the word in rs2 to memory, and it writes zero to rd. If
+/// > the SC.W fails, the instruction does not write to memory, and it writes a nonzero value to
+/// > rd. Regardless of success or failure, executing an SC.W instruction invalidates any
+/// > reservation held by this hart.
+///
+/// Reservations are tracked on physical addresses. Besides SC, a reservation is invalidated by
+/// traps, and by stores (including AMOs) that overlap the reserved granule.
+#[derive(Debug, Clone, Default)]
+pub struct Reservation {
+    /// Physical address of the reserved granule, if a reservation is held.
+    granule: Option<u32>,
+    /// Number of SCs that would have succeeded, used to inject spurious failures deterministically.
+    sc_count: u32,
+}
+
+impl Reservation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the physical address of the reserved granule, if any.
+    pub fn granule(&self) -> Option<u32> {
+        self.granule
+    }
+
+    /// Registers a reservation set containing the physical `address`, replacing any previous one.
+    pub fn reserve(&mut self, address: u32) {
+        let granule = address & !(RESERVATION_GRANULE - 1);
+        trace!("Registering reservation set at {granule:#010x}");
+        self.granule = Some(granule);
+    }
+
+    /// Invalidates the reservation, if any.
+    pub fn invalidate(&mut self) {
+        if self.granule.take().is_some() {
+            trace!("Invalidating reservation set");
+        }
+    }
+
+    /// Returns `true` if a write of `size` bytes to the physical `address` overlaps the reserved
+    /// granule.
+    pub fn overlaps(&self, address: u32, size: usize) -> bool {
+        self.granule.is_some_and(|granule| {
+            let end = address as u64 + size as u64;
+            (address as u64) < granule as u64 + RESERVATION_GRANULE as u64 && end > granule as u64
+        })
+    }
+
+    /// Consumes the reservation for an SC to the physical `address`, and returns whether the SC
+    /// succeeds.
+    ///
+    /// If `failure_interval` is `Some(n)`, every n-th SC that would otherwise succeed fails
+    /// spuriously.
+    pub fn store_conditional(
+        &mut self,
+        address: u32,
+        failure_interval: Option<NonZeroU32>,
+    ) -> bool {
+        let valid = self.overlaps(address, RESERVATION_GRANULE as usize);
+        self.invalidate();
+        if !valid {
+            return false;
+        }
+        self.sc_count = self.sc_count.wrapping_add(1);
+        match failure_interval {
+            Some(interval) if self.sc_count.is_multiple_of(interval.get()) => {
+                trace!("Forcing spurious SC failure");
+                false
+            }
+            _ => true,
+        }
+    }
+}
+
+impl<A: Allocator, B: SystemBus<A>> Core<A, B> {
+    /// Invalidates this hart's reservation, if it holds one.
+    pub(super) fn invalidate_reservation(&self, allocator: &mut A) {
+        // Avoid touching the allocated state in the common case where there is no reservation.
+        if self.reservation.get(allocator).granule().is_some() {
+            self.reservation.get_mut(allocator).invalidate();
+        }
+    }
+
+    /// Invalidates this hart's reservation if a write of `size` bytes to the physical `address`
+    /// overlaps it.
+    ///
+    /// The system bus must call this for every write to it, including those of this hart, see
+    /// [`SystemBus`]. That way LR/SC sequences cannot succeed when this hart, another hart or a
+    /// device stored to the reserved granule in between.
+    pub fn invalidate_reservation_on_write(&self, allocator: &mut A, address: u32, size: usize) {
+        if self.reservation.get(allocator).overlaps(address, size) {
+            self.reservation.get_mut(allocator).invalidate();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_store_conditional() {
+        let mut reservation = Reservation::new();
+        // No reservation held.
+        assert!(!reservation.store_conditional(0x8000_0000, None));
+        reservation.reserve(0x8000_0002);
+        assert_eq!(Some(0x8000_0000), reservation.granule());
+        // Different granule.
+        assert!(!reservation.store_conditional(0x8000_0004, None));
+        // The failed SC invalidated the reservation.
+        assert!(!reservation.store_conditional(0x8000_0000, None));
+        reservation.reserve(0x8000_0000);
+        assert!(reservation.store_conditional(0x8000_0000, None));
+        assert_eq!(None, reservation.granule());
+    }
+
+    #[test]
+    fn test_overlaps() {
+        let mut reservation = Reservation::new();
+        assert!(!reservation.overlaps(0x100, 4));
+        reservation.reserve(0x100);
+        assert!(reservation.overlaps(0x100, 1));
+        assert!(reservation.overlaps(0x103, 1));
+        assert!(reservation.overlaps(0xFE, 4));
+        assert!(!reservation.overlaps(0xFC, 4));
+        assert!(!reservation.overlaps(0x104, 8));
+        reservation.reserve(0xFFFF_FFFC);
+        assert!(reservation.overlaps(0xFFFF_FFF8, 8));
+    }
+
+    #[test]
+    fn test_spurious_failures() {
+        let mut reservation = Reservation::new();
+        let interval = NonZeroU32::new(3);
+        let results: Vec<bool> = (0..6)
+            .map(|_| {
+                reservation.reserve(0x40);
+                reservation.store_conditional(0x40, interval)
+            })
+            .collect();
+        assert_eq!(vec![true, true, false, true, true, false], results);
+    }
+}
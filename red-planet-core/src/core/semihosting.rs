@@ -0,0 +1,83 @@
+use log::debug;
+use space_time::allocator::Allocator;
+
+use crate::bus::{Bus, BusError};
+use crate::registers::Specifier;
+use crate::system_bus::SystemBus;
+
+use super::mmu::Mmu;
+use super::Core;
+
+/// `slli x0, x0, 0x1f`, the instruction right before the `ebreak` of the semihosting sequence.
+const ENTRY_NOP: u32 = 0x01F0_1013;
+/// `srai x0, x0, 7`, the instruction right after the `ebreak` of the semihosting sequence.
+const EXIT_NOP: u32 = 0x4070_5013;
+
+impl<A: Allocator, B: SystemBus<A>> Core<A, B> {
+    /// Returns `true` if the `ebreak` at `pc` is part of the semihosting sequence:
+    ///
+    /// ```text
+    /// slli x0, x0, 0x1f
+    /// ebreak
+    /// srai x0, x0, 7
+    /// ```
+    ///
+    /// None of these instructions may be compressed.
+    ///
+    /// See <https://github.com/riscv-non-isa/riscv-semihosting/blob/main/riscv-semihosting.adoc>.
+    pub(super) fn is_semihosting_sequence(&self, allocator: &A, pc: u32) -> bool {
+        let read = |address: u32| {
+            let mut buf = [0; 4];
+            self.mmu()
+                .read_range_debug(&mut buf, allocator, address)
+                .map(|()| u32::from_le_bytes(buf))
+        };
+        read(pc.wrapping_sub(4)) == Ok(ENTRY_NOP) && read(pc.wrapping_add(4)) == Ok(EXIT_NOP)
+    }
+
+    /// Performs the semihosting call in a0 with the parameter in a1 through the system bus, and
+    /// writes the result to a0. Returns `false` if the system bus does not support semihosting in
+    /// the current privilege mode.
+    pub(super) fn semihosting_call(&self, allocator: &mut A) -> bool {
+        let registers = self.registers(allocator);
+        let operation = registers.x(Specifier::A0);
+        let parameter = registers.x(Specifier::A1);
+        debug!("Semihosting call {operation:#x} with parameter {parameter:#010x}");
+        let memory = VirtualMemory(self.mmu());
+        let privilege = self.privilege_mode(allocator);
+        match (self.system_bus)
+            .semihosting_call(allocator, &memory, privilege, operation, parameter)
+        {
+            Some(result) => {
+                self.registers_mut(allocator).set_x(Specifier::A0, result);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Memory as seen by a core, through which the system bus accesses the parameter blocks and
+/// buffers of semihosting calls.
+#[derive(Debug)]
+struct VirtualMemory<'c, A: Allocator, B: SystemBus<A>>(Mmu<'c, A, B>);
+
+impl<A: Allocator, B: SystemBus<A>> Bus<A> for VirtualMemory<'_, A, B> {
+    fn read(&self, buf: &mut [u8], allocator: &mut A, address: u32) -> Result<(), BusError> {
+        (self.0)
+            .read_range(buf, allocator, address)
+            .map_err(|_| BusError::Denied)
+    }
+
+    fn read_debug(&self, buf: &mut [u8], allocator: &A, address: u32) -> Result<(), BusError> {
+        (self.0)
+            .read_range_debug(buf, allocator, address)
+            .map_err(|_| BusError::Denied)
+    }
+
+    fn write(&self, allocator: &mut A, address: u32, buf: &[u8]) -> Result<(), BusError> {
+        (self.0)
+            .write_range(allocator, address, buf)
+            .map_err(|_| BusError::Denied)
+    }
+}
@@ -28,11 +28,17 @@ impl Default for Status {
 }
 
 impl Status {
+    /// Returns the status registers in their reset state.
+    ///
+    /// The FS field is reset to Initial rather than Off, so software that uses the F and D
+    /// extensions can run without first having to enable them.
     pub fn new() -> Self {
-        Self {
+        let mut status = Self {
             mstatus: 0x0000_0000,
             mstatush: 0x0000_0000,
-        }
+        };
+        status.set_fs(ExtensionContextStatus::Initial);
+        status
     }
 
     /// Returns `true` if the MIE (M-mode Interrupt Enable) bit is set.
@@ -352,7 +358,7 @@ impl<A: Allocator, B: SystemBus<A>> Core<A, B> {
     pub fn write_mstatus(&self, allocator: &mut A, value: u32, mask: u32) -> CsrWriteResult {
         let status = self.status.get_mut(allocator);
 
-        let mask_bits = value.view_bits::<Lsb0>();
+        let mask_bits = mask.view_bits::<Lsb0>();
         let updated = status.mstatus & !mask | value & mask;
         let updated_bits = updated.view_bits::<Lsb0>();
 
@@ -402,9 +408,6 @@ impl<A: Allocator, B: SystemBus<A>> Core<A, B> {
         if mask_bits[idx::TVM] {
             status.set_tvm(updated_bits[idx::TVM]);
         }
-        if mask_bits[idx::TVM] {
-            status.set_tvm(updated_bits[idx::TVM]);
-        }
         if mask_bits[idx::TW] {
             status.set_tw(updated_bits[idx::TW]);
         }
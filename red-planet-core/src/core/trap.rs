@@ -279,6 +279,7 @@ impl Trap {
 impl<A: Allocator, B: SystemBus<A>> Core<A, B> {
     pub(super) fn trap(&self, allocator: &mut A, cause: Cause) {
         debug!("Trapping for cause {cause:?}");
+        self.invalidate_reservation(allocator);
         let pc = self.registers(allocator).pc();
         let privilege_mode = self.privilege_mode(allocator);
         // Determine whether we are trapping into S-mode or M-mode.
@@ -307,8 +308,8 @@ impl<A: Allocator, B: SystemBus<A>> Core<A, B> {
         let tval = match cause {
             Cause::Exception(Some(exception)) => match exception {
                 Exception::IllegalInstruction(raw_instruction) => raw_instruction.unwrap_or(0),
-                Exception::Breakpoint => pc,
-                Exception::InstructionAddressMisaligned(vaddr)
+                Exception::Breakpoint(vaddr)
+                | Exception::InstructionAddressMisaligned(vaddr)
                 | Exception::InstructionAccessFault(vaddr)
                 | Exception::LoadAddressMisaligned(vaddr)
                 | Exception::StoreOrAmoAddressMisaligned(vaddr)
@@ -477,8 +478,9 @@ impl<A: Allocator, B: SystemBus<A>> Core<A, B> {
     }
 
     pub fn write_mepc(&self, allocator: &mut A, value: u32, mask: u32) -> CsrWriteResult {
+        let ialign_mask = self.instruction_alignment().as_power_of_two() - 1;
         let mepc = &mut self.trap.get_mut(allocator).mepc;
-        *mepc = *mepc & !mask | value & mask & !0b11;
+        *mepc = *mepc & !mask | value & mask & !ialign_mask;
         Ok(())
     }
 
@@ -487,8 +489,9 @@ impl<A: Allocator, B: SystemBus<A>> Core<A, B> {
     }
 
     pub fn write_sepc(&self, allocator: &mut A, value: u32, mask: u32) -> CsrWriteResult {
+        let ialign_mask = self.instruction_alignment().as_power_of_two() - 1;
         let sepc = &mut self.trap.get_mut(allocator).sepc;
-        *sepc = *sepc & !mask | value & mask & !0b11;
+        *sepc = *sepc & !mask | value & mask & !ialign_mask;
         Ok(())
     }
 
@@ -602,6 +605,9 @@ impl<A: Allocator, B: SystemBus<A>> Core<A, B> {
         };
         trap.satp_asid = value[22..31].load_le();
         trap.satp_ppn = value[..22].load_le();
+        if trap.satp_mode != SatpMode::Bare {
+            *self.paging_ever_enabled.get_mut(allocator) = true;
+        }
         Ok(())
     }
 }
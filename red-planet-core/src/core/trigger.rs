@@ -0,0 +1,548 @@
+//! Trigger module, as per the Sdtrig extension of the RISC-V debug specification.
+//!
+//! > Triggers can cause a breakpoint exception, entry into Debug Mode, or a trace action without
+//! > having to execute a special instruction. This makes them invaluable when debugging code from
+//! > ROM. They can trigger on execution of instructions at a given memory address, or on the
+//! > address/data in loads/stores.
+//!
+//! Only the mcontrol6 and icount trigger types are supported, and the only supported action is
+//! raising a breakpoint exception, since Debug Mode is not implemented.
+
+use bitvec::{field::BitField, order::Lsb0, view::BitView};
+use log::{debug, trace};
+use space_time::allocator::Allocator;
+
+use crate::system_bus::SystemBus;
+use crate::PrivilegeLevel;
+
+use super::{Core, CsrReadResult, CsrWriteResult, Exception, ExceptionCode, ExecutionResult};
+
+/// Trigger type of a disabled trigger, as stored in the type field of tdata1.
+pub const TYPE_DISABLED: u32 = 15;
+/// Trigger type of an instruction count trigger.
+pub const TYPE_ICOUNT: u32 = 3;
+/// Trigger type of an address/data match trigger.
+pub const TYPE_MCONTROL6: u32 = 6;
+
+/// Value of tinfo: version 1 (Sdtrig 1.0), supporting the icount, mcontrol6, and disabled types.
+const TINFO: u32 = 1 << 24 | 1 << TYPE_DISABLED | 1 << TYPE_MCONTROL6 | 1 << TYPE_ICOUNT;
+
+/// Kind of operation a trigger is matched against.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum TriggerAccess {
+    Execute,
+    Load,
+    Store,
+}
+
+/// Provides the tselect, tdata1, tdata2, tdata3, and tinfo registers.
+#[derive(Debug, Clone)]
+pub struct Triggers {
+    tselect: u32,
+    triggers: Vec<Trigger>,
+}
+
+#[derive(Debug, Clone)]
+struct Trigger {
+    tdata1: u32,
+    tdata2: u32,
+}
+
+impl Triggers {
+    /// Creates `count` triggers, all of which are disabled mcontrol6 triggers.
+    pub fn new(count: u8) -> Self {
+        Self {
+            tselect: 0,
+            triggers: vec![
+                Trigger {
+                    tdata1: TYPE_MCONTROL6 << 28,
+                    tdata2: 0,
+                };
+                count as usize
+            ],
+        }
+    }
+
+    pub fn tselect(&self) -> u32 {
+        self.tselect
+    }
+
+    /// > If this register is written with a value greater than or equal to the number of
+    /// > supported triggers, then the following tdata* register accesses will see a trigger of
+    /// > type 0 (no trigger).
+    ///
+    /// This implementation keeps the previous value instead, which is the other permitted option.
+    pub fn set_tselect(&mut self, value: u32) {
+        if (value as usize) < self.triggers.len() {
+            self.tselect = value;
+        } else {
+            debug!("Ignoring write of unsupported trigger index {value} to tselect");
+        }
+    }
+
+    pub fn tdata1(&self, index: usize) -> u32 {
+        self.triggers[index].tdata1
+    }
+
+    pub fn tdata2(&self, index: usize) -> u32 {
+        self.triggers[index].tdata2
+    }
+
+    /// Writes tdata1 of trigger `index`, legalizing the written value.
+    ///
+    /// Writes of an unsupported type disable the trigger. Unsupported field values are replaced by
+    /// zero, with the exception of match values, which fall back to 0 (equal).
+    pub fn set_tdata1(&mut self, index: usize, value: u32) {
+        let tdata1 = match value >> 28 {
+            TYPE_MCONTROL6 => TYPE_MCONTROL6 << 28 | legalize_mcontrol6(value),
+            TYPE_ICOUNT => TYPE_ICOUNT << 28 | legalize_icount(value),
+            _ => TYPE_DISABLED << 28,
+        };
+        trace!("Setting tdata1 of trigger {index} to {tdata1:#010x}");
+        self.triggers[index].tdata1 = tdata1;
+    }
+
+    pub fn set_tdata2(&mut self, index: usize, value: u32) {
+        trace!("Setting tdata2 of trigger {index} to {value:#010x}");
+        self.triggers[index].tdata2 = value;
+    }
+
+    /// Returns the index of the first mcontrol6 trigger that matches the given access, if any.
+    ///
+    /// If `data` is `None`, only triggers with select=0 (address) are considered, and `address` is
+    /// compared. Otherwise, only triggers with select=1 (data) are considered, and `data` is
+    /// compared.
+    pub fn match_mcontrol6(
+        &self,
+        privilege_level: PrivilegeLevel,
+        access: TriggerAccess,
+        address: u32,
+        data: Option<u32>,
+    ) -> Option<usize> {
+        self.triggers.iter().position(|trigger| {
+            if trigger.tdata1 >> 28 != TYPE_MCONTROL6 {
+                return false;
+            }
+            let bits = trigger.tdata1.view_bits::<Lsb0>();
+            let mode_enabled = match privilege_level {
+                PrivilegeLevel::Machine => bits[mcontrol6::M],
+                PrivilegeLevel::Supervisor => bits[mcontrol6::S],
+                PrivilegeLevel::User => bits[mcontrol6::U],
+            };
+            let access_enabled = match access {
+                TriggerAccess::Execute => bits[mcontrol6::EXECUTE],
+                TriggerAccess::Load => bits[mcontrol6::LOAD],
+                TriggerAccess::Store => bits[mcontrol6::STORE],
+            };
+            let value = match (bits[mcontrol6::SELECT], data) {
+                (false, None) => address,
+                (true, Some(data)) => data,
+                _ => return false,
+            };
+            mode_enabled
+                && access_enabled
+                && matches(bits[mcontrol6::MATCH].load_le(), value, trigger.tdata2)
+        })
+    }
+
+    /// Sets the hit0 bit of mcontrol6 trigger `index`.
+    pub fn set_hit(&mut self, index: usize) {
+        let tdata1 = self.triggers[index].tdata1.view_bits_mut::<Lsb0>();
+        match tdata1[28..32].load_le::<u32>() {
+            TYPE_MCONTROL6 => tdata1.set(mcontrol6::HIT0, true),
+            TYPE_ICOUNT => tdata1.set(icount::HIT, true),
+            _ => {}
+        }
+    }
+
+    /// Returns the index of the first icount trigger that is pending and enabled in
+    /// `privilege_level`, if any.
+    ///
+    /// > When pending is set, the trigger fires just before any further instructions are executed
+    /// > in a mode where the trigger is enabled.
+    pub fn pending_icount(&self, privilege_level: PrivilegeLevel) -> Option<usize> {
+        self.triggers.iter().position(|trigger| {
+            let bits = trigger.tdata1.view_bits::<Lsb0>();
+            trigger.tdata1 >> 28 == TYPE_ICOUNT
+                && bits[icount::PENDING]
+                && icount_mode_enabled(trigger.tdata1, privilege_level)
+        })
+    }
+
+    /// Clears the pending bit of icount trigger `index`.
+    pub fn clear_pending(&mut self, index: usize) {
+        let tdata1 = self.triggers[index].tdata1.view_bits_mut::<Lsb0>();
+        tdata1.set(icount::PENDING, false);
+    }
+
+    /// Returns `true` if any icount trigger is counting instructions in `privilege_level`.
+    pub fn icount_active(&self, privilege_level: PrivilegeLevel) -> bool {
+        self.triggers.iter().any(|trigger| {
+            trigger.tdata1 >> 28 == TYPE_ICOUNT
+                && trigger.tdata1.view_bits::<Lsb0>()[icount::COUNT].load_le::<u32>() > 0
+                && icount_mode_enabled(trigger.tdata1, privilege_level)
+        })
+    }
+
+    /// Counts an instruction retired in `privilege_level` for all enabled icount triggers.
+    ///
+    /// > When count is greater than 1 and the trigger matches, then count is decremented by 1.
+    /// >
+    /// > When count is 1 and the trigger matches, then pending becomes set. In addition count will
+    /// > become 0 unless it is hard-wired to 1.
+    pub fn count_instruction(&mut self, privilege_level: PrivilegeLevel) {
+        for trigger in &mut self.triggers {
+            if trigger.tdata1 >> 28 != TYPE_ICOUNT
+                || !icount_mode_enabled(trigger.tdata1, privilege_level)
+            {
+                continue;
+            }
+            let bits = trigger.tdata1.view_bits_mut::<Lsb0>();
+            let count = bits[icount::COUNT].load_le::<u32>();
+            if count == 0 {
+                continue;
+            }
+            bits[icount::COUNT].store_le(count - 1);
+            if count == 1 {
+                bits.set(icount::PENDING, true);
+            }
+        }
+    }
+}
+
+/// Returns `true` if `value` matches `tdata2` according to the mcontrol6 `match_type`.
+fn matches(match_type: u32, value: u32, tdata2: u32) -> bool {
+    match match_type {
+        // > Matches when any compare value equals tdata2.
+        0 => value == tdata2,
+        // > Matches when the top M bits of any compare value match the top M bits of tdata2. M is
+        // > XLEN − 1 minus the index of the least-significant bit containing 0 in tdata2.
+        1 => {
+            let mask = u32::MAX
+                .checked_shl(tdata2.trailing_ones() + 1)
+                .unwrap_or(0);
+            value & mask == tdata2 & mask
+        }
+        // > Matches when any compare value is greater than (unsigned) or equal to tdata2.
+        2 => value >= tdata2,
+        // > Matches when any compare value is less than (unsigned) tdata2.
+        3 => value < tdata2,
+        // > Matches when ((any compare value[XLEN/2 − 1 : 0]) & tdata2[XLEN − 1 : XLEN/2]) ==
+        // > tdata2[XLEN/2 − 1 : 0].
+        4 => value & (tdata2 >> 16) & 0xFFFF == tdata2 & 0xFFFF,
+        // > Matches when ((any compare value[XLEN − 1 : XLEN/2]) & tdata2[XLEN − 1 : XLEN/2]) ==
+        // > tdata2[XLEN/2 − 1 : 0].
+        5 => (value >> 16) & (tdata2 >> 16) == tdata2 & 0xFFFF,
+        // > This match type is the negation of the match type with bit 3 cleared.
+        8 | 9 | 12 | 13 => !matches(match_type & 0b0111, value, tdata2),
+        _ => false,
+    }
+}
+
+fn icount_mode_enabled(tdata1: u32, privilege_level: PrivilegeLevel) -> bool {
+    let bits = tdata1.view_bits::<Lsb0>();
+    match privilege_level {
+        PrivilegeLevel::Machine => bits[icount::M],
+        PrivilegeLevel::Supervisor => bits[icount::S],
+        PrivilegeLevel::User => bits[icount::U],
+    }
+}
+
+/// Keeps the supported mcontrol6 fields of `value`: hit0, select, match, m, s, u, execute,
+/// store, and load. Size, action, and chain are hardwired to zero.
+fn legalize_mcontrol6(value: u32) -> u32 {
+    let mut result = 0u32;
+    let bits = value.view_bits::<Lsb0>();
+    let result_bits = result.view_bits_mut::<Lsb0>();
+    for idx in [
+        mcontrol6::HIT0,
+        mcontrol6::SELECT,
+        mcontrol6::M,
+        mcontrol6::S,
+        mcontrol6::U,
+        mcontrol6::EXECUTE,
+        mcontrol6::STORE,
+        mcontrol6::LOAD,
+    ] {
+        result_bits.set(idx, bits[idx]);
+    }
+    let match_type = match bits[mcontrol6::MATCH].load_le::<u32>() {
+        match_type @ (0..=5 | 8 | 9 | 12 | 13) => match_type,
+        _ => 0,
+    };
+    result_bits[mcontrol6::MATCH].store_le(match_type);
+    result
+}
+
+/// Keeps the supported icount fields of `value`: hit, count, m, pending, s, and u. Action is
+/// hardwired to zero.
+fn legalize_icount(value: u32) -> u32 {
+    let mut result = 0u32;
+    let bits = value.view_bits::<Lsb0>();
+    let result_bits = result.view_bits_mut::<Lsb0>();
+    for idx in [
+        icount::HIT,
+        icount::M,
+        icount::PENDING,
+        icount::S,
+        icount::U,
+    ] {
+        result_bits.set(idx, bits[idx]);
+    }
+    result_bits[icount::COUNT].store_le(bits[icount::COUNT].load_le::<u32>());
+    result
+}
+
+/// Bit indices for the fields of tdata1 when it holds an mcontrol6 trigger.
+mod mcontrol6 {
+    use std::ops::Range;
+
+    pub const HIT0: usize = 22;
+    pub const SELECT: usize = 21;
+    pub const MATCH: Range<usize> = 7..11;
+    pub const M: usize = 6;
+    pub const S: usize = 4;
+    pub const U: usize = 3;
+    pub const EXECUTE: usize = 2;
+    pub const STORE: usize = 1;
+    pub const LOAD: usize = 0;
+}
+
+/// Bit indices for the fields of tdata1 when it holds an icount trigger.
+mod icount {
+    use std::ops::Range;
+
+    pub const HIT: usize = 24;
+    pub const COUNT: Range<usize> = 10..24;
+    pub const M: usize = 9;
+    pub const PENDING: usize = 8;
+    pub const S: usize = 7;
+    pub const U: usize = 6;
+}
+
+impl<A: Allocator, B: SystemBus<A>> Core<A, B> {
+    pub fn read_tselect(&self, allocator: &mut A) -> CsrReadResult {
+        Ok(self.triggers.get(allocator).tselect())
+    }
+
+    pub fn write_tselect(&self, allocator: &mut A, value: u32, mask: u32) -> CsrWriteResult {
+        let triggers = self.triggers.get_mut(allocator);
+        triggers.set_tselect(triggers.tselect() & !mask | value & mask);
+        Ok(())
+    }
+
+    pub fn read_tdata1(&self, allocator: &mut A) -> CsrReadResult {
+        let triggers = self.triggers.get(allocator);
+        Ok(triggers.tdata1(triggers.tselect() as usize))
+    }
+
+    pub fn write_tdata1(&self, allocator: &mut A, value: u32, mask: u32) -> CsrWriteResult {
+        let triggers = self.triggers.get_mut(allocator);
+        let index = triggers.tselect() as usize;
+        triggers.set_tdata1(index, triggers.tdata1(index) & !mask | value & mask);
+        Ok(())
+    }
+
+    pub fn read_tdata2(&self, allocator: &mut A) -> CsrReadResult {
+        let triggers = self.triggers.get(allocator);
+        Ok(triggers.tdata2(triggers.tselect() as usize))
+    }
+
+    pub fn write_tdata2(&self, allocator: &mut A, value: u32, mask: u32) -> CsrWriteResult {
+        let triggers = self.triggers.get_mut(allocator);
+        let index = triggers.tselect() as usize;
+        triggers.set_tdata2(index, triggers.tdata2(index) & !mask | value & mask);
+        Ok(())
+    }
+
+    /// None of the supported trigger types use tdata3, so it is hardwired to zero.
+    pub fn read_tdata3(&self, _allocator: &mut A) -> CsrReadResult {
+        Ok(0)
+    }
+
+    pub fn write_tdata3(&self, _allocator: &mut A, _value: u32, _mask: u32) -> CsrWriteResult {
+        Ok(())
+    }
+
+    pub fn read_tinfo(&self, _allocator: &mut A) -> CsrReadResult {
+        Ok(TINFO)
+    }
+
+    /// Raises a breakpoint exception if an mcontrol6 trigger matches the given access.
+    ///
+    /// See [`Triggers::match_mcontrol6`] for the meaning of `address` and `data`.
+    pub(super) fn check_triggers(
+        &self,
+        allocator: &mut A,
+        access: TriggerAccess,
+        address: u32,
+        data: Option<u32>,
+    ) -> ExecutionResult {
+        if self.config.trigger_count == 0 || self.triggers_suppressed(allocator) {
+            return Ok(());
+        }
+        let privilege_level = match access {
+            TriggerAccess::Execute => self.privilege_mode(allocator),
+            TriggerAccess::Load | TriggerAccess::Store => self.effective_privilege_mode(allocator),
+        };
+        let triggers = self.triggers.get(allocator);
+        match triggers.match_mcontrol6(privilege_level, access, address, data) {
+            Some(index) => {
+                debug!("Trigger {index} fired on {access:?} at {address:#010x}");
+                self.triggers.get_mut(allocator).set_hit(index);
+                Err(Exception::Breakpoint(address))
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Raises a breakpoint exception if an icount trigger is pending in the current privilege
+    /// mode.
+    pub(super) fn check_icount_triggers(&self, allocator: &mut A) -> ExecutionResult {
+        if self.config.trigger_count == 0 || self.triggers_suppressed(allocator) {
+            return Ok(());
+        }
+        let privilege_level = self.privilege_mode(allocator);
+        match self.triggers.get(allocator).pending_icount(privilege_level) {
+            Some(index) => {
+                debug!("Instruction count trigger {index} fired");
+                let triggers = self.triggers.get_mut(allocator);
+                triggers.clear_pending(index);
+                triggers.set_hit(index);
+                Err(Exception::Breakpoint(self.registers(allocator).pc()))
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Returns `true` if triggers must not fire, so that a trigger does not fire again in the
+    /// handler of the breakpoint exception it raised.
+    ///
+    /// Since tcontrol is not implemented, this uses the other mechanism the Sdtrig extension
+    /// allows: triggers do not fire in M-mode while mstatus.MIE is 0, and not in S-mode while
+    /// sstatus.SIE is 0 if breakpoint exceptions are delegated to S-mode.
+    fn triggers_suppressed(&self, allocator: &A) -> bool {
+        let status = self.status.get(allocator);
+        match self.privilege_mode(allocator) {
+            PrivilegeLevel::Machine => !status.mie(),
+            PrivilegeLevel::Supervisor => {
+                !status.sie()
+                    && (self.trap.get(allocator))
+                        .should_delegate_exception(ExceptionCode::Breakpoint)
+            }
+            PrivilegeLevel::User => false,
+        }
+    }
+
+    /// Updates the icount triggers after an instruction retired in `privilege_level`.
+    pub(super) fn count_instruction_for_triggers(
+        &self,
+        allocator: &mut A,
+        privilege_level: PrivilegeLevel,
+    ) {
+        // Avoid touching the allocated state in the common case where no icount trigger is active.
+        if self.triggers.get(allocator).icount_active(privilege_level) {
+            self.triggers
+                .get_mut(allocator)
+                .count_instruction(privilege_level);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const M: u32 = 1 << 6;
+    const U: u32 = 1 << 3;
+    const EXECUTE: u32 = 1 << 2;
+    const STORE: u32 = 1 << 1;
+    const LOAD: u32 = 1 << 0;
+    const SELECT: u32 = 1 << 21;
+
+    fn mcontrol6(fields: u32, match_type: u32) -> u32 {
+        TYPE_MCONTROL6 << 28 | match_type << 7 | fields
+    }
+
+    #[test]
+    fn test_tdata1_legalize() {
+        let mut triggers = Triggers::new(2);
+        triggers.set_tdata1(0, 0x2000_0000);
+        assert_eq!(TYPE_DISABLED << 28, triggers.tdata1(0));
+        // Action, chain, and size are hardwired to zero, reserved match values become 0.
+        triggers.set_tdata1(1, mcontrol6(0x0007_F800 | M | LOAD, 7));
+        assert_eq!(mcontrol6(M | LOAD, 0), triggers.tdata1(1));
+        triggers.set_tselect(2);
+        assert_eq!(0, triggers.tselect());
+        triggers.set_tselect(1);
+        assert_eq!(1, triggers.tselect());
+    }
+
+    #[test]
+    fn test_match_types() {
+        assert!(matches(0, 0x1234, 0x1234));
+        assert!(!matches(8, 0x1234, 0x1234));
+        // NAPOT 0x1000..0x1100
+        assert!(matches(1, 0x10FF, 0x107F));
+        assert!(!matches(1, 0x1100, 0x107F));
+        assert!(matches(2, 0x1000, 0x1000));
+        assert!(!matches(3, 0x1000, 0x1000));
+        // Low half masked with 0xFF00 must equal 0x1200.
+        assert!(matches(4, 0xABCD_12EF, 0xFF00_1200));
+        assert!(matches(5, 0x12EF_ABCD, 0xFF00_1200));
+        assert!(matches(13, 0x13EF_ABCD, 0xFF00_1200));
+    }
+
+    #[test]
+    fn test_match_mcontrol6() {
+        let mut triggers = Triggers::new(2);
+        triggers.set_tdata1(0, mcontrol6(U | STORE | LOAD, 0));
+        triggers.set_tdata2(0, 0x8000_0000);
+        triggers.set_tdata1(1, mcontrol6(M | EXECUTE | SELECT, 0));
+        triggers.set_tdata2(1, 0x0010_0073);
+        use PrivilegeLevel::{Machine, User};
+        let store = TriggerAccess::Store;
+        assert_eq!(
+            Some(0),
+            triggers.match_mcontrol6(User, store, 0x8000_0000, None)
+        );
+        assert_eq!(
+            None,
+            triggers.match_mcontrol6(Machine, store, 0x8000_0000, None)
+        );
+        assert_eq!(
+            None,
+            triggers.match_mcontrol6(User, TriggerAccess::Execute, 0x8000_0000, None)
+        );
+        // Data triggers only match data.
+        let execute = TriggerAccess::Execute;
+        assert_eq!(
+            None,
+            triggers.match_mcontrol6(Machine, execute, 0x0010_0073, None)
+        );
+        assert_eq!(
+            Some(1),
+            triggers.match_mcontrol6(Machine, execute, 0, Some(0x0010_0073))
+        );
+        triggers.set_hit(1);
+        assert_ne!(0, triggers.tdata1(1) & 1 << 22);
+    }
+
+    #[test]
+    fn test_icount() {
+        let mut triggers = Triggers::new(1);
+        triggers.set_tdata1(0, TYPE_ICOUNT << 28 | 2 << 10 | 1 << 6);
+        assert!(triggers.icount_active(PrivilegeLevel::User));
+        assert!(!triggers.icount_active(PrivilegeLevel::Machine));
+        triggers.count_instruction(PrivilegeLevel::Machine);
+        triggers.count_instruction(PrivilegeLevel::User);
+        assert_eq!(None, triggers.pending_icount(PrivilegeLevel::User));
+        triggers.count_instruction(PrivilegeLevel::User);
+        assert_eq!(None, triggers.pending_icount(PrivilegeLevel::Machine));
+        assert_eq!(Some(0), triggers.pending_icount(PrivilegeLevel::User));
+        assert!(!triggers.icount_active(PrivilegeLevel::User));
+        triggers.clear_pending(0);
+        triggers.set_hit(0);
+        assert_eq!(TYPE_ICOUNT << 28 | 1 << 24 | 1 << 6, triggers.tdata1(0));
+    }
+}
@@ -0,0 +1,142 @@
+//! Debugger watchpoints on the data accesses of a hart.
+//!
+//! Unlike the triggers of the Sdtrig extension, watchpoints are invisible to the hart. They are
+//! state of the debugger rather than of the simulated system, so they are stored outside of the
+//! allocator and are unaffected by going back and forth in time.
+
+use log::debug;
+use space_time::allocator::Allocator;
+
+use crate::system_bus::SystemBus;
+
+use super::Core;
+
+/// Kind of data access a watchpoint fires on.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum WatchKind {
+    Read,
+    Write,
+    /// Both reads and writes.
+    Access,
+}
+
+impl WatchKind {
+    fn matches(self, write: bool) -> bool {
+        match self {
+            WatchKind::Read => !write,
+            WatchKind::Write => write,
+            WatchKind::Access => true,
+        }
+    }
+}
+
+/// A watchpoint on the `len` bytes starting at virtual address `address`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct Watchpoint {
+    pub address: u32,
+    pub len: u32,
+    pub kind: WatchKind,
+}
+
+impl Watchpoint {
+    /// Returns the first watched address in the access of `size` bytes at `address`, if any.
+    fn first_overlap(&self, address: u32, size: u32) -> Option<u32> {
+        let start = address.max(self.address);
+        let end = address
+            .wrapping_add(size)
+            .wrapping_sub(1)
+            .min(self.address.wrapping_add(self.len).wrapping_sub(1));
+        (start <= end).then_some(start)
+    }
+}
+
+/// A data access that matched a [`Watchpoint`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct WatchpointHit {
+    pub watchpoint: Watchpoint,
+    /// First accessed byte that is watched by `watchpoint`.
+    pub address: u32,
+    /// `true` if the access was a write.
+    pub write: bool,
+}
+
+/// Watchpoints of a single hart, together with the first hit that has not been taken yet.
+#[derive(Debug, Clone, Default)]
+pub(super) struct Watchpoints {
+    watchpoints: Vec<Watchpoint>,
+    hit: Option<WatchpointHit>,
+}
+
+impl<A: Allocator, B: SystemBus<A>> Core<A, B> {
+    /// Watch the data accesses of this hart. Hits can be retrieved with
+    /// [`take_watchpoint_hit`](Self::take_watchpoint_hit).
+    ///
+    /// Only loads and stores performed by instructions (including LR/SC and AMOs) are watched.
+    /// Instruction fetches, implicit page-table accesses and debug accesses are not.
+    pub fn add_watchpoint(&self, watchpoint: Watchpoint) {
+        self.watchpoints.borrow_mut().watchpoints.push(watchpoint);
+    }
+
+    /// Remove a watchpoint added by [`add_watchpoint`](Self::add_watchpoint). Returns `false` if
+    /// there was no such watchpoint.
+    pub fn remove_watchpoint(&self, watchpoint: Watchpoint) -> bool {
+        let watchpoints = &mut self.watchpoints.borrow_mut().watchpoints;
+        match watchpoints.iter().position(|w| *w == watchpoint) {
+            Some(index) => {
+                watchpoints.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns the first watchpoint hit since the last call, and clears it.
+    pub fn take_watchpoint_hit(&self) -> Option<WatchpointHit> {
+        self.watchpoints.borrow_mut().hit.take()
+    }
+
+    /// Record a hit if a data access of `size` bytes at virtual `address` matches a watchpoint.
+    pub(super) fn check_watchpoints(&self, address: u32, size: usize, write: bool) {
+        let mut watchpoints = self.watchpoints.borrow_mut();
+        if watchpoints.hit.is_some() {
+            return;
+        }
+        let hit = watchpoints.watchpoints.iter().find_map(|watchpoint| {
+            if !watchpoint.kind.matches(write) {
+                return None;
+            }
+            let address = watchpoint.first_overlap(address, size as u32)?;
+            Some(WatchpointHit {
+                watchpoint: *watchpoint,
+                address,
+                write,
+            })
+        });
+        if let Some(hit) = hit {
+            debug!(
+                "Watchpoint hit on {address:#010x} by hart {}",
+                self.hart_id()
+            );
+            watchpoints.hit = Some(hit);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overlap() {
+        let watchpoint = Watchpoint {
+            address: 0x100,
+            len: 4,
+            kind: WatchKind::Write,
+        };
+        assert_eq!(watchpoint.first_overlap(0x100, 4), Some(0x100));
+        assert_eq!(watchpoint.first_overlap(0xFE, 4), Some(0x100));
+        assert_eq!(watchpoint.first_overlap(0x103, 8), Some(0x103));
+        assert_eq!(watchpoint.first_overlap(0xFC, 4), None);
+        assert_eq!(watchpoint.first_overlap(0x104, 1), None);
+    }
+}
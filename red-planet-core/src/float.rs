@@ -0,0 +1,1058 @@
+//! Software implementation of IEEE 754 binary floating-point arithmetic, as needed by the F and D
+//! extensions.
+//!
+//! The host's floating-point unit is deliberately not used: it cannot be told which rounding mode
+//! to use, doesn't report the accrued exception flags, and the NaNs it produces differ between
+//! platforms. Doing everything on the raw bit patterns keeps execution bit-exact, which replaying
+//! history relies on.
+//!
+//! All values are passed around as the raw bits of the encoding, stored in the low bits of a `u64`.
+
+/// Rounding modes, as encoded in the `frm` CSR and in the `rm` field of instructions.
+///
+/// > | Rounding Mode | Mnemonic | Meaning                                                   |
+/// > |---------------|----------|-----------------------------------------------------------|
+/// > | 000           | RNE      | Round to Nearest, ties to Even                            |
+/// > | 001           | RTZ      | Round towards Zero                                        |
+/// > | 010           | RDN      | Round Down (towards −∞)                                   |
+/// > | 011           | RUP      | Round Up (towards +∞)                                     |
+/// > | 100           | RMM      | Round to Nearest, ties to Max Magnitude                   |
+/// > | 101           |          | Reserved for future use.                                  |
+/// > | 110           |          | Reserved for future use.                                  |
+/// > | 111           | DYN      | In instruction’s rm field, selects dynamic rounding mode; |
+/// > |               |          | In Rounding Mode register, reserved.                      |
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum RoundingMode {
+    Rne = 0b000,
+    Rtz = 0b001,
+    Rdn = 0b010,
+    Rup = 0b011,
+    Rmm = 0b100,
+}
+
+impl RoundingMode {
+    /// Convert a 3-bit value into a [`RoundingMode`], returning `None` for the reserved values and
+    /// for `DYN` (`0b111`).
+    /// Panics if the value doesn't fit in 3 bits (`0..=7`).
+    pub fn from_u3(value_u3: u8) -> Option<Self> {
+        match value_u3 {
+            0b000 => Some(Self::Rne),
+            0b001 => Some(Self::Rtz),
+            0b010 => Some(Self::Rdn),
+            0b011 => Some(Self::Rup),
+            0b100 => Some(Self::Rmm),
+            0b101..=0b111 => None,
+            _ => panic!("out of range u3 used"),
+        }
+    }
+}
+
+/// Bits of the accrued exception flags, as found in the `fflags` CSR.
+pub mod flags {
+    /// Invalid Operation
+    pub const NV: u8 = 1 << 4;
+    /// Divide by Zero
+    pub const DZ: u8 = 1 << 3;
+    /// Overflow
+    pub const OF: u8 = 1 << 2;
+    /// Underflow
+    pub const UF: u8 = 1 << 1;
+    /// Inexact
+    pub const NX: u8 = 1 << 0;
+    /// All flag bits.
+    pub const ALL: u8 = NV | DZ | OF | UF | NX;
+}
+
+/// An IEEE 754 binary interchange format.
+///
+/// All operations take the rounding mode to use, and OR any raised exception flags (see [`flags`])
+/// into `flags`. Whenever the result of an operation is a NaN, it is the canonical NaN:
+///
+/// > Except when otherwise stated, if the result of a floating-point operation is NaN, it is the
+/// > canonical NaN.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Format {
+    exponent_bits: u32,
+    fraction_bits: u32,
+}
+
+/// Classification of a value, as reported by `fclass`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Class {
+    Zero,
+    /// Value is `sig * 2^exp`, with `sig != 0`.
+    Finite {
+        exp: i32,
+        sig: u64,
+    },
+    Infinite,
+    Nan {
+        signaling: bool,
+    },
+}
+
+impl Format {
+    /// The 32-bit single-precision format used by the F extension.
+    pub const SINGLE: Self = Self {
+        exponent_bits: 8,
+        fraction_bits: 23,
+    };
+
+    /// The 64-bit double-precision format used by the D extension.
+    pub const DOUBLE: Self = Self {
+        exponent_bits: 11,
+        fraction_bits: 52,
+    };
+
+    fn bias(self) -> i32 {
+        (1 << (self.exponent_bits - 1)) - 1
+    }
+
+    fn sign_mask(self) -> u64 {
+        1 << (self.exponent_bits + self.fraction_bits)
+    }
+
+    fn fraction_mask(self) -> u64 {
+        (1 << self.fraction_bits) - 1
+    }
+
+    fn exponent_max(self) -> u64 {
+        (1 << self.exponent_bits) - 1
+    }
+
+    /// Returns the canonical NaN of this format.
+    ///
+    /// > The canonical NaN has a positive sign and all significand bits clear except the MSB, a.k.a.
+    /// > the quiet bit.
+    pub fn canonical_nan(self) -> u64 {
+        (self.exponent_max() << self.fraction_bits) | (1 << (self.fraction_bits - 1))
+    }
+
+    fn sign_bits(self, sign: bool) -> u64 {
+        if sign {
+            self.sign_mask()
+        } else {
+            0
+        }
+    }
+
+    fn zero(self, sign: bool) -> u64 {
+        self.sign_bits(sign)
+    }
+
+    fn infinity(self, sign: bool) -> u64 {
+        self.sign_bits(sign) | (self.exponent_max() << self.fraction_bits)
+    }
+
+    fn max_finite(self, sign: bool) -> u64 {
+        self.infinity(sign) - 1
+    }
+
+    fn sign(self, bits: u64) -> bool {
+        bits & self.sign_mask() != 0
+    }
+
+    fn class(self, bits: u64) -> Class {
+        let exponent = (bits >> self.fraction_bits) & self.exponent_max();
+        let fraction = bits & self.fraction_mask();
+        if exponent == self.exponent_max() {
+            if fraction == 0 {
+                Class::Infinite
+            } else {
+                Class::Nan {
+                    signaling: fraction >> (self.fraction_bits - 1) == 0,
+                }
+            }
+        } else if exponent == 0 {
+            if fraction == 0 {
+                Class::Zero
+            } else {
+                Class::Finite {
+                    exp: 1 - self.bias() - self.fraction_bits as i32,
+                    sig: fraction,
+                }
+            }
+        } else {
+            Class::Finite {
+                exp: exponent as i32 - self.bias() - self.fraction_bits as i32,
+                sig: fraction | (1 << self.fraction_bits),
+            }
+        }
+    }
+
+    /// Returns `true` if `bits` encodes a (quiet or signaling) NaN.
+    pub fn is_nan(self, bits: u64) -> bool {
+        matches!(self.class(bits), Class::Nan { .. })
+    }
+
+    /// Returns `true` if `bits` encodes a signaling NaN.
+    pub fn is_signaling_nan(self, bits: u64) -> bool {
+        matches!(self.class(bits), Class::Nan { signaling: true })
+    }
+
+    /// Raises the invalid operation flag if any of `operands` is a signaling NaN.
+    fn check_signaling(self, operands: &[u64], flags: &mut u8) {
+        if operands.iter().any(|&bits| self.is_signaling_nan(bits)) {
+            *flags |= flags::NV;
+        }
+    }
+
+    /// Rounds `sig * 2^exp` to this format and packs it. `sig` must be non-zero.
+    ///
+    /// Tininess is detected after rounding, as RISC-V requires.
+    fn round_pack(self, sign: bool, exp: i32, sig: u128, rm: RoundingMode, flags: &mut u8) -> u64 {
+        debug_assert_ne!(sig, 0);
+        let f = self.fraction_bits as i32;
+        let e_min = 1 - self.bias();
+        let e_max = self.bias();
+        // Exponent of the leading bit, such that the value lies in [2^e, 2^(e+1)).
+        let e = exp + (127 - sig.leading_zeros() as i32);
+        // Exponent of the least significant bit that is kept.
+        let lsb = e.max(e_min) - f;
+        let (mut m, inexact) = round_shift(sign, sig, lsb - exp, rm);
+        let tiny = if e < e_min - 1 {
+            true
+        } else if e == e_min - 1 {
+            // Tiny unless rounding with unbounded exponent range would carry up to 2^e_min.
+            let (unbounded, _) = round_shift(sign, sig, e - f - exp, rm);
+            unbounded >> (f + 1) == 0
+        } else {
+            false
+        };
+        let mut e_result = lsb + f;
+        if m >> (f + 1) != 0 {
+            // Rounding carried out, so m is exactly 2^(f+1).
+            m >>= 1;
+            e_result += 1;
+        }
+        if e_result > e_max {
+            *flags |= flags::OF | flags::NX;
+            let to_infinity = match rm {
+                RoundingMode::Rne | RoundingMode::Rmm => true,
+                RoundingMode::Rtz => false,
+                RoundingMode::Rdn => sign,
+                RoundingMode::Rup => !sign,
+            };
+            return if to_infinity {
+                self.infinity(sign)
+            } else {
+                self.max_finite(sign)
+            };
+        }
+        if inexact {
+            *flags |= flags::NX;
+            if tiny {
+                *flags |= flags::UF;
+            }
+        }
+        let m = m as u64;
+        let magnitude = if m >> f != 0 {
+            (((e_result + self.bias()) as u64) << f) | (m & self.fraction_mask())
+        } else {
+            m
+        };
+        self.sign_bits(sign) | magnitude
+    }
+
+    /// Returns the sum `a + b`.
+    pub fn add(self, a: u64, b: u64, rm: RoundingMode, flags: &mut u8) -> u64 {
+        self.add_signed(a, self.sign(a), b, self.sign(b), rm, flags)
+    }
+
+    /// Returns the difference `a - b`.
+    pub fn sub(self, a: u64, b: u64, rm: RoundingMode, flags: &mut u8) -> u64 {
+        self.add_signed(a, self.sign(a), b, !self.sign(b), rm, flags)
+    }
+
+    /// Adds `a` and `b`, using `sign_a` and `sign_b` as their signs instead of their sign bits.
+    fn add_signed(
+        self,
+        a: u64,
+        sign_a: bool,
+        b: u64,
+        sign_b: bool,
+        rm: RoundingMode,
+        flags: &mut u8,
+    ) -> u64 {
+        match (self.class(a), self.class(b)) {
+            (Class::Nan { .. }, _) | (_, Class::Nan { .. }) => {
+                self.check_signaling(&[a, b], flags);
+                self.canonical_nan()
+            }
+            (Class::Infinite, Class::Infinite) if sign_a != sign_b => {
+                *flags |= flags::NV;
+                self.canonical_nan()
+            }
+            (Class::Infinite, _) => self.infinity(sign_a),
+            (_, Class::Infinite) => self.infinity(sign_b),
+            (Class::Zero, Class::Zero) => self.zero(if sign_a == sign_b {
+                sign_a
+            } else {
+                rm == RoundingMode::Rdn
+            }),
+            (Class::Zero, Class::Finite { .. }) => self.sign_bits(sign_b) | (b & !self.sign_mask()),
+            (Class::Finite { .. }, Class::Zero) => self.sign_bits(sign_a) | (a & !self.sign_mask()),
+            (
+                Class::Finite {
+                    exp: exp_a,
+                    sig: sig_a,
+                },
+                Class::Finite {
+                    exp: exp_b,
+                    sig: sig_b,
+                },
+            ) => self.add_finite(
+                (sign_a, exp_a, sig_a as u128),
+                (sign_b, exp_b, sig_b as u128),
+                rm,
+                flags,
+            ),
+        }
+    }
+
+    /// Adds two non-zero finite values given as `(sign, exp, sig)` with `sig < 2^126`.
+    fn add_finite(
+        self,
+        a: (bool, i32, u128),
+        b: (bool, i32, u128),
+        rm: RoundingMode,
+        flags: &mut u8,
+    ) -> u64 {
+        let (sign_a, exp_a, sig_a) = normalize(a.0, a.1, a.2);
+        let (sign_b, exp_b, sig_b) = normalize(b.0, b.1, b.2);
+        let ((sign, exp, large), (_, small_exp, small)) = if (exp_a, sig_a) >= (exp_b, sig_b) {
+            ((sign_a, exp_a, sig_a), (sign_b, exp_b, sig_b))
+        } else {
+            ((sign_b, exp_b, sig_b), (sign_a, exp_a, sig_a))
+        };
+        let small = shift_right_jam(small, (exp - small_exp) as u32);
+        let sig = if sign_a == sign_b {
+            large + small
+        } else {
+            large - small
+        };
+        if sig == 0 {
+            return self.zero(rm == RoundingMode::Rdn);
+        }
+        self.round_pack(sign, exp, sig, rm, flags)
+    }
+
+    /// Returns the product `a * b`.
+    pub fn mul(self, a: u64, b: u64, rm: RoundingMode, flags: &mut u8) -> u64 {
+        let sign = self.sign(a) != self.sign(b);
+        match (self.class(a), self.class(b)) {
+            (Class::Nan { .. }, _) | (_, Class::Nan { .. }) => {
+                self.check_signaling(&[a, b], flags);
+                self.canonical_nan()
+            }
+            (Class::Infinite, Class::Zero) | (Class::Zero, Class::Infinite) => {
+                *flags |= flags::NV;
+                self.canonical_nan()
+            }
+            (Class::Infinite, _) | (_, Class::Infinite) => self.infinity(sign),
+            (Class::Zero, _) | (_, Class::Zero) => self.zero(sign),
+            (
+                Class::Finite {
+                    exp: exp_a,
+                    sig: sig_a,
+                },
+                Class::Finite {
+                    exp: exp_b,
+                    sig: sig_b,
+                },
+            ) => self.round_pack(
+                sign,
+                exp_a + exp_b,
+                sig_a as u128 * sig_b as u128,
+                rm,
+                flags,
+            ),
+        }
+    }
+
+    /// Returns the quotient `a / b`.
+    pub fn div(self, a: u64, b: u64, rm: RoundingMode, flags: &mut u8) -> u64 {
+        let sign = self.sign(a) != self.sign(b);
+        match (self.class(a), self.class(b)) {
+            (Class::Nan { .. }, _) | (_, Class::Nan { .. }) => {
+                self.check_signaling(&[a, b], flags);
+                self.canonical_nan()
+            }
+            (Class::Infinite, Class::Infinite) | (Class::Zero, Class::Zero) => {
+                *flags |= flags::NV;
+                self.canonical_nan()
+            }
+            (Class::Infinite, _) => self.infinity(sign),
+            (_, Class::Infinite) => self.zero(sign),
+            (_, Class::Zero) => {
+                *flags |= flags::DZ;
+                self.infinity(sign)
+            }
+            (Class::Zero, _) => self.zero(sign),
+            (
+                Class::Finite {
+                    exp: exp_a,
+                    sig: sig_a,
+                },
+                Class::Finite {
+                    exp: exp_b,
+                    sig: sig_b,
+                },
+            ) => {
+                // Put the dividend's leading bit at bit 126 and the divisor's at bit 63, so the
+                // quotient has at least 63 significant bits.
+                let shift_a = 126 - (63 - sig_a.leading_zeros() as i32);
+                let shift_b = 63 - (63 - sig_b.leading_zeros() as i32);
+                let dividend = (sig_a as u128) << shift_a;
+                let divisor = (sig_b as u128) << shift_b;
+                let quotient = dividend / divisor;
+                let sticky = !dividend.is_multiple_of(divisor) as u128;
+                let exp = (exp_a - shift_a) - (exp_b - shift_b);
+                self.round_pack(sign, exp, quotient | sticky, rm, flags)
+            }
+        }
+    }
+
+    /// Returns the square root of `a`.
+    pub fn sqrt(self, a: u64, rm: RoundingMode, flags: &mut u8) -> u64 {
+        match self.class(a) {
+            Class::Nan { .. } => {
+                self.check_signaling(&[a], flags);
+                self.canonical_nan()
+            }
+            Class::Zero => a,
+            _ if self.sign(a) => {
+                *flags |= flags::NV;
+                self.canonical_nan()
+            }
+            Class::Infinite => a,
+            Class::Finite { exp, sig } => {
+                // Put the leading bit at bit 124 or 125, such that the exponent becomes even.
+                let mut shift = 125 - (63 - sig.leading_zeros() as i32);
+                if (exp - shift) % 2 != 0 {
+                    shift -= 1;
+                }
+                let (root, remainder) = isqrt((sig as u128) << shift);
+                let sticky = (remainder != 0) as u128;
+                self.round_pack(false, (exp - shift) / 2, root | sticky, rm, flags)
+            }
+        }
+    }
+
+    /// Returns `±(a * b) ± c` computed with a single rounding, where the product is negated if
+    /// `negate_product` is set, and the addend if `negate_addend` is set.
+    #[allow(clippy::too_many_arguments)]
+    pub fn mul_add(
+        self,
+        a: u64,
+        b: u64,
+        c: u64,
+        negate_product: bool,
+        negate_addend: bool,
+        rm: RoundingMode,
+        flags: &mut u8,
+    ) -> u64 {
+        let sign_product = self.sign(a) ^ self.sign(b) ^ negate_product;
+        let sign_c = self.sign(c) != negate_addend;
+        let (class_a, class_b, class_c) = (self.class(a), self.class(b), self.class(c));
+        self.check_signaling(&[a, b, c], flags);
+        // > The fused multiply-add instructions must set the invalid operation exception flag when
+        // > the multiplicands are ∞ and zero, even when the addend is a quiet NaN.
+        if matches!(
+            (class_a, class_b),
+            (Class::Infinite, Class::Zero) | (Class::Zero, Class::Infinite)
+        ) {
+            *flags |= flags::NV;
+            return self.canonical_nan();
+        }
+        if [class_a, class_b, class_c]
+            .iter()
+            .any(|class| matches!(class, Class::Nan { .. }))
+        {
+            return self.canonical_nan();
+        }
+        match (class_a, class_b, class_c) {
+            (Class::Infinite, _, _) | (_, Class::Infinite, _) => {
+                if class_c == Class::Infinite && sign_c != sign_product {
+                    *flags |= flags::NV;
+                    self.canonical_nan()
+                } else {
+                    self.infinity(sign_product)
+                }
+            }
+            (_, _, Class::Infinite) => self.infinity(sign_c),
+            (Class::Zero, _, Class::Zero) | (_, Class::Zero, Class::Zero) => {
+                self.zero(if sign_product == sign_c {
+                    sign_c
+                } else {
+                    rm == RoundingMode::Rdn
+                })
+            }
+            (Class::Zero, _, _) | (_, Class::Zero, _) => {
+                self.sign_bits(sign_c) | (c & !self.sign_mask())
+            }
+            (
+                Class::Finite {
+                    exp: exp_a,
+                    sig: sig_a,
+                },
+                Class::Finite {
+                    exp: exp_b,
+                    sig: sig_b,
+                },
+                class_c,
+            ) => {
+                let product = (sign_product, exp_a + exp_b, sig_a as u128 * sig_b as u128);
+                match class_c {
+                    Class::Finite { exp, sig } => {
+                        self.add_finite(product, (sign_c, exp, sig as u128), rm, flags)
+                    }
+                    _ => self.round_pack(product.0, product.1, product.2, rm, flags),
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Converts `a` to a 32-bit integer, signed if `signed` is set, rounding with `rm`.
+    ///
+    /// Out of range inputs and NaNs saturate and raise the invalid operation flag:
+    ///
+    /// > If the rounded result is not representable in the destination format, it is clipped to
+    /// > the nearest value and the invalid flag is set.
+    /// >
+    /// > | Input         | `fcvt.w.s`  | `fcvt.wu.s` |
+    /// > |---------------|-------------|-------------|
+    /// > | −∞ or too low | −2^31       | 0           |
+    /// > | +∞ or too high| 2^31 − 1    | 2^32 − 1    |
+    /// > | NaN           | 2^31 − 1    | 2^32 − 1    |
+    pub fn to_int(self, a: u64, signed: bool, rm: RoundingMode, flags: &mut u8) -> u32 {
+        let sign = self.sign(a);
+        let (min, max) = if signed {
+            (i32::MIN as u32, i32::MAX as u32)
+        } else {
+            (u32::MIN, u32::MAX)
+        };
+        let saturate = |flags: &mut u8, negative: bool| {
+            *flags |= flags::NV;
+            if negative {
+                min
+            } else {
+                max
+            }
+        };
+        let (exp, sig) = match self.class(a) {
+            Class::Nan { .. } => return saturate(flags, false),
+            Class::Infinite => return saturate(flags, sign),
+            Class::Zero => return 0,
+            Class::Finite { exp, sig } => (exp, sig),
+        };
+        let (magnitude, inexact) = if exp > 32 {
+            return saturate(flags, sign);
+        } else if exp >= 0 {
+            ((sig as u128) << exp, false)
+        } else {
+            round_shift(sign, sig as u128, -exp, rm)
+        };
+        let result = match (signed, sign) {
+            (true, true) if magnitude <= 1 << 31 => (magnitude as u32).wrapping_neg(),
+            (true, false) if magnitude < 1 << 31 => magnitude as u32,
+            (false, true) if magnitude == 0 => 0,
+            (false, false) if magnitude <= u32::MAX as u128 => magnitude as u32,
+            _ => return saturate(flags, sign),
+        };
+        if inexact {
+            *flags |= flags::NX;
+        }
+        result
+    }
+
+    /// Converts the 32-bit integer `value` to this format, interpreting it as signed if `signed`
+    /// is set.
+    pub fn from_int(self, value: u32, signed: bool, rm: RoundingMode, flags: &mut u8) -> u64 {
+        let sign = signed && (value as i32) < 0;
+        let magnitude = if sign { value.wrapping_neg() } else { value };
+        if magnitude == 0 {
+            return self.zero(false);
+        }
+        self.round_pack(sign, 0, magnitude as u128, rm, flags)
+    }
+
+    /// Converts `a` from this format to the format `to`.
+    pub fn convert(self, to: Format, a: u64, rm: RoundingMode, flags: &mut u8) -> u64 {
+        let sign = self.sign(a);
+        match self.class(a) {
+            Class::Nan { .. } => {
+                self.check_signaling(&[a], flags);
+                to.canonical_nan()
+            }
+            Class::Infinite => to.infinity(sign),
+            Class::Zero => to.zero(sign),
+            Class::Finite { exp, sig } => to.round_pack(sign, exp, sig as u128, rm, flags),
+        }
+    }
+
+    /// Returns a key for `bits` (which must not be a NaN) that orders like the value it encodes,
+    /// with `-0` ordered before `+0` if `signed_zeros` is set, or equal to it otherwise.
+    fn order_key(self, bits: u64, signed_zeros: bool) -> i128 {
+        let magnitude = (bits & !self.sign_mask()) as i128;
+        match (self.sign(bits), signed_zeros) {
+            (false, _) => magnitude,
+            (true, false) => -magnitude,
+            (true, true) => -magnitude - 1,
+        }
+    }
+
+    /// Quiet equality comparison, which only signals on signaling NaN inputs.
+    pub fn eq(self, a: u64, b: u64, flags: &mut u8) -> bool {
+        if self.is_nan(a) || self.is_nan(b) {
+            self.check_signaling(&[a, b], flags);
+            return false;
+        }
+        self.order_key(a, false) == self.order_key(b, false)
+    }
+
+    /// Signaling less-than comparison, which signals on any NaN input.
+    pub fn lt(self, a: u64, b: u64, flags: &mut u8) -> bool {
+        if self.is_nan(a) || self.is_nan(b) {
+            *flags |= flags::NV;
+            return false;
+        }
+        self.order_key(a, false) < self.order_key(b, false)
+    }
+
+    /// Signaling less-than-or-equal comparison, which signals on any NaN input.
+    pub fn le(self, a: u64, b: u64, flags: &mut u8) -> bool {
+        if self.is_nan(a) || self.is_nan(b) {
+            *flags |= flags::NV;
+            return false;
+        }
+        self.order_key(a, false) <= self.order_key(b, false)
+    }
+
+    /// Returns the smaller of `a` and `b`, following the `minimumNumber` operation of IEEE
+    /// 754-2019.
+    ///
+    /// > For the purposes of these instructions only, the value −0.0 is considered to be less than
+    /// > the value +0.0. If both inputs are NaNs, the result is the canonical NaN. If only one
+    /// > operand is a NaN, the result is the non-NaN operand. Signaling NaN inputs set the invalid
+    /// > operation exception flag, even when the result is not NaN.
+    pub fn min(self, a: u64, b: u64, flags: &mut u8) -> u64 {
+        self.min_max(a, b, false, flags)
+    }
+
+    /// Returns the larger of `a` and `b`, following the `maximumNumber` operation of IEEE
+    /// 754-2019. See [`Self::min`].
+    pub fn max(self, a: u64, b: u64, flags: &mut u8) -> u64 {
+        self.min_max(a, b, true, flags)
+    }
+
+    fn min_max(self, a: u64, b: u64, max: bool, flags: &mut u8) -> u64 {
+        self.check_signaling(&[a, b], flags);
+        match (self.is_nan(a), self.is_nan(b)) {
+            (true, true) => self.canonical_nan(),
+            (true, false) => b,
+            (false, true) => a,
+            (false, false) => {
+                let a_smaller = self.order_key(a, true) < self.order_key(b, true);
+                if a_smaller != max {
+                    a
+                } else {
+                    b
+                }
+            }
+        }
+    }
+
+    /// Returns the 10-bit classification mask of `a`, as written by `fclass`.
+    ///
+    /// > | bit | Meaning                             |
+    /// > |-----|-------------------------------------|
+    /// > | 0   | rs1 is −∞.                          |
+    /// > | 1   | rs1 is a negative normal number.    |
+    /// > | 2   | rs1 is a negative subnormal number. |
+    /// > | 3   | rs1 is −0.                          |
+    /// > | 4   | rs1 is +0.                          |
+    /// > | 5   | rs1 is a positive subnormal number. |
+    /// > | 6   | rs1 is a positive normal number.    |
+    /// > | 7   | rs1 is +∞.                          |
+    /// > | 8   | rs1 is a signaling NaN.             |
+    /// > | 9   | rs1 is a quiet NaN.                 |
+    pub fn classify(self, a: u64) -> u32 {
+        let sign = self.sign(a);
+        let subnormal = (a >> self.fraction_bits) & self.exponent_max() == 0;
+        let bit = match self.class(a) {
+            Class::Nan { signaling: true } => 8,
+            Class::Nan { signaling: false } => 9,
+            Class::Infinite => 7,
+            Class::Finite { .. } if !subnormal => 6,
+            Class::Finite { .. } => 5,
+            Class::Zero => 4,
+        };
+        let bit = match (sign, bit) {
+            (true, 4..=7) => 7 - bit,
+            _ => bit,
+        };
+        1 << bit
+    }
+}
+
+/// Normalizes `sig * 2^exp` such that the leading bit of `sig` is at bit 125, which leaves room for
+/// an addition carry while keeping every bit of the operands.
+fn normalize(sign: bool, exp: i32, sig: u128) -> (bool, i32, u128) {
+    let shift = sig.leading_zeros() as i32 - 2;
+    (sign, exp - shift, sig << shift)
+}
+
+/// Shifts `value` right by `shift` bits, ORing all bits shifted out into the least significant bit.
+fn shift_right_jam(value: u128, shift: u32) -> u128 {
+    if shift == 0 {
+        value
+    } else if shift >= 128 {
+        (value != 0) as u128
+    } else {
+        (value >> shift) | ((value << (128 - shift) != 0) as u128)
+    }
+}
+
+/// Shifts `sig` right by `shift` bits (or left if `shift` is negative), rounding the result with
+/// `rm` for a value of the given sign. Also returns whether the result is inexact.
+fn round_shift(sign: bool, sig: u128, shift: i32, rm: RoundingMode) -> (u128, bool) {
+    if shift <= 0 {
+        return (sig << -shift, false);
+    }
+    let (kept, round, sticky) = match shift {
+        1..=127 => (
+            sig >> shift,
+            (sig >> (shift - 1)) & 1 != 0,
+            sig & ((1 << (shift - 1)) - 1) != 0,
+        ),
+        128 => (0, sig >> 127 != 0, sig << 1 != 0),
+        _ => (0, false, sig != 0),
+    };
+    let inexact = round || sticky;
+    let increment = match rm {
+        RoundingMode::Rne => round && (sticky || kept & 1 != 0),
+        RoundingMode::Rmm => round,
+        RoundingMode::Rtz => false,
+        RoundingMode::Rdn => sign && inexact,
+        RoundingMode::Rup => !sign && inexact,
+    };
+    (kept + increment as u128, inexact)
+}
+
+/// Returns the integer square root of `value`, together with the remainder.
+fn isqrt(value: u128) -> (u128, u128) {
+    let mut remainder = value;
+    let mut root = 0;
+    let mut bit = 1 << 126;
+    while bit > value {
+        bit >>= 2;
+    }
+    while bit != 0 {
+        if remainder >= root + bit {
+            remainder -= root + bit;
+            root = (root >> 1) + bit;
+        } else {
+            root >>= 1;
+        }
+        bit >>= 2;
+    }
+    (root, remainder)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const S: Format = Format::SINGLE;
+    const D: Format = Format::DOUBLE;
+
+    fn f(value: f32) -> u64 {
+        value.to_bits() as u64
+    }
+
+    fn d(value: f64) -> u64 {
+        value.to_bits()
+    }
+
+    #[test]
+    fn test_rounding_mode_from_u3() {
+        assert_eq!(Some(RoundingMode::Rne), RoundingMode::from_u3(0));
+        assert_eq!(Some(RoundingMode::Rmm), RoundingMode::from_u3(4));
+        assert_eq!(None, RoundingMode::from_u3(5));
+        assert_eq!(None, RoundingMode::from_u3(7));
+    }
+
+    #[test]
+    fn test_add_sub_matches_host() {
+        let values = [
+            0.0, -0.0, 1.0, -1.5, 3.25, 1e-40, -1e-45, 3.4e38, 1e10, 0.1, 7.0e-39, 16777217.0,
+        ];
+        for &a in &values {
+            for &b in &values {
+                let mut flags = 0;
+                assert_eq!(f(a + b), S.add(f(a), f(b), RoundingMode::Rne, &mut flags));
+                assert_eq!(f(a - b), S.sub(f(a), f(b), RoundingMode::Rne, &mut flags));
+                let (a, b) = (a as f64 * 1.1e300, b as f64);
+                assert_eq!(d(a + b), D.add(d(a), d(b), RoundingMode::Rne, &mut flags));
+            }
+        }
+    }
+
+    #[test]
+    fn test_mul_div_sqrt_matches_host() {
+        let values = [1.0, -2.5, 3.0, 1e-30, 7.0e-39, 3.0e38, 0.1, 12345.678, -0.0];
+        for &a in &values {
+            for &b in &values {
+                let mut flags = 0;
+                assert_eq!(f(a * b), S.mul(f(a), f(b), RoundingMode::Rne, &mut flags));
+                let (a64, b64) = (a as f64 / 3.0, b as f64 * 7.0);
+                assert_eq!(
+                    d(a64 * b64),
+                    D.mul(d(a64), d(b64), RoundingMode::Rne, &mut flags)
+                );
+                if b != 0.0 {
+                    assert_eq!(f(a / b), S.div(f(a), f(b), RoundingMode::Rne, &mut flags));
+                    assert_eq!(
+                        d(a64 / b64),
+                        D.div(d(a64), d(b64), RoundingMode::Rne, &mut flags)
+                    );
+                }
+            }
+            let mut flags = 0;
+            if a >= 0.0 {
+                assert_eq!(f(a.sqrt()), S.sqrt(f(a), RoundingMode::Rne, &mut flags));
+                let a = a as f64 * 3.3;
+                assert_eq!(d(a.sqrt()), D.sqrt(d(a), RoundingMode::Rne, &mut flags));
+            }
+        }
+    }
+
+    #[test]
+    fn test_mul_add_matches_host() {
+        let values = [1.0, -2.5, 3.0, 1e-30, 0.1, 12345.678, 1.0 + f64::EPSILON];
+        for &a in &values {
+            for &b in &values {
+                for &c in &values {
+                    let mut flags = 0;
+                    let result = D.mul_add(
+                        d(a),
+                        d(b),
+                        d(c),
+                        false,
+                        false,
+                        RoundingMode::Rne,
+                        &mut flags,
+                    );
+                    assert_eq!(d(a.mul_add(b, c)), result);
+                    let result =
+                        D.mul_add(d(a), d(b), d(c), true, true, RoundingMode::Rne, &mut flags);
+                    assert_eq!(d(-a.mul_add(b, c)), result);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_flags() {
+        let mut flags = 0;
+        S.add(f(1.0), f(2.0), RoundingMode::Rne, &mut flags);
+        assert_eq!(0, flags);
+        S.add(f(1.0), f(1e-10), RoundingMode::Rne, &mut flags);
+        assert_eq!(flags::NX, flags);
+
+        let mut flags = 0;
+        assert_eq!(
+            S.infinity(false),
+            S.mul(f(3e38), f(3e38), RoundingMode::Rne, &mut flags)
+        );
+        assert_eq!(flags::OF | flags::NX, flags);
+
+        let mut flags = 0;
+        assert_eq!(
+            S.max_finite(false),
+            S.mul(f(3e38), f(3e38), RoundingMode::Rtz, &mut flags)
+        );
+
+        let mut flags = 0;
+        S.mul(f(1e-30), f(1e-10), RoundingMode::Rne, &mut flags);
+        assert_eq!(flags::UF | flags::NX, flags);
+
+        let mut flags = 0;
+        assert_eq!(
+            S.infinity(true),
+            S.div(f(-1.0), f(0.0), RoundingMode::Rne, &mut flags)
+        );
+        assert_eq!(flags::DZ, flags);
+
+        let mut flags = 0;
+        assert_eq!(
+            S.canonical_nan(),
+            S.sqrt(f(-1.0), RoundingMode::Rne, &mut flags)
+        );
+        assert_eq!(flags::NV, flags);
+    }
+
+    #[test]
+    fn test_directed_rounding() {
+        let mut flags = 0;
+        let third = |rm| S.div(f(1.0), f(3.0), rm, &mut 0);
+        assert_eq!(0x3EAA_AAAB, third(RoundingMode::Rne));
+        assert_eq!(0x3EAA_AAAA, third(RoundingMode::Rtz));
+        assert_eq!(0x3EAA_AAAA, third(RoundingMode::Rdn));
+        assert_eq!(0x3EAA_AAAB, third(RoundingMode::Rup));
+        assert_eq!(
+            0xBEAA_AAAB,
+            S.div(f(-1.0), f(3.0), RoundingMode::Rdn, &mut 0)
+        );
+        assert_eq!(
+            f(-0.0),
+            S.sub(f(1.0), f(1.0), RoundingMode::Rdn, &mut flags)
+        );
+        assert_eq!(f(0.0), S.sub(f(1.0), f(1.0), RoundingMode::Rne, &mut flags));
+    }
+
+    #[test]
+    fn test_nan_handling() {
+        let snan = 0x7F80_0001;
+        let qnan = 0x7FC1_2345;
+        let mut flags = 0;
+        assert_eq!(
+            S.canonical_nan(),
+            S.add(qnan, f(1.0), RoundingMode::Rne, &mut flags)
+        );
+        assert_eq!(0, flags);
+        assert_eq!(
+            S.canonical_nan(),
+            S.add(snan, f(1.0), RoundingMode::Rne, &mut flags)
+        );
+        assert_eq!(flags::NV, flags);
+
+        let mut flags = 0;
+        let inf = S.infinity(false);
+        S.mul_add(
+            inf,
+            f(0.0),
+            qnan,
+            false,
+            false,
+            RoundingMode::Rne,
+            &mut flags,
+        );
+        assert_eq!(flags::NV, flags);
+
+        let mut flags = 0;
+        assert!(!S.eq(qnan, qnan, &mut flags));
+        assert_eq!(0, flags);
+        assert!(!S.lt(qnan, f(1.0), &mut flags));
+        assert_eq!(flags::NV, flags);
+
+        let mut flags = 0;
+        assert_eq!(f(1.0), S.min(qnan, f(1.0), &mut flags));
+        assert_eq!(0, flags);
+        assert_eq!(f(1.0), S.max(f(1.0), snan, &mut flags));
+        assert_eq!(flags::NV, flags);
+        assert_eq!(S.canonical_nan(), S.max(qnan, snan, &mut flags));
+    }
+
+    #[test]
+    fn test_min_max_signed_zero() {
+        let mut flags = 0;
+        assert_eq!(f(-0.0), S.min(f(0.0), f(-0.0), &mut flags));
+        assert_eq!(f(0.0), S.max(f(-0.0), f(0.0), &mut flags));
+        assert!(S.eq(f(0.0), f(-0.0), &mut flags));
+        assert!(!S.lt(f(-0.0), f(0.0), &mut flags));
+        assert_eq!(0, flags);
+    }
+
+    #[test]
+    fn test_int_conversions() {
+        let mut flags = 0;
+        assert_eq!(
+            -3i32 as u32,
+            S.to_int(f(-2.5), true, RoundingMode::Rdn, &mut flags)
+        );
+        assert_eq!(
+            -2i32 as u32,
+            S.to_int(f(-2.5), true, RoundingMode::Rne, &mut flags)
+        );
+        assert_eq!(3, D.to_int(d(2.5), false, RoundingMode::Rmm, &mut flags));
+        assert_eq!(flags::NX, flags);
+
+        let mut flags = 0;
+        assert_eq!(
+            i32::MAX as u32,
+            S.to_int(f(3e9), true, RoundingMode::Rne, &mut flags)
+        );
+        assert_eq!(
+            i32::MIN as u32,
+            D.to_int(d(-3e9), true, RoundingMode::Rne, &mut flags)
+        );
+        assert_eq!(0, S.to_int(f(-1.0), false, RoundingMode::Rne, &mut flags));
+        assert_eq!(
+            u32::MAX,
+            S.to_int(S.canonical_nan(), false, RoundingMode::Rne, &mut flags)
+        );
+        assert_eq!(flags::NV, flags);
+
+        let mut flags = 0;
+        assert_eq!(0, S.to_int(f(-0.25), false, RoundingMode::Rne, &mut flags));
+        assert_eq!(flags::NX, flags);
+        assert_eq!(
+            i32::MIN as u32,
+            D.to_int(d(-2147483648.0), true, RoundingMode::Rne, &mut flags)
+        );
+
+        let mut flags = 0;
+        assert_eq!(
+            d(-7.0),
+            D.from_int(-7i32 as u32, true, RoundingMode::Rne, &mut flags)
+        );
+        assert_eq!(
+            d(4294967295.0),
+            D.from_int(u32::MAX, false, RoundingMode::Rne, &mut flags)
+        );
+        assert_eq!(0, flags);
+        assert_eq!(
+            f(16777216.0),
+            S.from_int(16777217, true, RoundingMode::Rne, &mut flags)
+        );
+        assert_eq!(flags::NX, flags);
+    }
+
+    #[test]
+    fn test_format_conversions() {
+        let mut flags = 0;
+        assert_eq!(
+            d(0.1f32 as f64),
+            S.convert(D, f(0.1), RoundingMode::Rne, &mut flags)
+        );
+        assert_eq!(0, flags);
+        assert_eq!(f(0.1), D.convert(S, d(0.1), RoundingMode::Rne, &mut flags));
+        assert_eq!(flags::NX, flags);
+        assert_eq!(
+            D.canonical_nan(),
+            S.convert(D, 0x7FC1_2345, RoundingMode::Rne, &mut flags)
+        );
+        assert_eq!(
+            f(1e-45),
+            D.convert(S, d(1e-45), RoundingMode::Rne, &mut flags)
+        );
+    }
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(1 << 0, S.classify(S.infinity(true)));
+        assert_eq!(1 << 1, S.classify(f(-1.0)));
+        assert_eq!(1 << 2, S.classify(f(-1e-40)));
+        assert_eq!(1 << 3, S.classify(f(-0.0)));
+        assert_eq!(1 << 4, S.classify(f(0.0)));
+        assert_eq!(1 << 5, S.classify(f(1e-40)));
+        assert_eq!(1 << 6, S.classify(f(1.0)));
+        assert_eq!(1 << 7, S.classify(S.infinity(false)));
+        assert_eq!(1 << 8, S.classify(0x7F80_0001));
+        assert_eq!(1 << 9, S.classify(S.canonical_nan()));
+        assert_eq!(1 << 9, D.classify(d(f64::NAN)));
+    }
+}
@@ -0,0 +1,700 @@
+//! Formatting of decoded instructions as assembly, in the same syntax as GNU objdump.
+
+use std::fmt;
+
+use super::{
+    AmoOp, BranchCondition, CsrOp, FenceOrderCombination, FloatCompareOp, FloatOp, FloatPrecision,
+    FusedMultiplyAddOp, Instruction, LoadWidth, MinMaxOp, RegImmOp, RegRegOp, RegShiftImmOp,
+    RegUnaryOp, SignInjectOp, StoreWidth,
+};
+use crate::core::csr::{self, CsrSpecifier};
+use crate::float::RoundingMode;
+use crate::registers::Specifier;
+
+impl Instruction {
+    /// Returns a value that formats this instruction as assembly, as found at `address`.
+    ///
+    /// The output matches that of `objdump -d`: registers use their ABI names, pseudo-instructions
+    /// (such as `li`, `mv`, `ret` and `csrr`) are used where they apply, and jump and branch targets
+    /// are absolute addresses. The mnemonic is separated from the operands by a tab.
+    ///
+    /// objdump annotates targets with the symbol they fall in (e.g. `j 80000010 <main+0x8>`). No
+    /// symbols are known here, but [`Self::jump_target`] can be used to add them.
+    pub fn disassemble(&self, address: u32) -> Disassembly {
+        Disassembly {
+            instruction: *self,
+            address,
+        }
+    }
+
+    /// Returns the target address of this instruction if it is a branch, or a jump with a target
+    /// that does not depend on registers, assuming it is found at `address`.
+    pub fn jump_target(&self, address: u32) -> Option<u32> {
+        match *self {
+            Instruction::Jal { offset, .. } | Instruction::Branch { offset, .. } => {
+                Some(address.wrapping_add_signed(offset))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// An [`Instruction`] formatted as assembly, see [`Instruction::disassemble`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Disassembly {
+    instruction: Instruction,
+    address: u32,
+}
+
+impl fmt::Display for Disassembly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (mnemonic, operands) = self.parts();
+        write!(f, "{mnemonic}")?;
+        if !operands.is_empty() {
+            write!(f, "\t{}", operands.join(","))?;
+        }
+        Ok(())
+    }
+}
+
+impl Disassembly {
+    /// Returns the mnemonic and the formatted operands.
+    fn parts(&self) -> (String, Vec<String>) {
+        let x = |specifier: Specifier| specifier.abi_name().to_owned();
+        let f = |specifier: Specifier| specifier.float_abi_name().to_owned();
+        let memory = |offset: i32, base: Specifier| format!("{offset}({})", base.abi_name());
+        let target = |offset: i32| format!("{:x}", self.address.wrapping_add_signed(offset));
+        let zero = Specifier::X0;
+
+        let (mnemonic, operands): (&str, Vec<String>) = match self.instruction {
+            Instruction::OpImm {
+                op,
+                dest,
+                src,
+                immediate,
+            } => match (op, immediate) {
+                (RegImmOp::Addi, 0) if dest == zero && src == zero => ("nop", vec![]),
+                (RegImmOp::Addi, _) if src == zero => ("li", vec![x(dest), immediate.to_string()]),
+                (RegImmOp::Addi, 0) => ("mv", vec![x(dest), x(src)]),
+                (RegImmOp::Xori, -1) => ("not", vec![x(dest), x(src)]),
+                (RegImmOp::Sltiu, 1) => ("seqz", vec![x(dest), x(src)]),
+                _ => (op.mnemonic(), vec![x(dest), x(src), immediate.to_string()]),
+            },
+            Instruction::OpShiftImm {
+                op,
+                dest,
+                src,
+                shift_amount_u5,
+            } => (
+                op.mnemonic(),
+                vec![x(dest), x(src), format!("{shift_amount_u5:#x}")],
+            ),
+            Instruction::Auipc { dest, immediate } => (
+                "auipc",
+                vec![x(dest), format!("{:#x}", immediate as u32 >> 12)],
+            ),
+            Instruction::Lui { dest, immediate } => (
+                "lui",
+                vec![x(dest), format!("{:#x}", immediate as u32 >> 12)],
+            ),
+            Instruction::Amo {
+                op,
+                aq,
+                rl,
+                src,
+                addr,
+                dest,
+            } => {
+                let ordering = match (aq, rl) {
+                    (false, false) => "",
+                    (true, false) => ".aq",
+                    (false, true) => ".rl",
+                    (true, true) => ".aqrl",
+                };
+                let mnemonic = format!("{}{ordering}", op.mnemonic());
+                let addr = format!("({})", addr.abi_name());
+                let operands = match op {
+                    AmoOp::Lr => vec![x(dest), addr],
+                    _ => vec![x(dest), x(src), addr],
+                };
+                return (mnemonic, operands);
+            }
+            Instruction::Op {
+                op,
+                dest,
+                src1,
+                src2,
+            } => match op {
+                RegRegOp::Sub if src1 == zero => ("neg", vec![x(dest), x(src2)]),
+                RegRegOp::Sltu if src1 == zero => ("snez", vec![x(dest), x(src2)]),
+                RegRegOp::Slt if src2 == zero => ("sltz", vec![x(dest), x(src1)]),
+                RegRegOp::Slt if src1 == zero => ("sgtz", vec![x(dest), x(src2)]),
+                _ => (op.mnemonic(), vec![x(dest), x(src1), x(src2)]),
+            },
+            Instruction::OpUnary { op, dest, src } => (op.mnemonic(), vec![x(dest), x(src)]),
+            Instruction::Jal { dest, offset } => match dest {
+                Specifier::X0 => ("j", vec![target(offset)]),
+                Specifier::RA => ("jal", vec![target(offset)]),
+                _ => ("jal", vec![x(dest), target(offset)]),
+            },
+            Instruction::Jalr { dest, base, offset } => match (dest, offset) {
+                (Specifier::X0, 0) if base == Specifier::RA => ("ret", vec![]),
+                (Specifier::X0, 0) => ("jr", vec![x(base)]),
+                (Specifier::X0, _) => ("jr", vec![memory(offset, base)]),
+                (Specifier::RA, 0) => ("jalr", vec![x(base)]),
+                (Specifier::RA, _) => ("jalr", vec![memory(offset, base)]),
+                _ => ("jalr", vec![x(dest), memory(offset, base)]),
+            },
+            Instruction::Branch {
+                condition,
+                src1,
+                src2,
+                offset,
+            } => match condition {
+                BranchCondition::Beq if src2 == zero => ("beqz", vec![x(src1), target(offset)]),
+                BranchCondition::Bne if src2 == zero => ("bnez", vec![x(src1), target(offset)]),
+                BranchCondition::Blt if src2 == zero => ("bltz", vec![x(src1), target(offset)]),
+                BranchCondition::Bge if src2 == zero => ("bgez", vec![x(src1), target(offset)]),
+                BranchCondition::Blt if src1 == zero => ("bgtz", vec![x(src2), target(offset)]),
+                BranchCondition::Bge if src1 == zero => ("blez", vec![x(src2), target(offset)]),
+                _ => (condition.mnemonic(), vec![x(src1), x(src2), target(offset)]),
+            },
+            Instruction::Load {
+                width,
+                dest,
+                base,
+                offset,
+            } => (width.mnemonic(), vec![x(dest), memory(offset, base)]),
+            Instruction::Store {
+                width,
+                src,
+                base,
+                offset,
+            } => (width.mnemonic(), vec![x(src), memory(offset, base)]),
+            Instruction::Fence {
+                predecessor,
+                successor,
+            } => match (predecessor.to_string(), successor.to_string()) {
+                (predecessor, successor) if predecessor == "iorw" && successor == "iorw" => {
+                    ("fence", vec![])
+                }
+                (predecessor, successor) if predecessor == "w" && successor == "0" => {
+                    ("pause", vec![])
+                }
+                (predecessor, successor) => ("fence", vec![predecessor, successor]),
+            },
+            Instruction::FenceI => ("fence.i", vec![]),
+            Instruction::Ecall => ("ecall", vec![]),
+            Instruction::Ebreak => ("ebreak", vec![]),
+            Instruction::Sret => ("sret", vec![]),
+            Instruction::Mret => ("mret", vec![]),
+            Instruction::Wfi => ("wfi", vec![]),
+            Instruction::SfenceVma { vaddr, asid } => match (vaddr, asid) {
+                (Specifier::X0, Specifier::X0) => ("sfence.vma", vec![]),
+                (_, Specifier::X0) => ("sfence.vma", vec![x(vaddr)]),
+                _ => ("sfence.vma", vec![x(vaddr), x(asid)]),
+            },
+            Instruction::Csr { op, dest, csr, src } => {
+                let name = csr_name(csr);
+                match (op, csr) {
+                    (CsrOp::ReadSet, _) if src == zero => match read_alias(csr) {
+                        Some(alias) => (alias, vec![x(dest)]),
+                        None => ("csrr", vec![x(dest), name]),
+                    },
+                    (CsrOp::ReadWrite, csr::FCSR | csr::FRM | csr::FFLAGS) => {
+                        let alias = match csr {
+                            csr::FCSR => "fscsr",
+                            csr::FRM => "fsrm",
+                            _ => "fsflags",
+                        };
+                        match dest {
+                            Specifier::X0 => (alias, vec![x(src)]),
+                            _ => (alias, vec![x(dest), x(src)]),
+                        }
+                    }
+                    (CsrOp::ReadWrite, _) if dest == zero => ("csrw", vec![name, x(src)]),
+                    (CsrOp::ReadSet, _) if dest == zero => ("csrs", vec![name, x(src)]),
+                    (CsrOp::ReadClear, _) if dest == zero => ("csrc", vec![name, x(src)]),
+                    _ => (op.mnemonic(), vec![x(dest), name, x(src)]),
+                }
+            }
+            Instruction::Csri {
+                op,
+                dest,
+                csr,
+                immediate,
+            } => {
+                let name = csr_name(csr);
+                let immediate = immediate.to_string();
+                match (op, csr) {
+                    (CsrOp::ReadWrite, csr::FRM | csr::FFLAGS) => {
+                        let alias = match csr {
+                            csr::FRM => "fsrmi",
+                            _ => "fsflagsi",
+                        };
+                        match dest {
+                            Specifier::X0 => (alias, vec![immediate]),
+                            _ => (alias, vec![x(dest), immediate]),
+                        }
+                    }
+                    (CsrOp::ReadWrite, _) if dest == zero => ("csrwi", vec![name, immediate]),
+                    (CsrOp::ReadSet, _) if dest == zero => ("csrsi", vec![name, immediate]),
+                    (CsrOp::ReadClear, _) if dest == zero => ("csrci", vec![name, immediate]),
+                    _ => {
+                        let mnemonic = format!("{}i", op.mnemonic());
+                        return (mnemonic, vec![x(dest), name, immediate]);
+                    }
+                }
+            }
+            Instruction::LoadFp {
+                precision,
+                dest,
+                base,
+                offset,
+            } => {
+                let mnemonic = match precision {
+                    FloatPrecision::Single => "flw",
+                    FloatPrecision::Double => "fld",
+                };
+                (mnemonic, vec![f(dest), memory(offset, base)])
+            }
+            Instruction::StoreFp {
+                precision,
+                src,
+                base,
+                offset,
+            } => {
+                let mnemonic = match precision {
+                    FloatPrecision::Single => "fsw",
+                    FloatPrecision::Double => "fsd",
+                };
+                (mnemonic, vec![f(src), memory(offset, base)])
+            }
+            Instruction::FusedMultiplyAdd {
+                op,
+                precision,
+                rounding_mode,
+                dest,
+                src1,
+                src2,
+                src3,
+            } => {
+                let mnemonic = format!("{}{}", op.mnemonic(), precision.suffix());
+                let mut operands = vec![f(dest), f(src1), f(src2), f(src3)];
+                operands.extend(rounding_mode.map(rounding_mode_name));
+                return (mnemonic, operands);
+            }
+            Instruction::OpFp {
+                op,
+                precision,
+                rounding_mode,
+                dest,
+                src1,
+                src2,
+            } => {
+                let mnemonic = format!("{}{}", op.mnemonic(), precision.suffix());
+                let mut operands = vec![f(dest), f(src1), f(src2)];
+                operands.extend(rounding_mode.map(rounding_mode_name));
+                return (mnemonic, operands);
+            }
+            Instruction::FloatSqrt {
+                precision,
+                rounding_mode,
+                dest,
+                src,
+            } => {
+                let mnemonic = format!("fsqrt{}", precision.suffix());
+                let mut operands = vec![f(dest), f(src)];
+                operands.extend(rounding_mode.map(rounding_mode_name));
+                return (mnemonic, operands);
+            }
+            Instruction::FloatSignInject {
+                op,
+                precision,
+                dest,
+                src1,
+                src2,
+            } => {
+                let (mnemonic, operands) = match op {
+                    _ if src1 != src2 => (op.mnemonic(), vec![f(dest), f(src1), f(src2)]),
+                    SignInjectOp::Sgnj => ("fmv", vec![f(dest), f(src1)]),
+                    SignInjectOp::Sgnjn => ("fneg", vec![f(dest), f(src1)]),
+                    SignInjectOp::Sgnjx => ("fabs", vec![f(dest), f(src1)]),
+                };
+                return (format!("{mnemonic}{}", precision.suffix()), operands);
+            }
+            Instruction::FloatMinMax {
+                op,
+                precision,
+                dest,
+                src1,
+                src2,
+            } => {
+                let mnemonic = match op {
+                    MinMaxOp::Min => "fmin",
+                    MinMaxOp::Max => "fmax",
+                };
+                let mnemonic = format!("{mnemonic}{}", precision.suffix());
+                return (mnemonic, vec![f(dest), f(src1), f(src2)]);
+            }
+            Instruction::FloatCompare {
+                op,
+                precision,
+                dest,
+                src1,
+                src2,
+            } => {
+                let mnemonic = match op {
+                    FloatCompareOp::Eq => "feq",
+                    FloatCompareOp::Lt => "flt",
+                    FloatCompareOp::Le => "fle",
+                };
+                let mnemonic = format!("{mnemonic}{}", precision.suffix());
+                return (mnemonic, vec![x(dest), f(src1), f(src2)]);
+            }
+            Instruction::FloatClassify {
+                precision,
+                dest,
+                src,
+            } => {
+                let mnemonic = format!("fclass{}", precision.suffix());
+                return (mnemonic, vec![x(dest), f(src)]);
+            }
+            Instruction::FloatToInt {
+                signed,
+                precision,
+                rounding_mode,
+                dest,
+                src,
+            } => {
+                let int = if signed { ".w" } else { ".wu" };
+                let mnemonic = format!("fcvt{int}{}", precision.suffix());
+                let mut operands = vec![x(dest), f(src)];
+                operands.extend(rounding_mode.map(rounding_mode_name));
+                return (mnemonic, operands);
+            }
+            Instruction::IntToFloat {
+                signed,
+                precision,
+                rounding_mode,
+                dest,
+                src,
+            } => {
+                let int = if signed { ".w" } else { ".wu" };
+                let mnemonic = format!("fcvt{}{int}", precision.suffix());
+                let mut operands = vec![f(dest), x(src)];
+                operands.extend(rounding_mode.map(rounding_mode_name));
+                return (mnemonic, operands);
+            }
+            Instruction::FloatConvert {
+                from,
+                to,
+                rounding_mode,
+                dest,
+                src,
+            } => {
+                let mnemonic = format!("fcvt{}{}", to.suffix(), from.suffix());
+                let mut operands = vec![f(dest), f(src)];
+                operands.extend(rounding_mode.map(rounding_mode_name));
+                return (mnemonic, operands);
+            }
+            Instruction::FloatMoveToInt { dest, src } => ("fmv.x.w", vec![x(dest), f(src)]),
+            Instruction::FloatMoveFromInt { dest, src } => ("fmv.w.x", vec![f(dest), x(src)]),
+        };
+        (mnemonic.to_owned(), operands)
+    }
+}
+
+/// Returns the name of a CSR, or its number if it has no name.
+fn csr_name(specifier: CsrSpecifier) -> String {
+    match csr::name(specifier) {
+        Some(name) => name.to_owned(),
+        None => format!("{specifier:#x}"),
+    }
+}
+
+/// Returns the pseudo-instruction that reads `specifier`, if there is a dedicated one.
+fn read_alias(specifier: CsrSpecifier) -> Option<&'static str> {
+    match specifier {
+        csr::CYCLE => Some("rdcycle"),
+        csr::TIME => Some("rdtime"),
+        csr::INSTRET => Some("rdinstret"),
+        csr::CYCLEH => Some("rdcycleh"),
+        csr::TIMEH => Some("rdtimeh"),
+        csr::INSTRETH => Some("rdinstreth"),
+        csr::FCSR => Some("frcsr"),
+        csr::FRM => Some("frrm"),
+        csr::FFLAGS => Some("frflags"),
+        _ => None,
+    }
+}
+
+fn rounding_mode_name(rounding_mode: RoundingMode) -> String {
+    match rounding_mode {
+        RoundingMode::Rne => "rne",
+        RoundingMode::Rtz => "rtz",
+        RoundingMode::Rdn => "rdn",
+        RoundingMode::Rup => "rup",
+        RoundingMode::Rmm => "rmm",
+    }
+    .to_owned()
+}
+
+impl fmt::Display for FenceOrderCombination {
+    /// Formats the set as a subset of `iorw`, or `0` if it is empty.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let flags = [
+            (self.device_input, 'i'),
+            (self.device_output, 'o'),
+            (self.memory_reads, 'r'),
+            (self.memory_writes, 'w'),
+        ];
+        let mut empty = true;
+        for (_, flag) in flags.iter().filter(|(set, _)| *set) {
+            write!(f, "{flag}")?;
+            empty = false;
+        }
+        if empty {
+            write!(f, "0")?;
+        }
+        Ok(())
+    }
+}
+
+impl FloatPrecision {
+    fn suffix(self) -> &'static str {
+        match self {
+            FloatPrecision::Single => ".s",
+            FloatPrecision::Double => ".d",
+        }
+    }
+}
+
+impl RegImmOp {
+    fn mnemonic(self) -> &'static str {
+        match self {
+            RegImmOp::Addi => "addi",
+            RegImmOp::Slti => "slti",
+            RegImmOp::Sltiu => "sltiu",
+            RegImmOp::Xori => "xori",
+            RegImmOp::Ori => "ori",
+            RegImmOp::Andi => "andi",
+        }
+    }
+}
+
+impl RegShiftImmOp {
+    fn mnemonic(self) -> &'static str {
+        match self {
+            RegShiftImmOp::Slli => "slli",
+            RegShiftImmOp::Srli => "srli",
+            RegShiftImmOp::Srai => "srai",
+            RegShiftImmOp::Rori => "rori",
+            RegShiftImmOp::Bclri => "bclri",
+            RegShiftImmOp::Bexti => "bexti",
+            RegShiftImmOp::Binvi => "binvi",
+            RegShiftImmOp::Bseti => "bseti",
+        }
+    }
+}
+
+impl AmoOp {
+    fn mnemonic(self) -> &'static str {
+        match self {
+            AmoOp::Lr => "lr.w",
+            AmoOp::Sc => "sc.w",
+            AmoOp::Swap => "amoswap.w",
+            AmoOp::Add => "amoadd.w",
+            AmoOp::Xor => "amoxor.w",
+            AmoOp::And => "amoand.w",
+            AmoOp::Or => "amoor.w",
+            AmoOp::Min => "amomin.w",
+            AmoOp::Max => "amomax.w",
+            AmoOp::Minu => "amominu.w",
+            AmoOp::Maxu => "amomaxu.w",
+        }
+    }
+}
+
+impl RegRegOp {
+    fn mnemonic(self) -> &'static str {
+        match self {
+            RegRegOp::Add => "add",
+            RegRegOp::Slt => "slt",
+            RegRegOp::Sltu => "sltu",
+            RegRegOp::And => "and",
+            RegRegOp::Or => "or",
+            RegRegOp::Xor => "xor",
+            RegRegOp::Sll => "sll",
+            RegRegOp::Srl => "srl",
+            RegRegOp::Sub => "sub",
+            RegRegOp::Sra => "sra",
+            RegRegOp::Mul => "mul",
+            RegRegOp::Mulh => "mulh",
+            RegRegOp::Mulhsu => "mulhsu",
+            RegRegOp::Mulhu => "mulhu",
+            RegRegOp::Div => "div",
+            RegRegOp::Divu => "divu",
+            RegRegOp::Rem => "rem",
+            RegRegOp::Remu => "remu",
+            RegRegOp::Sh1add => "sh1add",
+            RegRegOp::Sh2add => "sh2add",
+            RegRegOp::Sh3add => "sh3add",
+            RegRegOp::Andn => "andn",
+            RegRegOp::Orn => "orn",
+            RegRegOp::Xnor => "xnor",
+            RegRegOp::Max => "max",
+            RegRegOp::Maxu => "maxu",
+            RegRegOp::Min => "min",
+            RegRegOp::Minu => "minu",
+            RegRegOp::Rol => "rol",
+            RegRegOp::Ror => "ror",
+            RegRegOp::Clmul => "clmul",
+            RegRegOp::Clmulh => "clmulh",
+            RegRegOp::Clmulr => "clmulr",
+            RegRegOp::Bclr => "bclr",
+            RegRegOp::Bext => "bext",
+            RegRegOp::Binv => "binv",
+            RegRegOp::Bset => "bset",
+        }
+    }
+}
+
+impl RegUnaryOp {
+    fn mnemonic(self) -> &'static str {
+        match self {
+            RegUnaryOp::Clz => "clz",
+            RegUnaryOp::Ctz => "ctz",
+            RegUnaryOp::Cpop => "cpop",
+            RegUnaryOp::SextB => "sext.b",
+            RegUnaryOp::SextH => "sext.h",
+            RegUnaryOp::ZextH => "zext.h",
+            RegUnaryOp::OrcB => "orc.b",
+            RegUnaryOp::Rev8 => "rev8",
+        }
+    }
+}
+
+impl BranchCondition {
+    fn mnemonic(self) -> &'static str {
+        match self {
+            BranchCondition::Beq => "beq",
+            BranchCondition::Bne => "bne",
+            BranchCondition::Blt => "blt",
+            BranchCondition::Bltu => "bltu",
+            BranchCondition::Bge => "bge",
+            BranchCondition::Bgeu => "bgeu",
+        }
+    }
+}
+
+impl LoadWidth {
+    fn mnemonic(self) -> &'static str {
+        match self {
+            LoadWidth::Lb => "lb",
+            LoadWidth::Lh => "lh",
+            LoadWidth::Lw => "lw",
+            LoadWidth::Lbu => "lbu",
+            LoadWidth::Lhu => "lhu",
+        }
+    }
+}
+
+impl StoreWidth {
+    fn mnemonic(self) -> &'static str {
+        match self {
+            StoreWidth::Sb => "sb",
+            StoreWidth::Sh => "sh",
+            StoreWidth::Sw => "sw",
+        }
+    }
+}
+
+impl FusedMultiplyAddOp {
+    fn mnemonic(self) -> &'static str {
+        match self {
+            FusedMultiplyAddOp::Madd => "fmadd",
+            FusedMultiplyAddOp::Msub => "fmsub",
+            FusedMultiplyAddOp::Nmsub => "fnmsub",
+            FusedMultiplyAddOp::Nmadd => "fnmadd",
+        }
+    }
+}
+
+impl FloatOp {
+    fn mnemonic(self) -> &'static str {
+        match self {
+            FloatOp::Add => "fadd",
+            FloatOp::Sub => "fsub",
+            FloatOp::Mul => "fmul",
+            FloatOp::Div => "fdiv",
+        }
+    }
+}
+
+impl SignInjectOp {
+    fn mnemonic(self) -> &'static str {
+        match self {
+            SignInjectOp::Sgnj => "fsgnj",
+            SignInjectOp::Sgnjn => "fsgnjn",
+            SignInjectOp::Sgnjx => "fsgnjx",
+        }
+    }
+}
+
+impl CsrOp {
+    fn mnemonic(self) -> &'static str {
+        match self {
+            CsrOp::ReadWrite => "csrrw",
+            CsrOp::ReadSet => "csrrs",
+            CsrOp::ReadClear => "csrrc",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn disassemble(raw_instruction: u32, address: u32) -> String {
+        Instruction::decode(raw_instruction)
+            .unwrap()
+            .disassemble(address)
+            .to_string()
+    }
+
+    #[test]
+    fn pseudo_instructions() {
+        assert_eq!(disassemble(0x0000_0013, 0), "nop");
+        assert_eq!(disassemble(0xFFF0_0513, 0), "li\ta0,-1");
+        assert_eq!(disassemble(0x4505, 0), "li\ta0,1");
+        assert_eq!(disassemble(0x0005_8513, 0), "mv\ta0,a1");
+        assert_eq!(disassemble(0x0000_8067, 0), "ret");
+        assert_eq!(disassemble(0x3000_2573, 0), "csrr\ta0,mstatus");
+        assert_eq!(disassemble(0x3052_9073, 0), "csrw\tmtvec,t0");
+        assert_eq!(disassemble(0xC000_2573, 0), "rdcycle\ta0");
+        assert_eq!(disassemble(0x0FF0_000F, 0), "fence");
+        assert_eq!(disassemble(0x0000_100F, 0), "fence.i");
+    }
+
+    #[test]
+    fn operands() {
+        assert_eq!(disassemble(0x8000_0537, 0), "lui\ta0,0x80000");
+        assert_eq!(disassemble(0x0025_1513, 0), "slli\ta0,a0,0x2");
+        assert_eq!(disassemble(0x00A1_2423, 0), "sw\ta0,8(sp)");
+        assert_eq!(disassemble(0x7C00_2573, 0), "csrr\ta0,0x7c0");
+        assert_eq!(disassemble(0x06B6_252F, 0), "amoadd.w.aqrl\ta0,a1,(a2)");
+        assert_eq!(disassemble(0x00C5_F553, 0), "fadd.s\tfa0,fa1,fa2");
+        assert_eq!(disassemble(0xC005_1553, 0), "fcvt.w.s\ta0,fa0,rtz");
+    }
+
+    #[test]
+    fn targets() {
+        assert_eq!(disassemble(0x0100_00EF, 0x8000_0000), "jal\t80000010");
+        assert_eq!(disassemble(0xFE05_0CE3, 0x8000_0010), "beqz\ta0,80000008");
+        let instruction = Instruction::decode(0xFE05_0CE3).unwrap();
+        assert_eq!(instruction.jump_target(0x8000_0010), Some(0x8000_0008));
+        let instruction = Instruction::decode(0x0000_8067).unwrap();
+        assert_eq!(instruction.jump_target(0x8000_0010), None);
+    }
+}
@@ -0,0 +1,635 @@
+//! Encoding of instructions, the inverse of [`Instruction::decode`].
+
+use thiserror::Error;
+
+use super::{
+    AmoOp, BranchCondition, CsrOp, FenceOrderCombination, FloatCompareOp, FloatOp, FloatPrecision,
+    FusedMultiplyAddOp, Instruction, LoadWidth, MinMaxOp, Opcode, RegImmOp, RegRegOp,
+    RegShiftImmOp, RegUnaryOp, SignInjectOp, StoreWidth,
+};
+use crate::core::csr;
+use crate::float::RoundingMode;
+use crate::registers::Specifier;
+
+/// Error returned by [`Instruction::encode`] for instructions that have no encoding.
+#[derive(Error, Debug, Clone, Eq, PartialEq)]
+pub enum EncodeError {
+    #[error("immediate {0} does not fit in the instruction")]
+    ImmediateOutOfRange(i64),
+    #[error("offset {0} is not a multiple of 2")]
+    MisalignedOffset(i32),
+    #[error("invalid CSR specifier {0:#x}")]
+    InvalidCsr(u16),
+    #[error("conversion between the same precision")]
+    SamePrecision,
+}
+
+impl Instruction {
+    /// Encodes this instruction into its 32-bit form, such that [`Instruction::decode`] returns
+    /// this instruction again. Compressed encodings are never used.
+    ///
+    /// Fails if an immediate, offset or CSR specifier does not fit in its field, or if the
+    /// instruction has no encoding at all.
+    pub fn encode(&self) -> Result<u32, EncodeError> {
+        Ok(match *self {
+            Instruction::OpImm {
+                op,
+                dest,
+                src,
+                immediate,
+            } => {
+                let funct3 = match op {
+                    RegImmOp::Addi => 0b000,
+                    RegImmOp::Slti => 0b010,
+                    RegImmOp::Sltiu => 0b011,
+                    RegImmOp::Xori => 0b100,
+                    RegImmOp::Ori => 0b110,
+                    RegImmOp::Andi => 0b111,
+                };
+                i_type(Opcode::OpImm, funct3, dest, src, signed(immediate, 12)?)
+            }
+            Instruction::OpShiftImm {
+                op,
+                dest,
+                src,
+                shift_amount_u5,
+            } => {
+                let (funct7, funct3) = match op {
+                    RegShiftImmOp::Slli => (0b0000000, 0b001),
+                    RegShiftImmOp::Srli => (0b0000000, 0b101),
+                    RegShiftImmOp::Srai => (0b0100000, 0b101),
+                    RegShiftImmOp::Rori => (0b0110000, 0b101),
+                    RegShiftImmOp::Bclri => (0b0100100, 0b001),
+                    RegShiftImmOp::Bexti => (0b0100100, 0b101),
+                    RegShiftImmOp::Binvi => (0b0110100, 0b001),
+                    RegShiftImmOp::Bseti => (0b0010100, 0b001),
+                };
+                let shift_amount = unsigned(shift_amount_u5, 5)?;
+                i_type(Opcode::OpImm, funct3, dest, src, funct7 << 5 | shift_amount)
+            }
+            Instruction::Auipc { dest, immediate } => u_type(Opcode::Auipc, dest, immediate)?,
+            Instruction::Lui { dest, immediate } => u_type(Opcode::Lui, dest, immediate)?,
+            Instruction::Amo {
+                op,
+                aq,
+                rl,
+                src,
+                addr,
+                dest,
+            } => {
+                let funct5 = match op {
+                    AmoOp::Lr => 0b00010,
+                    AmoOp::Sc => 0b00011,
+                    AmoOp::Swap => 0b00001,
+                    AmoOp::Add => 0b00000,
+                    AmoOp::Xor => 0b00100,
+                    AmoOp::And => 0b01100,
+                    AmoOp::Or => 0b01000,
+                    AmoOp::Min => 0b10000,
+                    AmoOp::Max => 0b10100,
+                    AmoOp::Minu => 0b11000,
+                    AmoOp::Maxu => 0b11100,
+                };
+                let funct7 = funct5 << 2 | (aq as u32) << 1 | rl as u32;
+                r_type(Opcode::Amo, funct7, 0b010, dest, addr, src)
+            }
+            Instruction::Op {
+                op,
+                dest,
+                src1,
+                src2,
+            } => {
+                let (funct7, funct3) = match op {
+                    RegRegOp::Add => (0b0000000, 0b000),
+                    RegRegOp::Sll => (0b0000000, 0b001),
+                    RegRegOp::Slt => (0b0000000, 0b010),
+                    RegRegOp::Sltu => (0b0000000, 0b011),
+                    RegRegOp::Xor => (0b0000000, 0b100),
+                    RegRegOp::Srl => (0b0000000, 0b101),
+                    RegRegOp::Or => (0b0000000, 0b110),
+                    RegRegOp::And => (0b0000000, 0b111),
+                    RegRegOp::Sub => (0b0100000, 0b000),
+                    RegRegOp::Sra => (0b0100000, 0b101),
+                    RegRegOp::Mul => (0b0000001, 0b000),
+                    RegRegOp::Mulh => (0b0000001, 0b001),
+                    RegRegOp::Mulhsu => (0b0000001, 0b010),
+                    RegRegOp::Mulhu => (0b0000001, 0b011),
+                    RegRegOp::Div => (0b0000001, 0b100),
+                    RegRegOp::Divu => (0b0000001, 0b101),
+                    RegRegOp::Rem => (0b0000001, 0b110),
+                    RegRegOp::Remu => (0b0000001, 0b111),
+                    RegRegOp::Sh1add => (0b0010000, 0b010),
+                    RegRegOp::Sh2add => (0b0010000, 0b100),
+                    RegRegOp::Sh3add => (0b0010000, 0b110),
+                    RegRegOp::Andn => (0b0100000, 0b111),
+                    RegRegOp::Orn => (0b0100000, 0b110),
+                    RegRegOp::Xnor => (0b0100000, 0b100),
+                    RegRegOp::Max => (0b0000101, 0b110),
+                    RegRegOp::Maxu => (0b0000101, 0b111),
+                    RegRegOp::Min => (0b0000101, 0b100),
+                    RegRegOp::Minu => (0b0000101, 0b101),
+                    RegRegOp::Rol => (0b0110000, 0b001),
+                    RegRegOp::Ror => (0b0110000, 0b101),
+                    RegRegOp::Clmul => (0b0000101, 0b001),
+                    RegRegOp::Clmulh => (0b0000101, 0b011),
+                    RegRegOp::Clmulr => (0b0000101, 0b010),
+                    RegRegOp::Bclr => (0b0100100, 0b001),
+                    RegRegOp::Bext => (0b0100100, 0b101),
+                    RegRegOp::Binv => (0b0110100, 0b001),
+                    RegRegOp::Bset => (0b0010100, 0b001),
+                };
+                r_type(Opcode::Op, funct7, funct3, dest, src1, src2)
+            }
+            Instruction::OpUnary { op, dest, src } => {
+                let (opcode, funct7, rs2, funct3) = match op {
+                    RegUnaryOp::Clz => (Opcode::OpImm, 0b0110000, 0b00000, 0b001),
+                    RegUnaryOp::Ctz => (Opcode::OpImm, 0b0110000, 0b00001, 0b001),
+                    RegUnaryOp::Cpop => (Opcode::OpImm, 0b0110000, 0b00010, 0b001),
+                    RegUnaryOp::SextB => (Opcode::OpImm, 0b0110000, 0b00100, 0b001),
+                    RegUnaryOp::SextH => (Opcode::OpImm, 0b0110000, 0b00101, 0b001),
+                    RegUnaryOp::OrcB => (Opcode::OpImm, 0b0010100, 0b00111, 0b101),
+                    RegUnaryOp::Rev8 => (Opcode::OpImm, 0b0110100, 0b11000, 0b101),
+                    RegUnaryOp::ZextH => (Opcode::Op, 0b0000100, 0b00000, 0b100),
+                };
+                r_type(opcode, funct7, funct3, dest, src, Specifier::from_u5(rs2))
+            }
+            Instruction::Jal { dest, offset } => {
+                let imm = aligned(offset)?;
+                let imm = signed(imm, 21)?;
+                let imm = (imm & 0x10_0000) << 11
+                    | (imm & 0x7FE) << 20
+                    | (imm & 0x800) << 9
+                    | (imm & 0xF_F000);
+                imm | reg(dest) << 7 | Opcode::Jal.bits()
+            }
+            Instruction::Jalr { dest, base, offset } => {
+                i_type(Opcode::Jalr, 0b000, dest, base, signed(offset, 12)?)
+            }
+            Instruction::Branch {
+                condition,
+                src1,
+                src2,
+                offset,
+            } => {
+                let funct3 = match condition {
+                    BranchCondition::Beq => 0b000,
+                    BranchCondition::Bne => 0b001,
+                    BranchCondition::Blt => 0b100,
+                    BranchCondition::Bge => 0b101,
+                    BranchCondition::Bltu => 0b110,
+                    BranchCondition::Bgeu => 0b111,
+                };
+                let imm = signed(aligned(offset)?, 13)?;
+                let imm = (imm & 0x1000) << 19
+                    | (imm & 0x7E0) << 20
+                    | (imm & 0x1E) << 7
+                    | (imm & 0x800) >> 4;
+                imm | reg(src2) << 20 | reg(src1) << 15 | funct3 << 12 | Opcode::Branch.bits()
+            }
+            Instruction::Load {
+                width,
+                dest,
+                base,
+                offset,
+            } => {
+                let funct3 = match width {
+                    LoadWidth::Lb => 0b000,
+                    LoadWidth::Lh => 0b001,
+                    LoadWidth::Lw => 0b010,
+                    LoadWidth::Lbu => 0b100,
+                    LoadWidth::Lhu => 0b101,
+                };
+                i_type(Opcode::Load, funct3, dest, base, signed(offset, 12)?)
+            }
+            Instruction::Store {
+                width,
+                src,
+                base,
+                offset,
+            } => {
+                let funct3 = match width {
+                    StoreWidth::Sb => 0b000,
+                    StoreWidth::Sh => 0b001,
+                    StoreWidth::Sw => 0b010,
+                };
+                s_type(Opcode::Store, funct3, base, src, offset)?
+            }
+            Instruction::Fence {
+                predecessor,
+                successor,
+            } => {
+                let imm = fence_bits(predecessor) << 4 | fence_bits(successor);
+                i_type(Opcode::MiscMem, 0b000, Specifier::X0, Specifier::X0, imm)
+            }
+            Instruction::FenceI => i_type(Opcode::MiscMem, 0b001, Specifier::X0, Specifier::X0, 0),
+            Instruction::Ecall => system(0b000000000000, Specifier::X0),
+            Instruction::Ebreak => system(0b000000000001, Specifier::X0),
+            Instruction::Sret => system(0b000100000010, Specifier::X0),
+            Instruction::Mret => system(0b001100000010, Specifier::X0),
+            Instruction::Wfi => system(0b000100000101, Specifier::X0),
+            Instruction::SfenceVma { vaddr, asid } => system(0b0001001 << 5 | reg(asid), vaddr),
+            Instruction::Csr { op, dest, csr, src } => {
+                let funct3 = match op {
+                    CsrOp::ReadWrite => 0b001,
+                    CsrOp::ReadSet => 0b010,
+                    CsrOp::ReadClear => 0b011,
+                };
+                i_type(Opcode::System, funct3, dest, src, csr_bits(csr)?)
+            }
+            Instruction::Csri {
+                op,
+                dest,
+                csr,
+                immediate,
+            } => {
+                let funct3 = match op {
+                    CsrOp::ReadWrite => 0b101,
+                    CsrOp::ReadSet => 0b110,
+                    CsrOp::ReadClear => 0b111,
+                };
+                let immediate = Specifier::from_u5(unsigned(immediate, 5)? as u8);
+                i_type(Opcode::System, funct3, dest, immediate, csr_bits(csr)?)
+            }
+            Instruction::LoadFp {
+                precision,
+                dest,
+                base,
+                offset,
+            } => {
+                let funct3 = width_bits(precision);
+                i_type(Opcode::LoadFp, funct3, dest, base, signed(offset, 12)?)
+            }
+            Instruction::StoreFp {
+                precision,
+                src,
+                base,
+                offset,
+            } => s_type(Opcode::StoreFp, width_bits(precision), base, src, offset)?,
+            Instruction::FusedMultiplyAdd {
+                op,
+                precision,
+                rounding_mode,
+                dest,
+                src1,
+                src2,
+                src3,
+            } => {
+                let opcode = match op {
+                    FusedMultiplyAddOp::Madd => Opcode::Madd,
+                    FusedMultiplyAddOp::Msub => Opcode::Msub,
+                    FusedMultiplyAddOp::Nmsub => Opcode::Nmsub,
+                    FusedMultiplyAddOp::Nmadd => Opcode::Nmadd,
+                };
+                let funct7 = reg(src3) << 2 | fmt_bits(precision);
+                r_type(opcode, funct7, rm_bits(rounding_mode), dest, src1, src2)
+            }
+            Instruction::OpFp {
+                op,
+                precision,
+                rounding_mode,
+                dest,
+                src1,
+                src2,
+            } => {
+                let funct5 = match op {
+                    FloatOp::Add => 0b00000,
+                    FloatOp::Sub => 0b00001,
+                    FloatOp::Mul => 0b00010,
+                    FloatOp::Div => 0b00011,
+                };
+                let rm = rm_bits(rounding_mode);
+                op_fp(funct5, precision, rm, dest, src1, src2)
+            }
+            Instruction::FloatSqrt {
+                precision,
+                rounding_mode,
+                dest,
+                src,
+            } => {
+                let rm = rm_bits(rounding_mode);
+                op_fp(0b01011, precision, rm, dest, src, Specifier::X0)
+            }
+            Instruction::FloatSignInject {
+                op,
+                precision,
+                dest,
+                src1,
+                src2,
+            } => {
+                let rm = match op {
+                    SignInjectOp::Sgnj => 0b000,
+                    SignInjectOp::Sgnjn => 0b001,
+                    SignInjectOp::Sgnjx => 0b010,
+                };
+                op_fp(0b00100, precision, rm, dest, src1, src2)
+            }
+            Instruction::FloatMinMax {
+                op,
+                precision,
+                dest,
+                src1,
+                src2,
+            } => {
+                let rm = match op {
+                    MinMaxOp::Min => 0b000,
+                    MinMaxOp::Max => 0b001,
+                };
+                op_fp(0b00101, precision, rm, dest, src1, src2)
+            }
+            Instruction::FloatCompare {
+                op,
+                precision,
+                dest,
+                src1,
+                src2,
+            } => {
+                let rm = match op {
+                    FloatCompareOp::Eq => 0b010,
+                    FloatCompareOp::Lt => 0b001,
+                    FloatCompareOp::Le => 0b000,
+                };
+                op_fp(0b10100, precision, rm, dest, src1, src2)
+            }
+            Instruction::FloatClassify {
+                precision,
+                dest,
+                src,
+            } => op_fp(0b11100, precision, 0b001, dest, src, Specifier::X0),
+            Instruction::FloatToInt {
+                signed,
+                precision,
+                rounding_mode,
+                dest,
+                src,
+            } => {
+                let rs2 = Specifier::from_u5(!signed as u8);
+                let rm = rm_bits(rounding_mode);
+                op_fp(0b11000, precision, rm, dest, src, rs2)
+            }
+            Instruction::IntToFloat {
+                signed,
+                precision,
+                rounding_mode,
+                dest,
+                src,
+            } => {
+                let rs2 = Specifier::from_u5(!signed as u8);
+                let rm = rm_bits(rounding_mode);
+                op_fp(0b11010, precision, rm, dest, src, rs2)
+            }
+            Instruction::FloatConvert {
+                from,
+                to,
+                rounding_mode,
+                dest,
+                src,
+            } => {
+                if from == to {
+                    return Err(EncodeError::SamePrecision);
+                }
+                let rs2 = Specifier::from_u5(fmt_bits(from) as u8);
+                op_fp(0b01000, to, rm_bits(rounding_mode), dest, src, rs2)
+            }
+            Instruction::FloatMoveToInt { dest, src } => op_fp(
+                0b11100,
+                FloatPrecision::Single,
+                0b000,
+                dest,
+                src,
+                Specifier::X0,
+            ),
+            Instruction::FloatMoveFromInt { dest, src } => op_fp(
+                0b11110,
+                FloatPrecision::Single,
+                0b000,
+                dest,
+                src,
+                Specifier::X0,
+            ),
+        })
+    }
+}
+
+impl Opcode {
+    /// Returns the 7-bit *opcode* field of this major opcode, see [`super::opcode`].
+    fn bits(self) -> u32 {
+        #[allow(clippy::unusual_byte_groupings)]
+        match self {
+            Opcode::Load => 0b00_000_11,
+            Opcode::LoadFp => 0b00_001_11,
+            Opcode::MiscMem => 0b00_011_11,
+            Opcode::OpImm => 0b00_100_11,
+            Opcode::Auipc => 0b00_101_11,
+            Opcode::Store => 0b01_000_11,
+            Opcode::StoreFp => 0b01_001_11,
+            Opcode::Amo => 0b01_011_11,
+            Opcode::Op => 0b01_100_11,
+            Opcode::Lui => 0b01_101_11,
+            Opcode::Madd => 0b10_000_11,
+            Opcode::Msub => 0b10_001_11,
+            Opcode::Nmsub => 0b10_010_11,
+            Opcode::Nmadd => 0b10_011_11,
+            Opcode::OpFp => 0b10_100_11,
+            Opcode::Branch => 0b11_000_11,
+            Opcode::Jalr => 0b11_001_11,
+            Opcode::Jal => 0b11_011_11,
+            Opcode::System => 0b11_100_11,
+        }
+    }
+}
+
+fn reg(specifier: Specifier) -> u32 {
+    u32::from(specifier)
+}
+
+fn r_type(
+    opcode: Opcode,
+    funct7: u32,
+    funct3: u32,
+    rd: Specifier,
+    rs1: Specifier,
+    rs2: Specifier,
+) -> u32 {
+    funct7 << 25 | reg(rs2) << 20 | reg(rs1) << 15 | funct3 << 12 | reg(rd) << 7 | opcode.bits()
+}
+
+/// `imm` must already fit in 12 bits.
+fn i_type(opcode: Opcode, funct3: u32, rd: Specifier, rs1: Specifier, imm: u32) -> u32 {
+    imm << 20 | reg(rs1) << 15 | funct3 << 12 | reg(rd) << 7 | opcode.bits()
+}
+
+fn s_type(
+    opcode: Opcode,
+    funct3: u32,
+    rs1: Specifier,
+    rs2: Specifier,
+    offset: i32,
+) -> Result<u32, EncodeError> {
+    let imm = signed(offset, 12)?;
+    Ok((imm & 0xFE0) << 20
+        | reg(rs2) << 20
+        | reg(rs1) << 15
+        | funct3 << 12
+        | (imm & 0x1F) << 7
+        | opcode.bits())
+}
+
+fn u_type(opcode: Opcode, rd: Specifier, immediate: i32) -> Result<u32, EncodeError> {
+    if immediate & 0xFFF != 0 {
+        return Err(EncodeError::ImmediateOutOfRange(immediate.into()));
+    }
+    Ok(immediate as u32 | reg(rd) << 7 | opcode.bits())
+}
+
+/// Encodes an instruction under the SYSTEM opcode with *funct3* zero.
+fn system(funct12: u32, rs1: Specifier) -> u32 {
+    i_type(Opcode::System, 0b000, Specifier::X0, rs1, funct12)
+}
+
+fn op_fp(
+    funct5: u32,
+    precision: FloatPrecision,
+    rm: u32,
+    rd: Specifier,
+    rs1: Specifier,
+    rs2: Specifier,
+) -> u32 {
+    let funct7 = funct5 << 2 | fmt_bits(precision);
+    r_type(Opcode::OpFp, funct7, rm, rd, rs1, rs2)
+}
+
+/// Returns the lowest `bits` bits of `value`, if it fits in that many bits as a signed integer.
+fn signed(value: i32, bits: u32) -> Result<u32, EncodeError> {
+    let min = -1 << (bits - 1);
+    let max = (1 << (bits - 1)) - 1;
+    match (min..=max).contains(&value) {
+        true => Ok(value as u32 & ((1 << bits) - 1)),
+        false => Err(EncodeError::ImmediateOutOfRange(value.into())),
+    }
+}
+
+/// Returns `value` if it fits in `bits` bits as an unsigned integer.
+fn unsigned(value: u32, bits: u32) -> Result<u32, EncodeError> {
+    match value < 1 << bits {
+        true => Ok(value),
+        false => Err(EncodeError::ImmediateOutOfRange(value.into())),
+    }
+}
+
+/// Jump and branch offsets are multiples of 2, since their lowest bit is not encoded.
+fn aligned(offset: i32) -> Result<i32, EncodeError> {
+    match offset % 2 {
+        0 => Ok(offset),
+        _ => Err(EncodeError::MisalignedOffset(offset)),
+    }
+}
+
+fn csr_bits(specifier: u16) -> Result<u32, EncodeError> {
+    match csr::is_valid(specifier) {
+        true => Ok(specifier.into()),
+        false => Err(EncodeError::InvalidCsr(specifier)),
+    }
+}
+
+fn fence_bits(combination: FenceOrderCombination) -> u32 {
+    (combination.device_input as u32) << 3
+        | (combination.device_output as u32) << 2
+        | (combination.memory_reads as u32) << 1
+        | combination.memory_writes as u32
+}
+
+fn width_bits(precision: FloatPrecision) -> u32 {
+    match precision {
+        FloatPrecision::Single => 0b010,
+        FloatPrecision::Double => 0b011,
+    }
+}
+
+fn fmt_bits(precision: FloatPrecision) -> u32 {
+    match precision {
+        FloatPrecision::Single => 0b00,
+        FloatPrecision::Double => 0b01,
+    }
+}
+
+/// Returns the *rm* field, where `None` selects the dynamic rounding mode.
+fn rm_bits(rounding_mode: Option<RoundingMode>) -> u32 {
+    match rounding_mode {
+        Some(rounding_mode) => rounding_mode as u32,
+        None => 0b111,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        // Every 32-bit instruction that decodes must encode back to the same bits, apart from
+        // the fields that are ignored by decoding.
+        let mut state = 0x1234_5678_u32;
+        let mut decoded = 0;
+        for _ in 0..2_000_000 {
+            // xorshift32
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            let raw = state | 0b11;
+            let Ok(instruction) = Instruction::decode(raw) else {
+                continue;
+            };
+            decoded += 1;
+            let encoded = instruction.encode().unwrap();
+            assert_eq!(Instruction::decode(encoded), Ok(instruction), "{raw:#010x}");
+            // Decoding ignores the reserved fields of FENCE and FENCE.I, and the funct3 field of
+            // JALR.
+            if !matches!(
+                instruction,
+                Instruction::Fence { .. } | Instruction::FenceI | Instruction::Jalr { .. }
+            ) {
+                assert_eq!(encoded, raw, "{instruction:?}");
+            }
+        }
+        assert!(decoded > 10_000);
+    }
+
+    #[test]
+    fn compressed_round_trip() {
+        for raw in 0..=u16::MAX {
+            if let Ok(instruction) = Instruction::decode_compressed(raw) {
+                let encoded = instruction.encode().unwrap();
+                assert_eq!(Instruction::decode(encoded), Ok(instruction), "{raw:#06x}");
+            }
+        }
+    }
+
+    #[test]
+    fn out_of_range() {
+        let x1 = Specifier::from_u5(1);
+        let addi = |immediate| Instruction::OpImm {
+            op: RegImmOp::Addi,
+            dest: x1,
+            src: x1,
+            immediate,
+        };
+        assert_eq!(addi(-2048).encode(), Ok(0x8000_8093));
+        assert_eq!(
+            addi(2048).encode(),
+            Err(EncodeError::ImmediateOutOfRange(2048))
+        );
+        let jal = |offset| Instruction::Jal { dest: x1, offset };
+        assert_eq!(jal(3).encode(), Err(EncodeError::MisalignedOffset(3)));
+        assert_eq!(
+            jal(1 << 20).encode(),
+            Err(EncodeError::ImmediateOutOfRange(1 << 20))
+        );
+        let csr = Instruction::Csr {
+            op: CsrOp::ReadSet,
+            dest: x1,
+            csr: 0x1000,
+            src: Specifier::X0,
+        };
+        assert_eq!(csr.encode(), Err(EncodeError::InvalidCsr(0x1000)));
+    }
+}
@@ -0,0 +1,2043 @@
+mod assemble;
+mod disassemble;
+mod encode;
+
+pub use assemble::AssembleError;
+pub use disassemble::Disassembly;
+pub use encode::EncodeError;
+
+use crate::core::CsrSpecifier;
+use crate::float::RoundingMode;
+use crate::registers::Specifier;
+use log::trace;
+use thiserror::Error;
+
+/// Data structure that can hold any supported instruction in its decoded form.
+///
+/// For the floating-point instructions, [`Specifier`]s refer to `f` registers unless documented
+/// otherwise. A `rounding_mode` of `None` selects the dynamic rounding mode held in `frm`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Instruction {
+    OpImm {
+        op: RegImmOp,
+        dest: Specifier,
+        src: Specifier,
+        immediate: i32,
+    },
+    OpShiftImm {
+        op: RegShiftImmOp,
+        dest: Specifier,
+        src: Specifier,
+        shift_amount_u5: u32,
+    },
+    Auipc {
+        dest: Specifier,
+        immediate: i32,
+    },
+    Lui {
+        dest: Specifier,
+        immediate: i32,
+    },
+    Amo {
+        op: AmoOp,
+        aq: bool,
+        rl: bool,
+        src: Specifier,
+        addr: Specifier,
+        dest: Specifier,
+    },
+    Op {
+        op: RegRegOp,
+        dest: Specifier,
+        src1: Specifier,
+        src2: Specifier,
+    },
+    /// Bit-manipulation instructions with a single source register, encoded under the OP-IMM or
+    /// OP opcode with a fixed `rs2`/immediate field.
+    OpUnary {
+        op: RegUnaryOp,
+        dest: Specifier,
+        src: Specifier,
+    },
+    Jal {
+        dest: Specifier,
+        offset: i32,
+    },
+    Jalr {
+        dest: Specifier,
+        base: Specifier,
+        offset: i32,
+    },
+    Branch {
+        condition: BranchCondition,
+        src1: Specifier,
+        src2: Specifier,
+        offset: i32,
+    },
+    Load {
+        width: LoadWidth,
+        dest: Specifier,
+        base: Specifier,
+        offset: i32,
+    },
+    Store {
+        width: StoreWidth,
+        src: Specifier,
+        base: Specifier,
+        offset: i32,
+    },
+    Fence {
+        predecessor: FenceOrderCombination,
+        successor: FenceOrderCombination,
+    },
+    FenceI,
+    Ecall,
+    Ebreak,
+    Sret,
+    Mret,
+    Wfi,
+    SfenceVma {
+        vaddr: Specifier,
+        asid: Specifier,
+    },
+    Csr {
+        op: CsrOp,
+        dest: Specifier,
+        csr: CsrSpecifier,
+        src: Specifier,
+    },
+    Csri {
+        op: CsrOp,
+        dest: Specifier,
+        csr: CsrSpecifier,
+        immediate: u32,
+    },
+    LoadFp {
+        precision: FloatPrecision,
+        dest: Specifier,
+        /// An `x` register.
+        base: Specifier,
+        offset: i32,
+    },
+    StoreFp {
+        precision: FloatPrecision,
+        src: Specifier,
+        /// An `x` register.
+        base: Specifier,
+        offset: i32,
+    },
+    FusedMultiplyAdd {
+        op: FusedMultiplyAddOp,
+        precision: FloatPrecision,
+        rounding_mode: Option<RoundingMode>,
+        dest: Specifier,
+        src1: Specifier,
+        src2: Specifier,
+        src3: Specifier,
+    },
+    OpFp {
+        op: FloatOp,
+        precision: FloatPrecision,
+        rounding_mode: Option<RoundingMode>,
+        dest: Specifier,
+        src1: Specifier,
+        src2: Specifier,
+    },
+    FloatSqrt {
+        precision: FloatPrecision,
+        rounding_mode: Option<RoundingMode>,
+        dest: Specifier,
+        src: Specifier,
+    },
+    FloatSignInject {
+        op: SignInjectOp,
+        precision: FloatPrecision,
+        dest: Specifier,
+        src1: Specifier,
+        src2: Specifier,
+    },
+    FloatMinMax {
+        op: MinMaxOp,
+        precision: FloatPrecision,
+        dest: Specifier,
+        src1: Specifier,
+        src2: Specifier,
+    },
+    FloatCompare {
+        op: FloatCompareOp,
+        precision: FloatPrecision,
+        /// An `x` register.
+        dest: Specifier,
+        src1: Specifier,
+        src2: Specifier,
+    },
+    FloatClassify {
+        precision: FloatPrecision,
+        /// An `x` register.
+        dest: Specifier,
+        src: Specifier,
+    },
+    FloatToInt {
+        signed: bool,
+        precision: FloatPrecision,
+        rounding_mode: Option<RoundingMode>,
+        /// An `x` register.
+        dest: Specifier,
+        src: Specifier,
+    },
+    IntToFloat {
+        signed: bool,
+        precision: FloatPrecision,
+        rounding_mode: Option<RoundingMode>,
+        dest: Specifier,
+        /// An `x` register.
+        src: Specifier,
+    },
+    FloatConvert {
+        from: FloatPrecision,
+        to: FloatPrecision,
+        rounding_mode: Option<RoundingMode>,
+        dest: Specifier,
+        src: Specifier,
+    },
+    /// `fmv.x.w`
+    FloatMoveToInt {
+        /// An `x` register.
+        dest: Specifier,
+        src: Specifier,
+    },
+    /// `fmv.w.x`
+    FloatMoveFromInt {
+        dest: Specifier,
+        /// An `x` register.
+        src: Specifier,
+    },
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum RegImmOp {
+    Addi,
+    Slti,
+    Sltiu,
+    Xori,
+    Ori,
+    Andi,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum RegShiftImmOp {
+    Slli,
+    Srli,
+    Srai,
+    // Zbb
+    Rori,
+    // Zbs
+    Bclri,
+    Bexti,
+    Binvi,
+    Bseti,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum AmoOp {
+    Lr,
+    Sc,
+    Swap,
+    Add,
+    Xor,
+    And,
+    Or,
+    Min,
+    Max,
+    Minu,
+    Maxu,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum RegRegOp {
+    Add,
+    Slt,
+    Sltu,
+    And,
+    Or,
+    Xor,
+    Sll,
+    Srl,
+    Sub,
+    Sra,
+    Mul,
+    Mulh,
+    Mulhsu,
+    Mulhu,
+    Div,
+    Divu,
+    Rem,
+    Remu,
+    // Zba
+    Sh1add,
+    Sh2add,
+    Sh3add,
+    // Zbb
+    Andn,
+    Orn,
+    Xnor,
+    Max,
+    Maxu,
+    Min,
+    Minu,
+    Rol,
+    Ror,
+    // Zbc
+    Clmul,
+    Clmulh,
+    Clmulr,
+    // Zbs
+    Bclr,
+    Bext,
+    Binv,
+    Bset,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum RegUnaryOp {
+    Clz,
+    Ctz,
+    Cpop,
+    SextB,
+    SextH,
+    ZextH,
+    OrcB,
+    Rev8,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum BranchCondition {
+    Beq,
+    Bne,
+    Blt,
+    Bltu,
+    Bge,
+    Bgeu,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum LoadWidth {
+    Lb,
+    Lh,
+    Lw,
+    Lbu,
+    Lhu,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum StoreWidth {
+    Sb,
+    Sh,
+    Sw,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct FenceOrderCombination {
+    pub device_input: bool,
+    pub device_output: bool,
+    pub memory_reads: bool,
+    pub memory_writes: bool,
+}
+
+/// The precision of a floating-point instruction, as encoded in its *fmt* field.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum FloatPrecision {
+    /// 32-bit single-precision, provided by the F extension.
+    Single,
+    /// 64-bit double-precision, provided by the D extension.
+    Double,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum FusedMultiplyAddOp {
+    /// `rs1×rs2+rs3`
+    Madd,
+    /// `rs1×rs2-rs3`
+    Msub,
+    /// `-rs1×rs2+rs3`
+    Nmsub,
+    /// `-rs1×rs2-rs3`
+    Nmadd,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum FloatOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum SignInjectOp {
+    Sgnj,
+    Sgnjn,
+    Sgnjx,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum MinMaxOp {
+    Min,
+    Max,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum FloatCompareOp {
+    Eq,
+    Lt,
+    Le,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum CsrOp {
+    /// Atomic Read/Write CSR.
+    ReadWrite,
+    /// Atomic Read and Set Bits in CSR.
+    ReadSet,
+    /// Atomic Read and Clear Bits in CSR.
+    ReadClear,
+}
+
+/// Returns `true` if the lowest 16 bits of `raw_instruction` hold a compressed instruction.
+///
+/// > The base ISA has IALIGN=32, but some standard extensions, including the compressed ISA
+/// > extension, relax IALIGN to 16. [...] The standard compressed ISA extension has 16-bit
+/// > instructions with their lowest two bits not equal to `11`.
+pub fn is_compressed(raw_instruction: u32) -> bool {
+    raw_instruction & 0b11 != 0b11
+}
+
+/// Returns the length in bytes of the instruction of which `raw_instruction` holds (at least) the
+/// first parcel. This is either `2` or `4`.
+pub fn instruction_length(raw_instruction: u32) -> u32 {
+    match is_compressed(raw_instruction) {
+        true => 2,
+        false => 4,
+    }
+}
+
+impl Instruction {
+    /// Decodes a 32-bit instruction, or a 16-bit compressed instruction held in the lower half of
+    /// `raw_instruction` (see [`is_compressed`]).
+    ///
+    /// Compressed instructions are expanded into their 32-bit equivalent.
+    pub fn decode(raw_instruction: u32) -> Result<Self, DecodeError> {
+        if is_compressed(raw_instruction) {
+            return Self::decode_compressed(raw_instruction as u16);
+        }
+        trace!("Decoding instruction {raw_instruction:#010x}");
+        match opcode(raw_instruction).ok_or(DecodeError::UnsupportedOpcode)? {
+            Opcode::OpImm => match i_funct(raw_instruction) {
+                Some(op) => Ok(Self::OpImm {
+                    op,
+                    dest: rd(raw_instruction),
+                    src: rs1(raw_instruction),
+                    immediate: i_imm(raw_instruction),
+                }),
+                None => match i_shfunct(raw_instruction) {
+                    Some(op) => Ok(Self::OpShiftImm {
+                        op,
+                        dest: rd(raw_instruction),
+                        src: rs1(raw_instruction),
+                        shift_amount_u5: shamt(raw_instruction),
+                    }),
+                    None => match i_unary_funct(raw_instruction) {
+                        Some(op) => Ok(Self::OpUnary {
+                            op,
+                            dest: rd(raw_instruction),
+                            src: rs1(raw_instruction),
+                        }),
+                        None => Err(DecodeError::IllegalInstruction),
+                    },
+                },
+            },
+            Opcode::Auipc => Ok(Self::Auipc {
+                dest: rd(raw_instruction),
+                immediate: u_imm(raw_instruction),
+            }),
+            Opcode::Lui => Ok(Self::Lui {
+                dest: rd(raw_instruction),
+                immediate: u_imm(raw_instruction),
+            }),
+            Opcode::Amo => match amo_op(raw_instruction) {
+                Some(op) => Ok(Self::Amo {
+                    op,
+                    aq: amo_aq(raw_instruction),
+                    rl: amo_rl(raw_instruction),
+                    src: rs2(raw_instruction),
+                    addr: rs1(raw_instruction),
+                    dest: rd(raw_instruction),
+                }),
+                None => Err(DecodeError::IllegalInstruction),
+            },
+            Opcode::Op => match r_funct(raw_instruction) {
+                Some(op) => Ok(Self::Op {
+                    op,
+                    dest: rd(raw_instruction),
+                    src1: rs1(raw_instruction),
+                    src2: rs2(raw_instruction),
+                }),
+                // zext.h is the only unary instruction under OP, with rs2 fixed to zero.
+                None if raw_instruction & 0xFFF0_7000 == 0x0800_4000 => Ok(Self::OpUnary {
+                    op: RegUnaryOp::ZextH,
+                    dest: rd(raw_instruction),
+                    src: rs1(raw_instruction),
+                }),
+                None => Err(DecodeError::IllegalInstruction),
+            },
+            Opcode::Jal => Ok(Self::Jal {
+                dest: rd(raw_instruction),
+                offset: j_imm(raw_instruction),
+            }),
+            Opcode::Jalr => Ok(Self::Jalr {
+                dest: rd(raw_instruction),
+                base: rs1(raw_instruction),
+                offset: i_imm(raw_instruction),
+            }),
+            Opcode::Branch => match b_funct(raw_instruction) {
+                Some(condition) => Ok(Self::Branch {
+                    condition,
+                    src1: rs1(raw_instruction),
+                    src2: rs2(raw_instruction),
+                    offset: b_imm(raw_instruction),
+                }),
+                None => Err(DecodeError::IllegalInstruction),
+            },
+            Opcode::Load => match i_width(raw_instruction) {
+                Some(width) => Ok(Self::Load {
+                    width,
+                    dest: rd(raw_instruction),
+                    base: rs1(raw_instruction),
+                    offset: i_imm(raw_instruction),
+                }),
+                None => Err(DecodeError::IllegalInstruction),
+            },
+            Opcode::Store => match s_width(raw_instruction) {
+                Some(width) => Ok(Self::Store {
+                    width,
+                    src: rs2(raw_instruction),
+                    base: rs1(raw_instruction),
+                    offset: s_imm(raw_instruction),
+                }),
+                None => Err(DecodeError::IllegalInstruction),
+            },
+            Opcode::MiscMem => {
+                match i_mem(raw_instruction) {
+                    Some(mem_funct) => match mem_funct {
+                        MemFunct::Fence => {
+                            let fm = raw_instruction >> 28;
+                            let rd = u8::from(rd(raw_instruction));
+                            let rs1 = u8::from(rs1(raw_instruction));
+                            if fm != 0b0000 || rd != 0 || rs1 != 0 {
+                                // All unused fields in the FENCE instruction encoding are reserved
+                                // for future use. According to the spec, they should be treated as
+                                // normal fence instructions (with fm == 0b0000) for forward
+                                // compatibility.
+                                //
+                                // Note that the current spec defines one more optional encoding
+                                // that we don't support: FENCE.TSO, which is encoded by
+                                // fm == 0b1000 && predecessor==0b0011 && successor==0b0011
+                                // && rs1 == 0 && rd == 0. The spec states this must be treated as
+                                // "reserved for future use" if not supported, which again means
+                                // treating it as a normal fence instruction (with fm == 0b0000) for
+                                // forward compatibility.
+                                //
+                                // Therefore, there's nothing to be done here. No [`DecodeError`]
+                                // that must be returned. We just continue with decoding the
+                                // instruction as if fm == 0b0000 && rs1 == 0 && rd == 0.
+                            }
+                            let predecessor = FenceOrderCombination {
+                                device_input: (raw_instruction >> 27) & 0b1 == 1,
+                                device_output: (raw_instruction >> 26) & 0b1 == 1,
+                                memory_reads: (raw_instruction >> 25) & 0b1 == 1,
+                                memory_writes: (raw_instruction >> 24) & 0b1 == 1,
+                            };
+                            let successor = FenceOrderCombination {
+                                device_input: (raw_instruction >> 23) & 0b1 == 1,
+                                device_output: (raw_instruction >> 22) & 0b1 == 1,
+                                memory_reads: (raw_instruction >> 21) & 0b1 == 1,
+                                memory_writes: (raw_instruction >> 20) & 0b1 == 1,
+                            };
+                            Ok(Self::Fence {
+                                predecessor,
+                                successor,
+                            })
+                        }
+                        // The imm, rs1 and rd fields are reserved for finer-grain fences, and
+                        // must be ignored for forward compatibility.
+                        MemFunct::FenceI => Ok(Self::FenceI),
+                    },
+                    None => Err(DecodeError::IllegalInstruction),
+                }
+            }
+            Opcode::System => match i_sys(raw_instruction) {
+                Some(sys) => match sys {
+                    SysFunct::Priv => match sys_priv(raw_instruction) {
+                        Some(sys_priv) => Ok(match sys_priv {
+                            SysPriv::Ecall => Self::Ecall,
+                            SysPriv::Ebreak => Self::Ebreak,
+                            SysPriv::Sret => Self::Sret,
+                            SysPriv::Mret => Self::Mret,
+                            SysPriv::Wfi => Self::Wfi,
+                            SysPriv::SfenceVma => Self::SfenceVma {
+                                vaddr: rs1(raw_instruction),
+                                asid: rs2(raw_instruction),
+                            },
+                        }),
+                        None => Err(DecodeError::IllegalInstruction),
+                    },
+                    SysFunct::Csrrw | SysFunct::Csrrs | SysFunct::Csrrc => {
+                        Ok(Instruction::Csr {
+                            op: match sys {
+                                SysFunct::Csrrw => CsrOp::ReadWrite,
+                                SysFunct::Csrrs => CsrOp::ReadSet,
+                                SysFunct::Csrrc => CsrOp::ReadClear,
+                                _ => unreachable!(), // Already checked in outer match
+                            },
+                            dest: rd(raw_instruction),
+                            csr: csr(raw_instruction),
+                            src: rs1(raw_instruction),
+                        })
+                    }
+                    SysFunct::Csrrwi | SysFunct::Csrrsi | SysFunct::Csrrci => {
+                        Ok(Instruction::Csri {
+                            op: match sys {
+                                SysFunct::Csrrwi => CsrOp::ReadWrite,
+                                SysFunct::Csrrsi => CsrOp::ReadSet,
+                                SysFunct::Csrrci => CsrOp::ReadClear,
+                                _ => unreachable!(), // Already checked in outer match
+                            },
+                            dest: rd(raw_instruction),
+                            csr: csr(raw_instruction),
+                            immediate: u32::from(rs1(raw_instruction)),
+                        })
+                    }
+                },
+                None => Err(DecodeError::IllegalInstruction),
+            },
+            Opcode::LoadFp => match fp_width(raw_instruction) {
+                Some(precision) => Ok(Self::LoadFp {
+                    precision,
+                    dest: rd(raw_instruction),
+                    base: rs1(raw_instruction),
+                    offset: i_imm(raw_instruction),
+                }),
+                None => Err(DecodeError::IllegalInstruction),
+            },
+            Opcode::StoreFp => match fp_width(raw_instruction) {
+                Some(precision) => Ok(Self::StoreFp {
+                    precision,
+                    src: rs2(raw_instruction),
+                    base: rs1(raw_instruction),
+                    offset: s_imm(raw_instruction),
+                }),
+                None => Err(DecodeError::IllegalInstruction),
+            },
+            Opcode::Madd | Opcode::Msub | Opcode::Nmsub | Opcode::Nmadd => {
+                Ok(Self::FusedMultiplyAdd {
+                    op: match opcode(raw_instruction) {
+                        Some(Opcode::Madd) => FusedMultiplyAddOp::Madd,
+                        Some(Opcode::Msub) => FusedMultiplyAddOp::Msub,
+                        Some(Opcode::Nmsub) => FusedMultiplyAddOp::Nmsub,
+                        _ => FusedMultiplyAddOp::Nmadd,
+                    },
+                    precision: fp_fmt(raw_instruction).ok_or(DecodeError::IllegalInstruction)?,
+                    rounding_mode: fp_rm(raw_instruction)?,
+                    dest: rd(raw_instruction),
+                    src1: rs1(raw_instruction),
+                    src2: rs2(raw_instruction),
+                    src3: rs3(raw_instruction),
+                })
+            }
+            Opcode::OpFp => decode_op_fp(raw_instruction),
+        }
+    }
+
+    /// Decodes a 16-bit compressed instruction, expanding it into its 32-bit equivalent.
+    ///
+    /// > Each RVC instruction must expand into a single 32-bit instruction in either the base ISA
+    /// > (RV32I/E, RV64I/E, or RV128I) or the F and D standard extensions, or Zicsr.
+    ///
+    /// HINT encodings are expanded like their non-HINT counterparts, which makes them behave as
+    /// the required no-ops. Reserved encodings result in [`DecodeError::IllegalInstruction`].
+    pub fn decode_compressed(raw_instruction: u16) -> Result<Self, DecodeError> {
+        trace!("Decoding compressed instruction {raw_instruction:#06x}");
+        let raw = raw_instruction;
+        match (raw & 0b11, c_funct3(raw)) {
+            // C.ADDI4SPN
+            (0b00, 0b000) => match c_addi4spn_imm(raw) {
+                // Also covers the all-zero instruction, which is defined to be illegal.
+                0 => Err(DecodeError::IllegalInstruction),
+                immediate => Ok(Self::OpImm {
+                    op: RegImmOp::Addi,
+                    dest: c_rs2_prime(raw),
+                    src: Specifier::SP,
+                    immediate,
+                }),
+            },
+            // C.LW
+            (0b00, 0b010) => Ok(Self::Load {
+                width: LoadWidth::Lw,
+                dest: c_rs2_prime(raw),
+                base: c_rs1_prime(raw),
+                offset: c_lw_imm(raw),
+            }),
+            // C.SW
+            (0b00, 0b110) => Ok(Self::Store {
+                width: StoreWidth::Sw,
+                src: c_rs2_prime(raw),
+                base: c_rs1_prime(raw),
+                offset: c_lw_imm(raw),
+            }),
+            // C.FLD
+            (0b00, 0b001) => Ok(Self::LoadFp {
+                precision: FloatPrecision::Double,
+                dest: c_rs2_prime(raw),
+                base: c_rs1_prime(raw),
+                offset: c_ld_imm(raw),
+            }),
+            // C.FLW
+            (0b00, 0b011) => Ok(Self::LoadFp {
+                precision: FloatPrecision::Single,
+                dest: c_rs2_prime(raw),
+                base: c_rs1_prime(raw),
+                offset: c_lw_imm(raw),
+            }),
+            // C.FSD
+            (0b00, 0b101) => Ok(Self::StoreFp {
+                precision: FloatPrecision::Double,
+                src: c_rs2_prime(raw),
+                base: c_rs1_prime(raw),
+                offset: c_ld_imm(raw),
+            }),
+            // C.FSW
+            (0b00, 0b111) => Ok(Self::StoreFp {
+                precision: FloatPrecision::Single,
+                src: c_rs2_prime(raw),
+                base: c_rs1_prime(raw),
+                offset: c_lw_imm(raw),
+            }),
+            // C.NOP, C.ADDI
+            (0b01, 0b000) => Ok(Self::OpImm {
+                op: RegImmOp::Addi,
+                dest: c_rd(raw),
+                src: c_rd(raw),
+                immediate: c_imm(raw),
+            }),
+            // C.JAL
+            (0b01, 0b001) => Ok(Self::Jal {
+                dest: Specifier::RA,
+                offset: c_j_imm(raw),
+            }),
+            // C.LI
+            (0b01, 0b010) => Ok(Self::OpImm {
+                op: RegImmOp::Addi,
+                dest: c_rd(raw),
+                src: Specifier::X0,
+                immediate: c_imm(raw),
+            }),
+            // C.ADDI16SP
+            (0b01, 0b011) if c_rd(raw) == Specifier::SP => match c_addi16sp_imm(raw) {
+                0 => Err(DecodeError::IllegalInstruction),
+                immediate => Ok(Self::OpImm {
+                    op: RegImmOp::Addi,
+                    dest: Specifier::SP,
+                    src: Specifier::SP,
+                    immediate,
+                }),
+            },
+            // C.LUI
+            (0b01, 0b011) => match c_imm(raw) {
+                0 => Err(DecodeError::IllegalInstruction),
+                immediate => Ok(Self::Lui {
+                    dest: c_rd(raw),
+                    immediate: immediate << 12,
+                }),
+            },
+            (0b01, 0b100) => {
+                let dest = c_rs1_prime(raw);
+                match (raw >> 10) & 0b11 {
+                    // C.SRLI, C.SRAI
+                    0b00 | 0b01 => {
+                        // > For RV32C, shamt[5] must be zero; the code points with shamt[5]=1 are
+                        // > designated for custom extensions.
+                        if (raw >> 12) & 0b1 != 0 {
+                            return Err(DecodeError::IllegalInstruction);
+                        }
+                        Ok(Self::OpShiftImm {
+                            op: match (raw >> 10) & 0b1 {
+                                0 => RegShiftImmOp::Srli,
+                                _ => RegShiftImmOp::Srai,
+                            },
+                            dest,
+                            src: dest,
+                            shift_amount_u5: c_imm(raw) as u32 & 0x1F,
+                        })
+                    }
+                    // C.ANDI
+                    0b10 => Ok(Self::OpImm {
+                        op: RegImmOp::Andi,
+                        dest,
+                        src: dest,
+                        immediate: c_imm(raw),
+                    }),
+                    _ => {
+                        let op = match ((raw >> 12) & 0b1, (raw >> 5) & 0b11) {
+                            (0, 0b00) => RegRegOp::Sub,
+                            (0, 0b01) => RegRegOp::Xor,
+                            (0, 0b10) => RegRegOp::Or,
+                            (0, 0b11) => RegRegOp::And,
+                            // C.SUBW and C.ADDW are RV64C-only, the rest is reserved.
+                            _ => return Err(DecodeError::IllegalInstruction),
+                        };
+                        Ok(Self::Op {
+                            op,
+                            dest,
+                            src1: dest,
+                            src2: c_rs2_prime(raw),
+                        })
+                    }
+                }
+            }
+            // C.J
+            (0b01, 0b101) => Ok(Self::Jal {
+                dest: Specifier::X0,
+                offset: c_j_imm(raw),
+            }),
+            // C.BEQZ, C.BNEZ
+            (0b01, 0b110 | 0b111) => Ok(Self::Branch {
+                condition: match c_funct3(raw) {
+                    0b110 => BranchCondition::Beq,
+                    _ => BranchCondition::Bne,
+                },
+                src1: c_rs1_prime(raw),
+                src2: Specifier::X0,
+                offset: c_b_imm(raw),
+            }),
+            // C.SLLI
+            (0b10, 0b000) => {
+                if (raw >> 12) & 0b1 != 0 {
+                    return Err(DecodeError::IllegalInstruction);
+                }
+                Ok(Self::OpShiftImm {
+                    op: RegShiftImmOp::Slli,
+                    dest: c_rd(raw),
+                    src: c_rd(raw),
+                    shift_amount_u5: c_imm(raw) as u32 & 0x1F,
+                })
+            }
+            // C.LWSP
+            (0b10, 0b010) => match c_rd(raw) {
+                Specifier::X0 => Err(DecodeError::IllegalInstruction),
+                dest => Ok(Self::Load {
+                    width: LoadWidth::Lw,
+                    dest,
+                    base: Specifier::SP,
+                    offset: c_lwsp_imm(raw),
+                }),
+            },
+            (0b10, 0b100) => {
+                let rs1 = c_rd(raw);
+                let rs2 = c_rs2(raw);
+                match ((raw >> 12) & 0b1, rs1, rs2) {
+                    // C.JR with rs1 == x0 is reserved.
+                    (0, Specifier::X0, Specifier::X0) => Err(DecodeError::IllegalInstruction),
+                    // C.JR
+                    (0, _, Specifier::X0) => Ok(Self::Jalr {
+                        dest: Specifier::X0,
+                        base: rs1,
+                        offset: 0,
+                    }),
+                    // C.MV
+                    (0, _, _) => Ok(Self::Op {
+                        op: RegRegOp::Add,
+                        dest: rs1,
+                        src1: Specifier::X0,
+                        src2: rs2,
+                    }),
+                    // C.EBREAK
+                    (_, Specifier::X0, Specifier::X0) => Ok(Self::Ebreak),
+                    // C.JALR
+                    (_, _, Specifier::X0) => Ok(Self::Jalr {
+                        dest: Specifier::RA,
+                        base: rs1,
+                        offset: 0,
+                    }),
+                    // C.ADD
+                    (_, _, _) => Ok(Self::Op {
+                        op: RegRegOp::Add,
+                        dest: rs1,
+                        src1: rs1,
+                        src2: rs2,
+                    }),
+                }
+            }
+            // C.SWSP
+            (0b10, 0b110) => Ok(Self::Store {
+                width: StoreWidth::Sw,
+                src: c_rs2(raw),
+                base: Specifier::SP,
+                offset: c_swsp_imm(raw),
+            }),
+            // C.FLDSP
+            (0b10, 0b001) => Ok(Self::LoadFp {
+                precision: FloatPrecision::Double,
+                dest: c_rd(raw),
+                base: Specifier::SP,
+                offset: c_ldsp_imm(raw),
+            }),
+            // C.FLWSP
+            (0b10, 0b011) => Ok(Self::LoadFp {
+                precision: FloatPrecision::Single,
+                dest: c_rd(raw),
+                base: Specifier::SP,
+                offset: c_lwsp_imm(raw),
+            }),
+            // C.FSDSP
+            (0b10, 0b101) => Ok(Self::StoreFp {
+                precision: FloatPrecision::Double,
+                src: c_rs2(raw),
+                base: Specifier::SP,
+                offset: c_sdsp_imm(raw),
+            }),
+            // C.FSWSP
+            (0b10, 0b111) => Ok(Self::StoreFp {
+                precision: FloatPrecision::Single,
+                src: c_rs2(raw),
+                base: Specifier::SP,
+                offset: c_swsp_imm(raw),
+            }),
+            // Quadrant 0, funct3 == 0b100 is reserved.
+            _ => Err(DecodeError::IllegalInstruction),
+        }
+    }
+}
+
+// TODO: Create either more decode errors or join this in to one, because the current variants are
+//       misleading! (i.e. they both indicate this is an unsupported encoding, which means it may
+//       be reserved, not implemented, part of another extension, intended for a coprocessor, etc.)
+#[derive(Error, Debug, Clone, Eq, PartialEq)]
+pub enum DecodeError {
+    #[error("instruction has unsupported opcode")]
+    UnsupportedOpcode,
+    #[error("illegal instruction")]
+    IllegalInstruction,
+}
+
+/// Returns the 7-bit *opcode* value of the instruction, or `None` if it isn't supported.
+fn opcode(raw_instruction: u32) -> Option<Opcode> {
+    #[allow(clippy::unusual_byte_groupings)]
+    match raw_instruction & 0x7F {
+        0b00_000_11 => Some(Opcode::Load),
+        0b00_001_11 => Some(Opcode::LoadFp),
+        // custom-0
+        0b00_011_11 => Some(Opcode::MiscMem),
+        0b00_100_11 => Some(Opcode::OpImm),
+        0b00_101_11 => Some(Opcode::Auipc),
+        // OP-IMM-32
+        // 48b
+        0b01_000_11 => Some(Opcode::Store),
+        0b01_001_11 => Some(Opcode::StoreFp),
+        // custom-1
+        // Amo = 0b01_011_11,
+        0b01_011_11 => Some(Opcode::Amo),
+        0b01_100_11 => Some(Opcode::Op),
+        0b01_101_11 => Some(Opcode::Lui),
+        // OP-32
+        // 64b
+        0b10_000_11 => Some(Opcode::Madd),
+        0b10_001_11 => Some(Opcode::Msub),
+        0b10_010_11 => Some(Opcode::Nmsub),
+        0b10_011_11 => Some(Opcode::Nmadd),
+        0b10_100_11 => Some(Opcode::OpFp),
+        // reserved
+        // custom-2/rv128
+        // 48b
+        0b11_000_11 => Some(Opcode::Branch),
+        0b11_001_11 => Some(Opcode::Jalr),
+        // reserved
+        0b11_011_11 => Some(Opcode::Jal),
+        0b11_100_11 => Some(Opcode::System),
+        // reserved
+        // custom-3/rv128
+        // >= 80b
+        _ => None,
+    }
+}
+
+/// Returns the 5-bit *rd* value for R-type, I-type, U-type, J-type instructions.
+fn rd(raw_instruction: u32) -> Specifier {
+    Specifier::from_u5(((raw_instruction >> 7) & 0x1F) as u8)
+}
+
+/// Returns the 5-bit *rs1* value for R-type, I-type, S-type, B-type instructions.
+fn rs1(raw_instruction: u32) -> Specifier {
+    Specifier::from_u5(((raw_instruction >> 15) & 0x1F) as u8)
+}
+
+/// Returns the 5-bit *rs2* value for R-type, S-type, B-type instructions.
+fn rs2(raw_instruction: u32) -> Specifier {
+    Specifier::from_u5(((raw_instruction >> 20) & 0x1F) as u8)
+}
+
+/// Returns the 5-bit *rs3* value for R4-type instructions.
+fn rs3(raw_instruction: u32) -> Specifier {
+    Specifier::from_u5((raw_instruction >> 27) as u8)
+}
+
+fn csr(raw_instruction: u32) -> CsrSpecifier {
+    (raw_instruction >> 20) as u16
+}
+
+fn i_funct(raw_instruction: u32) -> Option<RegImmOp> {
+    match funct3(raw_instruction) {
+        0b000 => Some(RegImmOp::Addi),
+        0b010 => Some(RegImmOp::Slti),
+        0b011 => Some(RegImmOp::Sltiu),
+        0b100 => Some(RegImmOp::Xori),
+        0b110 => Some(RegImmOp::Ori),
+        0b111 => Some(RegImmOp::Andi),
+        _ => None,
+    }
+}
+
+/// On RV32, the upper seven bits of the immediate select the shift-like operation, and the
+/// remaining five hold the shift amount or bit index.
+fn i_shfunct(raw_instruction: u32) -> Option<RegShiftImmOp> {
+    match (funct7(raw_instruction), funct3(raw_instruction)) {
+        (0b0000000, 0b001) => Some(RegShiftImmOp::Slli),
+        (0b0000000, 0b101) => Some(RegShiftImmOp::Srli),
+        (0b0100000, 0b101) => Some(RegShiftImmOp::Srai),
+        (0b0110000, 0b101) => Some(RegShiftImmOp::Rori),
+        (0b0100100, 0b001) => Some(RegShiftImmOp::Bclri),
+        (0b0100100, 0b101) => Some(RegShiftImmOp::Bexti),
+        (0b0110100, 0b001) => Some(RegShiftImmOp::Binvi),
+        (0b0010100, 0b001) => Some(RegShiftImmOp::Bseti),
+        _ => None,
+    }
+}
+
+/// The unary bit-manipulation instructions under OP-IMM use the whole 12-bit immediate as an
+/// additional function code, with the `rs2` position selecting the operation.
+fn i_unary_funct(raw_instruction: u32) -> Option<RegUnaryOp> {
+    let rs2 = (raw_instruction >> 20) & 0x1F;
+    match (funct7(raw_instruction), rs2, funct3(raw_instruction)) {
+        (0b0110000, 0b00000, 0b001) => Some(RegUnaryOp::Clz),
+        (0b0110000, 0b00001, 0b001) => Some(RegUnaryOp::Ctz),
+        (0b0110000, 0b00010, 0b001) => Some(RegUnaryOp::Cpop),
+        (0b0110000, 0b00100, 0b001) => Some(RegUnaryOp::SextB),
+        (0b0110000, 0b00101, 0b001) => Some(RegUnaryOp::SextH),
+        (0b0010100, 0b00111, 0b101) => Some(RegUnaryOp::OrcB),
+        (0b0110100, 0b11000, 0b101) => Some(RegUnaryOp::Rev8),
+        _ => None,
+    }
+}
+
+fn i_sys(raw_instruction: u32) -> Option<SysFunct> {
+    match funct3(raw_instruction) {
+        0b000 => Some(SysFunct::Priv),
+        0b001 => Some(SysFunct::Csrrw),
+        0b010 => Some(SysFunct::Csrrs),
+        0b011 => Some(SysFunct::Csrrc),
+        0b101 => Some(SysFunct::Csrrwi),
+        0b110 => Some(SysFunct::Csrrsi),
+        0b111 => Some(SysFunct::Csrrci),
+        _ => None,
+    }
+}
+
+fn sys_priv(raw_instruction: u32) -> Option<SysPriv> {
+    if u8::from(rd(raw_instruction)) != 0 {
+        return None;
+    }
+    if funct7(raw_instruction) == 0b0001001 {
+        return Some(SysPriv::SfenceVma);
+    }
+    if u8::from(rs1(raw_instruction)) != 0 {
+        return None;
+    }
+    let funct = funct12(raw_instruction);
+    if funct >> 11 != 0 {
+        // Custom SYSTEM instruction, but none are supported.
+        return None;
+    }
+    match funct {
+        0 => Some(SysPriv::Ecall),
+        1 => Some(SysPriv::Ebreak),
+        _ => match (funct7(raw_instruction), u8::from(rs2(raw_instruction))) {
+            (0b0001000, 2) => Some(SysPriv::Sret),
+            (0b0011000, 2) => Some(SysPriv::Mret),
+            (0b0001000, 5) => Some(SysPriv::Wfi),
+            _ => None,
+        },
+    }
+}
+
+fn i_mem(raw_instruction: u32) -> Option<MemFunct> {
+    match funct3(raw_instruction) {
+        0b000 => Some(MemFunct::Fence),
+        0b001 => Some(MemFunct::FenceI),
+        _ => None,
+    }
+}
+
+fn i_width(raw_instruction: u32) -> Option<LoadWidth> {
+    match funct3(raw_instruction) {
+        0b000 => Some(LoadWidth::Lb),
+        0b001 => Some(LoadWidth::Lh),
+        0b010 => Some(LoadWidth::Lw),
+        0b100 => Some(LoadWidth::Lbu),
+        0b101 => Some(LoadWidth::Lhu),
+        _ => None,
+    }
+}
+
+fn s_width(raw_instruction: u32) -> Option<StoreWidth> {
+    match funct3(raw_instruction) {
+        0b000 => Some(StoreWidth::Sb),
+        0b001 => Some(StoreWidth::Sh),
+        0b010 => Some(StoreWidth::Sw),
+        _ => None,
+    }
+}
+
+fn fp_width(raw_instruction: u32) -> Option<FloatPrecision> {
+    match funct3(raw_instruction) {
+        0b010 => Some(FloatPrecision::Single),
+        0b011 => Some(FloatPrecision::Double),
+        _ => None,
+    }
+}
+
+/// Returns the precision encoded in the 2-bit *fmt* field of floating-point instructions, or `None`
+/// for the unsupported half and quad precisions.
+fn fp_fmt(raw_instruction: u32) -> Option<FloatPrecision> {
+    match (raw_instruction >> 25) & 0b11 {
+        0b00 => Some(FloatPrecision::Single),
+        0b01 => Some(FloatPrecision::Double),
+        _ => None,
+    }
+}
+
+/// Returns the rounding mode encoded in the 3-bit *rm* field, where `None` indicates the dynamic
+/// rounding mode.
+///
+/// The reserved values `0b101` and `0b110` are illegal:
+///
+/// > If rm is set to an invalid value, an illegal instruction exception is raised.
+fn fp_rm(raw_instruction: u32) -> Result<Option<RoundingMode>, DecodeError> {
+    match funct3(raw_instruction) {
+        0b111 => Ok(None),
+        rm => RoundingMode::from_u3(rm)
+            .map(Some)
+            .ok_or(DecodeError::IllegalInstruction),
+    }
+}
+
+/// Decodes an instruction with the OP-FP major opcode.
+fn decode_op_fp(raw_instruction: u32) -> Result<Instruction, DecodeError> {
+    use DecodeError::IllegalInstruction;
+    let precision = fp_fmt(raw_instruction).ok_or(IllegalInstruction)?;
+    let dest = rd(raw_instruction);
+    let src1 = rs1(raw_instruction);
+    let src2 = rs2(raw_instruction);
+    let rs2 = u8::from(src2);
+    let rm = funct3(raw_instruction);
+    match (funct7(raw_instruction) >> 2, rs2, rm) {
+        (0b00000..=0b00011, _, _) => Ok(Instruction::OpFp {
+            op: match funct7(raw_instruction) >> 2 {
+                0b00000 => FloatOp::Add,
+                0b00001 => FloatOp::Sub,
+                0b00010 => FloatOp::Mul,
+                _ => FloatOp::Div,
+            },
+            precision,
+            rounding_mode: fp_rm(raw_instruction)?,
+            dest,
+            src1,
+            src2,
+        }),
+        (0b01011, 0, _) => Ok(Instruction::FloatSqrt {
+            precision,
+            rounding_mode: fp_rm(raw_instruction)?,
+            dest,
+            src: src1,
+        }),
+        (0b00100, _, 0b000..=0b010) => Ok(Instruction::FloatSignInject {
+            op: match rm {
+                0b000 => SignInjectOp::Sgnj,
+                0b001 => SignInjectOp::Sgnjn,
+                _ => SignInjectOp::Sgnjx,
+            },
+            precision,
+            dest,
+            src1,
+            src2,
+        }),
+        (0b00101, _, 0b000..=0b001) => Ok(Instruction::FloatMinMax {
+            op: match rm {
+                0b000 => MinMaxOp::Min,
+                _ => MinMaxOp::Max,
+            },
+            precision,
+            dest,
+            src1,
+            src2,
+        }),
+        // FCVT.S.D and FCVT.D.S, where rs2 holds the source format.
+        (0b01000, 0b00..=0b01, _) => {
+            let from = match rs2 {
+                0b00 => FloatPrecision::Single,
+                _ => FloatPrecision::Double,
+            };
+            if from == precision {
+                return Err(IllegalInstruction);
+            }
+            Ok(Instruction::FloatConvert {
+                from,
+                to: precision,
+                rounding_mode: fp_rm(raw_instruction)?,
+                dest,
+                src: src1,
+            })
+        }
+        (0b10100, _, 0b000..=0b010) => Ok(Instruction::FloatCompare {
+            op: match rm {
+                0b010 => FloatCompareOp::Eq,
+                0b001 => FloatCompareOp::Lt,
+                _ => FloatCompareOp::Le,
+            },
+            precision,
+            dest,
+            src1,
+            src2,
+        }),
+        (0b11000, 0b00..=0b01, _) => Ok(Instruction::FloatToInt {
+            signed: rs2 == 0,
+            precision,
+            rounding_mode: fp_rm(raw_instruction)?,
+            dest,
+            src: src1,
+        }),
+        (0b11010, 0b00..=0b01, _) => Ok(Instruction::IntToFloat {
+            signed: rs2 == 0,
+            precision,
+            rounding_mode: fp_rm(raw_instruction)?,
+            dest,
+            src: src1,
+        }),
+        (0b11100, 0, 0b000) if precision == FloatPrecision::Single => {
+            Ok(Instruction::FloatMoveToInt { dest, src: src1 })
+        }
+        (0b11100, 0, 0b001) => Ok(Instruction::FloatClassify {
+            precision,
+            dest,
+            src: src1,
+        }),
+        (0b11110, 0, 0b000) if precision == FloatPrecision::Single => {
+            Ok(Instruction::FloatMoveFromInt { dest, src: src1 })
+        }
+        _ => Err(IllegalInstruction),
+    }
+}
+
+fn r_funct(raw_instruction: u32) -> Option<RegRegOp> {
+    match (funct7(raw_instruction), funct3(raw_instruction)) {
+        (0b0000000, 0b000) => Some(RegRegOp::Add),
+        (0b0000000, 0b001) => Some(RegRegOp::Sll),
+        (0b0000000, 0b010) => Some(RegRegOp::Slt),
+        (0b0000000, 0b011) => Some(RegRegOp::Sltu),
+        (0b0000000, 0b100) => Some(RegRegOp::Xor),
+        (0b0000000, 0b101) => Some(RegRegOp::Srl),
+        (0b0000000, 0b110) => Some(RegRegOp::Or),
+        (0b0000000, 0b111) => Some(RegRegOp::And),
+        (0b0100000, 0b000) => Some(RegRegOp::Sub),
+        (0b0100000, 0b101) => Some(RegRegOp::Sra),
+        // funct7 == MULDIV
+        (0b0000001, 0b000) => Some(RegRegOp::Mul),
+        (0b0000001, 0b001) => Some(RegRegOp::Mulh),
+        (0b0000001, 0b010) => Some(RegRegOp::Mulhsu),
+        (0b0000001, 0b011) => Some(RegRegOp::Mulhu),
+        (0b0000001, 0b100) => Some(RegRegOp::Div),
+        (0b0000001, 0b101) => Some(RegRegOp::Divu),
+        (0b0000001, 0b110) => Some(RegRegOp::Rem),
+        (0b0000001, 0b111) => Some(RegRegOp::Remu),
+        // Zba
+        (0b0010000, 0b010) => Some(RegRegOp::Sh1add),
+        (0b0010000, 0b100) => Some(RegRegOp::Sh2add),
+        (0b0010000, 0b110) => Some(RegRegOp::Sh3add),
+        // Zbb
+        (0b0100000, 0b111) => Some(RegRegOp::Andn),
+        (0b0100000, 0b110) => Some(RegRegOp::Orn),
+        (0b0100000, 0b100) => Some(RegRegOp::Xnor),
+        (0b0000101, 0b110) => Some(RegRegOp::Max),
+        (0b0000101, 0b111) => Some(RegRegOp::Maxu),
+        (0b0000101, 0b100) => Some(RegRegOp::Min),
+        (0b0000101, 0b101) => Some(RegRegOp::Minu),
+        (0b0110000, 0b001) => Some(RegRegOp::Rol),
+        (0b0110000, 0b101) => Some(RegRegOp::Ror),
+        // Zbc
+        (0b0000101, 0b001) => Some(RegRegOp::Clmul),
+        (0b0000101, 0b011) => Some(RegRegOp::Clmulh),
+        (0b0000101, 0b010) => Some(RegRegOp::Clmulr),
+        // Zbs
+        (0b0100100, 0b001) => Some(RegRegOp::Bclr),
+        (0b0100100, 0b101) => Some(RegRegOp::Bext),
+        (0b0110100, 0b001) => Some(RegRegOp::Binv),
+        (0b0010100, 0b001) => Some(RegRegOp::Bset),
+        _ => None,
+    }
+}
+
+fn b_funct(raw_instruction: u32) -> Option<BranchCondition> {
+    match funct3(raw_instruction) {
+        0b000 => Some(BranchCondition::Beq),
+        0b001 => Some(BranchCondition::Bne),
+        0b100 => Some(BranchCondition::Blt),
+        0b101 => Some(BranchCondition::Bge),
+        0b110 => Some(BranchCondition::Bltu),
+        0b111 => Some(BranchCondition::Bgeu),
+        _ => None,
+    }
+}
+
+fn amo_op(raw_instruction: u32) -> Option<AmoOp> {
+    if funct3(raw_instruction) != 0b010 {
+        return None;
+    }
+    match funct7(raw_instruction) >> 2 {
+        0b00010 => Some(AmoOp::Lr),
+        0b00011 => Some(AmoOp::Sc),
+        0b00001 => Some(AmoOp::Swap),
+        0b00000 => Some(AmoOp::Add),
+        0b00100 => Some(AmoOp::Xor),
+        0b01100 => Some(AmoOp::And),
+        0b01000 => Some(AmoOp::Or),
+        0b10000 => Some(AmoOp::Min),
+        0b10100 => Some(AmoOp::Max),
+        0b11000 => Some(AmoOp::Minu),
+        0b11100 => Some(AmoOp::Maxu),
+        _ => None,
+    }
+}
+
+fn amo_rl(raw_instruction: u32) -> bool {
+    (raw_instruction >> 25) & 0b1 == 1
+}
+
+fn amo_aq(raw_instruction: u32) -> bool {
+    (raw_instruction >> 26) & 0b1 == 1
+}
+
+/// Returns the 3-bit *funct3* value for R-type, I-type, S-type, B-type instructions.
+fn funct3(raw_instruction: u32) -> u8 {
+    ((raw_instruction >> 12) & 0b111) as u8
+}
+
+/// Returns the 7-bit *funct7* value for R-type instructions.
+fn funct7(raw_instruction: u32) -> u8 {
+    (raw_instruction >> 25) as u8
+}
+
+/// Returns the 5-bit *shamt* value for S-type shift instructions.
+fn shamt(raw_instruction: u32) -> u32 {
+    (raw_instruction >> 20) & 0x1F
+}
+
+/// Returns the 12-bit I-immediate sign-extended to 32 bits.
+fn i_imm(raw_instruction: u32) -> i32 {
+    raw_instruction as i32 >> 20
+}
+
+/// Returns the 12-bit I-immediate zero-extended to 32 bits.
+fn funct12(raw_instruction: u32) -> u32 {
+    raw_instruction >> 20
+}
+
+/// Returns the 12-bit S-immediate sign-extended to 32 bits.
+fn s_imm(raw_instruction: u32) -> i32 {
+    let imm_11_5 = raw_instruction & 0xFE00_0000;
+    let imm_4_0 = raw_instruction & 0x0000_0F80;
+    (imm_11_5 | (imm_4_0 << 13)) as i32 >> 20
+}
+
+/// Returns the 13-bit B-immediate sign-extended to 32 bits.
+fn b_imm(raw_instruction: u32) -> i32 {
+    let imm_12 = raw_instruction & 0x8000_0000;
+    let imm_10_5 = raw_instruction & 0x7E00_0000;
+    let imm_4_1 = raw_instruction & 0x0000_0F00;
+    let imm_11 = raw_instruction & 0x0000_0080;
+    (imm_12 | (imm_11 << 23) | (imm_10_5 >> 1) | (imm_4_1 << 12)) as i32 >> 19
+}
+
+/// Returns the signed 32-bit U-immediate.
+fn u_imm(raw_instruction: u32) -> i32 {
+    (raw_instruction & 0xFFFF_F000) as i32
+}
+
+/// Returns the 21-bit J-immediate sign-extended to 32 bits.
+fn j_imm(raw_instruction: u32) -> i32 {
+    let imm_20 = raw_instruction & 0x8000_0000;
+    let imm_10_1 = raw_instruction & 0x7FE0_0000;
+    let imm_11 = raw_instruction & 0x0010_0000;
+    let imm_19_12 = raw_instruction & 0x000F_F000;
+    (imm_20 | (imm_19_12 << 11) | (imm_11 << 2) | (imm_10_1 >> 9)) as i32 >> 11
+}
+
+/// Returns the 3-bit *funct3* value of a compressed instruction.
+fn c_funct3(raw_instruction: u16) -> u8 {
+    (raw_instruction >> 13) as u8
+}
+
+/// Returns the 5-bit *rd*/*rs1* value for CR-type and CI-type compressed instructions.
+fn c_rd(raw_instruction: u16) -> Specifier {
+    Specifier::from_u5(((raw_instruction >> 7) & 0x1F) as u8)
+}
+
+/// Returns the 5-bit *rs2* value for CR-type and CSS-type compressed instructions.
+fn c_rs2(raw_instruction: u16) -> Specifier {
+    Specifier::from_u5(((raw_instruction >> 2) & 0x1F) as u8)
+}
+
+/// Returns the 3-bit *rs1'*/*rd'* value mapped to the corresponding register `x8`-`x15`.
+fn c_rs1_prime(raw_instruction: u16) -> Specifier {
+    Specifier::from_u5(8 + ((raw_instruction >> 7) & 0b111) as u8)
+}
+
+/// Returns the 3-bit *rs2'*/*rd'* value mapped to the corresponding register `x8`-`x15`.
+fn c_rs2_prime(raw_instruction: u16) -> Specifier {
+    Specifier::from_u5(8 + ((raw_instruction >> 2) & 0b111) as u8)
+}
+
+/// Returns the 6-bit CI-immediate (`imm[5]` at bit 12, `imm[4:0]` at bits 6:2) sign-extended to 32
+/// bits.
+fn c_imm(raw_instruction: u16) -> i32 {
+    let imm_5 = (raw_instruction >> 7) & 0x20;
+    let imm_4_0 = (raw_instruction >> 2) & 0x1F;
+    (((imm_5 | imm_4_0) as i32) << 26) >> 26
+}
+
+/// Returns the zero-extended, scaled 10-bit immediate of `c.addi4spn`.
+fn c_addi4spn_imm(raw_instruction: u16) -> i32 {
+    let raw = raw_instruction as i32;
+    let imm_5_4 = (raw >> 7) & 0x30;
+    let imm_9_6 = (raw >> 1) & 0x3C0;
+    let imm_2 = (raw >> 4) & 0x4;
+    let imm_3 = (raw >> 2) & 0x8;
+    imm_9_6 | imm_5_4 | imm_3 | imm_2
+}
+
+/// Returns the sign-extended, scaled 10-bit immediate of `c.addi16sp`.
+fn c_addi16sp_imm(raw_instruction: u16) -> i32 {
+    let raw = raw_instruction as i32;
+    let imm_9 = (raw >> 3) & 0x200;
+    let imm_4 = (raw >> 2) & 0x10;
+    let imm_6 = (raw << 1) & 0x40;
+    let imm_8_7 = (raw << 4) & 0x180;
+    let imm_5 = (raw << 3) & 0x20;
+    ((imm_9 | imm_8_7 | imm_6 | imm_5 | imm_4) << 22) >> 22
+}
+
+/// Returns the zero-extended, scaled 7-bit offset of `c.lw` and `c.sw`.
+fn c_lw_imm(raw_instruction: u16) -> i32 {
+    let raw = raw_instruction as i32;
+    let imm_5_3 = (raw >> 7) & 0x38;
+    let imm_2 = (raw >> 4) & 0x4;
+    let imm_6 = (raw << 1) & 0x40;
+    imm_6 | imm_5_3 | imm_2
+}
+
+/// Returns the zero-extended, scaled 8-bit offset of `c.fld` and `c.fsd`.
+fn c_ld_imm(raw_instruction: u16) -> i32 {
+    let raw = raw_instruction as i32;
+    let imm_5_3 = (raw >> 7) & 0x38;
+    let imm_7_6 = (raw << 1) & 0xC0;
+    imm_7_6 | imm_5_3
+}
+
+/// Returns the zero-extended, scaled 8-bit offset of `c.lwsp`.
+fn c_lwsp_imm(raw_instruction: u16) -> i32 {
+    let raw = raw_instruction as i32;
+    let imm_5 = (raw >> 7) & 0x20;
+    let imm_4_2 = (raw >> 2) & 0x1C;
+    let imm_7_6 = (raw << 4) & 0xC0;
+    imm_7_6 | imm_5 | imm_4_2
+}
+
+/// Returns the zero-extended, scaled 8-bit offset of `c.swsp`.
+fn c_swsp_imm(raw_instruction: u16) -> i32 {
+    let raw = raw_instruction as i32;
+    let imm_5_2 = (raw >> 7) & 0x3C;
+    let imm_7_6 = (raw >> 1) & 0xC0;
+    imm_7_6 | imm_5_2
+}
+
+/// Returns the zero-extended, scaled 9-bit offset of `c.fldsp`.
+fn c_ldsp_imm(raw_instruction: u16) -> i32 {
+    let raw = raw_instruction as i32;
+    let imm_5 = (raw >> 7) & 0x20;
+    let imm_4_3 = (raw >> 2) & 0x18;
+    let imm_8_6 = (raw << 4) & 0x1C0;
+    imm_8_6 | imm_5 | imm_4_3
+}
+
+/// Returns the zero-extended, scaled 9-bit offset of `c.fsdsp`.
+fn c_sdsp_imm(raw_instruction: u16) -> i32 {
+    let raw = raw_instruction as i32;
+    let imm_5_3 = (raw >> 7) & 0x38;
+    let imm_8_6 = (raw >> 1) & 0x1C0;
+    imm_8_6 | imm_5_3
+}
+
+/// Returns the 12-bit CJ-immediate of `c.j` and `c.jal` sign-extended to 32 bits.
+fn c_j_imm(raw_instruction: u16) -> i32 {
+    let raw = raw_instruction as i32;
+    let imm_11 = (raw >> 1) & 0x800;
+    let imm_4 = (raw >> 7) & 0x10;
+    let imm_9_8 = (raw >> 1) & 0x300;
+    let imm_10 = (raw << 2) & 0x400;
+    let imm_6 = (raw >> 1) & 0x40;
+    let imm_7 = (raw << 1) & 0x80;
+    let imm_3_1 = (raw >> 2) & 0xE;
+    let imm_5 = (raw << 3) & 0x20;
+    let imm = imm_11 | imm_10 | imm_9_8 | imm_7 | imm_6 | imm_5 | imm_4 | imm_3_1;
+    (imm << 20) >> 20
+}
+
+/// Returns the 9-bit CB-immediate of `c.beqz` and `c.bnez` sign-extended to 32 bits.
+fn c_b_imm(raw_instruction: u16) -> i32 {
+    let raw = raw_instruction as i32;
+    let imm_8 = (raw >> 4) & 0x100;
+    let imm_4_3 = (raw >> 7) & 0x18;
+    let imm_7_6 = (raw << 1) & 0xC0;
+    let imm_2_1 = (raw >> 2) & 0x6;
+    let imm_5 = (raw << 3) & 0x20;
+    ((imm_8 | imm_7_6 | imm_5 | imm_4_3 | imm_2_1) << 23) >> 23
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Opcode {
+    OpImm,
+    Auipc,
+    Lui,
+    Amo,
+    Op,
+    Jal,
+    Jalr,
+    Branch,
+    Load,
+    Store,
+    MiscMem,
+    System,
+    LoadFp,
+    StoreFp,
+    Madd,
+    Msub,
+    Nmsub,
+    Nmadd,
+    OpFp,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum SysFunct {
+    Priv,
+    Csrrw,
+    Csrrs,
+    Csrrc,
+    Csrrwi,
+    Csrrsi,
+    Csrrci,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum SysPriv {
+    Ecall,
+    Ebreak,
+    Sret,
+    Mret,
+    Wfi,
+    SfenceVma,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum MemFunct {
+    Fence,
+    FenceI,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_i_imm() {
+        assert_eq!(0, i_imm(0x0000_0000));
+        assert_eq!(-1, i_imm(0xFFF0_0000));
+        assert_eq!(2047, i_imm(2047 << 20));
+        assert_eq!(-2048, i_imm(0x8000_0000));
+        assert_eq!(-42, i_imm((-42_i32 << 20) as u32));
+        // Check other bits are ignored
+        assert_eq!(0, i_imm(0x000F_FFFF));
+        assert_eq!(-1, i_imm(0xFFF1_2345));
+        assert_eq!(1209, i_imm((1209 << 20) | 0x000C_D10A));
+    }
+
+    fn x(index: u8) -> Specifier {
+        Specifier::new(index).unwrap()
+    }
+
+    #[test]
+    fn test_instruction_length() {
+        assert_eq!(2, instruction_length(0x0001));
+        assert_eq!(2, instruction_length(0xFFFF_8082));
+        assert_eq!(4, instruction_length(0x0000_0013));
+    }
+
+    #[test]
+    fn test_decode_compressed() {
+        let cases = [
+            // c.addi4spn x8, 16
+            (
+                0x0800,
+                Instruction::OpImm {
+                    op: RegImmOp::Addi,
+                    dest: x(8),
+                    src: x(2),
+                    immediate: 16,
+                },
+            ),
+            // c.lw a0, 4(a1)
+            (
+                0x41C8,
+                Instruction::Load {
+                    width: LoadWidth::Lw,
+                    dest: x(10),
+                    base: x(11),
+                    offset: 4,
+                },
+            ),
+            // c.nop
+            (
+                0x0001,
+                Instruction::OpImm {
+                    op: RegImmOp::Addi,
+                    dest: x(0),
+                    src: x(0),
+                    immediate: 0,
+                },
+            ),
+            // c.li a0, -1
+            (
+                0x557D,
+                Instruction::OpImm {
+                    op: RegImmOp::Addi,
+                    dest: x(10),
+                    src: x(0),
+                    immediate: -1,
+                },
+            ),
+            // c.addi16sp -16
+            (
+                0x717D,
+                Instruction::OpImm {
+                    op: RegImmOp::Addi,
+                    dest: x(2),
+                    src: x(2),
+                    immediate: -16,
+                },
+            ),
+            // c.lui a0, 0xfffff
+            (
+                0x757D,
+                Instruction::Lui {
+                    dest: x(10),
+                    immediate: -4096,
+                },
+            ),
+            // c.sub s0, s1
+            (
+                0x8C05,
+                Instruction::Op {
+                    op: RegRegOp::Sub,
+                    dest: x(8),
+                    src1: x(8),
+                    src2: x(9),
+                },
+            ),
+            // c.j -2
+            (
+                0xBFFD,
+                Instruction::Jal {
+                    dest: x(0),
+                    offset: -2,
+                },
+            ),
+            // c.beqz s0, -2
+            (
+                0xDC7D,
+                Instruction::Branch {
+                    condition: BranchCondition::Beq,
+                    src1: x(8),
+                    src2: x(0),
+                    offset: -2,
+                },
+            ),
+            // c.lwsp a0, 12(sp)
+            (
+                0x4532,
+                Instruction::Load {
+                    width: LoadWidth::Lw,
+                    dest: x(10),
+                    base: x(2),
+                    offset: 12,
+                },
+            ),
+            // c.jr ra
+            (
+                0x8082,
+                Instruction::Jalr {
+                    dest: x(0),
+                    base: x(1),
+                    offset: 0,
+                },
+            ),
+            // c.mv a0, a1
+            (
+                0x852E,
+                Instruction::Op {
+                    op: RegRegOp::Add,
+                    dest: x(10),
+                    src1: x(0),
+                    src2: x(11),
+                },
+            ),
+            // c.ebreak
+            (0x9002, Instruction::Ebreak),
+            // c.jalr a0
+            (
+                0x9502,
+                Instruction::Jalr {
+                    dest: x(1),
+                    base: x(10),
+                    offset: 0,
+                },
+            ),
+            // c.add a0, a1
+            (
+                0x952E,
+                Instruction::Op {
+                    op: RegRegOp::Add,
+                    dest: x(10),
+                    src1: x(10),
+                    src2: x(11),
+                },
+            ),
+            // c.swsp a0, 12(sp)
+            (
+                0xC62A,
+                Instruction::Store {
+                    width: StoreWidth::Sw,
+                    src: x(10),
+                    base: x(2),
+                    offset: 12,
+                },
+            ),
+        ];
+        for (raw, expected) in cases {
+            assert_eq!(
+                Ok(expected),
+                Instruction::decode_compressed(raw),
+                "{raw:#06x}"
+            );
+            // The upper half of a 32-bit value must be ignored for compressed instructions.
+            assert_eq!(Ok(expected), Instruction::decode(0xABCD_0000 | raw as u32));
+        }
+    }
+
+    #[test]
+    fn test_decode_compressed_illegal() {
+        // Defined illegal instruction
+        assert_eq!(
+            Err(DecodeError::IllegalInstruction),
+            Instruction::decode_compressed(0x0000)
+        );
+        // c.lwsp with rd == x0 is reserved
+        assert_eq!(
+            Err(DecodeError::IllegalInstruction),
+            Instruction::decode_compressed(0x4002)
+        );
+        // c.jr with rs1 == x0 is reserved
+        assert_eq!(
+            Err(DecodeError::IllegalInstruction),
+            Instruction::decode_compressed(0x8002)
+        );
+        // c.slli with shamt[5] == 1 is reserved for custom extensions on RV32
+        assert_eq!(
+            Err(DecodeError::IllegalInstruction),
+            Instruction::decode_compressed(0x1502)
+        );
+    }
+
+    #[test]
+    fn test_decode_compressed_fp() {
+        // c.fld f8, 8(x9)
+        assert_eq!(
+            Ok(Instruction::LoadFp {
+                precision: FloatPrecision::Double,
+                dest: Specifier::from_u5(8),
+                base: Specifier::from_u5(9),
+                offset: 8,
+            }),
+            Instruction::decode_compressed(0x2480)
+        );
+        // c.fsw f10, 4(x11)
+        assert_eq!(
+            Ok(Instruction::StoreFp {
+                precision: FloatPrecision::Single,
+                src: Specifier::from_u5(10),
+                base: Specifier::from_u5(11),
+                offset: 4,
+            }),
+            Instruction::decode_compressed(0xE1C8)
+        );
+        // c.fldsp f1, 264(sp)
+        assert_eq!(
+            Ok(Instruction::LoadFp {
+                precision: FloatPrecision::Double,
+                dest: Specifier::from_u5(1),
+                base: Specifier::SP,
+                offset: 264,
+            }),
+            Instruction::decode_compressed(0x20B2)
+        );
+        // c.fsdsp f2, 264(sp)
+        assert_eq!(
+            Ok(Instruction::StoreFp {
+                precision: FloatPrecision::Double,
+                src: Specifier::from_u5(2),
+                base: Specifier::SP,
+                offset: 264,
+            }),
+            Instruction::decode_compressed(0xA60A)
+        );
+    }
+
+    #[test]
+    fn test_decode_fp() {
+        let f = Specifier::from_u5;
+        // fadd.d f1, f2, f3, rne
+        assert_eq!(
+            Ok(Instruction::OpFp {
+                op: FloatOp::Add,
+                precision: FloatPrecision::Double,
+                rounding_mode: Some(RoundingMode::Rne),
+                dest: f(1),
+                src1: f(2),
+                src2: f(3),
+            }),
+            Instruction::decode(0x023100D3)
+        );
+        // fmadd.s f1, f2, f3, f4 (dynamic rounding)
+        assert_eq!(
+            Ok(Instruction::FusedMultiplyAdd {
+                op: FusedMultiplyAddOp::Madd,
+                precision: FloatPrecision::Single,
+                rounding_mode: None,
+                dest: f(1),
+                src1: f(2),
+                src2: f(3),
+                src3: f(4),
+            }),
+            Instruction::decode(0x203170C3)
+        );
+        // flw f1, 16(x2)
+        assert_eq!(
+            Ok(Instruction::LoadFp {
+                precision: FloatPrecision::Single,
+                dest: f(1),
+                base: f(2),
+                offset: 16,
+            }),
+            Instruction::decode(0x01012087)
+        );
+        // fsd f3, -8(x2)
+        assert_eq!(
+            Ok(Instruction::StoreFp {
+                precision: FloatPrecision::Double,
+                src: f(3),
+                base: f(2),
+                offset: -8,
+            }),
+            Instruction::decode(0xFE313C27)
+        );
+        // fcvt.s.d f1, f2
+        assert_eq!(
+            Ok(Instruction::FloatConvert {
+                from: FloatPrecision::Double,
+                to: FloatPrecision::Single,
+                rounding_mode: None,
+                dest: f(1),
+                src: f(2),
+            }),
+            Instruction::decode(0x401170D3)
+        );
+        // fcvt.wu.d x10, f1, rtz
+        assert_eq!(
+            Ok(Instruction::FloatToInt {
+                signed: false,
+                precision: FloatPrecision::Double,
+                rounding_mode: Some(RoundingMode::Rtz),
+                dest: f(10),
+                src: f(1),
+            }),
+            Instruction::decode(0xC2109553)
+        );
+        // feq.s x10, f1, f2
+        assert_eq!(
+            Ok(Instruction::FloatCompare {
+                op: FloatCompareOp::Eq,
+                precision: FloatPrecision::Single,
+                dest: f(10),
+                src1: f(1),
+                src2: f(2),
+            }),
+            Instruction::decode(0xA020A553)
+        );
+        // fmv.x.w x10, f1
+        assert_eq!(
+            Ok(Instruction::FloatMoveToInt {
+                dest: f(10),
+                src: f(1),
+            }),
+            Instruction::decode(0xE0008553)
+        );
+        // fclass.d x10, f1
+        assert_eq!(
+            Ok(Instruction::FloatClassify {
+                precision: FloatPrecision::Double,
+                dest: f(10),
+                src: f(1),
+            }),
+            Instruction::decode(0xE2009553)
+        );
+    }
+
+    #[test]
+    fn test_decode_fp_illegal() {
+        // fadd.s with reserved rounding mode 0b101
+        assert_eq!(
+            Err(DecodeError::IllegalInstruction),
+            Instruction::decode(0x003150D3)
+        );
+        // fadd.h is not supported
+        assert_eq!(
+            Err(DecodeError::IllegalInstruction),
+            Instruction::decode(0x043100D3)
+        );
+        // fmv.x.d does not exist on RV32
+        assert_eq!(
+            Err(DecodeError::IllegalInstruction),
+            Instruction::decode(0xE2008553)
+        );
+        // fcvt.s.s does not exist
+        assert_eq!(
+            Err(DecodeError::IllegalInstruction),
+            Instruction::decode(0x400170D3)
+        );
+    }
+
+    #[test]
+    fn test_decode_bitmanip() {
+        // <op> a0, a1, a2
+        let reg_reg = [
+            (0x20C5_A533, RegRegOp::Sh1add),
+            (0x20C5_C533, RegRegOp::Sh2add),
+            (0x20C5_E533, RegRegOp::Sh3add),
+            (0x40C5_F533, RegRegOp::Andn),
+            (0x40C5_E533, RegRegOp::Orn),
+            (0x40C5_C533, RegRegOp::Xnor),
+            (0x0AC5_E533, RegRegOp::Max),
+            (0x0AC5_F533, RegRegOp::Maxu),
+            (0x0AC5_C533, RegRegOp::Min),
+            (0x0AC5_D533, RegRegOp::Minu),
+            (0x60C5_9533, RegRegOp::Rol),
+            (0x60C5_D533, RegRegOp::Ror),
+            (0x0AC5_9533, RegRegOp::Clmul),
+            (0x0AC5_B533, RegRegOp::Clmulh),
+            (0x0AC5_A533, RegRegOp::Clmulr),
+            (0x48C5_9533, RegRegOp::Bclr),
+            (0x48C5_D533, RegRegOp::Bext),
+            (0x68C5_9533, RegRegOp::Binv),
+            (0x28C5_9533, RegRegOp::Bset),
+        ];
+        for (raw, op) in reg_reg {
+            let expected = Instruction::Op {
+                op,
+                dest: x(10),
+                src1: x(11),
+                src2: x(12),
+            };
+            assert_eq!(Ok(expected), Instruction::decode(raw), "{raw:#010x}");
+        }
+        // <op> a0, a1, shamt
+        let shift_imm = [
+            (0x6075_D513, RegShiftImmOp::Rori, 7),
+            (0x49F5_9513, RegShiftImmOp::Bclri, 31),
+            (0x4835_D513, RegShiftImmOp::Bexti, 3),
+            (0x6805_9513, RegShiftImmOp::Binvi, 0),
+            (0x2915_9513, RegShiftImmOp::Bseti, 17),
+        ];
+        for (raw, op, shift_amount_u5) in shift_imm {
+            let expected = Instruction::OpShiftImm {
+                op,
+                dest: x(10),
+                src: x(11),
+                shift_amount_u5,
+            };
+            assert_eq!(Ok(expected), Instruction::decode(raw), "{raw:#010x}");
+        }
+        // <op> a0, a1
+        let unary = [
+            (0x6005_9513, RegUnaryOp::Clz),
+            (0x6015_9513, RegUnaryOp::Ctz),
+            (0x6025_9513, RegUnaryOp::Cpop),
+            (0x6045_9513, RegUnaryOp::SextB),
+            (0x6055_9513, RegUnaryOp::SextH),
+            (0x0805_C533, RegUnaryOp::ZextH),
+            (0x2875_D513, RegUnaryOp::OrcB),
+            (0x6985_D513, RegUnaryOp::Rev8),
+        ];
+        for (raw, op) in unary {
+            let expected = Instruction::OpUnary {
+                op,
+                dest: x(10),
+                src: x(11),
+            };
+            assert_eq!(Ok(expected), Instruction::decode(raw), "{raw:#010x}");
+        }
+    }
+
+    #[test]
+    fn test_decode_bitmanip_illegal() {
+        // slli with shamt[5] set is reserved on RV32
+        assert_eq!(
+            Err(DecodeError::IllegalInstruction),
+            Instruction::decode(0x0205_9513)
+        );
+        // clz with a nonzero rs2 field
+        assert_eq!(
+            Err(DecodeError::IllegalInstruction),
+            Instruction::decode(0x6035_9513)
+        );
+        // zext.h with a nonzero rs2 field
+        assert_eq!(
+            Err(DecodeError::IllegalInstruction),
+            Instruction::decode(0x0815_C533)
+        );
+    }
+}
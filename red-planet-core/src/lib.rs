@@ -1,6 +1,7 @@
 #[macro_use]
 extern crate static_assertions;
 
+use serde::Deserialize;
 use std::cmp::Ordering;
 use std::fmt;
 use thiserror::Error;
@@ -10,6 +11,7 @@ pub mod address_range;
 pub mod board;
 pub mod bus;
 pub mod core;
+pub mod float;
 pub mod instruction;
 pub mod interrupt;
 pub mod registers;
@@ -253,7 +255,8 @@ impl Alignment {
 }
 
 /// Sum type for the two possible byte orders: big-endian or little-endian.
-#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Endianness {
     /// Little-endian (least significant byte at lowest address)
     LE,
@@ -1,4 +1,4 @@
-//! General purpose registers, unallocated.
+//! General purpose and floating-point registers, unallocated.
 
 use core::fmt;
 use std::fmt::Formatter;
@@ -84,6 +84,107 @@ impl Registers {
     }
 }
 
+/// The type of a single `f` register. Wide enough to hold a double-precision value (FLEN=64).
+pub type F = u64;
+
+/// A RISC-V core's floating-point registers, as added by the F and D extensions, together with the
+/// floating-point control and status register `fcsr`.
+///
+/// > The F extension adds 32 floating-point registers, f0–f31, each 32 bits wide, and a
+/// > floating-point control and status register fcsr, which contains the operating mode and
+/// > exception status of the floating-point unit.
+/// >
+/// > The D extension widens the 32 floating-point registers, f0–f31, to 64 bits (FLEN=64).
+///
+/// Single-precision values are stored NaN-boxed:
+///
+/// > When multiple floating-point precisions are supported, then valid values of narrower n-bit
+/// > types, n<FLEN, are represented in the lower n bits of an FLEN-bit NaN value, in a process
+/// > termed NaN-boxing. The upper bits of a valid NaN-boxed value must be all 1s.
+///
+/// The `fcsr` fields are kept here rather than in a separate allocation, since nearly every
+/// floating-point instruction that writes an `f` register also accrues exception flags.
+#[derive(Debug, Clone, Default)]
+pub struct FloatRegisters {
+    f_registers: [F; LEN as usize],
+    /// Accrued exception flags (`fflags`), 5 bits.
+    fflags: u8,
+    /// Dynamic rounding mode (`frm`), 3 bits.
+    frm: u8,
+}
+
+impl FloatRegisters {
+    /// Upper half of a NaN-boxed single-precision value.
+    const NAN_BOX: u64 = 0xFFFF_FFFF_0000_0000;
+
+    /// The canonical single-precision NaN.
+    const CANONICAL_NAN_F32: u32 = 0x7FC0_0000;
+
+    /// Returns a fresh set of all-zero floating-point registers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the raw 64-bit value of an `f` register.
+    pub fn f(&self, specifier: Specifier) -> F {
+        self.f_registers[usize::from(specifier)]
+    }
+
+    /// Sets the raw 64-bit value of an `f` register.
+    pub fn set_f(&mut self, specifier: Specifier, value: F) {
+        self.f_registers[usize::from(specifier)] = value;
+    }
+
+    /// Returns the single-precision value held in an `f` register.
+    ///
+    /// > Floating-point n-bit transfer operations move external values held in IEEE standard
+    /// > formats into and out of the f registers, and comprise floating-point loads and stores
+    /// > (FLn/FSn) and floating-point move instructions (FMV.n.X/FMV.X.n). [...] Apart from
+    /// > transfer operations described in the previous paragraph, all other floating-point
+    /// > operations on narrower n-bit operations, n<FLEN, check if the input operands are
+    /// > correctly NaN-boxed, i.e., all upper FLEN-n bits are 1. If so, the n least-significant
+    /// > bits of the input are used as the input value, otherwise the input value is treated as an
+    /// > n-bit canonical NaN.
+    pub fn f32(&self, specifier: Specifier) -> u32 {
+        let value = self.f(specifier);
+        if value & Self::NAN_BOX == Self::NAN_BOX {
+            value as u32
+        } else {
+            Self::CANONICAL_NAN_F32
+        }
+    }
+
+    /// Sets an `f` register to a NaN-boxed single-precision value.
+    pub fn set_f32(&mut self, specifier: Specifier, value: u32) {
+        self.set_f(specifier, Self::NAN_BOX | value as u64);
+    }
+
+    /// Returns the accrued exception flags (`fflags`).
+    pub fn fflags(&self) -> u8 {
+        self.fflags
+    }
+
+    /// Sets the accrued exception flags (`fflags`). Only the lower 5 bits are used.
+    pub fn set_fflags(&mut self, value: u8) {
+        self.fflags = value & 0b1_1111;
+    }
+
+    /// Accrues the given exception flags, ORing them into `fflags`.
+    pub fn accrue_fflags(&mut self, flags: u8) {
+        self.set_fflags(self.fflags | flags);
+    }
+
+    /// Returns the dynamic rounding mode field (`frm`).
+    pub fn frm(&self) -> u8 {
+        self.frm
+    }
+
+    /// Sets the dynamic rounding mode field (`frm`). Only the lower 3 bits are used.
+    pub fn set_frm(&mut self, value: u8) {
+        self.frm = value & 0b111;
+    }
+}
+
 /// An `x` register specifier. Can take values in the range `0..LEN`.
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
 pub struct Specifier(u8);
@@ -92,6 +193,22 @@ impl Specifier {
     /// Register `x0`, a.k.a. register `zero`, always returns `0` on read, and ignores any writes.
     pub const X0: Self = Specifier(0);
 
+    /// Register `x1`, a.k.a. register `ra`, is the link register used by the standard calling
+    /// convention, and implicitly by `c.jal` and `c.jalr`.
+    pub const RA: Self = Specifier(1);
+
+    /// Register `x2`, a.k.a. register `sp`, is the stack pointer used by the standard calling
+    /// convention, and implicitly by the stack-pointer-based compressed instructions.
+    pub const SP: Self = Specifier(2);
+
+    /// Register `x10`, a.k.a. register `a0`, holds the first argument and the return value in the
+    /// standard calling convention.
+    pub const A0: Self = Specifier(10);
+
+    /// Register `x11`, a.k.a. register `a1`, holds the second argument in the standard calling
+    /// convention.
+    pub const A1: Self = Specifier(11);
+
     /// Create a register specifier from its index, returning `None` if `index > 31`.
     pub fn new<U: TryInto<u8>>(index: U) -> Option<Self> {
         let index = index.try_into().ok()?;
@@ -112,6 +229,28 @@ impl Specifier {
     pub fn iter_all() -> impl Iterator<Item = Self> {
         (0..32).map(Self)
     }
+
+    /// Returns the name of this `x` register in the standard calling convention, e.g. `sp` for
+    /// `x2`. Register `x8` is named `s0` rather than `fp`, like objdump does.
+    pub fn abi_name(self) -> &'static str {
+        const NAMES: [&str; LEN as usize] = [
+            "zero", "ra", "sp", "gp", "tp", "t0", "t1", "t2", "s0", "s1", "a0", "a1", "a2", "a3",
+            "a4", "a5", "a6", "a7", "s2", "s3", "s4", "s5", "s6", "s7", "s8", "s9", "s10", "s11",
+            "t3", "t4", "t5", "t6",
+        ];
+        NAMES[self.0 as usize]
+    }
+
+    /// Returns the name of this register as an `f` register in the standard calling convention,
+    /// e.g. `fa0` for `f10`.
+    pub fn float_abi_name(self) -> &'static str {
+        const NAMES: [&str; LEN as usize] = [
+            "ft0", "ft1", "ft2", "ft3", "ft4", "ft5", "ft6", "ft7", "fs0", "fs1", "fa0", "fa1",
+            "fa2", "fa3", "fa4", "fa5", "fa6", "fa7", "fs2", "fs3", "fs4", "fs5", "fs6", "fs7",
+            "fs8", "fs9", "fs10", "fs11", "ft8", "ft9", "ft10", "ft11",
+        ];
+        NAMES[self.0 as usize]
+    }
 }
 
 impl From<Specifier> for u8 {
@@ -208,4 +347,29 @@ mod tests {
             assert_eq!(i as u32 + 1, registers.x(Specifier::from_u5(i)));
         }
     }
+
+    #[test]
+    fn test_nan_boxing() {
+        let mut registers = FloatRegisters::new();
+        let f1 = Specifier::from_u5(1);
+        registers.set_f32(f1, 0x3F80_0000);
+        assert_eq!(0xFFFF_FFFF_3F80_0000, registers.f(f1));
+        assert_eq!(0x3F80_0000, registers.f32(f1));
+        registers.set_f(f1, 0x3FF0_0000_0000_0000);
+        assert_eq!(0x7FC0_0000, registers.f32(f1));
+        registers.set_f(f1, 0xFFFF_FFFE_3F80_0000);
+        assert_eq!(0x7FC0_0000, registers.f32(f1));
+    }
+
+    #[test]
+    fn test_fcsr_fields() {
+        let mut registers = FloatRegisters::new();
+        registers.set_fflags(0xFF);
+        assert_eq!(0b1_1111, registers.fflags());
+        registers.set_fflags(0b1);
+        registers.accrue_fflags(0b100);
+        assert_eq!(0b101, registers.fflags());
+        registers.set_frm(0xFF);
+        assert_eq!(0b111, registers.frm());
+    }
 }
@@ -0,0 +1,350 @@
+//! Implementation of the Host-Target Interface (HTIF) of Spike, used by binaries that signal
+//! completion and print through the `tohost` and `fromhost` symbols.
+
+use log::debug;
+use space_time::allocator::Allocator;
+
+use crate::bus::{Bus, BusError};
+
+/// Syscall proxy device, which handles syscalls and exits.
+const DEVICE_SYSCALL: u8 = 0;
+/// Blocking character device, which writes characters to the console.
+const DEVICE_CONSOLE: u8 = 1;
+const CONSOLE_PUTCHAR: u8 = 1;
+
+const SYS_WRITE: u64 = 64;
+const SYS_EXIT: u64 = 93;
+
+const EBADF: i64 = 9;
+const EFAULT: i64 = 14;
+const ENOSYS: i64 = 38;
+
+/// Number of bytes a write syscall copies from guest memory at once. The length of a write comes
+/// from the guest, so a buffer of that length is only built up as far as memory can be read.
+const WRITE_CHUNK_SIZE: u32 = 0x1000;
+
+/// HTIF of Spike, reduced to exiting and writing to stdout and stderr.
+///
+/// The guest issues a command by writing it to `tohost`, which is a 64-bit word in memory: the
+/// device in bits 63..56, the command in bits 55..48 and the payload in bits 47..0. The host polls
+/// `tohost`, clears it once the command is handled, and writes the response to `fromhost`. Like in
+/// QEMU, a command is only handled after the upper half of `tohost` is written, since RV32 guests
+/// write the lower half first.
+///
+/// Supported commands:
+/// - Device 0, command 0, with bit 0 of the payload set: exit with code `payload >> 1`.
+/// - Device 0, command 0, with bit 0 of the payload clear: the payload points to 8 words holding a
+///   syscall number and its arguments, and the result is written to the first word. Only
+///   `write` (64) to stdout or stderr and `exit` (93) are supported.
+/// - Device 1, command 1: write the lowest byte of the payload to the console.
+///
+/// Output is buffered until the host takes it with [`Htif::take_output`].
+///
+/// Resources:
+/// - <https://github.com/riscv-software-src/riscv-isa-sim/blob/master/fesvr/htif.cc>
+/// - <https://github.com/riscv-software-src/riscv-pk/blob/master/pk/syscall.h>
+#[derive(Debug)]
+pub struct Htif<A: Allocator> {
+    /// Output of the guest that was not yet taken by the host.
+    output: A::Id<Vec<u8>>,
+    /// Whether the upper half of `tohost` was written since the previous poll.
+    written: A::Id<bool>,
+    tohost: u32,
+    fromhost: Option<u32>,
+}
+
+impl<A: Allocator> Htif<A> {
+    /// Creates a HTIF with the given addresses of `tohost` and `fromhost`. Without `fromhost`,
+    /// responses are dropped.
+    pub fn new(allocator: &mut A, tohost: u32, fromhost: Option<u32>) -> Self {
+        Self {
+            output: allocator.insert(Vec::new()),
+            written: allocator.insert(false),
+            tohost,
+            fromhost,
+        }
+    }
+
+    /// Forgets a write of `tohost` that was not polled yet. Output that the host did not take yet
+    /// is kept, since the guest already printed it.
+    pub fn reset(&self, allocator: &mut A) {
+        if *allocator.get(self.written).unwrap() {
+            *allocator.get_mut(self.written).unwrap() = false;
+        }
+    }
+
+    pub fn drop(self, allocator: &mut A) {
+        allocator.remove(self.output).unwrap();
+        allocator.remove(self.written).unwrap();
+    }
+
+    /// Records a write of `len` bytes at `address`, which must be called for every write to the
+    /// memory holding `tohost`.
+    pub fn observe_write(&self, allocator: &mut A, address: u32, len: usize) {
+        let start = address as u64;
+        let upper_half = self.tohost as u64 + 4;
+        if start < upper_half + 4
+            && start + len as u64 > upper_half
+            && !*allocator.get(self.written).unwrap()
+        {
+            *allocator.get_mut(self.written).unwrap() = true;
+        }
+    }
+
+    /// Handles the command in `tohost` if it was written, accessing memory through `bus`. Returns
+    /// the exit code if the guest exited.
+    ///
+    /// Does not modify any state if `tohost` was not written since the previous poll.
+    pub fn poll(&self, allocator: &mut A, bus: &dyn Bus<A>) -> Option<u64> {
+        if !*allocator.get(self.written).unwrap() {
+            return None;
+        }
+        let exit = self.handle_command(allocator, bus);
+        // Clearing `tohost` counts as a write as well.
+        *allocator.get_mut(self.written).unwrap() = false;
+        exit
+    }
+
+    fn handle_command(&self, allocator: &mut A, bus: &dyn Bus<A>) -> Option<u64> {
+        let mut buf = [0; 8];
+        bus.read_debug(&mut buf, allocator, self.tohost).ok()?;
+        let command = u64::from_le_bytes(buf);
+        if command == 0 {
+            return None;
+        }
+        if let Err(error) = bus.write(allocator, self.tohost, &[0; 8]) {
+            debug!(error:%; "Failed to clear tohost");
+        }
+
+        let device = (command >> 56) as u8;
+        let cmd = (command >> 48) as u8;
+        let payload = command & 0xFFFF_FFFF_FFFF;
+        let (response, exit) = match (device, cmd) {
+            (DEVICE_SYSCALL, 0) if payload & 1 != 0 => (None, Some(payload >> 1)),
+            (DEVICE_SYSCALL, 0) => match self.syscall(allocator, bus, payload) {
+                Ok(exit) => (Some(1), exit),
+                Err(error) => {
+                    debug!(error:%; "Failed to access the arguments of a HTIF syscall");
+                    (None, None)
+                }
+            },
+            (DEVICE_CONSOLE, CONSOLE_PUTCHAR) => {
+                allocator.get_mut(self.output).unwrap().push(payload as u8);
+                (Some(0x100 | payload as u8 as u64), None)
+            }
+            _ => {
+                debug!("Ignoring unsupported HTIF command {command:#018x}");
+                (None, None)
+            }
+        };
+
+        if let (Some(response), Some(fromhost)) = (response, self.fromhost) {
+            let response = (command & 0xFFFF_0000_0000_0000) | response;
+            if let Err(error) = bus.write(allocator, fromhost, &response.to_le_bytes()) {
+                debug!(error:%; "Failed to write fromhost");
+            }
+        }
+        exit
+    }
+
+    /// Handles the syscall whose number and arguments are at `address`, returning the exit code if
+    /// it is an exit.
+    fn syscall(
+        &self,
+        allocator: &mut A,
+        bus: &dyn Bus<A>,
+        address: u64,
+    ) -> Result<Option<u64>, BusError> {
+        let address = u32::try_from(address).map_err(|_| BusError::Denied)?;
+        let mut buf = [0; 64];
+        bus.read(&mut buf, allocator, address)?;
+        let mut words = buf
+            .chunks_exact(8)
+            .map(|word| u64::from_le_bytes(word.try_into().unwrap()));
+        let mut word = || words.next().unwrap();
+        let (number, args) = (word(), [word(), word(), word()]);
+
+        let result = match number {
+            SYS_EXIT => return Ok(Some(args[0])),
+            SYS_WRITE => match args {
+                [1 | 2, buffer, len] => self.write(allocator, bus, buffer, len),
+                _ => -EBADF,
+            },
+            _ => {
+                debug!("Unsupported HTIF syscall {number}");
+                -ENOSYS
+            }
+        };
+        bus.write(allocator, address, &result.to_le_bytes())?;
+        Ok(None)
+    }
+
+    /// Writes `len` bytes at `buffer` to the output, returning the number of bytes written or the
+    /// negated error number.
+    fn write(&self, allocator: &mut A, bus: &dyn Bus<A>, buffer: u64, len: u64) -> i64 {
+        let (Ok(buffer), Ok(len)) = (u32::try_from(buffer), u32::try_from(len)) else {
+            return -EFAULT;
+        };
+        if buffer.checked_add(len).is_none() {
+            return -EFAULT;
+        }
+        let mut data = Vec::new();
+        let mut chunk = [0; WRITE_CHUNK_SIZE as usize];
+        while (data.len() as u32) < len {
+            let offset = data.len() as u32;
+            let chunk = &mut chunk[..(len - offset).min(WRITE_CHUNK_SIZE) as usize];
+            if bus.read(chunk, allocator, buffer + offset).is_err() {
+                return -EFAULT;
+            }
+            data.extend_from_slice(chunk);
+        }
+        allocator.get_mut(self.output).unwrap().extend(data);
+        len as i64
+    }
+
+    /// Returns the output of the guest that was not yet taken.
+    pub fn pending_output<'a>(&self, allocator: &'a A) -> &'a [u8] {
+        allocator.get(self.output).unwrap()
+    }
+
+    /// Takes the output of the guest, leaving the buffer empty.
+    pub fn take_output(&self, allocator: &mut A) -> Vec<u8> {
+        if self.pending_output(allocator).is_empty() {
+            return Vec::new();
+        }
+        std::mem::take(allocator.get_mut(self.output).unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use space_time::SpaceTime;
+
+    use super::*;
+    use crate::board::testing::{boot, run_until};
+    use crate::board::{Config, ExitStatus, HtifConfig};
+    use crate::resources::ram::Ram;
+
+    const TOHOST: u32 = 0x1000;
+    const FROMHOST: u32 = 0x1008;
+    const MAGIC_MEM: u32 = 0x2000;
+    const BUFFER: u32 = 0x3000;
+
+    fn read_u64(ram: &Ram<SpaceTime>, allocator: &SpaceTime, address: u32) -> u64 {
+        let mut buf = [0; 8];
+        ram.read(&mut buf, allocator, address);
+        u64::from_le_bytes(buf)
+    }
+
+    /// Writes `command` to `tohost`, and returns the result of the poll that handles it.
+    fn issue(
+        htif: &Htif<SpaceTime>,
+        ram: &Ram<SpaceTime>,
+        allocator: &mut SpaceTime,
+        command: u64,
+    ) -> Option<u64> {
+        write(htif, ram, allocator, TOHOST, &command.to_le_bytes());
+        let result = htif.poll(allocator, ram);
+        assert_eq!(0, read_u64(ram, allocator, TOHOST));
+        result
+    }
+
+    /// Writes to memory like the system bus does.
+    fn write(
+        htif: &Htif<SpaceTime>,
+        ram: &Ram<SpaceTime>,
+        allocator: &mut SpaceTime,
+        address: u32,
+        buf: &[u8],
+    ) {
+        ram.write(allocator, address, buf);
+        htif.observe_write(allocator, address, buf.len());
+    }
+
+    #[test]
+    fn test_commands() {
+        let mut allocator = SpaceTime::new();
+        let ram = Ram::new(&mut allocator, 0x10000).unwrap();
+        let htif = Htif::new(&mut allocator, TOHOST, Some(FROMHOST));
+        assert_eq!(None, htif.poll(&mut allocator, &ram));
+        let allocator = &mut allocator;
+        let magic_mem = |allocator: &mut SpaceTime, words: &[u64]| {
+            for (index, word) in words.iter().enumerate() {
+                ram.write(allocator, MAGIC_MEM + 8 * index as u32, &word.to_le_bytes());
+            }
+        };
+
+        // write(1, "hi", 2)
+        ram.write(allocator, BUFFER, b"hi");
+        magic_mem(allocator, &[SYS_WRITE, 1, BUFFER as u64, 2]);
+        assert_eq!(None, issue(&htif, &ram, allocator, MAGIC_MEM as u64));
+        assert_eq!(1, read_u64(&ram, allocator, FROMHOST));
+        assert_eq!(2, read_u64(&ram, allocator, MAGIC_MEM));
+
+        // Console putchar, written in two halves like RV32 guests do.
+        write(&htif, &ram, allocator, TOHOST, &0x21u32.to_le_bytes());
+        assert_eq!(None, htif.poll(allocator, &ram));
+        assert_eq!(0x21, read_u64(&ram, allocator, TOHOST));
+        write(
+            &htif,
+            &ram,
+            allocator,
+            TOHOST + 4,
+            &0x0101_0000u32.to_le_bytes(),
+        );
+        assert_eq!(None, htif.poll(allocator, &ram));
+        assert_eq!(0, read_u64(&ram, allocator, TOHOST));
+        assert_eq!(0x0101_0000_0000_0121, read_u64(&ram, allocator, FROMHOST));
+        assert_eq!(b"hi!", htif.pending_output(allocator));
+        assert_eq!(b"hi!".to_vec(), htif.take_output(allocator));
+        assert!(htif.pending_output(allocator).is_empty());
+
+        // Unsupported syscalls and file descriptors fail.
+        magic_mem(allocator, &[57]);
+        assert_eq!(None, issue(&htif, &ram, allocator, MAGIC_MEM as u64));
+        assert_eq!(-ENOSYS, read_u64(&ram, allocator, MAGIC_MEM) as i64);
+        magic_mem(allocator, &[SYS_WRITE, 3]);
+        assert_eq!(None, issue(&htif, &ram, allocator, MAGIC_MEM as u64));
+        assert_eq!(-EBADF, read_u64(&ram, allocator, MAGIC_MEM) as i64);
+
+        // Writes are copied in chunks, and fail if the buffer does not fit in the address space.
+        magic_mem(allocator, &[SYS_WRITE, 1, BUFFER as u64, 0x1800]);
+        assert_eq!(None, issue(&htif, &ram, allocator, MAGIC_MEM as u64));
+        assert_eq!(0x1800, read_u64(&ram, allocator, MAGIC_MEM));
+        assert_eq!(0x1800, htif.take_output(allocator).len());
+        magic_mem(allocator, &[SYS_WRITE, 1, BUFFER as u64, 0xFFFF_F000]);
+        assert_eq!(None, issue(&htif, &ram, allocator, MAGIC_MEM as u64));
+        assert_eq!(-EFAULT, read_u64(&ram, allocator, MAGIC_MEM) as i64);
+        assert!(htif.pending_output(allocator).is_empty());
+
+        // exit(3), through the syscall and directly.
+        magic_mem(allocator, &[SYS_EXIT, 3]);
+        assert_eq!(Some(3), issue(&htif, &ram, allocator, MAGIC_MEM as u64));
+        assert_eq!(Some(3), issue(&htif, &ram, allocator, 3 << 1 | 1));
+    }
+
+    #[test]
+    fn test_board() {
+        let config = Config {
+            htif: Some(HtifConfig {
+                tohost: 0x8000_1000,
+                fromhost: None,
+            }),
+            ..Config::default()
+        };
+        let mut simulator = boot(
+            config,
+            &[
+                0x800012B7, // lui    t0, 0x80001
+                0x00700313, // li     t1, 7
+                0x0062A023, // sw     t1, 0(t0)
+                0x0002A223, // sw     zero, 4(t0)
+                0x0000006F, // j      .
+            ],
+        );
+        // The board polls the HTIF at the end of the step writing the upper half of tohost.
+        run_until(&mut simulator, 0x8000_0010);
+        let (allocator, board) = simulator.inspect();
+        assert_eq!(Some(ExitStatus::Fail(3)), board.exit_status(allocator));
+    }
+}
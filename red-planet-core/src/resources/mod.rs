@@ -1,6 +1,9 @@
 //! Provides implementations for common memory resources, such as RAM, ROM, and common I/O devices.
 
+pub mod htif;
 pub mod plic;
 pub mod ram;
 pub mod rom;
+pub mod semihosting;
 pub mod uart;
+pub mod virtio_block;
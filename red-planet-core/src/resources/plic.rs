@@ -1,11 +1,11 @@
 //! Platform-level interrupt controller
 
 use bitvec::array::BitArray;
-use bitvec::order::Msb0;
+use bitvec::order::Lsb0;
 use bitvec::BitArr;
 use space_time::allocator::Allocator;
 
-use crate::bus::Bus;
+use crate::bus::{Bus, BusError};
 
 use crate::interrupt::DynIrqCallback;
 
@@ -15,33 +15,62 @@ pub const PRIORITY_LAST_ADDR: u32 = 0xD0;
 pub const PENDING_BASE_ADDR: u32 = 0x1000;
 pub const PENDING_LAST_ADDR: u32 = 0x1004;
 
+/// Address of the enable bits of context 0.
 pub const ENABLES_BASE_ADDR: u32 = 0x2000;
-pub const ENABLES_LAST_ADDR: u32 = 0x2004;
+/// Distance between the enable bits of consecutive contexts.
+pub const ENABLES_STRIDE: u32 = 0x80;
 
+/// Address of the priority threshold of context 0.
 pub const THRESHOLD_ADDR: u32 = 0x20_0000;
+/// Address of the claim/complete register of context 0.
 pub const CLAIMCOMPLETE_ADDR: u32 = 0x20_0004;
+/// Distance between the threshold and claim/complete registers of consecutive contexts.
+pub const CONTEXT_STRIDE: u32 = 0x1000;
+
+/// Maximum number of contexts of a PLIC.
+pub const MAX_CONTEXTS: usize = 15872;
+
+/// Highest interrupt source of the PLIC. Source 0 means "no interrupt".
+pub const MAX_SOURCE: u32 = PRIORITY_LAST_ADDR / 4;
 
 #[derive(Debug)]
 pub struct Plic<A: Allocator> {
     state: A::Id<State>,
-    interrupt_callback: DynIrqCallback<A>,
+    /// Interrupt line of every context, i.e. of every hart and privilege mode pair.
+    interrupt_callbacks: Vec<DynIrqCallback<A>>,
 }
 
+type InterruptBits = BitArr!(for 53, in u32, Lsb0);
+
 #[derive(Debug, Clone, Eq, PartialEq)]
 struct State {
     priorities: [u32; 53],
-    pending: BitArr!(for 53, in u32, Msb0),
-    enabled: BitArr!(for 53, in u32, Msb0),
+    pending: InterruptBits,
+    contexts: Vec<Context>,
+}
+
+/// State of a single interrupt target.
+///
+/// > Interrupt targets are usually hart contexts, where a hart context is a given privilege mode
+/// > on a given hart [...].
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct Context {
+    enabled: InterruptBits,
     priority_threshold: u32,
 }
 
 impl State {
-    fn new() -> Self {
+    fn new(context_count: usize) -> Self {
         Self {
             priorities: [0; 53],
             pending: BitArray::ZERO,
-            enabled: BitArray::ZERO,
-            priority_threshold: 0,
+            contexts: vec![
+                Context {
+                    enabled: BitArray::ZERO,
+                    priority_threshold: 0,
+                };
+                context_count
+            ],
         }
     }
 
@@ -57,18 +86,19 @@ impl State {
         self.priorities[index] = value.min(7);
     }
 
-    fn set_priority_threshold(&mut self, value: u32) {
-        self.priority_threshold = value.min(7);
+    fn set_priority_threshold(&mut self, context: usize, value: u32) {
+        self.contexts[context].priority_threshold = value.min(7);
     }
 
-    /// Returns 0 if no interrupts are pending
-    fn highest_priority_pending(&self) -> u32 {
+    /// Returns 0 if no interrupts are pending for `context`
+    fn highest_priority_pending(&self, context: usize) -> u32 {
+        let context = &self.contexts[context];
         let Some((idx, priority)) = self
             .priorities
             .iter()
             .enumerate()
             .zip(self.pending)
-            .zip(self.enabled)
+            .zip(context.enabled)
             .filter(|((_, pending), enabled)| *enabled && *pending)
             .map(|(((idx, priority), _), _)| (idx as u32, *priority))
             .rev()
@@ -77,22 +107,22 @@ impl State {
             return 0;
         };
 
-        if priority <= self.priority_threshold {
+        if priority <= context.priority_threshold {
             return 0;
         }
         idx
     }
 
-    fn claim_highest_priority_pending(&mut self) -> u32 {
-        let idx = self.highest_priority_pending();
+    fn claim_highest_priority_pending(&mut self, context: usize) -> u32 {
+        let idx = self.highest_priority_pending(context);
         if idx != 0 {
             *self.pending.get_mut(idx as usize).unwrap() = false;
         }
         idx
     }
 
-    fn needs_interrupt(&self) -> bool {
-        self.highest_priority_pending() != 0
+    fn needs_interrupt(&self, context: usize) -> bool {
+        self.highest_priority_pending(context) != 0
     }
 }
 
@@ -100,44 +130,72 @@ impl State {
 enum AddrAccessor {
     Priorities(usize),
     Pending(usize),
-    Enabled(usize),
-    Threshold,
-    ClaimComplete,
+    Enabled { context: usize, word: usize },
+    Threshold(usize),
+    ClaimComplete(usize),
 }
 
 impl AddrAccessor {
-    fn from_address(address: u32) -> Option<Self> {
-        match address {
-            PRIORITY_BASE_ADDR..=PRIORITY_LAST_ADDR => {
-                // `+ 1` to skip the interrupt 0 "no interrupt"
-                Some(Self::Priorities(
-                    (address - PRIORITY_BASE_ADDR + 1) as usize,
-                ))
-            }
+    fn from_address(address: u32, context_count: usize) -> Option<Self> {
+        let accessor = match address {
+            PRIORITY_BASE_ADDR..=PRIORITY_LAST_ADDR => Self::Priorities((address / 4) as usize),
             PENDING_BASE_ADDR..=PENDING_LAST_ADDR => {
-                Some(Self::Pending((address - PENDING_BASE_ADDR) as usize))
+                Self::Pending(((address - PENDING_BASE_ADDR) / 4) as usize)
+            }
+            ENABLES_BASE_ADDR..THRESHOLD_ADDR => {
+                let offset = address - ENABLES_BASE_ADDR;
+                let word = (offset % ENABLES_STRIDE / 4) as usize;
+                if word >= InterruptBits::ZERO.as_raw_slice().len() {
+                    return None;
+                }
+                Self::Enabled {
+                    context: (offset / ENABLES_STRIDE) as usize,
+                    word,
+                }
+            }
+            THRESHOLD_ADDR.. => {
+                let offset = address - THRESHOLD_ADDR;
+                let context = (offset / CONTEXT_STRIDE) as usize;
+                match offset % CONTEXT_STRIDE {
+                    0 => Self::Threshold(context),
+                    4 => Self::ClaimComplete(context),
+                    _ => return None,
+                }
             }
-            ENABLES_BASE_ADDR..=ENABLES_LAST_ADDR => {
-                Some(Self::Enabled((address - ENABLES_BASE_ADDR) as usize))
+            _ => return None,
+        };
+        match accessor {
+            Self::Enabled { context, .. }
+            | Self::Threshold(context)
+            | Self::ClaimComplete(context)
+                if context >= context_count =>
+            {
+                None
             }
-            THRESHOLD_ADDR => Some(Self::Threshold),
-            CLAIMCOMPLETE_ADDR => Some(Self::ClaimComplete),
-            _ => None,
+            accessor => Some(accessor),
         }
     }
 }
 
 impl<A: Allocator> Plic<A> {
-    /// Create new Plic in reset state.
-    pub fn new(allocator: &mut A, interrupt_callback: DynIrqCallback<A>) -> Self {
+    /// Create new Plic in reset state, with one context for every entry of
+    /// `interrupt_callbacks`.
+    ///
+    /// Panics if more than [`MAX_CONTEXTS`] contexts are given.
+    pub fn new(allocator: &mut A, interrupt_callbacks: Vec<DynIrqCallback<A>>) -> Self {
+        assert!(
+            interrupt_callbacks.len() <= MAX_CONTEXTS,
+            "a PLIC supports at most {MAX_CONTEXTS} contexts",
+        );
         Self {
-            state: allocator.insert(State::new()),
-            interrupt_callback,
+            state: allocator.insert(State::new(interrupt_callbacks.len())),
+            interrupt_callbacks,
         }
     }
 
     pub fn reset(&self, allocator: &mut A) {
-        self.update(allocator, |state| *state = State::new());
+        let context_count = self.interrupt_callbacks.len();
+        self.update(allocator, |state| *state = State::new(context_count));
     }
 
     pub fn drop(self, allocator: &mut A) {
@@ -152,56 +210,53 @@ impl<A: Allocator> Plic<A> {
         // The PLIC ignores lowers explicitly
     }
 
-    fn read_u32(&self, allocator: &mut A, address: u32) -> u32 {
-        let Some(address) = AddrAccessor::from_address(address) else {
-            return 0;
-        };
+    /// Returns the register accessed by a bus access of `size` bytes at `address`. Only aligned
+    /// word accesses to implemented registers are supported.
+    fn accessor(&self, address: u32, size: usize) -> Result<AddrAccessor, BusError> {
+        if !address.is_multiple_of(4) || size != 4 {
+            return Err(BusError::Unsupported);
+        }
+        AddrAccessor::from_address(address, self.interrupt_callbacks.len())
+            .ok_or(BusError::Unsupported)
+    }
+
+    fn read_u32(&self, allocator: &mut A, address: AddrAccessor) -> u32 {
         match address {
-            AddrAccessor::Priorities(i) => allocator.get(self.state).unwrap().priorities[i],
-            AddrAccessor::Enabled(i) => {
-                allocator.get(self.state).unwrap().enabled.as_raw_slice()[i]
-            }
-            AddrAccessor::Pending(i) => {
-                allocator.get(self.state).unwrap().pending.as_raw_slice()[i]
-            }
-            AddrAccessor::Threshold => allocator.get(self.state).unwrap().priority_threshold,
-            AddrAccessor::ClaimComplete => {
-                self.update(allocator, |state| state.claim_highest_priority_pending())
-            }
+            AddrAccessor::ClaimComplete(context) => self.update(allocator, |state| {
+                state.claim_highest_priority_pending(context)
+            }),
+            address => self.read_u32_debug(allocator, address),
         }
     }
 
-    fn read_u32_debug(&self, allocator: &A, address: u32) -> u32 {
-        let Some(address) = AddrAccessor::from_address(address) else {
-            return 0;
-        };
+    fn read_u32_debug(&self, allocator: &A, address: AddrAccessor) -> u32 {
         let state = allocator.get(self.state).unwrap();
         match address {
             AddrAccessor::Priorities(i) => state.priorities[i],
-            AddrAccessor::Enabled(i) => state.enabled.as_raw_slice()[i],
+            AddrAccessor::Enabled { context, word } => {
+                state.contexts[context].enabled.as_raw_slice()[word]
+            }
             AddrAccessor::Pending(i) => state.pending.as_raw_slice()[i],
-            AddrAccessor::Threshold => state.priority_threshold,
-            AddrAccessor::ClaimComplete => state.highest_priority_pending(),
+            AddrAccessor::Threshold(context) => state.contexts[context].priority_threshold,
+            AddrAccessor::ClaimComplete(context) => state.highest_priority_pending(context),
         }
     }
 
-    fn write_u32(&self, allocator: &mut A, address: u32, value: u32) {
-        let Some(address) = AddrAccessor::from_address(address) else {
-            return;
-        };
+    fn write_u32(&self, allocator: &mut A, address: AddrAccessor, value: u32) {
         self.update(allocator, |state| match address {
             AddrAccessor::Priorities(i) => state.set_priority(i, value),
-            AddrAccessor::Enabled(i) => {
-                let value = if i == 0 { value & 0x8000_0000 } else { value };
-                state.enabled.as_raw_mut_slice()[i] = value;
+            AddrAccessor::Enabled { context, word } => {
+                // Interrupt 0 means "no interrupt", so it cannot be enabled.
+                let value = if word == 0 { value & !1 } else { value };
+                state.contexts[context].enabled.as_raw_mut_slice()[word] = value;
             }
             AddrAccessor::Pending(i) => {
-                let value = if i == 0 { value & 0x8000_0000 } else { value };
+                let value = if i == 0 { value & !1 } else { value };
                 state.pending.as_raw_mut_slice()[i] = value;
             }
-            AddrAccessor::Threshold => state.set_priority_threshold(value),
-            AddrAccessor::ClaimComplete => {
-                if (1..=52).contains(&value) {
+            AddrAccessor::Threshold(context) => state.set_priority_threshold(context, value),
+            AddrAccessor::ClaimComplete(_) => {
+                if (1..=MAX_SOURCE).contains(&value) {
                     state.set_complete(value as u8)
                 }
             }
@@ -209,46 +264,152 @@ impl<A: Allocator> Plic<A> {
     }
 
     fn update<R>(&self, allocator: &mut A, op: impl FnOnce(&mut State) -> R) -> R {
+        let context_count = self.interrupt_callbacks.len();
         let state = allocator.get_mut(self.state).unwrap();
-        let irq_before = state.needs_interrupt();
+        let irq_before: Vec<bool> = (0..context_count)
+            .map(|context| state.needs_interrupt(context))
+            .collect();
         let res = op(state);
-        let irq_after = state.needs_interrupt();
-        match (irq_before, irq_after) {
-            (true, false) => self.interrupt_callback.lower(allocator),
-            (false, true) => self.interrupt_callback.raise(allocator),
-            _ => {}
+        let irq_after: Vec<bool> = (0..context_count)
+            .map(|context| state.needs_interrupt(context))
+            .collect();
+        for (callback, (before, after)) in self
+            .interrupt_callbacks
+            .iter()
+            .zip(irq_before.into_iter().zip(irq_after))
+        {
+            match (before, after) {
+                (true, false) => callback.lower(allocator),
+                (false, true) => callback.raise(allocator),
+                _ => {}
+            }
         }
         res
     }
 }
 
 impl<A: Allocator> Bus<A> for Plic<A> {
-    fn read(&self, buf: &mut [u8], allocator: &mut A, address: u32) {
-        if address != address & !0b11 {
-            return;
-        }
-        if buf.len() == 4 {
-            let v = self.read_u32(allocator, address);
-            buf.copy_from_slice(&v.to_le_bytes())
-        }
+    fn read(&self, buf: &mut [u8], allocator: &mut A, address: u32) -> Result<(), BusError> {
+        let address = self.accessor(address, buf.len())?;
+        let v = self.read_u32(allocator, address);
+        buf.copy_from_slice(&v.to_le_bytes());
+        Ok(())
     }
 
-    fn read_debug(&self, buf: &mut [u8], allocator: &A, address: u32) {
-        if address != address & !0b11 {
-            return;
-        }
-        if buf.len() == 4 {
-            let v = self.read_u32_debug(allocator, address);
-            buf.copy_from_slice(&v.to_le_bytes())
-        }
+    fn read_debug(&self, buf: &mut [u8], allocator: &A, address: u32) -> Result<(), BusError> {
+        let address = self.accessor(address, buf.len())?;
+        let v = self.read_u32_debug(allocator, address);
+        buf.copy_from_slice(&v.to_le_bytes());
+        Ok(())
     }
 
-    fn write(&self, allocator: &mut A, address: u32, buf: &[u8]) {
-        if address != address & !0b11 {
-            return;
+    fn write(&self, allocator: &mut A, address: u32, buf: &[u8]) -> Result<(), BusError> {
+        let address = self.accessor(address, buf.len())?;
+        // Unwrap safety: only accesses of 4 bytes have an accessor.
+        let value = u32::from_le_bytes(buf.try_into().unwrap());
+        self.write_u32(allocator, address, value);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    use space_time::SpaceTime;
+
+    use super::*;
+    use crate::interrupt::IrqCallback;
+
+    struct Line(Rc<Cell<bool>>);
+
+    impl IrqCallback<SpaceTime> for Line {
+        fn raise(&self, _allocator: &mut SpaceTime) {
+            self.0.set(true);
         }
-        if let [a, b, c, d] = buf {
-            self.write_u32(allocator, address, u32::from_le_bytes([*a, *b, *c, *d]));
+
+        fn lower(&self, _allocator: &mut SpaceTime) {
+            self.0.set(false);
         }
     }
+
+    fn write(plic: &Plic<SpaceTime>, allocator: &mut SpaceTime, address: u32, value: u32) {
+        Bus::write(plic, allocator, address, &value.to_le_bytes()).unwrap();
+    }
+
+    fn read(plic: &Plic<SpaceTime>, allocator: &mut SpaceTime, address: u32) -> u32 {
+        let mut buf = [0; 4];
+        Bus::read(plic, &mut buf, allocator, address).unwrap();
+        u32::from_le_bytes(buf)
+    }
+
+    #[test]
+    fn test_contexts() {
+        let mut allocator = SpaceTime::new();
+        let lines: Vec<_> = (0..2).map(|_| Rc::new(Cell::new(false))).collect();
+        let callbacks = lines
+            .iter()
+            .map(|line| DynIrqCallback(Box::new(Line(line.clone())) as Box<_>))
+            .collect();
+        let plic = Plic::new(&mut allocator, callbacks);
+
+        // Source 3 with priority 1, enabled only in context 1.
+        write(&plic, &mut allocator, PRIORITY_BASE_ADDR + 8, 1);
+        write(
+            &plic,
+            &mut allocator,
+            ENABLES_BASE_ADDR + ENABLES_STRIDE,
+            1 << 3,
+        );
+        assert_eq!(
+            1 << 3,
+            read(&plic, &mut allocator, ENABLES_BASE_ADDR + ENABLES_STRIDE)
+        );
+        assert_eq!(0, read(&plic, &mut allocator, ENABLES_BASE_ADDR));
+
+        plic.raise(&mut allocator, 3);
+        assert!(!lines[0].get());
+        assert!(lines[1].get());
+        assert_eq!(1 << 3, read(&plic, &mut allocator, PENDING_BASE_ADDR));
+
+        // A threshold equal to the priority masks the interrupt.
+        write(&plic, &mut allocator, THRESHOLD_ADDR + CONTEXT_STRIDE, 1);
+        assert!(!lines[1].get());
+        write(&plic, &mut allocator, THRESHOLD_ADDR + CONTEXT_STRIDE, 0);
+        assert!(lines[1].get());
+
+        // Context 0 cannot claim it, context 1 can.
+        assert_eq!(0, read(&plic, &mut allocator, CLAIMCOMPLETE_ADDR));
+        assert_eq!(
+            3,
+            read(&plic, &mut allocator, CLAIMCOMPLETE_ADDR + CONTEXT_STRIDE)
+        );
+        assert!(!lines[1].get());
+
+        // Interrupt 0 cannot be enabled.
+        write(&plic, &mut allocator, ENABLES_BASE_ADDR, 0xFFFF_FFFF);
+        assert_eq!(0xFFFF_FFFE, read(&plic, &mut allocator, ENABLES_BASE_ADDR));
+
+        // Registers of contexts that do not exist, and misaligned or non-word accesses, are not
+        // supported.
+        let mut buf = [0; 4];
+        let address = THRESHOLD_ADDR + 2 * CONTEXT_STRIDE;
+        assert_eq!(
+            Err(BusError::Unsupported),
+            Bus::write(&plic, &mut allocator, address, &5u32.to_le_bytes())
+        );
+        assert_eq!(
+            Err(BusError::Unsupported),
+            Bus::read(&plic, &mut buf, &mut allocator, address)
+        );
+        assert_eq!(
+            Err(BusError::Unsupported),
+            Bus::read_debug(&plic, &mut buf, &allocator, THRESHOLD_ADDR + 2)
+        );
+        assert_eq!(
+            Err(BusError::Unsupported),
+            Bus::read(&plic, &mut buf[..2], &mut allocator, THRESHOLD_ADDR)
+        );
+    }
 }
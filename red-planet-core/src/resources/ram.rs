@@ -1,4 +1,4 @@
-use crate::bus::Bus;
+use crate::bus::{Bus, BusError};
 use crate::AddressRange;
 use space_time::allocator::{Allocator, ArrayAccessor, ArrayAccessorMut};
 
@@ -44,6 +44,12 @@ impl<A: Allocator> Ram<A> {
         self.max_address as usize + 1
     }
 
+    /// Returns the id of the array in the allocator holding all bytes, e.g. to compare it between
+    /// two points in time.
+    pub(crate) fn data_id(&self) -> A::ArrayId<u8> {
+        self.data
+    }
+
     /// Returns the address range of the continuous region of bytes stored in this RAM unit.
     ///
     /// Note that `self.range().start()` will always be `0`, and `self.range().end()` always
@@ -102,15 +108,18 @@ impl<A: Allocator> Ram<A> {
 }
 
 impl<A: Allocator> Bus<A> for Ram<A> {
-    fn read(&self, buf: &mut [u8], allocator: &mut A, address: u32) {
+    fn read(&self, buf: &mut [u8], allocator: &mut A, address: u32) -> Result<(), BusError> {
         self.read(buf, allocator, address);
+        Ok(())
     }
 
-    fn read_debug(&self, buf: &mut [u8], allocator: &A, address: u32) {
+    fn read_debug(&self, buf: &mut [u8], allocator: &A, address: u32) -> Result<(), BusError> {
         self.read(buf, allocator, address);
+        Ok(())
     }
 
-    fn write(&self, allocator: &mut A, address: u32, buf: &[u8]) {
+    fn write(&self, allocator: &mut A, address: u32, buf: &[u8]) -> Result<(), BusError> {
         self.write(allocator, address, buf);
+        Ok(())
     }
 }
@@ -1,4 +1,4 @@
-use crate::bus::Bus;
+use crate::bus::{Bus, BusError};
 use crate::AddressRange;
 use space_time::allocator::{Allocator, ArrayAccessor, ArrayAccessorMut};
 
@@ -97,16 +97,20 @@ impl<A: Allocator> Rom<A> {
 }
 
 impl<A: Allocator> Bus<A> for Rom<A> {
-    fn read(&self, buf: &mut [u8], allocator: &mut A, address: u32) {
+    fn read(&self, buf: &mut [u8], allocator: &mut A, address: u32) -> Result<(), BusError> {
         self.read(buf, allocator, address);
+        Ok(())
     }
 
-    fn read_debug(&self, buf: &mut [u8], allocator: &A, address: u32) {
+    fn read_debug(&self, buf: &mut [u8], allocator: &A, address: u32) -> Result<(), BusError> {
         self.read(buf, allocator, address);
+        Ok(())
     }
 
     /// See [`Bus::write`].
     ///
-    /// Writes are always ignored.
-    fn write(&self, _allocator: &mut A, _address: u32, _buf: &[u8]) {}
+    /// Writes are always denied.
+    fn write(&self, _allocator: &mut A, _address: u32, _buf: &[u8]) -> Result<(), BusError> {
+        Err(BusError::Denied)
+    }
 }
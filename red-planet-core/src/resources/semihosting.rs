@@ -0,0 +1,1036 @@
+//! Implementation of RISC-V semihosting, through which bare-metal programs use the console, files
+//! and clock of the host, and exit.
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use log::debug;
+use space_time::allocator::Allocator;
+
+use crate::bus::{Bus, BusError};
+use crate::core::clint;
+use crate::PrivilegeLevel;
+
+/// Frequency in Hz of the ticks passed to [`Semihosting::call`]. Boards pass mtime, see
+/// [`clint::MTIME_FREQUENCY`].
+pub const TICK_FREQUENCY: u32 = clint::MTIME_FREQUENCY;
+
+const SYS_OPEN: u32 = 0x01;
+const SYS_CLOSE: u32 = 0x02;
+const SYS_WRITEC: u32 = 0x03;
+const SYS_WRITE0: u32 = 0x04;
+const SYS_WRITE: u32 = 0x05;
+const SYS_READ: u32 = 0x06;
+const SYS_READC: u32 = 0x07;
+const SYS_ISERROR: u32 = 0x08;
+const SYS_ISTTY: u32 = 0x09;
+const SYS_SEEK: u32 = 0x0A;
+const SYS_FLEN: u32 = 0x0C;
+const SYS_REMOVE: u32 = 0x0E;
+const SYS_RENAME: u32 = 0x0F;
+const SYS_CLOCK: u32 = 0x10;
+const SYS_TIME: u32 = 0x11;
+const SYS_ERRNO: u32 = 0x13;
+const SYS_GET_CMDLINE: u32 = 0x15;
+const SYS_HEAPINFO: u32 = 0x16;
+const SYS_EXIT: u32 = 0x18;
+const SYS_EXIT_EXTENDED: u32 = 0x20;
+const SYS_ELAPSED: u32 = 0x30;
+const SYS_TICKFREQ: u32 = 0x31;
+
+/// Reason of an exit through [`SYS_EXIT`] or [`SYS_EXIT_EXTENDED`] that signals a normal exit.
+const ADP_STOPPED_APPLICATION_EXIT: u32 = 0x20026;
+
+/// Contents of the `:semihosting-features` file: the magic bytes, followed by a byte in which only
+/// `SH_EXT_EXIT_EXTENDED` is set.
+const FEATURES: &[u8] = b"SHFB\x01";
+
+const ENOENT: u32 = 2;
+const EIO: u32 = 5;
+const EBADF: u32 = 9;
+const EACCES: u32 = 13;
+const EFAULT: u32 = 14;
+const EINVAL: u32 = 22;
+const ESPIPE: u32 = 29;
+const ENOSYS: u32 = 38;
+
+/// Result of a semihosting call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// The call returns the value, which is written to a0.
+    Return(u32),
+    /// The guest exited with the code, which is zero for a normal exit.
+    Exit(u32),
+}
+
+/// A handle opened by [`SYS_OPEN`].
+#[derive(Debug, Clone)]
+enum Handle {
+    /// `:tt` opened for reading, which is always at its end.
+    Stdin,
+    /// `:tt` opened for writing or appending, which writes to the output.
+    Stdout,
+    /// `:semihosting-features`, read up to `position`.
+    Features { position: usize },
+    File {
+        /// Path relative to the root directory.
+        path: PathBuf,
+        position: usize,
+        read: bool,
+        write: bool,
+        append: bool,
+    },
+}
+
+#[derive(Debug, Clone, Default)]
+struct State {
+    /// Handle `n` is at index `n - 1`, since zero is not a valid handle.
+    handles: Vec<Option<Handle>>,
+    /// Error number of the last call that failed, which [`SYS_ERRNO`] returns.
+    errno: u32,
+}
+
+/// Contents of a file in the root directory, as seen by the guest.
+#[derive(Debug, Clone)]
+struct File {
+    data: Vec<u8>,
+    /// Whether the guest changed the file, so it must be written back to the host.
+    modified: bool,
+}
+
+/// Failed semihosting call.
+#[derive(Debug)]
+struct Error {
+    /// Error number that [`SYS_ERRNO`] returns afterwards.
+    errno: u32,
+    /// Value returned to the guest, which is -1 for most calls.
+    result: u32,
+}
+
+impl Error {
+    fn new(errno: u32) -> Self {
+        Self {
+            errno,
+            result: u32::MAX,
+        }
+    }
+
+    fn returning(self, result: u32) -> Self {
+        Self { result, ..self }
+    }
+}
+
+impl From<BusError> for Error {
+    fn from(_: BusError) -> Self {
+        Self::new(EFAULT)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Self::new(error.raw_os_error().map_or(EIO, |errno| errno as u32))
+    }
+}
+
+/// Host side of the semihosting calls of the guest, which keeps all of its state in the allocator.
+///
+/// Console output is buffered until the host takes it with [`Semihosting::take_output`]. The
+/// console has no input, so reading from it always hits the end of the file.
+///
+/// Files are sandboxed to a root directory: paths are relative to it, and may not contain `..`.
+/// Without a root directory, only the special files `:tt` and `:semihosting-features` can be
+/// opened. The guest never modifies the root directory directly. Instead, files are copied into
+/// the simulation when the guest first opens them, and changes are only written back by
+/// [`Semihosting::write_back`]. Going back in time therefore also undoes file accesses. The root
+/// directory is only read once: the contents it had when the guest first accessed a file are kept
+/// outside of the simulation, so running forward again sees the same contents.
+///
+/// Time is measured in ticks of mtime rather than on the host, so it is deterministic as well.
+/// `SYS_TIME` adds them to a fixed start time.
+///
+/// Resources:
+/// - <https://github.com/riscv-non-isa/riscv-semihosting/blob/main/riscv-semihosting.adoc>
+/// - <https://github.com/ARM-software/abi-aa/blob/main/semihosting/semihosting.rst>
+#[derive(Debug)]
+pub struct Semihosting<A: Allocator> {
+    state: A::Id<State>,
+    /// Files of the root directory that the guest accessed, or `None` if the guest removed them.
+    files: A::Id<BTreeMap<PathBuf, Option<File>>>,
+    /// Output of the guest that was not yet taken by the host.
+    output: A::Id<Vec<u8>>,
+    root: Option<PathBuf>,
+    /// Contents of the files of the root directory when the guest first accessed them, or `None`
+    /// if they did not exist. Unlike `files`, this is not undone by going back in time.
+    host_files: RefCell<BTreeMap<PathBuf, Option<Vec<u8>>>>,
+    /// Whether the directories of the root directory existed when the guest first accessed them.
+    host_directories: RefCell<BTreeMap<PathBuf, bool>>,
+    cmdline: String,
+    /// Whether calls made in S-mode are performed.
+    supervisor: bool,
+    /// Seconds since the Unix epoch at which the simulation started.
+    start_time: u32,
+}
+
+impl<A: Allocator> Semihosting<A> {
+    /// Creates the host side of semihosting, with files sandboxed to `root`, and with the given
+    /// command line for `SYS_GET_CMDLINE`. Calls made in S-mode are only accepted if `supervisor`
+    /// is set. `SYS_TIME` counts from `start_time`, in seconds since the Unix epoch.
+    pub fn new(
+        allocator: &mut A,
+        root: Option<PathBuf>,
+        cmdline: String,
+        supervisor: bool,
+        start_time: u32,
+    ) -> Self {
+        Self {
+            state: allocator.insert(State::default()),
+            files: allocator.insert(BTreeMap::new()),
+            output: allocator.insert(Vec::new()),
+            root,
+            host_files: RefCell::default(),
+            host_directories: RefCell::default(),
+            cmdline,
+            supervisor,
+            start_time,
+        }
+    }
+
+    /// Returns `true` if calls made at `privilege` are performed. Calls from U-mode are never
+    /// performed, so user programs cannot access the host, and calls from S-mode only if enabled.
+    pub fn accepts(&self, privilege: PrivilegeLevel) -> bool {
+        match privilege {
+            PrivilegeLevel::User => false,
+            PrivilegeLevel::Supervisor => self.supervisor,
+            PrivilegeLevel::Machine => true,
+        }
+    }
+
+    /// Closes all handles and clears the error number. The files of the root directory keep the
+    /// contents the guest gave them, like a disk does. So does output the host did not take yet.
+    pub fn reset(&self, allocator: &mut A) {
+        *allocator.get_mut(self.state).unwrap() = State::default();
+    }
+
+    pub fn drop(self, allocator: &mut A) {
+        allocator.remove(self.state).unwrap();
+        allocator.remove(self.files).unwrap();
+        allocator.remove(self.output).unwrap();
+    }
+
+    /// Performs semihosting call `operation` with the given parameter, accessing the memory of the
+    /// guest through `memory`. `ticks` is the current time, in ticks at [`TICK_FREQUENCY`].
+    pub fn call(
+        &self,
+        allocator: &mut A,
+        memory: &dyn Bus<A>,
+        operation: u32,
+        parameter: u32,
+        ticks: u64,
+    ) -> Outcome {
+        let result = match operation {
+            SYS_OPEN => self.open(allocator, memory, parameter),
+            SYS_CLOSE => self.close(allocator, memory, parameter),
+            SYS_WRITEC => self.write_c(allocator, memory, parameter),
+            SYS_WRITE0 => self.write_0(allocator, memory, parameter),
+            SYS_WRITE => self.write(allocator, memory, parameter),
+            SYS_READ => self.read(allocator, memory, parameter),
+            // The console has no input.
+            SYS_READC => Ok(u32::MAX),
+            SYS_ISERROR => read_words(allocator, memory, parameter)
+                .map(|[status]| ((status as i32) < 0) as u32),
+            SYS_ISTTY => self.is_tty(allocator, memory, parameter),
+            SYS_SEEK => self.seek(allocator, memory, parameter),
+            SYS_FLEN => self.flen(allocator, memory, parameter),
+            SYS_REMOVE => self.remove(allocator, memory, parameter),
+            SYS_RENAME => self.rename(allocator, memory, parameter),
+            SYS_CLOCK => Ok((ticks / (TICK_FREQUENCY / 100) as u64) as u32),
+            SYS_TIME => Ok(self
+                .start_time
+                .wrapping_add((ticks / TICK_FREQUENCY as u64) as u32)),
+            SYS_ERRNO => Ok(allocator.get(self.state).unwrap().errno),
+            SYS_GET_CMDLINE => self.get_cmdline(allocator, memory, parameter),
+            SYS_HEAPINFO => read_words(allocator, memory, parameter).and_then(|[block]| {
+                // Zeros let the C library pick the heap and stack itself.
+                memory.write(allocator, block, &[0; 16])?;
+                Ok(0)
+            }),
+            SYS_EXIT => {
+                return Outcome::Exit((parameter != ADP_STOPPED_APPLICATION_EXIT) as u32);
+            }
+            SYS_EXIT_EXTENDED => match read_words(allocator, memory, parameter) {
+                Ok([ADP_STOPPED_APPLICATION_EXIT, code]) => return Outcome::Exit(code),
+                Ok(_) => return Outcome::Exit(1),
+                Err(error) => Err(error),
+            },
+            SYS_ELAPSED => memory
+                .write(allocator, parameter, &ticks.to_le_bytes())
+                .map(|()| 0)
+                .map_err(Error::from),
+            SYS_TICKFREQ => Ok(TICK_FREQUENCY),
+            _ => {
+                debug!("Unsupported semihosting call {operation:#x}");
+                Err(Error::new(ENOSYS))
+            }
+        };
+        match result {
+            Ok(value) => Outcome::Return(value),
+            Err(Error { errno, result }) => {
+                debug!("Semihosting call {operation:#x} failed with errno {errno}");
+                allocator.get_mut(self.state).unwrap().errno = errno;
+                Outcome::Return(result)
+            }
+        }
+    }
+
+    fn open(&self, allocator: &mut A, memory: &dyn Bus<A>, parameter: u32) -> Result<u32, Error> {
+        let [name, mode, len] = read_words(allocator, memory, parameter)?;
+        let name = read_bytes(allocator, memory, name, len)?;
+        if mode > 11 {
+            return Err(Error::new(EINVAL));
+        }
+        // Modes 0 to 3 read, 4 to 7 truncate and 8 to 11 append. Odd modes are binary, which is
+        // the same, and modes 2, 3, 6, 7, 10 and 11 can both read and write.
+        let update = mode & 2 != 0;
+        let handle = match &name[..] {
+            b":tt" if mode < 4 => Handle::Stdin,
+            b":tt" => Handle::Stdout,
+            b":semihosting-features" if mode < 2 => Handle::Features { position: 0 },
+            b":semihosting-features" => return Err(Error::new(EACCES)),
+            name => {
+                let path = self.resolve(name)?;
+                let exists = self.load(allocator, &path)?;
+                if mode < 4 && !exists {
+                    return Err(Error::new(ENOENT));
+                }
+                let create = mode >= 4 && !exists;
+                if create && !self.parent_exists(&path)? {
+                    return Err(Error::new(ENOENT));
+                }
+                if create || (4..8).contains(&mode) {
+                    let file = File {
+                        data: Vec::new(),
+                        modified: true,
+                    };
+                    let files = allocator.get_mut(self.files).unwrap();
+                    files.insert(path.clone(), Some(file));
+                }
+                Handle::File {
+                    path,
+                    position: 0,
+                    read: mode < 4 || update,
+                    write: mode >= 4 || update,
+                    append: mode >= 8,
+                }
+            }
+        };
+
+        let handles = &mut allocator.get_mut(self.state).unwrap().handles;
+        let index = match handles.iter().position(Option::is_none) {
+            Some(index) => {
+                handles[index] = Some(handle);
+                index
+            }
+            None => {
+                handles.push(Some(handle));
+                handles.len() - 1
+            }
+        };
+        Ok(index as u32 + 1)
+    }
+
+    fn close(&self, allocator: &mut A, memory: &dyn Bus<A>, parameter: u32) -> Result<u32, Error> {
+        let [handle] = read_words(allocator, memory, parameter)?;
+        self.handle(allocator, handle)?;
+        let handles = &mut allocator.get_mut(self.state).unwrap().handles;
+        handles[handle as usize - 1] = None;
+        Ok(0)
+    }
+
+    fn write_c(
+        &self,
+        allocator: &mut A,
+        memory: &dyn Bus<A>,
+        parameter: u32,
+    ) -> Result<u32, Error> {
+        let mut char = [0];
+        memory.read(&mut char, allocator, parameter)?;
+        allocator.get_mut(self.output).unwrap().push(char[0]);
+        Ok(0)
+    }
+
+    fn write_0(
+        &self,
+        allocator: &mut A,
+        memory: &dyn Bus<A>,
+        parameter: u32,
+    ) -> Result<u32, Error> {
+        let mut string = Vec::new();
+        let mut char = [0];
+        for address in (parameter..=u32::MAX).chain(0..parameter) {
+            memory.read(&mut char, allocator, address)?;
+            if char[0] == 0 {
+                break;
+            }
+            string.push(char[0]);
+        }
+        allocator.get_mut(self.output).unwrap().extend(string);
+        Ok(0)
+    }
+
+    /// Returns the number of bytes that were not written.
+    fn write(&self, allocator: &mut A, memory: &dyn Bus<A>, parameter: u32) -> Result<u32, Error> {
+        let [handle, buffer, len] = read_words(allocator, memory, parameter)?;
+        let not_written = |error: Error| error.returning(len);
+        let data = read_bytes(allocator, memory, buffer, len).map_err(not_written)?;
+        match self.handle(allocator, handle).map_err(not_written)? {
+            Handle::Stdout => allocator.get_mut(self.output).unwrap().extend(data),
+            Handle::File {
+                path,
+                position,
+                write: true,
+                append,
+                ..
+            } => {
+                let files = allocator.get_mut(self.files).unwrap();
+                let Some(Some(file)) = files.get_mut(&path) else {
+                    return Err(Error::new(ENOENT).returning(len));
+                };
+                let start = if append { file.data.len() } else { position };
+                let end = start + data.len();
+                if end > file.data.len() {
+                    file.data.resize(end, 0);
+                }
+                file.data[start..end].copy_from_slice(&data);
+                file.modified = true;
+                self.set_position(allocator, handle, end);
+            }
+            _ => return Err(Error::new(EBADF).returning(len)),
+        }
+        Ok(0)
+    }
+
+    /// Returns the number of bytes that were not read, which is nonzero at the end of the file.
+    fn read(&self, allocator: &mut A, memory: &dyn Bus<A>, parameter: u32) -> Result<u32, Error> {
+        let [handle, buffer, len] = read_words(allocator, memory, parameter)?;
+        let not_read = |error: Error| error.returning(len);
+        let (data, position) = match self.handle(allocator, handle).map_err(not_read)? {
+            Handle::Stdin => return Ok(len),
+            Handle::Features { position } => (FEATURES, position),
+            Handle::File {
+                path,
+                position,
+                read: true,
+                ..
+            } => match allocator.get(self.files).unwrap().get(&path) {
+                Some(Some(file)) => (&file.data[..], position),
+                _ => return Err(Error::new(ENOENT).returning(len)),
+            },
+            _ => return Err(Error::new(EBADF).returning(len)),
+        };
+        let start = position.min(data.len());
+        let data = data[start..]
+            .iter()
+            .take(len as usize)
+            .copied()
+            .collect::<Vec<_>>();
+        memory
+            .write(allocator, buffer, &data)
+            .map_err(|error| Error::from(error).returning(len))?;
+        self.set_position(allocator, handle, start + data.len());
+        Ok(len - data.len() as u32)
+    }
+
+    fn is_tty(&self, allocator: &mut A, memory: &dyn Bus<A>, parameter: u32) -> Result<u32, Error> {
+        let [handle] = read_words(allocator, memory, parameter)?;
+        match self.handle(allocator, handle)? {
+            Handle::Stdin | Handle::Stdout => Ok(1),
+            Handle::Features { .. } | Handle::File { .. } => Ok(0),
+        }
+    }
+
+    fn seek(&self, allocator: &mut A, memory: &dyn Bus<A>, parameter: u32) -> Result<u32, Error> {
+        let [handle, position] = read_words(allocator, memory, parameter)?;
+        let len = match self.handle(allocator, handle)? {
+            Handle::Stdin | Handle::Stdout => return Err(Error::new(ESPIPE)),
+            handle => self.len(allocator, &handle)?,
+        };
+        if position > len {
+            return Err(Error::new(EINVAL));
+        }
+        self.set_position(allocator, handle, position as usize);
+        Ok(0)
+    }
+
+    fn flen(&self, allocator: &mut A, memory: &dyn Bus<A>, parameter: u32) -> Result<u32, Error> {
+        let [handle] = read_words(allocator, memory, parameter)?;
+        let handle = self.handle(allocator, handle)?;
+        self.len(allocator, &handle)
+    }
+
+    /// Returns zero, or the error number if the call failed.
+    fn remove(&self, allocator: &mut A, memory: &dyn Bus<A>, parameter: u32) -> Result<u32, Error> {
+        let remove = |allocator: &mut A| {
+            let [name, len] = read_words(allocator, memory, parameter)?;
+            let path = self.resolve(&read_bytes(allocator, memory, name, len)?)?;
+            if !self.load(allocator, &path)? {
+                return Err(Error::new(ENOENT));
+            }
+            allocator.get_mut(self.files).unwrap().insert(path, None);
+            Ok(0)
+        };
+        remove(allocator).map_err(|error| Error {
+            result: error.errno,
+            ..error
+        })
+    }
+
+    /// Returns zero, or the error number if the call failed.
+    fn rename(&self, allocator: &mut A, memory: &dyn Bus<A>, parameter: u32) -> Result<u32, Error> {
+        let rename = |allocator: &mut A| {
+            let [old, old_len, new, new_len] = read_words(allocator, memory, parameter)?;
+            let old = self.resolve(&read_bytes(allocator, memory, old, old_len)?)?;
+            let new = self.resolve(&read_bytes(allocator, memory, new, new_len)?)?;
+            if !self.load(allocator, &old)? {
+                return Err(Error::new(ENOENT));
+            }
+            if !self.parent_exists(&new)? {
+                return Err(Error::new(ENOENT));
+            }
+            let files = allocator.get_mut(self.files).unwrap();
+            // Unwrap safety: loading the file ensures it is present.
+            let mut file = files.insert(old, None).flatten().unwrap();
+            file.modified = true;
+            files.insert(new, Some(file));
+            Ok(0)
+        };
+        rename(allocator).map_err(|error| Error {
+            result: error.errno,
+            ..error
+        })
+    }
+
+    fn get_cmdline(
+        &self,
+        allocator: &mut A,
+        memory: &dyn Bus<A>,
+        parameter: u32,
+    ) -> Result<u32, Error> {
+        let [buffer, len] = read_words(allocator, memory, parameter)?;
+        let cmdline = self.cmdline.as_bytes();
+        if cmdline.len() >= len as usize {
+            return Err(Error::new(EINVAL));
+        }
+        memory.write(allocator, buffer, &[cmdline, &[0]].concat())?;
+        memory.write(
+            allocator,
+            parameter.wrapping_add(4),
+            &(cmdline.len() as u32).to_le_bytes(),
+        )?;
+        Ok(0)
+    }
+
+    /// Returns the open handle with the given number.
+    fn handle(&self, allocator: &A, handle: u32) -> Result<Handle, Error> {
+        let handles = &allocator.get(self.state).unwrap().handles;
+        let index = (handle as usize).wrapping_sub(1);
+        match handles.get(index) {
+            Some(Some(handle)) => Ok(handle.clone()),
+            _ => Err(Error::new(EBADF)),
+        }
+    }
+
+    fn set_position(&self, allocator: &mut A, handle: u32, new_position: usize) {
+        let handles = &mut allocator.get_mut(self.state).unwrap().handles;
+        if let Some(Handle::Features { position } | Handle::File { position, .. }) =
+            &mut handles[handle as usize - 1]
+        {
+            *position = new_position;
+        }
+    }
+
+    /// Returns the length of the file opened by `handle`, which is zero for the console.
+    fn len(&self, allocator: &A, handle: &Handle) -> Result<u32, Error> {
+        match handle {
+            Handle::Stdin | Handle::Stdout => Ok(0),
+            Handle::Features { .. } => Ok(FEATURES.len() as u32),
+            Handle::File { path, .. } => match allocator.get(self.files).unwrap().get(path) {
+                Some(Some(file)) => Ok(file.data.len() as u32),
+                _ => Err(Error::new(ENOENT)),
+            },
+        }
+    }
+
+    /// Turns the name of a file into a path relative to the root directory, which must not leave
+    /// it.
+    fn resolve(&self, name: &[u8]) -> Result<PathBuf, Error> {
+        let name = std::str::from_utf8(name).map_err(|_| Error::new(ENOENT))?;
+        let mut path = PathBuf::new();
+        for component in Path::new(name).components() {
+            match component {
+                Component::Normal(component) => path.push(component),
+                Component::CurDir => {}
+                Component::ParentDir | Component::RootDir | Component::Prefix(_) => {
+                    return Err(Error::new(EACCES));
+                }
+            }
+        }
+        match path.as_os_str().is_empty() {
+            true => Err(Error::new(ENOENT)),
+            false => Ok(path),
+        }
+    }
+
+    /// Returns the path on the host of the file at `path`, relative to the root directory.
+    fn root_path(&self, path: &Path) -> Result<PathBuf, Error> {
+        match &self.root {
+            Some(root) => Ok(root.join(path)),
+            None => Err(Error::new(EACCES)),
+        }
+    }
+
+    /// Returns whether the directory containing the file at `path` exists on the host, as it did
+    /// when the guest first accessed it.
+    fn parent_exists(&self, path: &Path) -> Result<bool, Error> {
+        let parent = path.parent().unwrap_or(Path::new(""));
+        if let Some(&exists) = self.host_directories.borrow().get(parent) {
+            return Ok(exists);
+        }
+        let exists = self.root_path(parent)?.is_dir();
+        let mut host_directories = self.host_directories.borrow_mut();
+        host_directories.insert(parent.to_path_buf(), exists);
+        Ok(exists)
+    }
+
+    /// Copies the file at `path` into the simulation if it was not accessed before, and returns
+    /// whether it exists.
+    fn load(&self, allocator: &mut A, path: &Path) -> Result<bool, Error> {
+        if let Some(file) = allocator.get(self.files).unwrap().get(path) {
+            return Ok(file.is_some());
+        }
+        // The file may have been accessed in a state that was undone since, in which case it must
+        // have the same contents as back then, even if it changed on the host.
+        let cached = self.host_files.borrow().get(path).cloned();
+        let data = match cached {
+            Some(data) => data,
+            None => {
+                let data = match fs::read(self.root_path(path)?) {
+                    Ok(data) => Some(data),
+                    Err(error) if error.kind() == io::ErrorKind::NotFound => None,
+                    Err(error) => return Err(error.into()),
+                };
+                let mut host_files = self.host_files.borrow_mut();
+                host_files.insert(path.to_path_buf(), data.clone());
+                data
+            }
+        };
+        let Some(data) = data else {
+            return Ok(false);
+        };
+        let file = File {
+            data,
+            modified: false,
+        };
+        let files = allocator.get_mut(self.files).unwrap();
+        files.insert(path.to_path_buf(), Some(file));
+        Ok(true)
+    }
+
+    /// Writes the files that the guest created, changed, renamed or removed back to the root
+    /// directory.
+    ///
+    /// Afterwards, the root directory no longer matches the contents seen by the guest, so this
+    /// should only be done once the simulation ends.
+    pub fn write_back(&self, allocator: &A) -> io::Result<()> {
+        let Some(root) = &self.root else {
+            return Ok(());
+        };
+        for (path, file) in allocator.get(self.files).unwrap() {
+            let path = root.join(path);
+            match file {
+                Some(File {
+                    data,
+                    modified: true,
+                }) => fs::write(path, data)?,
+                Some(File {
+                    modified: false, ..
+                }) => {}
+                None => match fs::remove_file(path) {
+                    Err(error) if error.kind() != io::ErrorKind::NotFound => return Err(error),
+                    _ => {}
+                },
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the output of the guest that was not yet taken.
+    pub fn pending_output<'a>(&self, allocator: &'a A) -> &'a [u8] {
+        allocator.get(self.output).unwrap()
+    }
+
+    /// Takes the output of the guest, leaving the buffer empty.
+    pub fn take_output(&self, allocator: &mut A) -> Vec<u8> {
+        if self.pending_output(allocator).is_empty() {
+            return Vec::new();
+        }
+        std::mem::take(allocator.get_mut(self.output).unwrap())
+    }
+}
+
+/// Reads the `N` words of a parameter block.
+fn read_words<A: Allocator, const N: usize>(
+    allocator: &mut A,
+    memory: &dyn Bus<A>,
+    address: u32,
+) -> Result<[u32; N], Error> {
+    let mut words = [0; N];
+    for (index, word) in words.iter_mut().enumerate() {
+        let mut buf = [0; 4];
+        memory.read(&mut buf, allocator, address.wrapping_add(4 * index as u32))?;
+        *word = u32::from_le_bytes(buf);
+    }
+    Ok(words)
+}
+
+fn read_bytes<A: Allocator>(
+    allocator: &mut A,
+    memory: &dyn Bus<A>,
+    address: u32,
+    len: u32,
+) -> Result<Vec<u8>, Error> {
+    let mut buf = vec![0; len as usize];
+    memory.read(&mut buf, allocator, address)?;
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use space_time::SpaceTime;
+
+    use super::*;
+    use crate::board::testing::{boot, boot_images, code, run_until};
+    use crate::board::{Board, Config, ExitStatus, SemihostingConfig};
+    use crate::core::csr;
+    use crate::registers::Specifier;
+    use crate::resources::ram::Ram;
+    use crate::simulator::{SimulationAllocator, Simulator};
+
+    const BLOCK: u32 = 0x1000;
+    const NAME: u32 = 0x2000;
+    const BUFFER: u32 = 0x3000;
+
+    struct Setup {
+        allocator: SpaceTime,
+        ram: Ram<SpaceTime>,
+        semihosting: Semihosting<SpaceTime>,
+    }
+
+    impl Setup {
+        fn new(root: Option<PathBuf>) -> Self {
+            let mut allocator = SpaceTime::new();
+            let ram = Ram::new(&mut allocator, 0x10000).unwrap();
+            let semihosting =
+                Semihosting::new(&mut allocator, root, "test -v".to_owned(), false, 1000);
+            Self {
+                allocator,
+                ram,
+                semihosting,
+            }
+        }
+
+        /// Performs call `operation` with a parameter block holding `words`.
+        fn call(&mut self, operation: u32, words: &[u32]) -> Outcome {
+            let block: Vec<u8> = words.iter().flat_map(|word| word.to_le_bytes()).collect();
+            self.ram.write(&mut self.allocator, BLOCK, &block);
+            self.semihosting
+                .call(&mut self.allocator, &self.ram, operation, BLOCK, 1234)
+        }
+
+        fn returned(&mut self, operation: u32, words: &[u32]) -> u32 {
+            match self.call(operation, words) {
+                Outcome::Return(result) => result,
+                Outcome::Exit(code) => panic!("unexpected exit with code {code}"),
+            }
+        }
+
+        fn open(&mut self, name: &str, mode: u32) -> u32 {
+            self.ram.write(&mut self.allocator, NAME, name.as_bytes());
+            self.returned(SYS_OPEN, &[NAME, mode, name.len() as u32])
+        }
+
+        fn write(&mut self, handle: u32, data: &[u8]) -> u32 {
+            self.ram.write(&mut self.allocator, BUFFER, data);
+            self.returned(SYS_WRITE, &[handle, BUFFER, data.len() as u32])
+        }
+
+        /// Reads up to `len` bytes, and returns the bytes that were read.
+        fn read(&mut self, handle: u32, len: u32) -> Vec<u8> {
+            let not_read = self.returned(SYS_READ, &[handle, BUFFER, len]);
+            let mut data = vec![0; (len - not_read) as usize];
+            self.ram.read(&mut data, &self.allocator, BUFFER);
+            data
+        }
+
+        fn errno(&mut self) -> u32 {
+            self.returned(SYS_ERRNO, &[])
+        }
+    }
+
+    #[test]
+    fn test_console() {
+        let mut setup = Setup::new(None);
+        setup.ram.write(&mut setup.allocator, BLOCK, b"a");
+        (setup.semihosting).call(&mut setup.allocator, &setup.ram, SYS_WRITEC, BLOCK, 0);
+        setup.ram.write(&mut setup.allocator, NAME, b"bc\0d");
+        (setup.semihosting).call(&mut setup.allocator, &setup.ram, SYS_WRITE0, NAME, 0);
+
+        let stdout = setup.open(":tt", 4);
+        let stdin = setup.open(":tt", 0);
+        assert_eq!((1, 2), (stdout, stdin));
+        assert_eq!(0, setup.write(stdout, b"de"));
+        assert_eq!(1, setup.returned(SYS_ISTTY, &[stdout]));
+        assert!(setup.read(stdin, 4).is_empty());
+        assert_eq!(2, setup.write(stdin, b"fg"));
+        assert_eq!(EBADF, setup.errno());
+        assert_eq!(
+            b"abcde".to_vec(),
+            setup.semihosting.take_output(&mut setup.allocator)
+        );
+
+        let features = setup.open(":semihosting-features", 0);
+        assert_eq!(5, setup.returned(SYS_FLEN, &[features]));
+        assert_eq!(b"SHFB\x01".to_vec(), setup.read(features, 8));
+        assert_eq!(0, setup.returned(SYS_CLOSE, &[features]));
+        assert_eq!(u32::MAX, setup.returned(SYS_CLOSE, &[features]));
+
+        // Without a root directory, no files can be opened.
+        assert_eq!(u32::MAX, setup.open("file.txt", 0));
+        assert_eq!(EACCES, setup.errno());
+    }
+
+    #[test]
+    fn test_misc() {
+        let mut setup = Setup::new(None);
+        assert_eq!(1, setup.returned(SYS_ISERROR, &[-5i32 as u32]));
+        assert_eq!(0, setup.returned(SYS_ISERROR, &[5]));
+        assert_eq!(1234 / 100_000, setup.returned(SYS_CLOCK, &[]));
+        assert_eq!(1000, setup.returned(SYS_TIME, &[]));
+        assert_eq!(TICK_FREQUENCY, setup.returned(SYS_TICKFREQ, &[]));
+        assert_eq!(0, setup.returned(SYS_ELAPSED, &[]));
+        let mut ticks = [0; 8];
+        setup.ram.read(&mut ticks, &setup.allocator, BLOCK);
+        assert_eq!(1234, u64::from_le_bytes(ticks));
+
+        assert_eq!(0, setup.returned(SYS_GET_CMDLINE, &[BUFFER, 80]));
+        let mut cmdline = [0; 8];
+        setup.ram.read(&mut cmdline, &setup.allocator, BUFFER);
+        assert_eq!(b"test -v\0", &cmdline);
+        assert_eq!(u32::MAX, setup.returned(SYS_GET_CMDLINE, &[BUFFER, 7]));
+
+        assert_eq!(u32::MAX, setup.returned(0x12, &[]));
+        assert_eq!(ENOSYS, setup.errno());
+
+        // On RV32, SYS_EXIT takes the reason directly rather than a parameter block.
+        assert_eq!(Outcome::Exit(1), setup.call(SYS_EXIT, &[]));
+        assert_eq!(
+            Outcome::Exit(0),
+            (setup.semihosting).call(
+                &mut setup.allocator,
+                &setup.ram,
+                SYS_EXIT,
+                ADP_STOPPED_APPLICATION_EXIT,
+                0
+            )
+        );
+        assert_eq!(
+            Outcome::Exit(3),
+            setup.call(SYS_EXIT_EXTENDED, &[ADP_STOPPED_APPLICATION_EXIT, 3])
+        );
+        assert_eq!(
+            Outcome::Exit(1),
+            setup.call(SYS_EXIT_EXTENDED, &[0x20023, 0])
+        );
+    }
+
+    #[test]
+    fn test_files() {
+        let root =
+            std::env::temp_dir().join(format!("red-planet-semihosting-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("input.txt"), b"hello").unwrap();
+        let mut setup = Setup::new(Some(root.clone()));
+
+        let input = setup.open("./input.txt", 1);
+        assert_eq!(5, setup.returned(SYS_FLEN, &[input]));
+        assert_eq!(0, setup.returned(SYS_ISTTY, &[input]));
+        assert_eq!(b"hel".to_vec(), setup.read(input, 3));
+        assert_eq!(0, setup.returned(SYS_SEEK, &[input, 1]));
+        assert_eq!(b"ello".to_vec(), setup.read(input, 8));
+        assert_eq!(u32::MAX, setup.returned(SYS_SEEK, &[input, 6]));
+        assert_eq!(5, setup.write(input, b"bye!!"));
+        assert_eq!(EBADF, setup.errno());
+
+        // Files cannot be opened outside of the root directory.
+        assert_eq!(u32::MAX, setup.open("../input.txt", 0));
+        assert_eq!(EACCES, setup.errno());
+        assert_eq!(u32::MAX, setup.open("/etc/passwd", 0));
+        assert_eq!(EACCES, setup.errno());
+        assert_eq!(u32::MAX, setup.open("missing.txt", 0));
+        assert_eq!(ENOENT, setup.errno());
+        assert_eq!(u32::MAX, setup.open("missing/output.txt", 4));
+        assert_eq!(ENOENT, setup.errno());
+
+        let snapshot = setup.allocator.make_snapshot();
+        let output = setup.open("output.txt", 6);
+        assert_eq!(0, setup.write(output, b"abc"));
+        assert_eq!(0, setup.returned(SYS_SEEK, &[output, 1]));
+        assert_eq!(0, setup.write(output, b"XYZ"));
+        assert_eq!(0, setup.returned(SYS_SEEK, &[output, 0]));
+        assert_eq!(b"aXYZ".to_vec(), setup.read(output, 8));
+        let appended = setup.open("input.txt", 8);
+        assert_eq!(0, setup.write(appended, b"!"));
+        assert_eq!(6, setup.returned(SYS_FLEN, &[input]));
+
+        // The root directory is only modified by write-backs.
+        assert!(!root.join("output.txt").exists());
+        assert_eq!(b"hello".to_vec(), fs::read(root.join("input.txt")).unwrap());
+
+        // Going back in time undoes the file accesses.
+        setup.allocator.checkout(snapshot).unwrap();
+        assert_eq!(u32::MAX, setup.returned(SYS_FLEN, &[output]));
+        assert_eq!(5, setup.returned(SYS_FLEN, &[input]));
+        // Changes made on the host after the first read are not picked up again.
+        fs::write(root.join("input.txt"), b"changed!").unwrap();
+        let reopened = setup.open("input.txt", 0);
+        assert_eq!(5, setup.returned(SYS_FLEN, &[reopened]));
+        let output = setup.open("output.txt", 4);
+        assert_eq!(0, setup.write(output, b"new"));
+
+        setup
+            .ram
+            .write(&mut setup.allocator, NAME, b"input.txtrenamed.txt");
+        assert_eq!(0, setup.returned(SYS_RENAME, &[NAME, 9, NAME + 9, 11]));
+        assert_eq!(ENOENT, setup.returned(SYS_REMOVE, &[NAME, 9]));
+        assert_eq!(u32::MAX, setup.open("input.txt", 0));
+
+        setup.semihosting.write_back(&setup.allocator).unwrap();
+        assert_eq!(b"new".to_vec(), fs::read(root.join("output.txt")).unwrap());
+        assert_eq!(
+            b"hello".to_vec(),
+            fs::read(root.join("renamed.txt")).unwrap()
+        );
+        assert!(!root.join("input.txt").exists());
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_board() {
+        let program = code(&[
+            0x800015B7, // lui    a1, 0x80001
+            0x00300513, // li     a0, 3 (SYS_WRITEC)
+            0x01F01013, // slli   zero, zero, 0x1f
+            0x00100073, // ebreak
+            0x40705013, // srai   zero, zero, 7
+            0x000205B7, // lui    a1, 0x20
+            0x02658593, // addi   a1, a1, 0x26 (ADP_Stopped_ApplicationExit)
+            0x01800513, // li     a0, 0x18 (SYS_EXIT)
+            0x01F01013, // slli   zero, zero, 0x1f
+            0x00100073, // ebreak
+            0x40705013, // srai   zero, zero, 7
+            0x0000006F, // j      .
+        ]);
+        let boot = |semihosting| {
+            let config = Config {
+                semihosting,
+                ..Config::default()
+            };
+            let images = vec![(0x8000_0000, program.clone()), (0x8000_1000, b"x".to_vec())];
+            boot_images(config, images)
+        };
+
+        let mut simulator = boot(Some(SemihostingConfig::default()));
+        run_until(&mut simulator, 0x8000_0024);
+        simulator.step();
+        let (allocator, board) = simulator.inspect();
+        assert_eq!(Some(ExitStatus::Pass), board.exit_status(allocator));
+        let semihosting = board.semihosting().unwrap();
+        assert_eq!(b"x", semihosting.pending_output(allocator));
+
+        // Without semihosting, the ebreak raises a breakpoint exception.
+        let mut simulator = boot(None);
+        run_until(&mut simulator, 0x8000_000C);
+        simulator.step();
+        let mcause = simulator.step_with("read mcause", |allocator, board| {
+            let core = board.core(0);
+            assert_eq!(3, core.registers(allocator).x(Specifier::A0));
+            core.read_csr(allocator, csr::MCAUSE, PrivilegeLevel::Machine)
+        });
+        assert_eq!(3, mcause.unwrap());
+    }
+
+    #[test]
+    fn test_board_privilege() {
+        // Runs the semihosting exit call in the mode set in mstatus.MPP, and steps past it.
+        let run = |mstatus, supervisor| {
+            let config = Config {
+                semihosting: Some(SemihostingConfig {
+                    supervisor,
+                    ..SemihostingConfig::default()
+                }),
+                ..Config::default()
+            };
+            let mut simulator = boot(
+                config,
+                &[
+                    0x800002B7, // lui    t0, 0x80000
+                    0x02028293, // addi   t0, t0, 0x20
+                    0x00001337, // lui    t1, 1
+                    0x80030313, // addi   t1, t1, -2048 (MPP = S)
+                    mstatus,    // csrw   mstatus, t1 or zero
+                    0x34129073, // csrw   mepc, t0
+                    0x30200073, // mret
+                    0x00000013, // nop
+                    0x01800513, // li     a0, 0x18 (SYS_EXIT)
+                    0x000205B7, // lui    a1, 0x20
+                    0x02658593, // addi   a1, a1, 0x26 (ADP_Stopped_ApplicationExit)
+                    0x01F01013, // slli   zero, zero, 0x1f
+                    0x00100073, // ebreak
+                    0x40705013, // srai   zero, zero, 7
+                    0x0000006F, // j      .
+                ],
+            );
+            run_until(&mut simulator, 0x8000_0030);
+            simulator.step();
+            simulator
+        };
+        let mcause = |simulator: &mut Simulator<Board<SimulationAllocator>>| {
+            simulator.step_with("read mcause", |allocator, board| {
+                board
+                    .core(0)
+                    .read_csr(allocator, csr::MCAUSE, PrivilegeLevel::Machine)
+            })
+        };
+
+        // In S-mode, semihosting calls are only performed if enabled.
+        let simulator = run(0x30031073, true);
+        let (allocator, board) = simulator.inspect();
+        assert_eq!(Some(ExitStatus::Pass), board.exit_status(allocator));
+        let mut simulator = run(0x30031073, false);
+        let (allocator, board) = simulator.inspect();
+        assert_eq!(None, board.exit_status(allocator));
+        assert_eq!(3, mcause(&mut simulator).unwrap());
+
+        // In U-mode, they never are.
+        let mut simulator = run(0x30001073, true);
+        let (allocator, board) = simulator.inspect();
+        assert_eq!(None, board.exit_status(allocator));
+        assert_eq!(
+            PrivilegeLevel::Machine,
+            board.core(0).privilege_mode(allocator)
+        );
+        assert_eq!(3, mcause(&mut simulator).unwrap());
+    }
+}
@@ -1,6 +1,6 @@
 //! Implementation of an UART16550A as a simulatable device.
 
-use crate::bus::Bus;
+use crate::bus::{Bus, BusError};
 use crate::interrupt::DynIrqCallback;
 use bitvec::order::Lsb0;
 use bitvec::view::BitView;
@@ -27,10 +27,14 @@ struct State {
     iir: u8,
     /// Line Control Register
     lcr: u8,
+    /// Modem Control Register
+    mcr: u8,
     /// Line Status Register
     lsr: u8,
     /// Modem Status Register
     msr: u8,
+    /// Scratch Register
+    scr: u8,
     /// Divisor Latch Register
     dlr: u16,
 
@@ -56,8 +60,10 @@ impl State {
             ier: 0x00,
             iir: 0xC1,
             lcr: 0x03,
+            mcr: 0x00,
             lsr: 0x60,
             msr: 0x00,
+            scr: 0x00,
             dlr: 0x0000,
             // RX FIFO Interrupt Trigger Level is 14 bytes on reset
             rx_fifo_itl: 14,
@@ -158,8 +164,6 @@ impl State {
 
 #[derive(Error, Debug)]
 pub enum ReadError {
-    #[error("cannot read from write-only register")]
-    WriteOnly(&'static str),
     #[error("no register mapped to address {0:#x}")]
     AddressInvalid(u8),
 }
@@ -221,9 +225,10 @@ impl<A: Allocator> Uart<A> {
             1 => self.read_ier(allocator),
             2 => self.read_iir(allocator),
             3 => self.read_lcr(allocator),
-            4 => return Err(ReadError::WriteOnly("Modem Control Register")),
+            4 => self.read_mcr(allocator),
             5 => self.read_lsr(allocator),
             6 => self.read_msr(allocator),
+            7 => self.read_scr(allocator),
             _ => return Err(ReadError::AddressInvalid(address)),
         };
         Ok(value)
@@ -239,9 +244,10 @@ impl<A: Allocator> Uart<A> {
             1 => self.read_ier_pure(allocator),
             2 => self.read_iir_pure(allocator),
             3 => self.read_lcr_pure(allocator),
-            4 => return Err(ReadError::WriteOnly("Modem Control Register")),
+            4 => self.read_mcr_pure(allocator),
             5 => self.read_lsr_pure(allocator),
             6 => self.read_msr_pure(allocator),
+            7 => self.read_scr_pure(allocator),
             _ => return Err(ReadError::AddressInvalid(address)),
         };
         Ok(value)
@@ -260,6 +266,7 @@ impl<A: Allocator> Uart<A> {
             4 => self.write_mcr(allocator, value),
             5 => return Err(WriteError::ReadOnly("Line Status Register")),
             6 => return Err(WriteError::ReadOnly("Modem Status Register")),
+            7 => self.write_scr(allocator, value),
             _ => return Err(WriteError::AddressInvalid(address)),
         }
         Ok(())
@@ -407,11 +414,22 @@ impl<A: Allocator> Uart<A> {
         allocator.get_mut(self.state).unwrap().lcr = value;
     }
 
+    /// Reads the value of the Modem Control Register.
+    pub fn read_mcr(&self, allocator: &mut A) -> u8 {
+        self.read_mcr_pure(allocator)
+    }
+
+    /// Reads the value of the Modem Control Register without performing side effects.
+    pub fn read_mcr_pure(&self, allocator: &A) -> u8 {
+        allocator.get(self.state).unwrap().mcr
+    }
+
     /// Writes a value to the Modem Control Register.
+    ///
+    /// The value is only stored, as the scenario of an attached "modem" is not simulated. The
+    /// upper 3 bits are reserved and always read as zero.
     pub fn write_mcr(&self, allocator: &mut A, value: u8) {
-        // Nothing needs to be done, as the scenario of an attached "modem" is not simulated,
-        // and the MCR has only write-only fields.
-        let _ = (allocator, value);
+        allocator.get_mut(self.state).unwrap().mcr = value & 0x1F;
     }
 
     /// Reads the value of the Line Status Register.
@@ -441,6 +459,21 @@ impl<A: Allocator> Uart<A> {
         allocator.get(self.state).unwrap().msr
     }
 
+    /// Reads the value of the Scratch Register.
+    pub fn read_scr(&self, allocator: &mut A) -> u8 {
+        self.read_scr_pure(allocator)
+    }
+
+    /// Reads the value of the Scratch Register without performing side effects.
+    pub fn read_scr_pure(&self, allocator: &A) -> u8 {
+        allocator.get(self.state).unwrap().scr
+    }
+
+    /// Writes a value to the Scratch Register, which has no effect other than being read back.
+    pub fn write_scr(&self, allocator: &mut A, value: u8) {
+        allocator.get_mut(self.state).unwrap().scr = value;
+    }
+
     pub fn drop(self, allocator: &mut A) {
         allocator.remove(self.state).unwrap()
     }
@@ -507,36 +540,28 @@ impl<A: Allocator> Bus<A> for Uart<A> {
     ///
     /// The address space is circular 8-bit.
     ///
-    /// Only the first byte (if `buf.len() >= 1`) will be updated. Invalid reads will cause that
-    /// first byte to have an undefined value. The other bytes are always left untouched.
-    fn read(&self, buf: &mut [u8], allocator: &mut A, address: u32) {
-        match self.read(allocator, address as u8) {
-            Ok(value) => {
-                if let Some(out) = buf.get_mut(0) {
-                    *out = value
-                }
-            }
-            Err(ReadError::AddressInvalid(_)) => {}
-            Err(ReadError::WriteOnly(_)) => {}
+    /// Only the first byte (if `buf.len() >= 1`) will be updated. The other bytes are always left
+    /// untouched. Reads of addresses without a register are not supported.
+    fn read(&self, buf: &mut [u8], allocator: &mut A, address: u32) -> Result<(), BusError> {
+        let value = self.read(allocator, address as u8)?;
+        if let Some(out) = buf.get_mut(0) {
+            *out = value
         }
+        Ok(())
     }
 
     /// See [`Bus::read_debug`].
     ///
     /// The address space is circular 8-bit.
     ///
-    /// Only the first byte (if `buf.len() >= 1`) will be updated. Invalid reads will cause that
-    /// first byte to have an undefined value. The other bytes are always left untouched.
-    fn read_debug(&self, buf: &mut [u8], allocator: &A, address: u32) {
-        match self.read_pure(allocator, address as u8) {
-            Ok(value) => {
-                if let Some(out) = buf.get_mut(0) {
-                    *out = value
-                }
-            }
-            Err(ReadError::AddressInvalid(_)) => {}
-            Err(ReadError::WriteOnly(_)) => {}
+    /// Only the first byte (if `buf.len() >= 1`) will be updated. The other bytes are always left
+    /// untouched. Reads of addresses without a register are not supported.
+    fn read_debug(&self, buf: &mut [u8], allocator: &A, address: u32) -> Result<(), BusError> {
+        let value = self.read_pure(allocator, address as u8)?;
+        if let Some(out) = buf.get_mut(0) {
+            *out = value
         }
+        Ok(())
     }
 
     /// See [`Bus::write`].
@@ -544,8 +569,70 @@ impl<A: Allocator> Bus<A> for Uart<A> {
     /// The address space is circular 8-bit.
     ///
     /// Only the first byte (if `buf.len() >= 1`) is written.
-    /// In case `buf.len() == 0`, the value `0x00` is used.
-    fn write(&self, allocator: &mut A, address: u32, buf: &[u8]) {
-        let _ = self.write(allocator, address as u8, buf.first().copied().unwrap_or(0));
+    /// In case `buf.len() == 0`, the value `0x00` is used. Writes to read-only registers are
+    /// denied, and writes to addresses without a register are not supported.
+    fn write(&self, allocator: &mut A, address: u32, buf: &[u8]) -> Result<(), BusError> {
+        self.write(allocator, address as u8, buf.first().copied().unwrap_or(0))?;
+        Ok(())
+    }
+}
+
+impl From<ReadError> for BusError {
+    fn from(error: ReadError) -> Self {
+        match error {
+            ReadError::AddressInvalid(_) => Self::Unsupported,
+        }
+    }
+}
+
+impl From<WriteError> for BusError {
+    fn from(error: WriteError) -> Self {
+        match error {
+            WriteError::ReadOnly(_) => Self::Denied,
+            WriteError::AddressInvalid(_) => Self::Unsupported,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use space_time::SpaceTime;
+
+    use super::*;
+    use crate::interrupt::IrqCallback;
+
+    struct Line;
+
+    impl IrqCallback<SpaceTime> for Line {
+        fn raise(&self, _allocator: &mut SpaceTime) {}
+
+        fn lower(&self, _allocator: &mut SpaceTime) {}
+    }
+
+    fn read(uart: &Uart<SpaceTime>, allocator: &mut SpaceTime, address: u32) -> u8 {
+        let mut buf = [0];
+        Bus::read(uart, &mut buf, allocator, address).unwrap();
+        buf[0]
+    }
+
+    #[test]
+    fn test_modem_control_and_scratch() {
+        let mut allocator = SpaceTime::new();
+        let uart = Uart::new(&mut allocator, DynIrqCallback(Box::new(Line)));
+        assert_eq!(0, read(&uart, &mut allocator, 4));
+        assert_eq!(0, read(&uart, &mut allocator, 7));
+
+        // Like the probe of the Linux 8250 driver.
+        Bus::write(&uart, &mut allocator, 4, &[0xFF]).unwrap();
+        Bus::write(&uart, &mut allocator, 7, &[0xA5]).unwrap();
+        assert_eq!(0x1F, read(&uart, &mut allocator, 4));
+        assert_eq!(0xA5, read(&uart, &mut allocator, 7));
+        let mut buf = [0];
+        Bus::read_debug(&uart, &mut buf, &allocator, 7).unwrap();
+        assert_eq!(0xA5, buf[0]);
+
+        uart.reset(&mut allocator);
+        assert_eq!(0, read(&uart, &mut allocator, 4));
+        assert_eq!(0, read(&uart, &mut allocator, 7));
     }
 }
@@ -0,0 +1,1093 @@
+//! Implementation of a virtio block device with the MMIO transport (version 2), backed by a
+//! read-only disk image with a copy-on-write overlay.
+
+use std::cell::RefCell;
+use std::fmt::Debug;
+use std::io::{self, Read, Seek, SeekFrom};
+
+use log::{debug, trace};
+use space_time::allocator::{Allocator, ArrayAccessor, ArrayAccessorMut};
+
+use crate::bus::{Bus, BusError};
+use crate::interrupt::DynIrqCallback;
+
+/// Size of the address range of a virtio-mmio device.
+pub const MMIO_SIZE: u32 = 0x1000;
+/// Size of a sector, the unit in which the capacity of the device and offsets of requests are
+/// expressed.
+pub const SECTOR_SIZE: usize = 512;
+/// Maximum size of the request queue.
+pub const QUEUE_NUM_MAX: u16 = 128;
+
+const MAGIC_VALUE: u32 = 0x7472_6976; // "virt"
+const VERSION: u32 = 2;
+const DEVICE_ID_BLOCK: u32 = 2;
+const VENDOR_ID: u32 = 0x4E4C_5052; // "RPLN"
+
+const MAGIC_VALUE_ADDR: u32 = 0x000;
+const VERSION_ADDR: u32 = 0x004;
+const DEVICE_ID_ADDR: u32 = 0x008;
+const VENDOR_ID_ADDR: u32 = 0x00C;
+const DEVICE_FEATURES_ADDR: u32 = 0x010;
+const DEVICE_FEATURES_SEL_ADDR: u32 = 0x014;
+const DRIVER_FEATURES_ADDR: u32 = 0x020;
+const DRIVER_FEATURES_SEL_ADDR: u32 = 0x024;
+const QUEUE_SEL_ADDR: u32 = 0x030;
+const QUEUE_NUM_MAX_ADDR: u32 = 0x034;
+const QUEUE_NUM_ADDR: u32 = 0x038;
+const QUEUE_READY_ADDR: u32 = 0x044;
+const QUEUE_NOTIFY_ADDR: u32 = 0x050;
+const INTERRUPT_STATUS_ADDR: u32 = 0x060;
+const INTERRUPT_ACK_ADDR: u32 = 0x064;
+const STATUS_ADDR: u32 = 0x070;
+const QUEUE_DESC_LOW_ADDR: u32 = 0x080;
+const QUEUE_DESC_HIGH_ADDR: u32 = 0x084;
+const QUEUE_DRIVER_LOW_ADDR: u32 = 0x090;
+const QUEUE_DRIVER_HIGH_ADDR: u32 = 0x094;
+const QUEUE_DEVICE_LOW_ADDR: u32 = 0x0A0;
+const QUEUE_DEVICE_HIGH_ADDR: u32 = 0x0A4;
+const CONFIG_GENERATION_ADDR: u32 = 0x0FC;
+const CONFIG_ADDR: u32 = 0x100;
+
+/// Length of the device configuration space: capacity, size_max, seg_max, geometry and blk_size.
+const CONFIG_LEN: u32 = 24;
+
+const VIRTIO_F_VERSION_1: u64 = 1 << 32;
+const VIRTIO_BLK_F_FLUSH: u64 = 1 << 9;
+/// Features offered by the device.
+const DEVICE_FEATURES: u64 = VIRTIO_F_VERSION_1 | VIRTIO_BLK_F_FLUSH;
+
+const STATUS_FEATURES_OK: u32 = 8;
+const STATUS_DRIVER_OK: u32 = 4;
+const STATUS_DEVICE_NEEDS_RESET: u32 = 64;
+
+const INTERRUPT_USED_BUFFER: u32 = 1;
+const INTERRUPT_CONFIG_CHANGE: u32 = 2;
+
+const VIRTQ_DESC_F_NEXT: u16 = 1;
+const VIRTQ_DESC_F_WRITE: u16 = 2;
+const VIRTQ_AVAIL_F_NO_INTERRUPT: u16 = 1;
+
+const VIRTIO_BLK_T_IN: u32 = 0;
+const VIRTIO_BLK_T_OUT: u32 = 1;
+const VIRTIO_BLK_T_FLUSH: u32 = 4;
+const VIRTIO_BLK_T_GET_ID: u32 = 8;
+
+const VIRTIO_BLK_S_OK: u8 = 0;
+const VIRTIO_BLK_S_IOERR: u8 = 1;
+const VIRTIO_BLK_S_UNSUPP: u8 = 2;
+
+/// Size of the header of a block request: type, reserved and sector.
+const REQUEST_HEADER_LEN: usize = 16;
+/// Identifier returned for `VIRTIO_BLK_T_GET_ID` requests, at most 20 bytes.
+const DEVICE_ID: &[u8] = b"redplanet-virtio-blk";
+
+/// Contents of a disk image, such as a [`File`](std::fs::File).
+pub trait Image: Read + Seek + Debug {}
+
+impl<T: Read + Seek + Debug> Image for T {}
+
+/// Disk image backing a [`VirtioBlock`], which is never written to.
+#[derive(Debug)]
+pub struct Disk {
+    image: Box<dyn Image>,
+    /// Capacity in sectors.
+    capacity: u64,
+}
+
+impl Disk {
+    /// Creates a disk from `image`. The capacity is the size of `image` rounded down to a whole
+    /// number of sectors.
+    ///
+    /// Fails if the size cannot be determined, or the disk does not fit in the address space of
+    /// the host.
+    pub fn new(mut image: Box<dyn Image>) -> io::Result<Self> {
+        let capacity = image.seek(SeekFrom::End(0))? / SECTOR_SIZE as u64;
+        usize::try_from(capacity)
+            .ok()
+            .and_then(|sectors| sectors.checked_mul(SECTOR_SIZE))
+            .ok_or_else(|| io::Error::other("disk image is too large"))?;
+        Ok(Self { image, capacity })
+    }
+
+    /// Returns the capacity in sectors.
+    pub fn capacity(&self) -> u64 {
+        self.capacity
+    }
+}
+
+/// Virtio block device with a single request queue, attached through the MMIO transport.
+///
+/// The device accesses the queues and buffers in memory directly through `dma`, and signals
+/// used buffers through `interrupt_callback`. The disk image is only ever read: sectors written by
+/// the driver are kept in an overlay in the allocator, so they are part of the history and going
+/// back in time undoes them. The image must therefore not change while the device exists.
+///
+/// Resources:
+/// - <https://docs.oasis-open.org/virtio/virtio/v1.2/virtio-v1.2.html>
+#[derive(Debug)]
+pub struct VirtioBlock<A: Allocator> {
+    state: A::Id<State>,
+    /// Contents of the sectors that have been written.
+    overlay: A::ArrayId<u8>,
+    /// For every sector, whether its contents are in `overlay` rather than in `image`.
+    written: A::ArrayId<bool>,
+    image: RefCell<Box<dyn Image>>,
+    /// Capacity in sectors.
+    capacity: u64,
+    dma: Box<dyn Bus<A>>,
+    interrupt_callback: DynIrqCallback<A>,
+}
+
+/// State of a [`VirtioBlock`].
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+struct State {
+    status: u32,
+    device_features_sel: u32,
+    driver_features: u64,
+    driver_features_sel: u32,
+    queue_sel: u32,
+    queue: Queue,
+    interrupt_status: u32,
+}
+
+/// State of the request queue.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+struct Queue {
+    num: u16,
+    ready: bool,
+    /// Address of the descriptor table.
+    desc: u64,
+    /// Address of the available ring.
+    driver: u64,
+    /// Address of the used ring.
+    device: u64,
+    /// Index in the available ring of the next request to process.
+    last_avail_idx: u16,
+    /// Index in the used ring of the next request to complete.
+    used_idx: u16,
+}
+
+/// Buffer of a descriptor chain.
+#[derive(Debug, Clone, Copy)]
+struct Segment {
+    address: u32,
+    len: u32,
+    writable: bool,
+}
+
+/// The driver broke the protocol, after which the device needs to be reset.
+#[derive(Debug)]
+struct DeviceError;
+
+impl From<BusError> for DeviceError {
+    fn from(_: BusError) -> Self {
+        DeviceError
+    }
+}
+
+impl<A: Allocator> VirtioBlock<A> {
+    /// Create a new block device in reset state, backed by `disk`.
+    pub fn new(
+        allocator: &mut A,
+        disk: Disk,
+        dma: Box<dyn Bus<A>>,
+        interrupt_callback: DynIrqCallback<A>,
+    ) -> Self {
+        // The size in bytes fits in a usize, see `Disk::new`.
+        let sectors = disk.capacity as usize;
+        Self {
+            state: allocator.insert(State::default()),
+            overlay: allocator.insert_array(0, sectors * SECTOR_SIZE),
+            written: allocator.insert_array(false, sectors),
+            image: RefCell::new(disk.image),
+            capacity: disk.capacity,
+            dma,
+            interrupt_callback,
+        }
+    }
+
+    /// Returns the capacity in sectors.
+    pub fn capacity(&self) -> u64 {
+        self.capacity
+    }
+
+    /// Puts the device in its reset state. The written sectors are kept, just like the contents
+    /// of a real disk.
+    pub fn reset(&self, allocator: &mut A) {
+        self.update_state(allocator, |state| *state = State::default());
+    }
+
+    pub fn drop(self, allocator: &mut A) {
+        allocator.remove(self.state).unwrap();
+        allocator.remove_array(self.overlay).unwrap();
+        allocator.remove_array(self.written).unwrap();
+    }
+
+    /// Reads `buf.len()` bytes of the disk, starting at sector `sector`.
+    ///
+    /// Fails if the range is out of bounds, or reading the image fails.
+    pub fn read_sectors(&self, allocator: &A, sector: u64, buf: &mut [u8]) -> io::Result<()> {
+        let range = self.sector_range(sector, buf.len())?;
+        let written = allocator.get_array(self.written).unwrap();
+        let overlay = allocator.get_array(self.overlay).unwrap();
+        for (sector, chunk) in range.zip(buf.chunks_mut(SECTOR_SIZE)) {
+            if written.get(sector).unwrap() {
+                match overlay.read(chunk, sector * SECTOR_SIZE) {
+                    true => (),
+                    false => unreachable!(),
+                }
+            } else {
+                let mut image = self.image.borrow_mut();
+                image.seek(SeekFrom::Start((sector * SECTOR_SIZE) as u64))?;
+                image.read_exact(chunk)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes `buf` to the disk, starting at sector `sector`. Only the overlay is written.
+    ///
+    /// Fails if the range is out of bounds.
+    pub fn write_sectors(&self, allocator: &mut A, sector: u64, buf: &[u8]) -> io::Result<()> {
+        let range = self.sector_range(sector, buf.len())?;
+        let start = range.start;
+        let mut overlay = allocator.get_array_mut(self.overlay).unwrap();
+        match overlay.write(start * SECTOR_SIZE, buf) {
+            true => (),
+            false => unreachable!(),
+        }
+        drop(overlay);
+        let mut written = allocator.get_array_mut(self.written).unwrap();
+        for sector in range {
+            match written.set(sector, true) {
+                true => (),
+                false => unreachable!(),
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the sectors covered by `len` bytes starting at sector `sector`, where `len` must be
+    /// a multiple of [`SECTOR_SIZE`].
+    fn sector_range(&self, sector: u64, len: usize) -> io::Result<std::ops::Range<usize>> {
+        if !len.is_multiple_of(SECTOR_SIZE) {
+            return Err(io::Error::other(
+                "length is not a multiple of the sector size",
+            ));
+        }
+        let end = sector
+            .checked_add((len / SECTOR_SIZE) as u64)
+            .filter(|&end| end <= self.capacity)
+            .ok_or_else(|| io::Error::other("sectors out of bounds"))?;
+        // The capacity fits in a usize, see `Disk::new`.
+        Ok(sector as usize..end as usize)
+    }
+
+    fn update_state(&self, allocator: &mut A, op: impl FnOnce(&mut State)) {
+        let state = allocator.get_mut(self.state).unwrap();
+        let before = state.interrupt_status != 0;
+        op(state);
+        let after = state.interrupt_status != 0;
+        match (before, after) {
+            (true, false) => self.interrupt_callback.lower(allocator),
+            (false, true) => self.interrupt_callback.raise(allocator),
+            _ => {}
+        }
+    }
+
+    fn read_register(&self, allocator: &A, address: u32) -> Result<u32, BusError> {
+        let state = allocator.get(self.state).unwrap();
+        let queue = state.queue_sel == 0;
+        Ok(match address {
+            MAGIC_VALUE_ADDR => MAGIC_VALUE,
+            VERSION_ADDR => VERSION,
+            DEVICE_ID_ADDR => DEVICE_ID_BLOCK,
+            VENDOR_ID_ADDR => VENDOR_ID,
+            DEVICE_FEATURES_ADDR => match state.device_features_sel {
+                0 => DEVICE_FEATURES as u32,
+                1 => (DEVICE_FEATURES >> 32) as u32,
+                _ => 0,
+            },
+            QUEUE_NUM_MAX_ADDR if queue => QUEUE_NUM_MAX as u32,
+            QUEUE_NUM_MAX_ADDR => 0,
+            QUEUE_READY_ADDR => (queue && state.queue.ready) as u32,
+            INTERRUPT_STATUS_ADDR => state.interrupt_status,
+            STATUS_ADDR => state.status,
+            CONFIG_GENERATION_ADDR => 0,
+            DEVICE_FEATURES_SEL_ADDR
+            | DRIVER_FEATURES_ADDR
+            | DRIVER_FEATURES_SEL_ADDR
+            | QUEUE_SEL_ADDR
+            | QUEUE_NUM_ADDR
+            | QUEUE_NOTIFY_ADDR
+            | INTERRUPT_ACK_ADDR
+            | QUEUE_DESC_LOW_ADDR
+            | QUEUE_DESC_HIGH_ADDR
+            | QUEUE_DRIVER_LOW_ADDR
+            | QUEUE_DRIVER_HIGH_ADDR
+            | QUEUE_DEVICE_LOW_ADDR
+            | QUEUE_DEVICE_HIGH_ADDR => return Err(BusError::Denied),
+            _ => return Err(BusError::Unsupported),
+        })
+    }
+
+    fn write_register(&self, allocator: &mut A, address: u32, value: u32) -> Result<(), BusError> {
+        fn set_low(field: &mut u64, value: u32) {
+            *field = *field & !0xFFFF_FFFF | value as u64;
+        }
+        fn set_high(field: &mut u64, value: u32) {
+            *field = *field & 0xFFFF_FFFF | (value as u64) << 32;
+        }
+
+        let state = allocator.get_mut(self.state).unwrap();
+        // Queue registers of queues that do not exist are ignored.
+        let queue = (state.queue_sel == 0).then_some(&mut state.queue);
+        match (address, queue) {
+            (DEVICE_FEATURES_SEL_ADDR, _) => state.device_features_sel = value,
+            (DRIVER_FEATURES_ADDR, _) => match state.driver_features_sel {
+                0 => set_low(&mut state.driver_features, value),
+                1 => set_high(&mut state.driver_features, value),
+                _ => {}
+            },
+            (DRIVER_FEATURES_SEL_ADDR, _) => state.driver_features_sel = value,
+            (QUEUE_SEL_ADDR, _) => state.queue_sel = value,
+            (QUEUE_NUM_ADDR, Some(queue)) => {
+                if value <= QUEUE_NUM_MAX as u32 {
+                    queue.num = value as u16;
+                }
+            }
+            (QUEUE_READY_ADDR, Some(queue)) => queue.ready = value & 1 != 0,
+            (QUEUE_DESC_LOW_ADDR, Some(queue)) => set_low(&mut queue.desc, value),
+            (QUEUE_DESC_HIGH_ADDR, Some(queue)) => set_high(&mut queue.desc, value),
+            (QUEUE_DRIVER_LOW_ADDR, Some(queue)) => set_low(&mut queue.driver, value),
+            (QUEUE_DRIVER_HIGH_ADDR, Some(queue)) => set_high(&mut queue.driver, value),
+            (QUEUE_DEVICE_LOW_ADDR, Some(queue)) => set_low(&mut queue.device, value),
+            (QUEUE_DEVICE_HIGH_ADDR, Some(queue)) => set_high(&mut queue.device, value),
+            (
+                QUEUE_NUM_ADDR
+                | QUEUE_READY_ADDR
+                | QUEUE_DESC_LOW_ADDR
+                | QUEUE_DESC_HIGH_ADDR
+                | QUEUE_DRIVER_LOW_ADDR
+                | QUEUE_DRIVER_HIGH_ADDR
+                | QUEUE_DEVICE_LOW_ADDR
+                | QUEUE_DEVICE_HIGH_ADDR,
+                None,
+            ) => {}
+            (QUEUE_NOTIFY_ADDR, _) => {
+                if value == 0 {
+                    self.process_queue(allocator);
+                }
+            }
+            (INTERRUPT_ACK_ADDR, _) => {
+                self.update_state(allocator, |state| state.interrupt_status &= !value)
+            }
+            (STATUS_ADDR, _) => self.write_status(allocator, value),
+            (
+                MAGIC_VALUE_ADDR
+                | VERSION_ADDR
+                | DEVICE_ID_ADDR
+                | VENDOR_ID_ADDR
+                | DEVICE_FEATURES_ADDR
+                | QUEUE_NUM_MAX_ADDR
+                | INTERRUPT_STATUS_ADDR
+                | CONFIG_GENERATION_ADDR,
+                _,
+            ) => return Err(BusError::Denied),
+            _ => return Err(BusError::Unsupported),
+        }
+        Ok(())
+    }
+
+    fn write_status(&self, allocator: &mut A, value: u32) {
+        if value == 0 {
+            debug!("Resetting virtio block device");
+            self.reset(allocator);
+            return;
+        }
+        let state = allocator.get_mut(self.state).unwrap();
+        let mut value = value;
+        // Only accept features that were offered, which must include VERSION_1 for this
+        // (non-legacy) interface.
+        let features = state.driver_features;
+        if features & !DEVICE_FEATURES != 0 || features & VIRTIO_F_VERSION_1 == 0 {
+            value &= !STATUS_FEATURES_OK;
+        }
+        // The driver cannot clear bits other than by resetting the device.
+        state.status |= value;
+    }
+
+    /// Reads from the device configuration space.
+    fn read_config(&self, buf: &mut [u8], offset: u32) -> Result<(), BusError> {
+        let mut config = [0; CONFIG_LEN as usize];
+        config[0..8].copy_from_slice(&self.capacity.to_le_bytes());
+        config[20..24].copy_from_slice(&(SECTOR_SIZE as u32).to_le_bytes());
+        let bytes = (offset as usize)
+            .checked_add(buf.len())
+            .and_then(|end| config.get(offset as usize..end))
+            .ok_or(BusError::Unsupported)?;
+        buf.copy_from_slice(bytes);
+        Ok(())
+    }
+
+    /// Processes all available requests, if the driver is ready.
+    fn process_queue(&self, allocator: &mut A) {
+        let state = allocator.get(self.state).unwrap();
+        let queue = state.queue;
+        if state.status & STATUS_DRIVER_OK == 0
+            || state.status & STATUS_DEVICE_NEEDS_RESET != 0
+            || !queue.ready
+            || queue.num == 0
+        {
+            return;
+        }
+        match self.process_requests(allocator, queue) {
+            Ok((queue, processed)) => {
+                let interrupt = processed > 0
+                    && self
+                        .read_u16(allocator, queue.driver)
+                        .map_or(true, |flags| flags & VIRTQ_AVAIL_F_NO_INTERRUPT == 0);
+                self.update_state(allocator, |state| {
+                    state.queue = queue;
+                    if interrupt {
+                        state.interrupt_status |= INTERRUPT_USED_BUFFER;
+                    }
+                });
+            }
+            Err(DeviceError) => {
+                debug!("Virtio block device needs a reset after an invalid request");
+                self.update_state(allocator, |state| {
+                    state.status |= STATUS_DEVICE_NEEDS_RESET;
+                    state.interrupt_status |= INTERRUPT_CONFIG_CHANGE;
+                });
+            }
+        }
+    }
+
+    /// Processes all available requests, returning the updated queue and the number of requests
+    /// that were processed.
+    fn process_requests(
+        &self,
+        allocator: &mut A,
+        mut queue: Queue,
+    ) -> Result<(Queue, u16), DeviceError> {
+        let num = queue.num;
+        let avail_idx = self.read_u16(allocator, queue.driver + 2)?;
+        let mut processed = 0;
+        while queue.last_avail_idx != avail_idx {
+            if processed == num {
+                // The driver made more requests available than fit in the queue.
+                return Err(DeviceError);
+            }
+            let ring_address = queue.driver + 4 + 2 * (queue.last_avail_idx % num) as u64;
+            let head = self.read_u16(allocator, ring_address)?;
+            let chain = self.read_chain(allocator, &queue, head)?;
+            let len = self.handle_request(allocator, &chain)?;
+
+            let used_address = queue.device + 4 + 8 * (queue.used_idx % num) as u64;
+            self.write_bytes(allocator, used_address, &(head as u32).to_le_bytes())?;
+            self.write_bytes(allocator, used_address + 4, &len.to_le_bytes())?;
+            queue.used_idx = queue.used_idx.wrapping_add(1);
+            self.write_bytes(allocator, queue.device + 2, &queue.used_idx.to_le_bytes())?;
+
+            queue.last_avail_idx = queue.last_avail_idx.wrapping_add(1);
+            processed += 1;
+        }
+        Ok((queue, processed))
+    }
+
+    /// Reads the descriptor chain starting at descriptor `head`.
+    fn read_chain(
+        &self,
+        allocator: &mut A,
+        queue: &Queue,
+        head: u16,
+    ) -> Result<Vec<Segment>, DeviceError> {
+        let mut chain = Vec::new();
+        let mut index = head;
+        // A chain visiting more descriptors than there are contains a loop.
+        for _ in 0..queue.num {
+            if index >= queue.num {
+                return Err(DeviceError);
+            }
+            let mut descriptor = [0; 16];
+            self.read_bytes(allocator, queue.desc + 16 * index as u64, &mut descriptor)?;
+            let address = u64::from_le_bytes(descriptor[0..8].try_into().unwrap());
+            let len = u32::from_le_bytes(descriptor[8..12].try_into().unwrap());
+            let flags = u16::from_le_bytes(descriptor[12..14].try_into().unwrap());
+            let next = u16::from_le_bytes(descriptor[14..16].try_into().unwrap());
+
+            let writable = flags & VIRTQ_DESC_F_WRITE != 0;
+            // Device-readable buffers must come before device-writable ones.
+            if !writable
+                && chain
+                    .last()
+                    .is_some_and(|segment: &Segment| segment.writable)
+            {
+                return Err(DeviceError);
+            }
+            chain.push(Segment {
+                address: u32::try_from(address).map_err(|_| DeviceError)?,
+                len,
+                writable,
+            });
+            if flags & VIRTQ_DESC_F_NEXT == 0 {
+                return Ok(chain);
+            }
+            index = next;
+        }
+        Err(DeviceError)
+    }
+
+    /// Handles the block request in `chain`, returning the number of bytes written to it.
+    fn handle_request(&self, allocator: &mut A, chain: &[Segment]) -> Result<u32, DeviceError> {
+        let readable_len: u64 = chain
+            .iter()
+            .filter(|s| !s.writable)
+            .map(|s| s.len as u64)
+            .sum();
+        let writable_len: u64 = chain
+            .iter()
+            .filter(|s| s.writable)
+            .map(|s| s.len as u64)
+            .sum();
+        // The last writable byte is the status.
+        let Some(data_len) = writable_len.checked_sub(1) else {
+            return Err(DeviceError);
+        };
+        let mut header = [0; REQUEST_HEADER_LEN];
+        if readable_len < REQUEST_HEADER_LEN as u64 {
+            return Err(DeviceError);
+        }
+        self.gather(allocator, chain, &mut header)?;
+        let request_type = u32::from_le_bytes(header[0..4].try_into().unwrap());
+        let sector = u64::from_le_bytes(header[8..16].try_into().unwrap());
+        let disk_len = self.capacity * SECTOR_SIZE as u64;
+
+        let (status, data) = match request_type {
+            VIRTIO_BLK_T_IN => {
+                trace!("Virtio block read of {data_len} bytes at sector {sector}");
+                let mut data = vec![0; data_len.min(disk_len) as usize];
+                let result = match data_len <= disk_len {
+                    true => self.read_sectors(allocator, sector, &mut data),
+                    false => Err(io::Error::other("read larger than the disk")),
+                };
+                match result {
+                    Ok(()) => (VIRTIO_BLK_S_OK, data),
+                    Err(e) => {
+                        debug!("Virtio block read at sector {sector} failed: {e}");
+                        (VIRTIO_BLK_S_IOERR, Vec::new())
+                    }
+                }
+            }
+            VIRTIO_BLK_T_OUT => {
+                let len = readable_len - REQUEST_HEADER_LEN as u64;
+                trace!("Virtio block write of {len} bytes at sector {sector}");
+                let result = match len <= disk_len {
+                    true => {
+                        let mut buf = vec![0; REQUEST_HEADER_LEN + len as usize];
+                        self.gather(allocator, chain, &mut buf)?;
+                        self.write_sectors(allocator, sector, &buf[REQUEST_HEADER_LEN..])
+                    }
+                    false => Err(io::Error::other("write larger than the disk")),
+                };
+                match result {
+                    Ok(()) => (VIRTIO_BLK_S_OK, Vec::new()),
+                    Err(e) => {
+                        debug!("Virtio block write at sector {sector} failed: {e}");
+                        (VIRTIO_BLK_S_IOERR, Vec::new())
+                    }
+                }
+            }
+            // Writes are never cached, so there is nothing to flush.
+            VIRTIO_BLK_T_FLUSH => (VIRTIO_BLK_S_OK, Vec::new()),
+            VIRTIO_BLK_T_GET_ID => {
+                let len = DEVICE_ID.len().min(data_len as usize);
+                (VIRTIO_BLK_S_OK, DEVICE_ID[..len].to_vec())
+            }
+            _ => {
+                debug!("Unsupported virtio block request type {request_type}");
+                (VIRTIO_BLK_S_UNSUPP, Vec::new())
+            }
+        };
+
+        self.scatter(allocator, chain, &data)?;
+        self.write_bytes(allocator, last_writable_byte(chain)?, &[status])?;
+        Ok(data.len() as u32 + 1)
+    }
+
+    /// Fills `buf` with the first bytes of the device-readable buffers of `chain`.
+    fn gather(&self, allocator: &mut A, chain: &[Segment], buf: &mut [u8]) -> Result<(), BusError> {
+        let mut buf = buf;
+        for segment in chain.iter().filter(|segment| !segment.writable) {
+            if buf.is_empty() {
+                break;
+            }
+            let len = buf.len().min(segment.len as usize);
+            let (chunk, rest) = buf.split_at_mut(len);
+            self.read_bytes(allocator, segment.address as u64, chunk)?;
+            buf = rest;
+        }
+        Ok(())
+    }
+
+    /// Writes `data` to the first bytes of the device-writable buffers of `chain`.
+    fn scatter(&self, allocator: &mut A, chain: &[Segment], data: &[u8]) -> Result<(), BusError> {
+        let mut data = data;
+        for segment in chain.iter().filter(|segment| segment.writable) {
+            if data.is_empty() {
+                break;
+            }
+            let (chunk, rest) = data.split_at(data.len().min(segment.len as usize));
+            self.write_bytes(allocator, segment.address as u64, chunk)?;
+            data = rest;
+        }
+        Ok(())
+    }
+
+    fn read_u16(&self, allocator: &mut A, address: u64) -> Result<u16, BusError> {
+        let mut buf = [0; 2];
+        self.read_bytes(allocator, address, &mut buf)?;
+        Ok(u16::from_le_bytes(buf))
+    }
+
+    fn read_bytes(&self, allocator: &mut A, address: u64, buf: &mut [u8]) -> Result<(), BusError> {
+        let address = u32::try_from(address).map_err(|_| BusError::Denied)?;
+        self.dma.read(buf, allocator, address)
+    }
+
+    fn write_bytes(&self, allocator: &mut A, address: u64, buf: &[u8]) -> Result<(), BusError> {
+        let address = u32::try_from(address).map_err(|_| BusError::Denied)?;
+        self.dma.write(allocator, address, buf)
+    }
+}
+
+/// Returns the address of the last device-writable byte of `chain`.
+fn last_writable_byte(chain: &[Segment]) -> Result<u64, DeviceError> {
+    chain
+        .iter()
+        .rev()
+        .find(|segment| segment.writable && segment.len > 0)
+        .map(|segment| segment.address as u64 + segment.len as u64 - 1)
+        .ok_or(DeviceError)
+}
+
+impl<A: Allocator> Bus<A> for VirtioBlock<A> {
+    /// See [`Bus::read`].
+    ///
+    /// Registers only support aligned 4-byte accesses, while the configuration space can be
+    /// accessed with any size.
+    fn read(&self, buf: &mut [u8], allocator: &mut A, address: u32) -> Result<(), BusError> {
+        self.read_debug(buf, allocator, address)
+    }
+
+    fn read_debug(&self, buf: &mut [u8], allocator: &A, address: u32) -> Result<(), BusError> {
+        if address >= CONFIG_ADDR {
+            return self.read_config(buf, address - CONFIG_ADDR);
+        }
+        if buf.len() != 4 || !address.is_multiple_of(4) {
+            return Err(BusError::Unsupported);
+        }
+        let value = self.read_register(allocator, address)?;
+        buf.copy_from_slice(&value.to_le_bytes());
+        Ok(())
+    }
+
+    /// See [`Bus::write`].
+    ///
+    /// Registers only support aligned 4-byte accesses, and the configuration space is read-only.
+    fn write(&self, allocator: &mut A, address: u32, buf: &[u8]) -> Result<(), BusError> {
+        if address >= CONFIG_ADDR {
+            return Err(BusError::Denied);
+        }
+        let (Ok(buf), true) = (<[u8; 4]>::try_from(buf), address.is_multiple_of(4)) else {
+            return Err(BusError::Unsupported);
+        };
+        self.write_register(allocator, address, u32::from_le_bytes(buf))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::io::Cursor;
+    use std::rc::Rc;
+
+    use space_time::SpaceTime;
+
+    use super::*;
+    use crate::board::testing::boot_images;
+    use crate::board::{Config, VirtioBlockConfig};
+    use crate::interrupt::IrqCallback;
+    use crate::resources::ram::Ram;
+
+    const DESC: u32 = 0x1000;
+    const DRIVER: u32 = 0x2000;
+    const DEVICE: u32 = 0x3000;
+    const HEADER: u32 = 0x4000;
+    const DATA: u32 = 0x5000;
+    const STATUS: u32 = 0x6000;
+
+    struct Line(Rc<Cell<bool>>);
+
+    impl IrqCallback<SpaceTime> for Line {
+        fn raise(&self, _allocator: &mut SpaceTime) {
+            self.0.set(true);
+        }
+
+        fn lower(&self, _allocator: &mut SpaceTime) {
+            self.0.set(false);
+        }
+    }
+
+    /// Memory shared between a test and the device.
+    #[derive(Debug)]
+    struct Memory(Rc<Ram<SpaceTime>>);
+
+    impl Bus<SpaceTime> for Memory {
+        fn read(
+            &self,
+            buf: &mut [u8],
+            allocator: &mut SpaceTime,
+            address: u32,
+        ) -> Result<(), BusError> {
+            Bus::read(&*self.0, buf, allocator, address)
+        }
+
+        fn read_debug(
+            &self,
+            buf: &mut [u8],
+            allocator: &SpaceTime,
+            address: u32,
+        ) -> Result<(), BusError> {
+            Bus::read_debug(&*self.0, buf, allocator, address)
+        }
+
+        fn write(
+            &self,
+            allocator: &mut SpaceTime,
+            address: u32,
+            buf: &[u8],
+        ) -> Result<(), BusError> {
+            Bus::write(&*self.0, allocator, address, buf)
+        }
+    }
+
+    struct Setup {
+        allocator: SpaceTime,
+        device: VirtioBlock<SpaceTime>,
+        ram: Rc<Ram<SpaceTime>>,
+        line: Rc<Cell<bool>>,
+    }
+
+    /// Creates a device backed by a 4-sector image, in which every byte of sector `i` is `i`.
+    fn setup() -> Setup {
+        let mut allocator = SpaceTime::new();
+        let image: Vec<u8> = (0..4).flat_map(|i| [i; SECTOR_SIZE]).collect();
+        let disk = Disk::new(Box::new(Cursor::new(image))).unwrap();
+        let ram = Rc::new(Ram::new(&mut allocator, 0x10000).unwrap());
+        let line = Rc::new(Cell::new(false));
+        let device = VirtioBlock::new(
+            &mut allocator,
+            disk,
+            Box::new(Memory(ram.clone())),
+            DynIrqCallback(Box::new(Line(line.clone()))),
+        );
+        Setup {
+            allocator,
+            device,
+            ram,
+            line,
+        }
+    }
+
+    impl Setup {
+        fn read(&mut self, address: u32) -> u32 {
+            let mut buf = [0; 4];
+            Bus::read(&self.device, &mut buf, &mut self.allocator, address).unwrap();
+            u32::from_le_bytes(buf)
+        }
+
+        fn write(&mut self, address: u32, value: u32) {
+            Bus::write(
+                &self.device,
+                &mut self.allocator,
+                address,
+                &value.to_le_bytes(),
+            )
+            .unwrap();
+        }
+
+        fn write_memory(&mut self, address: u32, buf: &[u8]) {
+            self.ram.write(&mut self.allocator, address, buf);
+        }
+
+        fn read_memory(&self, address: u32, buf: &mut [u8]) {
+            self.ram.read(buf, &self.allocator, address);
+        }
+
+        /// Performs the driver initialization, with a queue of 4 entries.
+        fn initialize(&mut self) {
+            self.write(STATUS_ADDR, 1 | 2); // ACKNOWLEDGE | DRIVER
+            self.write(DEVICE_FEATURES_SEL_ADDR, 1);
+            assert_eq!(self.read(DEVICE_FEATURES_ADDR), 1);
+            self.write(DRIVER_FEATURES_SEL_ADDR, 1);
+            self.write(DRIVER_FEATURES_ADDR, 1);
+            self.write(STATUS_ADDR, 1 | 2 | STATUS_FEATURES_OK);
+            assert_eq!(self.read(STATUS_ADDR), 1 | 2 | STATUS_FEATURES_OK);
+            self.write(QUEUE_SEL_ADDR, 0);
+            assert_eq!(self.read(QUEUE_NUM_MAX_ADDR), QUEUE_NUM_MAX as u32);
+            self.write(QUEUE_NUM_ADDR, 4);
+            self.write(QUEUE_DESC_LOW_ADDR, DESC);
+            self.write(QUEUE_DRIVER_LOW_ADDR, DRIVER);
+            self.write(QUEUE_DEVICE_LOW_ADDR, DEVICE);
+            self.write(QUEUE_READY_ADDR, 1);
+            self.write(STATUS_ADDR, 1 | 2 | STATUS_FEATURES_OK | STATUS_DRIVER_OK);
+        }
+
+        /// Submits a request of `len` bytes through descriptors 0 to 2 and notifies the device,
+        /// returning the status and the length in the used ring.
+        fn request(&mut self, request_type: u32, sector: u64, len: u32) -> (u8, u32) {
+            let mut header = [0; REQUEST_HEADER_LEN];
+            header[0..4].copy_from_slice(&request_type.to_le_bytes());
+            header[8..16].copy_from_slice(&sector.to_le_bytes());
+            self.write_memory(HEADER, &header);
+
+            let data_flags = match request_type {
+                VIRTIO_BLK_T_OUT => VIRTQ_DESC_F_NEXT,
+                _ => VIRTQ_DESC_F_NEXT | VIRTQ_DESC_F_WRITE,
+            };
+            let descriptors = [
+                (HEADER, REQUEST_HEADER_LEN as u32, VIRTQ_DESC_F_NEXT, 1u16),
+                (DATA, len, data_flags, 2),
+                (STATUS, 1, VIRTQ_DESC_F_WRITE, 0),
+            ];
+            for (index, (address, len, flags, next)) in descriptors.into_iter().enumerate() {
+                let mut descriptor = [0; 16];
+                descriptor[0..8].copy_from_slice(&(address as u64).to_le_bytes());
+                descriptor[8..12].copy_from_slice(&len.to_le_bytes());
+                descriptor[12..14].copy_from_slice(&flags.to_le_bytes());
+                descriptor[14..16].copy_from_slice(&next.to_le_bytes());
+                self.write_memory(DESC + 16 * index as u32, &descriptor);
+            }
+
+            let mut idx = [0; 2];
+            self.read_memory(DRIVER + 2, &mut idx);
+            let idx = u16::from_le_bytes(idx);
+            self.write_memory(DRIVER + 4 + 2 * (idx % 4) as u32, &0u16.to_le_bytes());
+            self.write_memory(DRIVER + 2, &(idx + 1).to_le_bytes());
+            self.write(QUEUE_NOTIFY_ADDR, 0);
+
+            let mut used_idx = [0; 2];
+            self.read_memory(DEVICE + 2, &mut used_idx);
+            assert_eq!(u16::from_le_bytes(used_idx), idx + 1);
+            let mut used = [0; 8];
+            self.read_memory(DEVICE + 4 + 8 * (idx % 4) as u32, &mut used);
+            assert_eq!(u32::from_le_bytes(used[0..4].try_into().unwrap()), 0);
+            let mut status = [0xFF];
+            self.read_memory(STATUS, &mut status);
+            (
+                status[0],
+                u32::from_le_bytes(used[4..8].try_into().unwrap()),
+            )
+        }
+    }
+
+    #[test]
+    fn test_registers() {
+        let mut setup = setup();
+        assert_eq!(setup.read(MAGIC_VALUE_ADDR), MAGIC_VALUE);
+        assert_eq!(setup.read(VERSION_ADDR), 2);
+        assert_eq!(setup.read(DEVICE_ID_ADDR), 2);
+        assert_eq!(setup.read(CONFIG_ADDR), 4);
+        assert_eq!(setup.read(CONFIG_ADDR + 20), SECTOR_SIZE as u32);
+
+        let mut buf = [0; 4];
+        let allocator = &mut setup.allocator;
+        assert_eq!(
+            Bus::read(&setup.device, &mut buf, allocator, QUEUE_NOTIFY_ADDR),
+            Err(BusError::Denied)
+        );
+        assert_eq!(
+            Bus::read(&setup.device, &mut buf[..2], allocator, MAGIC_VALUE_ADDR),
+            Err(BusError::Unsupported)
+        );
+        assert_eq!(
+            Bus::write(&setup.device, allocator, MAGIC_VALUE_ADDR, &buf),
+            Err(BusError::Denied)
+        );
+        assert_eq!(
+            Bus::read(&setup.device, &mut buf, allocator, CONFIG_ADDR + CONFIG_LEN),
+            Err(BusError::Unsupported)
+        );
+
+        // Features without VERSION_1 are refused.
+        setup.write(STATUS_ADDR, 1 | 2);
+        setup.write(STATUS_ADDR, 1 | 2 | STATUS_FEATURES_OK);
+        assert_eq!(setup.read(STATUS_ADDR), 1 | 2);
+        setup.write(STATUS_ADDR, 0);
+        assert_eq!(setup.read(STATUS_ADDR), 0);
+    }
+
+    #[test]
+    fn test_requests() {
+        let mut setup = setup();
+        setup.initialize();
+
+        setup.write_memory(DATA, &[0xAB; SECTOR_SIZE]);
+        assert_eq!(
+            setup.request(VIRTIO_BLK_T_OUT, 1, 512),
+            (VIRTIO_BLK_S_OK, 1)
+        );
+        assert!(setup.line.get());
+        assert_eq!(setup.read(INTERRUPT_STATUS_ADDR), INTERRUPT_USED_BUFFER);
+        setup.write(INTERRUPT_ACK_ADDR, INTERRUPT_USED_BUFFER);
+        assert!(!setup.line.get());
+
+        let snapshot = setup.allocator.make_snapshot();
+        setup.write_memory(DATA, &[0xCD; SECTOR_SIZE]);
+        assert_eq!(
+            setup.request(VIRTIO_BLK_T_OUT, 2, 512),
+            (VIRTIO_BLK_S_OK, 1)
+        );
+
+        let mut data = [0; 4 * SECTOR_SIZE];
+        assert_eq!(
+            setup.request(VIRTIO_BLK_T_IN, 0, 2048),
+            (VIRTIO_BLK_S_OK, 2049)
+        );
+        setup.read_memory(DATA, &mut data);
+        assert!(data[..512].iter().all(|&byte| byte == 0));
+        assert!(data[512..1024].iter().all(|&byte| byte == 0xAB));
+        assert!(data[1024..1536].iter().all(|&byte| byte == 0xCD));
+        assert!(data[1536..].iter().all(|&byte| byte == 3));
+
+        // Going back in time undoes the second write.
+        setup.allocator.checkout(snapshot).unwrap();
+        let mut buf = [0; SECTOR_SIZE];
+        (setup.device)
+            .read_sectors(&setup.allocator, 2, &mut buf)
+            .unwrap();
+        assert!(buf.iter().all(|&byte| byte == 2));
+        (setup.device)
+            .read_sectors(&setup.allocator, 1, &mut buf)
+            .unwrap();
+        assert!(buf.iter().all(|&byte| byte == 0xAB));
+
+        assert_eq!(
+            setup.request(VIRTIO_BLK_T_IN, 3, 1024).0,
+            VIRTIO_BLK_S_IOERR
+        );
+        assert_eq!(setup.request(VIRTIO_BLK_T_IN, 0, 100).0, VIRTIO_BLK_S_IOERR);
+        assert_eq!(setup.request(VIRTIO_BLK_T_FLUSH, 0, 0).0, VIRTIO_BLK_S_OK);
+        assert_eq!(
+            setup.request(VIRTIO_BLK_T_GET_ID, 0, 20),
+            (VIRTIO_BLK_S_OK, 21)
+        );
+        assert_eq!(setup.request(0xFF, 0, 0).0, VIRTIO_BLK_S_UNSUPP);
+    }
+
+    #[test]
+    fn test_invalid_chain() {
+        let mut setup = setup();
+        setup.initialize();
+
+        // A descriptor that refers to itself loops forever.
+        let mut descriptor = [0; 16];
+        descriptor[0..8].copy_from_slice(&(HEADER as u64).to_le_bytes());
+        descriptor[8..12].copy_from_slice(&16u32.to_le_bytes());
+        descriptor[12..14].copy_from_slice(&VIRTQ_DESC_F_NEXT.to_le_bytes());
+        setup.write_memory(DESC, &descriptor);
+        setup.write_memory(DRIVER + 2, &1u16.to_le_bytes());
+        setup.write(QUEUE_NOTIFY_ADDR, 0);
+
+        assert_ne!(setup.read(STATUS_ADDR) & STATUS_DEVICE_NEEDS_RESET, 0);
+        assert_eq!(setup.read(INTERRUPT_STATUS_ADDR), INTERRUPT_CONFIG_CHANGE);
+        assert!(setup.line.get());
+        setup.write(STATUS_ADDR, 0);
+        assert!(!setup.line.get());
+    }
+
+    #[test]
+    fn test_board() {
+        let image: Vec<u8> = (0..2).flat_map(|i| [i; SECTOR_SIZE]).collect();
+        let path = std::env::temp_dir().join(format!("red-planet-{}.img", std::process::id()));
+        std::fs::write(&path, &image).unwrap();
+
+        let mut config = Config::default();
+        config.devices.virtio_blocks.push(VirtioBlockConfig {
+            base: 0x1000_1000,
+            irq: 8,
+            image: path.clone(),
+        });
+        let mut simulator = boot_images(config, Vec::new());
+        let (allocator, board) = simulator.inspect();
+        let mmu = board.core(0).mmu();
+        assert_eq!(Ok(MAGIC_VALUE), mmu.read_word_debug(allocator, 0x1000_1000));
+        assert_eq!(
+            Ok(2),
+            mmu.read_word_debug(allocator, 0x1000_1000 + CONFIG_ADDR)
+        );
+
+        // Write sector 1 with a single descriptor chain: the header at 0x8000_4000, data at
+        // 0x8000_5000 and status at 0x8000_6000. The queue is at 0x8000_1000.
+        let mut queue = Vec::new();
+        for (address, len, flags, next) in [
+            (0x8000_4000u64, 16u32, VIRTQ_DESC_F_NEXT, 1u16),
+            (0x8000_5000, 512, VIRTQ_DESC_F_NEXT, 2),
+            (0x8000_6000, 1, VIRTQ_DESC_F_WRITE, 0),
+        ] {
+            queue.extend(address.to_le_bytes());
+            queue.extend(len.to_le_bytes());
+            queue.extend(flags.to_le_bytes());
+            queue.extend(next.to_le_bytes());
+        }
+        simulator.step_with("submit", move |allocator, board| {
+            board.load_physical(allocator, 0x8000_1000, &queue);
+            // Available ring with idx 1 and descriptor 0.
+            board.load_physical(allocator, 0x8000_2000, &[0, 0, 1, 0, 0, 0]);
+            board.load_physical(allocator, 0x8000_4000, &[1, 0, 0, 0, 0, 0, 0, 0, 1]);
+            board.load_physical(allocator, 0x8000_5000, &[0xAB; SECTOR_SIZE]);
+            board.load_physical(allocator, 0x8000_6000, &[0xFF]);
+            for (offset, value) in [
+                (STATUS_ADDR, 1 | 2),
+                (DRIVER_FEATURES_SEL_ADDR, 1),
+                (DRIVER_FEATURES_ADDR, 1),
+                (STATUS_ADDR, 1 | 2 | STATUS_FEATURES_OK),
+                (QUEUE_NUM_ADDR, 4),
+                (QUEUE_DESC_LOW_ADDR, 0x8000_1000),
+                (QUEUE_DRIVER_LOW_ADDR, 0x8000_2000),
+                (QUEUE_DEVICE_LOW_ADDR, 0x8000_3000),
+                (QUEUE_READY_ADDR, 1),
+                (STATUS_ADDR, 1 | 2 | STATUS_FEATURES_OK | STATUS_DRIVER_OK),
+                (QUEUE_NOTIFY_ADDR, 0),
+            ] {
+                let mmu = board.core(0).mmu();
+                mmu.write_word(allocator, 0x1000_1000 + offset, value)
+                    .unwrap();
+            }
+        });
+
+        // The device reads and writes DRAM through the system bus, but never the image file.
+        let (allocator, board) = simulator.inspect();
+        let mmu = board.core(0).mmu();
+        assert_eq!(
+            Ok(VIRTIO_BLK_S_OK),
+            mmu.read_byte_debug(allocator, 0x8000_6000)
+        );
+        assert_eq!(Ok(1), mmu.read_byte_debug(allocator, 0x8000_3002));
+        assert_eq!(
+            Ok(INTERRUPT_USED_BUFFER),
+            mmu.read_word_debug(allocator, 0x1000_1000 + INTERRUPT_STATUS_ADDR)
+        );
+        let mut sector = [0; SECTOR_SIZE];
+        let virtio_block = board.virtio_block(0).unwrap();
+        virtio_block
+            .read_sectors(allocator, 1, &mut sector)
+            .unwrap();
+        assert_eq!([0xAB; SECTOR_SIZE], sector);
+        assert_eq!(image, std::fs::read(&path).unwrap());
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,378 @@
+//! Tree of timelines in a [`Simulator`].
+//!
+//! Stepping forward from a past state doesn't discard the forward history. Instead, the old
+//! timeline is kept as a separate branch, and a new branch is created for the diverging timeline.
+//! Every branch holds a full linear history, so branches share the snapshots (and thus the
+//! copy-on-write pages in [`space_time`]) of their common past.
+
+use super::{Head, Simulatable, SimulationAllocator, Simulator, StateIndex, StepIndex, Tick};
+use log::trace;
+use space_time::SnapshotId;
+use std::collections::HashSet;
+use std::fmt;
+use std::rc::Rc;
+use std::time::Duration;
+use thiserror::Error;
+
+/// Identifies a branch of a [`Simulator`]. Ids are never reused, even after a branch is deleted.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct BranchId(usize);
+
+impl BranchId {
+    /// Id of the branch every [`Simulator`] starts with.
+    pub const ROOT: Self = Self(0);
+}
+
+impl fmt::Display for BranchId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Description of a branch, as returned by [`Simulator::branches`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct BranchInfo {
+    pub id: BranchId,
+    pub name: Option<String>,
+    /// The branch this branch diverged from, and the number of steps from the start of history to
+    /// the state where it diverged. The history up to that state is shared with the parent.
+    ///
+    /// This is `None` for the root branch, or if the parent's shared history no longer exists.
+    pub fork: Option<(BranchId, usize)>,
+    /// Total number of steps in this branch's history.
+    pub steps: usize,
+    /// `true` if this is the branch HEAD is on.
+    pub active: bool,
+}
+
+#[derive(Error, Debug, Clone, Eq, PartialEq)]
+pub enum BranchError {
+    #[error("branch {0} does not exist")]
+    NotFound(BranchId),
+    #[error("cannot delete the active branch")]
+    DeleteActive,
+}
+
+#[derive(Debug)]
+pub(super) struct Branch<S: Simulatable<SimulationAllocator>> {
+    pub name: Option<String>,
+    pub fork: Option<(BranchId, usize)>,
+    /// The history of this branch while it's not active. The active branch's history is stored in
+    /// the [`Simulator`] itself.
+    pub timeline: Option<Timeline<S>>,
+}
+
+/// History of an inactive branch.
+#[derive(Debug)]
+pub(super) struct Timeline<S: Simulatable<SimulationAllocator>> {
+    /// See [`Simulator::snapshots`]. The last state of the timeline is always a snapshot.
+    pub snapshots: Vec<(Head, SnapshotId)>,
+    /// See [`Simulator::custom_ticks`].
+    pub custom_ticks: Vec<(StepIndex, Rc<Tick<S>>)>,
+    /// The state HEAD was at when the branch was left, which is restored when switching back.
+    pub state_index: StateIndex,
+}
+
+impl<S: Simulatable<SimulationAllocator>> Timeline<S> {
+    pub fn total_steps(&self) -> usize {
+        let (head, _) = self
+            .snapshots
+            .last()
+            .expect("the initial snapshot should always be present");
+        head.state_index.steps_since(StateIndex::new()).len()
+    }
+}
+
+impl<S: Simulatable<SimulationAllocator>> Simulator<S> {
+    /// Returns the id of the branch HEAD is on.
+    pub fn active_branch(&self) -> BranchId {
+        self.active_branch
+    }
+
+    /// Returns all branches, ordered by id.
+    pub fn branches(&self) -> impl Iterator<Item = BranchInfo> + '_ {
+        self.branches
+            .iter()
+            .enumerate()
+            .filter_map(|(index, branch)| Some((BranchId(index), branch.as_ref()?)))
+            .map(|(id, branch)| BranchInfo {
+                id,
+                name: branch.name.clone(),
+                fork: branch.fork,
+                steps: match &branch.timeline {
+                    Some(timeline) => timeline.total_steps(),
+                    None => self.available_steps(),
+                },
+                active: id == self.active_branch,
+            })
+    }
+
+    /// Start a new branch at the current state, and make it the active branch.
+    ///
+    /// The previously active branch keeps its full history, including any steps after HEAD.
+    /// Note that [`step`](Self::step) and [`step_with`](Self::step_with) automatically create a new
+    /// branch when stepping from a past state.
+    pub fn create_branch(&mut self) -> BranchId {
+        // The last state of the old timeline must be kept.
+        if self.is_head_dirty() {
+            self.make_snapshot();
+        }
+
+        let parent = self.active_branch;
+        trace!("Creating branch from branch {parent} at HEAD");
+
+        let timeline = Timeline {
+            snapshots: self.snapshots.clone(),
+            custom_ticks: self.custom_ticks.clone(),
+            state_index: self.head.state_index,
+        };
+        self.branch_mut(parent).timeline = Some(timeline);
+
+        let id = BranchId(self.branches.len());
+        self.branches.push(Some(Branch {
+            name: None,
+            fork: Some((parent, self.current_steps())),
+            timeline: None,
+        }));
+        self.active_branch = id;
+
+        // All future snapshots are still referenced by the parent, so they're not dropped.
+        self.clear_forward_history();
+        id
+    }
+
+    /// Make another branch the active branch, restoring HEAD to where it was on that branch.
+    pub fn switch_branch(&mut self, id: BranchId) -> Result<(), BranchError> {
+        if id == self.active_branch {
+            return Ok(());
+        }
+        let timeline = self
+            .branches
+            .get_mut(id.0)
+            .and_then(Option::as_mut)
+            .ok_or(BranchError::NotFound(id))?
+            .timeline
+            .take()
+            .expect("inactive branches store their timeline");
+
+        trace!("Switching to branch {id}");
+
+        if self.is_head_dirty() {
+            self.make_snapshot();
+        }
+        let old_timeline = Timeline {
+            snapshots: std::mem::replace(&mut self.snapshots, timeline.snapshots),
+            custom_ticks: std::mem::replace(&mut self.custom_ticks, timeline.custom_ticks),
+            state_index: self.head.state_index,
+        };
+        let active_branch = self.active_branch;
+        self.branch_mut(active_branch).timeline = Some(old_timeline);
+        self.active_branch = id;
+
+        // Check out the end of the new timeline, and move back to where HEAD was.
+        let (head, snapshot_id) = self.snapshots[self.last_snapshot_index()].clone();
+        self.allocator.0.checkout(snapshot_id).unwrap();
+        self.replay_time = Duration::ZERO;
+        self.base_allocated_bytes = self.allocator.0.allocated_bytes();
+        self.head = head;
+        self.go_to_state(timeline.state_index);
+        Ok(())
+    }
+
+    /// Give a branch a name, or remove its name if `name` is `None`.
+    pub fn rename_branch(&mut self, id: BranchId, name: Option<String>) -> Result<(), BranchError> {
+        self.branches
+            .get_mut(id.0)
+            .and_then(Option::as_mut)
+            .ok_or(BranchError::NotFound(id))?
+            .name = name;
+        Ok(())
+    }
+
+    /// Delete an inactive branch, freeing the snapshots no other branch uses.
+    ///
+    /// Branches that diverged from the deleted branch are kept, but no longer have a parent.
+    pub fn delete_branch(&mut self, id: BranchId) -> Result<(), BranchError> {
+        if id == self.active_branch {
+            return Err(BranchError::DeleteActive);
+        }
+        let branch = self
+            .branches
+            .get_mut(id.0)
+            .and_then(Option::take)
+            .ok_or(BranchError::NotFound(id))?;
+
+        trace!("Deleting branch {id}");
+
+        for child in self.branches.iter_mut().flatten() {
+            if matches!(child.fork, Some((parent, _)) if parent == id) {
+                child.fork = None;
+            }
+        }
+        let timeline = branch
+            .timeline
+            .expect("inactive branches store their timeline");
+        self.release_snapshots(timeline.snapshots.into_iter().map(|(_, id)| id));
+        Ok(())
+    }
+
+    /// Drop the given snapshots from [`space_time`], unless a branch still uses them.
+    ///
+    /// The snapshots must already have been removed from the active timeline, if they were in it.
+    pub(super) fn release_snapshots(&mut self, snapshot_ids: impl IntoIterator<Item = SnapshotId>) {
+        let used: HashSet<SnapshotId> = self
+            .branches
+            .iter()
+            .flatten()
+            .filter_map(|branch| branch.timeline.as_ref())
+            .flat_map(|timeline| &timeline.snapshots)
+            .chain(&self.snapshots)
+            .map(|(_, snapshot_id)| *snapshot_id)
+            .collect();
+
+        for snapshot_id in snapshot_ids {
+            if !used.contains(&snapshot_id) {
+                self.allocator.0.drop_snapshot(snapshot_id).unwrap();
+            }
+        }
+    }
+
+    /// Forget that branches diverged from the active branch after `steps`, since that part of the
+    /// active branch's history no longer exists.
+    pub(super) fn detach_branches_after(&mut self, steps: usize) {
+        let active_branch = self.active_branch;
+        for branch in self.branches.iter_mut().flatten() {
+            if matches!(branch.fork, Some((parent, fork)) if parent == active_branch && fork > steps)
+            {
+                branch.fork = None;
+            }
+        }
+    }
+
+    /// Returns the timeline of an inactive branch, or `None` for the active branch.
+    pub(super) fn stored_timeline(&self, id: BranchId) -> Option<&Timeline<S>> {
+        self.branches[id.0].as_ref()?.timeline.as_ref()
+    }
+
+    pub(super) fn branch_mut(&mut self, id: BranchId) -> &mut Branch<S> {
+        self.branches[id.0].as_mut().expect("branch should exist")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Allocator;
+
+    #[derive(Debug)]
+    struct Counter(<SimulationAllocator as Allocator>::Id<u64>);
+
+    impl Simulatable<SimulationAllocator> for Counter {
+        fn tick(&self, allocator: &mut SimulationAllocator) {
+            *allocator.get_mut(self.0).unwrap() += 1;
+        }
+
+        fn drop(self, allocator: &mut SimulationAllocator) {
+            allocator.remove(self.0).unwrap();
+        }
+    }
+
+    fn count(simulator: &Simulator<Counter>) -> u64 {
+        let (allocator, counter) = simulator.inspect();
+        *allocator.get(counter.0).unwrap()
+    }
+
+    fn set(simulator: &mut Simulator<Counter>, value: u64) {
+        simulator.step_with("set", move |allocator, counter| {
+            *allocator.get_mut(counter.0).unwrap() = value;
+        });
+    }
+
+    #[test]
+    fn diverging_creates_branch() {
+        let mut simulator = Simulator::new(|allocator| Counter(allocator.insert(0)));
+        for _ in 0..10 {
+            simulator.step();
+        }
+        simulator.go_to(4);
+        set(&mut simulator, 100);
+        simulator.step();
+        assert_eq!(count(&simulator), 101);
+
+        let branch = simulator.active_branch();
+        assert_ne!(branch, BranchId::ROOT);
+        assert_eq!(
+            simulator.branches().collect::<Vec<_>>(),
+            vec![
+                BranchInfo {
+                    id: BranchId::ROOT,
+                    name: None,
+                    fork: None,
+                    steps: 10,
+                    active: false,
+                },
+                BranchInfo {
+                    id: branch,
+                    name: None,
+                    fork: Some((BranchId::ROOT, 4)),
+                    steps: 6,
+                    active: true,
+                },
+            ]
+        );
+
+        // The old timeline is kept, with HEAD where it diverged.
+        simulator.switch_branch(BranchId::ROOT).unwrap();
+        assert_eq!(simulator.current_steps(), 4);
+        assert_eq!(count(&simulator), 4);
+        simulator.go_to(10);
+        assert_eq!(count(&simulator), 10);
+
+        // Switching back restores the end of the new branch, which was never snapshotted.
+        simulator.switch_branch(branch).unwrap();
+        assert_eq!(simulator.current_steps(), 6);
+        assert_eq!(count(&simulator), 101);
+        simulator.go_to(5);
+        assert_eq!(count(&simulator), 100);
+        simulator.go_to(2);
+        assert_eq!(count(&simulator), 2);
+    }
+
+    #[test]
+    fn rename_and_delete() {
+        let mut simulator = Simulator::new(|allocator| Counter(allocator.insert(0)));
+        for _ in 0..10 {
+            simulator.step();
+        }
+        let branch = simulator.create_branch();
+        simulator
+            .rename_branch(branch, Some("experiment".to_owned()))
+            .unwrap();
+        assert_eq!(
+            simulator.branches().last().unwrap().name.as_deref(),
+            Some("experiment")
+        );
+
+        assert_eq!(
+            simulator.delete_branch(branch),
+            Err(BranchError::DeleteActive)
+        );
+        simulator.go_to(3);
+        set(&mut simulator, 42);
+        let other = simulator.active_branch();
+        simulator.delete_branch(branch).unwrap();
+        assert_eq!(
+            simulator.switch_branch(branch),
+            Err(BranchError::NotFound(branch))
+        );
+
+        // The root branch still works, including the shared part of the deleted branch.
+        simulator.switch_branch(BranchId::ROOT).unwrap();
+        simulator.go_to(10);
+        assert_eq!(count(&simulator), 10);
+        simulator.switch_branch(other).unwrap();
+        assert_eq!(count(&simulator), 42);
+        simulator.go_to(2);
+        assert_eq!(count(&simulator), 2);
+    }
+}
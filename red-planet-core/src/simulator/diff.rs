@@ -0,0 +1,207 @@
+//! Comparing the state of a [`Simulator`] at two points in time.
+
+use super::{Simulatable, SimulationAllocator, Simulator};
+use crate::Allocator;
+use log::trace;
+use space_time::SnapshotId;
+use std::ops::Range;
+
+/// Trait for simulatables whose state can be compared between two points in time, see
+/// [`Simulator::diff`].
+pub trait Diffable: Simulatable<SimulationAllocator> {
+    /// Parts of the state captured at a single point in time, to be compared by value.
+    type Capture;
+    /// Differences between two states.
+    type Diff;
+
+    /// Capture the parts of the current state that are compared by value.
+    ///
+    /// Any changes made to `allocator` are discarded afterwards, so this can use methods that may
+    /// have side effects.
+    fn capture(&self, allocator: &mut SimulationAllocator) -> Self::Capture;
+
+    /// Compare two captured states. `context` can be used to cheaply compare arrays (such as
+    /// memory) without capturing them.
+    fn diff(&self, old: Self::Capture, new: Self::Capture, context: &DiffContext) -> Self::Diff;
+}
+
+/// Gives access to both states compared by [`Diffable::diff`].
+#[derive(Debug)]
+pub struct DiffContext<'a> {
+    allocator: &'a SimulationAllocator,
+    old: SnapshotId,
+    new: SnapshotId,
+}
+
+impl<'a> DiffContext<'a> {
+    pub(super) fn new(
+        allocator: &'a SimulationAllocator,
+        old: SnapshotId,
+        new: SnapshotId,
+    ) -> Self {
+        Self {
+            allocator,
+            old,
+            new,
+        }
+    }
+
+    /// Returns the ranges of indices at which the contents of the array `id` differ between both
+    /// states, in increasing order.
+    ///
+    /// Pages of the array that were not written in between are skipped without comparing them.
+    ///
+    /// Panics if the array does not exist in both states.
+    pub fn changed_ranges<T: Copy + PartialEq + 'static>(
+        &self,
+        id: <SimulationAllocator as Allocator>::ArrayId<T>,
+    ) -> Vec<Range<usize>> {
+        self.allocator
+            .0
+            .diff_array(id, self.old, self.new)
+            .expect("array should exist in both states")
+    }
+
+    /// Returns the ranges of indices of the array `id` that may have been written in between both
+    /// states, in increasing order.
+    ///
+    /// This is cheaper than [`changed_ranges`](Self::changed_ranges), but less precise: ranges are
+    /// rounded to whole pages, and include writes that did not change any value.
+    ///
+    /// Panics if the array does not exist in both states.
+    pub fn written_ranges<T: Copy + PartialEq + 'static>(
+        &self,
+        id: <SimulationAllocator as Allocator>::ArrayId<T>,
+    ) -> Vec<Range<usize>> {
+        self.allocator
+            .0
+            .written_array(id, self.old, self.new)
+            .expect("array should exist in both states")
+    }
+}
+
+impl<S: Diffable> Simulator<S> {
+    /// Returns the differences between the state after `from` steps and the state after `to` steps
+    /// from the start of history, or `None` if either is beyond [`available_steps`].
+    ///
+    /// HEAD is restored to its current state afterwards.
+    ///
+    /// [`available_steps`]: Self::available_steps
+    pub fn diff(&mut self, from: usize, to: usize) -> Option<S::Diff> {
+        if from.max(to) > self.available_steps() {
+            return None;
+        }
+        trace!("Computing diff between steps {from} and {to}");
+
+        let current_steps = self.current_steps();
+        // Snapshots made only for this diff, which are dropped again afterwards.
+        let mut temporary = Vec::new();
+        let (old, old_id) = self.capture_at(from, &mut temporary);
+        let (new, new_id) = self.capture_at(to, &mut temporary);
+
+        let context = DiffContext::new(&self.allocator, old_id, new_id);
+        let diff = self.simulatable.diff(old, new, &context);
+
+        for snapshot_id in temporary {
+            self.allocator.0.drop_snapshot(snapshot_id).unwrap();
+        }
+        self.go_to(current_steps);
+        Some(diff)
+    }
+
+    /// Go to the state after `steps` steps, and capture it. Returns the capture, together with the
+    /// id of a snapshot of that state in [`space_time`].
+    ///
+    /// If no snapshot of that state exists yet, a temporary one is made and added to `temporary`.
+    fn capture_at(
+        &mut self,
+        steps: usize,
+        temporary: &mut Vec<SnapshotId>,
+    ) -> (S::Capture, SnapshotId) {
+        self.go_to(steps);
+        let snapshot_id = match self.allocator.0.head() {
+            Some(snapshot_id) => snapshot_id,
+            None => {
+                let snapshot_id = self.allocator.0.make_snapshot();
+                temporary.push(snapshot_id);
+                snapshot_id
+            }
+        };
+
+        let capture = self.simulatable.capture(&mut self.allocator);
+        // Undo any side effects of capturing.
+        self.allocator.0.checkout(snapshot_id).unwrap();
+        (capture, snapshot_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use space_time::allocator::ArrayAccessorMut;
+
+    #[derive(Debug)]
+    struct Memory {
+        counter: <SimulationAllocator as Allocator>::Id<u64>,
+        data: <SimulationAllocator as Allocator>::ArrayId<u8>,
+    }
+
+    impl Simulatable<SimulationAllocator> for Memory {
+        fn tick(&self, allocator: &mut SimulationAllocator) {
+            let counter = allocator.get_mut(self.counter).unwrap();
+            *counter += 1;
+            let index = (*counter * 1000) as usize;
+            let mut data = allocator.get_array_mut(self.data).unwrap();
+            assert!(data.write(index, &[1, 1]));
+        }
+
+        fn drop(self, allocator: &mut SimulationAllocator) {
+            allocator.remove(self.counter).unwrap();
+            allocator.remove_array(self.data).unwrap();
+        }
+    }
+
+    impl Diffable for Memory {
+        type Capture = u64;
+        type Diff = (u64, u64, Vec<Range<usize>>);
+
+        fn capture(&self, allocator: &mut SimulationAllocator) -> u64 {
+            let counter = allocator.get_mut(self.counter).unwrap();
+            let value = *counter;
+            // Side effects of capturing must not leak into the history.
+            *counter = 0;
+            value
+        }
+
+        fn diff(&self, old: u64, new: u64, context: &DiffContext) -> Self::Diff {
+            (old, new, context.changed_ranges(self.data))
+        }
+    }
+
+    #[test]
+    #[allow(clippy::single_range_in_vec_init)]
+    fn diff_between_steps() {
+        let mut simulator = Simulator::new(|allocator| Memory {
+            counter: allocator.insert(0),
+            data: allocator.insert_array(0, 1 << 20),
+        });
+        for _ in 0..10 {
+            simulator.step();
+        }
+        simulator.go_to(7);
+
+        assert_eq!(
+            simulator.diff(2, 5),
+            Some((2, 5, vec![3000..3002, 4000..4002, 5000..5002]))
+        );
+        assert_eq!(simulator.diff(10, 9), Some((10, 9, vec![10_000..10_002])));
+        assert_eq!(simulator.diff(4, 4), Some((4, 4, vec![])));
+        assert_eq!(simulator.diff(0, 11), None);
+
+        assert_eq!(simulator.current_steps(), 7);
+        assert_eq!(simulator.available_steps(), 10);
+        simulator.step();
+        let (allocator, memory) = simulator.inspect();
+        assert_eq!(*allocator.get(memory.counter).unwrap(), 8);
+    }
+}
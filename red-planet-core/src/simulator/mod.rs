@@ -1,10 +1,20 @@
+pub mod branch;
+pub mod diff;
+pub mod session;
+pub mod snapshot_policy;
+
 use crate::Allocator;
+use branch::{Branch, BranchId};
+use diff::DiffContext;
 use log::trace;
+use snapshot_policy::{AdaptivePolicy, SnapshotPolicy, SnapshotStats};
 use space_time::allocator::{ArrayAccessor, ArrayAccessorMut};
 use space_time::errors::InvalidIdError;
 use space_time::{SnapshotId, SpaceTime};
 use std::cmp::Ordering;
 use std::fmt::{Debug, Formatter};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
 
 /// Trait for types that can be simulated by [`Simulator`].
 ///
@@ -92,7 +102,10 @@ impl Allocator for SimulationAllocator {
 }
 
 /// A simulator can simulate any `Simulatable`.
-/// It provides a full linear simulation history with undo and redo capabilities.
+/// It provides a full simulation history with undo and redo capabilities.
+///
+/// The history is a tree of linear timelines (see [`branch`]). HEAD is always on one of them, the
+/// active branch, and all navigation happens within that branch.
 #[derive(Debug)]
 pub struct Simulator<S: Simulatable<SimulationAllocator>> {
     allocator: SimulationAllocator,
@@ -113,8 +126,20 @@ pub struct Simulator<S: Simulatable<SimulationAllocator>> {
     /// Ordered timeline of `(step_index, custom_tick)` pairs, where `custom_tick` is the
     /// [`IntoTick`] that was passed to [`step_with`] to use as custom tick function at step
     /// `step_index`.
-    custom_ticks: Vec<(StepIndex, Tick<S>)>,
+    ///
+    /// Ticks are shared with the branches that have the same step in their history.
+    custom_ticks: Vec<(StepIndex, Rc<Tick<S>>)>,
     head: Head,
+    /// All branches ever created, indexed by [`BranchId`]. Deleted branches are `None`.
+    branches: Vec<Option<Branch<S>>>,
+    /// The branch whose timeline is stored in `snapshots` and `custom_ticks`.
+    active_branch: BranchId,
+    /// Decides when to make snapshots, and which ones to discard.
+    snapshot_policy: Box<dyn SnapshotPolicy>,
+    /// Time spent ticking the simulatable since HEAD's base snapshot.
+    replay_time: Duration,
+    /// [`SpaceTime::allocated_bytes`] right after HEAD's base snapshot was made or checked out.
+    base_allocated_bytes: usize,
 }
 
 #[derive(Debug, Clone)]
@@ -123,9 +148,21 @@ pub enum UndoStepStopReason<R> {
     Pred(R),
 }
 
+/// State to stop at when a step matches in [`Simulator::undo_steps_until_step`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepStop {
+    /// The state right before the step.
+    Before,
+    /// The state resulting from the step.
+    After,
+}
+
 impl<S: Simulatable<SimulationAllocator>> Simulator<S> {
     /// Create a new `Simulator` with a clear history and a [`Simulatable`] in reset state.
     ///
+    /// Snapshots are made according to the default [`AdaptivePolicy`]. Use
+    /// [`set_snapshot_policy`](Self::set_snapshot_policy) to change this.
+    ///
     /// `simulatable_constructor` must be a function that constructs the [`Simulatable`] that must
     /// be simulated based on the [`SimulationAllocator`] passed to it. Note that the constructed
     /// [`Simulatable`] must manage all its state through the provided [`SimulationAllocator`],
@@ -143,15 +180,37 @@ impl<S: Simulatable<SimulationAllocator>> Simulator<S> {
             base_snapshot_index: 0,
             next_custom_tick_index: 0,
         };
+        let base_allocated_bytes = allocator.0.allocated_bytes();
         Self {
             allocator,
             simulatable,
             snapshots: vec![(head.clone(), snapshot_id)],
             custom_ticks: Vec::new(),
             head,
+            branches: vec![Some(Branch {
+                name: None,
+                fork: None,
+                timeline: None,
+            })],
+            active_branch: BranchId::ROOT,
+            snapshot_policy: Box::new(AdaptivePolicy::default()),
+            replay_time: Duration::ZERO,
+            base_allocated_bytes,
         }
     }
 
+    /// Replace the policy that decides when snapshots of the history are made.
+    ///
+    /// Existing snapshots are kept.
+    pub fn set_snapshot_policy(&mut self, policy: impl SnapshotPolicy + 'static) {
+        self.snapshot_policy = Box::new(policy);
+    }
+
+    /// Returns the number of snapshots currently stored in the history.
+    pub fn snapshot_count(&self) -> usize {
+        self.snapshots.len()
+    }
+
     /// Provides immutable access to the simulatable.
     ///
     /// Prefer this over [`inspect`](Self::inspect) if all you need is access to the simulatable's
@@ -197,8 +256,8 @@ impl<S: Simulatable<SimulationAllocator>> Simulator<S> {
     /// Advance the simulation forward by one tick, but use a custom `tick` function instead of
     /// [`Simulatable::tick`].
     ///
-    /// Note that this will erase the forward history, i.e. all future undone steps can no longer be
-    /// redone hereafter.
+    /// If any steps were undone, a new branch is started from the current state (see
+    /// [`create_branch`](Self::create_branch)), so the undone steps remain on the previous branch.
     ///
     /// Note that every method on the board or any of its components may trigger side effects if it
     /// takes a `&mut impl Allocator` (if not documented otherwise).
@@ -229,6 +288,20 @@ impl<S: Simulatable<SimulationAllocator>> Simulator<S> {
     /// simulator.step();
     /// ```
     pub fn step_with<F, R>(&mut self, name: &'static str, custom_tick: F) -> R
+    where
+        F: 'static + Fn(&mut SimulationAllocator, &S) -> R,
+    {
+        self.step_with_record(name, None, custom_tick)
+    }
+
+    /// Implementation of [`step_with`](Self::step_with), storing `record` alongside the custom tick
+    /// so it can be saved in a [`session::Session`].
+    fn step_with_record<F, R>(
+        &mut self,
+        name: &'static str,
+        record: Option<Vec<u8>>,
+        custom_tick: F,
+    ) -> R
     where
         F: 'static + Fn(&mut SimulationAllocator, &S) -> R,
     {
@@ -236,17 +309,20 @@ impl<S: Simulatable<SimulationAllocator>> Simulator<S> {
 
         if self.is_head_detached() {
             trace!("Simulator HEAD is detached");
-            self.clear_forward_history();
+            self.create_branch();
         }
 
+        let start = Instant::now();
         let res = custom_tick(&mut self.allocator, &self.simulatable);
+        self.replay_time += start.elapsed();
 
-        let tick = Tick {
+        let tick = Rc::new(Tick {
             name,
+            record,
             tick: Box::new(move |allocator, simulatable| {
                 custom_tick(allocator, simulatable);
             }),
-        };
+        });
 
         self.head.next_custom_tick_index = self.custom_ticks.len();
         self.custom_ticks
@@ -256,6 +332,7 @@ impl<S: Simulatable<SimulationAllocator>> Simulator<S> {
 
         if self.should_create_snapshot() {
             self.make_snapshot();
+            self.thin_snapshots();
         }
 
         res
@@ -263,23 +340,26 @@ impl<S: Simulatable<SimulationAllocator>> Simulator<S> {
 
     /// Advance the simulation forward by one tick.
     ///
-    /// This will erase the forward history, i.e. all future undone steps can no longer be redone
-    /// hereafter.
+    /// If any steps were undone, a new branch is started from the current state (see
+    /// [`create_branch`](Self::create_branch)), so the undone steps remain on the previous branch.
     pub fn step(&mut self) {
         trace!("Stepping simulator once");
 
         if self.is_head_detached() {
             trace!("Simulator HEAD is detached");
-            self.clear_forward_history();
+            self.create_branch();
         }
 
         trace!("Ticking the simulatable");
+        let start = Instant::now();
         self.simulatable.tick(&mut self.allocator);
+        self.replay_time += start.elapsed();
 
         self.head.state_index = self.head.state_index.next();
 
         if self.should_create_snapshot() {
             self.make_snapshot();
+            self.thin_snapshots();
         }
     }
 
@@ -290,6 +370,7 @@ impl<S: Simulatable<SimulationAllocator>> Simulator<S> {
 
         let step_index = self.head.state_index.next_step();
 
+        let start = Instant::now();
         match self.custom_ticks.get(self.head.next_custom_tick_index) {
             Some((s, custom_tick)) if *s == step_index => {
                 trace!("Step to replay used custom tick \"{}\"", &custom_tick.name);
@@ -298,6 +379,7 @@ impl<S: Simulatable<SimulationAllocator>> Simulator<S> {
             }
             _ => self.simulatable.tick(&mut self.allocator),
         }
+        self.replay_time += start.elapsed();
 
         self.head.state_index = self.head.state_index.next();
     }
@@ -410,6 +492,94 @@ impl<S: Simulatable<SimulationAllocator>> Simulator<S> {
         UndoStepStopReason::ReachedStart
     }
 
+    /// Revert the simulation to the most recent past state right before or after a step for which
+    /// `pred` returns `Some`. The [`StepStop`] returned by `pred` selects which of both states.
+    /// Returns [`UndoStepStopReason::ReachedStart`] if `pred` returned `None` for all steps in the
+    /// past.
+    ///
+    /// Unlike [`undo_steps_until`](Self::undo_steps_until), `pred` inspects steps rather than
+    /// states. It is called right after every step is replayed, so it can also observe side effects
+    /// of executing the step that are not part of the simulated state. Steps are never restored
+    /// from a snapshot instead. The step that resulted in the current state is included, but the
+    /// current state itself is never stopped at.
+    ///
+    /// Before replaying the steps in between two consecutive snapshots, `skip` is called with a
+    /// [`DiffContext`] comparing these snapshots. If it returns `true`, `pred` is assumed to return
+    /// `None` for all of these steps, so they are not replayed. This makes searching long stretches
+    /// of history cheap if it can be decided from the differences.
+    ///
+    /// The guarantees on the calls to `pred` and `visit` are the same as for
+    /// [`undo_steps_until`](Self::undo_steps_until).
+    pub fn undo_steps_until_step<R>(
+        &mut self,
+        mut skip: impl FnMut(&S, &DiffContext) -> bool,
+        mut pred: impl FnMut(&SimulationAllocator, &S) -> Option<(StepStop, R)>,
+        mut visit: impl FnMut(&Self),
+    ) -> UndoStepStopReason<R> {
+        let Some(previous_state_index) = self.head.state_index.previous() else {
+            // Cannot undo when at the start of history
+            trace!("Undoing steps in simulator while at the start of history; doing nothing");
+            return UndoStepStopReason::ReachedStart;
+        };
+
+        // If the current state is the newest AND dirty, then we need to save it, so it can be
+        // restored later when redoing.
+        if self.is_head_dirty() {
+            self.make_snapshot();
+        }
+
+        let current_state_index = self.head.state_index;
+
+        for snapshot_index in (0..=self.find_base_snapshot(previous_state_index)).rev() {
+            // The current state is either a snapshot or in between two, so there always is a next
+            // snapshot.
+            let (old, new) = (
+                self.snapshots[snapshot_index].1,
+                self.snapshots[snapshot_index + 1].1,
+            );
+            if skip(
+                &self.simulatable,
+                &DiffContext::new(&self.allocator, old, new),
+            ) {
+                trace!("Skipping steps after snapshot with index {snapshot_index}");
+                continue;
+            }
+
+            self.go_to_snapshot(snapshot_index);
+            let end_state_index = self.snapshots[snapshot_index + 1]
+                .0
+                .state_index
+                .min(current_state_index);
+
+            // Later steps always match later (or equal) states, so the last match is kept.
+            let mut last_matched_state = None;
+            while self.head.state_index != end_state_index {
+                self.replay_step();
+                if let Some((stop, r)) = pred(&self.allocator, &self.simulatable) {
+                    let state_index = match stop {
+                        StepStop::Before => self.head.state_index.previous().unwrap(),
+                        StepStop::After => self.head.state_index,
+                    };
+                    if state_index < current_state_index {
+                        last_matched_state = Some((state_index, r));
+                    }
+                }
+            }
+
+            if let Some((state_index, r)) = last_matched_state {
+                self.go_to_state(state_index);
+                visit(self);
+                return UndoStepStopReason::Pred(r);
+            }
+
+            visit(self);
+        }
+
+        // Reached the start of history, while `pred` still hasn't returned `Some`.
+        self.go_to_snapshot(0);
+        UndoStepStopReason::ReachedStart
+    }
+
     /// Redo the last undone step. Returns `false` if there was nothing to redo.
     pub fn redo_step(&mut self) -> bool {
         if !self.is_head_detached() {
@@ -425,6 +595,27 @@ impl<S: Simulatable<SimulationAllocator>> Simulator<S> {
         true
     }
 
+    /// Same as [`redo_step`](Self::redo_step), but the step is always replayed, even if the
+    /// resulting state could be restored from a snapshot. This allows observing side effects of
+    /// executing the step that are not part of the simulated state.
+    pub fn redo_step_by_replay(&mut self) -> bool {
+        if !self.is_head_detached() {
+            trace!("Redoing step in simulator while at the end of history; doing nothing");
+            return false;
+        }
+
+        trace!("Redoing step in simulator by replaying it");
+
+        self.replay_step();
+        // Make sure HEAD is tracked as being at the snapshot of the resulting state, if any.
+        let snapshot_index = self.find_base_snapshot(self.head.state_index);
+        if snapshot_index != self.head.base_snapshot_index {
+            self.checkout_snapshot(snapshot_index);
+        }
+
+        true
+    }
+
     /// Jump to the state resulting from applying `steps` steps from the start of history.
     pub fn go_to(&mut self, steps: usize) {
         // If the current state is the newest AND dirty, then we need to save it, so it can be
@@ -436,11 +627,56 @@ impl<S: Simulatable<SimulationAllocator>> Simulator<S> {
         self.go_to_state(StateIndex::new().add_steps(steps));
     }
 
-    /// Heuristic to determine if we should take a snapshot already.
-    fn should_create_snapshot(&self) -> bool {
+    /// Ask the snapshot policy whether we should take a snapshot already.
+    fn should_create_snapshot(&mut self) -> bool {
         trace!("Checking whether to create snapshot");
-        // TODO: make a better heuristic
-        self.steps_since_last_snapshot() > 2048
+        let stats = SnapshotStats {
+            steps: self.steps_since_last_snapshot(),
+            replay_time: self.replay_time,
+            dirty_bytes: self
+                .allocator
+                .0
+                .allocated_bytes()
+                .saturating_sub(self.base_allocated_bytes),
+        };
+        self.snapshot_policy.should_snapshot(&stats)
+    }
+
+    /// Discard the snapshots the snapshot policy no longer wants to keep.
+    ///
+    /// Assumes HEAD is at the last snapshot.
+    fn thin_snapshots(&mut self) {
+        let steps: Vec<usize> = self
+            .snapshots
+            .iter()
+            .map(|(head, _)| head.state_index.steps_since(StateIndex::new()).len())
+            .collect();
+        let last_snapshot_index = self.last_snapshot_index();
+        let mut discard = vec![false; self.snapshots.len()];
+        for index in self.snapshot_policy.thin(&steps) {
+            if index != 0 && index < last_snapshot_index {
+                discard[index] = true;
+            }
+        }
+        if !discard.contains(&true) {
+            return;
+        }
+
+        trace!("Thinning simulator snapshots");
+        let mut discard = discard.into_iter();
+        let mut dropped = Vec::new();
+        self.snapshots.retain(|(_, snapshot_id)| {
+            let keep = !discard.next().unwrap();
+            if !keep {
+                dropped.push(*snapshot_id);
+            }
+            keep
+        });
+        self.release_snapshots(dropped);
+        for (index, (head, _)) in self.snapshots.iter_mut().enumerate() {
+            head.base_snapshot_index = index;
+        }
+        self.head.base_snapshot_index = self.last_snapshot_index();
     }
 
     /// Returns the number of completed steps since last snapshot.
@@ -459,6 +695,8 @@ impl<S: Simulatable<SimulationAllocator>> Simulator<S> {
         // It is important that `self.head.base_snapshot_index` has been updated before `self.head`
         // is added to `self.snapshots`
         self.snapshots.push((self.head.clone(), snapshot_id));
+        self.replay_time = Duration::ZERO;
+        self.base_allocated_bytes = self.allocator.0.allocated_bytes();
     }
 
     /// Returns the state HEAD had right after the snapshot with the highest `state_index` was made.
@@ -555,16 +793,24 @@ impl<S: Simulatable<SimulationAllocator>> Simulator<S> {
     fn go_to_snapshot(&mut self, snapshot_index: usize) {
         trace!("Reverting to snapshot with index {snapshot_index}");
 
-        let (Head { state_index, .. }, snapshot_id) = self.snapshots[snapshot_index];
-
-        if self.head.state_index == state_index {
+        if self.head.state_index == self.snapshots[snapshot_index].0.state_index {
             return;
         }
 
+        self.checkout_snapshot(snapshot_index);
+    }
+
+    /// Check out the snapshot at `self.snapshots[snapshot_index]`, even if HEAD is already at the
+    /// same state.
+    fn checkout_snapshot(&mut self, snapshot_index: usize) {
+        let (Head { state_index, .. }, snapshot_id) = self.snapshots[snapshot_index];
+
         // Compute new indices in `custom_ticks`
         let next_custom_tick_index = self.custom_ticks.partition_point(|(s, _)| *s < state_index);
 
         self.allocator.0.checkout(snapshot_id).unwrap();
+        self.replay_time = Duration::ZERO;
+        self.base_allocated_bytes = self.allocator.0.allocated_bytes();
 
         self.head = Head {
             state_index,
@@ -573,18 +819,31 @@ impl<S: Simulatable<SimulationAllocator>> Simulator<S> {
         };
     }
 
+    /// Discard all states after HEAD on the active branch.
+    ///
+    /// Other branches are unaffected, but branches that diverged from a discarded state are no
+    /// longer considered to be forked from the active branch.
     pub fn clear_forward_history(&mut self) {
         trace!("Clearing forward history of simulator");
-        for (_, snapshot_id) in self.snapshots.drain((self.head.base_snapshot_index + 1)..) {
-            self.allocator.0.drop_snapshot(snapshot_id).unwrap();
-        }
+        let allocated_bytes = self.allocator.0.allocated_bytes();
+        let dropped: Vec<_> = self
+            .snapshots
+            .drain((self.head.base_snapshot_index + 1)..)
+            .map(|(_, snapshot_id)| snapshot_id)
+            .collect();
+        self.release_snapshots(dropped);
+        self.detach_branches_after(self.current_steps());
+        // Memory freed by the dropped snapshots doesn't count against the current state.
+        let freed_bytes = allocated_bytes - self.allocator.0.allocated_bytes();
+        self.base_allocated_bytes = self.base_allocated_bytes.saturating_sub(freed_bytes);
         self.custom_ticks.truncate(self.head.next_custom_tick_index);
     }
 }
 
 struct Tick<S: Simulatable<SimulationAllocator>> {
-    #[allow(dead_code)]
     name: &'static str,
+    /// Encoded [`session::RecordedTick`] this tick was created from, if any.
+    record: Option<Vec<u8>>,
     #[allow(clippy::type_complexity)]
     tick: Box<dyn Fn(&mut SimulationAllocator, &S) + 'static>,
 }
@@ -690,7 +949,6 @@ impl StepIndex {
         )
     }
 
-    #[allow(unused)]
     pub fn state_before(self) -> StateIndex {
         StateIndex(self.0)
     }
@@ -717,7 +975,29 @@ impl PartialOrd<StateIndex> for StepIndex {
 
 #[cfg(test)]
 mod tests {
-    use super::StateIndex;
+    use super::snapshot_policy::{FixedIntervalPolicy, LongHistoryPolicy};
+    use super::{
+        Simulatable, SimulationAllocator, Simulator, StateIndex, StepStop, UndoStepStopReason,
+    };
+    use crate::Allocator;
+
+    #[derive(Debug)]
+    struct Counter(<SimulationAllocator as Allocator>::Id<u64>);
+
+    impl Simulatable<SimulationAllocator> for Counter {
+        fn tick(&self, allocator: &mut SimulationAllocator) {
+            *allocator.get_mut(self.0).unwrap() += 1;
+        }
+
+        fn drop(self, allocator: &mut SimulationAllocator) {
+            allocator.remove(self.0).unwrap();
+        }
+    }
+
+    fn count(simulator: &Simulator<Counter>) -> u64 {
+        let (allocator, counter) = simulator.inspect();
+        *allocator.get(counter.0).unwrap()
+    }
 
     #[test]
     fn compare_state_and_step_index() {
@@ -748,4 +1028,112 @@ mod tests {
         assert_ne!(state1, step0);
         assert_ne!(state1, step1);
     }
+
+    #[test]
+    fn fixed_interval_snapshots() {
+        let mut simulator = Simulator::new(|allocator| Counter(allocator.insert(0)));
+        simulator.set_snapshot_policy(FixedIntervalPolicy::new(10));
+
+        for _ in 0..105 {
+            simulator.step();
+        }
+        assert_eq!(simulator.snapshot_count(), 11);
+
+        simulator.go_to(42);
+        assert_eq!(count(&simulator), 42);
+    }
+
+    #[test]
+    fn long_history_keeps_states_reachable() {
+        let mut simulator = Simulator::new(|allocator| Counter(allocator.insert(0)));
+        simulator.set_snapshot_policy(LongHistoryPolicy::new(FixedIntervalPolicy::new(1), 2));
+
+        for _ in 0..10_000 {
+            simulator.step();
+        }
+        assert!(simulator.snapshot_count() < 40);
+
+        for steps in [0, 1, 1234, 5000, 9999, 10_000] {
+            simulator.go_to(steps);
+            assert_eq!(count(&simulator), steps as u64);
+        }
+
+        simulator.go_to(7000);
+        assert!(simulator.undo_step());
+        assert_eq!(count(&simulator), 6999);
+        simulator.step();
+        simulator.step();
+        assert_eq!(count(&simulator), 7001);
+        assert_eq!(simulator.available_steps(), 7001);
+        assert!(simulator.undo_step());
+        assert_eq!(count(&simulator), 7000);
+    }
+
+    #[test]
+    fn undo_until_step() {
+        let mut simulator = Simulator::new(|allocator| Counter(allocator.insert(0)));
+        simulator.set_snapshot_policy(FixedIntervalPolicy::new(10));
+        for _ in 0..105 {
+            simulator.step();
+        }
+        simulator.go_to(57);
+
+        let undo = |simulator: &mut Simulator<Counter>, stop| {
+            let result = simulator.undo_steps_until_step(
+                |_, _| false,
+                |allocator, counter| {
+                    let count = *allocator.get(counter.0).unwrap();
+                    (count % 20 == 13 || count == 57).then_some((stop, count))
+                },
+                |_| {},
+            );
+            match result {
+                UndoStepStopReason::Pred(count) => Some(count),
+                UndoStepStopReason::ReachedStart => None,
+            }
+        };
+
+        // The step to the current state is included, but the current state itself is not.
+        assert_eq!(undo(&mut simulator, StepStop::Before), Some(57));
+        assert_eq!(count(&simulator), 56);
+        assert_eq!(undo(&mut simulator, StepStop::Before), Some(53));
+        assert_eq!(count(&simulator), 52);
+        assert_eq!(undo(&mut simulator, StepStop::After), Some(33));
+        assert_eq!(count(&simulator), 33);
+        assert_eq!(undo(&mut simulator, StepStop::After), Some(13));
+        assert_eq!(undo(&mut simulator, StepStop::After), None);
+        assert_eq!(count(&simulator), 0);
+
+        // Skipped steps are never passed to `pred`.
+        simulator.go_to(100);
+        let result = simulator.undo_steps_until_step(
+            |_, _| true,
+            |_, _| -> Option<(StepStop, ())> { panic!("step should have been skipped") },
+            |_| {},
+        );
+        assert!(matches!(result, UndoStepStopReason::ReachedStart));
+        assert_eq!(count(&simulator), 0);
+        assert_eq!(simulator.available_steps(), 105);
+    }
+
+    #[test]
+    fn redo_by_replay() {
+        let mut simulator = Simulator::new(|allocator| Counter(allocator.insert(0)));
+        simulator.set_snapshot_policy(FixedIntervalPolicy::new(10));
+        for _ in 0..20 {
+            simulator.step();
+        }
+
+        simulator.go_to(8);
+        for steps in 9..=20 {
+            assert!(simulator.redo_step_by_replay());
+            assert_eq!(count(&simulator), steps);
+        }
+        assert!(!simulator.redo_step_by_replay());
+
+        // HEAD is back at the end of history, so stepping does not create a branch.
+        simulator.step();
+        assert_eq!(simulator.available_steps(), 21);
+        assert_eq!(simulator.branches().count(), 1);
+    }
 }
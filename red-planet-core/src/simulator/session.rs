@@ -0,0 +1,678 @@
+//! Saving and restoring the history of a [`Simulator`] to and from disk.
+//!
+//! Since simulation is deterministic, a [`Session`] doesn't store the simulated state itself.
+//! Instead, it stores everything needed to reconstruct the exact same timeline:
+//!
+//! - a description of how to construct the simulatable (provided by the user of [`Simulator`]),
+//! - for every branch, the length of its history, the position of HEAD within it, where it
+//!   diverged from its parent branch, and a log of all custom ticks, each recorded by a
+//!   [`RecordedTick`],
+//! - which branch is active.
+//!
+//! [`Simulator::from_session`] replays these logs, recreating the branches and snapshots along the
+//! way, so the restored simulator can navigate the history exactly like the original one.
+//!
+//! Because replaying depends on the exact behavior of the simulator, sessions are tagged with a
+//! format version and the version of this crate, and sessions from incompatible builds are
+//! rejected.
+
+use super::branch::BranchId;
+use super::{Simulatable, SimulationAllocator, Simulator, StateIndex, StepIndex, Tick};
+use std::io::{self, Read, Write};
+use std::rc::Rc;
+use thiserror::Error;
+
+/// Magic bytes every session file starts with.
+pub const MAGIC: [u8; 8] = *b"RPSESSN\0";
+/// Version of the session file format, incremented on every incompatible change.
+pub const FORMAT_VERSION: u32 = 2;
+/// Version of the simulator that recorded a session. Replaying a session on a different version
+/// may not reproduce the same timeline.
+const BUILD_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[derive(Error, Debug)]
+pub enum SessionError {
+    #[error("i/o error: {0}")]
+    Io(#[from] io::Error),
+    #[error("not a session file")]
+    InvalidMagic,
+    #[error("unsupported session format version {0}, expected version {FORMAT_VERSION}")]
+    UnsupportedFormat(u32),
+    #[error("session was recorded by version {0}, which is incompatible with {BUILD_VERSION}")]
+    IncompatibleBuild(String),
+    #[error("custom tick \"{0}\" is not recorded, so it cannot be saved")]
+    UnrecordedTick(&'static str),
+    #[error("unknown custom tick \"{0}\"")]
+    UnknownTick(String),
+    #[error("{0} changed since the session was recorded")]
+    HostFileChanged(String),
+    #[error("malformed session")]
+    Malformed,
+}
+
+/// A custom tick (see [`Simulator::step_with`]) that can be saved in a [`Session`] and replayed.
+pub trait RecordedTick<S>: 'static {
+    /// Value returned by [`Self::tick`].
+    type Output;
+
+    /// Name identifying this kind of tick in a session. Must be unique per simulatable.
+    const NAME: &'static str;
+
+    /// Encode all parameters of this tick, such that it can be reconstructed when replaying the
+    /// session (see [`Simulator::from_session`]).
+    fn encode(&self, encoder: &mut Encoder);
+
+    /// Perform the custom tick. This must be deterministic, see [`Simulatable::tick`].
+    fn tick(&self, allocator: &mut SimulationAllocator, simulatable: &S) -> Self::Output;
+}
+
+/// Custom tick stored in a [`Session`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct SessionTick {
+    /// Number of steps from the start of history to the step of this tick.
+    step: usize,
+    name: String,
+    record: Vec<u8>,
+}
+
+/// Branch stored in a [`Session`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct SessionBranch {
+    name: Option<String>,
+    /// Index in [`Session::branches`] of the parent branch, and the number of steps shared with it.
+    fork: Option<(usize, usize)>,
+    total_steps: usize,
+    current_steps: usize,
+    /// Custom ticks of this branch, including the ones shared with the parent branch.
+    ticks: Vec<SessionTick>,
+}
+
+/// Everything needed to reconstruct the history of a [`Simulator`].
+///
+/// Created by [`Simulator::session`], and turned into a [`Simulator`] again by
+/// [`Simulator::from_session`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Session {
+    /// Description of how to construct the simulatable, in a format chosen by the user.
+    pub description: Vec<u8>,
+    /// All branches, ordered by [`BranchId`]. A branch's parent always comes before it.
+    branches: Vec<SessionBranch>,
+    /// Index in `branches` of the active branch.
+    active_branch: usize,
+}
+
+impl Session {
+    /// Write this session to `writer`, including a versioned header.
+    pub fn write(&self, writer: &mut impl Write) -> io::Result<()> {
+        let mut encoder = Encoder::default();
+        encoder.put_u32(FORMAT_VERSION);
+        encoder.put_str(BUILD_VERSION);
+        encoder.put_bytes(&self.description);
+        encoder.put_usize(self.branches.len());
+        for branch in &self.branches {
+            encoder.put_bool(branch.name.is_some());
+            if let Some(name) = &branch.name {
+                encoder.put_str(name);
+            }
+            encoder.put_bool(branch.fork.is_some());
+            if let Some((parent, steps)) = branch.fork {
+                encoder.put_usize(parent);
+                encoder.put_usize(steps);
+            }
+            encoder.put_usize(branch.total_steps);
+            encoder.put_usize(branch.current_steps);
+            encoder.put_usize(branch.ticks.len());
+            for tick in &branch.ticks {
+                encoder.put_usize(tick.step);
+                encoder.put_str(&tick.name);
+                encoder.put_bytes(&tick.record);
+            }
+        }
+        encoder.put_usize(self.active_branch);
+
+        writer.write_all(&MAGIC)?;
+        writer.write_all(&encoder.into_bytes())
+    }
+
+    /// Read a session written by [`Self::write`].
+    ///
+    /// Sessions written by an incompatible build are rejected.
+    pub fn read(reader: &mut impl Read) -> Result<Self, SessionError> {
+        let mut magic = [0; MAGIC.len()];
+        reader.read_exact(&mut magic)?;
+        if magic != MAGIC {
+            return Err(SessionError::InvalidMagic);
+        }
+
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf)?;
+        let mut decoder = Decoder::new(&buf);
+
+        let format_version = decoder.get_u32()?;
+        if format_version != FORMAT_VERSION {
+            return Err(SessionError::UnsupportedFormat(format_version));
+        }
+        let build_version = decoder.get_str()?;
+        if build_version != BUILD_VERSION {
+            return Err(SessionError::IncompatibleBuild(build_version.to_owned()));
+        }
+
+        let description = decoder.get_bytes()?.to_vec();
+        let branch_count = decoder.get_usize()?;
+        let mut branches: Vec<SessionBranch> = Vec::new();
+        for index in 0..branch_count {
+            let branch = Self::read_branch(&mut decoder)?;
+            // A branch can only fork from an earlier branch, within both histories.
+            if let Some((parent, steps)) = branch.fork {
+                if parent >= index
+                    || steps > branches[parent].total_steps
+                    || steps > branch.total_steps
+                {
+                    return Err(SessionError::Malformed);
+                }
+            }
+            branches.push(branch);
+        }
+        let active_branch = decoder.get_usize()?;
+        if branches.first().is_none_or(|root| root.fork.is_some())
+            || active_branch >= branches.len()
+            || !decoder.is_empty()
+        {
+            return Err(SessionError::Malformed);
+        }
+
+        Ok(Self {
+            description,
+            branches,
+            active_branch,
+        })
+    }
+
+    fn read_branch(decoder: &mut Decoder) -> Result<SessionBranch, SessionError> {
+        let name = match decoder.get_bool()? {
+            true => Some(decoder.get_str()?.to_owned()),
+            false => None,
+        };
+        let fork = match decoder.get_bool()? {
+            true => Some((decoder.get_usize()?, decoder.get_usize()?)),
+            false => None,
+        };
+        let total_steps = decoder.get_usize()?;
+        let current_steps = decoder.get_usize()?;
+        let tick_count = decoder.get_usize()?;
+        let mut ticks: Vec<SessionTick> = Vec::new();
+        for _ in 0..tick_count {
+            let tick = SessionTick {
+                step: decoder.get_usize()?,
+                name: decoder.get_str()?.to_owned(),
+                record: decoder.get_bytes()?.to_vec(),
+            };
+            // Ticks must be ordered, at most one per step, and within the history.
+            if ticks.last().is_some_and(|last| last.step >= tick.step) || tick.step >= total_steps {
+                return Err(SessionError::Malformed);
+            }
+            ticks.push(tick);
+        }
+        if current_steps > total_steps {
+            return Err(SessionError::Malformed);
+        }
+
+        Ok(SessionBranch {
+            name,
+            fork,
+            total_steps,
+            current_steps,
+            ticks,
+        })
+    }
+}
+
+impl<S: Simulatable<SimulationAllocator>> Simulator<S> {
+    /// Advance the simulation forward by one tick, using a custom tick that can be saved in a
+    /// [`Session`].
+    ///
+    /// Behaves like [`step_with`](Self::step_with) otherwise.
+    pub fn step_recorded<T: RecordedTick<S>>(&mut self, tick: T) -> T::Output {
+        let mut encoder = Encoder::default();
+        tick.encode(&mut encoder);
+        self.step_with_record(T::NAME, Some(encoder.into_bytes()), move |allocator, s| {
+            tick.tick(allocator, s)
+        })
+    }
+
+    /// Capture the full history of this simulator (including any undone steps and all branches)
+    /// in a [`Session`].
+    ///
+    /// `description` should describe how to construct the simulatable, so the session can be
+    /// restored with [`from_session`](Self::from_session).
+    ///
+    /// Fails if a step used a custom tick that wasn't recorded, i.e. one that was not stepped with
+    /// [`step_recorded`](Self::step_recorded).
+    pub fn session(&self, description: Vec<u8>) -> Result<Session, SessionError> {
+        let ids: Vec<BranchId> = self.branches().map(|info| info.id).collect();
+        let mut branches = Vec::with_capacity(ids.len());
+        for info in self.branches() {
+            let (custom_ticks, current_steps) = match self.stored_timeline(info.id) {
+                Some(timeline) => (
+                    &timeline.custom_ticks,
+                    timeline.state_index.steps_since(StateIndex::new()).len(),
+                ),
+                None => (&self.custom_ticks, self.current_steps()),
+            };
+            branches.push(SessionBranch {
+                name: info.name,
+                // Deleted branches are skipped, so ids don't match indices in the session.
+                fork: info.fork.map(|(parent, steps)| {
+                    let parent = ids.binary_search(&parent).expect("parent should exist");
+                    (parent, steps)
+                }),
+                total_steps: info.steps,
+                current_steps,
+                ticks: session_ticks(custom_ticks)?,
+            });
+        }
+
+        Ok(Session {
+            description,
+            active_branch: ids.binary_search(&self.active_branch()).unwrap(),
+            branches,
+        })
+    }
+
+    /// Reconstruct a simulator from a [`Session`], with the exact same branches and HEAD
+    /// positions.
+    ///
+    /// `simulatable_constructor` is used like in [`Simulator::new`], and should construct the
+    /// simulatable according to [`Session::description`].
+    ///
+    /// The session is replayed step by step. For every recorded custom tick, `replay` is called
+    /// with the tick's name and record. It must decode the record (using a [`Decoder`]) and perform
+    /// exactly one [`step_recorded`](Self::step_recorded) with the corresponding [`RecordedTick`],
+    /// or return [`SessionError::UnknownTick`].
+    ///
+    /// Branches are recreated in order, so branch ids may differ from the original simulator if
+    /// any branches were deleted.
+    pub fn from_session<F, R>(
+        session: &Session,
+        simulatable_constructor: F,
+        mut replay: R,
+    ) -> Result<Self, SessionError>
+    where
+        F: FnOnce(&mut SimulationAllocator) -> S,
+        R: FnMut(&mut Self, &str, &[u8]) -> Result<(), SessionError>,
+    {
+        let mut simulator = Self::new(simulatable_constructor);
+
+        let mut ids = Vec::with_capacity(session.branches.len());
+        for (index, branch) in session.branches.iter().enumerate() {
+            // Only the steps after the fork need to be replayed, the rest is shared with the parent.
+            let fork_steps = match branch.fork {
+                Some((parent, steps)) => {
+                    simulator.switch_branch(ids[parent]).unwrap();
+                    simulator.go_to(steps);
+                    steps
+                }
+                None => {
+                    simulator.go_to(0);
+                    0
+                }
+            };
+            if index > 0 {
+                let id = simulator.create_branch();
+                simulator.branch_mut(id).fork =
+                    branch.fork.map(|(parent, steps)| (ids[parent], steps));
+            }
+            ids.push(simulator.active_branch());
+
+            let mut ticks = branch
+                .ticks
+                .iter()
+                .skip_while(|tick| tick.step < fork_steps)
+                .peekable();
+            for step in fork_steps..branch.total_steps {
+                match ticks.next_if(|tick| tick.step == step) {
+                    Some(tick) => {
+                        replay(&mut simulator, &tick.name, &tick.record)?;
+                        if simulator.current_steps() != step + 1 {
+                            return Err(SessionError::Malformed);
+                        }
+                    }
+                    None => simulator.step(),
+                }
+            }
+        }
+
+        for (branch, &id) in session.branches.iter().zip(&ids) {
+            simulator.switch_branch(id).unwrap();
+            simulator.rename_branch(id, branch.name.clone()).unwrap();
+            simulator.go_to(branch.current_steps);
+        }
+        simulator.switch_branch(ids[session.active_branch]).unwrap();
+        Ok(simulator)
+    }
+}
+
+/// Convert the custom ticks of a timeline to [`SessionTick`]s.
+fn session_ticks<S: Simulatable<SimulationAllocator>>(
+    custom_ticks: &[(StepIndex, Rc<Tick<S>>)],
+) -> Result<Vec<SessionTick>, SessionError> {
+    custom_ticks
+        .iter()
+        .map(|(step_index, tick)| match &tick.record {
+            Some(record) => Ok(SessionTick {
+                step: step_index
+                    .state_before()
+                    .steps_since(StateIndex::new())
+                    .len(),
+                name: tick.name.to_owned(),
+                record: record.clone(),
+            }),
+            None => Err(SessionError::UnrecordedTick(tick.name)),
+        })
+        .collect()
+}
+
+/// Little-endian encoder for the contents of a [`Session`].
+#[derive(Debug, Default, Clone)]
+pub struct Encoder {
+    buf: Vec<u8>,
+}
+
+impl Encoder {
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.buf
+    }
+
+    pub fn put_u8(&mut self, value: u8) {
+        self.buf.push(value);
+    }
+
+    pub fn put_u16(&mut self, value: u16) {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn put_u32(&mut self, value: u32) {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn put_u64(&mut self, value: u64) {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn put_bool(&mut self, value: bool) {
+        self.put_u8(value as u8);
+    }
+
+    /// Encodes a `usize` as a `u64`, so sessions are portable between hosts.
+    pub fn put_usize(&mut self, value: usize) {
+        self.put_u64(value as u64);
+    }
+
+    /// Encodes a length-prefixed byte slice.
+    pub fn put_bytes(&mut self, value: &[u8]) {
+        self.put_usize(value.len());
+        self.buf.extend_from_slice(value);
+    }
+
+    pub fn put_str(&mut self, value: &str) {
+        self.put_bytes(value.as_bytes());
+    }
+}
+
+/// Decoder for data written by an [`Encoder`].
+///
+/// All methods return [`SessionError::Malformed`] if the data runs out or is invalid.
+#[derive(Debug, Clone)]
+pub struct Decoder<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Decoder<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self { buf }
+    }
+
+    /// Returns `true` if all data has been decoded.
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], SessionError> {
+        let (bytes, rest) = self
+            .buf
+            .split_first_chunk::<N>()
+            .ok_or(SessionError::Malformed)?;
+        self.buf = rest;
+        Ok(*bytes)
+    }
+
+    pub fn get_u8(&mut self) -> Result<u8, SessionError> {
+        self.take().map(u8::from_le_bytes)
+    }
+
+    pub fn get_u16(&mut self) -> Result<u16, SessionError> {
+        self.take().map(u16::from_le_bytes)
+    }
+
+    pub fn get_u32(&mut self) -> Result<u32, SessionError> {
+        self.take().map(u32::from_le_bytes)
+    }
+
+    pub fn get_u64(&mut self) -> Result<u64, SessionError> {
+        self.take().map(u64::from_le_bytes)
+    }
+
+    pub fn get_usize(&mut self) -> Result<usize, SessionError> {
+        usize::try_from(self.get_u64()?).map_err(|_| SessionError::Malformed)
+    }
+
+    pub fn get_bool(&mut self) -> Result<bool, SessionError> {
+        match self.get_u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(SessionError::Malformed),
+        }
+    }
+
+    pub fn get_bytes(&mut self) -> Result<&'a [u8], SessionError> {
+        let len = self.get_usize()?;
+        if len > self.buf.len() {
+            return Err(SessionError::Malformed);
+        }
+        let (bytes, rest) = self.buf.split_at(len);
+        self.buf = rest;
+        Ok(bytes)
+    }
+
+    pub fn get_str(&mut self) -> Result<&'a str, SessionError> {
+        std::str::from_utf8(self.get_bytes()?).map_err(|_| SessionError::Malformed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Allocator;
+
+    #[derive(Debug)]
+    struct Counter(<SimulationAllocator as Allocator>::Id<u64>);
+
+    impl Simulatable<SimulationAllocator> for Counter {
+        fn tick(&self, allocator: &mut SimulationAllocator) {
+            *allocator.get_mut(self.0).unwrap() += 1;
+        }
+
+        fn drop(self, allocator: &mut SimulationAllocator) {
+            allocator.remove(self.0).unwrap();
+        }
+    }
+
+    struct Add(u64);
+
+    impl RecordedTick<Counter> for Add {
+        type Output = u64;
+
+        const NAME: &'static str = "add";
+
+        fn encode(&self, encoder: &mut Encoder) {
+            encoder.put_u64(self.0);
+        }
+
+        fn tick(&self, allocator: &mut SimulationAllocator, counter: &Counter) -> u64 {
+            let count = allocator.get_mut(counter.0).unwrap();
+            *count += self.0;
+            *count
+        }
+    }
+
+    fn count(simulator: &Simulator<Counter>) -> u64 {
+        let (allocator, counter) = simulator.inspect();
+        *allocator.get(counter.0).unwrap()
+    }
+
+    fn replay(
+        simulator: &mut Simulator<Counter>,
+        name: &str,
+        record: &[u8],
+    ) -> Result<(), SessionError> {
+        match name {
+            Add::NAME => {
+                let amount = Decoder::new(record).get_u64()?;
+                simulator.step_recorded(Add(amount));
+                Ok(())
+            }
+            _ => Err(SessionError::UnknownTick(name.to_owned())),
+        }
+    }
+
+    #[test]
+    fn save_and_restore() {
+        let mut simulator = Simulator::new(|allocator| Counter(allocator.insert(0)));
+        for _ in 0..10 {
+            simulator.step();
+        }
+        assert_eq!(simulator.step_recorded(Add(100)), 110);
+        for _ in 0..5 {
+            simulator.step();
+        }
+        simulator.go_to(12);
+
+        let mut file = Vec::new();
+        simulator
+            .session(b"counter".to_vec())
+            .unwrap()
+            .write(&mut file)
+            .unwrap();
+
+        let session = Session::read(&mut file.as_slice()).unwrap();
+        assert_eq!(session.description, b"counter");
+        let mut restored =
+            Simulator::from_session(&session, |allocator| Counter(allocator.insert(0)), replay)
+                .unwrap();
+
+        assert_eq!(restored.current_steps(), 12);
+        assert_eq!(restored.available_steps(), 16);
+        assert_eq!(count(&restored), 111);
+        restored.go_to(16);
+        assert_eq!(count(&restored), 115);
+        restored.go_to(10);
+        assert_eq!(count(&restored), 10);
+    }
+
+    #[test]
+    fn save_and_restore_branches() {
+        let mut simulator = Simulator::new(|allocator| Counter(allocator.insert(0)));
+        for _ in 0..10 {
+            simulator.step();
+        }
+        simulator.go_to(5);
+        simulator.step_recorded(Add(100));
+        simulator.step();
+        let first = simulator.active_branch();
+        simulator
+            .rename_branch(first, Some("first".to_owned()))
+            .unwrap();
+        simulator.go_to(6);
+        simulator.step_recorded(Add(1000));
+        let second = simulator.active_branch();
+        // Deleting a branch makes the ids of the restored branches differ from the original ones.
+        simulator.delete_branch(first).unwrap();
+        simulator.switch_branch(BranchId::ROOT).unwrap();
+        simulator.go_to(8);
+
+        let mut file = Vec::new();
+        simulator
+            .session(Vec::new())
+            .unwrap()
+            .write(&mut file)
+            .unwrap();
+        let session = Session::read(&mut file.as_slice()).unwrap();
+        let mut restored =
+            Simulator::from_session(&session, |allocator| Counter(allocator.insert(0)), replay)
+                .unwrap();
+
+        let original: Vec<_> = simulator.branches().collect();
+        let branches: Vec<_> = restored.branches().collect();
+        assert_eq!(branches.len(), 2);
+        assert_eq!(branches[1].fork, None);
+        assert_eq!(branches[1].steps, original[1].steps);
+        assert!(branches[0].active);
+        assert_eq!(restored.current_steps(), 8);
+        assert_eq!(count(&restored), 8);
+
+        restored.switch_branch(branches[1].id).unwrap();
+        assert_eq!(restored.current_steps(), 7);
+        assert_eq!(count(&restored), 1105);
+        restored.go_to(6);
+        assert_eq!(count(&restored), 105);
+        assert_eq!(simulator.active_branch(), BranchId::ROOT);
+        assert_ne!(branches[1].id, second);
+    }
+
+    #[test]
+    fn reject_invalid_sessions() {
+        let mut simulator = Simulator::new(|allocator| Counter(allocator.insert(0)));
+        simulator.step_with("unrecorded", |allocator, counter: &Counter| {
+            *allocator.get_mut(counter.0).unwrap() = 3;
+        });
+        assert!(matches!(
+            simulator.session(Vec::new()),
+            Err(SessionError::UnrecordedTick("unrecorded"))
+        ));
+
+        let mut file = Vec::new();
+        Simulator::new(|allocator| Counter(allocator.insert(0)))
+            .session(Vec::new())
+            .unwrap()
+            .write(&mut file)
+            .unwrap();
+
+        let mut bad_magic = file.clone();
+        bad_magic[0] ^= 0xFF;
+        assert!(matches!(
+            Session::read(&mut bad_magic.as_slice()),
+            Err(SessionError::InvalidMagic)
+        ));
+
+        let mut bad_format = file.clone();
+        bad_format[MAGIC.len()] ^= 0xFF;
+        assert!(matches!(
+            Session::read(&mut bad_format.as_slice()),
+            Err(SessionError::UnsupportedFormat(_))
+        ));
+
+        let mut bad_build = file.clone();
+        let version_start = MAGIC.len() + 4 + 8;
+        bad_build[version_start] ^= 1;
+        assert!(matches!(
+            Session::read(&mut bad_build.as_slice()),
+            Err(SessionError::IncompatibleBuild(_))
+        ));
+
+        let truncated = &file[..file.len() - 1];
+        assert!(matches!(
+            Session::read(&mut &truncated[..]),
+            Err(SessionError::Malformed)
+        ));
+    }
+}
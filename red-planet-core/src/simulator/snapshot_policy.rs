@@ -0,0 +1,226 @@
+//! Policies that decide when a [`Simulator`](super::Simulator) makes snapshots of its history, and
+//! which older snapshots it may discard again.
+//!
+//! Every snapshot retains the memory of all state that was modified since the previous snapshot,
+//! while every step between two snapshots has to be replayed when navigating to a state in
+//! between. A [`SnapshotPolicy`] balances the two.
+
+use std::fmt::Debug;
+use std::time::Duration;
+
+/// Measurements of the history since the last snapshot, passed to
+/// [`SnapshotPolicy::should_snapshot`].
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub struct SnapshotStats {
+    /// Number of steps since the last snapshot.
+    pub steps: usize,
+    /// Time it took to simulate the steps since the last snapshot. This is what it would cost to
+    /// replay them when navigating to the current state.
+    pub replay_time: Duration,
+    /// Number of bytes copied by writes since the last snapshot (see
+    /// [`SpaceTime::allocated_bytes`](space_time::SpaceTime::allocated_bytes)). This is the memory
+    /// a snapshot of the current state would retain.
+    pub dirty_bytes: usize,
+}
+
+/// Decides when a [`Simulator`](super::Simulator) makes snapshots of its history.
+pub trait SnapshotPolicy: Debug {
+    /// Returns `true` if a snapshot of the current state should be made.
+    ///
+    /// This is called after every step that extends the history.
+    fn should_snapshot(&mut self, stats: &SnapshotStats) -> bool;
+
+    /// Returns the indices of the snapshots that should be discarded, in increasing order.
+    ///
+    /// This is called after a snapshot was made at the end of history. `snapshots` holds the number
+    /// of steps from the start of history to each snapshot, oldest first, ending with the snapshot
+    /// that was just made. The first and last snapshot are never discarded, even if their indices
+    /// are returned.
+    ///
+    /// By default, snapshots are never discarded.
+    fn thin(&mut self, snapshots: &[usize]) -> Vec<usize> {
+        let _ = snapshots;
+        Vec::new()
+    }
+}
+
+/// Makes a snapshot every `interval` steps, regardless of their cost.
+///
+/// Since this doesn't depend on time measurements, the resulting snapshots are deterministic.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct FixedIntervalPolicy {
+    pub interval: usize,
+}
+
+impl FixedIntervalPolicy {
+    pub fn new(interval: usize) -> Self {
+        Self { interval }
+    }
+}
+
+impl SnapshotPolicy for FixedIntervalPolicy {
+    fn should_snapshot(&mut self, stats: &SnapshotStats) -> bool {
+        stats.steps >= self.interval
+    }
+}
+
+/// Makes a snapshot once replaying the steps since the last snapshot becomes too expensive compared
+/// to the memory the snapshot would retain.
+///
+/// A snapshot is made when [`SnapshotStats::replay_time`] reaches [`Self::replay_time`], scaled up
+/// by the number of times [`SnapshotStats::dirty_bytes`] exceeds [`Self::dirty_bytes`]. So tight
+/// loops that modify little state are snapshotted often, while memory-heavy code is snapshotted
+/// less frequently. This is bounded by [`Self::min_steps`] and [`Self::max_steps`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct AdaptivePolicy {
+    /// Replay time after which a snapshot is made if at most [`Self::dirty_bytes`] were copied.
+    pub replay_time: Duration,
+    /// Number of dirty bytes considered to be worth [`Self::replay_time`] of replaying.
+    pub dirty_bytes: usize,
+    /// Never make a snapshot before this many steps.
+    pub min_steps: usize,
+    /// Always make a snapshot after this many steps.
+    pub max_steps: usize,
+}
+
+impl Default for AdaptivePolicy {
+    fn default() -> Self {
+        Self {
+            replay_time: Duration::from_millis(1),
+            dirty_bytes: 1 << 20,
+            min_steps: 16,
+            max_steps: 1 << 16,
+        }
+    }
+}
+
+impl SnapshotPolicy for AdaptivePolicy {
+    fn should_snapshot(&mut self, stats: &SnapshotStats) -> bool {
+        if stats.steps < self.min_steps {
+            return false;
+        }
+        if stats.steps >= self.max_steps {
+            return true;
+        }
+        // replay_time / self.replay_time >= max(1, dirty_bytes / self.dirty_bytes)
+        let dirty_bytes = stats.dirty_bytes.max(self.dirty_bytes) as u128;
+        stats.replay_time.as_nanos() * self.dirty_bytes as u128
+            >= self.replay_time.as_nanos() * dirty_bytes
+    }
+}
+
+/// Keeps memory bounded over very long histories by thinning older snapshots logarithmically.
+///
+/// When to make snapshots is decided by the wrapped policy. Afterwards, a snapshot is only kept if
+/// the distance to the next kept snapshot is at least `1 / density` times its age (in steps, as
+/// seen from the end of history). This keeps about `density` snapshots per doubling of the
+/// history's length, so navigating recent history stays cheap, while navigating far into the past
+/// may require replaying proportionally more steps.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct LongHistoryPolicy<P = AdaptivePolicy> {
+    pub inner: P,
+    pub density: usize,
+}
+
+impl<P: SnapshotPolicy> LongHistoryPolicy<P> {
+    pub fn new(inner: P, density: usize) -> Self {
+        Self { inner, density }
+    }
+}
+
+impl Default for LongHistoryPolicy {
+    fn default() -> Self {
+        Self::new(AdaptivePolicy::default(), 16)
+    }
+}
+
+impl<P: SnapshotPolicy> SnapshotPolicy for LongHistoryPolicy<P> {
+    fn should_snapshot(&mut self, stats: &SnapshotStats) -> bool {
+        self.inner.should_snapshot(stats)
+    }
+
+    fn thin(&mut self, snapshots: &[usize]) -> Vec<usize> {
+        let inner_discard = self.inner.thin(snapshots);
+        let mut discard = Vec::new();
+        let Some((&end, rest)) = snapshots.split_last() else {
+            return inner_discard;
+        };
+
+        let mut next_kept = end;
+        for (index, &steps) in rest.iter().enumerate().skip(1).rev() {
+            if inner_discard.binary_search(&index).is_ok() {
+                discard.push(index);
+                continue;
+            }
+            let age = end - steps;
+            let distance = next_kept - steps;
+            if distance.saturating_mul(self.density) >= age {
+                next_kept = steps;
+            } else {
+                discard.push(index);
+            }
+        }
+
+        discard.reverse();
+        discard
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adaptive_weighs_replay_time_against_dirty_bytes() {
+        let mut policy = AdaptivePolicy {
+            replay_time: Duration::from_millis(1),
+            dirty_bytes: 1000,
+            min_steps: 10,
+            max_steps: 100,
+        };
+        let stats = |steps, micros, dirty_bytes| SnapshotStats {
+            steps,
+            replay_time: Duration::from_micros(micros),
+            dirty_bytes,
+        };
+
+        assert!(!policy.should_snapshot(&stats(50, 999, 0)));
+        assert!(policy.should_snapshot(&stats(50, 1000, 0)));
+        assert!(policy.should_snapshot(&stats(50, 1000, 1000)));
+        // Twice the memory is only worth it for twice the replay time.
+        assert!(!policy.should_snapshot(&stats(50, 1999, 2000)));
+        assert!(policy.should_snapshot(&stats(50, 2000, 2000)));
+        // Step bounds take precedence.
+        assert!(!policy.should_snapshot(&stats(9, 5000, 0)));
+        assert!(policy.should_snapshot(&stats(100, 0, 1 << 30)));
+    }
+
+    #[test]
+    fn long_history_thins_logarithmically() {
+        let mut policy = LongHistoryPolicy::new(FixedIntervalPolicy::new(1), 4);
+        let mut snapshots = vec![0];
+        for steps in 1..=100_000 {
+            snapshots.push(steps);
+            let discard = policy.thin(&snapshots);
+            assert!(!discard.contains(&0));
+            assert!(!discard.contains(&(snapshots.len() - 1)));
+            let mut index = 0;
+            snapshots.retain(|_| {
+                let keep = discard.binary_search(&index).is_err();
+                index += 1;
+                keep
+            });
+        }
+
+        // About `density` snapshots per doubling of the history.
+        assert!(
+            snapshots.len() < 4 * 17 + 8,
+            "{} snapshots",
+            snapshots.len()
+        );
+        assert_eq!(snapshots.first(), Some(&0));
+        assert_eq!(snapshots.last(), Some(&100_000));
+        // Recent history is dense.
+        assert!(snapshots.contains(&99_999));
+    }
+}
@@ -1,5 +1,5 @@
 use crate::bus::Bus;
-use crate::Allocator;
+use crate::{Allocator, PrivilegeLevel};
 use core::fmt;
 use std::fmt::Debug;
 
@@ -23,6 +23,30 @@ impl fmt::Display for AccessType {
     }
 }
 
+/// Bus through which cores access memory and devices.
+///
+/// Every write to it must invalidate the LR/SC reservations it overlaps on all harts, see
+/// [`Core::invalidate_reservation_on_write`](crate::core::Core::invalidate_reservation_on_write).
+/// Cores do not do this themselves, so that writes of other bus masters are handled the same.
 pub trait SystemBus<A: Allocator>: Bus<A> {
     fn accepts(&self, address: u32, size: usize, access_type: AccessType) -> bool;
+
+    /// Handles a semihosting call of a core, with `operation` and `parameter` taken from its a0 and
+    /// a1 registers. The guest memory is accessed through `memory`, which translates addresses like
+    /// the core does, and `privilege` is the privilege mode the core is in. The returned value is
+    /// written to a0.
+    ///
+    /// Returns `None` if semihosting is not supported, or not at `privilege`, in which case the
+    /// core raises a breakpoint exception instead. This is the default.
+    fn semihosting_call(
+        &self,
+        allocator: &mut A,
+        memory: &dyn Bus<A>,
+        privilege: PrivilegeLevel,
+        operation: u32,
+        parameter: u32,
+    ) -> Option<u32> {
+        let _ = (allocator, memory, privilege, operation, parameter);
+        None
+    }
 }
@@ -1,10 +1,11 @@
 use clap::Parser;
 use goblin::elf::program_header::PT_LOAD;
-use red_planet_core::board::{Board, Config};
+use red_planet_core::board::{Board, Config, HtifConfig};
 use red_planet_core::simulator::SimulationAllocator;
 use std::fs::File;
 use std::io::Read;
 use std::io::Write;
+use std::process::ExitCode;
 
 type Simulator = red_planet_core::simulator::Simulator<Board<SimulationAllocator>>;
 
@@ -14,11 +15,15 @@ struct Args {
     #[arg(long, short)]
     // Signature file to output signature to
     signature: Option<String>,
+    #[arg(long)]
+    // Board description file, instead of the built-in board
+    board: Option<String>,
     // Elf file to run
     elf: String,
 }
 
-fn main() -> std::io::Result<()> {
+/// Runs the ELF until the guest powers down, and exits with the exit code of the guest.
+fn main() -> std::io::Result<ExitCode> {
     let args = Args::parse();
 
     let mut buf = Vec::new();
@@ -26,27 +31,75 @@ fn main() -> std::io::Result<()> {
     let mut file = File::open(args.elf)?;
     file.read_to_end(&mut buf)?;
 
+    let mut config = match args.board {
+        Some(path) => {
+            Config::from_toml(&std::fs::read_to_string(path)?).map_err(std::io::Error::other)?
+        }
+        None => Config::default(),
+    };
+
     let elf_header = goblin::elf::Elf::parse(&buf).expect("failed to parse elf file");
 
+    // Programs built for Spike signal completion and print through HTIF.
+    let address_of = |name| {
+        elf_header
+            .syms
+            .iter()
+            .find(|sym| elf_header.strtab.get_at(sym.st_name) == Some(name))
+            .map(|sym| sym.st_value as u32)
+    };
+    config.htif = address_of("tohost").map(|tohost| HtifConfig {
+        tohost,
+        fromhost: address_of("fromhost"),
+    });
+    config.validate().map_err(std::io::Error::other)?;
+
     let segments = elf_header
         .program_headers
         .iter()
         .filter(|h| h.p_type == PT_LOAD);
 
     let mut simulator = Simulator::new(|allocator| {
-        let board = Board::new(allocator, Config::default());
-        for h in segments {
-            board.load_physical(allocator, h.p_paddr as u32, &buf[h.file_range()]);
-        }
+        let board = Board::new(allocator, config);
+        let images = segments
+            .map(|h| (h.p_paddr as u32, buf[h.file_range()].to_vec()))
+            .collect();
+        board.set_boot_images(allocator, images);
         board
     });
 
     // Run
-    while {
+    let status = loop {
         let (allocator, board) = simulator.inspect();
-        !board.is_powered_down(allocator)
-    } {
+        let status = board.exit_status(allocator);
+        // Programs print through HTIF or semihosting.
+        let has_output = board
+            .htif()
+            .is_some_and(|htif| !htif.pending_output(allocator).is_empty())
+            || board
+                .semihosting()
+                .is_some_and(|semihosting| !semihosting.pending_output(allocator).is_empty());
+        if has_output {
+            let output = simulator.step_with("take console output", |allocator, board| {
+                let mut output = (board.htif())
+                    .map(|htif| htif.take_output(allocator))
+                    .unwrap_or_default();
+                if let Some(semihosting) = board.semihosting() {
+                    output.extend(semihosting.take_output(allocator));
+                }
+                output
+            });
+            std::io::stdout().write_all(&output)?;
+        }
+        if let Some(status) = status {
+            break status;
+        }
         simulator.step()
+    };
+
+    let (allocator, board) = simulator.inspect();
+    if let Some(semihosting) = board.semihosting() {
+        semihosting.write_back(allocator)?;
     }
 
     if let Some(path) = args.signature {
@@ -72,7 +125,7 @@ fn main() -> std::io::Result<()> {
         let mut signature = Vec::new();
 
         let (allocator, board) = simulator.inspect();
-        let mmu = board.core().mmu();
+        let mmu = board.core(0).mmu();
         for address in (signature_start..signature_end).step_by(4) {
             let word = mmu
                 .read_word_debug(allocator, address)
@@ -86,5 +139,5 @@ fn main() -> std::io::Result<()> {
         }
     }
 
-    Ok(())
+    Ok(ExitCode::from(status.exit_code()))
 }
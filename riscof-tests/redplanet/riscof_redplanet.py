@@ -101,6 +101,9 @@ class redplanet(pluginTemplate):
           self.isa += 'd'
       if "C" in ispec["ISA"]:
           self.isa += 'c'
+      for ext in ["Zba", "Zbb", "Zbc", "Zbs"]:
+          if ext in ispec["ISA"]:
+              self.isa += '_' + ext.lower()
 
       #TODO: The following assumes you are using the riscv-gcc toolchain. If
       #      not please change appropriately
@@ -1,3 +1,5 @@
+use std::ops::Range;
+
 use downcast_rs::{impl_downcast, Downcast};
 
 use crate::table::{Table, TablePtr, TableTrait};
@@ -8,6 +10,9 @@ pub(crate) trait ArrayStorageTrait: Downcast {
     fn clone_instance(&mut self, instance: &Instance) -> Instance;
 
     fn drop_instance(&mut self, instance: Instance);
+
+    /// Returns the number of bytes taken up by all pages of this storage.
+    fn allocated_bytes(&self) -> usize;
 }
 impl_downcast!(ArrayStorageTrait);
 
@@ -395,7 +400,70 @@ impl<T: Copy> ArrayStorage<T> {
     }
 }
 
-impl std::fmt::Debug for (dyn ArrayStorageTrait + 'static) {
+impl<T: Copy + PartialEq> ArrayStorage<T> {
+    /// Appends the ranges of indices at which the pages `old` and `new` differ to `ranges`, where
+    /// `offset` is the index of the first item of the pages.
+    ///
+    /// Pages that are shared by both are skipped without comparing their contents. If
+    /// `compare_contents` is `false`, other pages are not compared either, and are appended as a
+    /// whole.
+    #[allow(clippy::too_many_arguments)]
+    fn diff_impl(
+        &self,
+        old: &TablePtr,
+        new: &TablePtr,
+        offset: u64,
+        table_depth: usize,
+        divisor: u64,
+        compare_contents: bool,
+        ranges: &mut Vec<Range<u64>>,
+    ) {
+        if old == new {
+            return;
+        }
+
+        if let Some(table) = self.page_tables.get(table_depth) {
+            let old_tables = table.get_item(old);
+            let new_tables = table.get_item(new);
+
+            for (i, (old, new)) in old_tables.iter().zip(new_tables).enumerate() {
+                self.diff_impl(
+                    old,
+                    new,
+                    offset + i as u64 * divisor,
+                    table_depth + 1,
+                    divisor / PAGE_SIZE as u64,
+                    compare_contents,
+                    ranges,
+                );
+            }
+        } else if !compare_contents {
+            match ranges.last_mut() {
+                Some(last) if last.end == offset => last.end += PAGE_SIZE as u64,
+                _ => ranges.push(offset..offset + PAGE_SIZE as u64),
+            }
+        } else {
+            debug_assert!(divisor == 1);
+            let old_page = self.data_table.get_item(old);
+            let new_page = self.data_table.get_item(new);
+
+            for (i, _) in old_page
+                .iter()
+                .zip(new_page)
+                .enumerate()
+                .filter(|(_, (old, new))| old != new)
+            {
+                let index = offset + i as u64;
+                match ranges.last_mut() {
+                    Some(last) if last.end == index => last.end += 1,
+                    _ => ranges.push(index..index + 1),
+                }
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for dyn ArrayStorageTrait + 'static {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("dyn ArrayStorageTrait").finish()
     }
@@ -420,6 +488,15 @@ impl<T: Copy> ArrayStorageTrait for ArrayStorage<T> {
             self.drop_table_ptr(table_ptr);
         }
     }
+
+    fn allocated_bytes(&self) -> usize {
+        self.data_table.allocated_bytes()
+            + self
+                .page_tables
+                .iter()
+                .map(|table| table.allocated_bytes())
+                .sum::<usize>()
+    }
 }
 
 #[derive(Debug)]
@@ -569,6 +646,40 @@ impl Instance {
         Some(iter)
     }
 
+    /// Returns the ranges of indices at which `self` and `other` differ, in increasing order.
+    ///
+    /// Both instances must be versions of the same array. If `compare_contents` is `false`, every
+    /// page that is not shared by both instances is returned as a whole, even if its contents are
+    /// equal.
+    pub(crate) fn diff<T: Copy + PartialEq + 'static>(
+        &self,
+        other: &Instance,
+        array_storage: &ArrayStorage<T>,
+        compare_contents: bool,
+    ) -> Vec<Range<u64>> {
+        debug_assert_eq!(self.size, other.size);
+        let items_per_top_level_page = array_storage.items_per_top_level_page();
+        let divisor = items_per_top_level_page / PAGE_SIZE as u64;
+
+        let mut ranges = Vec::new();
+        for (i, (old, new)) in self.pages.iter().zip(other.pages.iter()).enumerate() {
+            array_storage.diff_impl(
+                old,
+                new,
+                i as u64 * items_per_top_level_page,
+                0,
+                divisor,
+                compare_contents,
+                &mut ranges,
+            );
+        }
+        // The last page may extend beyond the end of the array.
+        if let Some(last) = ranges.last_mut() {
+            last.end = last.end.min(self.size);
+        }
+        ranges
+    }
+
     pub(crate) fn reset<T: Copy + 'static>(&mut self, array_storage: &mut ArrayStorage<T>) {
         for page in self.pages.iter_mut() {
             if page != &self.reset_page {
@@ -14,3 +14,24 @@ pub struct InvalidIdError;
 /// [`crate::SpaceTime`].
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
 pub struct InvalidSnapshotIdError;
+
+/// This error indicates an invalid id or [`crate::SnapshotId`] was passed to
+/// [`crate::SpaceTime::diff_array`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum DiffError {
+    /// The array does not exist in one of the snapshots.
+    InvalidId(InvalidIdError),
+    InvalidSnapshotId(InvalidSnapshotIdError),
+}
+
+impl From<InvalidIdError> for DiffError {
+    fn from(error: InvalidIdError) -> Self {
+        Self::InvalidId(error)
+    }
+}
+
+impl From<InvalidSnapshotIdError> for DiffError {
+    fn from(error: InvalidSnapshotIdError) -> Self {
+        Self::InvalidSnapshotId(error)
+    }
+}
@@ -11,11 +11,11 @@ mod snapshot;
 mod table;
 mod typemap;
 
-use std::ops::RangeBounds;
+use std::ops::{Range, RangeBounds};
 
 use allocator::{Allocator, ArrayAccessor, ArrayAccessorMut};
 use array_storage::{ArrayStorage, Instance};
-use errors::{InvalidIdError, InvalidSnapshotIdError};
+use errors::{DiffError, InvalidIdError, InvalidSnapshotIdError};
 use generational_arena::{Arena, Index};
 use ids::SpaceTimeId;
 use snapshot::{Snapshot, TypedInstance, TypedTablePtr};
@@ -29,6 +29,16 @@ pub struct SpaceTimeStorage {
 }
 
 impl SpaceTimeStorage {
+    fn allocated_bytes(&self) -> usize {
+        let tables: usize = self.tables.values().map(|t| t.allocated_bytes()).sum();
+        let arrays: usize = self
+            .array_storage
+            .values()
+            .map(|a| a.allocated_bytes())
+            .sum();
+        tables + arrays
+    }
+
     fn clone_snapshot(&mut self, snapshot: &Snapshot) -> Snapshot {
         let table_ptrs = snapshot
             .iter_table_ptrs()
@@ -137,8 +147,16 @@ impl SpaceTimeSnapshots {
     }
 
     pub fn make_snapshot(&mut self, storage: &mut SpaceTimeStorage) -> SnapshotId {
-        let head = self.get_head();
-        let snapshot = storage.clone_snapshot(head);
+        // A dirty HEAD is not referenced by anything else, so it can become the snapshot as is.
+        let snapshot = match &self.head {
+            Head::Dirty(_) => {
+                let Head::Dirty(head) = std::mem::take(&mut self.head) else {
+                    unreachable!()
+                };
+                head
+            }
+            Head::Checkout(_) => storage.clone_snapshot(self.get_head()),
+        };
 
         let index = self.snapshots.insert(snapshot);
 
@@ -179,6 +197,12 @@ impl SpaceTimeSnapshots {
         self.snapshots.contains(snapshot_id.index)
     }
 
+    fn get(&self, snapshot_id: SnapshotId) -> Result<&Snapshot, InvalidSnapshotIdError> {
+        self.snapshots
+            .get(snapshot_id.index)
+            .ok_or(InvalidSnapshotIdError)
+    }
+
     pub fn checkout(
         &mut self,
         snapshot_id: SnapshotId,
@@ -225,7 +249,7 @@ impl Default for Head {
 ///
 /// [`SnapshotId`]'s can be checked for equality, but this only makes sense if they're created by
 /// the same [`SpaceTime`].
-#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
 pub struct SnapshotId {
     index: Index,
 }
@@ -287,6 +311,79 @@ impl SpaceTime {
     pub fn checkout(&mut self, snapshot_id: SnapshotId) -> Result<(), InvalidSnapshotIdError> {
         self.snapshots.checkout(snapshot_id, &mut self.storage)
     }
+
+    /// Returns the number of bytes taken up by all objects and array pages stored in this
+    /// [`SpaceTime`], across HEAD and all snapshots.
+    ///
+    /// Objects and pages shared between HEAD and snapshots are only counted once. Since every write
+    /// to an object or page shared with a snapshot makes a copy of it, the growth of this value
+    /// since the last [`Self::make_snapshot`] is a measure for the memory a new snapshot would
+    /// retain.
+    pub fn allocated_bytes(&self) -> usize {
+        self.storage.allocated_bytes()
+    }
+
+    /// Returns the ranges of indices at which the contents of the array `id` differ between the
+    /// snapshots `old` and `new`, in increasing order.
+    ///
+    /// Pages that are shared between both snapshots, i.e. that were not written in between, are
+    /// skipped without comparing their contents. This makes comparing large arrays cheap if only
+    /// a few pages were written.
+    pub fn diff_array<T: Copy + PartialEq + 'static>(
+        &self,
+        id: <Self as Allocator>::ArrayId<T>,
+        old: SnapshotId,
+        new: SnapshotId,
+    ) -> Result<Vec<Range<usize>>, DiffError> {
+        self.diff_array_impl(id, old, new, true)
+    }
+
+    /// Returns the ranges of indices of the array `id` that may have been written in between the
+    /// snapshots `old` and `new`, in increasing order.
+    ///
+    /// Unlike [`SpaceTime::diff_array`], this never compares the contents of pages. Every page
+    /// that is not shared between both snapshots is returned as a whole, so this includes writes
+    /// that did not change any value, or that were later undone. Only resetting the array may go
+    /// unnoticed.
+    pub fn written_array<T: Copy + PartialEq + 'static>(
+        &self,
+        id: <Self as Allocator>::ArrayId<T>,
+        old: SnapshotId,
+        new: SnapshotId,
+    ) -> Result<Vec<Range<usize>>, DiffError> {
+        self.diff_array_impl(id, old, new, false)
+    }
+
+    fn diff_array_impl<T: Copy + PartialEq + 'static>(
+        &self,
+        id: <Self as Allocator>::ArrayId<T>,
+        old: SnapshotId,
+        new: SnapshotId,
+        compare_contents: bool,
+    ) -> Result<Vec<Range<usize>>, DiffError> {
+        let instance = |snapshot_id| -> Result<&Instance, DiffError> {
+            Ok(&self
+                .snapshots
+                .get(snapshot_id)?
+                .get_instance(id.index)
+                .ok_or(InvalidIdError)?
+                .instance)
+        };
+        let old = instance(old)?;
+        let new = instance(new)?;
+
+        let array_storage = self
+            .storage
+            .array_storage
+            .get::<T>()
+            .expect("T should be in the storage_array if there is a ptr in a snapshot");
+
+        Ok(old
+            .diff(new, array_storage, compare_contents)
+            .into_iter()
+            .map(|range| range.start as usize..range.end as usize)
+            .collect())
+    }
 }
 
 impl Allocator for SpaceTime {
@@ -1048,4 +1145,65 @@ mod tests {
 
         assert!(arr.iter_range(1..10).unwrap().cloned().eq(1..10));
     }
+
+    #[test]
+    fn allocated_bytes() {
+        let mut sp = SpaceTime::new();
+        assert_eq!(sp.allocated_bytes(), 0);
+
+        let id = sp.insert(1u64);
+        assert_eq!(sp.allocated_bytes(), 8);
+
+        // Writing without a snapshot modifies the object in place.
+        *sp.get_mut(id).unwrap() = 2;
+        assert_eq!(sp.allocated_bytes(), 8);
+
+        // Writing after a snapshot copies the object.
+        let cp = sp.make_snapshot();
+        *sp.get_mut(id).unwrap() = 3;
+        assert_eq!(sp.allocated_bytes(), 16);
+
+        sp.drop_snapshot(cp).unwrap();
+        assert_eq!(sp.allocated_bytes(), 8);
+
+        // One data page, and one page per layer of page tables.
+        let page_tables = 3 * 64 * std::mem::size_of::<u32>();
+        let aid = sp.insert_array(0u8, 128);
+        assert_eq!(sp.allocated_bytes(), 8 + 64 + page_tables);
+
+        sp.make_snapshot();
+        let _ = sp.get_array_mut(aid).unwrap().write(0, &[1]);
+        assert_eq!(sp.allocated_bytes(), 8 + 2 * (64 + page_tables));
+    }
+
+    #[test]
+    fn diff_array() {
+        let mut sp = SpaceTime::new();
+        let aid = sp.insert_array(0u8, 1 << 20);
+        let old = sp.make_snapshot();
+
+        let mut array = sp.get_array_mut(aid).unwrap();
+        assert!(array.write(100, &[1, 2, 3]));
+        assert!(array.write(500_000, &[4; 200]));
+        // Writing the same value again does not count as a difference.
+        assert!(array.write(1000, &[0]));
+        drop(array);
+        let new = sp.make_snapshot();
+
+        assert_eq!(
+            sp.diff_array(aid, old, new),
+            Ok(vec![100..103, 500_000..500_200])
+        );
+        assert_eq!(sp.diff_array(aid, new, new), Ok(vec![]));
+        assert_eq!(
+            sp.written_array(aid, old, new),
+            Ok(vec![64..128, 960..1024, 499_968..500_224])
+        );
+
+        sp.drop_snapshot(old).unwrap();
+        assert_eq!(
+            sp.diff_array(aid, old, new),
+            Err(DiffError::InvalidSnapshotId(InvalidSnapshotIdError))
+        );
+    }
 }
@@ -82,6 +82,8 @@ pub(crate) struct Table<T: 'static> {
     metadata: Vec<ItemMetaData>,
     table: Vec<MaybeUninit<T>>,
     next_empty: u32,
+    /// Number of filled items.
+    len: usize,
 }
 
 impl<T> Default for Table<T> {
@@ -96,6 +98,7 @@ impl<T: 'static> Table<T> {
             metadata: Vec::new(),
             table: Vec::new(),
             next_empty: 0,
+            len: 0,
         }
     }
 
@@ -103,6 +106,7 @@ impl<T: 'static> Table<T> {
     pub(crate) fn add_item(&mut self, item: T) -> TablePtr {
         // TODO: Find earlier empty page
         let index: u32 = self.next_empty;
+        self.len += 1;
         if index as usize == self.table.len() {
             self.table.push(MaybeUninit::new(item));
             self.metadata.push(ItemMetaData::filled());
@@ -151,6 +155,7 @@ impl<T: 'static> Table<T> {
         if refs_left == 0 {
             self.metadata[index] = ItemMetaData::empty(self.next_empty);
             self.next_empty = table_ptr.0;
+            self.len -= 1;
 
             // Safety: We checked that the metadata said this cell is used before so it is
             // guaranteed to be initialized. It is also safe to make a bitwise copy as we just set
@@ -223,6 +228,9 @@ pub(crate) trait TableTrait: Downcast {
     fn is_unique_table_ptr(&mut self, table_ptr: &TablePtr) -> bool;
 
     fn drop_table_ptr(&mut self, table_ptr: TablePtr);
+
+    /// Returns the number of bytes taken up by the filled items of this table.
+    fn allocated_bytes(&self) -> usize;
 }
 impl_downcast!(TableTrait);
 
@@ -244,9 +252,13 @@ impl<T: 'static> TableTrait for Table<T> {
     fn drop_table_ptr(&mut self, table_ptr: TablePtr) {
         self.pop_item(table_ptr);
     }
+
+    fn allocated_bytes(&self) -> usize {
+        self.len * std::mem::size_of::<T>()
+    }
 }
 
-impl std::fmt::Debug for (dyn TableTrait + 'static) {
+impl std::fmt::Debug for dyn TableTrait + 'static {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("dyn TableTrait").finish()
     }
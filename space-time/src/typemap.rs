@@ -63,6 +63,10 @@ impl TableTypeMap {
             .expect("HashMap should never contain type not coresponding to its key");
         (type_id, table)
     }
+
+    pub(crate) fn values(&self) -> impl Iterator<Item = &dyn TableTrait> {
+        self.0.values().map(|a| &**a)
+    }
 }
 
 #[derive(Debug, Default)]
@@ -115,4 +119,7 @@ impl ArrayStorageTypeMap {
             .expect("HashMap should never contain type not coresponding to its key");
         (type_id, table)
     }
+    pub(crate) fn values(&self) -> impl Iterator<Item = &dyn ArrayStorageTrait> {
+        self.0.values().map(|a| &**a)
+    }
 }